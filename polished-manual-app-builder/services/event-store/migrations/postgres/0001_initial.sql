@@ -0,0 +1,77 @@
+-- Baseline: the schema as of the switch to versioned migrations. Every
+-- statement is idempotent so databases set up before then adopt it as-is.
+
+-- Month partitions of the events table, for pre-creation and archival
+CREATE TABLE IF NOT EXISTS event_partitions (
+    table_name VARCHAR PRIMARY KEY,
+    partition_key VARCHAR NOT NULL,
+    range_start TIMESTAMPTZ NOT NULL,
+    range_end TIMESTAMPTZ NOT NULL
+);
+
+-- Events, partitioned by project; each project is sub-partitioned by month
+CREATE TABLE IF NOT EXISTS events (
+    id UUID NOT NULL,
+    stream_id VARCHAR NOT NULL,
+    event_type VARCHAR NOT NULL,
+    data JSONB,
+    payload BYTEA,
+    content_type VARCHAR NOT NULL DEFAULT 'application/json',
+    metadata JSONB,
+    version BIGINT NOT NULL,
+    position BIGSERIAL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    partition_key VARCHAR NOT NULL,
+    tenant_id VARCHAR,
+    PRIMARY KEY (partition_key, id, created_at),
+    UNIQUE (partition_key, stream_id, version, created_at)
+) PARTITION BY LIST (partition_key);
+
+-- Lookups by stream are served by the (partition_key, stream_id, version)
+-- unique index; partition pruning replaces a partition_key index.
+CREATE INDEX IF NOT EXISTS idx_events_created_at ON events(created_at);
+CREATE INDEX IF NOT EXISTS idx_events_position ON events(position);
+CREATE INDEX IF NOT EXISTS idx_events_event_type ON events(event_type);
+
+CREATE TABLE IF NOT EXISTS snapshots (
+    id UUID PRIMARY KEY,
+    stream_id VARCHAR NOT NULL,
+    version BIGINT NOT NULL,
+    data BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(stream_id, version)
+);
+
+CREATE INDEX IF NOT EXISTS idx_snapshots_stream_version ON snapshots(stream_id, version DESC);
+
+CREATE TABLE IF NOT EXISTS stream_metadata (
+    stream_id VARCHAR PRIMARY KEY,
+    metadata JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS event_type_deprecations (
+    event_type VARCHAR PRIMARY KEY,
+    deprecation JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID PRIMARY KEY,
+    key_hash VARCHAR NOT NULL UNIQUE,
+    api_key JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS encryption_policies (
+    event_type VARCHAR PRIMARY KEY,
+    policy JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Per-subject data keys; deleting a row crypto-shreds the subject's fields
+CREATE TABLE IF NOT EXISTS subject_keys (
+    subject_id VARCHAR PRIMARY KEY,
+    key BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,18 @@
+-- Archived events move out of the hot table. Each stream's archived events
+-- are a prefix of its versions; the head always stays in events.
+CREATE TABLE events_archive (
+    id UUID NOT NULL,
+    stream_id VARCHAR NOT NULL,
+    event_type VARCHAR NOT NULL,
+    data JSONB,
+    payload BYTEA,
+    content_type VARCHAR NOT NULL DEFAULT 'application/json',
+    metadata JSONB,
+    version BIGINT NOT NULL,
+    position BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    partition_key VARCHAR NOT NULL,
+    tenant_id VARCHAR,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (partition_key, stream_id, version)
+);
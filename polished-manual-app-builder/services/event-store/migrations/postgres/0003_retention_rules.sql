@@ -0,0 +1,5 @@
+CREATE TABLE retention_rules (
+    name VARCHAR PRIMARY KEY,
+    rule JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
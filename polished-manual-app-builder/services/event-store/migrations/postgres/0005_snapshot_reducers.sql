@@ -0,0 +1,9 @@
+CREATE TABLE snapshot_reducers (
+    name VARCHAR PRIMARY KEY,
+    reducer JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Scheduled snapshots record the reducer that produced them, so the next
+-- one can continue from them instead of folding the whole stream again.
+ALTER TABLE snapshots ADD COLUMN reducer VARCHAR;
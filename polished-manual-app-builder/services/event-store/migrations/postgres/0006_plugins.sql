@@ -0,0 +1,12 @@
+CREATE TABLE plugins (
+    name VARCHAR PRIMARY KEY,
+    plugin JSONB NOT NULL,
+    module BYTEA NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE projections (
+    name VARCHAR PRIMARY KEY,
+    projection JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,8 @@
+CREATE TABLE dead_letters (
+    id UUID PRIMARY KEY,
+    source VARCHAR NOT NULL,
+    dead_letter JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_dead_letters_source ON dead_letters (source, created_at);
@@ -0,0 +1,4 @@
+-- JSON bodies over the compression threshold are stored compressed in
+-- payload, with data left NULL; this records how. NULL means uncompressed.
+ALTER TABLE events ADD COLUMN data_compression VARCHAR;
+ALTER TABLE events_archive ADD COLUMN data_compression VARCHAR;
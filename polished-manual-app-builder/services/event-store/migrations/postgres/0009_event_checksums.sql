@@ -0,0 +1,4 @@
+-- Hex SHA-256 of each event body as stored, checked by POST /admin/verify.
+-- NULL for events appended before checksums were kept.
+ALTER TABLE events ADD COLUMN checksum VARCHAR;
+ALTER TABLE events_archive ADD COLUMN checksum VARCHAR;
@@ -0,0 +1,19 @@
+-- Appends per project and UTC day, rolled up from the global position by
+-- the stats job, so stats read these rows instead of counting events.
+CREATE TABLE event_stats_daily (
+    partition_key VARCHAR NOT NULL,
+    day DATE NOT NULL,
+    events BIGINT NOT NULL DEFAULT 0,
+    streams_created BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (partition_key, day)
+);
+
+CREATE INDEX idx_event_stats_daily_day ON event_stats_daily (day);
+
+-- Position up to which events are counted in event_stats_daily.
+CREATE TABLE event_stats_cursor (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    position BIGINT NOT NULL
+);
+
+INSERT INTO event_stats_cursor (position) VALUES (0);
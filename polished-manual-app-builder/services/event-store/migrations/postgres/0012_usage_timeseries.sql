@@ -0,0 +1,24 @@
+-- Appends, body bytes and active streams per project in hourly and daily
+-- UTC buckets, rolled up alongside event_stats_daily.
+CREATE TABLE event_stats_timeseries (
+    granularity VARCHAR NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    partition_key VARCHAR NOT NULL,
+    events BIGINT NOT NULL DEFAULT 0,
+    bytes BIGINT NOT NULL DEFAULT 0,
+    active_streams BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (granularity, bucket, partition_key)
+);
+
+-- Streams already counted as active in a recent bucket, so a stream is
+-- counted once per bucket however many batches its appends span. Rows are
+-- dropped once their bucket is well past.
+CREATE TABLE event_stats_streams (
+    granularity VARCHAR NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    partition_key VARCHAR NOT NULL,
+    stream_id VARCHAR NOT NULL,
+    PRIMARY KEY (granularity, bucket, partition_key, stream_id)
+);
+
+CREATE INDEX idx_event_stats_streams_bucket ON event_stats_streams (bucket);
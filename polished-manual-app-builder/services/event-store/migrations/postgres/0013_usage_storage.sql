@@ -0,0 +1,11 @@
+-- Bytes each project keeps in the event log, sampled through the day by
+-- the usage sampler; each row holds the day's peak. Billing averages them
+-- over the month.
+CREATE TABLE usage_storage_daily (
+    partition_key VARCHAR NOT NULL,
+    day DATE NOT NULL,
+    bytes BIGINT NOT NULL,
+    PRIMARY KEY (partition_key, day)
+);
+
+CREATE INDEX idx_usage_storage_daily_day ON usage_storage_daily (day);
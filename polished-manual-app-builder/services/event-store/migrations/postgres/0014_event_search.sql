@@ -0,0 +1,22 @@
+-- Full-text index over the JSON fields named in SEARCH_FIELDS, filled from
+-- the global position by the search indexer. `content` is the text the
+-- fields held, kept for highlighting matches.
+CREATE TABLE event_search (
+    position BIGINT PRIMARY KEY,
+    partition_key VARCHAR NOT NULL,
+    stream_id VARCHAR NOT NULL,
+    version BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    document TSVECTOR NOT NULL
+);
+
+CREATE INDEX idx_event_search_document ON event_search USING GIN (document);
+CREATE INDEX idx_event_search_partition_position ON event_search (partition_key, position);
+
+-- Position up to which events have been indexed.
+CREATE TABLE event_search_cursor (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    position BIGINT NOT NULL
+);
+
+INSERT INTO event_search_cursor (position) VALUES (0);
@@ -0,0 +1,8 @@
+-- Position up to which this store holds the events of the primary it
+-- replicates. Created by the first replicated batch, from the highest
+-- position already here, so a secondary seeded from a backup carries on
+-- where the backup ends.
+CREATE TABLE replication_checkpoint (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    position BIGINT NOT NULL
+);
@@ -0,0 +1,10 @@
+-- The lease an instance holds while it is the leader, the only one whose
+-- writes are accepted. Each new holder takes the next epoch; writes
+-- share-lock this row and are refused unless it carries their instance's
+-- epoch, so a takeover waits for writes in flight and fences off the rest.
+CREATE TABLE leader_lease (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    holder VARCHAR NOT NULL,
+    epoch BIGINT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Registered projects and workspaces, by `project` or `project/workspace`.
+CREATE TABLE namespaces (
+    id VARCHAR PRIMARY KEY,
+    namespace JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,6 @@
+-- Persistent subscription groups and their acknowledged checkpoints.
+CREATE TABLE subscription_groups (
+    name VARCHAR PRIMARY KEY,
+    subscription_group JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
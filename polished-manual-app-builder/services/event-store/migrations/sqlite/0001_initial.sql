@@ -0,0 +1,65 @@
+-- Baseline: the schema as of the switch to versioned migrations. Every
+-- statement is idempotent so databases set up before then adopt it as-is.
+
+CREATE TABLE IF NOT EXISTS events (
+    id TEXT PRIMARY KEY,
+    stream_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    data TEXT,
+    payload BLOB,
+    content_type TEXT NOT NULL DEFAULT 'application/json',
+    metadata TEXT,
+    version INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    partition_key TEXT NOT NULL,
+    tenant_id TEXT,
+    archived INTEGER NOT NULL DEFAULT 0,
+    UNIQUE(stream_id, version)
+);
+
+CREATE INDEX IF NOT EXISTS idx_events_partition_key ON events(partition_key);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_events_position ON events(position);
+CREATE INDEX IF NOT EXISTS idx_events_created_at ON events(created_at);
+CREATE INDEX IF NOT EXISTS idx_events_event_type ON events(event_type);
+CREATE INDEX IF NOT EXISTS idx_events_tenant_id ON events(tenant_id);
+
+CREATE TABLE IF NOT EXISTS snapshots (
+    id TEXT PRIMARY KEY,
+    stream_id TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    created_at TEXT NOT NULL,
+    UNIQUE(stream_id, version)
+);
+
+CREATE TABLE IF NOT EXISTS stream_metadata (
+    stream_id TEXT PRIMARY KEY,
+    metadata TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS event_type_deprecations (
+    event_type TEXT PRIMARY KEY,
+    deprecation TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY,
+    key_hash TEXT NOT NULL UNIQUE,
+    api_key TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS encryption_policies (
+    event_type TEXT PRIMARY KEY,
+    policy TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS subject_keys (
+    subject_id TEXT PRIMARY KEY,
+    key BLOB NOT NULL,
+    created_at TEXT NOT NULL
+);
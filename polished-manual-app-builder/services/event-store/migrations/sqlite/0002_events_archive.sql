@@ -0,0 +1,30 @@
+-- Archived events move out of the hot table. Each stream's archived events
+-- are a prefix of its versions; the head always stays in events.
+CREATE TABLE events_archive (
+    id TEXT PRIMARY KEY,
+    stream_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    data TEXT,
+    payload BLOB,
+    content_type TEXT NOT NULL DEFAULT 'application/json',
+    metadata TEXT,
+    version INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    partition_key TEXT NOT NULL,
+    tenant_id TEXT,
+    archived_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(stream_id, version)
+);
+
+-- Rows flagged by the old archiver, except stream heads
+INSERT INTO events_archive
+    (id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id)
+SELECT e.id, e.stream_id, e.event_type, e.data, e.payload, e.content_type, e.metadata, e.version, e.position, e.created_at, e.partition_key, e.tenant_id
+FROM events e
+WHERE e.archived = 1
+AND e.version < (SELECT MAX(h.version) FROM events h WHERE h.stream_id = e.stream_id);
+
+DELETE FROM events WHERE id IN (SELECT id FROM events_archive);
+
+ALTER TABLE events DROP COLUMN archived;
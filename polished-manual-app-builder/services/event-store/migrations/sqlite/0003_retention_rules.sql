@@ -0,0 +1,5 @@
+CREATE TABLE retention_rules (
+    name TEXT PRIMARY KEY,
+    rule TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
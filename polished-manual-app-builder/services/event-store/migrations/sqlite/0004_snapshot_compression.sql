@@ -0,0 +1,2 @@
+-- Snapshots record how their data is compressed; existing ones are LZ4.
+ALTER TABLE snapshots ADD COLUMN compression TEXT NOT NULL DEFAULT 'lz4';
@@ -0,0 +1,9 @@
+CREATE TABLE snapshot_reducers (
+    name TEXT PRIMARY KEY,
+    reducer TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Scheduled snapshots record the reducer that produced them, so the next
+-- one can continue from them instead of folding the whole stream again.
+ALTER TABLE snapshots ADD COLUMN reducer TEXT;
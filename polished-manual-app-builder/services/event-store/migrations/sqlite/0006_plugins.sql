@@ -0,0 +1,12 @@
+CREATE TABLE plugins (
+    name TEXT PRIMARY KEY,
+    plugin TEXT NOT NULL,
+    module BLOB NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE projections (
+    name TEXT PRIMARY KEY,
+    projection TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
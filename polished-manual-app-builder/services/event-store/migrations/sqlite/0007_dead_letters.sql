@@ -0,0 +1,8 @@
+CREATE TABLE dead_letters (
+    id TEXT PRIMARY KEY,
+    source TEXT NOT NULL,
+    dead_letter TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_dead_letters_source ON dead_letters (source, created_at);
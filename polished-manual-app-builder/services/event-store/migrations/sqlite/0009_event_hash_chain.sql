@@ -0,0 +1,14 @@
+-- Each event's link in its stream's hash chain. NULL for events appended
+-- before the chain.
+ALTER TABLE events ADD COLUMN chain_hash TEXT;
+ALTER TABLE events_archive ADD COLUMN chain_hash TEXT;
+
+-- Signed chain heads, each covering the appends since the one before.
+CREATE TABLE chain_anchors (
+    id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    anchor TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_chain_anchors_created_at ON chain_anchors (created_at);
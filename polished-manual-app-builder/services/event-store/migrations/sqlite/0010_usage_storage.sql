@@ -0,0 +1,8 @@
+-- Bytes each project keeps in the event log, sampled through the day; each
+-- row holds the day's peak.
+CREATE TABLE usage_storage_daily (
+    partition_key TEXT NOT NULL,
+    day TEXT NOT NULL,
+    bytes INTEGER NOT NULL,
+    PRIMARY KEY (partition_key, day)
+);
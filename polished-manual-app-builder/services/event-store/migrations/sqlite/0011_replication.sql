@@ -0,0 +1,6 @@
+-- Position up to which this store holds the events of the primary it
+-- replicates; a single row, created by the first replicated batch.
+CREATE TABLE replication_checkpoint (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    position INTEGER NOT NULL
+);
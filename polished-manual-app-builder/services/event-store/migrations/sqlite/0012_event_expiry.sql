@@ -0,0 +1,7 @@
+-- When an event appended with ttl_seconds may be deleted by the scavenger.
+-- NULL for events kept until retention rules say otherwise.
+ALTER TABLE events ADD COLUMN expires_at TEXT;
+ALTER TABLE events_archive ADD COLUMN expires_at TEXT;
+
+CREATE INDEX idx_events_expires_at ON events (expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX idx_events_archive_expires_at ON events_archive (expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,6 @@
+-- Registered projects and workspaces, by `project` or `project/workspace`.
+CREATE TABLE namespaces (
+    id TEXT PRIMARY KEY,
+    namespace TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
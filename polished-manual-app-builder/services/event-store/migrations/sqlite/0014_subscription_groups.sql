@@ -0,0 +1,6 @@
+-- Persistent subscription groups and their acknowledged checkpoints.
+CREATE TABLE subscription_groups (
+    name TEXT PRIMARY KEY,
+    subscription_group TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,257 @@
+use axum::{
+    extract::{Query, State},
+    response::Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::integrity;
+use crate::jobs::{self, Job};
+use crate::models::{AnchoredHead, ChainAnchor, Event};
+use crate::storage::{EventStorage, ReadDirection};
+use crate::AppState;
+
+/// Events read per query while collecting chain heads.
+const ANCHOR_PAGE_SIZE: i64 = 1_000;
+
+const DEFAULT_ANCHOR_LIMIT: i64 = 100;
+
+/// Signs chain anchors with the key at `chain_anchor_key_path`. Auditors
+/// check them against its public key, which `GET /admin/chain/anchors`
+/// publishes, so the store can't forge an old anchor without the key.
+pub struct AnchorSigner {
+    key_pair: Ed25519KeyPair,
+    public_key: String,
+}
+
+impl AnchorSigner {
+    /// `None` when no key is configured.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(path) = &config.chain_anchor_key_path else {
+            return Ok(None);
+        };
+        let invalid = |reason: String| AppError::Internal(format!("Invalid chain anchor key {}: {}", path, reason));
+
+        let bytes = std::fs::read(path).map_err(|e| invalid(e.to_string()))?;
+        let der = match std::str::from_utf8(&bytes) {
+            Ok(pem) if pem.trim_start().starts_with("-----BEGIN") => {
+                let body: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+                STANDARD.decode(body.trim()).map_err(|e| invalid(e.to_string()))?
+            }
+            _ => bytes,
+        };
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der).map_err(|e| invalid(e.to_string()))?;
+        let public_key = hex::encode(key_pair.public_key().as_ref());
+
+        info!("Chain anchors enabled, signed by public key {}", public_key);
+        Ok(Some(Self { key_pair, public_key }))
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    fn sign(&self, anchor: &ChainAnchor) -> Result<String> {
+        Ok(hex::encode(self.key_pair.sign(&signed_bytes(anchor)?).as_ref()))
+    }
+
+    fn verify(&self, anchor: &ChainAnchor) -> Result<bool> {
+        let Ok(signature) = hex::decode(&anchor.signature) else {
+            return Ok(false);
+        };
+        let public_key = UnparsedPublicKey::new(&ED25519, self.key_pair.public_key().as_ref());
+        Ok(public_key.verify(&signed_bytes(anchor)?, &signature).is_ok())
+    }
+}
+
+/// What an anchor's signature covers: its canonical JSON without the
+/// signature itself.
+fn signed_bytes(anchor: &ChainAnchor) -> Result<Vec<u8>> {
+    let mut value = serde_json::to_value(anchor)?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("signature");
+    }
+    Ok(integrity::canonical_json(&value))
+}
+
+/// Signs an anchor over the chain heads of the streams appended to since the
+/// last one, up to the committed position. `None` when nothing new was
+/// chained.
+pub async fn anchor(storage: &dyn EventStorage, signer: &AnchorSigner) -> Result<Option<ChainAnchor>> {
+    let last = storage.chain_anchors(1).await?.pop();
+    let up_to = storage.committed_position().await?;
+
+    let mut heads: BTreeMap<String, AnchoredHead> = BTreeMap::new();
+    let mut position = last.as_ref().map_or(0, |anchor| anchor.position);
+    while position < up_to {
+        let page = storage.read_all(position, ANCHOR_PAGE_SIZE).await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        position = last.position;
+
+        for event in page.into_iter().filter(|event| event.position <= up_to) {
+            if let Some(chain_hash) = event.chain_hash {
+                let head = AnchoredHead {
+                    stream_id: event.stream_id.clone(),
+                    version: event.version,
+                    chain_hash,
+                };
+                heads.insert(event.stream_id, head);
+            }
+        }
+    }
+    if heads.is_empty() {
+        return Ok(None);
+    }
+
+    let mut anchor = ChainAnchor {
+        id: Uuid::new_v4(),
+        created_at: Utc::now(),
+        position: up_to,
+        previous: last.map(|anchor| anchor.signature),
+        heads: heads.into_values().collect(),
+        signature: String::new(),
+    };
+    anchor.signature = signer.sign(&anchor)?;
+    storage.add_chain_anchor(&anchor).await?;
+    Ok(Some(anchor))
+}
+
+// Background task: Signs a chain anchor on a fixed interval
+pub async fn anchor_periodically(job: Arc<Job>, storage: Arc<dyn EventStorage>, signer: Arc<AnchorSigner>) {
+    jobs::run_periodically("chain_anchor", job, || async {
+        Ok(match anchor(storage.as_ref(), &signer).await? {
+            Some(anchor) => format!(
+                "Anchor {} signs {} stream heads up to position {}",
+                anchor.id,
+                anchor.heads.len(),
+                anchor.position
+            ),
+            None => "Nothing appended since the last anchor".to_string(),
+        })
+    })
+    .await
+}
+
+fn signer(state: &AppState) -> Result<&AnchorSigner> {
+    state
+        .anchor_signer
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest("Chain anchors are disabled; set CHAIN_ANCHOR_KEY_PATH".to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnchorQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnchorList {
+    /// Hex Ed25519 public key the anchors are signed with.
+    pub public_key: String,
+    pub anchors: Vec<ChainAnchor>,
+}
+
+/// GET /admin/chain/anchors — the newest anchors, with the key to check them.
+pub async fn list_anchors(State(state): State<AppState>, Query(query): Query<AnchorQuery>) -> Result<Json<AnchorList>> {
+    let signer = signer(&state)?;
+    let anchors = state.storage.chain_anchors(query.limit.unwrap_or(DEFAULT_ANCHOR_LIMIT)).await?;
+    Ok(Json(AnchorList {
+        public_key: signer.public_key().to_string(),
+        anchors,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnchorProblem {
+    pub anchor_id: Uuid,
+    pub problem: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AnchorReport {
+    pub anchors_checked: u64,
+    pub heads_checked: u64,
+    /// Anchored events no longer in the store, e.g. after a retention purge.
+    pub heads_missing: u64,
+    pub problems: Vec<AnchorProblem>,
+}
+
+/// The event at `version` of a stream, archived or not.
+async fn event_at(storage: &dyn EventStorage, stream_id: &str, version: i64) -> Result<Option<Event>> {
+    let local = storage
+        .read_stream(stream_id, version, 1, ReadDirection::Forward, Deadline(None))
+        .await?
+        .pop()
+        .filter(|event| event.version == version);
+    match local {
+        Some(event) => Ok(Some(event)),
+        None => Ok(storage.read_archived(stream_id, version, version).await?.pop()),
+    }
+}
+
+/// POST /admin/chain/anchors/verify — checks the newest anchors (`?limit=`)
+/// against the current key, against each other, and against the log: every
+/// anchored stream head must still carry the chain hash that was signed.
+/// With `POST /admin/verify/chain` confirming each stream's chain up to its
+/// head, that shows nothing anchored has been rewritten since.
+pub async fn verify_anchors(
+    State(state): State<AppState>,
+    Query(query): Query<AnchorQuery>,
+) -> Result<Json<AnchorReport>> {
+    let signer = signer(&state)?;
+    let mut anchors = state.storage.chain_anchors(query.limit.unwrap_or(DEFAULT_ANCHOR_LIMIT)).await?;
+    anchors.reverse();
+
+    let mut report = AnchorReport::default();
+    let mut previous: Option<&ChainAnchor> = None;
+    for anchor in &anchors {
+        report.anchors_checked += 1;
+        let mut problem = |problem: String| {
+            error!("Chain anchor {}: {}", anchor.id, problem);
+            report.problems.push(AnchorProblem {
+                anchor_id: anchor.id,
+                problem,
+            });
+        };
+
+        if !signer.verify(anchor)? {
+            problem("signature does not match".to_string());
+        }
+        if let Some(previous) = previous {
+            if anchor.previous.as_deref() != Some(previous.signature.as_str()) {
+                problem(format!("does not follow anchor {}", previous.id));
+            }
+        }
+        previous = Some(anchor);
+
+        for head in &anchor.heads {
+            match event_at(state.storage.as_ref(), &head.stream_id, head.version).await? {
+                Some(event) if event.chain_hash.as_deref() == Some(head.chain_hash.as_str()) => {}
+                Some(_) => problem(format!(
+                    "{} v{} no longer has the anchored chain hash",
+                    head.stream_id, head.version
+                )),
+                None => report.heads_missing += 1,
+            }
+            report.heads_checked += 1;
+        }
+    }
+
+    info!(
+        "Verified {} chain anchors: {} problems",
+        report.anchors_checked,
+        report.problems.len()
+    );
+    Ok(Json(report))
+}
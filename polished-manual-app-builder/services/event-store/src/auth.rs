@@ -0,0 +1,374 @@
+use async_trait::async_trait;
+use axum::extract::{FromRequestParts, Path, RawPathParams, Request, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderName, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, Role, Scope};
+use crate::storage::EventStorage;
+use crate::{get_partition_key, ui, AppState};
+
+pub static API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+pub static TENANT_HEADER: HeaderName = HeaderName::from_static("x-tenant-id");
+
+/// Endpoints that stay reachable without a key, for probes and scrapers.
+const PUBLIC_PATHS: &[&str] = &["/health", "/health/live", "/health/ready", "/metrics", "/errors/catalog"];
+
+/// Hex SHA-256 of a presented key. Only hashes are stored or kept in memory.
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Known API keys by hash: those from `API_KEYS` plus those created through
+/// the admin API. While there are none, authentication is off.
+pub struct ApiKeyRegistry {
+    keys: RwLock<HashMap<String, ApiKey>>,
+}
+
+impl ApiKeyRegistry {
+    pub async fn load(storage: &dyn EventStorage, config: &Config) -> Result<Self> {
+        let mut keys: HashMap<String, ApiKey> = storage.api_keys().await?.into_iter().collect();
+
+        // API_KEYS="key1=read+write@acme,key2=admin"
+        for (i, entry) in config
+            .api_keys
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .enumerate()
+        {
+            let (secret, scopes) = entry
+                .split_once('=')
+                .ok_or_else(|| AppError::Internal(format!("API_KEYS entry {} has no scopes", i + 1)))?;
+            let (scopes, project_id) = match scopes.split_once('@') {
+                Some((scopes, project)) => (scopes, Some(project.trim().to_string())),
+                None => (scopes, None),
+            };
+            let scopes = scopes
+                .split('+')
+                .map(|scope| {
+                    serde_json::from_value(serde_json::Value::String(scope.trim().to_string()))
+                        .map_err(|_| AppError::Internal(format!("Unknown scope '{}' in API_KEYS", scope)))
+                })
+                .collect::<Result<Vec<Scope>>>()?;
+            check_project_binding(&scopes, project_id.as_deref())
+                .map_err(|e| AppError::Internal(format!("API_KEYS entry {}: {}", i + 1, e)))?;
+
+            keys.insert(
+                hash_key(secret),
+                ApiKey {
+                    id: Uuid::nil(),
+                    name: format!("config-{}", i + 1),
+                    scopes,
+                    project_id,
+                    created_at: Utc::now(),
+                },
+            );
+        }
+
+        if keys.is_empty() {
+            warn!("No API keys configured; authentication is disabled until one is created");
+        } else {
+            info!("API key authentication enabled ({} keys)", keys.len());
+        }
+
+        Ok(Self {
+            keys: RwLock::new(keys),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.read().unwrap().is_empty()
+    }
+
+    pub fn lookup(&self, key: &str) -> Option<ApiKey> {
+        self.keys.read().unwrap().get(&hash_key(key)).cloned()
+    }
+
+    fn insert(&self, key_hash: String, key: ApiKey) {
+        self.keys.write().unwrap().insert(key_hash, key);
+    }
+
+    fn remove(&self, id: Uuid) {
+        self.keys.write().unwrap().retain(|_, key| key.id != id);
+    }
+}
+
+/// Only admin keys may act for every tenant; every other key is bound to the
+/// project it acts for.
+fn check_project_binding(scopes: &[Scope], project_id: Option<&str>) -> Result<()> {
+    match (Role::from_scopes(scopes), project_id) {
+        (Some(Role::Admin), _) => Ok(()),
+        (_, Some(project)) if !project.is_empty() && !project.contains('/') => Ok(()),
+        (_, Some(project)) => Err(AppError::BadRequest(format!("Invalid project id '{}'", project))),
+        (_, None) => Err(AppError::BadRequest(
+            "A key without the admin scope needs a project_id".to_string(),
+        )),
+    }
+}
+
+/// The role a request needs: reader for safe methods and GraphQL (whose
+/// schema has no mutations), writer for everything else. Admin routes
+/// additionally go through `require_admin`.
+fn required_role(method: &Method, path: &str) -> Role {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) || path.starts_with("/graphql") {
+        Role::Reader
+    } else {
+        Role::Writer
+    }
+}
+
+/// The tenant a request acts for: the `project_id` claim of its bearer token
+/// or the project its API key is bound to, else, for admin keys and open
+/// deployments, the `X-Tenant-Id` header. A tenant owns the streams whose id starts
+/// with its id; that id is also the partition key of their events. `None`
+/// (admin keys, open deployments) is not restricted.
+#[derive(Debug, Clone, Default)]
+pub struct Tenant(pub Option<String>);
+
+impl Tenant {
+    /// Fails unless `stream_id` belongs to this tenant.
+    pub fn authorize(&self, stream_id: &str) -> Result<()> {
+        match &self.0 {
+            Some(tenant) if get_partition_key(stream_id) != *tenant => Err(AppError::Forbidden(format!(
+                "Tenant '{}' cannot access stream {}",
+                tenant, stream_id
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// The tenant stamped on events written to `stream_id`.
+    pub fn id_for(&self, stream_id: &str) -> String {
+        self.0.clone().unwrap_or_else(|| get_partition_key(stream_id))
+    }
+}
+
+/// Who a request's credentials identify, for per-caller limits: an API key,
+/// or the project of a bearer token. Unlike the tenant, never taken from a
+/// header. Set by `authenticate`; absent while authentication is off.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub id: String,
+    /// The project the credentials are bound to; none for admin keys.
+    pub project_id: Option<String>,
+}
+
+/// Set by `authenticate`; handlers that take a stream id from the body,
+/// which the middleware cannot see, call `authorize` themselves.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Tenant {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        Ok(parts.extensions.get::<Tenant>().cloned().unwrap_or_default())
+    }
+}
+
+/// Middleware: authenticates by `Authorization: Bearer <jwt>` when a JWKS
+/// is configured, or by `X-Api-Key`, checks the caller's role covers the
+/// request method, then resolves the request's tenant and checks it owns the
+/// stream in the path. Rejects requests without valid credentials (401) or
+/// whose credentials do not cover the request (403). The caller's `Role`,
+/// `Caller` and `Tenant` are added to the request extensions.
+pub async fn authenticate(
+    State(state): State<AppState>,
+    params: Option<RawPathParams>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response> {
+    let path = request.uri().path().to_string();
+    if PUBLIC_PATHS.contains(&path.as_str()) || ui::is_ui_path(&path) {
+        return Ok(next.run(request).await);
+    }
+
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let (role, credential_tenant) = match (bearer, &state.jwt) {
+        (Some(token), Some(verifier)) => {
+            let claims = verifier.verify(token.trim()).await?;
+            let role = claims
+                .role()
+                .ok_or_else(|| AppError::Forbidden("Token grants no read, write or admin scope".to_string()))?;
+            request.extensions_mut().insert(Caller {
+                id: format!("project:{}", claims.project_id),
+                project_id: Some(claims.project_id.clone()),
+            });
+            (role, Some(claims.project_id))
+        }
+        _ => match check_api_key(&state, &request)? {
+            Some(key) => {
+                let role = Role::from_scopes(&key.scopes)
+                    .ok_or_else(|| AppError::Forbidden(format!("API key '{}' has no scopes", key.name)))?;
+                // Keys created before keys were bound to projects have none
+                if role != Role::Admin && key.project_id.is_none() {
+                    return Err(AppError::Forbidden(format!(
+                        "API key '{}' is bound to no project; replace it with one that is",
+                        key.name
+                    )));
+                }
+                let project_id = key.project_id.clone().filter(|_| role != Role::Admin);
+                request.extensions_mut().insert(Caller {
+                    // Keys from API_KEYS share the nil id but have distinct names
+                    id: if key.id.is_nil() { format!("key:{}", key.name) } else { format!("key:{}", key.id) },
+                    project_id: project_id.clone(),
+                });
+                request.extensions_mut().insert(key);
+                (role, project_id)
+            }
+            // Authentication is off, so there is no one to restrict
+            None => (Role::Admin, None),
+        },
+    };
+
+    let required = required_role(request.method(), &path);
+    if role < required {
+        return Err(AppError::Forbidden(format!(
+            "{} {} needs the {:?} role; the caller is {:?}",
+            request.method(),
+            path,
+            required,
+            role
+        )));
+    }
+    request.extensions_mut().insert(role);
+
+    let header_tenant = request
+        .headers()
+        .get(&TENANT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string());
+    let tenant = match (credential_tenant, header_tenant) {
+        (Some(token), Some(header)) if token != header => {
+            return Err(AppError::Forbidden(format!(
+                "X-Tenant-Id '{}' does not match the credentials' project '{}'",
+                header, token
+            )));
+        }
+        (token, header) => Tenant(token.or(header)),
+    };
+    if tenant.0.is_none() && state.config.require_tenant && !path.starts_with("/admin") {
+        return Err(AppError::BadRequest("Missing X-Tenant-Id header".to_string()));
+    }
+
+    if let Some(params) = &params {
+        for (name, value) in params.iter() {
+            if name == "stream_id" {
+                tenant.authorize(value)?;
+            }
+        }
+    }
+    request.extensions_mut().insert(tenant);
+    Ok(next.run(request).await)
+}
+
+/// Looks up the request's `X-Api-Key` in the registry. `None` when
+/// authentication is off.
+fn check_api_key(state: &AppState, request: &Request) -> Result<Option<ApiKey>> {
+    if !state.api_keys.is_enabled() {
+        if state.jwt.is_some() {
+            return Err(AppError::Unauthorized("Missing bearer token".to_string()));
+        }
+        return Ok(None);
+    }
+
+    let presented = request
+        .headers()
+        .get(&API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("Missing X-Api-Key header or bearer token".to_string()))?;
+    let key = state
+        .api_keys
+        .lookup(presented)
+        .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()))?;
+    Ok(Some(key))
+}
+
+/// Route layer for the admin endpoints (backups, restores, bulk loads, API
+/// keys, event type migrations): only callers with the admin role pass.
+pub async fn require_admin(request: Request, next: Next) -> Result<Response> {
+    match request.extensions().get::<Role>() {
+        Some(Role::Admin) => Ok(next.run(request).await),
+        role => Err(AppError::Forbidden(format!(
+            "{} needs the Admin role; the caller is {:?}",
+            request.uri().path(),
+            role.copied().unwrap_or(Role::Reader)
+        ))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Required unless the key has the admin scope.
+    pub project_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    /// The secret; shown only in this response.
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+/// GET /admin/api-keys
+pub async fn list_api_keys(State(state): State<AppState>) -> Result<Json<Vec<ApiKey>>> {
+    let keys = state.storage.api_keys().await?;
+    Ok(Json(keys.into_iter().map(|(_, key)| key).collect()))
+}
+
+/// POST /admin/api-keys
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKey>)> {
+    if request.scopes.is_empty() {
+        return Err(AppError::BadRequest("An API key needs at least one scope".to_string()));
+    }
+    check_project_binding(&request.scopes, request.project_id.as_deref())?;
+
+    let secret = format!("esk_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let api_key = ApiKey {
+        id: Uuid::new_v4(),
+        name: request.name,
+        // An admin key acts for every tenant, so a project would mislead
+        project_id: request.project_id.filter(|_| Role::from_scopes(&request.scopes) != Some(Role::Admin)),
+        scopes: request.scopes,
+        created_at: Utc::now(),
+    };
+    let key_hash = hash_key(&secret);
+
+    state.storage.insert_api_key(&key_hash, &api_key).await?;
+    state.api_keys.insert(key_hash, api_key.clone());
+    info!("API key created: {} ({})", api_key.name, api_key.id);
+
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { key: secret, api_key })))
+}
+
+/// DELETE /admin/api-keys/:id
+pub async fn revoke_api_key(Path(id): Path<Uuid>, State(state): State<AppState>) -> Result<Response> {
+    if !state.storage.revoke_api_key(id).await? {
+        return Err(AppError::NotFound(format!("API key {} not found", id)));
+    }
+
+    state.api_keys.remove(id);
+    info!("API key revoked: {}", id);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
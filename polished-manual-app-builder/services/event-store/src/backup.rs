@@ -0,0 +1,494 @@
+use axum::extract::State;
+use axum::response::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::jobs::{self, Job};
+use crate::models::StreamMetadata;
+use crate::object_store::{decode_events, encode_events, S3Bucket};
+use crate::storage::EventStorage;
+use crate::AppState;
+
+/// Events per backup chunk object.
+const CHUNK_EVENTS: i64 = 10_000;
+const MANIFEST: &str = "manifest.json";
+const STREAM_METADATA: &str = "stream_metadata.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupKind {
+    Full,
+    Incremental,
+}
+
+/// Written last, so a backup without a manifest is incomplete and ignored.
+///
+/// A backup covers global positions `(from_position, to_position]`: full
+/// backups start at 0, incrementals where the previous backup stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub id: String,
+    pub kind: BackupKind,
+    pub parent: Option<String>,
+    pub from_position: i64,
+    pub to_position: i64,
+    pub event_count: u64,
+    pub chunks: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// Whether every stream's metadata was saved with the backup. Full
+    /// backups always save it; incrementals written by older versions don't.
+    #[serde(default)]
+    pub stream_metadata: bool,
+}
+
+/// Where backups are written: a local directory or an S3 prefix, laid out as
+/// `backups/{id}/manifest.json` plus chunk files.
+pub enum BackupTarget {
+    Directory(PathBuf),
+    Bucket { bucket: S3Bucket, prefix: String },
+}
+
+impl BackupTarget {
+    /// Parses `BACKUP_LOCATION`: `s3://bucket/prefix` or a directory path.
+    pub async fn from_config(config: &Config) -> Option<Self> {
+        let location = config.backup_location.as_deref()?;
+
+        let target = match location.strip_prefix("s3://") {
+            Some(rest) => {
+                let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+                BackupTarget::Bucket {
+                    bucket: S3Bucket::connect(bucket.to_string(), config.s3_endpoint.as_deref()).await,
+                    prefix: prefix.trim_end_matches('/').to_string(),
+                }
+            }
+            None => BackupTarget::Directory(PathBuf::from(location)),
+        };
+
+        info!("Backups enabled: {}", target.describe());
+        Some(target)
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            BackupTarget::Directory(dir) => dir.display().to_string(),
+            BackupTarget::Bucket { bucket, prefix } => format!("s3://{}/{}", bucket.name(), prefix),
+        }
+    }
+
+    fn key(prefix: &str, name: &str) -> String {
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", prefix, name)
+        }
+    }
+
+    async fn put(&self, name: &str, body: Vec<u8>) -> Result<()> {
+        match self {
+            BackupTarget::Directory(dir) => {
+                let path = dir.join(name);
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await.map_err(|e| {
+                        AppError::Internal(format!("Failed to create {}: {}", parent.display(), e))
+                    })?;
+                }
+                tokio::fs::write(&path, body)
+                    .await
+                    .map_err(|e| AppError::Internal(format!("Failed to write {}: {}", path.display(), e)))
+            }
+            BackupTarget::Bucket { bucket, prefix } => {
+                bucket
+                    .put(&Self::key(prefix, name), body, "application/octet-stream")
+                    .await
+            }
+        }
+    }
+
+    async fn get(&self, name: &str) -> Result<Vec<u8>> {
+        match self {
+            BackupTarget::Directory(dir) => {
+                let path = dir.join(name);
+                tokio::fs::read(&path)
+                    .await
+                    .map_err(|e| AppError::Internal(format!("Failed to read {}: {}", path.display(), e)))
+            }
+            BackupTarget::Bucket { bucket, prefix } => bucket.get(&Self::key(prefix, name)).await,
+        }
+    }
+
+    /// Completed backups, oldest first.
+    pub async fn manifests(&self) -> Result<Vec<BackupManifest>> {
+        let ids: Vec<String> = match self {
+            BackupTarget::Directory(dir) => {
+                let mut ids = Vec::new();
+                let mut entries = match tokio::fs::read_dir(dir.join("backups")).await {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+                    Err(e) => return Err(AppError::Internal(format!("Failed to list backups: {}", e))),
+                };
+                while let Some(entry) = entries
+                    .next_entry()
+                    .await
+                    .map_err(|e| AppError::Internal(format!("Failed to list backups: {}", e)))?
+                {
+                    if entry.path().join(MANIFEST).exists() {
+                        ids.push(entry.file_name().to_string_lossy().to_string());
+                    }
+                }
+                ids
+            }
+            BackupTarget::Bucket { bucket, prefix } => {
+                let backups_prefix = Self::key(prefix, "backups/");
+                bucket
+                    .list(&backups_prefix)
+                    .await?
+                    .iter()
+                    .filter_map(|key| key.strip_prefix(&backups_prefix)?.strip_suffix("/manifest.json"))
+                    .map(str::to_string)
+                    .collect()
+            }
+        };
+
+        let mut manifests = Vec::with_capacity(ids.len());
+        for id in ids {
+            let manifest: BackupManifest =
+                serde_json::from_slice(&self.get(&format!("backups/{}/{}", id, MANIFEST)).await?)?;
+            manifests.push(manifest);
+        }
+        manifests.sort_by_key(|m| (m.to_position, m.created_at));
+
+        Ok(manifests)
+    }
+}
+
+/// Writes a backup of everything committed since the previous one (or since
+/// the beginning, for full backups). An incremental backup with nothing new
+/// returns the latest manifest instead of writing an empty one.
+pub async fn create_backup(
+    storage: &dyn EventStorage,
+    target: &BackupTarget,
+    kind: Option<BackupKind>,
+) -> Result<BackupManifest> {
+    let latest = target.manifests().await?.pop();
+    let kind = kind.unwrap_or(if latest.is_some() {
+        BackupKind::Incremental
+    } else {
+        BackupKind::Full
+    });
+
+    let (from_position, parent) = match (kind, &latest) {
+        (BackupKind::Full, _) => (0, None),
+        (BackupKind::Incremental, Some(latest)) => (latest.to_position, Some(latest.id.clone())),
+        (BackupKind::Incremental, None) => {
+            return Err(AppError::BadRequest(
+                "An incremental backup needs a previous backup; take a full one first".to_string(),
+            ))
+        }
+    };
+
+    let to_position = storage.committed_position().await?;
+    if kind == BackupKind::Incremental && to_position <= from_position {
+        if let Some(latest) = latest {
+            return Ok(latest);
+        }
+    }
+
+    let created_at = Utc::now();
+    let kind_name = match kind {
+        BackupKind::Full => "full",
+        BackupKind::Incremental => "incremental",
+    };
+    let id = format!("{}-{}", created_at.format("%Y%m%dT%H%M%S%3fZ"), kind_name);
+
+    let mut chunks = Vec::new();
+    let mut event_count = 0;
+    let mut after = from_position;
+    while after < to_position {
+        let mut events = storage.read_all_with_archive(after, CHUNK_EVENTS).await?;
+        events.retain(|e| e.position <= to_position);
+        let Some(last) = events.last() else {
+            break;
+        };
+        after = last.position;
+
+        let chunk = format!("events-{:06}.ndjson.zst", chunks.len());
+        target
+            .put(&format!("backups/{}/{}", id, chunk), encode_events(&events)?)
+            .await?;
+        chunks.push(chunk);
+        event_count += events.len() as u64;
+    }
+
+    let metadata = storage.all_stream_metadata().await?;
+    target
+        .put(&format!("backups/{}/{}", id, STREAM_METADATA), serde_json::to_vec(&metadata)?)
+        .await?;
+
+    let manifest = BackupManifest {
+        id,
+        kind,
+        parent,
+        from_position,
+        to_position,
+        event_count,
+        chunks,
+        created_at,
+        stream_metadata: true,
+    };
+    target
+        .put(&format!("backups/{}/{}", manifest.id, MANIFEST), serde_json::to_vec_pretty(&manifest)?)
+        .await?;
+
+    info!(
+        "Backup {} written: positions {}..={} ({} events)",
+        manifest.id, manifest.from_position + 1, manifest.to_position, manifest.event_count
+    );
+    Ok(manifest)
+}
+
+/// Restores an empty store from the newest full backup at or before
+/// `position` plus the incrementals after it, stopping at `position`.
+/// Returns the manifests that were applied.
+pub async fn restore(
+    storage: &dyn EventStorage,
+    target: &BackupTarget,
+    position: Option<i64>,
+) -> Result<(i64, Vec<BackupManifest>)> {
+    if !storage.is_empty().await? {
+        return Err(AppError::Conflict(
+            "Restore requires an empty event store".to_string(),
+        ));
+    }
+
+    let manifests = target.manifests().await?;
+    let Some(latest) = manifests.last() else {
+        return Err(AppError::NotFound("No backups found".to_string()));
+    };
+    let position = position.unwrap_or(latest.to_position);
+
+    // Plan the chain before touching the store, so a gap fails cleanly
+    let fulls = manifests.iter().filter(|m| m.kind == BackupKind::Full);
+    let base = fulls
+        .clone()
+        .filter(|m| m.to_position <= position)
+        .last()
+        .or_else(|| fulls.clone().next())
+        .ok_or_else(|| AppError::NotFound("No full backup to restore from".to_string()))?;
+
+    let mut plan = vec![base.clone()];
+    let mut covered = base.to_position;
+    for manifest in manifests.iter().filter(|m| m.kind == BackupKind::Incremental) {
+        if covered >= position {
+            break;
+        }
+        if manifest.to_position <= covered {
+            continue;
+        }
+        if manifest.from_position > covered {
+            return Err(AppError::Conflict(format!(
+                "Backup chain has a gap between positions {} and {}",
+                covered, manifest.from_position
+            )));
+        }
+        plan.push(manifest.clone());
+        covered = manifest.to_position;
+    }
+    if covered < position {
+        return Err(AppError::BadRequest(format!(
+            "Backups only reach position {}, not {}",
+            covered, position
+        )));
+    }
+
+    let mut restored_to = 0;
+    for manifest in &plan {
+        for chunk in &manifest.chunks {
+            let mut events = decode_events(&target.get(&format!("backups/{}/{}", manifest.id, chunk)).await?)?;
+            events.retain(|e| e.position > restored_to && e.position <= position);
+            if let Some(last) = events.last() {
+                restored_to = last.position;
+            }
+            storage.import_events(&events).await?;
+        }
+        info!("Restored backup {}", manifest.id);
+    }
+
+    // Metadata as of the newest backup applied that saved it
+    let saved = plan
+        .iter()
+        .rev()
+        .find(|m| m.kind == BackupKind::Full || m.stream_metadata)
+        .unwrap_or(base);
+    let metadata: Vec<(String, StreamMetadata)> =
+        serde_json::from_slice(&target.get(&format!("backups/{}/{}", saved.id, STREAM_METADATA)).await?)?;
+    for (stream_id, metadata) in &metadata {
+        storage.set_stream_metadata(stream_id, metadata).await?;
+    }
+
+    Ok((restored_to, plan))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BackupRequest {
+    pub kind: Option<BackupKind>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RestoreRequest {
+    pub position: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreResponse {
+    pub position: i64,
+    pub backups: Vec<String>,
+}
+
+fn backup_target(state: &AppState) -> Result<&BackupTarget> {
+    state
+        .backup_target
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest("Backups are not configured; set BACKUP_LOCATION".to_string()))
+}
+
+/// POST /admin/backups
+pub async fn create_backup_handler(
+    State(state): State<AppState>,
+    request: Option<Json<BackupRequest>>,
+) -> Result<Json<BackupManifest>> {
+    let kind = request.and_then(|Json(r)| r.kind);
+    let manifest = create_backup(state.storage.as_ref(), backup_target(&state)?, kind).await?;
+    Ok(Json(manifest))
+}
+
+/// GET /admin/backups
+pub async fn list_backups(State(state): State<AppState>) -> Result<Json<Vec<BackupManifest>>> {
+    Ok(Json(backup_target(&state)?.manifests().await?))
+}
+
+/// POST /admin/restore
+pub async fn restore_handler(
+    State(state): State<AppState>,
+    request: Option<Json<RestoreRequest>>,
+) -> Result<Json<RestoreResponse>> {
+    let position = request.and_then(|Json(r)| r.position);
+    let (position, plan) = restore(state.storage.as_ref(), backup_target(&state)?, position).await?;
+    if let Some(cache) = &state.read_cache {
+        cache.clear();
+    }
+
+    Ok(Json(RestoreResponse {
+        position,
+        backups: plan.into_iter().map(|m| m.id).collect(),
+    }))
+}
+
+// Background task: Incremental backups on a fixed interval
+pub async fn continuous_backup(job: Arc<Job>, storage: Arc<dyn EventStorage>, target: Arc<BackupTarget>) {
+    jobs::run_periodically("continuous_backup", job, || async {
+        let manifest = create_backup(storage.as_ref(), &target, None).await?;
+        Ok(format!(
+            "Backup {} ({:?}, {} events)",
+            manifest.id, manifest.kind, manifest.event_count
+        ))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deadline::Deadline;
+    use crate::models::Event;
+    use crate::storage::{ArchiveRange, MemoryStorage, ReadDirection};
+    use uuid::Uuid;
+
+    const STREAM: &str = "acme/orders/1";
+
+    fn event(version: i64) -> Event {
+        Event {
+            id: Uuid::new_v4(),
+            stream_id: STREAM.to_string(),
+            event_type: "OrderUpdated".to_string(),
+            data: serde_json::json!({ "n": version }),
+            payload: None,
+            content_type: "application/json".to_string(),
+            metadata: None,
+            version,
+            position: version,
+            created_at: Utc::now(),
+            checksum: None,
+            chain_hash: None,
+            link: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn restores_events_that_were_archived_before_the_backup() {
+        let dir = std::env::temp_dir().join(format!("event-store-backup-{}", Uuid::new_v4()));
+        let target = BackupTarget::Directory(dir.clone());
+
+        let source = MemoryStorage::new();
+        source.import_events(&(1..=5).map(event).collect::<Vec<_>>()).await.unwrap();
+        let archived = source
+            .archive_ranges(&[ArchiveRange {
+                stream_id: STREAM.to_string(),
+                from_version: 1,
+                to_version: 3,
+            }])
+            .await
+            .unwrap();
+        assert_eq!(archived, 3);
+
+        let manifest = create_backup(&source, &target, Some(BackupKind::Full)).await.unwrap();
+        assert_eq!(manifest.event_count, 5);
+
+        let restored = MemoryStorage::new();
+        let (position, _) = restore(&restored, &target, None).await.unwrap();
+        assert_eq!(position, 5);
+        let events = restored
+            .read_stream(STREAM, 1, 10, ReadDirection::Forward, Deadline(None))
+            .await
+            .unwrap();
+        assert_eq!(events.iter().map(|e| e.version).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
+
+    #[tokio::test]
+    async fn restores_stream_metadata_set_after_the_full_backup() {
+        let dir = std::env::temp_dir().join(format!("event-store-backup-{}", Uuid::new_v4()));
+        let target = BackupTarget::Directory(dir.clone());
+
+        let source = MemoryStorage::new();
+        source.import_events(&[event(1), event(2)]).await.unwrap();
+        create_backup(&source, &target, Some(BackupKind::Full)).await.unwrap();
+        let metadata = StreamMetadata {
+            max_events: Some(100),
+            ..Default::default()
+        };
+        source.set_stream_metadata(STREAM, &metadata).await.unwrap();
+        source.import_events(&[event(3)]).await.unwrap();
+        let manifest = create_backup(&source, &target, None).await.unwrap();
+        assert_eq!(manifest.kind, BackupKind::Incremental);
+
+        let restored = MemoryStorage::new();
+        let (_, plan) = restore(&restored, &target, None).await.unwrap();
+        assert_eq!(plan.len(), 2);
+        let restored_metadata = restored.stream_metadata(STREAM).await.unwrap().unwrap();
+        assert_eq!(restored_metadata.max_events, Some(100));
+
+        // The restored store now holds events, so a second restore is refused
+        assert!(matches!(
+            restore(&restored, &target, None).await,
+            Err(AppError::Conflict(_))
+        ));
+
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
+}
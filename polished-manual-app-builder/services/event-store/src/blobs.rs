@@ -0,0 +1,176 @@
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::Event;
+use crate::object_store::S3Bucket;
+use crate::{is_json_content_type, AppState};
+
+/// Content type of an event whose body was offloaded; its data is a
+/// `BlobRef`. Clients that list it in `Accept` get events in this form
+/// instead of with their bodies fetched back.
+pub const BLOB_REF_MIME: &str = "application/vnd.event-store.blob-ref+json";
+
+/// Events buffered between a streamed read and the resolver.
+const RESOLVE_BUFFER: usize = 256;
+
+/// Where an offloaded event body went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobRef {
+    /// `s3://bucket/key`
+    pub url: String,
+    /// Hex SHA-256 of the body, checked when it's fetched back.
+    pub sha256: String,
+    pub size: u64,
+    /// The event's own content type.
+    pub content_type: String,
+}
+
+/// Event bodies over `blob_offload_threshold_kb`, kept in object storage
+/// under `{prefix}/{stream_id}/{event_id}` so the event row only holds a
+/// reference to them.
+pub struct BlobStore {
+    bucket: S3Bucket,
+    prefix: String,
+    threshold_bytes: usize,
+}
+
+impl BlobStore {
+    /// Builds the store from the environment's AWS credentials, or returns
+    /// `None` when no bucket is configured.
+    pub async fn from_config(config: &Config) -> Option<Self> {
+        let bucket = config.blob_bucket.clone()?;
+
+        info!(
+            "Blob offloading enabled: bodies over {} KB go to s3://{}/{}",
+            config.blob_offload_threshold_kb, bucket, config.blob_prefix
+        );
+        Some(Self {
+            bucket: S3Bucket::connect(bucket, config.s3_endpoint.as_deref()).await,
+            prefix: config.blob_prefix.trim_end_matches('/').to_string(),
+            threshold_bytes: config.blob_offload_threshold_kb as usize * 1024,
+        })
+    }
+
+    pub fn should_offload(&self, bytes: usize) -> bool {
+        bytes > self.threshold_bytes
+    }
+
+    /// Uploads `body`, returning the reference to store in its place.
+    pub async fn offload(&self, stream_id: &str, event_id: Uuid, content_type: &str, body: Vec<u8>) -> Result<BlobRef> {
+        let key = format!("{}/{}/{}", self.prefix, stream_id, event_id);
+        let blob = BlobRef {
+            url: format!("s3://{}/{}", self.bucket.name(), key),
+            sha256: hex::encode(Sha256::digest(&body)),
+            size: body.len() as u64,
+            content_type: content_type.to_string(),
+        };
+        self.bucket.put(&key, body, content_type).await?;
+        Ok(blob)
+    }
+
+    fn key<'a>(&self, blob: &'a BlobRef) -> Result<&'a str> {
+        blob.url
+            .strip_prefix("s3://")
+            .and_then(|rest| rest.strip_prefix(self.bucket.name()))
+            .and_then(|rest| rest.strip_prefix('/'))
+            .ok_or_else(|| AppError::ColdStorage(format!("Blob {} is not in bucket {}", blob.url, self.bucket.name())))
+    }
+
+    /// The body `blob` points at, checked against its hash.
+    async fn fetch(&self, blob: &BlobRef) -> Result<Vec<u8>> {
+        let body = self.bucket.get(self.key(blob)?).await?;
+        if hex::encode(Sha256::digest(&body)) != blob.sha256 {
+            return Err(AppError::ColdStorage(format!("Blob {} does not match its hash", blob.url)));
+        }
+        Ok(body)
+    }
+}
+
+/// Deletes the body offloaded for event `id` after its append failed,
+/// unless the event is there after all, e.g. from an earlier attempt at
+/// the same append since `since`. Failures are only logged; the client
+/// needs the append's error, not this one.
+pub async fn discard(state: &AppState, stream_id: &str, id: Uuid, since: DateTime<Utc>, blob: &BlobRef) {
+    let Some(blobs) = &state.blobs else {
+        return;
+    };
+    let discarded = async {
+        if state.storage.find_event(stream_id, id, since).await?.is_some() {
+            return Ok(());
+        }
+        blobs.bucket.delete(blobs.key(blob)?).await
+    };
+    if let Err(e) = discarded.await {
+        warn!("Failed to discard blob {} of a failed append: {}", blob.url, e);
+    }
+}
+
+/// Whether the client asked for offloaded bodies as references.
+pub fn wants_links(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| {
+            accept
+                .split(',')
+                .any(|mime| mime.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case(BLOB_REF_MIME))
+        })
+}
+
+async fn resolve_event(state: &AppState, event: &mut Event) -> Result<()> {
+    if event.content_type != BLOB_REF_MIME {
+        return Ok(());
+    }
+    let Some(blobs) = &state.blobs else {
+        // Without the bucket the reference is the best there is
+        warn!("Event {} has an offloaded body, but BLOB_BUCKET is not set", event.id);
+        return Ok(());
+    };
+
+    let blob: BlobRef = serde_json::from_value(event.data.clone())
+        .map_err(|e| AppError::Internal(format!("Event {} has an unreadable blob reference: {}", event.id, e)))?;
+    let body = blobs.fetch(&blob).await?;
+    if is_json_content_type(&blob.content_type) {
+        event.data = serde_json::from_slice(&body)?;
+        event.payload = None;
+    } else {
+        event.data = serde_json::Value::Null;
+        event.payload = Some(body);
+    }
+    event.content_type = blob.content_type;
+    Ok(())
+}
+
+/// Replaces offloaded bodies with the real ones.
+pub async fn resolve_events(state: &AppState, events: &mut [Event]) -> Result<()> {
+    for event in events {
+        resolve_event(state, event).await?;
+    }
+    Ok(())
+}
+
+/// `resolve_events` for a streamed read.
+pub fn resolving(state: AppState, mut events: mpsc::Receiver<Result<Event>>) -> mpsc::Receiver<Result<Event>> {
+    let (sender, receiver) = mpsc::channel(RESOLVE_BUFFER);
+
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let event = match event {
+                Ok(mut event) => resolve_event(&state, &mut event).await.map(|_| event),
+                Err(e) => Err(e),
+            };
+            if sender.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::metrics::Metrics;
+use crate::models::Event;
+
+/// A subsystem that reacts to committed events (metrics, webhooks,
+/// projections, cache invalidation, ...). Each consumer runs on its own task
+/// and sees events in commit order.
+#[async_trait]
+pub trait BusConsumer: Send + Sync + 'static {
+    fn name(&self) -> &str;
+
+    async fn handle(&self, event: &Event);
+}
+
+/// What `publish` does when a consumer's queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait for the consumer, slowing down the commit path. For consumers
+    /// that must see every event.
+    Block,
+    /// Drop the event for this consumer and count it. For best-effort
+    /// consumers that must never hold up appends.
+    Drop,
+}
+
+struct ConsumerHandle {
+    name: String,
+    sender: mpsc::Sender<Arc<Event>>,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+/// In-process fan-out of committed events to registered consumers, each
+/// behind its own bounded queue.
+#[derive(Clone)]
+pub struct EventBus {
+    consumers: Arc<RwLock<Vec<Arc<ConsumerHandle>>>>,
+    metrics: Metrics,
+    captured: bool,
+}
+
+impl EventBus {
+    pub fn new(metrics: Metrics) -> Self {
+        Self {
+            consumers: Arc::new(RwLock::new(Vec::new())),
+            metrics,
+            captured: false,
+        }
+    }
+
+    /// Events reach the bus from change data capture instead of the write
+    /// paths, whose `publish` calls become no-ops so nothing is seen twice.
+    pub fn captured(mut self) -> Self {
+        self.captured = true;
+        self
+    }
+
+    /// Registers a consumer and starts its task.
+    pub fn spawn<C: BusConsumer>(&self, consumer: C, capacity: usize, overflow: Overflow) {
+        let (sender, mut receiver) = mpsc::channel::<Arc<Event>>(capacity);
+        let name = consumer.name().to_string();
+        let lag = self.metrics.bus_consumer_lag.with_label_values(&[&name]);
+
+        self.consumers.write().unwrap().push(Arc::new(ConsumerHandle {
+            name: name.clone(),
+            sender,
+            capacity,
+            overflow,
+        }));
+
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                consumer.handle(&event).await;
+                lag.set(receiver.len() as i64);
+            }
+            info!("Event bus consumer {} stopped", name);
+        });
+    }
+
+    /// Hands a committed event to every consumer, unless events are captured.
+    pub async fn publish(&self, event: Event) {
+        if !self.captured {
+            self.deliver(event).await;
+        }
+    }
+
+    /// Hands an event read by change data capture to every consumer.
+    pub async fn publish_captured(&self, event: Event) {
+        self.deliver(event).await;
+    }
+
+    async fn deliver(&self, event: Event) {
+        let event = Arc::new(event);
+        let consumers = self.consumers.read().unwrap().clone();
+        self.metrics.bus_events_published.inc();
+
+        for consumer in consumers {
+            let result = match consumer.overflow {
+                Overflow::Block => consumer.sender.send(event.clone()).await.map_err(|_| ()),
+                Overflow::Drop => consumer.sender.try_send(event.clone()).map_err(|_| ()),
+            };
+
+            if result.is_err() {
+                self.metrics
+                    .bus_events_dropped
+                    .with_label_values(&[&consumer.name])
+                    .inc();
+                warn!("Event bus consumer {} dropped {} v{}", consumer.name, event.stream_id, event.version);
+            }
+
+            let queued = consumer.capacity - consumer.sender.capacity();
+            self.metrics
+                .bus_consumer_lag
+                .with_label_values(&[&consumer.name])
+                .set(queued as i64);
+        }
+    }
+}
+
+/// Per-event counters, global and per partition, that used to be bumped
+/// inline in the append handler.
+pub struct MetricsConsumer {
+    metrics: Metrics,
+}
+
+impl MetricsConsumer {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
+
+#[async_trait]
+impl BusConsumer for MetricsConsumer {
+    fn name(&self) -> &str {
+        "metrics"
+    }
+
+    async fn handle(&self, event: &Event) {
+        self.metrics.events_stored.inc();
+        self.metrics.record_append(event);
+    }
+}
@@ -0,0 +1,213 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::bus::EventBus;
+use crate::error::{AppError, Result};
+use crate::jobs::{self, Job};
+use crate::models::Event;
+use crate::storage;
+
+/// Logical decoding output plugin. wal2json ships with most managed Postgres
+/// offerings and, unlike pgoutput, can be read over a normal connection.
+const OUTPUT_PLUGIN: &str = "wal2json";
+/// SQLSTATE raised when the replication slot already exists.
+const DUPLICATE_OBJECT: &str = "42710";
+
+/// One change as emitted by wal2json with `format-version` 2.
+#[derive(Debug, Deserialize)]
+struct Wal2JsonChange {
+    action: String,
+    #[serde(default)]
+    table: String,
+    #[serde(default)]
+    columns: Vec<Column>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Column {
+    name: String,
+    value: Value,
+}
+
+/// Reads committed inserts into the events table from a logical replication
+/// slot and publishes them on the event bus. The slot holds WAL until a batch
+/// is confirmed, so events written outside the API (bulk SQL, restores, other
+/// services) are published too and nothing is missed across restarts; a
+/// crash between publishing and confirming replays the batch, so delivery is
+/// at least once.
+pub struct CdcReader {
+    pool: PgPool,
+    slot: String,
+    batch_size: i64,
+}
+
+impl CdcReader {
+    /// Connects and creates the slot if it doesn't exist yet. Needs
+    /// `wal_level = logical` and a role with the REPLICATION attribute.
+    pub async fn connect(database_url: &str, slot: &str, batch_size: i64) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(database_url)
+            .await
+            .map_err(|e| AppError::Database(format!("Failed to open CDC connection: {}", e)))?;
+
+        match sqlx::query("SELECT pg_create_logical_replication_slot($1, $2)")
+            .bind(slot)
+            .bind(OUTPUT_PLUGIN)
+            .execute(&pool)
+            .await
+        {
+            Ok(_) => info!("Created replication slot {}", slot),
+            Err(sqlx::Error::Database(db)) if db.code().as_deref() == Some(DUPLICATE_OBJECT) => {}
+            Err(e) => {
+                return Err(AppError::Database(format!(
+                    "Failed to create replication slot {}: {}",
+                    slot, e
+                )))
+            }
+        }
+
+        Ok(Self {
+            pool,
+            slot: slot.to_string(),
+            batch_size,
+        })
+    }
+
+    /// Publishes one batch of captured events, then confirms it. Returns how
+    /// many changes were read and how many events were published.
+    async fn poll(&self, bus: &EventBus) -> Result<(usize, usize)> {
+        // Peek rather than get, so a crash before confirming replays the batch.
+        // Decoding stops at a transaction boundary, so the last row is a commit.
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT lsn::text, data FROM pg_logical_slot_peek_changes($1, NULL, $2, 'format-version', '2')",
+        )
+        .bind(&self.slot)
+        .bind(self.batch_size as i32)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::Database(format!("Failed to read replication slot {}: {}", self.slot, e)))?;
+
+        let Some((last_lsn, _)) = rows.last() else {
+            return Ok((0, 0));
+        };
+
+        let mut published = 0;
+        for (lsn, data) in &rows {
+            let change: Wal2JsonChange = serde_json::from_str(data)
+                .map_err(|e| AppError::Internal(format!("Malformed change at {}: {}", lsn, e)))?;
+            // Inserts land in the partitions, never the parent; the archive is left out
+            let events_table = change.table == "events" || change.table.starts_with("events_p_");
+            if change.action != "I" || !events_table {
+                continue;
+            }
+
+            let event = event_from_columns(change.columns)
+                .map_err(|e| AppError::Internal(format!("Unreadable event at {}: {}", lsn, e)))?;
+            bus.publish_captured(event).await;
+            published += 1;
+        }
+
+        sqlx::query("SELECT pg_replication_slot_advance($1, $2::pg_lsn)")
+            .bind(&self.slot)
+            .bind(last_lsn)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AppError::Database(format!("Failed to confirm replication slot {}: {}", self.slot, e)))?;
+
+        Ok((rows.len(), published))
+    }
+}
+
+/// Rebuilds an event from a captured row. wal2json gives each column's text
+/// representation, so JSON, bytea and timestamps need parsing back.
+fn event_from_columns(columns: Vec<Column>) -> std::result::Result<Event, String> {
+    let mut row: Map<String, Value> = columns.into_iter().map(|c| (c.name, c.value)).collect();
+    let mut take = |name: &str| row.remove(name).unwrap_or(Value::Null);
+
+    let text = |value: Value, name: &str| match value {
+        Value::String(s) => Ok(s),
+        other => Err(format!("{} is {}, expected text", name, other)),
+    };
+    let int = |value: Value, name: &str| {
+        value
+            .as_i64()
+            .ok_or_else(|| format!("{} is {}, expected an integer", name, value))
+    };
+    let json = |value: Value| match value {
+        Value::String(s) => serde_json::from_str(&s).map_err(|e| e.to_string()),
+        other => Ok(other),
+    };
+
+    let id = text(take("id"), "id")?;
+    let mut payload = match take("payload") {
+        Value::Null => None,
+        value => Some(decode_bytea(&text(value, "payload")?)?),
+    };
+    // A large JSON body may be stored compressed in payload
+    let data = match take("data_compression") {
+        Value::Null => json(take("data"))?,
+        value => {
+            let compression = text(value, "data_compression")?.parse()?;
+            let bytes = payload.take().ok_or("data_compression is set without a payload")?;
+            storage::decompress_data(compression, &bytes).map_err(|e| e.to_string())?
+        }
+    };
+    let metadata = match take("metadata") {
+        Value::Null => None,
+        value => Some(json(value)?),
+    };
+    let created_at = text(take("created_at"), "created_at")?;
+    let checksum = match take("checksum") {
+        Value::Null => None,
+        value => Some(text(value, "checksum")?),
+    };
+    let chain_hash = match take("chain_hash") {
+        Value::Null => None,
+        value => Some(text(value, "chain_hash")?),
+    };
+
+    Ok(Event {
+        id: id.parse().map_err(|e| format!("id {}: {}", id, e))?,
+        stream_id: text(take("stream_id"), "stream_id")?,
+        event_type: text(take("event_type"), "event_type")?,
+        data,
+        payload,
+        content_type: text(take("content_type"), "content_type")?,
+        metadata,
+        version: int(take("version"), "version")?,
+        position: int(take("position"), "position")?,
+        created_at: DateTime::parse_from_str(&created_at, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .map(|at| at.with_timezone(&Utc))
+            .map_err(|e| format!("created_at {}: {}", created_at, e))?,
+        checksum,
+        chain_hash,
+        link: None,
+    })
+}
+
+/// Decodes bytea in Postgres' hex output format, `\x0a1b...`.
+fn decode_bytea(text: &str) -> std::result::Result<Vec<u8>, String> {
+    let encoded = text.strip_prefix("\\x").ok_or("payload is not hex-encoded bytea")?;
+    hex::decode(encoded).map_err(|e| format!("payload: {}", e))
+}
+
+// Background task: Drain the replication slot onto the event bus
+pub async fn capture(job: Arc<Job>, reader: Arc<CdcReader>, bus: EventBus) {
+    jobs::run_periodically("cdc_reader", job, || async {
+        let mut published = 0;
+        loop {
+            let (changes, events) = reader.poll(&bus).await?;
+            published += events;
+            if (changes as i64) < reader.batch_size {
+                break;
+            }
+        }
+        Ok(format!("Published {} captured events", published))
+    })
+    .await
+}
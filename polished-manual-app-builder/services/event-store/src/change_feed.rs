@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tracing::{info, warn};
+
+use crate::error::{AppError, Result};
+
+/// Changes buffered for subscribers that fall behind.
+const CHANGE_BUFFER: usize = 4096;
+
+/// A committed append, as announced by the Postgres backend with NOTIFY.
+/// Only the coordinates travel; subscribers read the event itself if they
+/// need it, which keeps payloads far under the 8000 byte NOTIFY limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub partition_key: String,
+    pub stream_id: String,
+    pub version: i64,
+    pub position: i64,
+}
+
+/// The NOTIFY channel for a partition: a readable prefix plus an FNV-1a hash
+/// of the key, keeping it under Postgres' 63 byte identifier limit.
+pub fn channel(partition_key: &str) -> String {
+    let readable: String = partition_key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .take(24)
+        .collect();
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in partition_key.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    format!("events_{}_{:016x}", readable, hash)
+}
+
+enum Command {
+    Listen(String, oneshot::Sender<()>),
+    Unlisten(String),
+}
+
+/// Appends committed by any instance sharing the database, delivered over
+/// one LISTEN connection. Partitions are listened to while at least one
+/// subscription needs them.
+#[derive(Clone)]
+pub struct ChangeFeed {
+    commands: mpsc::UnboundedSender<Command>,
+    changes: broadcast::Sender<Change>,
+}
+
+impl ChangeFeed {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let listener = PgListener::connect(database_url)
+            .await
+            .map_err(|e| AppError::Database(format!("Failed to open change feed connection: {}", e)))?;
+
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let (changes, _) = broadcast::channel(CHANGE_BUFFER);
+        tokio::spawn(run(listener, receiver, changes.clone()));
+
+        info!("Change feed connected");
+        Ok(Self { commands, changes })
+    }
+
+    /// Subscribes to a partition's changes. Returns once the LISTEN is in
+    /// effect, so a read made afterwards can't miss a change.
+    pub async fn subscribe(&self, partition_key: &str) -> ChangeSubscription {
+        let receiver = self.changes.subscribe();
+        let (ack, acked) = oneshot::channel();
+        if self.commands.send(Command::Listen(partition_key.to_string(), ack)).is_ok() {
+            let _ = acked.await;
+        }
+
+        ChangeSubscription {
+            partition_key: partition_key.to_string(),
+            receiver,
+            commands: self.commands.clone(),
+        }
+    }
+}
+
+pub struct ChangeSubscription {
+    partition_key: String,
+    receiver: broadcast::Receiver<Change>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl ChangeSubscription {
+    /// The next change on the subscribed partition, or `None` once the feed
+    /// has stopped. Changes a slow subscriber missed are skipped.
+    pub async fn recv(&mut self) -> Option<Change> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(change) if change.partition_key == self.partition_key => return Some(change),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Change feed subscriber for {} skipped {} changes", self.partition_key, missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for ChangeSubscription {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Unlisten(std::mem::take(&mut self.partition_key)));
+    }
+}
+
+async fn run(
+    mut listener: PgListener,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    changes: broadcast::Sender<Change>,
+) {
+    // Subscriptions per partition
+    let mut listening: HashMap<String, usize> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Listen(partition_key, ack)) => {
+                    let count = listening.entry(partition_key.clone()).or_insert(0);
+                    *count += 1;
+                    if *count == 1 {
+                        if let Err(e) = listener.listen(&channel(&partition_key)).await {
+                            warn!("Failed to listen for changes on {}: {}", partition_key, e);
+                        }
+                    }
+                    let _ = ack.send(());
+                }
+                Some(Command::Unlisten(partition_key)) => {
+                    let Some(count) = listening.get_mut(&partition_key) else {
+                        continue;
+                    };
+                    *count -= 1;
+                    if *count == 0 {
+                        listening.remove(&partition_key);
+                        if let Err(e) = listener.unlisten(&channel(&partition_key)).await {
+                            warn!("Failed to stop listening for changes on {}: {}", partition_key, e);
+                        }
+                    }
+                }
+                None => break,
+            },
+            notification = listener.recv() => match notification {
+                Ok(notification) => match serde_json::from_str::<Change>(notification.payload()) {
+                    Ok(change) => {
+                        let _ = changes.send(change);
+                    }
+                    Err(e) => warn!("Ignoring malformed change on {}: {}", notification.channel(), e),
+                },
+                Err(e) => {
+                    // The listener reconnects and re-listens on the next recv
+                    warn!("Change feed connection lost: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            },
+        }
+    }
+
+    info!("Change feed stopped");
+}
@@ -0,0 +1,258 @@
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderName, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::auth;
+use crate::error::{AppError, Result};
+use crate::storage::{ChaosStorage, EventStorage};
+use crate::AppState;
+
+/// Requests never faulted, so faults can always be turned off again and
+/// probes keep answering.
+const EXEMPT_PATHS: &[&str] = &["/admin/chaos", "/health", "/health/live", "/health/ready", "/metrics"];
+
+/// Per-request overrides of the configured faults. `X-Chaos-Layer` picks
+/// the faults they replace: `http` (the default) or `storage`.
+static LAYER_HEADER: HeaderName = HeaderName::from_static("x-chaos-layer");
+static LATENCY_HEADER: HeaderName = HeaderName::from_static("x-chaos-latency-ms");
+static JITTER_HEADER: HeaderName = HeaderName::from_static("x-chaos-jitter-ms");
+static DROP_RATE_HEADER: HeaderName = HeaderName::from_static("x-chaos-drop-rate");
+static ERROR_RATE_HEADER: HeaderName = HeaderName::from_static("x-chaos-error-rate");
+static CONFLICT_RATE_HEADER: HeaderName = HeaderName::from_static("x-chaos-conflict-rate");
+
+tokio::task_local! {
+    /// Storage faults of the request being handled.
+    static REQUEST_STORAGE_FAULTS: Faults;
+}
+
+/// Faults injected into one layer. Rates are the chance, from 0 to 1, that
+/// a call fails that way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Faults {
+    /// Delay added to every call.
+    pub latency_ms: u64,
+    /// Up to this much more delay, at random.
+    pub jitter_ms: u64,
+    /// Requests are handled but their response is cut off, so the client
+    /// can't tell whether a write took effect; storage calls fail as on a
+    /// lost database connection.
+    pub drop_rate: f64,
+    /// Calls fail with a server error without taking effect.
+    pub error_rate: f64,
+    /// Writes are refused with a 409 without taking effect.
+    pub conflict_rate: f64,
+}
+
+impl Faults {
+    fn validate(&self, layer: &str) -> Result<()> {
+        for (name, rate) in [
+            ("drop_rate", self.drop_rate),
+            ("error_rate", self.error_rate),
+            ("conflict_rate", self.conflict_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(AppError::BadRequest(format!(
+                    "{}.{} must be between 0 and 1",
+                    layer, name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits out the latency, then draws the call's fault, if any.
+    /// Conflicts are only drawn for calls that can conflict.
+    async fn draw(&self, conflicts: bool) -> Option<Fault> {
+        let jitter = match self.jitter_ms {
+            0 => 0,
+            jitter_ms => (Uuid::new_v4().as_u128() % (jitter_ms as u128 + 1)) as u64,
+        };
+        let latency = self.latency_ms + jitter;
+        if latency > 0 {
+            tokio::time::sleep(Duration::from_millis(latency)).await;
+        }
+
+        if roll(self.drop_rate) {
+            Some(Fault::Drop)
+        } else if roll(self.error_rate) {
+            Some(Fault::Error)
+        } else if conflicts && roll(self.conflict_rate) {
+            Some(Fault::Conflict)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Drop,
+    Error,
+    Conflict,
+}
+
+/// Whether a call with this chance of failing fails. Draws on the UUID
+/// generator's randomness, like the retry jitter.
+fn roll(rate: f64) -> bool {
+    rate > 0.0 && (Uuid::new_v4().as_u128() % 1_000_000) as f64 / 1_000_000.0 < rate
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosSettings {
+    /// Faults of requests, before they reach the handlers.
+    pub http: Faults,
+    /// Faults of storage calls, from requests and background tasks alike.
+    pub storage: Faults,
+}
+
+/// Fault injection for resilience testing, in builds with the `chaos`
+/// feature only. Nothing is injected until faults are set through
+/// `PUT /admin/chaos`, or for one request through its `X-Chaos-*`
+/// headers, so downstream teams can check their retries against latency,
+/// dropped connections, server errors and conflicts.
+#[derive(Debug, Default)]
+pub struct Chaos {
+    settings: RwLock<ChaosSettings>,
+}
+
+impl Chaos {
+    /// Puts `storage` behind the faults set here.
+    pub fn wrap(storage: Arc<dyn EventStorage>) -> (Arc<Self>, Arc<dyn EventStorage>) {
+        warn!("Built with the chaos feature: faults can be injected into requests and storage calls");
+        let chaos = Arc::new(Self::default());
+        let storage = Arc::new(ChaosStorage::new(storage, chaos.clone()));
+        (chaos, storage)
+    }
+
+    fn settings(&self) -> ChaosSettings {
+        *self.settings.read().unwrap()
+    }
+
+    /// The fault of a storage call, made for a request or not.
+    pub async fn storage_fault(&self, conflicts: bool) -> Option<Fault> {
+        let faults = REQUEST_STORAGE_FAULTS
+            .try_with(|faults| *faults)
+            .unwrap_or_else(|_| self.settings().storage);
+        faults.draw(conflicts).await
+    }
+}
+
+fn header_value<T: FromStr>(headers: &HeaderMap, name: &HeaderName) -> Result<Option<T>> {
+    let Some(value) = headers.get(name) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .map(Some)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid {} header", name)))
+}
+
+/// The configured faults, with those of the layer a request's headers
+/// name overridden by their values.
+fn request_faults(headers: &HeaderMap, mut settings: ChaosSettings) -> Result<ChaosSettings> {
+    let (layer, faults) = match headers.get(&LAYER_HEADER).map(|value| value.to_str().unwrap_or_default()) {
+        None | Some("http") => ("http", &mut settings.http),
+        Some("storage") => ("storage", &mut settings.storage),
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid {} header '{}'; expected http or storage",
+                LAYER_HEADER, other
+            )))
+        }
+    };
+    if let Some(latency_ms) = header_value(headers, &LATENCY_HEADER)? {
+        faults.latency_ms = latency_ms;
+    }
+    if let Some(jitter_ms) = header_value(headers, &JITTER_HEADER)? {
+        faults.jitter_ms = jitter_ms;
+    }
+    if let Some(rate) = header_value(headers, &DROP_RATE_HEADER)? {
+        faults.drop_rate = rate;
+    }
+    if let Some(rate) = header_value(headers, &ERROR_RATE_HEADER)? {
+        faults.error_rate = rate;
+    }
+    if let Some(rate) = header_value(headers, &CONFLICT_RATE_HEADER)? {
+        faults.conflict_rate = rate;
+    }
+    faults.validate(layer)?;
+    Ok(settings)
+}
+
+/// Keeps the response head but fails its body, so the connection is closed
+/// before the client has the whole response.
+fn cut_off(response: Response) -> Response {
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let body = futures::stream::once(async {
+        Err::<Bytes, _>(io::Error::new(io::ErrorKind::ConnectionReset, "Injected connection drop"))
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+/// Injects the configured faults into requests, and scopes the storage
+/// faults their headers set to the storage calls made while handling them.
+pub async fn inject(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let settings = match request_faults(request.headers(), state.chaos.settings()) {
+        Ok(settings) => settings,
+        Err(e) => return e.into_response(),
+    };
+
+    let writing = !matches!(*request.method(), Method::GET | Method::HEAD);
+    match settings.http.draw(writing).await {
+        Some(Fault::Error) => AppError::Internal("Injected server error".to_string()).into_response(),
+        Some(Fault::Conflict) => AppError::Conflict("Injected conflict".to_string()).into_response(),
+        Some(Fault::Drop) => cut_off(REQUEST_STORAGE_FAULTS.scope(settings.storage, next.run(request)).await),
+        None => REQUEST_STORAGE_FAULTS.scope(settings.storage, next.run(request)).await,
+    }
+}
+
+/// GET /admin/chaos — the faults being injected.
+pub async fn get_settings(State(state): State<AppState>) -> Json<ChaosSettings> {
+    Json(state.chaos.settings())
+}
+
+/// PUT /admin/chaos — replaces the faults injected into every request and
+/// storage call.
+pub async fn set_settings(
+    State(state): State<AppState>,
+    Json(settings): Json<ChaosSettings>,
+) -> Result<Json<ChaosSettings>> {
+    settings.http.validate("http")?;
+    settings.storage.validate("storage")?;
+    *state.chaos.settings.write().unwrap() = settings;
+    warn!("Injecting faults: {:?}", settings);
+    Ok(Json(settings))
+}
+
+/// DELETE /admin/chaos — stops injecting faults.
+pub async fn clear_settings(State(state): State<AppState>) -> StatusCode {
+    *state.chaos.settings.write().unwrap() = ChaosSettings::default();
+    warn!("Fault injection cleared");
+    StatusCode::NO_CONTENT
+}
+
+/// The chaos admin API, for the admin role only.
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/chaos", get(get_settings).put(set_settings).delete(clear_settings))
+        .route_layer(middleware::from_fn(auth::require_admin))
+}
@@ -0,0 +1,177 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::{AppError, ErrorCode, Result};
+use crate::error_capture::CapturedError;
+use crate::metrics::Metrics;
+use crate::{storage, ui, AppState};
+
+/// Requests the breaker never refuses: probes and diagnostics must keep
+/// answering while the database is down.
+const EXEMPT_PATHS: &[&str] = &["/health", "/health/live", "/health/ready", "/metrics", "/errors/catalog"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    /// Open long enough that one request is let through to test the database.
+    HalfOpen,
+}
+
+impl BreakerState {
+    fn gauge(self) -> i64 {
+        match self {
+            BreakerState::Closed => 0,
+            BreakerState::HalfOpen => 1,
+            BreakerState::Open => 2,
+        }
+    }
+}
+
+struct Breaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+}
+
+/// Stops sending requests to an unhealthy database. After
+/// `circuit_breaker_failure_threshold` database failures in a row, requests
+/// are refused with 503 for `circuit_breaker_open_seconds` instead of
+/// queueing on the pool; then one request is let through, and its outcome
+/// closes or reopens the breaker. The readiness probe's ping counts too, so
+/// a recovered database closes the breaker without waiting for traffic.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_for: Duration,
+    breaker: Mutex<Breaker>,
+    metrics: Metrics,
+}
+
+impl CircuitBreaker {
+    pub fn from_config(config: &Config, metrics: Metrics) -> Option<Self> {
+        if config.circuit_breaker_failure_threshold == 0 || !storage::uses_postgres(config) {
+            return None;
+        }
+
+        info!(
+            "Database circuit breaker enabled: opens after {} failures, for {}s",
+            config.circuit_breaker_failure_threshold, config.circuit_breaker_open_seconds
+        );
+        metrics.circuit_breaker_state.set(BreakerState::Closed.gauge());
+        Some(Self {
+            failure_threshold: config.circuit_breaker_failure_threshold,
+            open_for: Duration::from_secs(config.circuit_breaker_open_seconds),
+            breaker: Mutex::new(Breaker {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+            }),
+            metrics,
+        })
+    }
+
+    fn set_state(&self, breaker: &mut Breaker, state: BreakerState) {
+        breaker.state = state;
+        self.metrics.circuit_breaker_state.set(state.gauge());
+    }
+
+    /// Lets a request through, or refuses it while the breaker is open.
+    pub fn check(&self) -> Result<()> {
+        let mut breaker = self.breaker.lock().unwrap();
+        let wait = match breaker.state {
+            BreakerState::Closed => return Ok(()),
+            BreakerState::Open => {
+                let elapsed = breaker.opened_at.elapsed();
+                if elapsed >= self.open_for {
+                    self.set_state(&mut breaker, BreakerState::HalfOpen);
+                    return Ok(());
+                }
+                self.open_for - elapsed
+            }
+            // The probe is still out
+            BreakerState::HalfOpen => Duration::from_secs(1),
+        };
+
+        self.metrics.circuit_breaker_rejections.inc();
+        let retry_after = wait.as_secs().max(1);
+        Err(AppError::DatabaseUnavailable(
+            format!("The database is unhealthy; retry in {}s", retry_after),
+            retry_after,
+        ))
+    }
+
+    pub fn record_success(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.consecutive_failures = 0;
+        if breaker.state != BreakerState::Closed {
+            self.set_state(&mut breaker, BreakerState::Closed);
+            info!("Database circuit breaker closed");
+        }
+    }
+
+    pub fn record_failure(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.consecutive_failures += 1;
+        let trips = match breaker.state {
+            BreakerState::Closed => breaker.consecutive_failures >= self.failure_threshold,
+            BreakerState::HalfOpen => true,
+            // Keep it open while failures continue, e.g. from the readiness probe
+            BreakerState::Open => {
+                breaker.opened_at = Instant::now();
+                false
+            }
+        };
+        if trips {
+            breaker.opened_at = Instant::now();
+            self.set_state(&mut breaker, BreakerState::Open);
+            self.metrics.circuit_breaker_trips.inc();
+            warn!(
+                "Database circuit breaker opened after {} consecutive failures; refusing requests for {}s",
+                breaker.consecutive_failures,
+                self.open_for.as_secs()
+            );
+        }
+    }
+
+    /// The current state, and when open, the seconds until a request is let
+    /// through again.
+    pub fn status(&self) -> (BreakerState, Option<u64>) {
+        let breaker = self.breaker.lock().unwrap();
+        let retry_after = (breaker.state == BreakerState::Open)
+            .then(|| self.open_for.saturating_sub(breaker.opened_at.elapsed()).as_secs());
+        (breaker.state, retry_after)
+    }
+}
+
+/// Refuses requests while the breaker is open, and feeds it the outcome of
+/// the ones let through: a database error response counts as a failure,
+/// anything else as a success.
+pub async fn guard(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(breaker) = &state.circuit_breaker else {
+        return next.run(request).await;
+    };
+    if EXEMPT_PATHS.contains(&request.uri().path()) || ui::is_ui_path(request.uri().path()) {
+        return next.run(request).await;
+    }
+    if let Err(e) = breaker.check() {
+        return e.into_response();
+    }
+
+    let response = next.run(request).await;
+    match response.extensions().get::<CapturedError>() {
+        Some(error) if matches!(error.code, ErrorCode::DatabaseError | ErrorCode::SqlError) => {
+            breaker.record_failure()
+        }
+        _ => breaker.record_success(),
+    }
+    response
+}
@@ -0,0 +1,389 @@
+use clap::{Parser, Subcommand};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::cold_storage::ColdStore;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::export::{check_next_version, IMPORT_BATCH_SIZE};
+use crate::integrity::{self, ImportSealer};
+use crate::metrics::Metrics;
+use crate::models::Event;
+use crate::storage::{self, EventStorage, MigrationStatus};
+use crate::stream_ids::StreamIdPolicy;
+use crate::archive_once;
+
+/// Events buffered between the storage scan and the export file.
+const EXPORT_BUFFER: usize = 1_024;
+/// Events read per page while verifying.
+const VERIFY_PAGE_SIZE: i64 = 1_000;
+/// Problems printed by `verify` before it only counts them.
+const MAX_REPORTED_PROBLEMS: usize = 100;
+/// How long `--check-config` waits for the database.
+const CHECK_DATABASE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Parser)]
+#[command(name = "event-store", version, about = "Event store server and maintenance commands")]
+pub struct Cli {
+    /// Use the volatile in-process backend instead of DATABASE_URL.
+    #[arg(long, global = true)]
+    pub memory: bool,
+
+    /// Same as the `migrate` subcommand.
+    #[arg(long, hide = true)]
+    pub migrate_only: bool,
+
+    /// Validate the configuration, database connectivity and migration
+    /// status, print a JSON report and exit; non-zero if a check failed.
+    #[arg(long)]
+    pub check_config: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default).
+    Serve,
+    /// Apply pending schema migrations, then exit.
+    Migrate,
+    /// Write a stream as NDJSON, one event per line in version order.
+    Export {
+        stream_id: String,
+        /// File to write; stdout when omitted.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[arg(long, default_value_t = 1)]
+        from_version: i64,
+    },
+    /// Append an NDJSON export to a stream, keeping ids, versions and timestamps.
+    Import {
+        stream_id: String,
+        /// File to read; stdin when omitted.
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// Check every stream for version gaps, position order and body consistency.
+    Verify,
+    /// Run one archival pass now: tier out and archive events older than
+    /// ARCHIVE_DAYS on snapshotted streams.
+    Compact {
+        /// Overrides ARCHIVE_DAYS for this run.
+        #[arg(long)]
+        days: Option<i64>,
+    },
+}
+
+impl Command {
+    /// Whether the command logs to stderr, keeping stdout for its output.
+    pub fn is_tool(&self) -> bool {
+        !matches!(self, Command::Serve)
+    }
+}
+
+/// Runs a maintenance command against `storage`. Schema migrations are not
+/// applied; run `migrate` first when upgrading.
+pub async fn run(command: Command, storage: &dyn EventStorage, config: &Config) -> Result<()> {
+    match command {
+        Command::Serve | Command::Migrate => Ok(()),
+        Command::Export {
+            stream_id,
+            output,
+            from_version,
+        } => {
+            let cold_store = ColdStore::from_config(config).await;
+            match output {
+                Some(path) => {
+                    let file = tokio::fs::File::create(&path)
+                        .await
+                        .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", path.display(), e)))?;
+                    export(storage, cold_store.as_ref(), &stream_id, from_version, file).await
+                }
+                None => export(storage, cold_store.as_ref(), &stream_id, from_version, tokio::io::stdout()).await,
+            }
+        }
+        Command::Import { stream_id, input } => {
+            let stream_ids = StreamIdPolicy::from_config(config)?;
+            match input {
+                Some(path) => {
+                    let file = tokio::fs::File::open(&path)
+                        .await
+                        .map_err(|e| AppError::Internal(format!("Failed to open {}: {}", path.display(), e)))?;
+                    import(storage, &stream_ids, &stream_id, file).await
+                }
+                None => import(storage, &stream_ids, &stream_id, tokio::io::stdin()).await,
+            }
+        }
+        Command::Verify => verify(storage).await,
+        Command::Compact { days } => {
+            let cold_store = ColdStore::from_config(config).await;
+            let days = days.unwrap_or(config.archive_days);
+            let archived = archive_once(storage, cold_store.as_ref(), &Metrics::new(), days).await?;
+            info!("Compaction archived {} events older than {} days", archived, days);
+            Ok(())
+        }
+    }
+}
+
+fn io_error(e: std::io::Error) -> AppError {
+    AppError::Internal(format!("I/O error: {}", e))
+}
+
+async fn write_event<W: AsyncWrite + Unpin>(out: &mut W, event: &Event) -> Result<()> {
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    out.write_all(&line).await.map_err(io_error)
+}
+
+async fn export<W: AsyncWrite + Unpin>(
+    storage: &dyn EventStorage,
+    cold_store: Option<&ColdStore>,
+    stream_id: &str,
+    from_version: i64,
+    out: W,
+) -> Result<()> {
+    let head = storage.stream_version(stream_id).await?;
+    if head == 0 {
+        return Err(AppError::NotFound(format!("Stream {} not found", stream_id)));
+    }
+
+    let mut out = BufWriter::new(out);
+    let mut from_version = from_version.max(1);
+    let mut exported = 0;
+
+    // Archived and tiered events are always a prefix of the stream; write them first
+    let mut older = BTreeMap::new();
+    for event in storage.read_archived(stream_id, from_version, head).await? {
+        older.insert(event.version, event);
+    }
+    if let Some(cold) = cold_store {
+        for event in cold.read_range(stream_id, from_version, head).await? {
+            older.entry(event.version).or_insert(event);
+        }
+    }
+    for event in older.into_values() {
+        from_version = event.version + 1;
+        write_event(&mut out, &event).await?;
+        exported += 1;
+    }
+
+    let (sink, mut events) = mpsc::channel(EXPORT_BUFFER);
+    let scan = storage.scan_stream(stream_id, from_version, head, sink);
+    let write = async {
+        while let Some(event) = events.recv().await {
+            write_event(&mut out, &event).await?;
+            exported += 1;
+        }
+        Ok::<_, AppError>(())
+    };
+    let (scanned, written) = tokio::join!(scan, write);
+    scanned?;
+    written?;
+
+    out.flush().await.map_err(io_error)?;
+    info!("Exported {} events from {}", exported, stream_id);
+    Ok(())
+}
+
+async fn import<R: tokio::io::AsyncRead + Unpin>(
+    storage: &dyn EventStorage,
+    stream_ids: &StreamIdPolicy,
+    stream_id: &str,
+    input: R,
+) -> Result<()> {
+    stream_ids.validate(stream_id)?;
+
+    let mut lines = BufReader::new(input).lines();
+    let mut batch: Vec<Event> = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    let mut line_number = 0;
+    let mut last_version = None;
+    let mut imported = 0;
+    let mut sealer = ImportSealer::default();
+
+    while let Some(line) = lines.next_line().await.map_err(io_error)? {
+        line_number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut event: Event = serde_json::from_str(&line)
+            .map_err(|e| AppError::BadRequest(format!("Invalid event on line {}: {}", line_number, e)))?;
+        check_next_version(last_version, &event, line_number)?;
+        last_version = Some(event.version);
+
+        event.stream_id = stream_id.to_string();
+        sealer.seal(storage, &mut event).await?;
+        batch.push(event);
+        if batch.len() >= IMPORT_BATCH_SIZE {
+            imported += storage.import_stream(stream_id, std::mem::take(&mut batch)).await?.len();
+        }
+    }
+    if !batch.is_empty() {
+        imported += storage.import_stream(stream_id, batch).await?.len();
+    }
+
+    // Imported events bypass a running server's bus, so its subscribers and
+    // read cache don't see them until they next read from storage
+    let version = storage.stream_version(stream_id).await?;
+    info!("Imported {} events into {} (now at version {})", imported, stream_id, version);
+    Ok(())
+}
+
+/// Walks every event in position order. Streams may start above version 1
+/// where a prefix has been archived, but must be contiguous from there and
+/// end at the stream's reported head. Bodies must match their checksums.
+async fn verify(storage: &dyn EventStorage) -> Result<()> {
+    let mut heads: HashMap<String, i64> = HashMap::new();
+    let mut problems = Vec::new();
+    let mut problem_count = 0;
+    let mut report = |problem: String| {
+        problem_count += 1;
+        if problems.len() < MAX_REPORTED_PROBLEMS {
+            problems.push(problem);
+        }
+    };
+
+    let mut position = 0;
+    let mut checked = 0u64;
+    loop {
+        let page = storage.read_all(position, VERIFY_PAGE_SIZE).await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        let next_position = last.position;
+
+        for event in &page {
+            if event.position <= position {
+                report(format!("Event {} has position {} after {}", event.id, event.position, position));
+            }
+            position = event.position;
+
+            if let Some(head) = heads.get(&event.stream_id) {
+                if event.version != head + 1 {
+                    report(format!(
+                        "Stream {} jumps from version {} to {}",
+                        event.stream_id, head, event.version
+                    ));
+                }
+            }
+            heads.insert(event.stream_id.clone(), event.version);
+
+            let json = crate::is_json_content_type(&event.content_type);
+            if json == event.payload.is_some() {
+                report(format!(
+                    "Event {} ({} v{}) has content type {} but {} payload",
+                    event.id,
+                    event.stream_id,
+                    event.version,
+                    event.content_type,
+                    if json { "a binary" } else { "no" }
+                ));
+            }
+            if let Some(expected) = &event.checksum {
+                if *expected != integrity::checksum(Some(&event.data), event.payload.as_deref()) {
+                    report(format!(
+                        "Event {} ({} v{}) does not match its checksum",
+                        event.id, event.stream_id, event.version
+                    ));
+                }
+            }
+            checked += 1;
+        }
+        position = next_position;
+    }
+
+    for (stream_id, last_version) in &heads {
+        let head = storage.stream_version(stream_id).await?;
+        if head != *last_version {
+            report(format!("Stream {} reports head {} but its last event is v{}", stream_id, head, last_version));
+        }
+    }
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if problem_count > problems.len() {
+        println!("... and {} more", problem_count - problems.len());
+    }
+    println!("Checked {} events in {} streams: {} problems", checked, heads.len(), problem_count);
+
+    if problem_count > 0 {
+        return Err(AppError::Internal(format!("Verification found {} problems", problem_count)));
+    }
+    Ok(())
+}
+
+async fn check_database(config: &Config) -> Result<MigrationStatus> {
+    let storage = storage::connect(config).await?;
+    storage.ping().await?;
+    storage.migration_status().await
+}
+
+/// `--check-config`: loads and validates the configuration, connects to the
+/// database and compares its migrations with this build's, then prints a
+/// JSON report. Pending migrations are fine, since `serve` applies them;
+/// migrations this build doesn't know mean the database belongs to a newer
+/// version, and fail the check.
+pub async fn check_config(memory: bool) -> Result<()> {
+    let mut ok = true;
+
+    let config = Config::load().map(|mut config| {
+        if memory {
+            config.storage = Some("memory".to_string());
+        }
+        config
+    });
+    let config_report = match &config {
+        Ok(_) => json!({ "status": "ok" }),
+        Err(e) => {
+            ok = false;
+            json!({ "status": "error", "error": format!("{:#}", e) })
+        }
+    };
+
+    let (database, migrations) = match &config {
+        Ok(config) => match tokio::time::timeout(CHECK_DATABASE_TIMEOUT, check_database(config)).await {
+            Ok(Ok(status)) => {
+                let state = if !status.unknown.is_empty() {
+                    ok = false;
+                    "ahead_of_build"
+                } else if !status.pending.is_empty() {
+                    "pending"
+                } else {
+                    "up_to_date"
+                };
+                (json!({ "status": "ok" }), json!({ "status": state, "versions": status }))
+            }
+            Ok(Err(e)) => {
+                ok = false;
+                (json!({ "status": "error", "error": e.to_string() }), json!({ "status": "unknown" }))
+            }
+            Err(_) => {
+                ok = false;
+                (
+                    json!({ "status": "error", "error": format!("no response within {:?}", CHECK_DATABASE_TIMEOUT) }),
+                    json!({ "status": "unknown" }),
+                )
+            }
+        },
+        Err(_) => (json!({ "status": "skipped" }), json!({ "status": "skipped" })),
+    };
+
+    let report = json!({
+        "ok": ok,
+        "config": config_report,
+        "database": database,
+        "migrations": migrations,
+    });
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if !ok {
+        return Err(AppError::Internal("Configuration check failed".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::error;
+
+use crate::error::AppError;
+
+const JSON_MIME: &str = "application/json";
+const MSGPACK_MIME: &str = "application/msgpack";
+const CBOR_MIME: &str = "application/cbor";
+
+/// Wire formats supported on the append and read endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl BodyFormat {
+    /// Maps a media type (parameters ignored) to a format.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let essence = mime.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "application/json" | "application/*" | "*/*" => Some(BodyFormat::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(BodyFormat::MessagePack)
+            }
+            "application/cbor" => Some(BodyFormat::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BodyFormat::Json => JSON_MIME,
+            BodyFormat::MessagePack => MSGPACK_MIME,
+            BodyFormat::Cbor => CBOR_MIME,
+        }
+    }
+
+    /// Picks the response format from an `Accept` header, honouring the order
+    /// the client listed its preferences in. Unknown types fall back to JSON.
+    pub fn from_accept(headers: &HeaderMap) -> Self {
+        headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|accept| accept.split(',').find_map(Self::from_mime))
+            .unwrap_or_default()
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, AppError> {
+        match self {
+            BodyFormat::Json => serde_json::from_slice(bytes)
+                .map_err(|e| AppError::BadRequest(format!("Invalid JSON body: {}", e))),
+            BodyFormat::MessagePack => rmp_serde::from_slice(bytes)
+                .map_err(|e| AppError::BadRequest(format!("Invalid MessagePack body: {}", e))),
+            BodyFormat::Cbor => ciborium::de::from_reader(bytes)
+                .map_err(|e| AppError::BadRequest(format!("Invalid CBOR body: {}", e))),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, AppError> {
+        match self {
+            BodyFormat::Json => Ok(serde_json::to_vec(value)?),
+            BodyFormat::MessagePack => rmp_serde::to_vec_named(value)
+                .map_err(|e| AppError::Internal(format!("MessagePack encoding failed: {}", e))),
+            BodyFormat::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::ser::into_writer(value, &mut buf)
+                    .map_err(|e| AppError::Internal(format!("CBOR encoding failed: {}", e)))?;
+                Ok(buf)
+            }
+        }
+    }
+}
+
+/// Extracts the response format the client asked for via `Accept`.
+#[derive(Debug, Clone, Copy)]
+pub struct Accept(pub BodyFormat);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Accept
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Accept(BodyFormat::from_accept(&parts.headers)))
+    }
+}
+
+/// Request body decoded according to its `Content-Type` (JSON when absent).
+#[derive(Debug)]
+pub struct Negotiated<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for Negotiated<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format = match req.headers().get(header::CONTENT_TYPE) {
+            Some(value) => {
+                let mime = value.to_str().unwrap_or_default();
+                BodyFormat::from_mime(mime).ok_or_else(|| {
+                    AppError::UnsupportedMediaType(format!("Unsupported content type: {}", mime))
+                })?
+            }
+            None => BodyFormat::Json,
+        };
+
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| AppError::BadRequest(format!("Failed to read request body: {}", e)))?;
+
+        format.decode(&bytes).map(Negotiated)
+    }
+}
+
+/// Response body serialized in the negotiated format.
+pub struct Encoded<T>(pub BodyFormat, pub T);
+
+impl<T: Serialize> IntoResponse for Encoded<T> {
+    fn into_response(self) -> Response {
+        let Encoded(format, value) = self;
+        match format.encode(&value) {
+            Ok(body) => (
+                [(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()))],
+                body,
+            )
+                .into_response(),
+            Err(e) => {
+                error!("Failed to encode response: {}", e);
+                e.into_response()
+            }
+        }
+    }
+}
+
+/// Serde adapter for opaque payload bytes: base64 in human-readable formats
+/// (JSON), native byte strings in MessagePack and CBOR.
+pub mod binary {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(bytes) if serializer.is_human_readable() => serializer.serialize_some(&STANDARD.encode(bytes)),
+            Some(bytes) => serializer.serialize_some(&BytesRef(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        if deserializer.is_human_readable() {
+            Option::<String>::deserialize(deserializer)?
+                .map(|encoded| STANDARD.decode(encoded).map_err(de::Error::custom))
+                .transpose()
+        } else {
+            Option::<ByteBuf>::deserialize(deserializer).map(|buf| buf.map(|b| b.0))
+        }
+    }
+
+    struct BytesRef<'a>(&'a [u8]);
+
+    impl serde::Serialize for BytesRef<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    struct ByteBuf(Vec<u8>);
+
+    impl<'de> Deserialize<'de> for ByteBuf {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct Visitor;
+
+            impl<'de> de::Visitor<'de> for Visitor {
+                type Value = ByteBuf;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("a byte string")
+                }
+
+                fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ByteBuf, E> {
+                    Ok(ByteBuf(v.to_vec()))
+                }
+
+                fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+                    Ok(ByteBuf(v))
+                }
+
+                fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+                    let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                    while let Some(b) = seq.next_element()? {
+                        bytes.push(b);
+                    }
+                    Ok(ByteBuf(bytes))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(Visitor)
+        }
+    }
+}
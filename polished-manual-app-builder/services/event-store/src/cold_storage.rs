@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use tracing::info;
+
+use crate::config::Config;
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::metrics::Metrics;
+use crate::models::Event;
+use crate::object_store::{decode_events, encode_events, S3Bucket};
+use crate::storage::{ArchiveRange, EventStorage, ReadDirection};
+use crate::{slow_log, AppState};
+
+/// Events per cold storage object.
+const SEGMENT_EVENTS: i64 = 10_000;
+/// Events fetched from the local store per query while building a segment.
+const READ_PAGE_SIZE: i64 = 1_000;
+const SEGMENT_SUFFIX: &str = ".ndjson.zst";
+
+/// Archived events in S3-compatible object storage.
+///
+/// Each object holds a contiguous range of one stream as zstd-compressed
+/// NDJSON, keyed `{prefix}/streams/{stream_id}/{from}-{to}.ndjson.zst` with
+/// zero-padded versions, so a stream's segments can be found by listing its
+/// prefix without any local index.
+pub struct ColdStore {
+    bucket: S3Bucket,
+    prefix: String,
+}
+
+impl ColdStore {
+    /// Builds the store from the environment's AWS credentials, or returns
+    /// `None` when no bucket is configured.
+    pub async fn from_config(config: &Config) -> Option<Self> {
+        let bucket = config.cold_storage_bucket.clone()?;
+
+        info!("Cold storage enabled: s3://{}/{}", bucket, config.cold_storage_prefix);
+        Some(Self {
+            bucket: S3Bucket::connect(bucket, config.s3_endpoint.as_deref()).await,
+            prefix: config.cold_storage_prefix.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn stream_prefix(&self, stream_id: &str) -> String {
+        format!("{}/streams/{}/", self.prefix, stream_id)
+    }
+
+    /// Uploads a contiguous, non-empty run of events from one stream.
+    pub async fn put_segment(&self, stream_id: &str, events: &[Event]) -> Result<()> {
+        let (Some(first), Some(last)) = (events.first(), events.last()) else {
+            return Ok(());
+        };
+
+        let key = format!(
+            "{}{:020}-{:020}{}",
+            self.stream_prefix(stream_id),
+            first.version,
+            last.version,
+            SEGMENT_SUFFIX
+        );
+
+        self.bucket
+            .put(&key, encode_events(events)?, "application/x-ndjson")
+            .await
+    }
+
+    /// Segment keys of a stream with the version range each one covers.
+    async fn list_segments(&self, stream_id: &str) -> Result<Vec<(i64, i64, String)>> {
+        let prefix = self.stream_prefix(stream_id);
+        let mut segments = Vec::new();
+
+        for key in self.bucket.list(&prefix).await? {
+            // Keys of nested streams (e.g. "a/b" under "a/") contain another '/'
+            let Some(range) = key
+                .strip_prefix(&prefix)
+                .filter(|name| !name.contains('/'))
+                .and_then(|name| name.strip_suffix(SEGMENT_SUFFIX))
+            else {
+                continue;
+            };
+            if let Some((from, to)) = range.split_once('-') {
+                if let (Ok(from), Ok(to)) = (from.parse(), to.parse()) {
+                    segments.push((from, to, key.clone()));
+                }
+            }
+        }
+
+        Ok(segments)
+    }
+
+    /// Archived events of a stream with versions in `[from, to]`, in order.
+    pub async fn read_range(&self, stream_id: &str, from: i64, to: i64) -> Result<Vec<Event>> {
+        let mut events = BTreeMap::new();
+
+        for (seg_from, seg_to, key) in self.list_segments(stream_id).await? {
+            if seg_to < from || seg_from > to {
+                continue;
+            }
+
+            let segment = decode_events(&self.bucket.get(&key).await?)
+                .map_err(|e| AppError::ColdStorage(format!("Corrupt segment {}: {}", key, e)))?;
+            for event in segment {
+                if (from..=to).contains(&event.version) {
+                    // Segments can overlap if an export was retried; versions dedupe them
+                    events.insert(event.version, event);
+                }
+            }
+        }
+
+        Ok(events.into_values().collect())
+    }
+}
+
+/// Moves archivable events to cold storage, one segment at a time. Each
+/// segment is uploaded before its events are deleted locally, so a failure
+/// part-way leaves every event in at least one tier.
+pub async fn tier_out(
+    storage: &dyn EventStorage,
+    cold: &ColdStore,
+    metrics: &Metrics,
+    ranges: &[ArchiveRange],
+) -> Result<u64> {
+    let mut tiered = 0;
+
+    for range in ranges {
+        let mut from = range.from_version;
+        while from <= range.to_version {
+            let to = (from + SEGMENT_EVENTS - 1).min(range.to_version);
+
+            let mut events = Vec::new();
+            let mut next = from;
+            while next <= to {
+                let page = storage
+                    .read_stream(
+                        &range.stream_id,
+                        next,
+                        (to - next + 1).min(READ_PAGE_SIZE),
+                        ReadDirection::Forward,
+                        Deadline(None),
+                    )
+                    .await?;
+                let Some(last) = page.last() else {
+                    break;
+                };
+                next = last.version + 1;
+                events.extend(page.into_iter().filter(|e| e.version <= to));
+            }
+
+            if events.is_empty() {
+                break;
+            }
+
+            cold.put_segment(&range.stream_id, &events).await?;
+            let deleted = storage.delete_events_through(&range.stream_id, to).await?;
+            metrics.events_tiered.inc_by(deleted);
+            tiered += deleted;
+
+            from = to + 1;
+        }
+    }
+
+    Ok(tiered)
+}
+
+/// Versions `[from, to]` of a stream that have left the local store: rows
+/// in the archive table when `include_archived` is set, then whatever cold
+/// storage holds of the rest, merged in version order.
+pub async fn read_older(
+    state: &AppState,
+    stream_id: &str,
+    from: i64,
+    to: i64,
+    include_archived: bool,
+) -> Result<Vec<Event>> {
+    let mut events = BTreeMap::new();
+    if include_archived {
+        for event in state.storage.read_archived(stream_id, from, to).await? {
+            events.insert(event.version, event);
+        }
+    }
+
+    if let Some(cold) = &state.cold_store {
+        if (events.len() as i64) < to - from + 1 {
+            state.metrics.cold_storage_reads.inc();
+            for event in cold.read_range(stream_id, from, to).await? {
+                events.entry(event.version).or_insert(event);
+            }
+        }
+    }
+
+    Ok(events.into_values().collect())
+}
+
+/// Reads a stream from the local store, filling in any older versions that
+/// have been moved to cold storage, or to the archive table when
+/// `include_archived` is set. The stream head always stays local, so those
+/// events are only ever a prefix of the stream.
+pub async fn read_stream(
+    state: &AppState,
+    stream_id: &str,
+    from_version: i64,
+    limit: i64,
+    direction: ReadDirection,
+    include_archived: bool,
+    deadline: Deadline,
+) -> Result<Vec<Event>> {
+    let read = state.storage.read_stream(stream_id, from_version, limit, direction, deadline);
+    let mut events = slow_log::timed(state, "read_stream", stream_id, read).await?;
+
+    if state.cold_store.is_none() && !include_archived {
+        return Ok(events);
+    }
+    match direction {
+        ReadDirection::Forward => {
+            let wanted_from = from_version.max(1);
+            if let Some(local_from) = events.first().map(|e| e.version) {
+                if local_from > wanted_from {
+                    let to = (local_from - 1).min(wanted_from + limit - 1);
+                    let mut older = read_older(state, stream_id, wanted_from, to, include_archived).await?;
+                    older.extend(events);
+                    older.truncate(limit.max(0) as usize);
+                    events = older;
+                }
+            }
+        }
+        ReadDirection::Backward => {
+            // Carry on below the oldest local event; a read starting below all
+            // of them finds nothing locally and goes straight to the archive
+            let missing = limit - events.len() as i64;
+            let below = events.last().map_or(from_version.saturating_add(1), |e| e.version);
+            if missing > 0 && below > 1 {
+                let from = (below - missing).max(1);
+                let older = read_older(state, stream_id, from, below - 1, include_archived).await?;
+                events.extend(older.into_iter().rev());
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+/// The newest `count` events of a stream, reaching into cold storage when
+/// the local store holds fewer.
+pub async fn read_latest(
+    state: &AppState,
+    stream_id: &str,
+    count: i64,
+    deadline: Deadline,
+) -> Result<Vec<Event>> {
+    let read = state.storage.read_latest(stream_id, count, deadline);
+    let events = slow_log::timed(state, "read_latest", stream_id, read).await?;
+
+    let Some(cold) = &state.cold_store else {
+        return Ok(events);
+    };
+    let missing = count - events.len() as i64;
+    let Some(local_from) = events.first().map(|e| e.version) else {
+        return Ok(events);
+    };
+    if missing <= 0 || local_from <= 1 {
+        return Ok(events);
+    }
+
+    state.metrics.cold_storage_reads.inc();
+    let mut older = cold
+        .read_range(stream_id, (local_from - missing).max(1), local_from - 1)
+        .await?;
+    older.extend(events);
+
+    Ok(older)
+}
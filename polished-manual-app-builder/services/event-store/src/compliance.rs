@@ -0,0 +1,190 @@
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::error::Result;
+use crate::models::RetentionRule;
+use crate::{get_partition_key, AppState};
+
+#[derive(Debug, Deserialize)]
+pub struct ReportQuery {
+    pub format: Option<String>, // "json" (default) or "csv"
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComplianceReport {
+    pub generated_at: DateTime<Utc>,
+    pub projects: Vec<ProjectCompliance>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectCompliance {
+    pub project_id: String,
+    pub stream_count: i64,
+    pub event_count: i64,
+    pub retention_policy: String,
+    /// The retention rules whose stream pattern reaches the project's
+    /// streams, e.g. `audit-logs: acme/audit/* keep 365 days`.
+    pub retention_rules: Vec<String>,
+    pub oldest_retained_event: Option<DateTime<Utc>>,
+    pub newest_event: Option<DateTime<Utc>>,
+    /// `none`, or `fields:` and the event types in the project's streams
+    /// whose declared fields are encrypted, e.g. `fields:UserRegistered`.
+    pub encryption_status: String,
+    pub legal_holds: Vec<String>,
+    pub last_backup_position: Option<i64>,
+}
+
+/// GET /admin/compliance/report — per-project retention, encryption, legal
+/// hold and backup summary, as JSON or CSV (`?format=csv`).
+pub async fn compliance_report(
+    State(state): State<AppState>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response> {
+    let report = build_report(&state).await?;
+
+    if query.format.as_deref() == Some("csv") {
+        let filename = format!(
+            "attachment; filename=\"compliance-report-{}.csv\"",
+            report.generated_at.format("%Y-%m-%d")
+        );
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (header::CONTENT_DISPOSITION, filename),
+            ],
+            to_csv(&report),
+        )
+            .into_response());
+    }
+
+    Ok(Json(report).into_response())
+}
+
+async fn build_report(state: &AppState) -> Result<ComplianceReport> {
+    let summaries = state.storage.project_summaries().await?;
+
+    let mut holds: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for stream_id in state.storage.legal_hold_streams().await? {
+        holds
+            .entry(get_partition_key(&stream_id))
+            .or_default()
+            .push(stream_id);
+    }
+
+    // Encryption policies are per event type; a project is covered by those
+    // whose type occurs in its streams
+    let mut encrypted: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for policy in state.storage.encryption_policies().await? {
+        for stream_id in state.storage.streams_with_event_type(&policy.event_type).await? {
+            encrypted
+                .entry(get_partition_key(&stream_id))
+                .or_default()
+                .insert(policy.event_type.clone());
+        }
+    }
+
+    // Backups cover the whole log, so every project shares the same position
+    let last_backup_position = match &state.backup_target {
+        Some(target) => target.manifests().await?.last().map(|m| m.to_position),
+        None => None,
+    };
+
+    let retention_policy = format!(
+        "archive after {} days (snapshotted streams)",
+        state.config.archive_days
+    );
+
+    let rules = state.storage.retention_rules().await?;
+
+    let projects = summaries
+        .into_iter()
+        .map(|summary| {
+            let encryption_status = match encrypted.remove(&summary.project_id) {
+                Some(types) => format!("fields:{}", types.into_iter().collect::<Vec<_>>().join(",")),
+                None => "none".to_string(),
+            };
+            ProjectCompliance {
+                legal_holds: holds.remove(&summary.project_id).unwrap_or_default(),
+                retention_rules: rules
+                    .iter()
+                    .filter(|rule| reaches_project(rule, &summary.project_id))
+                    .map(describe_rule)
+                    .collect(),
+                project_id: summary.project_id,
+                stream_count: summary.stream_count,
+                event_count: summary.event_count,
+                retention_policy: retention_policy.clone(),
+                oldest_retained_event: summary.oldest_event_at,
+                newest_event: summary.newest_event_at,
+                encryption_status,
+                last_backup_position,
+            }
+        })
+        .collect();
+
+    Ok(ComplianceReport {
+        generated_at: Utc::now(),
+        projects,
+    })
+}
+
+/// Whether `rule`'s stream pattern can match streams of `project`, whose
+/// ids are the project id or start with `{project}/`.
+fn reaches_project(rule: &RetentionRule, project: &str) -> bool {
+    match rule.stream_prefix() {
+        Some(prefix) => {
+            let scope = format!("{}/", project);
+            scope.starts_with(prefix) || prefix.starts_with(&scope)
+        }
+        None => get_partition_key(&rule.stream_pattern) == project,
+    }
+}
+
+fn describe_rule(rule: &RetentionRule) -> String {
+    let event_type = rule.event_type.as_ref().map(|t| format!(" {}", t)).unwrap_or_default();
+    match rule.keep_days {
+        Some(days) => format!("{}: {}{} keep {} days", rule.name, rule.stream_pattern, event_type, days),
+        None => format!("{}: {}{} keep forever", rule.name, rule.stream_pattern, event_type),
+    }
+}
+
+fn to_csv(report: &ComplianceReport) -> String {
+    let mut out = String::from(
+        "project_id,stream_count,event_count,retention_policy,retention_rules,oldest_retained_event,newest_event,encryption_status,legal_hold_count,legal_hold_streams,last_backup_position\n",
+    );
+
+    for project in &report.projects {
+        let fields = [
+            project.project_id.clone(),
+            project.stream_count.to_string(),
+            project.event_count.to_string(),
+            project.retention_policy.clone(),
+            project.retention_rules.join(";"),
+            project.oldest_retained_event.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            project.newest_event.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            project.encryption_status.clone(),
+            project.legal_holds.len().to_string(),
+            project.legal_holds.join(";"),
+            project.last_backup_position.map(|p| p.to_string()).unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
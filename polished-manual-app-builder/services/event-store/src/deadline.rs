@@ -0,0 +1,73 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use std::time::Duration;
+
+use crate::error::{AppError, Result};
+use crate::AppState;
+
+pub const DEADLINE_HEADER: &str = "x-request-deadline";
+
+/// Point in time after which the caller no longer cares about the result.
+///
+/// Taken from the `X-Request-Deadline` header (RFC 3339 timestamp or Unix
+/// epoch milliseconds), falling back to the configured request timeout.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline(pub Option<DateTime<Utc>>);
+
+impl Deadline {
+    pub fn parse(value: &str) -> Option<DateTime<Utc>> {
+        let value = value.trim();
+        if let Ok(millis) = value.parse::<i64>() {
+            return Utc.timestamp_millis_opt(millis).single();
+        }
+        DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc))
+    }
+
+    /// Time left before the deadline, or an error if it has already passed.
+    pub fn remaining(&self) -> Result<Option<Duration>> {
+        match self.0 {
+            Some(deadline) => {
+                let remaining = deadline - Utc::now();
+                match remaining.to_std() {
+                    Ok(d) if !d.is_zero() => Ok(Some(d)),
+                    _ => Err(AppError::DeadlineExceeded(
+                        "Request deadline elapsed before the query was issued".to_string(),
+                    )),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Deadline
+where
+    S: Send + Sync,
+    AppState: FromRef<S>,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        if let Some(value) = parts.headers.get(DEADLINE_HEADER) {
+            let raw = value.to_str().unwrap_or_default();
+            let deadline = Self::parse(raw).ok_or_else(|| {
+                AppError::BadRequest(format!("Invalid {} header: {}", DEADLINE_HEADER, raw))
+            })?;
+            return Ok(Deadline(Some(deadline)));
+        }
+
+        let state = AppState::from_ref(state);
+        let fallback = state
+            .config
+            .request_timeout_ms
+            .map(|ms| Utc::now() + chrono::Duration::milliseconds(ms as i64));
+        Ok(Deadline(fallback))
+    }
+}
@@ -0,0 +1,166 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{DeadLetter, DeadLetterQuery, DeliveryAttempt, Event, Sink};
+use crate::{encryption, AppState};
+
+/// How long a sink gets to accept one batch.
+const SINK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest page of GET /admin/dead-letters.
+const MAX_DEAD_LETTERS_PAGE: i64 = 1000;
+
+/// One try at handing a batch of decrypted events to an external sink.
+async fn send(state: &AppState, client: &reqwest::Client, sink: &Sink, source: &str, events: &[Event]) -> Result<()> {
+    let sink_error = |e: reqwest::Error| AppError::Internal(format!("Sink rejected the delivery: {}", e));
+
+    let request = match sink {
+        Sink::Webhook { url } => client.post(url).json(&json!({ "source": source, "events": events })),
+        Sink::Kafka { topic } => {
+            let base = state
+                .config
+                .kafka_rest_url
+                .as_deref()
+                .ok_or_else(|| AppError::Internal("KAFKA_REST_URL is not set".to_string()))?;
+            let records: Vec<Value> = events
+                .iter()
+                .map(|e| json!({ "key": e.stream_id, "value": e }))
+                .collect();
+            client
+                .post(format!("{}/topics/{}", base.trim_end_matches('/'), topic))
+                .header(reqwest::header::CONTENT_TYPE, "application/vnd.kafka.json.v2+json")
+                .json(&json!({ "records": records }))
+        }
+        Sink::Projection { .. } => {
+            return Err(AppError::BadRequest("Projections are not an external sink".to_string()));
+        }
+    };
+
+    request
+        .timeout(SINK_TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(sink_error)?;
+    Ok(())
+}
+
+/// Tries `delivery_max_attempts` times, doubling the pause between tries.
+/// Returns the failed attempts, or none once a try succeeds.
+async fn send_with_retries(state: &AppState, sink: &Sink, source: &str, events: &[Event]) -> Vec<DeliveryAttempt> {
+    let client = reqwest::Client::new();
+    let mut backoff = Duration::from_millis(state.config.delivery_retry_backoff_ms);
+    let mut attempts = Vec::new();
+
+    for attempt in 1..=state.config.delivery_max_attempts {
+        match send(state, &client, sink, source, events).await {
+            Ok(()) => return Vec::new(),
+            Err(e) => {
+                warn!("Delivery from {} failed (attempt {}): {}", source, attempt, e);
+                attempts.push(DeliveryAttempt {
+                    at: Utc::now(),
+                    error: e.to_string(),
+                });
+            }
+        }
+        if attempt < state.config.delivery_max_attempts {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    attempts
+}
+
+/// Delivers events, as stored, to an external sink. When every retry fails
+/// each event is dead-lettered with the attempt history and false is
+/// returned; the caller moves on rather than blocking on one bad batch.
+pub async fn deliver(state: &AppState, sink: &Sink, source: &str, events: Vec<Event>) -> Result<bool> {
+    let mut decrypted = events.clone();
+    encryption::decrypt_events(state, &mut decrypted).await?;
+
+    let attempts = send_with_retries(state, sink, source, &decrypted).await;
+    if attempts.is_empty() {
+        return Ok(true);
+    }
+
+    let created_at = Utc::now();
+    let letters: Vec<DeadLetter> = events
+        .into_iter()
+        .map(|event| DeadLetter {
+            id: Uuid::new_v4(),
+            source: source.to_string(),
+            sink: sink.clone(),
+            event,
+            attempts: attempts.clone(),
+            created_at,
+        })
+        .collect();
+    state.storage.add_dead_letters(&letters).await?;
+    state.metrics.dead_letters.inc_by(letters.len() as u64);
+
+    warn!("Dead-lettered {} events from {}", letters.len(), source);
+    Ok(false)
+}
+
+/// GET /admin/dead-letters?source=&limit= — oldest first.
+pub async fn list_dead_letters(
+    State(state): State<AppState>,
+    Query(query): Query<DeadLetterQuery>,
+) -> Result<Json<Vec<DeadLetter>>> {
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_DEAD_LETTERS_PAGE);
+    Ok(Json(state.storage.dead_letters(query.source.as_deref(), limit).await?))
+}
+
+/// GET /admin/dead-letters/:id
+pub async fn get_dead_letter(State(state): State<AppState>, Path(id): Path<Uuid>) -> Result<Json<DeadLetter>> {
+    state
+        .storage
+        .dead_letter(id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("Dead letter {} not found", id)))
+}
+
+/// POST /admin/dead-letters/:id/redeliver — sends the event to its sink
+/// again, with retries. The dead letter is removed once delivered; otherwise
+/// the new attempts are added to its history.
+pub async fn redeliver(State(state): State<AppState>, Path(id): Path<Uuid>) -> Result<Response> {
+    let mut letter = state
+        .storage
+        .dead_letter(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Dead letter {} not found", id)))?;
+
+    let mut events = vec![letter.event.clone()];
+    encryption::decrypt_events(&state, &mut events).await?;
+    let attempts = send_with_retries(&state, &letter.sink, &letter.source, &events).await;
+
+    if attempts.is_empty() {
+        state.storage.remove_dead_letter(id).await?;
+        info!("Dead letter {} redelivered", id);
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    let error = attempts.last().map(|a| a.error.clone()).unwrap_or_default();
+    letter.attempts.extend(attempts);
+    state.storage.set_dead_letter(&letter).await?;
+    Err(AppError::Internal(format!("Redelivery of dead letter {} failed: {}", id, error)))
+}
+
+/// DELETE /admin/dead-letters/:id — discards a dead letter without
+/// delivering it.
+pub async fn discard_dead_letter(State(state): State<AppState>, Path(id): Path<Uuid>) -> Result<Response> {
+    if !state.storage.remove_dead_letter(id).await? {
+        return Err(AppError::NotFound(format!("Dead letter {} not found", id)));
+    }
+    info!("Dead letter {} discarded", id);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
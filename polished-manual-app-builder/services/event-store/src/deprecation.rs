@@ -0,0 +1,469 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderName, HeaderValue},
+    response::{IntoResponseParts, Json, ResponseParts},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::bus::BusConsumer;
+use crate::cold_storage;
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::integrity;
+use crate::metrics::Metrics;
+use crate::models::{Event, EventTypeDeprecation};
+use crate::storage::{EventStorage, NewEvent, ReadDirection};
+use crate::{get_partition_key, AppState};
+
+/// Events copied per page while rewriting a stream.
+const MIGRATION_PAGE_SIZE: i64 = 500;
+
+/// Rewrites the data of one historical event into the shape its successor
+/// type expects.
+pub trait Upcaster: Send + Sync {
+    fn upcast(&self, data: serde_json::Value) -> Result<serde_json::Value>;
+}
+
+/// The upcaster registered alongside a deprecation: renames top-level fields
+/// and fills in defaults for fields the successor type added.
+pub struct FieldMapping<'a> {
+    pub rename_fields: &'a BTreeMap<String, String>,
+    pub default_fields: &'a serde_json::Map<String, serde_json::Value>,
+}
+
+impl Upcaster for FieldMapping<'_> {
+    fn upcast(&self, data: serde_json::Value) -> Result<serde_json::Value> {
+        let serde_json::Value::Object(mut fields) = data else {
+            return Err(AppError::BadRequest(
+                "Only JSON object events can be upcast".to_string(),
+            ));
+        };
+
+        for (from, to) in self.rename_fields {
+            if let Some(value) = fields.remove(from) {
+                fields.insert(to.clone(), value);
+            }
+        }
+        for (name, value) in self.default_fields {
+            fields.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+
+        Ok(serde_json::Value::Object(fields))
+    }
+}
+
+/// In-process view of the deprecated event types, consulted on every append.
+/// Loaded from storage at startup and kept in sync by the admin endpoints.
+#[derive(Debug, Default)]
+pub struct DeprecationRegistry {
+    entries: RwLock<HashMap<String, EventTypeDeprecation>>,
+}
+
+impl DeprecationRegistry {
+    pub async fn load(storage: &dyn EventStorage) -> Result<Self> {
+        let entries = storage
+            .event_type_deprecations()
+            .await?
+            .into_iter()
+            .map(|d| (d.event_type.clone(), d))
+            .collect();
+
+        Ok(Self {
+            entries: RwLock::new(entries),
+        })
+    }
+
+    pub fn get(&self, event_type: &str) -> Option<EventTypeDeprecation> {
+        self.entries.read().unwrap().get(event_type).cloned()
+    }
+
+    fn insert(&self, deprecation: EventTypeDeprecation) {
+        self.entries
+            .write()
+            .unwrap()
+            .insert(deprecation.event_type.clone(), deprecation);
+    }
+
+    fn remove(&self, event_type: &str) {
+        self.entries.write().unwrap().remove(event_type);
+    }
+
+    /// The `Warning` header value for appends of a deprecated type.
+    pub fn warning(&self, event_type: &str) -> DeprecationWarning {
+        DeprecationWarning(self.get(event_type).map(|d| {
+            let message = match &d.successor {
+                Some(successor) => format!("Event type '{}' is deprecated; use '{}'", d.event_type, successor),
+                None => format!("Event type '{}' is deprecated", d.event_type),
+            };
+            format!("299 event-store \"{}\"", message.replace('"', "'"))
+        }))
+    }
+}
+
+/// Counts and logs appends of deprecated event types.
+pub struct DeprecationConsumer {
+    registry: Arc<DeprecationRegistry>,
+    metrics: Metrics,
+}
+
+impl DeprecationConsumer {
+    pub fn new(registry: Arc<DeprecationRegistry>, metrics: Metrics) -> Self {
+        Self { registry, metrics }
+    }
+}
+
+#[async_trait]
+impl BusConsumer for DeprecationConsumer {
+    fn name(&self) -> &str {
+        "deprecations"
+    }
+
+    async fn handle(&self, event: &Event) {
+        // Rewrites into a new generation carry their provenance; don't count those
+        let migrated = event
+            .metadata
+            .as_ref()
+            .is_some_and(|m| m.get("migrated_from").is_some());
+
+        if let Some(deprecation) = self.registry.get(&event.event_type).filter(|_| !migrated) {
+            self.metrics
+                .deprecated_events_appended
+                .with_label_values(&[&event.event_type])
+                .inc();
+            warn!(
+                "Deprecated event type {} appended to {} (successor: {})",
+                event.event_type,
+                event.stream_id,
+                deprecation.successor.as_deref().unwrap_or("none")
+            );
+        }
+    }
+}
+
+/// Adds an RFC 7234 `Warning` header to append responses when the event type
+/// is deprecated.
+pub struct DeprecationWarning(Option<String>);
+
+impl IntoResponseParts for DeprecationWarning {
+    type Error = std::convert::Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> std::result::Result<ResponseParts, Self::Error> {
+        if let Some(value) = self.0.and_then(|w| HeaderValue::from_str(&w).ok()) {
+            res.headers_mut().insert(HeaderName::from_static("warning"), value);
+        }
+        Ok(res)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeprecateRequest {
+    pub successor: Option<String>,
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub rename_fields: BTreeMap<String, String>,
+    #[serde(default)]
+    pub default_fields: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MigrateRequest {
+    /// Streams to rewrite; defaults to every stream containing the type.
+    pub stream_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationReport {
+    pub event_type: String,
+    pub successor: String,
+    pub streams: Vec<StreamMigration>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreamMigration {
+    pub source_stream_id: String,
+    pub target_stream_id: String,
+    pub generation: i64,
+    pub events_copied: u64,
+    pub events_upcast: u64,
+}
+
+/// GET /admin/event-types/deprecations
+pub async fn list_deprecations(State(state): State<AppState>) -> Result<Json<Vec<EventTypeDeprecation>>> {
+    Ok(Json(state.storage.event_type_deprecations().await?))
+}
+
+/// PUT /admin/event-types/:event_type/deprecation
+pub async fn deprecate_event_type(
+    Path(event_type): Path<String>,
+    State(state): State<AppState>,
+    Json(request): Json<DeprecateRequest>,
+) -> Result<Json<EventTypeDeprecation>> {
+    if request.successor.as_deref() == Some(event_type.as_str()) {
+        return Err(AppError::BadRequest(
+            "An event type cannot be its own successor".to_string(),
+        ));
+    }
+
+    let deprecation = EventTypeDeprecation {
+        event_type,
+        successor: request.successor,
+        reason: request.reason,
+        rename_fields: request.rename_fields,
+        default_fields: request.default_fields,
+        deprecated_at: Utc::now(),
+    };
+
+    state.storage.set_event_type_deprecation(&deprecation).await?;
+    state.deprecations.insert(deprecation.clone());
+    info!("Event type deprecated: {}", deprecation.event_type);
+
+    Ok(Json(deprecation))
+}
+
+/// DELETE /admin/event-types/:event_type/deprecation
+pub async fn undeprecate_event_type(
+    Path(event_type): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>> {
+    if !state.storage.remove_event_type_deprecation(&event_type).await? {
+        return Err(AppError::NotFound(format!(
+            "Event type {} is not deprecated",
+            event_type
+        )));
+    }
+
+    state.deprecations.remove(&event_type);
+    info!("Event type deprecation lifted: {}", event_type);
+
+    Ok(Json(serde_json::json!({ "event_type": event_type, "deprecated": false })))
+}
+
+/// POST /admin/event-types/:event_type/migrate — copies each affected stream
+/// into its next generation, upcasting events of the deprecated type to the
+/// successor. Source streams are left intact and marked as superseded. A
+/// rerun after a failure resumes each stream where its copy stopped.
+pub async fn migrate_event_type(
+    Path(event_type): Path<String>,
+    State(state): State<AppState>,
+    request: Option<Json<MigrateRequest>>,
+) -> Result<Json<MigrationReport>> {
+    let deprecation = state
+        .deprecations
+        .get(&event_type)
+        .ok_or_else(|| AppError::NotFound(format!("Event type {} is not deprecated", event_type)))?;
+    let successor = deprecation.successor.clone().ok_or_else(|| {
+        AppError::BadRequest(format!("Event type {} has no successor to migrate to", event_type))
+    })?;
+
+    let stream_ids = match request.and_then(|Json(r)| r.stream_ids) {
+        Some(ids) => ids,
+        None => state.storage.streams_with_event_type(&event_type).await?,
+    };
+
+    let mut streams = Vec::with_capacity(stream_ids.len());
+    for stream_id in stream_ids {
+        let migration = migrate_stream(&state, &deprecation, &successor, &stream_id).await?;
+        info!(
+            "Migrated {} -> {} ({} events, {} upcast)",
+            migration.source_stream_id,
+            migration.target_stream_id,
+            migration.events_copied,
+            migration.events_upcast
+        );
+        streams.push(migration);
+    }
+
+    Ok(Json(MigrationReport {
+        event_type,
+        successor,
+        streams,
+    }))
+}
+
+async fn migrate_stream(
+    state: &AppState,
+    deprecation: &EventTypeDeprecation,
+    successor: &str,
+    stream_id: &str,
+) -> Result<StreamMigration> {
+    let mut source_metadata = state.storage.stream_metadata(stream_id).await?.unwrap_or_default();
+    if let Some(target) = source_metadata.custom.get("superseded_by").and_then(|v| v.as_str()) {
+        return Err(AppError::Conflict(format!(
+            "Stream {} was already migrated to {}",
+            stream_id, target
+        )));
+    }
+
+    // Generations share a base id: orders/42, orders/42-gen2, orders/42-gen3, ...
+    let base = source_metadata
+        .custom
+        .get("generation_of")
+        .and_then(|v| v.as_str())
+        .unwrap_or(stream_id)
+        .to_string();
+    let generation = source_metadata
+        .custom
+        .get("generation")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(1)
+        + 1;
+    let target = format!("{}-gen{}", base, generation);
+
+    if !state.stream_ids.is_valid(&target) {
+        return Err(AppError::BadRequest(format!("Invalid target stream id {}", target)));
+    }
+    let (copied, copied_through) = copy_progress(state, stream_id, &target).await?;
+    if copied > 0 {
+        info!("Resuming migration of {} into {} after version {}", stream_id, target, copied_through);
+    }
+
+    let upcaster = FieldMapping {
+        rename_fields: &deprecation.rename_fields,
+        default_fields: &deprecation.default_fields,
+    };
+
+    let mut events_copied = copied as u64;
+    let mut events_upcast = 0;
+    let mut from_version = copied_through + 1;
+    loop {
+        let page = cold_storage::read_stream(
+            state,
+            stream_id,
+            from_version,
+            MIGRATION_PAGE_SIZE,
+            ReadDirection::Forward,
+            true,
+            Deadline(None),
+        )
+        .await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        from_version = last.version + 1;
+
+        for event in page {
+            let upcast = event.event_type == deprecation.event_type;
+            let upcast_with = upcast.then_some((successor, &upcaster as &dyn Upcaster));
+            let new_event = copy_event(event, state.providers.new_id(), &target, upcast_with)?;
+            let stored = state
+                .storage
+                .append(new_event, Some(events_copied as i64), Deadline(None))
+                .await?;
+            state.bus.publish(stored).await;
+
+            events_copied += 1;
+            if upcast {
+                events_upcast += 1;
+            }
+        }
+    }
+
+    state.metrics.events_upcast.inc_by(events_upcast);
+
+    let mut target_metadata = source_metadata.clone();
+    if let Some(allowed) = target_metadata.allowed_event_types.as_mut() {
+        allowed.retain(|t| t != &deprecation.event_type);
+        if !allowed.iter().any(|t| t == successor) {
+            allowed.push(successor.to_string());
+        }
+    }
+    target_metadata.custom.insert("generation_of".to_string(), base.into());
+    target_metadata.custom.insert("generation".to_string(), generation.into());
+    target_metadata.custom.insert("migrated_from".to_string(), stream_id.into());
+    state.storage.set_stream_metadata(&target, &target_metadata).await?;
+
+    source_metadata.custom.insert("superseded_by".to_string(), target.clone().into());
+    state.storage.set_stream_metadata(stream_id, &source_metadata).await?;
+
+    Ok(StreamMigration {
+        source_stream_id: stream_id.to_string(),
+        target_stream_id: target,
+        generation,
+        events_copied,
+        events_upcast,
+    })
+}
+
+/// How far an earlier, interrupted copy of `source` into `target` got: the
+/// target's head version and the source version that head was copied from,
+/// both 0 while the target is empty. Fails when the target holds anything
+/// but a copy of `source`, so a rerun only ever resumes its own copy.
+pub async fn copy_progress(state: &AppState, source: &str, target: &str) -> Result<(i64, i64)> {
+    let head = state
+        .storage
+        .read_stream(target, i64::MAX, 1, ReadDirection::Backward, Deadline(None))
+        .await?;
+    let Some(head) = head.into_iter().next() else {
+        return Ok((0, 0));
+    };
+    let copied_from = head.metadata.as_ref().and_then(|m| m.get("migrated_from"));
+    let from_source = copied_from.and_then(|m| m.get("stream_id")).and_then(|v| v.as_str()) == Some(source);
+    match copied_from.and_then(|m| m.get("version")).and_then(|v| v.as_i64()) {
+        Some(version) if from_source => Ok((head.version, version)),
+        _ => Err(AppError::Conflict(format!("Target stream {} already has events", target))),
+    }
+}
+
+/// Copies an event into the target stream as `id`, keeping its original
+/// timestamp and recording where it came from in the event metadata.
+pub fn copy_event(
+    event: Event,
+    id: Uuid,
+    target: &str,
+    upcast: Option<(&str, &dyn Upcaster)>,
+) -> Result<NewEvent> {
+    let (event_type, data) = match upcast {
+        Some((successor, upcaster)) => {
+            if event.payload.is_some() {
+                warn!("Cannot upcast binary event {} in {}", event.id, event.stream_id);
+                return Err(AppError::BadRequest(format!(
+                    "Event {} has a binary payload and cannot be upcast",
+                    event.id
+                )));
+            }
+            (successor.to_string(), upcaster.upcast(event.data)?)
+        }
+        None => (event.event_type, event.data),
+    };
+
+    let mut metadata = match event.metadata {
+        Some(serde_json::Value::Object(map)) => map,
+        Some(other) => {
+            let mut map = serde_json::Map::new();
+            map.insert("original".to_string(), other);
+            map
+        }
+        None => serde_json::Map::new(),
+    };
+    metadata.insert(
+        "migrated_from".to_string(),
+        serde_json::json!({
+            "stream_id": event.stream_id,
+            "event_id": event.id,
+            "version": event.version,
+        }),
+    );
+
+    let data = event.payload.is_none().then_some(data);
+    Ok(NewEvent {
+        id,
+        stream_id: target.to_string(),
+        event_type,
+        checksum: integrity::checksum(data.as_ref(), event.payload.as_deref()),
+        data,
+        payload: event.payload,
+        content_type: event.content_type,
+        metadata: Some(serde_json::Value::Object(metadata)),
+        tenant_id: get_partition_key(target),
+        partition_key: get_partition_key(target),
+        created_at: event.created_at,
+        expires_at: None,
+        preconditions: Vec::new(),
+    })
+}
@@ -0,0 +1,303 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::error::{AppError, Result};
+use crate::models::{EncryptionPolicy, Event};
+use crate::storage::EventStorage;
+use crate::AppState;
+
+/// Marks an encrypted field: `{"$encrypted": "<base64 nonce||ciphertext>", "subject": "<id>"}`.
+/// The subject is kept alongside so reads don't depend on the current policy.
+const ENCRYPTED_MARKER: &str = "$encrypted";
+
+/// Decrypted events buffered ahead of a streamed response.
+const DECRYPT_BUFFER: usize = 1_024;
+
+/// In-process view of the encryption policies, consulted on every append.
+/// Loaded from storage at startup and kept in sync by the admin endpoints.
+#[derive(Debug, Default)]
+pub struct EncryptionRegistry {
+    policies: RwLock<HashMap<String, EncryptionPolicy>>,
+}
+
+impl EncryptionRegistry {
+    pub async fn load(storage: &dyn EventStorage) -> Result<Self> {
+        let policies = storage
+            .encryption_policies()
+            .await?
+            .into_iter()
+            .map(|p| (p.event_type.clone(), p))
+            .collect();
+
+        Ok(Self {
+            policies: RwLock::new(policies),
+        })
+    }
+
+    pub fn get(&self, event_type: &str) -> Option<EncryptionPolicy> {
+        self.policies.read().unwrap().get(event_type).cloned()
+    }
+
+    fn insert(&self, policy: EncryptionPolicy) {
+        self.policies.write().unwrap().insert(policy.event_type.clone(), policy);
+    }
+
+    fn remove(&self, event_type: &str) {
+        self.policies.write().unwrap().remove(event_type);
+    }
+}
+
+fn subject_id(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn cipher(key: &[u8]) -> Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, key)
+        .map_err(|_| AppError::Internal("Invalid subject key length".to_string()))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Binds each ciphertext to its subject and field, so it can't be moved
+/// to another field or subject undetected.
+fn aad(subject: &str, field: &str) -> Vec<u8> {
+    format!("{}\0{}", subject, field).into_bytes()
+}
+
+/// The subject's data key, created on first use.
+async fn subject_key(state: &AppState, subject: &str) -> Result<Vec<u8>> {
+    if let Some(key) = state.storage.subject_key(subject).await? {
+        return Ok(key);
+    }
+
+    let mut key = vec![0u8; 32];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| AppError::Internal("Failed to generate subject key".to_string()))?;
+    state.storage.insert_subject_key(subject, &key).await
+}
+
+/// Encrypts the fields the event type's policy declares, in place. Events
+/// of types without a policy are left untouched.
+pub async fn encrypt_fields(state: &AppState, event_type: &str, data: &mut serde_json::Value) -> Result<()> {
+    let Some(policy) = state.encryption.get(event_type) else {
+        return Ok(());
+    };
+    let serde_json::Value::Object(fields) = data else {
+        return Err(AppError::BadRequest(format!(
+            "Event type '{}' has encrypted fields; its data must be a JSON object",
+            event_type
+        )));
+    };
+    let subject = fields.get(&policy.subject_field).and_then(subject_id).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Event type '{}' needs a subject id in '{}'",
+            event_type, policy.subject_field
+        ))
+    })?;
+
+    let key = cipher(&subject_key(state, &subject).await?)?;
+    let rng = SystemRandom::new();
+    for name in &policy.fields {
+        let Some(value) = fields.get_mut(name) else {
+            continue;
+        };
+
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut nonce)
+            .map_err(|_| AppError::Internal("Failed to generate nonce".to_string()))?;
+        let mut sealed = serde_json::to_vec(value)?;
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(aad(&subject, name)),
+            &mut sealed,
+        )
+        .map_err(|_| AppError::Internal(format!("Failed to encrypt field '{}'", name)))?;
+
+        let mut ciphertext = nonce.to_vec();
+        ciphertext.extend_from_slice(&sealed);
+        *value = serde_json::json!({
+            ENCRYPTED_MARKER: STANDARD.encode(ciphertext),
+            "subject": subject,
+        });
+    }
+
+    Ok(())
+}
+
+/// Decrypts a single sealed value, or `None` when it can't be opened. That
+/// includes ciphertext under a shredded key: a subject who writes again after
+/// erasure gets a fresh key, which doesn't open their older events.
+fn open(key: &LessSafeKey, subject: &str, field: &str, sealed: &str) -> Option<serde_json::Value> {
+    let mut bytes = STANDARD.decode(sealed).ok()?;
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let mut ciphertext = bytes.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&bytes).ok()?;
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(aad(subject, field)), &mut ciphertext)
+        .ok()?;
+    serde_json::from_slice(plaintext).ok()
+}
+
+/// Looks up and caches subject keys for one read; `None` once shredded.
+#[derive(Default)]
+struct KeyCache {
+    keys: HashMap<String, Option<LessSafeKey>>,
+}
+
+impl KeyCache {
+    async fn get(&mut self, state: &AppState, subject: &str) -> Result<Option<&LessSafeKey>> {
+        if !self.keys.contains_key(subject) {
+            let key = state
+                .storage
+                .subject_key(subject)
+                .await?
+                .map(|key| cipher(&key))
+                .transpose()?;
+            self.keys.insert(subject.to_string(), key);
+        }
+        Ok(self.keys[subject].as_ref())
+    }
+}
+
+async fn decrypt_event(state: &AppState, cache: &mut KeyCache, event: &mut Event) -> Result<()> {
+    let serde_json::Value::Object(fields) = &mut event.data else {
+        return Ok(());
+    };
+
+    for (name, value) in fields.iter_mut() {
+        let Some(sealed) = value.get(ENCRYPTED_MARKER).and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let Some(subject) = value.get("subject").and_then(subject_id) else {
+            continue;
+        };
+
+        // Erased subjects read as null; the event itself is never rewritten
+        let plaintext = match cache.get(state, &subject).await? {
+            Some(key) => open(key, &subject, name, sealed),
+            None => None,
+        };
+        if plaintext.is_none() {
+            warn!("Field '{}' of event {} is unreadable; its subject key was shredded", name, event.id);
+        }
+        *value = plaintext.unwrap_or(serde_json::Value::Null);
+    }
+
+    Ok(())
+}
+
+/// Replaces encrypted fields with their plaintext, or null for subjects whose
+/// key has been shredded.
+pub async fn decrypt_events(state: &AppState, events: &mut [Event]) -> Result<()> {
+    let mut cache = KeyCache::default();
+    for event in events {
+        decrypt_event(state, &mut cache, event).await?;
+    }
+    Ok(())
+}
+
+/// `decrypt_events` for a streamed read.
+pub fn decrypting(state: AppState, mut events: mpsc::Receiver<Result<Event>>) -> mpsc::Receiver<Result<Event>> {
+    let (sender, receiver) = mpsc::channel(DECRYPT_BUFFER);
+
+    tokio::spawn(async move {
+        let mut cache = KeyCache::default();
+        while let Some(event) = events.recv().await {
+            let event = match event {
+                Ok(mut event) => decrypt_event(&state, &mut cache, &mut event).await.map(|_| event),
+                Err(e) => Err(e),
+            };
+            if sender.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EncryptionPolicyRequest {
+    pub subject_field: String,
+    pub fields: Vec<String>,
+}
+
+/// GET /admin/encryption/policies
+pub async fn list_policies(State(state): State<AppState>) -> Result<Json<Vec<EncryptionPolicy>>> {
+    Ok(Json(state.storage.encryption_policies().await?))
+}
+
+/// PUT /admin/event-types/:event_type/encryption
+pub async fn set_policy(
+    Path(event_type): Path<String>,
+    State(state): State<AppState>,
+    Json(request): Json<EncryptionPolicyRequest>,
+) -> Result<Json<EncryptionPolicy>> {
+    if request.fields.is_empty() {
+        return Err(AppError::BadRequest("An encryption policy needs at least one field".to_string()));
+    }
+    if request.fields.contains(&request.subject_field) {
+        return Err(AppError::BadRequest(format!(
+            "The subject field '{}' cannot itself be encrypted",
+            request.subject_field
+        )));
+    }
+
+    let policy = EncryptionPolicy {
+        event_type,
+        subject_field: request.subject_field,
+        fields: request.fields,
+        declared_at: Utc::now(),
+    };
+
+    state.storage.set_encryption_policy(&policy).await?;
+    state.encryption.insert(policy.clone());
+    info!("Encryption policy set for {}: {:?}", policy.event_type, policy.fields);
+
+    Ok(Json(policy))
+}
+
+/// DELETE /admin/event-types/:event_type/encryption — later appends are
+/// stored in plaintext; events already encrypted stay encrypted.
+pub async fn remove_policy(Path(event_type): Path<String>, State(state): State<AppState>) -> Result<Response> {
+    if !state.storage.remove_encryption_policy(&event_type).await? {
+        return Err(AppError::NotFound(format!(
+            "Event type {} has no encryption policy",
+            event_type
+        )));
+    }
+
+    state.encryption.remove(&event_type);
+    info!("Encryption policy removed for {}", event_type);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// DELETE /subjects/:subject_id/keys — crypto-shreds a subject for erasure
+/// requests: their encrypted fields become unreadable in every stream,
+/// without rewriting any event.
+pub async fn shred_subject_key(Path(subject_id): Path<String>, State(state): State<AppState>) -> Result<Response> {
+    if !state.storage.delete_subject_key(&subject_id).await? {
+        return Err(AppError::NotFound(format!("Subject {} has no key", subject_id)));
+    }
+
+    info!("Subject key shredded: {}", subject_id);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -1,11 +1,16 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
 use thiserror::Error;
 
+use crate::error_capture::CapturedError;
+use crate::models::Event;
+use crate::request_id;
+
 pub type Result<T> = std::result::Result<T, AppError>;
 
 #[derive(Error, Debug)]
@@ -13,15 +18,63 @@ pub enum AppError {
     #[error("Database error: {0}")]
     Database(String),
 
+    /// Refused by the circuit breaker; retry after the given seconds.
+    #[error("Database unavailable: {0}")]
+    DatabaseUnavailable(String, u64),
+
     #[error("Bad request: {0}")]
     BadRequest(String),
 
     #[error("Conflict: {0}")]
     Conflict(String),
 
+    /// An append's expected version didn't match the stream head.
+    #[error(
+        "Conflict: version conflict on {}: expected {}, got {}",
+        .0.stream_id,
+        .0.expected_version,
+        .0.current_version
+    )]
+    VersionConflict(Box<VersionConflict>),
+
+    /// A write refused because this instance doesn't hold the leader lease,
+    /// or lost it to a newer epoch.
+    #[error("Conflict: {0}")]
+    Fenced(String),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// Too many requests for the caller's rate limit; retry after the given seconds.
+    #[error("Rate limited: {0}")]
+    RateLimited(String, u64),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    /// An event larger than its stream's `max_payload_bytes`.
+    #[error("Quota exceeded: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    /// Shed because too many requests are in progress; retry after the given seconds.
+    #[error("Overloaded: {0}")]
+    Overloaded(String, u64),
+
+    #[error("Deadline exceeded: {0}")]
+    DeadlineExceeded(String),
+
+    #[error("Cold storage error: {0}")]
+    ColdStorage(String),
+
     #[error("Internal error: {0}")]
     Internal(String),
 
@@ -32,51 +85,325 @@ pub enum AppError {
     Sql(#[from] sqlx::Error),
 }
 
+/// Stable, client-facing error categories, returned as `category` in
+/// problem responses. Every `AppError` maps to exactly one, and
+/// `GET /errors/catalog` publishes `ErrorCode::ALL`, with the finer problem
+/// codes each one covers, so SDKs can generate their error handling from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    DatabaseError,
+    DatabaseUnavailable,
+    BadRequest,
+    Conflict,
+    NotFound,
+    Unauthorized,
+    Forbidden,
+    RateLimited,
+    QuotaExceeded,
+    UnsupportedMediaType,
+    Overloaded,
+    DeadlineExceeded,
+    ColdStorageError,
+    InternalError,
+    SerializationError,
+    SqlError,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::DatabaseError,
+        ErrorCode::DatabaseUnavailable,
+        ErrorCode::BadRequest,
+        ErrorCode::Conflict,
+        ErrorCode::NotFound,
+        ErrorCode::Unauthorized,
+        ErrorCode::Forbidden,
+        ErrorCode::RateLimited,
+        ErrorCode::QuotaExceeded,
+        ErrorCode::UnsupportedMediaType,
+        ErrorCode::Overloaded,
+        ErrorCode::DeadlineExceeded,
+        ErrorCode::ColdStorageError,
+        ErrorCode::InternalError,
+        ErrorCode::SerializationError,
+        ErrorCode::SqlError,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::DatabaseError => "DATABASE_ERROR",
+            ErrorCode::DatabaseUnavailable => "DATABASE_UNAVAILABLE",
+            ErrorCode::BadRequest => "BAD_REQUEST",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::QuotaExceeded => "QUOTA_EXCEEDED",
+            ErrorCode::UnsupportedMediaType => "UNSUPPORTED_MEDIA_TYPE",
+            ErrorCode::Overloaded => "OVERLOADED",
+            ErrorCode::DeadlineExceeded => "DEADLINE_EXCEEDED",
+            ErrorCode::ColdStorageError => "COLD_STORAGE_ERROR",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+            ErrorCode::SerializationError => "SERIALIZATION_ERROR",
+            ErrorCode::SqlError => "SQL_ERROR",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::DatabaseError | ErrorCode::SqlError | ErrorCode::InternalError => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            ErrorCode::DatabaseUnavailable | ErrorCode::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::BadRequest | ErrorCode::SerializationError => StatusCode::BAD_REQUEST,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::RateLimited | ErrorCode::QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorCode::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::ColdStorageError => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    /// The `code`s a problem response in this category can carry: its own
+    /// name in snake case first, then any more specific ones.
+    pub fn problem_codes(&self) -> &'static [&'static str] {
+        match self {
+            ErrorCode::DatabaseError => &["database_error"],
+            ErrorCode::DatabaseUnavailable => &["database_unavailable"],
+            ErrorCode::BadRequest => &["bad_request"],
+            ErrorCode::Conflict => &["conflict", "wrong_expected_version", "fenced"],
+            ErrorCode::NotFound => &["not_found"],
+            ErrorCode::Unauthorized => &["unauthorized"],
+            ErrorCode::Forbidden => &["forbidden"],
+            ErrorCode::RateLimited => &["rate_limited"],
+            ErrorCode::QuotaExceeded => &["quota_exceeded", "payload_too_large"],
+            ErrorCode::UnsupportedMediaType => &["unsupported_media_type"],
+            ErrorCode::Overloaded => &["overloaded"],
+            ErrorCode::DeadlineExceeded => &["deadline_exceeded"],
+            ErrorCode::ColdStorageError => &["cold_storage_error"],
+            ErrorCode::InternalError => &["internal_error"],
+            ErrorCode::SerializationError => &["serialization_error"],
+            ErrorCode::SqlError => &["sql_error"],
+        }
+    }
+
+    /// Short human-readable title, returned as `title` in response bodies.
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorCode::DatabaseError | ErrorCode::SqlError => "Database error",
+            ErrorCode::DatabaseUnavailable => "Database unavailable",
+            ErrorCode::BadRequest => "Bad request",
+            ErrorCode::Conflict => "Conflict",
+            ErrorCode::NotFound => "Not found",
+            ErrorCode::Unauthorized => "Unauthorized",
+            ErrorCode::Forbidden => "Forbidden",
+            ErrorCode::RateLimited => "Rate limited",
+            ErrorCode::QuotaExceeded => "Quota exceeded",
+            ErrorCode::UnsupportedMediaType => "Unsupported media type",
+            ErrorCode::Overloaded => "Overloaded",
+            ErrorCode::DeadlineExceeded => "Deadline exceeded",
+            ErrorCode::ColdStorageError => "Cold storage error",
+            ErrorCode::InternalError => "Internal error",
+            ErrorCode::SerializationError => "Serialization error",
+        }
+    }
+
+    pub fn severity(&self) -> &'static str {
+        match self {
+            ErrorCode::DatabaseError | ErrorCode::SqlError | ErrorCode::ColdStorageError => "high",
+            ErrorCode::DatabaseUnavailable => "high",
+            ErrorCode::InternalError => "critical",
+            ErrorCode::BadRequest | ErrorCode::SerializationError | ErrorCode::UnsupportedMediaType => "low",
+            ErrorCode::Conflict | ErrorCode::NotFound | ErrorCode::DeadlineExceeded => "medium",
+            ErrorCode::Unauthorized | ErrorCode::Forbidden => "medium",
+            ErrorCode::RateLimited | ErrorCode::QuotaExceeded => "low",
+            ErrorCode::Overloaded => "medium",
+        }
+    }
+
+    /// Whether repeating the same request unchanged can succeed.
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::DatabaseError
+                | ErrorCode::DatabaseUnavailable
+                | ErrorCode::SqlError
+                | ErrorCode::DeadlineExceeded
+                | ErrorCode::ColdStorageError
+                | ErrorCode::RateLimited
+                | ErrorCode::Overloaded
+        )
+    }
+
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            ErrorCode::DatabaseError | ErrorCode::SqlError => {
+                "The storage backend failed or is unreachable. Retry with backoff; if it persists, check database health."
+            }
+            ErrorCode::DatabaseUnavailable => {
+                "The database is unhealthy and requests are refused while it recovers. Wait for the number of seconds in the Retry-After header, then retry."
+            }
+            ErrorCode::BadRequest => {
+                "The request is invalid. Fix the request as described in the message before retrying."
+            }
+            ErrorCode::Conflict => {
+                "The request conflicts with current state, usually an expected version mismatch. Rebase onto the current version in the conflict details (pass return_conflict_events=true to get the missed events) and retry. A fenced write reached an instance that is not the leader; send it to the leader."
+            }
+            ErrorCode::NotFound => "The stream, snapshot or resource does not exist. Check the identifier.",
+            ErrorCode::Unauthorized => {
+                "Send a valid API key in the X-Api-Key header or a bearer token in the Authorization header."
+            }
+            ErrorCode::Forbidden => {
+                "The caller's role (reader, writer or admin) or tenant does not cover this request. Use credentials that do."
+            }
+            ErrorCode::RateLimited => "Too many requests. Wait for the number of seconds in the Retry-After header, then retry.",
+            ErrorCode::QuotaExceeded => {
+                "The stream or payload is over its quota. Start a new stream, shrink the payload, or raise the limit in stream metadata."
+            }
+            ErrorCode::UnsupportedMediaType => {
+                "Send a supported Content-Type (application/json, application/msgpack or application/cbor)."
+            }
+            ErrorCode::Overloaded => {
+                "The service is at its limit of concurrent requests and shed this one. Wait for the number of seconds in the Retry-After header, then retry."
+            }
+            ErrorCode::DeadlineExceeded => {
+                "The request ran out of time. Retry with a later X-Request-Deadline or a smaller page size."
+            }
+            ErrorCode::ColdStorageError => {
+                "Archived events could not be fetched from object storage. Retry later, or read only recent versions."
+            }
+            ErrorCode::InternalError => "An unexpected server error occurred. Report it with the request details.",
+            ErrorCode::SerializationError => "The body is not valid for this endpoint. Check it against the expected schema.",
+        }
+    }
+}
+
+/// What a client needs to rebase a command after losing an append race,
+/// returned as `conflict` in the 409 problem.
+#[derive(Debug, Serialize)]
+pub struct VersionConflict {
+    pub stream_id: String,
+    pub expected_version: i64,
+    pub current_version: i64,
+    /// The events appended since `expected_version`, when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<Event>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorCatalogEntry {
+    pub code: &'static str,
+    pub status: u16,
+    pub title: &'static str,
+    pub severity: &'static str,
+    pub retryable: bool,
+    pub remediation: &'static str,
+    pub problem_codes: &'static [&'static str],
+}
+
+impl From<ErrorCode> for ErrorCatalogEntry {
+    fn from(code: ErrorCode) -> Self {
+        Self {
+            code: code.as_str(),
+            status: code.status().as_u16(),
+            title: code.title(),
+            severity: code.severity(),
+            retryable: code.retryable(),
+            remediation: code.remediation(),
+            problem_codes: code.problem_codes(),
+        }
+    }
+}
+
+/// The full error taxonomy, in a stable order.
+pub fn catalog() -> Vec<ErrorCatalogEntry> {
+    ErrorCode::ALL.iter().copied().map(ErrorCatalogEntry::from).collect()
+}
+
 impl AppError {
-    pub fn error_type(&self) -> &str {
+    pub fn code(&self) -> ErrorCode {
         match self {
-            AppError::Database(_) => "DATABASE_ERROR",
-            AppError::BadRequest(_) => "BAD_REQUEST",
-            AppError::Conflict(_) => "CONFLICT",
-            AppError::NotFound(_) => "NOT_FOUND",
-            AppError::Internal(_) => "INTERNAL_ERROR",
-            AppError::Serialization(_) => "SERIALIZATION_ERROR",
-            AppError::Sql(_) => "SQL_ERROR",
+            AppError::Database(_) => ErrorCode::DatabaseError,
+            AppError::DatabaseUnavailable(..) => ErrorCode::DatabaseUnavailable,
+            AppError::BadRequest(_) => ErrorCode::BadRequest,
+            AppError::Conflict(_) | AppError::VersionConflict(_) | AppError::Fenced(_) => ErrorCode::Conflict,
+            AppError::NotFound(_) => ErrorCode::NotFound,
+            AppError::Unauthorized(_) => ErrorCode::Unauthorized,
+            AppError::Forbidden(_) => ErrorCode::Forbidden,
+            AppError::RateLimited(..) => ErrorCode::RateLimited,
+            AppError::QuotaExceeded(_) | AppError::PayloadTooLarge(_) => ErrorCode::QuotaExceeded,
+            AppError::UnsupportedMediaType(_) => ErrorCode::UnsupportedMediaType,
+            AppError::Overloaded(..) => ErrorCode::Overloaded,
+            AppError::DeadlineExceeded(_) => ErrorCode::DeadlineExceeded,
+            AppError::ColdStorage(_) => ErrorCode::ColdStorageError,
+            AppError::Internal(_) => ErrorCode::InternalError,
+            AppError::Serialization(_) => ErrorCode::SerializationError,
+            AppError::Sql(_) => ErrorCode::SqlError,
         }
     }
 
-    pub fn severity(&self) -> &str {
+    /// The machine-readable `code` of the problem response: the most
+    /// specific of the category's problem codes that applies.
+    pub fn problem_code(&self) -> &'static str {
         match self {
-            AppError::Database(_) | AppError::Sql(_) => "high",
-            AppError::Internal(_) => "critical",
-            AppError::BadRequest(_) | AppError::Serialization(_) => "low",
-            AppError::Conflict(_) | AppError::NotFound(_) => "medium",
+            AppError::VersionConflict(_) => "wrong_expected_version",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::Fenced(_) => "fenced",
+            other => other.code().problem_codes()[0],
         }
     }
 
+    pub fn error_type(&self) -> &str {
+        self.code().as_str()
+    }
+
+    pub fn severity(&self) -> &str {
+        self.code().severity()
+    }
+
     pub fn stack_trace(&self) -> Option<String> {
         // In a real implementation, you'd capture the actual stack trace
         Some(format!("Error occurred in event-store service: {}", self))
     }
 }
 
+/// Errors are RFC 7807 problem details. `code` is what clients should
+/// branch on; `type` points at the category's entry in the error catalog.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
-            AppError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error"),
-            AppError::BadRequest(_) => (StatusCode::BAD_REQUEST, "Bad request"),
-            AppError::Conflict(_) => (StatusCode::CONFLICT, "Conflict"),
-            AppError::NotFound(_) => (StatusCode::NOT_FOUND, "Not found"),
-            AppError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
-            AppError::Serialization(_) => (StatusCode::BAD_REQUEST, "Serialization error"),
-            AppError::Sql(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Database error"),
-        };
-
-        let body = Json(json!({
-            "error": error_message,
-            "message": self.to_string(),
-        }));
-
-        (status, body).into_response()
+        let code = self.code();
+
+        let mut body = json!({
+            "type": format!("/errors/catalog#{}", code.as_str()),
+            "title": code.title(),
+            "status": code.status().as_u16(),
+            "detail": self.to_string(),
+            "code": self.problem_code(),
+            "category": code.as_str(),
+            "retryable": code.retryable(),
+            "request_id": request_id::current(),
+        });
+        if let AppError::VersionConflict(conflict) = &self {
+            body["conflict"] = json!(conflict);
+        }
+
+        let mut response = (
+            code.status(),
+            [(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"))],
+            Json(body),
+        )
+            .into_response();
+        response.extensions_mut().insert(CapturedError::new(&self));
+        if let AppError::RateLimited(_, retry_after)
+        | AppError::DatabaseUnavailable(_, retry_after)
+        | AppError::Overloaded(_, retry_after) = self
+        {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
     }
 }
@@ -0,0 +1,184 @@
+use axum::{extract::State, response::Json};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::{AppError, ErrorCode, Result};
+use crate::metrics::Metrics;
+use crate::AppState;
+
+const SEVERITIES: [&str; 4] = ["critical", "high", "medium", "low"];
+
+/// Recent errors of one type.
+#[derive(Default)]
+struct Window {
+    errors: VecDeque<Instant>,
+    alerted_at: Option<Instant>,
+}
+
+/// Counts errors per error type over a sliding window and alerts when a
+/// type goes over the budget for its severity, e.g. a burst of CONFLICT
+/// errors from clients fighting over a stream. An alert fires at most once
+/// per window per type, and again if the type is still over budget after.
+pub struct ErrorBudgets {
+    window: Duration,
+    /// Errors allowed per window, by severity; severities without one are
+    /// never alerted on.
+    budgets: HashMap<&'static str, u64>,
+    webhook_url: Option<String>,
+    client: reqwest::Client,
+    metrics: Metrics,
+    windows: Mutex<HashMap<&'static str, Window>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorBudgetView {
+    pub error_type: &'static str,
+    pub severity: &'static str,
+    pub errors_in_window: u64,
+    pub budget: Option<u64>,
+    pub exceeded: bool,
+}
+
+impl ErrorBudgets {
+    pub fn from_config(config: &Config, metrics: Metrics) -> Result<Self> {
+        // ERROR_BUDGETS="critical=1,high=20"
+        let mut budgets = HashMap::new();
+        for entry in config
+            .error_budgets
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let invalid = || AppError::Internal(format!("Invalid ERROR_BUDGETS entry '{}'", entry));
+            let (severity, budget) = entry.split_once('=').ok_or_else(invalid)?;
+            let severity = SEVERITIES
+                .into_iter()
+                .find(|s| *s == severity.trim())
+                .ok_or_else(invalid)?;
+            let budget: u64 = budget.trim().parse().map_err(|_| invalid())?;
+            budgets.insert(severity, budget);
+        }
+
+        info!(
+            "Error budgets per {}s: {}",
+            config.error_budget_window_seconds,
+            config.error_budgets
+        );
+        Ok(Self {
+            window: Duration::from_secs(config.error_budget_window_seconds),
+            budgets,
+            webhook_url: config.error_budget_webhook_url.clone(),
+            client: reqwest::Client::new(),
+            metrics,
+            windows: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Counts an error, alerting if it takes its type over budget.
+    pub fn record(&self, code: ErrorCode) {
+        let now = Instant::now();
+        let error_type = code.as_str();
+        let severity = code.severity();
+
+        let (errors, budget) = {
+            let mut windows = self.windows.lock().unwrap();
+            let window = windows.entry(error_type).or_default();
+            window.errors.push_back(now);
+            self.expire(window, now);
+            let errors = window.errors.len() as u64;
+
+            let Some(budget) = self.budgets.get(severity).copied() else {
+                return;
+            };
+            let alerted_recently = window
+                .alerted_at
+                .is_some_and(|at| now.duration_since(at) < self.window);
+            if errors <= budget || alerted_recently {
+                return;
+            }
+            window.alerted_at = Some(now);
+            (errors, budget)
+        };
+
+        self.alert(error_type, severity, errors, budget);
+    }
+
+    fn expire(&self, window: &mut Window, now: Instant) {
+        while window
+            .errors
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > self.window)
+        {
+            window.errors.pop_front();
+        }
+    }
+
+    fn alert(&self, error_type: &'static str, severity: &'static str, errors: u64, budget: u64) {
+        self.metrics
+            .error_budget_alerts
+            .with_label_values(&[error_type, severity])
+            .inc();
+        warn!(
+            "Error budget exceeded: {} {} errors in the last {}s (budget {} for {} severity)",
+            errors,
+            error_type,
+            self.window.as_secs(),
+            budget,
+            severity
+        );
+
+        let Some(url) = self.webhook_url.clone() else {
+            return;
+        };
+        let alert = json!({
+            "alert": "error_budget_exceeded",
+            "service": "event-store",
+            "error_type": error_type,
+            "severity": severity,
+            "errors": errors,
+            "budget": budget,
+            "window_seconds": self.window.as_secs(),
+            "at": Utc::now(),
+        });
+        let request = self.client.post(url).json(&alert).timeout(Duration::from_secs(5));
+        tokio::spawn(async move {
+            if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+                warn!("Failed to send error budget alert: {}", e);
+            }
+        });
+    }
+
+    fn views(&self) -> Vec<ErrorBudgetView> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        ErrorCode::ALL
+            .iter()
+            .map(|code| {
+                let errors_in_window = windows.get_mut(code.as_str()).map_or(0, |window| {
+                    self.expire(window, now);
+                    window.errors.len() as u64
+                });
+                let budget = self.budgets.get(code.severity()).copied();
+                ErrorBudgetView {
+                    error_type: code.as_str(),
+                    severity: code.severity(),
+                    errors_in_window,
+                    budget,
+                    exceeded: budget.is_some_and(|budget| errors_in_window > budget),
+                }
+            })
+            .collect()
+    }
+}
+
+/// GET /admin/error-budgets — errors per type in the current window against
+/// their budgets.
+pub async fn list_error_budgets(State(state): State<AppState>) -> Json<Vec<ErrorBudgetView>> {
+    Json(state.error_budgets.views())
+}
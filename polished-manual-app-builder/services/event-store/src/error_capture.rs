@@ -1,140 +1,263 @@
-use std::fs::OpenOptions;
-use std::io::Write;
-use serde_json::json;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
 use chrono::Utc;
-use crate::error::AppError;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::MissedTickBehavior;
+
+use crate::config::Config;
+use crate::error::{AppError, ErrorCode, Result};
+use crate::error_sinks::{self, ErrorSink, RotatingLog};
+use crate::metrics::Metrics;
+use crate::{request_id, AppState};
+
+/// Most captured errors written in one go.
+const MAX_BATCH: usize = 256;
 
-pub struct ErrorCapture;
+/// What an error response carries for the capture middleware; put in the
+/// response extensions by AppError's IntoResponse.
+#[derive(Debug, Clone)]
+pub struct CapturedError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub stack_trace: Option<String>,
+}
+
+impl CapturedError {
+    pub fn new(error: &AppError) -> Self {
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+            stack_trace: error.stack_trace(),
+        }
+    }
+}
+
+/// Hands errors to a background writer, so capturing one never waits on the
+/// disk or a remote sink. When the writer falls `error_capture_buffer` errors
+/// behind, new ones are dropped and counted instead.
+#[derive(Clone)]
+pub struct ErrorCapture {
+    sender: mpsc::Sender<Message>,
+    metrics: Metrics,
+}
+
+enum Message {
+    Error(Value),
+    /// Write out the aggregated error patterns, then reply.
+    Flush(oneshot::Sender<()>),
+}
 
 impl ErrorCapture {
-    pub async fn log_error(
-        error: &AppError,
+    /// Starts the writer. It finishes what's queued, flushes the error
+    /// patterns and stops once every handle has been dropped.
+    pub fn spawn(config: &Config, metrics: Metrics) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel(config.error_capture_buffer);
+        let writer = Writer {
+            patterns: RotatingLog::new(
+                PathBuf::from(&config.error_log_dir),
+                "pending-error-patterns",
+                config.error_log_max_bytes,
+            ),
+            sinks: error_sinks::from_config(config)?,
+            pending: HashMap::new(),
+        };
+        tokio::spawn(writer.run(
+            receiver,
+            Duration::from_secs(config.error_pattern_flush_interval_seconds),
+        ));
+
+        Ok(Self { sender, metrics })
+    }
+
+    pub fn log_error(
+        &self,
+        error: &CapturedError,
         context: &str,
         service: &str,
-        additional_data: Option<serde_json::Value>,
+        additional_data: Option<Value>,
     ) {
         let error_log = json!({
             "timestamp": Utc::now().to_rfc3339(),
             "service": service,
             "context": context,
-            "error_type": error.error_type(),
-            "error_message": error.to_string(),
-            "severity": error.severity(),
-            "stack_trace": error.stack_trace(),
+            "request_id": request_id::current(),
+            "error_type": error.code.as_str(),
+            "error_message": error.message,
+            "severity": error.code.severity(),
+            "retryable": error.code.retryable(),
+            "remediation": error.code.remediation(),
+            "stack_trace": error.stack_trace,
             "additional_data": additional_data,
             "environment": std::env::var("ENVIRONMENT").unwrap_or_else(|_| "development".to_string()),
             "version": env!("CARGO_PKG_VERSION"),
         });
 
-        // Log to structured error file
-        let log_path = "../../logs/errors/event-store-errors.jsonl";
-        if let Err(e) = Self::write_error_log(&error_log, log_path).await {
-            eprintln!("Failed to write error log: {}", e);
-        }
-
-        // Send to error monitoring system
-        if let Err(e) = Self::send_to_monitor(&error_log).await {
-            eprintln!("Failed to send error to monitor: {}", e);
+        // Full, or closed while shutting down
+        if self.sender.try_send(Message::Error(error_log)).is_err() {
+            self.metrics.errors_capture_dropped.inc();
         }
+    }
 
-        // Update error guide if this is a new error pattern
-        if let Err(e) = Self::update_error_guide(&error_log).await {
-            eprintln!("Failed to update error guide: {}", e);
+    /// Waits for the errors captured so far to reach the sinks and for the
+    /// error patterns to be written. Called on shutdown.
+    pub async fn flush(&self) {
+        let (reply, flushed) = oneshot::channel();
+        if self.sender.send(Message::Flush(reply)).await.is_ok() {
+            flushed.await.ok();
         }
     }
+}
 
-    async fn write_error_log(
-        error_log: &serde_json::Value,
-        log_path: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Ensure log directory exists
-        if let Some(parent) = std::path::Path::new(log_path).parent() {
-            tokio::fs::create_dir_all(parent).await?;
-        }
+/// Captures the error behind each error response, with its route as the
+/// context.
+pub async fn capture_errors(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| request.uri().path().to_string(), |path| path.as_str().to_string());
+    let context = format!("{} {}", request.method(), route);
 
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(log_path)?;
+    let response = next.run(request).await;
 
-        writeln!(file, "{}", serde_json::to_string(error_log)?)?;
-        Ok(())
+    if let Some(error) = response.extensions().get::<CapturedError>() {
+        state.error_budgets.record(error.code);
+        state.errors.log_error(error, &context, "event-store", None);
     }
+    response
+}
+
+struct Writer {
+    patterns: RotatingLog,
+    sinks: Vec<Box<dyn ErrorSink>>,
+    /// Error guide entries by pattern hash, counted up until the next flush
+    /// so a hot error makes one line per flush rather than one per hit.
+    pending: HashMap<String, Value>,
+}
 
-    async fn send_to_monitor(
-        error_log: &serde_json::Value,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let client = reqwest::Client::new();
-        
-        // Send to local error monitor
-        let monitor_url = "http://localhost:8090/errors";
-        
-        match client
-            .post(monitor_url)
-            .json(error_log)
-            .timeout(Duration::from_secs(5))
-            .send()
-            .await
-        {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                // Don't fail the main operation if monitoring fails
-                eprintln!("Warning: Failed to send error to monitor: {}", e);
-                Ok(())
+impl Writer {
+    async fn run(mut self, mut receiver: mpsc::Receiver<Message>, flush_interval: Duration) {
+        let mut ticks = tokio::time::interval(flush_interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut batch = Vec::with_capacity(MAX_BATCH);
+        let mut replies = Vec::new();
+
+        loop {
+            let message = tokio::select! {
+                message = receiver.recv() => message,
+                _ = ticks.tick() => {
+                    self.flush().await;
+                    continue;
+                }
+            };
+            let Some(mut message) = message else {
+                break;
+            };
+
+            loop {
+                match message {
+                    Message::Error(error_log) => batch.push(error_log),
+                    Message::Flush(reply) => replies.push(reply),
+                }
+                if batch.len() >= MAX_BATCH {
+                    break;
+                }
+                match receiver.try_recv() {
+                    Ok(next) => message = next,
+                    Err(_) => break,
+                }
+            }
+
+            self.write(&batch).await;
+            batch.clear();
+            if !replies.is_empty() {
+                self.flush().await;
+                for reply in replies.drain(..) {
+                    reply.send(()).ok();
+                }
             }
         }
-    }
 
-    async fn update_error_guide(
-        error_log: &serde_json::Value,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        // Check if this error pattern exists in our knowledge base
-        let error_type = error_log["error_type"].as_str().unwrap_or("unknown");
-        let error_message = error_log["error_message"].as_str().unwrap_or("unknown");
-        
-        // Create error pattern hash for deduplication
-        let pattern_hash = format!("{:x}", 
-            std::collections::hash_map::DefaultHasher::new()
-                .chain(error_type)
-                .chain(error_message)
-                .finish()
-        );
-
-        let new_error_entry = json!({
-            "pattern_hash": pattern_hash,
-            "error_type": error_type,
-            "service": error_log["service"],
-            "context": error_log["context"],
-            "message": error_message,
-            "first_seen": error_log["timestamp"],
-            "last_seen": error_log["timestamp"],
-            "occurrence_count": 1,
-            "resolved": false,
-            "solution": null,
-            "prevention_tips": [],
-            "related_errors": [],
-            "severity": error_log["severity"]
-        });
+        self.flush().await;
+    }
 
-        // Write to pending errors file for review and integration
-        let pending_path = "../../logs/errors/pending-error-patterns.jsonl";
-        if let Some(parent) = std::path::Path::new(pending_path).parent() {
-            tokio::fs::create_dir_all(parent).await?;
+    async fn write(&mut self, batch: &[Value]) {
+        for sink in &self.sinks {
+            if let Err(e) = sink.send(batch).await {
+                // One sink being down doesn't keep errors from the others
+                eprintln!("Failed to send errors to the {} sink: {}", sink.name(), e);
+            }
         }
 
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(pending_path)?;
+        for error_log in batch {
+            let pattern = error_pattern(error_log);
+            let hash = pattern["pattern_hash"].as_str().unwrap_or_default().to_string();
+            self.pending
+                .entry(hash)
+                .and_modify(|seen| {
+                    let count = seen["occurrence_count"].as_u64().unwrap_or(0);
+                    seen["occurrence_count"] = json!(count + 1);
+                    seen["last_seen"] = error_log["timestamp"].clone();
+                })
+                .or_insert(pattern);
+        }
+    }
 
-        writeln!(file, "{}", serde_json::to_string(&new_error_entry)?)?;
-        Ok(())
+    /// Writes the pending error patterns to the pending errors file for
+    /// review and integration.
+    async fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let patterns: Vec<Value> = self.pending.drain().map(|(_, pattern)| pattern).collect();
+        if let Err(e) = self.patterns.append(&patterns).await {
+            eprintln!("Failed to update error guide: {}", e);
+        }
     }
 }
 
+/// The error guide entry for a captured error, keyed by a hash of its type
+/// and message.
+fn error_pattern(error_log: &Value) -> Value {
+    // Check if this error pattern exists in our knowledge base
+    let error_type = error_log["error_type"].as_str().unwrap_or("unknown");
+    let error_message = error_log["error_message"].as_str().unwrap_or("unknown");
+
+    // Create error pattern hash for deduplication
+    let pattern_hash = format!(
+        "{:x}",
+        std::collections::hash_map::DefaultHasher::new()
+            .chain(error_type)
+            .chain(error_message)
+            .finish()
+    );
+
+    json!({
+        "pattern_hash": pattern_hash,
+        "error_type": error_type,
+        "service": error_log["service"],
+        "context": error_log["context"],
+        "message": error_message,
+        "first_seen": error_log["timestamp"],
+        "last_seen": error_log["timestamp"],
+        "occurrence_count": 1,
+        "resolved": false,
+        // Documented remediation from the /errors/catalog taxonomy
+        "solution": error_log["remediation"],
+        "prevention_tips": [],
+        "related_errors": [],
+        "severity": error_log["severity"]
+    })
+}
+
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
-use std::time::Duration;
 
 trait HashBuilder {
     fn chain<T: Hash>(self, value: T) -> Self;
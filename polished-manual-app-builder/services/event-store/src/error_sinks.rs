@@ -0,0 +1,363 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+
+/// How long a remote sink gets to accept one request.
+const SINK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Somewhere captured errors are sent. Sinks get errors in batches, as the
+/// error log entries built by ErrorCapture, and run on the capture writer's
+/// task, so a slow sink delays the others but never a request.
+#[async_trait]
+pub trait ErrorSink: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn send(&self, errors: &[Value]) -> Result<()>;
+}
+
+/// The sinks named in `ERROR_SINKS`, e.g. `file,sentry`.
+pub fn from_config(config: &Config) -> Result<Vec<Box<dyn ErrorSink>>> {
+    let client = reqwest::Client::new();
+    let missing = |sink: &str, setting: &str| {
+        AppError::Internal(format!("The {} error sink needs {} to be set", sink, setting))
+    };
+
+    let mut sinks: Vec<Box<dyn ErrorSink>> = Vec::new();
+    for name in config.error_sink_names() {
+        let sink: Box<dyn ErrorSink> = match name {
+            "file" => Box::new(FileSink {
+                log: Mutex::new(RotatingLog::new(
+                    PathBuf::from(&config.error_log_dir),
+                    "event-store-errors",
+                    config.error_log_max_bytes,
+                )),
+            }),
+            "monitor" => Box::new(MonitorSink {
+                client: client.clone(),
+                url: config.error_monitor_url.clone(),
+            }),
+            "webhook" => Box::new(WebhookSink {
+                client: client.clone(),
+                url: config.error_webhook_url.clone().ok_or_else(|| missing(name, "ERROR_WEBHOOK_URL"))?,
+            }),
+            "sentry" => {
+                let dsn = config.sentry_dsn.as_deref().ok_or_else(|| missing(name, "SENTRY_DSN"))?;
+                Box::new(SentrySink::new(client.clone(), dsn)?)
+            }
+            "otlp" => {
+                let endpoint = config
+                    .error_otlp_endpoint
+                    .as_deref()
+                    .ok_or_else(|| missing(name, "ERROR_OTLP_ENDPOINT"))?;
+                Box::new(OtlpSink {
+                    client: client.clone(),
+                    url: format!("{}/v1/logs", endpoint.trim_end_matches('/')),
+                })
+            }
+            other => return Err(AppError::Internal(format!("Unknown error sink '{}'", other))),
+        };
+        sinks.push(sink);
+    }
+
+    info!(
+        "Error sinks: {}",
+        sinks.iter().map(|s| s.name()).collect::<Vec<_>>().join(", ")
+    );
+    Ok(sinks)
+}
+
+async fn post(request: reqwest::RequestBuilder) -> Result<()> {
+    request
+        .timeout(SINK_TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| AppError::Internal(format!("Error sink rejected the errors: {}", e)))?;
+    Ok(())
+}
+
+/// A JSONL file in `dir` named for the day it's written on, moving on to a
+/// numbered sibling whenever it reaches `max_bytes`:
+/// `event-store-errors-2024-05-01.jsonl`, then `...-2024-05-01.1.jsonl`.
+pub struct RotatingLog {
+    dir: PathBuf,
+    stem: &'static str,
+    max_bytes: u64,
+    date: Option<NaiveDate>,
+    index: u32,
+    size: u64,
+}
+
+impl RotatingLog {
+    pub fn new(dir: PathBuf, stem: &'static str, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            stem,
+            max_bytes,
+            date: None,
+            index: 0,
+            size: 0,
+        }
+    }
+
+    fn path(&self, date: NaiveDate, index: u32) -> PathBuf {
+        let name = match index {
+            0 => format!("{}-{}.jsonl", self.stem, date),
+            _ => format!("{}-{}.{}.jsonl", self.stem, date, index),
+        };
+        self.dir.join(name)
+    }
+
+    pub async fn append(&mut self, lines: &[Value]) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        for line in lines {
+            serde_json::to_writer(&mut buffer, line)?;
+            buffer.push(b'\n');
+        }
+
+        let today = Utc::now().date_naive();
+        if self.date != Some(today) {
+            // Carry on after whatever an earlier run wrote today
+            self.date = Some(today);
+            self.index = 0;
+            self.size = file_size(&self.path(today, 0)).await;
+            while self.size >= self.max_bytes {
+                self.index += 1;
+                self.size = file_size(&self.path(today, self.index)).await;
+            }
+        } else if self.size > 0 && self.size + buffer.len() as u64 > self.max_bytes {
+            self.index += 1;
+            self.size = 0;
+        }
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(today, self.index))
+            .await?;
+        file.write_all(&buffer).await?;
+        self.size += buffer.len() as u64;
+        Ok(())
+    }
+}
+
+async fn file_size(path: &Path) -> u64 {
+    tokio::fs::metadata(path).await.map_or(0, |metadata| metadata.len())
+}
+
+/// Appends each error as a line to the rotating error log.
+struct FileSink {
+    log: Mutex<RotatingLog>,
+}
+
+#[async_trait]
+impl ErrorSink for FileSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn send(&self, errors: &[Value]) -> Result<()> {
+        self.log
+            .lock()
+            .await
+            .append(errors)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to write error log: {}", e)))
+    }
+}
+
+/// Posts each error on its own to the local error monitor.
+struct MonitorSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[async_trait]
+impl ErrorSink for MonitorSink {
+    fn name(&self) -> &str {
+        "monitor"
+    }
+
+    async fn send(&self, errors: &[Value]) -> Result<()> {
+        for error in errors {
+            post(self.client.post(&self.url).json(error)).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Posts each batch as `{"errors": [...]}`.
+struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[async_trait]
+impl ErrorSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send(&self, errors: &[Value]) -> Result<()> {
+        post(self.client.post(&self.url).json(&json!({ "errors": errors }))).await
+    }
+}
+
+/// Sends each error to Sentry's store endpoint as an event, tagged with its
+/// type and route so Sentry groups them the way the error catalog does.
+struct SentrySink {
+    client: reqwest::Client,
+    store_url: String,
+    auth: String,
+}
+
+impl SentrySink {
+    /// Takes a DSN of the form `https://<key>@<host>/<project id>`.
+    fn new(client: reqwest::Client, dsn: &str) -> Result<Self> {
+        let invalid = |reason: &str| AppError::Internal(format!("Invalid SENTRY_DSN: {}", reason));
+        let url = reqwest::Url::parse(dsn).map_err(|e| invalid(&e.to_string()))?;
+        let key = url.username();
+        if key.is_empty() {
+            return Err(invalid("missing public key"));
+        }
+        let host = url.host_str().ok_or_else(|| invalid("missing host"))?;
+        let (prefix, project) = url
+            .path()
+            .trim_end_matches('/')
+            .rsplit_once('/')
+            .filter(|(_, project)| !project.is_empty())
+            .ok_or_else(|| invalid("missing project id"))?;
+        let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+
+        Ok(Self {
+            client,
+            store_url: format!("{}://{}{}{}/api/{}/store/", url.scheme(), host, port, prefix, project),
+            auth: format!(
+                "Sentry sentry_version=7, sentry_key={}, sentry_client=event-store/{}",
+                key,
+                env!("CARGO_PKG_VERSION")
+            ),
+        })
+    }
+}
+
+#[async_trait]
+impl ErrorSink for SentrySink {
+    fn name(&self) -> &str {
+        "sentry"
+    }
+
+    async fn send(&self, errors: &[Value]) -> Result<()> {
+        for error in errors {
+            let level = match error["severity"].as_str() {
+                Some("critical") => "fatal",
+                Some("high") => "error",
+                Some("medium") => "warning",
+                _ => "info",
+            };
+            let event = json!({
+                "event_id": Uuid::new_v4().simple().to_string(),
+                "timestamp": error["timestamp"],
+                "level": level,
+                "logger": "event-store",
+                "platform": "other",
+                "message": { "formatted": error["error_message"] },
+                "environment": error["environment"],
+                "release": error["version"],
+                "tags": {
+                    "error_type": error["error_type"],
+                    "context": error["context"],
+                    "service": error["service"],
+                    "retryable": error["retryable"].to_string(),
+                    "request_id": error["request_id"],
+                },
+                "fingerprint": [error["error_type"], error["context"]],
+                "extra": {
+                    "remediation": error["remediation"],
+                    "stack_trace": error["stack_trace"],
+                    "additional_data": error["additional_data"],
+                },
+            });
+            post(self.client.post(&self.store_url).header("X-Sentry-Auth", &self.auth).json(&event)).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Exports each batch as OTLP log records over HTTP/JSON, for collectors
+/// that already take the service's traces.
+struct OtlpSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[async_trait]
+impl ErrorSink for OtlpSink {
+    fn name(&self) -> &str {
+        "otlp"
+    }
+
+    async fn send(&self, errors: &[Value]) -> Result<()> {
+        let records: Vec<Value> = errors.iter().map(log_record).collect();
+        let body = json!({
+            "resourceLogs": [{
+                "resource": {
+                    "attributes": [{ "key": "service.name", "value": { "stringValue": "event-store" } }],
+                },
+                "scopeLogs": [{
+                    "scope": { "name": "event-store.errors" },
+                    "logRecords": records,
+                }],
+            }],
+        });
+        post(self.client.post(&self.url).json(&body)).await
+    }
+}
+
+fn log_record(error: &Value) -> Value {
+    let (severity_number, severity_text) = match error["severity"].as_str() {
+        Some("critical") => (21, "FATAL"),
+        Some("high") => (17, "ERROR"),
+        Some("medium") => (13, "WARN"),
+        _ => (9, "INFO"),
+    };
+    let time = error["timestamp"]
+        .as_str()
+        .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        .and_then(|at| at.timestamp_nanos_opt())
+        .unwrap_or_default();
+
+    // Everything but the message becomes an attribute
+    let attributes: Vec<Value> = error
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, value)| *key != "error_message" && *key != "timestamp" && !value.is_null())
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(s) => json!({ "stringValue": s }),
+                Value::Bool(b) => json!({ "boolValue": b }),
+                other => json!({ "stringValue": other.to_string() }),
+            };
+            json!({ "key": key, "value": value })
+        })
+        .collect();
+
+    json!({
+        "timeUnixNano": time.to_string(),
+        "severityNumber": severity_number,
+        "severityText": severity_text,
+        "body": { "stringValue": error["error_message"] },
+        "attributes": attributes,
+    })
+}
@@ -0,0 +1,144 @@
+use async_graphql::{InputObject, Json as GraphqlJson};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{AppError, Result};
+use crate::json_path::JsonPath;
+use crate::models::Event;
+
+/// Which events a reader wants: the `filter` argument of the GraphQL
+/// queries and subscriptions, and of persistent subscription groups.
+/// Every condition given must pass.
+#[derive(Debug, Clone, Default, InputObject, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// Only events of these types.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_types: Option<Vec<String>>,
+    /// Only events created at or after this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<DateTime<Utc>>,
+    /// Only events created before this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<DateTime<Utc>>,
+    /// Only events whose metadata contains this object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<GraphqlJson<Value>>,
+    /// Only events whose data contains this object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<GraphqlJson<Value>>,
+    /// Only events passing every one of these.
+    #[graphql(name = "where")]
+    #[serde(default, rename = "where", skip_serializing_if = "Option::is_none")]
+    pub predicates: Option<Vec<FieldPredicate>>,
+}
+
+/// A test of the values a JSONPath under `$.data` or `$.metadata` selects,
+/// e.g. `$.data.items[*].sku`; it passes when any one of them passes every
+/// comparison given, or, with none given, when there is one. Encrypted
+/// fields are compared as stored.
+#[derive(Debug, Clone, InputObject, Serialize, Deserialize)]
+pub struct FieldPredicate {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eq: Option<GraphqlJson<Value>>,
+    /// Contains this, as `metadata` on the filter does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<GraphqlJson<Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gt: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gte: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lt: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lte: Option<f64>,
+    /// `false` passes when the path selects nothing.
+    #[graphql(default = true)]
+    #[serde(default = "exists_default")]
+    pub exists: bool,
+}
+
+fn exists_default() -> bool {
+    true
+}
+
+impl FieldPredicate {
+    fn passes(&self, value: &Value) -> bool {
+        let number = value.as_f64();
+        let compare = |bound: Option<f64>, test: fn(f64, f64) -> bool| {
+            bound.map_or(true, |bound| number.is_some_and(|n| test(n, bound)))
+        };
+        self.eq.as_ref().map_or(true, |eq| *value == eq.0)
+            && self.contains.as_ref().map_or(true, |wanted| contains(value, &wanted.0))
+            && compare(self.gt, |n, bound| n > bound)
+            && compare(self.gte, |n, bound| n >= bound)
+            && compare(self.lt, |n, bound| n < bound)
+            && compare(self.lte, |n, bound| n <= bound)
+    }
+}
+
+impl EventFilter {
+    /// Parses the `where` paths up front, so a bad one fails the request
+    /// rather than matching nothing.
+    pub fn compile(self) -> Result<EventMatcher> {
+        let paths = self
+            .predicates
+            .iter()
+            .flatten()
+            .map(|predicate| {
+                let path = JsonPath::parse(&predicate.path)?;
+                match path.split_field() {
+                    Some((root @ ("data" | "metadata"), below)) => Ok((root == "data", below)),
+                    _ => Err(AppError::BadRequest(format!(
+                        "JSONPath '{}' must start with $.data or $.metadata",
+                        predicate.path
+                    ))),
+                }
+            })
+            .collect::<Result<_>>()?;
+        Ok(EventMatcher { filter: self, paths })
+    }
+}
+
+/// An `EventFilter` with its `where` paths parsed: whether each is under
+/// the data, and the path below that.
+#[derive(Debug, Clone)]
+pub struct EventMatcher {
+    filter: EventFilter,
+    paths: Vec<(bool, JsonPath)>,
+}
+
+impl EventMatcher {
+    pub fn matches(&self, event: &Event) -> bool {
+        let filter = &self.filter;
+        filter.event_types.as_ref().map_or(true, |types| types.contains(&event.event_type))
+            && filter.after.map_or(true, |after| event.created_at >= after)
+            && filter.before.map_or(true, |before| event.created_at < before)
+            && filter.metadata.as_ref().map_or(true, |wanted| {
+                event.metadata.as_ref().is_some_and(|metadata| contains(metadata, &wanted.0))
+            })
+            && filter.data.as_ref().map_or(true, |wanted| contains(&event.data, &wanted.0))
+            && filter.predicates.iter().flatten().zip(&self.paths).all(|(predicate, (in_data, path))| {
+                let root = if *in_data { Some(&event.data) } else { event.metadata.as_ref() };
+                let selected = root.map(|root| path.select(root)).unwrap_or_default();
+                if !predicate.exists {
+                    return selected.is_empty();
+                }
+                selected.into_iter().any(|value| predicate.passes(value))
+            })
+    }
+}
+
+/// Whether `value` contains `wanted`, like Postgres' `@>` on JSONB.
+fn contains(value: &Value, wanted: &Value) -> bool {
+    match (value, wanted) {
+        (Value::Object(fields), Value::Object(wanted)) => wanted
+            .iter()
+            .all(|(key, wanted)| fields.get(key).is_some_and(|field| contains(field, wanted))),
+        (Value::Array(items), Value::Array(wanted)) => {
+            wanted.iter().all(|wanted| items.iter().any(|item| contains(item, wanted)))
+        }
+        _ => value == wanted,
+    }
+}
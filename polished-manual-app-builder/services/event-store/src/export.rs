@@ -0,0 +1,249 @@
+use axum::body::{Body, BodyDataStream};
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Json, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio_stream::StreamExt;
+use tracing::info;
+
+use crate::error::{AppError, Result};
+use crate::integrity::ImportSealer;
+use crate::models::Event;
+use crate::streaming::{self, NDJSON_MIME};
+use crate::AppState;
+
+/// Events written per transaction while importing.
+pub const IMPORT_BATCH_SIZE: usize = 1_000;
+/// Events written per transaction during bulk loads.
+const BULK_BATCH_SIZE: usize = 10_000;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub from_version: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkLoadResponse {
+    pub loaded: u64,
+    pub streams: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    pub stream_id: String,
+    pub imported: u64,
+    pub version: i64,
+}
+
+/// GET /streams/:stream_id/export — the stream as NDJSON, one event per line
+/// in version order, including versions already moved to cold storage.
+/// Streamed as it is read, so exports of any size run in constant memory.
+pub async fn export_stream(
+    Path(stream_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+    State(state): State<AppState>,
+) -> Result<Response> {
+    let head = state.storage.stream_version(&stream_id).await?;
+    if head == 0 {
+        return Err(AppError::NotFound(format!("Stream {} not found", stream_id)));
+    }
+
+    let from_version = query.from_version.unwrap_or(1).max(crate::visible_from(&state, &stream_id).await?);
+    let filename = format!(
+        "attachment; filename=\"{}.ndjson\"",
+        stream_id.replace('/', "_")
+    );
+    let events = streaming::scan(state, stream_id, from_version, head, true);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, NDJSON_MIME.to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        streaming::ndjson_body(events),
+    )
+        .into_response())
+}
+
+/// Parses events from an NDJSON request body one line at a time.
+struct NdjsonEvents {
+    body: BodyDataStream,
+    buffer: Vec<u8>,
+    line_number: u64,
+    done: bool,
+}
+
+impl NdjsonEvents {
+    fn new(body: Body) -> Self {
+        Self {
+            body: body.into_data_stream(),
+            buffer: Vec::new(),
+            line_number: 0,
+            done: false,
+        }
+    }
+
+    async fn next(&mut self) -> Result<Option<Event>> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                self.line_number += 1;
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                let event = serde_json::from_slice(&line).map_err(|e| {
+                    AppError::BadRequest(format!("Invalid event on line {}: {}", self.line_number, e))
+                })?;
+                return Ok(Some(event));
+            }
+
+            if self.done {
+                return Ok(None);
+            }
+            match self.body.next().await {
+                Some(chunk) => {
+                    let chunk = chunk.map_err(|e| {
+                        AppError::BadRequest(format!("Failed to read request body: {}", e))
+                    })?;
+                    self.buffer.extend_from_slice(&chunk);
+                }
+                None => {
+                    self.done = true;
+                    // A final line without a trailing newline
+                    if !self.buffer.is_empty() {
+                        self.buffer.push(b'\n');
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// POST /streams/:stream_id/import — appends an NDJSON export to the stream,
+/// keeping each event's id, version and timestamp. The first version must
+/// follow the stream head (1 for a new stream) and versions must be
+/// contiguous. The body is read and written in batches, so a failed import
+/// leaves a valid prefix in place and can be resumed by re-sending the rest.
+pub async fn import_stream(
+    Path(stream_id): Path<String>,
+    State(state): State<AppState>,
+    body: Body,
+) -> Result<Json<ImportResponse>> {
+    state.stream_ids.validate(&stream_id)?;
+
+    let mut events = NdjsonEvents::new(body);
+    let mut batch: Vec<Event> = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    let mut imported = 0;
+    let mut last_version = None;
+    let mut sealer = ImportSealer::default();
+
+    while let Some(mut event) = events.next().await? {
+        check_next_version(last_version, &event, events.line_number)?;
+        last_version = Some(event.version);
+
+        // Imports may land under a different name than they were exported from
+        event.stream_id = stream_id.clone();
+        sealer.seal(state.storage.as_ref(), &mut event).await?;
+        batch.push(event);
+
+        if batch.len() >= IMPORT_BATCH_SIZE {
+            imported += flush(&state, &stream_id, &mut batch).await?;
+        }
+    }
+    imported += flush(&state, &stream_id, &mut batch).await?;
+
+    let version = state.storage.stream_version(&stream_id).await?;
+    info!("Imported {} events into {} (now at version {})", imported, stream_id, version);
+
+    Ok(Json(ImportResponse {
+        stream_id,
+        imported,
+        version,
+    }))
+}
+
+/// POST /admin/bulk-load — loads NDJSON events for any number of streams,
+/// keeping ids, versions and timestamps, for migrations and large replays.
+/// Each batch is one transaction (a single `COPY` on Postgres); as with
+/// imports, a failure leaves the committed batches in place. Bulk-loaded
+/// events are not published to the event bus.
+pub async fn bulk_load(State(state): State<AppState>, body: Body) -> Result<Json<BulkLoadResponse>> {
+    let started = std::time::Instant::now();
+    let mut events = NdjsonEvents::new(body);
+    let mut batch: Vec<Event> = Vec::with_capacity(BULK_BATCH_SIZE);
+    let mut last_versions: HashMap<String, i64> = HashMap::new();
+    let mut loaded = 0;
+    let mut sealer = ImportSealer::default();
+
+    while let Some(mut event) = events.next().await? {
+        if !state.stream_ids.is_valid(&event.stream_id) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid stream_id on line {}",
+                events.line_number
+            )));
+        }
+        check_next_version(last_versions.get(&event.stream_id).copied(), &event, events.line_number)?;
+        last_versions.insert(event.stream_id.clone(), event.version);
+        sealer.seal(state.storage.as_ref(), &mut event).await?;
+        batch.push(event);
+
+        if batch.len() >= BULK_BATCH_SIZE {
+            loaded += load_batch(&state, &mut batch).await?;
+        }
+    }
+    loaded += load_batch(&state, &mut batch).await?;
+
+    info!(
+        "Bulk loaded {} events into {} streams in {:.1}s",
+        loaded,
+        last_versions.len(),
+        started.elapsed().as_secs_f64()
+    );
+
+    Ok(Json(BulkLoadResponse {
+        loaded,
+        streams: last_versions.len() as u64,
+    }))
+}
+
+pub fn check_next_version(last_version: Option<i64>, event: &Event, line_number: u64) -> Result<()> {
+    match last_version {
+        Some(last) if event.version != last + 1 => Err(AppError::BadRequest(format!(
+            "Line {} has version {} of {}, expected {}",
+            line_number,
+            event.version,
+            event.stream_id,
+            last + 1
+        ))),
+        _ => Ok(()),
+    }
+}
+
+async fn load_batch(state: &AppState, batch: &mut Vec<Event>) -> Result<u64> {
+    if batch.is_empty() {
+        return Ok(0);
+    }
+
+    let loaded = state.storage.bulk_load(std::mem::take(batch)).await?;
+    state.metrics.events_bulk_loaded.inc_by(loaded);
+    // Bulk loads bypass the bus, so cached pages aren't invalidated per stream
+    if let Some(cache) = &state.read_cache {
+        cache.clear();
+    }
+    Ok(loaded)
+}
+
+async fn flush(state: &AppState, stream_id: &str, batch: &mut Vec<Event>) -> Result<u64> {
+    if batch.is_empty() {
+        return Ok(0);
+    }
+
+    let stored = state.storage.import_stream(stream_id, std::mem::take(batch)).await?;
+    let count = stored.len() as u64;
+    for event in stored {
+        state.bus.publish(event).await;
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,241 @@
+use async_graphql::futures_util::{stream, Stream, StreamExt};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, Semaphore};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::auth::Tenant;
+use crate::error::{AppError, Result};
+use crate::metrics::Metrics;
+use crate::AppState;
+
+/// Most credits a subscription may hold unspent.
+const MAX_CREDITS: usize = 1_000_000;
+
+/// What happens when a subscriber's buffer is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubscriptionOverflow {
+    /// Drop the oldest buffered message to make room, and count it.
+    #[default]
+    DropOldest,
+    /// End the subscription; the client resubscribes from where it was.
+    Disconnect,
+}
+
+/// A subscription's credit window: one credit per message sent, topped up
+/// by the client. Subscriptions opened without a window are not credit
+/// limited and only bounded by their buffer.
+struct Window {
+    tenant: Option<String>,
+    credits: Semaphore,
+}
+
+/// Credit-limited subscriptions open on this instance, by id.
+#[derive(Default)]
+pub struct Subscriptions {
+    windows: Mutex<HashMap<Uuid, Arc<Window>>>,
+}
+
+/// What a flow-controlled subscription yields.
+pub enum Delivery<T> {
+    Message(T),
+    /// The buffer overflowed under `SubscriptionOverflow::Disconnect`; the
+    /// subscription ends after this.
+    Overflowed,
+}
+
+struct Buffer<T> {
+    messages: VecDeque<T>,
+    done: bool,
+    overflowed: bool,
+}
+
+/// State owned by the subscriber's side; dropping it, when the client goes
+/// away, stops the pump and forgets the window.
+struct Subscriber<T> {
+    buffer: Arc<(Mutex<Buffer<T>>, Notify)>,
+    window: Option<Arc<Window>>,
+    subscriptions: Arc<Subscriptions>,
+    id: Uuid,
+    pump: JoinHandle<()>,
+    metrics: Metrics,
+}
+
+impl<T> Drop for Subscriber<T> {
+    fn drop(&mut self) {
+        self.pump.abort();
+        self.metrics.subscriptions_open.dec();
+        self.subscriptions.windows.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl<T> Subscriber<T> {
+    async fn next(&mut self) -> Option<Delivery<T>> {
+        let (buffer, ready) = &*self.buffer;
+        let message = loop {
+            {
+                let mut buffer = buffer.lock().unwrap();
+                if let Some(message) = buffer.messages.pop_front() {
+                    break message;
+                }
+                if buffer.overflowed {
+                    buffer.overflowed = false;
+                    buffer.done = true;
+                    return Some(Delivery::Overflowed);
+                }
+                if buffer.done {
+                    return None;
+                }
+            }
+            ready.notified().await;
+        };
+
+        if let Some(window) = &self.window {
+            window.credits.acquire().await.ok()?.forget();
+        }
+        Some(Delivery::Message(message))
+    }
+}
+
+/// Puts a bounded buffer between `source` and the client, applying the
+/// configured overflow policy when the client reads slower than `source`
+/// produces. With `window`, the client also has to grant credits before
+/// more than that many messages are sent.
+pub fn control<T, S>(
+    state: &AppState,
+    id: Uuid,
+    tenant: &Tenant,
+    source: S,
+    window: Option<usize>,
+) -> impl Stream<Item = Delivery<T>>
+where
+    T: Send + 'static,
+    S: Stream<Item = T> + Send + 'static,
+{
+    let subscriptions = state.subscriptions.clone();
+    let window = window.map(|credits| {
+        let window = Arc::new(Window {
+            tenant: tenant.0.clone(),
+            credits: Semaphore::new(credits.min(MAX_CREDITS)),
+        });
+        subscriptions.windows.lock().unwrap().insert(id, window.clone());
+        window
+    });
+
+    let buffer_size = state.config.subscription_buffer_size;
+    let buffer = Arc::new((
+        Mutex::new(Buffer {
+            messages: VecDeque::with_capacity(buffer_size),
+            done: false,
+            overflowed: false,
+        }),
+        Notify::new(),
+    ));
+    let pump = tokio::spawn(pump(
+        source,
+        buffer.clone(),
+        buffer_size,
+        state.config.subscription_overflow,
+        state.metrics.clone(),
+    ));
+
+    let subscriber = Subscriber {
+        buffer,
+        window,
+        subscriptions,
+        id,
+        pump,
+        metrics: state.metrics.clone(),
+    };
+    state.metrics.subscriptions_open.inc();
+    stream::unfold(subscriber, |mut subscriber| async move {
+        subscriber.next().await.map(|delivery| (delivery, subscriber))
+    })
+}
+
+/// Moves messages from `source` into the subscriber's buffer as fast as
+/// they come, whether or not the subscriber keeps up.
+async fn pump<T, S>(
+    source: S,
+    buffer: Arc<(Mutex<Buffer<T>>, Notify)>,
+    buffer_size: usize,
+    overflow: SubscriptionOverflow,
+    metrics: Metrics,
+) where
+    S: Stream<Item = T>,
+{
+    let (shared, ready) = &*buffer;
+    let mut source = Box::pin(source);
+
+    while let Some(message) = source.next().await {
+        {
+            let mut shared = shared.lock().unwrap();
+            if shared.messages.len() >= buffer_size {
+                match overflow {
+                    SubscriptionOverflow::DropOldest => {
+                        shared.messages.pop_front();
+                        metrics.subscription_messages_dropped.inc();
+                    }
+                    SubscriptionOverflow::Disconnect => {
+                        shared.messages.clear();
+                        shared.overflowed = true;
+                        metrics.subscriptions_disconnected.inc();
+                        ready.notify_one();
+                        return;
+                    }
+                }
+            }
+            shared.messages.push_back(message);
+        }
+        ready.notify_one();
+    }
+
+    shared.lock().unwrap().done = true;
+    ready.notify_one();
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreditGrant {
+    pub credits: usize,
+}
+
+/// POST /graphql/stream/:id/credits — lets a credit-limited subscription
+/// send `credits` more messages.
+pub async fn grant_credits(
+    Path(id): Path<Uuid>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(grant): Json<CreditGrant>,
+) -> Result<Response> {
+    let not_found = || AppError::NotFound(format!("Subscription {} not found", id));
+    let window = state
+        .subscriptions
+        .windows
+        .lock()
+        .unwrap()
+        .get(&id)
+        .cloned()
+        .ok_or_else(not_found)?;
+    // Other tenants' subscriptions don't exist as far as the caller can tell
+    if tenant.0.is_some() && tenant.0 != window.tenant {
+        return Err(not_found());
+    }
+
+    if window.credits.available_permits() + grant.credits > MAX_CREDITS {
+        return Err(AppError::BadRequest(format!(
+            "A subscription can hold at most {} unspent credits",
+            MAX_CREDITS
+        )));
+    }
+    window.credits.add_permits(grant.credits);
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
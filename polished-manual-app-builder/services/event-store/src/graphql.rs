@@ -0,0 +1,400 @@
+use async_graphql::futures_util::{stream, Stream, StreamExt};
+use async_graphql::{
+    Context, EmptyMutation, ErrorExtensions, Json as GraphqlJson, Object, Schema, SimpleObject, Subscription,
+};
+use async_trait::async_trait;
+use axum::{
+    extract::{Query, State},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        Json,
+    },
+};
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::auth::Tenant;
+use crate::bus::BusConsumer;
+use crate::deadline::Deadline;
+use crate::error::AppError;
+use crate::event_filter::EventFilter;
+use crate::flow_control::{self, Delivery};
+use crate::models::Event;
+use crate::snapshots::SnapshotView;
+use crate::storage::ReadDirection;
+use crate::{cold_storage, encryption, AppState};
+
+/// Events read per page while filtering a stream.
+const PAGE_SIZE: i64 = 1_000;
+/// Largest `limit` accepted for `Stream.events`.
+const MAX_EVENTS: i64 = 1_000;
+/// Events queued for a live subscription before it starts skipping.
+const LIVE_BUFFER: usize = 1_024;
+
+pub type GraphqlSchema = Schema<QueryRoot, EmptyMutation, SubscriptionRoot>;
+
+/// The schema has no mutations: writes stay on the REST API, where
+/// idempotency, quotas and deprecation warnings live.
+pub fn schema(live: LiveEvents) -> GraphqlSchema {
+    Schema::build(QueryRoot, EmptyMutation, SubscriptionRoot)
+        .data(live)
+        .limit_depth(10)
+        .limit_complexity(1_000)
+        .finish()
+}
+
+/// Keeps the problem code and category from `GET /errors/catalog` in
+/// `extensions.code` and `extensions.category`, as in REST problem responses.
+fn graphql_error(e: AppError) -> async_graphql::Error {
+    (&e).extend_with(|_, extensions| {
+        extensions.set("code", e.problem_code());
+        extensions.set("category", e.code().as_str());
+    })
+}
+
+/// Committed events fanned out to live subscriptions.
+#[derive(Clone)]
+pub struct LiveEvents(broadcast::Sender<Arc<Event>>);
+
+impl LiveEvents {
+    pub fn new() -> Self {
+        Self(broadcast::channel(LIVE_BUFFER).0)
+    }
+}
+
+#[async_trait]
+impl BusConsumer for LiveEvents {
+    fn name(&self) -> &str {
+        "graphql"
+    }
+
+    async fn handle(&self, event: &Event) {
+        // No receivers just means no one is subscribed
+        let _ = self.0.send(Arc::new(event.clone()));
+    }
+}
+
+struct EventNode(Event);
+
+#[Object(name = "Event")]
+impl EventNode {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn stream_id(&self) -> &str {
+        &self.0.stream_id
+    }
+
+    async fn event_type(&self) -> &str {
+        &self.0.event_type
+    }
+
+    async fn version(&self) -> i64 {
+        self.0.version
+    }
+
+    async fn position(&self) -> i64 {
+        self.0.position
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn content_type(&self) -> &str {
+        &self.0.content_type
+    }
+
+    /// The event's JSON body; null for binary events.
+    async fn data(&self) -> GraphqlJson<Value> {
+        GraphqlJson(self.0.data.clone())
+    }
+
+    async fn metadata(&self) -> Option<GraphqlJson<Value>> {
+        self.0.metadata.clone().map(GraphqlJson)
+    }
+
+    /// The body of a binary event, base64-encoded.
+    async fn payload(&self) -> Option<String> {
+        self.0
+            .payload
+            .as_ref()
+            .map(|bytes| base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Snapshot")]
+struct SnapshotNode {
+    version: i64,
+    created_at: DateTime<Utc>,
+    data: GraphqlJson<Value>,
+}
+
+impl From<SnapshotView> for SnapshotNode {
+    fn from(view: SnapshotView) -> Self {
+        Self {
+            version: view.version,
+            created_at: view.created_at,
+            data: GraphqlJson(view.data),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Stats")]
+struct StatsNode {
+    total_events: i64,
+    total_streams: i64,
+    total_snapshots: i64,
+    storage_bytes: Option<i64>,
+    events_per_day: Vec<DailyCountNode>,
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "DailyCount")]
+struct DailyCountNode {
+    day: NaiveDate,
+    events: i64,
+}
+
+struct StreamNode {
+    id: String,
+    version: i64,
+}
+
+#[Object(name = "Stream")]
+impl StreamNode {
+    async fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn version(&self) -> i64 {
+        self.version
+    }
+
+    async fn metadata(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<GraphqlJson<Value>>> {
+        let state = ctx.data::<AppState>()?;
+        let metadata = state.storage.stream_metadata(&self.id).await.map_err(graphql_error)?;
+        Ok(metadata.map(|metadata| GraphqlJson(serde_json::to_value(metadata).unwrap_or_default())))
+    }
+
+    /// Events from `fromVersion` on, oldest first, skipping those `filter`
+    /// rejects until `limit` match or the stream ends.
+    async fn events(
+        &self,
+        ctx: &Context<'_>,
+        from_version: Option<i64>,
+        #[graphql(default = 100)] limit: i64,
+        filter: Option<EventFilter>,
+        #[graphql(default = false)] include_archived: bool,
+    ) -> async_graphql::Result<Vec<EventNode>> {
+        let state = ctx.data::<AppState>()?;
+        let deadline = *ctx.data::<Deadline>()?;
+        let filter = filter.unwrap_or_default().compile().map_err(graphql_error)?;
+        let limit = limit.clamp(0, MAX_EVENTS) as usize;
+
+        let visible_from = crate::visible_from(state, &self.id).await.map_err(graphql_error)?;
+        let mut next = from_version.unwrap_or(1).max(visible_from).max(1);
+        let mut events = Vec::new();
+        while events.len() < limit && next <= self.version {
+            let page = cold_storage::read_stream(
+                state,
+                &self.id,
+                next,
+                PAGE_SIZE,
+                ReadDirection::Forward,
+                include_archived,
+                deadline,
+            )
+            .await
+            .map_err(graphql_error)?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            next = last.version + 1;
+            let short = (page.len() as i64) < PAGE_SIZE;
+            events.extend(page.into_iter().filter(|e| filter.matches(e)));
+            if short {
+                break;
+            }
+        }
+        events.truncate(limit);
+
+        encryption::decrypt_events(state, &mut events).await.map_err(graphql_error)?;
+        Ok(events.into_iter().map(EventNode).collect())
+    }
+
+    /// The newest snapshot at or before `version`, or the latest.
+    async fn snapshot(&self, ctx: &Context<'_>, version: Option<i64>) -> async_graphql::Result<Option<SnapshotNode>> {
+        let state = ctx.data::<AppState>()?;
+        let snapshot = match version {
+            Some(version) => state.storage.snapshot_at(&self.id, version).await,
+            None => state.storage.latest_snapshot(&self.id).await,
+        }
+        .map_err(graphql_error)?;
+
+        Ok(snapshot
+            .map(|snapshot| SnapshotView::new(snapshot, &state.snapshot_codec))
+            .transpose()
+            .map_err(graphql_error)?
+            .map(SnapshotNode::from))
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A stream, or null if it has no events.
+    async fn stream(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Option<StreamNode>> {
+        let state = ctx.data::<AppState>()?;
+        ctx.data::<Tenant>()?.authorize(&id).map_err(graphql_error)?;
+
+        let version = state.storage.stream_version(&id).await.map_err(graphql_error)?;
+        Ok((version > 0).then_some(StreamNode { id, version }))
+    }
+
+    /// Totals for the caller's tenant, or the whole store for admins.
+    async fn stats(&self, ctx: &Context<'_>) -> async_graphql::Result<StatsNode> {
+        let state = ctx.data::<AppState>()?;
+        let stats = match &ctx.data::<Tenant>()?.0 {
+            Some(tenant_id) => state.storage.tenant_stats(tenant_id).await,
+            None => state.storage.stats().await,
+        }
+        .map_err(graphql_error)?;
+
+        Ok(StatsNode {
+            total_events: stats.total_events,
+            total_streams: stats.total_streams,
+            total_snapshots: stats.total_snapshots,
+            storage_bytes: stats.storage_bytes,
+            events_per_day: stats
+                .events_per_day
+                .into_iter()
+                .map(|count| DailyCountNode {
+                    day: count.day,
+                    events: count.events,
+                })
+                .collect(),
+        })
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Events as they commit, on one stream or on every stream the caller
+    /// can read, narrowed by `filter` before they're sent. A subscriber that
+    /// falls behind skips events rather than holding up the others.
+    async fn events(
+        &self,
+        ctx: &Context<'_>,
+        stream_id: Option<String>,
+        event_types: Option<Vec<String>>,
+        filter: Option<EventFilter>,
+    ) -> async_graphql::Result<impl Stream<Item = EventNode>> {
+        let state = ctx.data::<AppState>()?.clone();
+        let tenant = ctx.data::<Tenant>()?.clone();
+        if let Some(stream_id) = &stream_id {
+            tenant.authorize(stream_id).map_err(graphql_error)?;
+        }
+        let matcher = Arc::new(filter.unwrap_or_default().compile().map_err(graphql_error)?);
+        let receiver = ctx.data::<LiveEvents>()?.0.subscribe();
+
+        let wanted = move |event: &Event| {
+            stream_id.as_ref().map_or(true, |id| *id == event.stream_id)
+                && event_types.as_ref().map_or(true, |types| types.contains(&event.event_type))
+                && tenant.authorize(&event.stream_id).is_ok()
+                && matcher.matches(event)
+        };
+        Ok(stream::unfold(receiver, move |mut receiver| {
+            let state = state.clone();
+            let wanted = wanted.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) if wanted(&event) => {
+                            let mut events = [event.as_ref().clone()];
+                            if encryption::decrypt_events(&state, &mut events).await.is_err() {
+                                continue;
+                            }
+                            let [event] = events;
+                            return Some((EventNode(event), receiver));
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        }))
+    }
+}
+
+fn with_context(
+    request: async_graphql::Request,
+    state: &AppState,
+    tenant: Tenant,
+    deadline: Deadline,
+) -> async_graphql::Request {
+    request.data(state.clone()).data(tenant).data(deadline)
+}
+
+/// POST /graphql — queries; results are scoped to the caller's tenant.
+pub async fn graphql(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    deadline: Deadline,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let request = with_context(request, &state, tenant, deadline);
+    Json(state.graphql.execute(request).await)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    /// Responses sent before the client has to grant more credits with
+    /// POST /graphql/stream/:id/credits; unlimited when unset.
+    pub window: Option<usize>,
+}
+
+/// POST /graphql/stream?window= — subscriptions (and queries) over
+/// server-sent events, one `data:` line per response. The first event,
+/// `subscription`, carries the id credits are granted to. A client that
+/// falls too far behind loses responses or the connection, per
+/// `SUBSCRIPTION_OVERFLOW`.
+pub async fn graphql_stream(
+    Query(query): Query<StreamQuery>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<async_graphql::Request>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let id = Uuid::new_v4();
+    state.system_streams.subscription_created(id, tenant.0.as_deref(), query.window).await;
+    // Subscriptions outlive any request deadline
+    let request = with_context(request, &state, tenant.clone(), Deadline(None));
+    let responses = state.graphql.execute_stream(request);
+
+    let opened = SseEvent::default()
+        .event("subscription")
+        .data(json!({ "id": id, "window": query.window }).to_string());
+    let messages = flow_control::control(&state, id, &tenant, responses, query.window).map(|delivery| {
+        Ok(match delivery {
+            Delivery::Message(response) => {
+                SseEvent::default().data(serde_json::to_string(&response).unwrap_or_default())
+            }
+            Delivery::Overflowed => SseEvent::default()
+                .event("overflow")
+                .data("The subscriber fell too far behind; resubscribe to continue"),
+        })
+    });
+    Sse::new(stream::once(async { Ok(opened) }).chain(messages)).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,128 @@
+use axum::{extract::State, http::StatusCode, response::Json};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::circuit_breaker::BreakerState;
+use crate::AppState;
+
+/// How long the readiness probe waits for the database.
+const DB_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the readiness probe knows beyond the database itself: whether
+/// migrations ran, and the background tasks that should run forever.
+#[derive(Debug, Default)]
+pub struct Health {
+    migrated: AtomicBool,
+    tasks: Mutex<Vec<(&'static str, JoinHandle<()>)>>,
+}
+
+impl Health {
+    pub fn set_migrated(&self) {
+        self.migrated.store(true, Ordering::Relaxed);
+    }
+
+    /// Tracks a background task; the service is not ready once it exits.
+    pub fn watch(&self, name: &'static str, task: JoinHandle<()>) {
+        self.tasks.lock().unwrap().push((name, task));
+    }
+}
+
+/// GET /health/live (and /health): the process is up and serving. Never
+/// checks dependencies, so a database outage doesn't get the pod restarted.
+pub async fn live() -> Json<Value> {
+    Json(json!({
+        "status": "healthy",
+        "service": "event-store",
+        "version": "1.0.0",
+        "timestamp": Utc::now()
+    }))
+}
+
+/// GET /health/ready: 200 when the service can take traffic, 503 otherwise,
+/// with the status of each component in the body.
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let mut ready = true;
+
+    let started = Instant::now();
+    let database = match tokio::time::timeout(DB_PING_TIMEOUT, state.storage.ping()).await {
+        Ok(Ok(())) => json!({ "status": "up", "latency_ms": started.elapsed().as_millis() as u64 }),
+        Ok(Err(e)) => {
+            ready = false;
+            json!({ "status": "down", "error": e.to_string() })
+        }
+        Err(_) => {
+            ready = false;
+            json!({ "status": "down", "error": format!("no response within {:?}", DB_PING_TIMEOUT) })
+        }
+    };
+
+    // The probe's ping is the breaker's view of the database between requests
+    let circuit_breaker = match &state.circuit_breaker {
+        Some(breaker) => {
+            if database["status"] == "up" {
+                breaker.record_success();
+            } else {
+                breaker.record_failure();
+            }
+            let (breaker_state, retry_after) = breaker.status();
+            ready &= breaker_state != BreakerState::Open;
+            json!({ "status": breaker_state, "retry_after_seconds": retry_after })
+        }
+        None => json!({ "status": "disabled" }),
+    };
+
+    let migrated = state.health.migrated.load(Ordering::Relaxed);
+    ready &= migrated;
+    let migrations = json!({ "status": if migrated { "up" } else { "pending" } });
+
+    let tasks: serde_json::Map<String, Value> = state
+        .health
+        .tasks
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, task)| {
+            // These loop forever; a finished one has panicked or bailed out
+            let running = !task.is_finished();
+            ready &= running;
+            (name.to_string(), json!(if running { "running" } else { "stopped" }))
+        })
+        .collect();
+
+    let max_lag = state.config.ready_max_replication_lag_seconds;
+    let replication = match state.storage.replication_lag().await {
+        Ok(Some(lag)) if lag > max_lag => {
+            ready = false;
+            json!({ "status": "lagging", "lag_seconds": lag, "max_lag_seconds": max_lag })
+        }
+        Ok(Some(lag)) => json!({ "status": "up", "lag_seconds": lag, "max_lag_seconds": max_lag }),
+        Ok(None) => json!({ "status": "none" }),
+        // Already reported under database when the backend is down
+        Err(e) => json!({ "status": "unknown", "error": e.to_string() }),
+    };
+
+    // Followers stay ready: they serve reads, and writes reaching them are fenced
+    let leadership = match &state.leadership {
+        Some(leadership) => leadership.status(),
+        None => json!({ "status": "disabled" }),
+    };
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "timestamp": Utc::now(),
+        "components": {
+            "database": database,
+            "migrations": migrations,
+            "background_tasks": tasks,
+            "replication": replication,
+            "circuit_breaker": circuit_breaker,
+            "leadership": leadership,
+        }
+    });
+    (status, Json(body))
+}
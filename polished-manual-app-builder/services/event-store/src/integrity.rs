@@ -0,0 +1,407 @@
+use axum::{extract::State, response::Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::models::{AnchoredHead, Event};
+use crate::storage::{EventStorage, NewEvent, ReadDirection};
+use crate::AppState;
+
+/// Events read per query while verifying.
+const VERIFY_PAGE_SIZE: i64 = 1_000;
+
+/// Events one `POST /admin/verify` checks unless it asks for fewer.
+const VERIFY_DEFAULT_LIMIT: u64 = 100_000;
+
+/// Hex SHA-256 of an event body as stored: the payload bytes of a non-JSON
+/// event, otherwise the canonical form of its JSON data. Taken after
+/// encryption and offloading, so it covers exactly what the store keeps.
+pub fn checksum(data: Option<&Value>, payload: Option<&[u8]>) -> String {
+    let digest = match payload {
+        Some(payload) => Sha256::digest(payload),
+        None => Sha256::digest(canonical_json(data.unwrap_or(&Value::Null))),
+    };
+    hex::encode(digest)
+}
+
+/// What an event's chain hash covers: its id, version, types, timestamp,
+/// metadata and body checksum, plus the chain hash of the event before it
+/// in the stream. The stream name and log position are left out, so a
+/// stream exported and imported under another name keeps its chain.
+pub struct Link<'a> {
+    id: Uuid,
+    version: i64,
+    event_type: &'a str,
+    content_type: &'a str,
+    created_at: DateTime<Utc>,
+    metadata: Option<&'a Value>,
+    checksum: &'a str,
+}
+
+impl<'a> Link<'a> {
+    pub fn new_event(event: &'a NewEvent, version: i64) -> Self {
+        Self {
+            id: event.id,
+            version,
+            event_type: &event.event_type,
+            content_type: &event.content_type,
+            created_at: event.created_at,
+            metadata: event.metadata.as_ref(),
+            checksum: &event.checksum,
+        }
+    }
+
+    /// `None` for an event without a checksum.
+    pub fn event(event: &'a Event) -> Option<Self> {
+        Some(Self {
+            id: event.id,
+            version: event.version,
+            event_type: &event.event_type,
+            content_type: &event.content_type,
+            created_at: event.created_at,
+            metadata: event.metadata.as_ref(),
+            checksum: event.checksum.as_deref()?,
+        })
+    }
+
+    /// Hex SHA-256 of the link following `previous`, which is `None` at the
+    /// start of a chain. Timestamps count to the microsecond, which is all
+    /// Postgres keeps.
+    pub fn hash(&self, previous: Option<&str>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "{}\n{}\n{}\n{}\n{}\n{}\n{}\n",
+            previous.unwrap_or(""),
+            self.id,
+            self.version,
+            self.event_type,
+            self.content_type,
+            self.created_at.timestamp_micros(),
+            self.checksum
+        ));
+        hasher.update(canonical_json(self.metadata.unwrap_or(&Value::Null)));
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Fills in the checksum and chain hash of imported events that arrive
+/// without them, e.g. from an export taken before they were kept, carrying
+/// each stream's chain on from its head. Events that bring their own keep
+/// them, so anything altered since the export shows up in verification.
+#[derive(Debug, Default)]
+pub struct ImportSealer {
+    /// Chain hash of the last event sealed, per stream.
+    previous: HashMap<String, Option<String>>,
+}
+
+impl ImportSealer {
+    pub async fn seal(&mut self, storage: &dyn EventStorage, event: &mut Event) -> Result<()> {
+        if event.checksum.is_none() {
+            event.checksum = Some(checksum(Some(&event.data), event.payload.as_deref()));
+        }
+
+        let previous = match self.previous.get(&event.stream_id) {
+            Some(previous) => previous.clone(),
+            None => {
+                let mut head = storage.read_latest(&event.stream_id, 1, Deadline(None)).await?;
+                head.pop().and_then(|head| head.chain_hash)
+            }
+        };
+        if event.chain_hash.is_none() {
+            let chain_hash = Link::event(event).map(|link| link.hash(previous.as_deref()));
+            event.chain_hash = chain_hash;
+        }
+        self.previous.insert(event.stream_id.clone(), event.chain_hash.clone());
+        Ok(())
+    }
+}
+
+pub fn canonical_json(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+/// Compact JSON with object keys sorted, and numbers written the way they
+/// read back from JSONB: an integral float comes back as an integer.
+fn write_canonical(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(b']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push(b'{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                out.extend_from_slice(Value::from(key.as_str()).to_string().as_bytes());
+                out.push(b':');
+                write_canonical(value, out);
+            }
+            out.push(b'}');
+        }
+        Value::Number(number) => match number.as_f64() {
+            Some(f) if number.is_f64() && f.fract() == 0.0 && f.abs() < (1u64 << 53) as f64 => {
+                out.extend_from_slice((f as i64).to_string().as_bytes())
+            }
+            _ => out.extend_from_slice(number.to_string().as_bytes()),
+        },
+        scalar => out.extend_from_slice(scalar.to_string().as_bytes()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyRequest {
+    /// Verify one stream by version instead of the global log by position.
+    pub stream_id: Option<String>,
+    /// First position (or version) to check; the start of the log when unset.
+    #[serde(default)]
+    pub from: i64,
+    /// Last position (or version) to check; the head when unset.
+    pub to: Option<i64>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Corruption {
+    pub id: Uuid,
+    pub stream_id: String,
+    pub version: i64,
+    pub position: i64,
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    pub checked: u64,
+    /// Events appended before checksums were stored, which can't be checked.
+    pub unverifiable: u64,
+    pub corrupted: Vec<Corruption>,
+    /// Where to continue from when the limit was reached before `to`.
+    pub next: Option<i64>,
+}
+
+impl VerifyReport {
+    fn check(&mut self, event: &Event) {
+        let Some(expected) = &event.checksum else {
+            self.unverifiable += 1;
+            return;
+        };
+        self.checked += 1;
+
+        let actual = checksum(Some(&event.data), event.payload.as_deref());
+        if actual != *expected {
+            error!(
+                "Event {} ({} v{}, position {}) does not match its checksum",
+                event.id, event.stream_id, event.version, event.position
+            );
+            self.corrupted.push(Corruption {
+                id: event.id,
+                stream_id: event.stream_id.clone(),
+                version: event.version,
+                position: event.position,
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+}
+
+/// POST /admin/verify — re-hashes a range of the log, or of one stream, and
+/// reports the events whose body no longer matches the checksum stored with
+/// it. Archived events aren't read. Large ranges are checked `limit` events
+/// at a time; call again from `next` to carry on.
+pub async fn verify(State(state): State<AppState>, Json(request): Json<VerifyRequest>) -> Result<Json<VerifyReport>> {
+    let limit = request.limit.unwrap_or(VERIFY_DEFAULT_LIMIT);
+    if limit == 0 {
+        return Err(AppError::BadRequest("limit must be positive".to_string()));
+    }
+    let to = request.to.unwrap_or(i64::MAX);
+
+    let mut report = VerifyReport::default();
+    let mut next = request.from.max(1);
+    let mut seen = 0;
+    while next <= to {
+        if seen >= limit {
+            report.next = Some(next);
+            break;
+        }
+        let page_size = VERIFY_PAGE_SIZE.min((limit - seen) as i64);
+        let page = match &request.stream_id {
+            Some(stream_id) => {
+                state
+                    .storage
+                    .read_stream(stream_id, next, page_size, ReadDirection::Forward, Deadline(None))
+                    .await?
+            }
+            None => state.storage.read_all(next - 1, page_size).await?,
+        };
+        let Some(last) = page.last() else {
+            break;
+        };
+        next = match &request.stream_id {
+            Some(_) => last.version + 1,
+            None => last.position + 1,
+        };
+
+        for event in &page {
+            let at = match &request.stream_id {
+                Some(_) => event.version,
+                None => event.position,
+            };
+            if at > to {
+                break;
+            }
+            report.check(event);
+            seen += 1;
+        }
+    }
+
+    if !report.corrupted.is_empty() {
+        state.metrics.checksum_mismatches.inc_by(report.corrupted.len() as u64);
+    }
+    info!(
+        "Verified {} events: {} corrupted, {} without a checksum",
+        report.checked,
+        report.corrupted.len(),
+        report.unverifiable
+    );
+    Ok(Json(report))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChainVerifyRequest {
+    pub stream_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainBreak {
+    pub id: Uuid,
+    pub version: i64,
+    pub reason: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainReport {
+    pub stream_id: String,
+    pub checked: u64,
+    /// Events appended before the chain, which can't be checked.
+    pub unchained: u64,
+    /// First version still in the store; the chain is checked from there.
+    pub starts_at: Option<i64>,
+    pub breaks: Vec<ChainBreak>,
+    /// The last link, to compare with an anchor or an earlier report.
+    pub head: Option<AnchoredHead>,
+}
+
+/// POST /admin/verify/chain — walks a stream's hash chain, archived events
+/// included, and reports every event whose body doesn't match its checksum
+/// or whose chain hash doesn't follow from the event before it. Where the
+/// oldest versions are gone, the chain is taken on trust from the first
+/// event left; anchors cover what comes before.
+pub async fn verify_chain(
+    State(state): State<AppState>,
+    Json(request): Json<ChainVerifyRequest>,
+) -> Result<Json<ChainReport>> {
+    let stream_id = request.stream_id;
+    let mut report = ChainReport {
+        stream_id: stream_id.clone(),
+        checked: 0,
+        unchained: 0,
+        starts_at: None,
+        breaks: Vec::new(),
+        head: None,
+    };
+    let mut previous: Option<String> = None;
+    let mut chained = false;
+    let mut check = |event: Event| {
+        let first = report.starts_at.is_none();
+        if first {
+            report.starts_at = Some(event.version);
+        }
+
+        let Some(chain_hash) = &event.chain_hash else {
+            if chained {
+                report.breaks.push(ChainBreak {
+                    id: event.id,
+                    version: event.version,
+                    reason: "missing chain hash",
+                });
+            } else {
+                report.unchained += 1;
+            }
+            return;
+        };
+        report.checked += 1;
+        chained = true;
+
+        let intact = event.checksum.as_deref() == Some(&checksum(Some(&event.data), event.payload.as_deref()));
+        let follows = (first && event.version > 1)
+            || Link::event(&event).is_some_and(|link| link.hash(previous.as_deref()) == *chain_hash);
+        if !intact || !follows {
+            error!("Event {} ({} v{}) breaks the hash chain", event.id, event.stream_id, event.version);
+            report.breaks.push(ChainBreak {
+                id: event.id,
+                version: event.version,
+                reason: if intact {
+                    "chain hash does not follow from the event before"
+                } else {
+                    "body does not match its checksum"
+                },
+            });
+        }
+
+        previous = Some(chain_hash.clone());
+        report.head = Some(AnchoredHead {
+            stream_id: event.stream_id.clone(),
+            version: event.version,
+            chain_hash: chain_hash.clone(),
+        });
+    };
+
+    let archived = state.storage.read_archived(&stream_id, 1, i64::MAX).await?;
+    let mut next = archived.last().map_or(1, |event| event.version + 1);
+    archived.into_iter().for_each(&mut check);
+    loop {
+        let page = state
+            .storage
+            .read_stream(&stream_id, next, VERIFY_PAGE_SIZE, ReadDirection::Forward, Deadline(None))
+            .await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        next = last.version + 1;
+        page.into_iter().for_each(&mut check);
+    }
+
+    if report.starts_at.is_none() {
+        return Err(AppError::NotFound(format!("Stream {} not found", stream_id)));
+    }
+    if !report.breaks.is_empty() {
+        state.metrics.chain_breaks.inc_by(report.breaks.len() as u64);
+    }
+    info!(
+        "Verified the hash chain of {}: {} events, {} breaks",
+        stream_id,
+        report.checked,
+        report.breaks.len()
+    );
+    Ok(Json(report))
+}
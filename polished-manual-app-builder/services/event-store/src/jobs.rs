@@ -0,0 +1,205 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::{error, info};
+
+use crate::error::{AppError, Result};
+use crate::AppState;
+
+/// Outcome of a job's most recent run.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobResult {
+    pub ok: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobStatus {
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_result: Option<JobResult>,
+}
+
+/// A periodic maintenance task that operators can pause or run on demand.
+#[derive(Debug)]
+pub struct Job {
+    interval: Duration,
+    paused: AtomicBool,
+    trigger: Notify,
+    status: Mutex<JobStatus>,
+}
+
+impl Job {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            paused: AtomicBool::new(false),
+            trigger: Notify::new(),
+            status: Mutex::new(JobStatus::default()),
+        }
+    }
+
+    async fn record<Fut>(&self, name: &str, run: Fut)
+    where
+        Fut: Future<Output = Result<String>>,
+    {
+        let started = Instant::now();
+        {
+            let mut status = self.status.lock().unwrap();
+            status.running = true;
+            status.last_started_at = Some(Utc::now());
+        }
+
+        let result = run.await;
+
+        let mut status = self.status.lock().unwrap();
+        status.running = false;
+        status.runs += 1;
+        status.last_duration_ms = Some(started.elapsed().as_millis() as u64);
+        status.last_result = Some(match result {
+            Ok(message) => {
+                info!("Job {}: {}", name, message);
+                JobResult { ok: true, message }
+            }
+            Err(e) => {
+                error!("Job {} failed: {}", name, e);
+                status.failures += 1;
+                JobResult {
+                    ok: false,
+                    message: e.to_string(),
+                }
+            }
+        });
+    }
+}
+
+/// The background jobs started by `serve`, by name.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: Mutex<BTreeMap<&'static str, Arc<Job>>>,
+}
+
+impl Jobs {
+    pub fn register(&self, name: &'static str, interval: Duration) -> Arc<Job> {
+        let job = Arc::new(Job::new(interval));
+        self.jobs.lock().unwrap().insert(name, job.clone());
+        job
+    }
+
+    /// Per job, seconds past the time its next scheduled run was due: 0
+    /// while on schedule, growing while a run overruns its interval, the
+    /// job is paused, or its task has died.
+    pub fn lag_seconds(&self) -> Vec<(&'static str, f64)> {
+        let now = Utc::now();
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, job)| {
+                let lag = job.status.lock().unwrap().last_started_at.map_or(0.0, |started| {
+                    let since = (now - started).to_std().unwrap_or_default();
+                    since.saturating_sub(job.interval).as_secs_f64()
+                });
+                (*name, lag)
+            })
+            .collect()
+    }
+
+    fn get(&self, name: &str) -> Result<Arc<Job>> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Job {} not found", name)))
+    }
+}
+
+/// Runs `run` every `job.interval`, and whenever the job is triggered.
+/// Scheduled runs are skipped while the job is paused; triggered ones are
+/// not, so an operator can still run a paused job by hand.
+pub async fn run_periodically<F, Fut>(name: &'static str, job: Arc<Job>, mut run: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let mut interval = tokio::time::interval(job.interval);
+
+    loop {
+        let triggered = tokio::select! {
+            _ = interval.tick() => false,
+            _ = job.trigger.notified() => true,
+        };
+        if !triggered && job.paused.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        job.record(name, run()).await;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobView {
+    pub name: String,
+    pub interval_seconds: u64,
+    pub paused: bool,
+    #[serde(flatten)]
+    pub status: JobStatus,
+}
+
+fn view(name: &str, job: &Job) -> JobView {
+    JobView {
+        name: name.to_string(),
+        interval_seconds: job.interval.as_secs(),
+        paused: job.paused.load(Ordering::Relaxed),
+        status: job.status.lock().unwrap().clone(),
+    }
+}
+
+/// GET /admin/jobs
+pub async fn list_jobs(State(state): State<AppState>) -> Json<Vec<JobView>> {
+    let jobs = state.jobs.jobs.lock().unwrap();
+    Json(jobs.iter().map(|(name, job)| view(name, job)).collect())
+}
+
+/// POST /admin/jobs/:name/run: starts a run now, or as soon as the current
+/// one finishes.
+pub async fn run_job(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<JobView>)> {
+    let job = state.jobs.get(&name)?;
+    job.trigger.notify_one();
+    info!("Job {} triggered", name);
+    Ok((StatusCode::ACCEPTED, Json(view(&name, &job))))
+}
+
+/// POST /admin/jobs/:name/pause: skips scheduled runs until resumed. A run
+/// already in progress finishes.
+pub async fn pause_job(State(state): State<AppState>, Path(name): Path<String>) -> Result<Json<JobView>> {
+    let job = state.jobs.get(&name)?;
+    job.paused.store(true, Ordering::Relaxed);
+    info!("Job {} paused", name);
+    Ok(Json(view(&name, &job)))
+}
+
+/// POST /admin/jobs/:name/resume
+pub async fn resume_job(State(state): State<AppState>, Path(name): Path<String>) -> Result<Json<JobView>> {
+    let job = state.jobs.get(&name)?;
+    job.paused.store(false, Ordering::Relaxed);
+    info!("Job {} resumed", name);
+    Ok(Json(view(&name, &job)))
+}
@@ -0,0 +1,165 @@
+use serde_json::Value;
+
+use crate::error::{AppError, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+    /// Every element of an array or value of an object.
+    Wildcard,
+}
+
+/// The subset of JSONPath filters use: from the root `$`, `.name` and
+/// `['name']` select a field, `[0]` an array element, and `.*` or `[*]`
+/// every child. Recursive descent and filter expressions aren't supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let invalid = |reason: &str| AppError::BadRequest(format!("Invalid JSONPath '{}': {}", expression, reason));
+
+        let mut rest = expression
+            .trim()
+            .strip_prefix('$')
+            .ok_or_else(|| invalid("it must start with $"))?;
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if rest.starts_with("..") {
+                return Err(invalid("recursive descent is not supported"));
+            }
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                let name = &after[..end];
+                segments.push(match name {
+                    "" => return Err(invalid("empty field name")),
+                    "*" => Segment::Wildcard,
+                    name => Segment::Field(name.to_string()),
+                });
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(|| invalid("unclosed ["))?;
+                let inner = after[..end].trim();
+                segments.push(if inner == "*" {
+                    Segment::Wildcard
+                } else if let Some(name) = quoted(inner) {
+                    Segment::Field(name.to_string())
+                } else {
+                    Segment::Index(inner.parse().map_err(|_| invalid("expected an index, '*' or a quoted name"))?)
+                });
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid("expected . or ["));
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Splits `$.name...` into `name` and the path below it, rooted at `$`.
+    pub fn split_field(&self) -> Option<(&str, JsonPath)> {
+        match self.segments.first() {
+            Some(Segment::Field(name)) => Some((
+                name,
+                JsonPath {
+                    segments: self.segments[1..].to_vec(),
+                },
+            )),
+            _ => None,
+        }
+    }
+
+    /// The values the path selects in `root`; none when it leads nowhere.
+    pub fn select<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            current = current
+                .into_iter()
+                .flat_map(|value| -> Vec<&'a Value> {
+                    match (segment, value) {
+                        (Segment::Field(name), Value::Object(fields)) => fields.get(name).into_iter().collect(),
+                        (Segment::Index(index), Value::Array(items)) => items.get(*index).into_iter().collect(),
+                        (Segment::Wildcard, Value::Object(fields)) => fields.values().collect(),
+                        (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        current
+    }
+}
+
+/// The name inside `'name'` or `"name"`.
+fn quoted(s: &str) -> Option<&str> {
+    s.strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .or_else(|| s.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn select(expression: &str, root: &Value) -> Vec<Value> {
+        JsonPath::parse(expression).unwrap().select(root).into_iter().cloned().collect()
+    }
+
+    #[test]
+    fn parses_dotted_and_bracketed_segments() {
+        let path = JsonPath::parse("$.items[0]['sku'][*].*").unwrap();
+        assert_eq!(
+            path.segments,
+            [
+                Segment::Field("items".to_string()),
+                Segment::Index(0),
+                Segment::Field("sku".to_string()),
+                Segment::Wildcard,
+                Segment::Wildcard,
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_names_may_hold_dots_and_either_quote() {
+        let root = json!({ "a.b": 1, "c d": 2 });
+        assert_eq!(select("$['a.b']", &root), [json!(1)]);
+        assert_eq!(select("$[\"c d\"]", &root), [json!(2)]);
+    }
+
+    #[test]
+    fn wildcards_select_every_child() {
+        let root = json!({ "items": [{ "sku": "a" }, { "sku": "b" }, { "qty": 1 }] });
+        assert_eq!(select("$.items[*].sku", &root), [json!("a"), json!("b")]);
+        assert_eq!(select("$.items.*.qty", &root), [json!(1)]);
+        assert_eq!(select("$.*", &json!({ "x": 1 })), [json!(1)]);
+    }
+
+    #[test]
+    fn selects_nothing_where_the_path_leads_nowhere() {
+        let root = json!({ "items": [1, 2] });
+        assert!(select("$.items[5]", &root).is_empty());
+        assert!(select("$.items.sku", &root).is_empty());
+        assert!(select("$.missing[0]", &root).is_empty());
+    }
+
+    #[test]
+    fn splits_off_the_first_field() {
+        let path = JsonPath::parse("$.data.total").unwrap();
+        let (field, below) = path.split_field().unwrap();
+        assert_eq!(field, "data");
+        assert_eq!(below, JsonPath::parse("$.total").unwrap());
+        assert!(JsonPath::parse("$[0]").unwrap().split_field().is_none());
+    }
+
+    #[test]
+    fn rejects_unsupported_and_malformed_paths() {
+        for expression in ["$..sku", "$.items..sku", "$.items[0", "$[", "items", "$.", "$.a[b]", "$a"] {
+            assert!(JsonPath::parse(expression).is_err(), "{} should not parse", expression);
+        }
+    }
+}
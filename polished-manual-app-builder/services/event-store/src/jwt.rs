@@ -0,0 +1,224 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use ring::signature::{self, RsaPublicKeyComponents};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::{Role, Scope};
+
+/// Unknown `kid`s trigger a JWKS refetch at most this often.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tolerated clock skew when checking `exp` and `nbf`.
+const LEEWAY_SECONDS: i64 = 30;
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    alg: String,
+    kid: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == audience,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == audience),
+        }
+    }
+}
+
+/// The claims the event store acts on. Other claims are ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Claims {
+    pub project_id: String,
+    /// Space-separated OAuth scopes; `read`, `write` and `admin` map to roles.
+    scope: Option<String>,
+    exp: i64,
+    nbf: Option<i64>,
+    iss: Option<String>,
+    aud: Option<Audience>,
+}
+
+impl Claims {
+    /// The role the token's scopes grant. Tokens without a `scope` claim are
+    /// writers for their project; `None` if the claim names no known scope.
+    pub fn role(&self) -> Option<Role> {
+        let Some(scope) = &self.scope else {
+            return Some(Role::Writer);
+        };
+        let scopes: Vec<Scope> = scope
+            .split_whitespace()
+            .filter_map(|scope| serde_json::from_value(serde_json::Value::String(scope.to_string())).ok())
+            .collect();
+        Role::from_scopes(&scopes)
+    }
+}
+
+struct KeyCache {
+    keys: HashMap<String, Jwk>,
+    fetched_at: Option<Instant>,
+}
+
+/// Verifies RS256/RS384/RS512 bearer tokens against the keys published at
+/// `JWKS_URL`. Keys are fetched at startup and again when a token names a
+/// `kid` we have not seen, so signing key rotation needs no restart.
+pub struct JwtVerifier {
+    jwks_url: String,
+    issuer: Option<String>,
+    audience: Option<String>,
+    client: reqwest::Client,
+    cache: RwLock<KeyCache>,
+}
+
+impl JwtVerifier {
+    pub async fn from_config(config: &Config) -> Option<Self> {
+        let jwks_url = config.jwks_url.clone()?;
+        let verifier = Self {
+            jwks_url,
+            issuer: config.jwt_issuer.clone(),
+            audience: config.jwt_audience.clone(),
+            client: reqwest::Client::new(),
+            cache: RwLock::new(KeyCache {
+                keys: HashMap::new(),
+                fetched_at: None,
+            }),
+        };
+
+        match verifier.refresh().await {
+            Ok(count) => info!("JWT authentication enabled ({} keys from {})", count, verifier.jwks_url),
+            Err(e) => warn!("Failed to fetch JWKS from {}, will retry on first token: {}", verifier.jwks_url, e),
+        }
+        Some(verifier)
+    }
+
+    async fn refresh(&self) -> Result<usize> {
+        let set: JwkSet = self
+            .client
+            .get(&self.jwks_url)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::Internal(format!("JWKS fetch failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Invalid JWKS document: {}", e)))?;
+
+        let keys: HashMap<String, Jwk> = set
+            .keys
+            .into_iter()
+            .filter(|key| key.kty == "RSA")
+            .map(|key| (key.kid.clone().unwrap_or_default(), key))
+            .collect();
+        let count = keys.len();
+
+        *self.cache.write().unwrap() = KeyCache {
+            keys,
+            fetched_at: Some(Instant::now()),
+        };
+        Ok(count)
+    }
+
+    async fn key(&self, kid: &str) -> Result<Jwk> {
+        let stale = {
+            let cache = self.cache.read().unwrap();
+            if let Some(key) = cache.keys.get(kid) {
+                return Ok(key.clone());
+            }
+            cache
+                .fetched_at
+                .map_or(true, |fetched_at| fetched_at.elapsed() >= JWKS_REFRESH_INTERVAL)
+        };
+
+        if stale {
+            self.refresh().await?;
+        }
+        self.cache
+            .read()
+            .unwrap()
+            .keys
+            .get(kid)
+            .cloned()
+            .ok_or_else(|| AppError::Unauthorized(format!("Unknown signing key '{}'", kid)))
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<Claims> {
+        let invalid = |reason: &str| AppError::Unauthorized(format!("Invalid bearer token: {}", reason));
+
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("malformed"));
+        };
+
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| invalid("bad encoding"));
+        let header: Header = serde_json::from_slice(&decode(header)?).map_err(|_| invalid("bad header"))?;
+        let algorithm: &'static signature::RsaParameters = match header.alg.as_str() {
+            "RS256" => &signature::RSA_PKCS1_2048_8192_SHA256,
+            "RS384" => &signature::RSA_PKCS1_2048_8192_SHA384,
+            "RS512" => &signature::RSA_PKCS1_2048_8192_SHA512,
+            _ => return Err(invalid("unsupported algorithm")),
+        };
+
+        let jwk = self.key(header.kid.as_deref().unwrap_or_default()).await?;
+        let (Some(n), Some(e)) = (&jwk.n, &jwk.e) else {
+            return Err(invalid("signing key has no modulus or exponent"));
+        };
+        let public_key = RsaPublicKeyComponents {
+            n: decode(n)?,
+            e: decode(e)?,
+        };
+        // The signature covers `header.payload`
+        let signing_input = &token[..token.len() - signature.len() - 1];
+        public_key
+            .verify(algorithm, signing_input.as_bytes(), &decode(signature)?)
+            .map_err(|_| invalid("signature mismatch"))?;
+
+        let claims: Claims = serde_json::from_slice(&decode(payload)?).map_err(|_| invalid("bad claims"))?;
+        let now = Utc::now().timestamp();
+        if claims.exp + LEEWAY_SECONDS < now {
+            return Err(invalid("expired"));
+        }
+        if claims.nbf.is_some_and(|nbf| nbf - LEEWAY_SECONDS > now) {
+            return Err(invalid("not yet valid"));
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.iss.as_ref() != Some(issuer) {
+                return Err(invalid("wrong issuer"));
+            }
+        }
+        if let Some(audience) = &self.audience {
+            if !claims.aud.as_ref().is_some_and(|aud| aud.contains(audience)) {
+                return Err(invalid("wrong audience"));
+            }
+        }
+
+        Ok(claims)
+    }
+}
@@ -0,0 +1,100 @@
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::jobs::{self, Job};
+use crate::metrics::Metrics;
+use crate::storage::EventStorage;
+use crate::{replication, AppState};
+
+/// Active-passive failover between instances sharing a database. Each
+/// instance contends for the leader lease; the holder writes, the others
+/// serve reads and take over once it lapses. Every takeover starts a new
+/// epoch, and the storage refuses writes made under any epoch but the
+/// current one, so a leader that stalled past its lease can't slip late
+/// appends in after its successor has started writing.
+pub struct Leadership {
+    /// This process, as recorded in the lease. Random, so a restarted
+    /// instance always starts a new epoch.
+    holder: String,
+    lease: Duration,
+    /// Epoch of the lease while this instance holds it, otherwise 0.
+    epoch: AtomicI64,
+}
+
+impl Leadership {
+    /// `None` unless `leader_lease_seconds` is set.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let lease = Duration::from_secs(config.leader_lease_seconds?);
+        Some(Self {
+            holder: Uuid::new_v4().to_string(),
+            lease,
+            epoch: AtomicI64::new(0),
+        })
+    }
+
+    /// How often the lease is renewed: often enough to survive two missed
+    /// renewals before it lapses.
+    pub fn renew_interval(&self) -> Duration {
+        self.lease / 3
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.epoch.load(Ordering::SeqCst) != 0
+    }
+
+    /// The `leadership` component of `/health/ready`.
+    pub fn status(&self) -> Value {
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        json!({
+            "role": if epoch != 0 { "leader" } else { "follower" },
+            "epoch": (epoch != 0).then_some(epoch),
+            "instance": self.holder,
+        })
+    }
+
+    async fn campaign(&self, storage: &dyn EventStorage, metrics: &Metrics) -> Result<String> {
+        // On failure the lease may lapse; writes are fenced if another instance takes it
+        let epoch = storage.acquire_leadership(&self.holder, self.lease).await?.unwrap_or(0);
+        let previous = self.epoch.swap(epoch, Ordering::SeqCst);
+        if epoch != previous && epoch == 0 {
+            warn!("Lost the leader lease of epoch {}; now following", previous);
+        } else if epoch != previous {
+            info!("Took the leader lease; leading in epoch {}", epoch);
+        }
+        metrics.leader.set((epoch != 0) as i64);
+        metrics.leader_epoch.set(epoch);
+
+        Ok(match epoch {
+            0 => "Following".to_string(),
+            epoch => format!("Leading in epoch {}", epoch),
+        })
+    }
+}
+
+// Background task: Take the leader lease when it's free, and keep it renewed
+pub async fn campaign(job: Arc<Job>, leadership: Arc<Leadership>, storage: Arc<dyn EventStorage>, metrics: Metrics) {
+    jobs::run_periodically("leader_election", job, || async {
+        leadership.campaign(storage.as_ref(), &metrics).await
+    })
+    .await
+}
+
+/// Refuses writes to the event log on followers up front. The storage
+/// would fence them anyway; this spares the database the attempt.
+pub async fn leader_only(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if let Some(leadership) = &state.leadership {
+        if !leadership.is_leader() && replication::writes_events(request.method(), request.uri().path()) {
+            return AppError::Fenced("This instance is not the leader".to_string()).into_response();
+        }
+    }
+    next.run(request).await
+}
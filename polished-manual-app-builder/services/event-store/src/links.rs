@@ -0,0 +1,157 @@
+use axum::extract::{Path, Query, State};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::auth::Tenant;
+use crate::codec::{Accept, Encoded};
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::models::{AppendEventRequest, AppendQuery, Event, EventLink};
+use crate::storage::ReadDirection;
+use crate::{cold_storage, store_event, visible_from, AppState};
+
+/// Content type of a link event; its data is a `LinkTarget`.
+pub const LINK_MIME: &str = "application/vnd.event-store.link+json";
+/// Event type of every link event.
+pub const LINK_EVENT_TYPE: &str = "$>";
+
+/// Events buffered between a streamed read and the resolver.
+const RESOLVE_BUFFER: usize = 256;
+
+/// The event a link points at. Links are checked against the id too, so
+/// one never resolves to a different event that took the same version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkTarget {
+    pub stream_id: String,
+    pub version: i64,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkRequest {
+    /// Stream of the event to link to.
+    pub stream_id: String,
+    /// The event to link to, by version or by id.
+    pub version: Option<i64>,
+    pub id: Option<Uuid>,
+    pub metadata: Option<serde_json::Value>,
+    pub expected_version: Option<i64>,
+}
+
+/// POST /streams/:stream_id/links — appends a link to an existing event,
+/// which stays where it is. Reads with `?resolve_links=true` return the
+/// event itself in the link's place, so index streams can gather events
+/// from many streams without copying them.
+pub async fn append_link(
+    Path(stream_id): Path<String>,
+    Query(query): Query<AppendQuery>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    deadline: Deadline,
+    Accept(format): Accept,
+    axum::Json(request): axum::Json<LinkRequest>,
+) -> Result<Encoded<Event>> {
+    tenant.authorize(&stream_id)?;
+    tenant.authorize(&request.stream_id)?;
+
+    let original = match (request.version, request.id) {
+        (Some(version), _) => read_at(&state, &request.stream_id, version, deadline)
+            .await?
+            .filter(|event| request.id.map_or(true, |id| id == event.id)),
+        (None, Some(id)) => state.storage.find_event(&request.stream_id, id, DateTime::UNIX_EPOCH).await?,
+        (None, None) => return Err(AppError::BadRequest("A link needs the version or id of its event".to_string())),
+    };
+    let original = original
+        .filter(|event| event.content_type != LINK_MIME)
+        .ok_or_else(|| AppError::NotFound(format!("No event to link to in {}", request.stream_id)))?;
+
+    let target = LinkTarget {
+        stream_id: original.stream_id,
+        version: original.version,
+        id: original.id,
+    };
+    let link = AppendEventRequest {
+        id: None,
+        stream_id,
+        event_type: LINK_EVENT_TYPE.to_string(),
+        data: serde_json::to_value(target)?,
+        payload: None,
+        content_type: Some(LINK_MIME.to_string()),
+        metadata: request.metadata,
+        expected_version: request.expected_version,
+        ttl_seconds: None,
+        preconditions: Vec::new(),
+    };
+    let event = store_event(&state, &tenant, deadline, link, &query).await?;
+    Ok(Encoded(format, event))
+}
+
+/// The event at `version` of a stream, wherever it is kept, unless the
+/// stream was truncated past it.
+async fn read_at(state: &AppState, stream_id: &str, version: i64, deadline: Deadline) -> Result<Option<Event>> {
+    if version < visible_from(state, stream_id).await? {
+        return Ok(None);
+    }
+    let events = cold_storage::read_stream(state, stream_id, version, 1, ReadDirection::Forward, true, deadline).await?;
+    Ok(events.into_iter().next().filter(|event| event.version == version))
+}
+
+async fn resolve_event(state: &AppState, tenant: &Tenant, event: &mut Event, deadline: Deadline) -> Result<()> {
+    if event.content_type != LINK_MIME {
+        return Ok(());
+    }
+    let target: LinkTarget = serde_json::from_value(event.data.clone())
+        .map_err(|e| AppError::Internal(format!("Event {} has an unreadable link: {}", event.id, e)))?;
+    // A link the caller may not follow, or to an event since deleted, is returned as it is
+    if tenant.authorize(&target.stream_id).is_err() {
+        return Ok(());
+    }
+    let Some(mut original) = read_at(state, &target.stream_id, target.version, deadline).await? else {
+        return Ok(());
+    };
+    if original.id != target.id {
+        return Ok(());
+    }
+
+    original.link = Some(EventLink {
+        id: event.id,
+        stream_id: event.stream_id.clone(),
+        version: event.version,
+        position: event.position,
+    });
+    *event = original;
+    Ok(())
+}
+
+/// Replaces link events with the events they point at.
+pub async fn resolve_events(state: &AppState, tenant: &Tenant, events: &mut [Event], deadline: Deadline) -> Result<()> {
+    for event in events {
+        resolve_event(state, tenant, event, deadline).await?;
+    }
+    Ok(())
+}
+
+/// `resolve_events` for a streamed read.
+pub fn resolving(
+    state: AppState,
+    tenant: Tenant,
+    mut events: mpsc::Receiver<Result<Event>>,
+) -> mpsc::Receiver<Result<Event>> {
+    let (sender, receiver) = mpsc::channel(RESOLVE_BUFFER);
+
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let event = match event {
+                Ok(mut event) => resolve_event(&state, &tenant, &mut event, Deadline(None)).await.map(|_| event),
+                Err(e) => Err(e),
+            };
+            if sender.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::bus::BusConsumer;
+use crate::change_feed::{ChangeFeed, ChangeSubscription};
+use crate::error::{AppError, Result};
+use crate::get_partition_key;
+use crate::models::Event;
+
+/// Parses a `wait` duration: `30s`, `500ms`, `2m`, or plain seconds.
+pub fn parse_wait(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => value.split_at(split),
+        None => (value, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| AppError::BadRequest(format!("Invalid wait: {}", value)))?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        _ => Err(AppError::BadRequest(format!(
+            "Invalid wait: {} (use e.g. 500ms, 30s or 1m)",
+            value
+        ))),
+    }
+}
+
+/// Streams with long-polling readers parked on them. Only those streams
+/// have a channel; the last reader to leave removes it. Appends made through
+/// this instance wake readers from the event bus, and with a change feed,
+/// appends made through other instances wake them too.
+pub struct StreamWaiters {
+    channels: Mutex<HashMap<String, watch::Sender<i64>>>,
+    change_feed: Option<ChangeFeed>,
+}
+
+impl StreamWaiters {
+    pub fn new(change_feed: Option<ChangeFeed>) -> Self {
+        Self {
+            channels: Mutex::new(HashMap::new()),
+            change_feed,
+        }
+    }
+
+    /// Registers interest in `stream_id`. Subscribe before reading, so an
+    /// event committed between the read and the wait still wakes the reader.
+    pub async fn subscribe(self: &Arc<Self>, stream_id: &str) -> Waiter {
+        let changes = match &self.change_feed {
+            Some(feed) => Some(feed.subscribe(&get_partition_key(stream_id)).await),
+            None => None,
+        };
+        let receiver = self
+            .channels
+            .lock()
+            .unwrap()
+            .entry(stream_id.to_string())
+            .or_insert_with(|| watch::channel(0).0)
+            .subscribe();
+
+        Waiter {
+            waiters: self.clone(),
+            stream_id: stream_id.to_string(),
+            receiver: Some(receiver),
+            changes,
+        }
+    }
+
+    fn notify(&self, event: &Event) {
+        if let Some(sender) = self.channels.lock().unwrap().get(&event.stream_id) {
+            sender.send_replace(event.version);
+        }
+    }
+}
+
+pub struct Waiter {
+    waiters: Arc<StreamWaiters>,
+    stream_id: String,
+    receiver: Option<watch::Receiver<i64>>,
+    changes: Option<ChangeSubscription>,
+}
+
+impl Waiter {
+    /// Waits for an event on the stream, returning false on timeout.
+    pub async fn next(&mut self, timeout: Duration) -> bool {
+        let receiver = self.receiver.as_mut().expect("receiver is only taken on drop");
+        let stream_id = &self.stream_id;
+        let changes = &mut self.changes;
+
+        let from_feed = async {
+            while let Some(changes) = changes.as_mut() {
+                match changes.recv().await {
+                    Some(change) if change.stream_id == *stream_id => return,
+                    Some(_) => continue,
+                    None => break,
+                }
+            }
+            std::future::pending().await
+        };
+        let woken = async {
+            tokio::select! {
+                changed = receiver.changed() => changed.is_ok(),
+                _ = from_feed => true,
+            }
+        };
+        tokio::time::timeout(timeout, woken).await.unwrap_or(false)
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        let mut channels = self.waiters.channels.lock().unwrap();
+        self.receiver.take();
+        if channels.get(&self.stream_id).is_some_and(|s| s.receiver_count() == 0) {
+            channels.remove(&self.stream_id);
+        }
+    }
+}
+
+/// Wakes parked readers as new events commit.
+pub struct WaiterNotifier {
+    waiters: Arc<StreamWaiters>,
+}
+
+impl WaiterNotifier {
+    pub fn new(waiters: Arc<StreamWaiters>) -> Self {
+        Self { waiters }
+    }
+}
+
+#[async_trait]
+impl BusConsumer for WaiterNotifier {
+    fn name(&self) -> &str {
+        "long-poll"
+    }
+
+    async fn handle(&self, event: &Event) {
+        self.waiters.notify(event);
+    }
+}
@@ -1,146 +1,730 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
-    routing::{get, post},
-    Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    middleware,
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, head, post, put},
+    Extension, Router,
 };
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row};
-use std::{collections::HashMap, sync::Arc, time::Duration};
-use tokio::time::sleep;
+use chrono::Utc;
+use futures::StreamExt;
+use std::{collections::HashMap, future::IntoFuture, sync::Arc, time::Duration};
 use tower::ServiceBuilder;
 use tower_http::{compression::CompressionLayer, cors::CorsLayer, trace::TraceLayer};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+mod anchors;
+mod auth;
+mod backup;
+mod blobs;
+mod bus;
+mod cdc;
+mod change_feed;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod circuit_breaker;
+mod cli;
+mod codec;
+mod cold_storage;
+mod compliance;
 mod config;
+mod deadline;
+mod delivery;
+mod deprecation;
+mod encryption;
 mod error;
+mod error_budgets;
 mod error_capture;
+mod error_sinks;
+mod event_filter;
+mod export;
+mod flow_control;
+mod graphql;
+mod health;
+mod integrity;
+mod jobs;
+mod json_path;
+mod jwt;
+mod leadership;
+mod links;
+mod long_poll;
 mod metrics;
+mod models;
+mod namespaces;
+mod object_store;
+mod overload;
+mod persistent_subscriptions;
+mod plugins;
+mod providers;
+mod queries;
+mod quota;
+mod read_cache;
+mod redaction;
+mod reducers;
+mod regions;
+mod replays;
+mod replication;
+mod request_id;
+mod retention;
+mod scavenger;
+mod search;
+mod slow_log;
+mod snapshots;
+mod storage;
+mod stream_copy;
+mod stream_ids;
+mod streaming;
+mod system_streams;
 mod telemetry;
-
+mod tls;
+mod ui;
+mod usage;
+
+use anchors::AnchorSigner;
+use auth::{ApiKeyRegistry, Tenant};
+use backup::BackupTarget;
+use blobs::BlobStore;
+use bus::{EventBus, MetricsConsumer, Overflow};
+use cdc::CdcReader;
+use change_feed::ChangeFeed;
+#[cfg(feature = "chaos")]
+use chaos::Chaos;
+use circuit_breaker::CircuitBreaker;
+use clap::Parser;
+use cli::{Cli, Command};
+use codec::{Accept, BodyFormat, Encoded, Negotiated};
+use cold_storage::ColdStore;
 use config::Config;
-use error::{AppError, Result};
+use deadline::Deadline;
+use deprecation::{DeprecationConsumer, DeprecationRegistry, DeprecationWarning};
+use encryption::EncryptionRegistry;
+use error::{AppError, ErrorCatalogEntry, Result, VersionConflict};
+use error_budgets::ErrorBudgets;
 use error_capture::ErrorCapture;
+use flow_control::Subscriptions;
+use graphql::{GraphqlSchema, LiveEvents};
+use health::Health;
+use jobs::{Job, Jobs};
+use jwt::JwtVerifier;
+use leadership::Leadership;
+use long_poll::{StreamWaiters, WaiterNotifier};
 use metrics::Metrics;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Event {
-    pub id: Uuid,
-    pub stream_id: String,
-    pub event_type: String,
-    pub data: serde_json::Value,
-    pub metadata: Option<serde_json::Value>,
-    pub version: i64,
-    pub created_at: DateTime<Utc>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AppendEventRequest {
-    pub stream_id: String,
-    pub event_type: String,
-    pub data: serde_json::Value,
-    pub metadata: Option<serde_json::Value>,
-    pub expected_version: Option<i64>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EventsQuery {
-    pub from_version: Option<i64>,
-    pub limit: Option<i64>,
-    pub direction: Option<String>, // "forward" or "backward"
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Snapshot {
-    pub id: Uuid,
-    pub stream_id: String,
-    pub version: i64,
-    pub data: Vec<u8>, // Compressed data
-    pub created_at: DateTime<Utc>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateSnapshotRequest {
-    pub stream_id: String,
-    pub version: i64,
-    pub data: serde_json::Value,
-}
-
-#[derive(Debug, Clone)]
+use models::{
+    AppendEventRequest, AppendQuery, CountQuery, CreateSnapshotRequest, Event, EventCount, EventsQuery, Granularity,
+    LatestEventsQuery, Role, Snapshot, StreamMetadata, TimeseriesQuery,
+};
+use namespaces::NamespaceRegistry;
+use overload::ConcurrencyLimits;
+use persistent_subscriptions::PersistentSubscriptions;
+use plugins::PluginHost;
+use providers::Providers;
+use quota::RateLimiter;
+use read_cache::{PageKey, ReadCache, ReadCacheInvalidator};
+use regions::Regions;
+use replays::Replays;
+use replication::{ReplicationStatus, Replicator};
+use scavenger::{ScavengeSettings, Scavenger};
+use snapshots::{SnapshotCodec, SnapshotRetention};
+use storage::{EventStorage, NewEvent, ReadDirection, MAX_HOURLY_DAYS};
+use stream_ids::StreamIdPolicy;
+use system_streams::SystemStreams;
+use tls::TlsFiles;
+
+/// Largest page returned by a buffered stream read.
+const MAX_PAGE_SIZE: i64 = 1000;
+/// Events folded into the running statistics per query.
+const STATS_ROLLUP_BATCH: i64 = 10_000;
+/// Longest span of daily usage one timeseries request may cover.
+const MAX_DAILY_DAYS: i64 = 366;
+/// How long open connections get to finish their requests on shutdown.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+/// Head version of the stream, on `HEAD /streams/:stream_id` and on state
+/// and snapshot reads, whose `If-Match` takes it back.
+const STREAM_VERSION_HEADER: HeaderName = HeaderName::from_static("x-stream-version");
+
+#[derive(Clone)]
 pub struct AppState {
-    pub db: PgPool,
+    pub storage: Arc<dyn EventStorage>,
     pub config: Config,
     pub metrics: Metrics,
+    pub deprecations: Arc<DeprecationRegistry>,
+    pub namespaces: Arc<NamespaceRegistry>,
+    pub encryption: Arc<EncryptionRegistry>,
+    pub health: Arc<Health>,
+    pub jobs: Arc<Jobs>,
+    pub scavenger: Arc<Scavenger>,
+    pub snapshot_codec: Arc<SnapshotCodec>,
+    pub plugins: Arc<PluginHost>,
+    pub api_keys: Arc<ApiKeyRegistry>,
+    pub jwt: Option<Arc<JwtVerifier>>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Postgres only.
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    pub cold_store: Option<Arc<ColdStore>>,
+    pub blobs: Option<Arc<BlobStore>>,
+    pub backup_target: Option<Arc<BackupTarget>>,
+    pub anchor_signer: Option<Arc<AnchorSigner>>,
+    /// Postgres only.
+    pub leadership: Option<Arc<Leadership>>,
+    pub regions: Option<Arc<Regions>>,
+    pub read_cache: Option<Arc<ReadCache>>,
+    pub bus: EventBus,
+    /// Appends from every instance sharing the database; Postgres only.
+    pub change_feed: Option<ChangeFeed>,
+    pub stream_waiters: Arc<StreamWaiters>,
+    pub graphql: GraphqlSchema,
+    pub replays: Arc<Replays>,
+    pub subscriptions: Arc<Subscriptions>,
+    pub persistent_subscriptions: Arc<PersistentSubscriptions>,
+    pub errors: ErrorCapture,
+    pub error_budgets: Arc<ErrorBudgets>,
+    pub concurrency: Arc<ConcurrencyLimits>,
+    pub system_streams: SystemStreams,
+    pub replication: Arc<ReplicationStatus>,
+    pub providers: Providers,
+    pub stream_ids: StreamIdPolicy,
+    #[cfg(feature = "chaos")]
+    pub chaos: Arc<Chaos>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let command = match cli.command {
+        Some(command) => command,
+        None if cli.migrate_only => Command::Migrate,
+        None => Command::Serve,
+    };
+
     // Initialize tracing
-    telemetry::init()?;
+    telemetry::init(cli.check_config || command.is_tool())?;
+    if cli.check_config {
+        return cli::check_config(cli.memory).await;
+    }
 
     // Load configuration
-    let config = Config::load()?;
+    let mut config = Config::load()?;
+    if cli.memory {
+        config.storage = Some("memory".to_string());
+    }
+
+    // Initialize storage
+    let storage = storage::connect(&config).await?;
+    match command {
+        Command::Serve => {
+            storage.migrate().await?;
+            serve(config, storage).await
+        }
+        Command::Migrate => {
+            storage.migrate().await?;
+            info!("Migrations applied");
+            Ok(())
+        }
+        command => cli::run(command, storage.as_ref(), &config).await,
+    }
+}
 
-    // Initialize database
-    let db = initialize_database(&config.database_url).await?;
-    run_migrations(&db).await?;
+async fn serve(config: Config, storage: Arc<dyn EventStorage>) -> Result<()> {
+    #[cfg(feature = "chaos")]
+    let (chaos, storage) = Chaos::wrap(storage);
+    let providers = Providers::from_config(&config)?;
+    let health = Arc::new(Health::default());
+    health.set_migrated();
+    let jobs = Arc::new(Jobs::default());
+    let scavenger = Arc::new(Scavenger::default());
+    let snapshot_codec = Arc::new(SnapshotCodec::from_config(&config)?);
+    let plugins = Arc::new(PluginHost::load(storage.as_ref(), &config).await?);
+    let deprecations = Arc::new(DeprecationRegistry::load(storage.as_ref()).await?);
+    let namespaces = Arc::new(NamespaceRegistry::load(storage.as_ref()).await?);
+    let persistent_subscriptions = Arc::new(PersistentSubscriptions::load(storage.as_ref(), &config).await?);
+    let encryption = Arc::new(EncryptionRegistry::load(storage.as_ref()).await?);
+    let api_keys = Arc::new(ApiKeyRegistry::load(storage.as_ref(), &config).await?);
+    let jwt = JwtVerifier::from_config(&config).await.map(Arc::new);
+    let rate_limiter = RateLimiter::from_config(&config)?.map(Arc::new);
 
     // Initialize metrics
-    let metrics = Metrics::new();
+    let metrics = Metrics::new().with_max_partition_labels(config.metrics_max_partition_labels);
+
+    let cold_store = ColdStore::from_config(&config).await.map(Arc::new);
+    let blobs = BlobStore::from_config(&config).await.map(Arc::new);
+    let backup_target = BackupTarget::from_config(&config).await.map(Arc::new);
+    let anchor_signer = AnchorSigner::from_config(&config)?.map(Arc::new);
+    let leadership = Leadership::from_config(&config).map(Arc::new);
+
+    // Subsystems that react to committed events hang off the bus, not the append path
+    let mut bus = EventBus::new(metrics.clone());
+    let cdc_reader = match &config.cdc_slot {
+        Some(slot) => {
+            bus = bus.captured();
+            Some(Arc::new(
+                CdcReader::connect(&config.database_url, slot, config.cdc_batch_size).await?,
+            ))
+        }
+        None => None,
+    };
+    bus.spawn(MetricsConsumer::new(metrics.clone()), 1024, Overflow::Block);
+    bus.spawn(
+        DeprecationConsumer::new(deprecations.clone(), metrics.clone()),
+        1024,
+        Overflow::Drop,
+    );
+    let read_cache = (config.read_cache_size > 0).then(|| {
+        Arc::new(ReadCache::new(
+            config.read_cache_size,
+            Duration::from_secs(config.read_cache_ttl_seconds),
+        ))
+    });
+    if let Some(read_cache) = &read_cache {
+        bus.spawn(ReadCacheInvalidator::new(read_cache.clone()), 1024, Overflow::Block);
+    }
+    // Other instances' appends reach this one through the change feed
+    let change_feed = if storage::uses_postgres(&config) {
+        Some(ChangeFeed::connect(&config.database_url).await?)
+    } else {
+        None
+    };
+    let stream_waiters = Arc::new(StreamWaiters::new(change_feed.clone()));
+    bus.spawn(WaiterNotifier::new(stream_waiters.clone()), 1024, Overflow::Block);
+    let live_events = LiveEvents::new();
+    bus.spawn(live_events.clone(), 1024, Overflow::Drop);
+    let system_streams = SystemStreams::new(storage.clone(), bus.clone(), &config, providers.clone());
+    if config.system_streams {
+        bus.spawn(system_streams.clone(), 1024, Overflow::Block);
+    }
 
     let state = AppState {
-        db: db.clone(),
+        storage: storage.clone(),
         config: config.clone(),
         metrics: metrics.clone(),
+        deprecations,
+        namespaces,
+        encryption,
+        health: health.clone(),
+        jobs: jobs.clone(),
+        scavenger: scavenger.clone(),
+        snapshot_codec: snapshot_codec.clone(),
+        plugins: plugins.clone(),
+        api_keys,
+        jwt,
+        rate_limiter,
+        circuit_breaker: CircuitBreaker::from_config(&config, metrics.clone()).map(Arc::new),
+        cold_store: cold_store.clone(),
+        blobs,
+        backup_target: backup_target.clone(),
+        anchor_signer: anchor_signer.clone(),
+        leadership: leadership.clone(),
+        regions: Regions::from_config(&config)?.map(Arc::new),
+        read_cache,
+        bus: bus.clone(),
+        change_feed,
+        stream_waiters,
+        graphql: graphql::schema(live_events),
+        replays: Arc::new(Replays::default()),
+        subscriptions: Arc::new(Subscriptions::default()),
+        persistent_subscriptions: persistent_subscriptions.clone(),
+        errors: ErrorCapture::spawn(&config, metrics.clone())?,
+        error_budgets: Arc::new(ErrorBudgets::from_config(&config, metrics.clone())?),
+        concurrency: Arc::new(ConcurrencyLimits::from_config(&config)),
+        system_streams: system_streams.clone(),
+        replication: Arc::new(ReplicationStatus::default()),
+        providers: providers.clone(),
+        stream_ids: StreamIdPolicy::from_config(&config)?,
+        #[cfg(feature = "chaos")]
+        chaos,
     };
 
     // Start background tasks
-    tokio::spawn(snapshot_scheduler(db.clone(), config.clone()));
-    tokio::spawn(stream_archiver(db.clone(), config.clone()));
+    let job = jobs.register(
+        "snapshot_scheduler",
+        Duration::from_secs(config.snapshot_interval_seconds),
+    );
+    health.watch(
+        "snapshot_scheduler",
+        tokio::spawn(snapshot_scheduler(
+            job,
+            storage.clone(),
+            snapshot_codec,
+            plugins.clone(),
+            system_streams,
+            config.clone(),
+            providers,
+        )),
+    );
+    let job = jobs.register("stream_archiver", Duration::from_secs(config.archive_interval_seconds));
+    health.watch(
+        "stream_archiver",
+        tokio::spawn(stream_archiver(
+            job,
+            storage.clone(),
+            cold_store,
+            metrics.clone(),
+            config.clone(),
+        )),
+    );
+    let job = jobs.register(
+        "partition_maintainer",
+        Duration::from_secs(config.partition_maintenance_interval_seconds),
+    );
+    health.watch(
+        "partition_maintainer",
+        tokio::spawn(partition_maintainer(job, storage.clone(), config.clone())),
+    );
+    let job = jobs.register(
+        "stats_rollup",
+        Duration::from_secs(config.stats_rollup_interval_seconds),
+    );
+    health.watch("stats_rollup", tokio::spawn(stats_rollup(job, storage.clone())));
+    let job = jobs.register(
+        "usage_sampler",
+        Duration::from_secs(config.usage_sample_interval_seconds),
+    );
+    health.watch("usage_sampler", tokio::spawn(usage_sampler(job, storage.clone())));
+    let job = jobs.register("scavenger", Duration::from_secs(config.scavenge_interval_seconds));
+    health.watch(
+        "scavenger",
+        tokio::spawn(scavenge_loop(
+            job,
+            scavenger,
+            storage.clone(),
+            metrics.clone(),
+            ScavengeSettings::from_config(&config),
+        )),
+    );
+    let job = jobs.register("projector", Duration::from_secs(config.projection_interval_seconds));
+    health.watch(
+        "projector",
+        tokio::spawn(plugins::projector(job, storage.clone(), plugins)),
+    );
+    let job = jobs.register(
+        "subscription_lag",
+        Duration::from_secs(config.subscription_lag_interval_seconds),
+    );
+    health.watch(
+        "subscription_lag",
+        tokio::spawn(persistent_subscriptions::observe_lag(
+            job,
+            persistent_subscriptions,
+            storage.clone(),
+            metrics.clone(),
+        )),
+    );
+    if let Some(reader) = cdc_reader {
+        let job = jobs.register("cdc_reader", Duration::from_millis(config.cdc_poll_interval_ms));
+        health.watch("cdc_reader", tokio::spawn(cdc::capture(job, reader, bus)));
+    }
+    if let (Some(target), Some(interval)) = (backup_target, config.backup_interval_seconds) {
+        let job = jobs.register("continuous_backup", Duration::from_secs(interval));
+        health.watch(
+            "continuous_backup",
+            tokio::spawn(backup::continuous_backup(job, storage.clone(), target)),
+        );
+    }
+    if config.search_fields.is_some() {
+        let job = jobs.register("search_indexer", Duration::from_secs(config.search_interval_seconds));
+        health.watch(
+            "search_indexer",
+            tokio::spawn(search::index_periodically(job, storage.clone(), config.clone())),
+        );
+        let job = jobs.register("search_pruner", search::PRUNE_INTERVAL);
+        health.watch(
+            "search_pruner",
+            tokio::spawn(search::prune_periodically(job, storage.clone())),
+        );
+    }
+    if let Some(leadership) = leadership {
+        let job = jobs.register("leader_election", leadership.renew_interval());
+        health.watch(
+            "leader_election",
+            tokio::spawn(leadership::campaign(job, leadership, storage.clone(), metrics.clone())),
+        );
+    }
+    if let Some(replicator) = Replicator::from_config(&config, storage.clone(), metrics.clone()) {
+        let job = jobs.register("replicator", Duration::from_millis(config.replication_interval_ms));
+        health.watch(
+            "replicator",
+            tokio::spawn(replication::replicate(job, Arc::new(replicator))),
+        );
+    }
+    if let Some(signer) = anchor_signer {
+        let job = jobs.register(
+            "chain_anchor",
+            Duration::from_secs(config.chain_anchor_interval_seconds),
+        );
+        health.watch(
+            "chain_anchor",
+            tokio::spawn(anchors::anchor_periodically(job, storage.clone(), signer)),
+        );
+    }
 
     // Build application
+    let errors = state.errors.clone();
     let app = create_app(state);
 
     // Start server
+    if let Some(files) = TlsFiles::from_config(&config)? {
+        let address: std::net::SocketAddr = config
+            .server_address
+            .parse()
+            .map_err(|e| AppError::Internal(format!("Invalid SERVER_ADDRESS for TLS: {}", e)))?;
+        let tls = files.rustls_config().await?;
+        health.watch(
+            "tls_reload",
+            tokio::spawn(tls::reload_on_change(
+                tls.clone(),
+                files.clone(),
+                Duration::from_secs(config.tls_reload_interval_seconds),
+            )),
+        );
+
+        info!(
+            "Event Store server starting on {} (TLS{})",
+            address,
+            if files.client_ca_path.is_some() {
+                ", client certificates required"
+            } else {
+                ""
+            }
+        );
+        let handle = axum_server::Handle::new();
+        tokio::spawn({
+            let handle = handle.clone();
+            async move {
+                shutdown_signal().await;
+                handle.graceful_shutdown(Some(SHUTDOWN_GRACE));
+            }
+        });
+        axum_server::bind_rustls(address, tls)
+            .handle(handle)
+            .serve(app.into_make_service())
+            .await
+            .map_err(|e| AppError::Internal(format!("Server error: {}", e)))?;
+        errors.flush().await;
+        return Ok(());
+    }
+
     let listener = tokio::net::TcpListener::bind(&config.server_address).await?;
     info!("Event Store server starting on {}", config.server_address);
 
-    axum::serve(listener, app).await?;
+    // Streamed subscriptions never finish on their own, so the grace period is enforced here too
+    let signalled = Arc::new(tokio::sync::Notify::new());
+    let server = axum::serve(listener, app).with_graceful_shutdown({
+        let signalled = signalled.clone();
+        async move {
+            shutdown_signal().await;
+            signalled.notify_one();
+        }
+    });
+    tokio::select! {
+        result = server.into_future() => result?,
+        _ = async {
+            signalled.notified().await;
+            tokio::time::sleep(SHUTDOWN_GRACE).await;
+        } => warn!("Connections still open {}s after shutdown began; closing them", SHUTDOWN_GRACE.as_secs()),
+    }
+    errors.flush().await;
 
     Ok(())
 }
 
+/// Resolves on Ctrl-C or SIGTERM. The server then stops accepting
+/// connections and lets requests in flight finish before buffered state,
+/// like the error patterns, is flushed.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    info!("Shutting down");
+}
+
 fn create_app(state: AppState) -> Router {
-    Router::new()
-        .route("/health", get(health_check))
+    let routes = Router::new()
+        .route("/health", get(health::live))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
         .route("/metrics", get(get_metrics))
+        .route("/errors/catalog", get(get_error_catalog))
+        .route("/ui", get(ui::index))
+        .route("/ui/", get(ui::index))
+        .route("/ui/:file", get(ui::asset))
         .route("/events", post(append_event))
-        .route("/streams/:stream_id/events", get(get_stream_events))
+        .route("/streams/:stream_id", head(stream_exists))
+        .route(
+            "/streams/:stream_id/events",
+            get(get_stream_events).post(append_raw_event),
+        )
+        .route("/streams/:stream_id/events/count", get(count_events))
+        .route("/streams/:stream_id/events/latest", get(get_latest_events))
+        .route("/streams/:stream_id/links", post(links::append_link))
+        .route("/streams/:stream_id/copy", post(stream_copy::copy_stream))
+        .route("/streams/:stream_id/move", post(stream_copy::move_stream))
+        .route("/streams/:stream_id/state", get(snapshots::get_stream_state))
+        .route("/streams/:stream_id/export", get(export::export_stream))
+        .route("/streams/:stream_id/import", post(export::import_stream))
+        .route(
+            "/streams/:stream_id/metadata",
+            get(get_stream_metadata).put(set_stream_metadata),
+        )
+        .route(
+            "/projects",
+            get(namespaces::list_projects).post(namespaces::create_project),
+        )
+        .route(
+            "/projects/:project/workspaces",
+            get(namespaces::list_workspaces).post(namespaces::create_workspace),
+        )
         .route("/snapshots", post(create_snapshot))
+        .route("/snapshots/:stream_id", get(snapshots::get_snapshot))
         .route("/snapshots/:stream_id/latest", get(get_latest_snapshot))
+        .route("/projections/:name", get(plugins::get_projection))
+        .route("/queries/merge", get(queries::merge_streams))
+        .route("/search", get(search::search))
         .route("/stats", get(get_stats))
+        .route("/stats/timeseries", get(get_usage_timeseries))
+        .route("/subscriptions", get(persistent_subscriptions::list_groups))
+        .route(
+            "/subscriptions/:group",
+            put(persistent_subscriptions::set_group).delete(persistent_subscriptions::delete_group),
+        )
+        .route("/subscriptions/:group/poll", post(persistent_subscriptions::poll))
+        .route("/subscriptions/:group/ack", post(persistent_subscriptions::ack))
+        .route("/subscriptions/:group/nack", post(persistent_subscriptions::nack))
+        .route("/subscriptions/:group/lag", get(persistent_subscriptions::get_lag))
+        .route("/graphql", post(graphql::graphql))
+        .route("/graphql/stream", post(graphql::graphql_stream))
+        .route("/graphql/stream/:id/credits", post(flow_control::grant_credits))
+        .merge(admin_routes());
+    // Test builds only: faults go in front of the handlers, behind authentication and limits
+    #[cfg(feature = "chaos")]
+    let routes = routes
+        .merge(chaos::routes())
+        .layer(middleware::from_fn_with_state(state.clone(), chaos::inject));
+
+    routes
+        .layer(middleware::from_fn_with_state(state.clone(), replication::read_only))
+        .layer(middleware::from_fn_with_state(state.clone(), leadership::leader_only))
+        .layer(middleware::from_fn_with_state(state.clone(), circuit_breaker::guard))
+        .layer(middleware::from_fn_with_state(state.clone(), quota::rate_limit))
+        .layer(middleware::from_fn_with_state(state.clone(), auth::authenticate))
+        .layer(middleware::from_fn_with_state(state.clone(), regions::route))
+        .layer(middleware::from_fn_with_state(state.clone(), overload::limit))
+        .layer(middleware::from_fn_with_state(state.clone(), metrics::track_requests))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            error_capture::capture_errors,
+        ))
+        .layer(middleware::from_fn(request_id::assign))
         .with_state(state)
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
+                .layer(TraceLayer::new_for_http().make_span_with(telemetry::request_span))
                 .layer(CompressionLayer::new())
-                .layer(CorsLayer::permissive())
+                .layer(CorsLayer::permissive()),
         )
 }
 
-async fn health_check() -> Result<Json<serde_json::Value>> {
-    Ok(Json(serde_json::json!({
-        "status": "healthy",
-        "service": "event-store",
-        "version": "1.0.0",
-        "timestamp": Utc::now()
-    })))
+/// Everything under `/admin`, crypto-shredding and deleting streams,
+/// projects and workspaces requires the admin role.
+fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/streams/:stream_id", delete(delete_stream))
+        .route("/projects/:project", delete(namespaces::delete_project))
+        .route(
+            "/projects/:project/workspaces/:workspace",
+            delete(namespaces::delete_workspace),
+        )
+        .route("/admin/compliance/report", get(compliance::compliance_report))
+        .route("/admin/event-types/deprecations", get(deprecation::list_deprecations))
+        .route("/admin/usage", get(usage::usage_report))
+        .route(
+            "/admin/event-types/:event_type/deprecation",
+            put(deprecation::deprecate_event_type).delete(deprecation::undeprecate_event_type),
+        )
+        .route(
+            "/admin/event-types/:event_type/migrate",
+            post(deprecation::migrate_event_type),
+        )
+        .route("/admin/encryption/policies", get(encryption::list_policies))
+        .route(
+            "/admin/event-types/:event_type/encryption",
+            put(encryption::set_policy).delete(encryption::remove_policy),
+        )
+        .route("/subjects/:subject_id/keys", delete(encryption::shred_subject_key))
+        .route(
+            "/admin/backups",
+            get(backup::list_backups).post(backup::create_backup_handler),
+        )
+        .route("/admin/restore", post(backup::restore_handler))
+        .route("/admin/bulk-load", post(export::bulk_load))
+        .route("/admin/verify", post(integrity::verify))
+        .route("/admin/verify/chain", post(integrity::verify_chain))
+        .route("/admin/redactions", post(redaction::create_redaction))
+        .route("/admin/chain/anchors", get(anchors::list_anchors))
+        .route("/admin/chain/anchors/verify", post(anchors::verify_anchors))
+        .route("/admin/api-keys", get(auth::list_api_keys).post(auth::create_api_key))
+        .route("/admin/api-keys/:id", delete(auth::revoke_api_key))
+        .route("/admin/retention/rules", get(retention::list_rules))
+        .route(
+            "/admin/retention/rules/:name",
+            put(retention::set_rule).delete(retention::remove_rule),
+        )
+        .route("/admin/retention/preview", get(retention::preview))
+        .route("/admin/plugins", get(plugins::list_plugins))
+        .route(
+            "/admin/plugins/:name",
+            put(plugins::upload_plugin).delete(plugins::remove_plugin),
+        )
+        .route("/admin/snapshot-reducers", get(reducers::list_reducers))
+        .route(
+            "/admin/snapshot-reducers/:name",
+            put(reducers::set_reducer).delete(reducers::remove_reducer),
+        )
+        .route("/admin/dead-letters", get(delivery::list_dead_letters))
+        .route(
+            "/admin/dead-letters/:id",
+            get(delivery::get_dead_letter).delete(delivery::discard_dead_letter),
+        )
+        .route("/admin/dead-letters/:id/redeliver", post(delivery::redeliver))
+        .route("/admin/replays", get(replays::list_replays).post(replays::start_replay))
+        .route("/admin/replays/:id", get(replays::get_replay))
+        .route("/admin/replays/:id/cancel", post(replays::cancel_replay))
+        .route("/admin/scavenger", get(scavenger::status))
+        .route("/admin/error-budgets", get(error_budgets::list_error_budgets))
+        .route("/admin/jobs", get(jobs::list_jobs))
+        .route("/admin/jobs/:name/run", post(jobs::run_job))
+        .route("/admin/jobs/:name/pause", post(jobs::pause_job))
+        .route("/admin/jobs/:name/resume", post(jobs::resume_job))
+        .route("/admin/replication/checkpoint", get(replication::get_checkpoint))
+        .route(
+            "/admin/replication/events",
+            post(replication::apply_batch).layer(DefaultBodyLimit::disable()),
+        )
+        .route_layer(middleware::from_fn(auth::require_admin))
+}
+
+async fn get_error_catalog() -> Json<Vec<ErrorCatalogEntry>> {
+    Json(error::catalog())
 }
 
 async fn get_metrics(State(state): State<AppState>) -> Result<String> {
+    state.metrics.observe(&state);
     let encoder = prometheus::TextEncoder::new();
     let metric_families = state.metrics.registry.gather();
     match encoder.encode_to_string(&metric_families) {
@@ -153,270 +737,761 @@ async fn get_metrics(State(state): State<AppState>) -> Result<String> {
 }
 
 async fn append_event(
+    Query(query): Query<AppendQuery>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    deadline: Deadline,
+    Accept(format): Accept,
+    Negotiated(request): Negotiated<AppendEventRequest>,
+) -> Result<(DeprecationWarning, Encoded<Event>)> {
+    tenant.authorize(&request.stream_id)?;
+    let stream_id = request.stream_id.clone();
+    let event = store_event(&state, &tenant, deadline, request, &query)
+        .await
+        .map_err(|e| {
+            state.metrics.record_error(&stream_id, "append");
+            e
+        })?;
+    Ok((state.deprecations.warning(&event.event_type), Encoded(format, event)))
+}
+
+// Appends an event whose body is the raw request payload (e.g. protobuf),
+// with the event envelope carried in headers instead of a JSON document.
+async fn append_raw_event(
+    Path(stream_id): Path<String>,
+    Query(query): Query<AppendQuery>,
     State(state): State<AppState>,
-    Json(request): Json<AppendEventRequest>,
-) -> Result<Json<Event>> {
+    tenant: Tenant,
+    deadline: Deadline,
+    Accept(format): Accept,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(DeprecationWarning, Encoded<Event>)> {
+    let header_str = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let event_type = header_str("x-event-type")
+        .ok_or_else(|| AppError::BadRequest("Missing X-Event-Type header".to_string()))?
+        .to_string();
+    let expected_version = header_str("x-expected-version")
+        .map(|v| v.parse::<i64>())
+        .transpose()
+        .map_err(|_| AppError::BadRequest("Invalid X-Expected-Version header".to_string()))?;
+    let id = header_str("x-event-id")
+        .map(Uuid::parse_str)
+        .transpose()
+        .map_err(|_| AppError::BadRequest("Invalid X-Event-Id header".to_string()))?;
+    let ttl_seconds = header_str("x-event-ttl-seconds")
+        .map(|v| v.parse::<u64>())
+        .transpose()
+        .map_err(|_| AppError::BadRequest("Invalid X-Event-Ttl-Seconds header".to_string()))?;
+    let content_type = header_str(header::CONTENT_TYPE.as_str())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+
+    let (data, payload) = if is_json_content_type(&content_type) {
+        (serde_json::from_slice(&body)?, None)
+    } else {
+        (serde_json::Value::Null, Some(body.to_vec()))
+    };
+
+    let request = AppendEventRequest {
+        id,
+        stream_id: stream_id.clone(),
+        event_type,
+        data,
+        payload,
+        content_type: Some(content_type),
+        metadata: None,
+        expected_version,
+        ttl_seconds,
+        preconditions: Vec::new(),
+    };
+
+    let event = store_event(&state, &tenant, deadline, request, &query)
+        .await
+        .map_err(|e| {
+            state.metrics.record_error(&stream_id, "append");
+            e
+        })?;
+    Ok((state.deprecations.warning(&event.event_type), Encoded(format, event)))
+}
+
+async fn store_event(
+    state: &AppState,
+    tenant: &Tenant,
+    deadline: Deadline,
+    request: AppendEventRequest,
+    query: &AppendQuery,
+) -> Result<Event> {
     let start_time = std::time::Instant::now();
     state.metrics.event_append_requests.inc();
 
-    // Validate stream_id format for partitioning
-    if !is_valid_stream_id(&request.stream_id) {
+    if let Err(e) = state.stream_ids.validate(&request.stream_id) {
+        state.metrics.event_append_errors.inc();
+        return Err(e);
+    }
+    if request.ttl_seconds == Some(0) {
         state.metrics.event_append_errors.inc();
-        return Err(AppError::BadRequest("Invalid stream_id format".to_string()));
+        return Err(AppError::BadRequest("ttl_seconds must be at least 1".to_string()));
+    }
+    for precondition in &request.preconditions {
+        if precondition.stream_id == request.stream_id {
+            state.metrics.event_append_errors.inc();
+            return Err(AppError::BadRequest(
+                "Preconditions name other streams; use expected_version for the stream appended to".to_string(),
+            ));
+        }
+        if let Err(e) = state.stream_ids.validate(&precondition.stream_id) {
+            state.metrics.event_append_errors.inc();
+            return Err(e);
+        }
+        if precondition.version < 0 {
+            state.metrics.event_append_errors.inc();
+            return Err(AppError::BadRequest(
+                "Precondition versions can't be negative".to_string(),
+            ));
+        }
+        tenant.authorize(&precondition.stream_id)?;
+    }
+    if state.config.namespaces_strict
+        && !state.namespaces.allows(state.storage.as_ref(), &request.stream_id).await?
+        && state.storage.stream_version(&request.stream_id).await? == 0
+    {
+        state.metrics.event_append_errors.inc();
+        return Err(AppError::BadRequest(format!(
+            "Stream {} is outside a registered project and workspace",
+            request.stream_id
+        )));
     }
 
-    // Get current version for optimistic concurrency control
-    let current_version = get_stream_version(&state.db, &request.stream_id).await?;
+    let content_type = request.content_type.unwrap_or_else(|| "application/json".to_string());
 
-    if let Some(expected) = request.expected_version {
-        if current_version != expected {
-            state.metrics.event_append_conflicts.inc();
-            return Err(AppError::Conflict(format!(
-                "Version conflict: expected {}, got {}",
-                expected, current_version
+    // JSON events live in the JSONB column; everything else is stored as opaque bytes
+    let (data, payload) = if is_json_content_type(&content_type) {
+        if request.payload.is_some() {
+            state.metrics.event_append_errors.inc();
+            return Err(AppError::BadRequest(
+                "payload is only allowed for non-JSON content types; use data".to_string(),
+            ));
+        }
+        let mut data = request.data;
+        encryption::encrypt_fields(state, &request.event_type, &mut data).await?;
+        (Some(data), None)
+    } else {
+        match request.payload {
+            Some(payload) if request.data.is_null() => (None, Some(payload)),
+            Some(_) => {
+                state.metrics.event_append_errors.inc();
+                return Err(AppError::BadRequest(
+                    "data and payload are mutually exclusive".to_string(),
+                ));
+            }
+            None => {
+                state.metrics.event_append_errors.inc();
+                return Err(AppError::BadRequest(format!(
+                    "payload is required for content type {}",
+                    content_type
+                )));
+            }
+        }
+    };
+
+    // Enforce the stream's event type allowlist, if it declares one
+    let metadata = state.storage.stream_metadata(&request.stream_id).await?;
+    if let Some(metadata) = &metadata {
+        if !metadata.allows_event_type(&request.event_type) {
+            state.metrics.event_append_errors.inc();
+            return Err(AppError::BadRequest(format!(
+                "Event type '{}' is not allowed on stream {} (allowed: {})",
+                request.event_type,
+                request.stream_id,
+                metadata.allowed_event_types.clone().unwrap_or_default().join(", ")
             )));
         }
     }
 
-    let new_version = current_version + 1;
-    let event_id = Uuid::new_v4();
-    let now = Utc::now();
+    let payload_bytes = match (&data, &payload) {
+        (_, Some(payload)) => payload.len(),
+        (Some(data), None) => serde_json::to_vec(data)?.len(),
+        (None, None) => 0,
+    };
+    quota::check_stream_quotas(state, &request.stream_id, metadata.as_ref(), payload_bytes).await?;
+    slow_log::check_payload(state, &request.stream_id, &request.event_type, payload_bytes);
 
-    // Insert event with partition key
-    let partition_key = get_partition_key(&request.stream_id);
-
-    sqlx::query!(
-        r#"
-        INSERT INTO events (id, stream_id, event_type, data, metadata, version, created_at, partition_key)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-        "#,
-        event_id,
-        request.stream_id,
-        request.event_type,
-        request.data,
-        request.metadata,
-        new_version,
-        now,
-        partition_key
-    )
-    .execute(&state.db)
-    .await
-    .map_err(|e| {
-        error!("Failed to insert event: {}", e);
-        state.metrics.event_append_errors.inc();
-        AppError::Database(e.to_string())
-    })?;
+    // A retried append of an event that already landed returns the original
+    if let Some(existing) = find_duplicate(state, &request.stream_id, request.id).await? {
+        info!("Duplicate event {} on {} ignored", existing.id, existing.stream_id);
+        return Ok(existing);
+    }
+
+    // Bodies over the offload threshold go to object storage, and the event keeps a reference
+    let id = request.id.unwrap_or_else(|| state.providers.new_id());
+    let mut offloaded = None;
+    let (data, payload, content_type) = match &state.blobs {
+        Some(blobs) if blobs.should_offload(payload_bytes) => {
+            let body = match payload {
+                Some(payload) => payload,
+                None => serde_json::to_vec(&data)?,
+            };
+            let blob = blobs.offload(&request.stream_id, id, &content_type, body).await?;
+            let reference = serde_json::to_value(&blob)?;
+            offloaded = Some(blob);
+            (
+                Some(reference),
+                None,
+                blobs::BLOB_REF_MIME.to_string(),
+            )
+        }
+        _ => (data, payload, content_type),
+    };
 
-    let event = Event {
-        id: event_id,
+    // The tenant is the partition: one tenant's events never share a partition with another's
+    let tenant_id = tenant.id_for(&request.stream_id);
+    let created_at = state.providers.now();
+    let new_event = NewEvent {
+        checksum: integrity::checksum(data.as_ref(), payload.as_deref()),
+        id,
+        partition_key: tenant_id.clone(),
+        tenant_id,
         stream_id: request.stream_id,
         event_type: request.event_type,
-        data: request.data,
+        data,
+        payload,
+        content_type,
         metadata: request.metadata,
-        version: new_version,
-        created_at: now,
+        created_at,
+        // A TTL too long to represent is as good as none
+        expires_at: request
+            .ttl_seconds
+            .and_then(|ttl| chrono::Duration::try_seconds(i64::try_from(ttl).ok()?))
+            .and_then(|ttl| created_at.checked_add_signed(ttl)),
+        preconditions: request.preconditions,
+    };
+
+    let stream_id = new_event.stream_id.clone();
+    let append = state.storage.append(new_event, request.expected_version, deadline);
+    let appended = slow_log::timed(state, "append", &stream_id, append).await;
+    if let (Err(_), Some(blob)) = (&appended, &offloaded) {
+        let since = created_at - chrono::Duration::seconds(state.config.dedup_window_seconds as i64);
+        blobs::discard(state, &stream_id, id, since, blob).await;
+    }
+    let event = match appended {
+        Ok(event) => event,
+        Err(AppError::Conflict(message)) => {
+            // The conflict may be a concurrent retry of this same event landing first
+            if let Some(existing) = find_duplicate(state, &stream_id, request.id).await? {
+                return Ok(existing);
+            }
+            state.metrics.event_append_conflicts.inc();
+            return Err(match request.expected_version {
+                Some(expected) => {
+                    version_conflict(state, &stream_id, expected, query.return_conflict_events, deadline).await?
+                }
+                None => AppError::Conflict(message),
+            });
+        }
+        // A stream in the preconditions moved on
+        Err(e @ AppError::VersionConflict(_)) => {
+            state.metrics.event_append_conflicts.inc();
+            return Err(e);
+        }
+        Err(e) => {
+            state.metrics.event_append_errors.inc();
+            return Err(e);
+        }
     };
 
-    state.metrics.events_stored.inc();
-    state.metrics.event_append_duration.observe(start_time.elapsed().as_secs_f64());
+    state
+        .metrics
+        .event_append_duration
+        .observe(start_time.elapsed().as_secs_f64());
+    state.bus.publish(event.clone()).await;
 
     info!("Event appended: {} v{}", event.stream_id, event.version);
 
-    Ok(Json(event))
+    Ok(event)
+}
+
+/// The event already appended with a client-supplied `id` within the dedup
+/// window, decrypted.
+async fn find_duplicate(state: &AppState, stream_id: &str, id: Option<Uuid>) -> Result<Option<Event>> {
+    let Some(id) = id.filter(|_| state.config.dedup_window_seconds > 0) else {
+        return Ok(None);
+    };
+    let since = state.providers.now() - chrono::Duration::seconds(state.config.dedup_window_seconds as i64);
+    let Some(event) = state.storage.find_event(stream_id, id, since).await? else {
+        return Ok(None);
+    };
+
+    let mut events = [event];
+    encryption::decrypt_events(state, &mut events).await?;
+    let [event] = events;
+    Ok(Some(event))
+}
+
+/// The details of a lost append race: the stream head and, when asked for,
+/// the events the client hasn't seen yet (up to a page of them).
+async fn version_conflict(
+    state: &AppState,
+    stream_id: &str,
+    expected_version: i64,
+    with_events: bool,
+    deadline: Deadline,
+) -> Result<AppError> {
+    let mut current_version = state.storage.stream_version(stream_id).await?;
+    let events = if with_events {
+        let from_version = (expected_version + 1).max(visible_from(state, stream_id).await?);
+        let mut events = state
+            .storage
+            .read_stream(stream_id, from_version, MAX_PAGE_SIZE, ReadDirection::Forward, deadline)
+            .await?;
+        encryption::decrypt_events(state, &mut events).await?;
+        // Appends may have landed since the head was read
+        if let Some(last) = events.last() {
+            current_version = current_version.max(last.version);
+        }
+        Some(events)
+    } else {
+        None
+    };
+
+    Ok(AppError::VersionConflict(Box::new(VersionConflict {
+        stream_id: stream_id.to_string(),
+        expected_version,
+        current_version,
+        events,
+    })))
 }
 
 async fn get_stream_events(
     Path(stream_id): Path<String>,
     Query(query): Query<EventsQuery>,
     State(state): State<AppState>,
-) -> Result<Json<Vec<Event>>> {
+    tenant: Tenant,
+    deadline: Deadline,
+    Accept(format): Accept,
+    headers: HeaderMap,
+) -> Result<Response> {
     let start_time = std::time::Instant::now();
     state.metrics.event_read_requests.inc();
 
-    let from_version = query.from_version.unwrap_or(0);
-    let limit = query.limit.unwrap_or(100).min(1000); // Cap at 1000
     let direction = query.direction.unwrap_or_else(|| "forward".to_string());
 
-    let order_clause = if direction == "backward" { "DESC" } else { "ASC" };
-
-    let query_str = format!(
-        r#"
-        SELECT id, stream_id, event_type, data, metadata, version, created_at
-        FROM events
-        WHERE stream_id = $1 AND version >= $2
-        ORDER BY version {}
-        LIMIT $3
-        "#,
-        order_clause
-    );
+    let direction = if direction == "backward" {
+        ReadDirection::Backward
+    } else {
+        ReadDirection::Forward
+    };
+    // Backward reads page down from the head, or from the version given;
+    // the next page starts one below the oldest event returned
+    let visible_from = visible_from(&state, &stream_id).await?;
+    let from_version = match direction {
+        ReadDirection::Forward => query.from_version.unwrap_or(0).max(visible_from),
+        ReadDirection::Backward => query.from_version.unwrap_or(i64::MAX),
+    };
+
+    // NDJSON, and JSON reads past the page cap, stream straight from the
+    // database instead of buffering the whole range
+    let ndjson = streaming::accepts_ndjson(&headers);
+    let oversized = format == BodyFormat::Json && query.limit.is_some_and(|l| l > MAX_PAGE_SIZE);
+    if direction == ReadDirection::Forward && (ndjson || oversized) {
+        let from_version = from_version.max(1);
+        let to_version = match query.limit {
+            Some(limit) => from_version.saturating_add(limit.max(0) - 1),
+            None => i64::MAX,
+        };
+        let mut events = streaming::scan(
+            state.clone(),
+            stream_id,
+            from_version,
+            to_version,
+            query.include_archived,
+        );
+        if query.resolve_links {
+            events = links::resolving(state.clone(), tenant, events);
+        }
+        if !blobs::wants_links(&headers) {
+            events = blobs::resolving(state.clone(), events);
+        }
+        let events = encryption::decrypting(state, events);
+
+        return Ok(if ndjson {
+            (
+                [(header::CONTENT_TYPE, streaming::NDJSON_MIME)],
+                streaming::ndjson_body(events),
+            )
+                .into_response()
+        } else {
+            (
+                [(header::CONTENT_TYPE, "application/json")],
+                streaming::json_array_body(events),
+            )
+                .into_response()
+        });
+    }
+    let limit = query.limit.unwrap_or(100).clamp(0, MAX_PAGE_SIZE);
+
+    // Long polls subscribe before the first read, so an append racing it still wakes them
+    let wait = query.wait.as_deref().map(long_poll::parse_wait).transpose()?;
+    let mut waiter = match (wait, direction) {
+        (Some(wait), ReadDirection::Forward) => {
+            let mut wait = wait.min(Duration::from_secs(state.config.long_poll_max_wait_seconds));
+            if let Some(remaining) = deadline.remaining()? {
+                wait = wait.min(remaining);
+            }
+            Some((
+                state.stream_waiters.subscribe(&stream_id).await,
+                tokio::time::Instant::now() + wait,
+            ))
+        }
+        _ => None,
+    };
+
+    let key = PageKey::new(&stream_id, from_version, limit, direction, query.include_archived);
+    let cached = state.read_cache.as_ref().and_then(|cache| cache.get(&key));
+    let events = match cached {
+        Some(events) => {
+            state.metrics.read_cache_hits.inc();
+            events
+        }
+        None => {
+            let events = cold_storage::read_stream(
+                &state,
+                &stream_id,
+                from_version,
+                limit,
+                direction,
+                query.include_archived,
+                deadline,
+            )
+            .await
+            .map_err(|e| {
+                state.metrics.event_read_errors.inc();
+                state.metrics.record_error(&stream_id, "read");
+                e
+            })?;
+            let events = Arc::new(events);
+            if let Some(cache) = &state.read_cache {
+                state.metrics.read_cache_misses.inc();
+                cache.insert(key, events.clone());
+            }
+            events
+        }
+    };
+
+    // The cache holds events as stored; decrypt after it so shredding takes effect at once
+    let mut events = events.as_ref().clone();
+    events.retain(|e| e.version >= visible_from);
+
+    if let Some((waiter, until)) = &mut waiter {
+        while events.is_empty() {
+            let left = until.saturating_duration_since(tokio::time::Instant::now());
+            if left.is_zero() || !waiter.next(left).await {
+                break;
+            }
+            // Past the cache, whose invalidation may lag the wakeup, and without the
+            // deadline, which the wait was sized to use up
+            events = cold_storage::read_stream(
+                &state,
+                &stream_id,
+                from_version,
+                limit,
+                direction,
+                query.include_archived,
+                Deadline(None),
+            )
+            .await?;
+            events.retain(|e| e.version >= visible_from);
+        }
+    }
+    if query.resolve_links {
+        links::resolve_events(&state, &tenant, &mut events, deadline).await?;
+    }
+    if !blobs::wants_links(&headers) {
+        blobs::resolve_events(&state, &mut events).await?;
+    }
+    encryption::decrypt_events(&state, &mut events).await?;
+
+    let etag = read_cache::etag(&events, format);
+    state
+        .metrics
+        .event_read_duration
+        .observe(start_time.elapsed().as_secs_f64());
+    if read_cache::not_modified(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    state.metrics.record_read(&stream_id, events.len() as u64);
+    Ok(([(header::ETAG, etag)], Encoded(format, events.as_slice())).into_response())
+}
+
+async fn get_latest_events(
+    Path(stream_id): Path<String>,
+    Query(query): Query<LatestEventsQuery>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    deadline: Deadline,
+    Accept(format): Accept,
+    headers: HeaderMap,
+) -> Result<Encoded<Vec<Event>>> {
+    let start_time = std::time::Instant::now();
+    state.metrics.event_read_requests.inc();
+
+    let count = query.count.unwrap_or(20).clamp(1, 1000); // Cap at 1000
 
-    let rows = sqlx::query(&query_str)
-        .bind(&stream_id)
-        .bind(from_version)
-        .bind(limit)
-        .fetch_all(&state.db)
+    let mut events = cold_storage::read_latest(&state, &stream_id, count, deadline)
         .await
         .map_err(|e| {
-            error!("Failed to fetch events: {}", e);
             state.metrics.event_read_errors.inc();
-            AppError::Database(e.to_string())
+            state.metrics.record_error(&stream_id, "read");
+            e
         })?;
+    let visible_from = visible_from(&state, &stream_id).await?;
+    events.retain(|e| e.version >= visible_from);
+    if query.resolve_links {
+        links::resolve_events(&state, &tenant, &mut events, deadline).await?;
+    }
+    if !blobs::wants_links(&headers) {
+        blobs::resolve_events(&state, &mut events).await?;
+    }
+    encryption::decrypt_events(&state, &mut events).await?;
 
-    let events: Result<Vec<Event>> = rows
-        .into_iter()
-        .map(|row| {
-            Ok(Event {
-                id: row.try_get("id")?,
-                stream_id: row.try_get("stream_id")?,
-                event_type: row.try_get("event_type")?,
-                data: row.try_get("data")?,
-                metadata: row.try_get("metadata")?,
-                version: row.try_get("version")?,
-                created_at: row.try_get("created_at")?,
-            })
-        })
-        .collect();
+    state.metrics.record_read(&stream_id, events.len() as u64);
+    state
+        .metrics
+        .event_read_duration
+        .observe(start_time.elapsed().as_secs_f64());
+
+    Ok(Encoded(format, events))
+}
+
+/// HEAD /streams/:stream_id — whether a stream exists, without reading
+/// any of it: 200 with its head version in `X-Stream-Version`, or 404 when
+/// it has no events or was deleted.
+async fn stream_exists(Path(stream_id): Path<String>, State(state): State<AppState>) -> Result<Response> {
+    let head = state.storage.stream_version(&stream_id).await?;
+    if head == 0 || head < visible_from(&state, &stream_id).await? {
+        return Err(AppError::NotFound(format!("Stream {} not found", stream_id)));
+    }
+
+    Ok([(STREAM_VERSION_HEADER, head.to_string())].into_response())
+}
+
+/// GET /streams/:stream_id/events/count — how many events a stream holds
+/// between two versions, inclusive, counted in the database.
+async fn count_events(
+    Path(stream_id): Path<String>,
+    Query(query): Query<CountQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<EventCount>> {
+    let from = query
+        .from
+        .unwrap_or(1)
+        .max(visible_from(&state, &stream_id).await?)
+        .max(1);
+    let to = match query.to {
+        Some(to) => to,
+        None => state.storage.stream_version(&stream_id).await?,
+    };
+    let count = if from <= to {
+        state.storage.count_events(&stream_id, from, to).await?
+    } else {
+        0
+    };
+
+    Ok(Json(EventCount {
+        stream_id,
+        from,
+        to,
+        count,
+    }))
+}
+
+/// First version readers may see: versions below the stream's
+/// `truncate_before` count as deleted before the scavenger removes them.
+pub async fn visible_from(state: &AppState, stream_id: &str) -> Result<i64> {
+    Ok(state
+        .storage
+        .stream_metadata(stream_id)
+        .await?
+        .and_then(|metadata| metadata.truncate_before)
+        .unwrap_or(0))
+}
+
+/// DELETE /streams/:stream_id (admin) — soft-deletes a stream by truncating it
+/// before the next version: reads return nothing, appends continue the
+/// version sequence, and the scavenger reclaims the events.
+async fn delete_stream(
+    Path(stream_id): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<StatusCode> {
+    tenant.authorize(&stream_id)?;
+    let head = state.storage.stream_version(&stream_id).await?;
+    if head == 0 {
+        return Err(AppError::NotFound(format!("Stream {} not found", stream_id)));
+    }
+
+    let mut metadata = state.storage.stream_metadata(&stream_id).await?.unwrap_or_default();
+    metadata.truncate_before = Some(head + 1);
+    state.storage.set_stream_metadata(&stream_id, &metadata).await?;
+    if let Some(cache) = &state.read_cache {
+        cache.invalidate_stream(&stream_id);
+    }
+    info!("Stream {} deleted at version {}", stream_id, head);
+    state.system_streams.stream_deleted(&stream_id, head).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_stream_metadata(
+    Path(stream_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<StreamMetadata>> {
+    let metadata = state
+        .storage
+        .stream_metadata(&stream_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No metadata for stream {}", stream_id)))?;
+
+    Ok(Json(metadata))
+}
+
+/// PUT /streams/:stream_id/metadata — replaces the stream's metadata.
+/// `truncate_before` and `legal_hold` delete and protect events, so only
+/// admins may change them; other callers must send them back as stored.
+async fn set_stream_metadata(
+    Path(stream_id): Path<String>,
+    State(state): State<AppState>,
+    Extension(role): Extension<Role>,
+    Json(metadata): Json<StreamMetadata>,
+) -> Result<Json<StreamMetadata>> {
+    state.stream_ids.validate(&stream_id)?;
+    if role < Role::Admin {
+        let stored = state.storage.stream_metadata(&stream_id).await?.unwrap_or_default();
+        if metadata.truncate_before != stored.truncate_before || metadata.legal_hold != stored.legal_hold {
+            return Err(AppError::Forbidden(
+                "Changing truncate_before or legal_hold needs the Admin role".to_string(),
+            ));
+        }
+    }
+    if metadata.archive_after_days.is_some_and(|days| days < 0) {
+        return Err(AppError::BadRequest(
+            "archive_after_days cannot be negative".to_string(),
+        ));
+    }
+    if metadata.truncate_before.is_some_and(|version| version < 0) {
+        return Err(AppError::BadRequest("truncate_before cannot be negative".to_string()));
+    }
+    if metadata.snapshot_threshold.is_some_and(|threshold| threshold <= 0) {
+        return Err(AppError::BadRequest(
+            "snapshot_threshold must be greater than 0".to_string(),
+        ));
+    }
 
-    let events = events?;
-    state.metrics.events_read.inc_by(events.len() as u64);
-    state.metrics.event_read_duration.observe(start_time.elapsed().as_secs_f64());
+    state.storage.set_stream_metadata(&stream_id, &metadata).await?;
+    // Truncation changes what reads return without any append to invalidate them
+    if let Some(cache) = &state.read_cache {
+        cache.invalidate_stream(&stream_id);
+    }
+    info!("Stream metadata updated: {}", stream_id);
 
-    Ok(Json(events))
+    Ok(Json(metadata))
 }
 
 async fn create_snapshot(
     State(state): State<AppState>,
+    tenant: Tenant,
     Json(request): Json<CreateSnapshotRequest>,
 ) -> Result<Json<Snapshot>> {
+    tenant.authorize(&request.stream_id)?;
     let start_time = std::time::Instant::now();
     state.metrics.snapshot_create_requests.inc();
 
-    // Compress data
-    let serialized_data = serde_json::to_vec(&request.data).map_err(|e| {
-        error!("Failed to serialize snapshot data: {}", e);
-        AppError::Internal("Serialization failed".to_string())
-    })?;
-
-    let compressed_data = lz4_flex::compress(&serialized_data);
-
-    let snapshot_id = Uuid::new_v4();
-    let now = Utc::now();
-
-    // Delete old snapshots for this stream (keep only latest)
-    sqlx::query!(
-        "DELETE FROM snapshots WHERE stream_id = $1",
-        request.stream_id
-    )
-    .execute(&state.db)
-    .await
-    .map_err(|e| {
-        error!("Failed to delete old snapshots: {}", e);
-        AppError::Database(e.to_string())
-    })?;
-
-    // Insert new snapshot
-    sqlx::query!(
-        r#"
-        INSERT INTO snapshots (id, stream_id, version, data, created_at)
-        VALUES ($1, $2, $3, $4, $5)
-        "#,
-        snapshot_id,
-        request.stream_id,
-        request.version,
-        compressed_data,
-        now
-    )
-    .execute(&state.db)
-    .await
-    .map_err(|e| {
-        error!("Failed to insert snapshot: {}", e);
+    let (compression, data) = state.snapshot_codec.encode(&request.data).map_err(|e| {
         state.metrics.snapshot_create_errors.inc();
-        AppError::Database(e.to_string())
+        e
     })?;
 
     let snapshot = Snapshot {
-        id: snapshot_id,
+        id: state.providers.new_id(),
         stream_id: request.stream_id,
         version: request.version,
-        data: compressed_data,
-        created_at: now,
+        data,
+        compression,
+        reducer: None,
+        created_at: state.providers.now(),
     };
 
+    state.storage.replace_snapshot(&snapshot).await.map_err(|e| {
+        state.metrics.snapshot_create_errors.inc();
+        e
+    })?;
+    snapshots::prune(
+        state.storage.as_ref(),
+        &snapshot.stream_id,
+        SnapshotRetention::from_config(&state.config),
+    )
+    .await?;
+
     state.metrics.snapshots_created.inc();
-    state.metrics.snapshot_create_duration.observe(start_time.elapsed().as_secs_f64());
+    state
+        .metrics
+        .snapshot_create_duration
+        .observe(start_time.elapsed().as_secs_f64());
 
     info!("Snapshot created: {} v{}", snapshot.stream_id, snapshot.version);
+    state.system_streams.snapshot_created(&snapshot).await;
 
     Ok(Json(snapshot))
 }
 
+/// GET /snapshots/:stream_id/latest — the newest snapshot's state, or 304
+/// while the stream's head is still at the `If-Match` version.
 async fn get_latest_snapshot(
     Path(stream_id): Path<String>,
     State(state): State<AppState>,
-) -> Result<Json<Option<serde_json::Value>>> {
+    headers: HeaderMap,
+) -> Result<Response> {
     let start_time = std::time::Instant::now();
     state.metrics.snapshot_read_requests.inc();
 
-    let row = sqlx::query!(
-        "SELECT data FROM snapshots WHERE stream_id = $1 ORDER BY version DESC LIMIT 1",
-        stream_id
-    )
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|e| {
-        error!("Failed to fetch snapshot: {}", e);
+    let head = state.storage.stream_version(&stream_id).await?;
+    if head > 0 && read_cache::version_matches(&headers, head) {
+        return Ok(read_cache::unchanged(head));
+    }
+
+    let snapshot = state.storage.latest_snapshot(&stream_id).await.map_err(|e| {
         state.metrics.snapshot_read_errors.inc();
-        AppError::Database(e.to_string())
+        e
     })?;
 
-    let result = if let Some(row) = row {
-        // Decompress data
-        let decompressed = lz4_flex::decompress(&row.data, 1024 * 1024) // 1MB max
-            .map_err(|e| {
-                error!("Failed to decompress snapshot: {}", e);
-                AppError::Internal("Decompression failed".to_string())
-            })?;
-
-        let data: serde_json::Value = serde_json::from_slice(&decompressed)
-            .map_err(|e| {
-                error!("Failed to deserialize snapshot: {}", e);
-                AppError::Internal("Deserialization failed".to_string())
-            })?;
-
-        Some(data)
-    } else {
-        None
-    };
+    let result = snapshot
+        .as_ref()
+        .map(|snapshot| state.snapshot_codec.decode(snapshot))
+        .transpose()?;
 
     state.metrics.snapshots_read.inc();
-    state.metrics.snapshot_read_duration.observe(start_time.elapsed().as_secs_f64());
+    state
+        .metrics
+        .snapshot_read_duration
+        .observe(start_time.elapsed().as_secs_f64());
 
-    Ok(Json(result))
+    Ok(([(STREAM_VERSION_HEADER, head.to_string())], Json(result)).into_response())
 }
 
-async fn get_stats(State(state): State<AppState>) -> Result<Json<serde_json::Value>> {
-    let total_events: i64 = sqlx::query_scalar!("SELECT COUNT(*) FROM events")
-        .fetch_one(&state.db)
-        .await
-        .map_err(|e| AppError::Database(e.to_string()))?
-        .unwrap_or(0);
-
-    let total_streams: i64 = sqlx::query_scalar!("SELECT COUNT(DISTINCT stream_id) FROM events")
-        .fetch_one(&state.db)
-        .await
-        .map_err(|e| AppError::Database(e.to_string()))?
-        .unwrap_or(0);
-
-    let total_snapshots: i64 = sqlx::query_scalar!("SELECT COUNT(*) FROM snapshots")
-        .fetch_one(&state.db)
-        .await
-        .map_err(|e| AppError::Database(e.to_string()))?
-        .unwrap_or(0);
+async fn get_stats(State(state): State<AppState>, tenant: Tenant) -> Result<Json<serde_json::Value>> {
+    let stats = match &tenant.0 {
+        Some(tenant_id) => state.storage.tenant_stats(tenant_id).await?,
+        None => state.storage.stats().await?,
+    };
 
     Ok(Json(serde_json::json!({
-        "total_events": total_events,
-        "total_streams": total_streams,
-        "total_snapshots": total_snapshots,
+        "total_events": stats.total_events,
+        "total_streams": stats.total_streams,
+        "total_snapshots": stats.total_snapshots,
+        "storage_bytes": stats.storage_bytes,
+        "events_per_day": stats.events_per_day,
         "uptime_seconds": std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -424,238 +1499,353 @@ async fn get_stats(State(state): State<AppState>) -> Result<Json<serde_json::Val
     })))
 }
 
-async fn initialize_database(database_url: &str) -> Result<PgPool> {
-    info!("Connecting to database...");
-    
-    let pool = PgPool::connect(database_url)
-        .await
-        .map_err(|e| AppError::Database(format!("Failed to connect to database: {}", e)))?;
-
-    info!("Database connection established");
-    Ok(pool)
-}
-
-async fn run_migrations(pool: &PgPool) -> Result<()> {
-    info!("Running database migrations...");
-
-    // Create events table
-    sqlx::query!(
-        r#"
-        CREATE TABLE IF NOT EXISTS events (
-            id UUID PRIMARY KEY,
-            stream_id VARCHAR NOT NULL,
-            event_type VARCHAR NOT NULL,
-            data JSONB NOT NULL,
-            metadata JSONB,
-            version BIGINT NOT NULL,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            partition_key VARCHAR NOT NULL,
-            UNIQUE(stream_id, version)
-        )
-        "#
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| AppError::Database(format!("Failed to create events table: {}", e)))?;
-
-    // Create indexes for performance
-    sqlx::query!("CREATE INDEX IF NOT EXISTS idx_events_stream_version ON events(stream_id, version)")
-        .execute(pool)
-        .await
-        .map_err(|e| AppError::Database(format!("Failed to create stream_version index: {}", e)))?;
-
-    sqlx::query!("CREATE INDEX IF NOT EXISTS idx_events_partition_key ON events(partition_key)")
-        .execute(pool)
-        .await
-        .map_err(|e| AppError::Database(format!("Failed to create partition_key index: {}", e)))?;
-
-    sqlx::query!("CREATE INDEX IF NOT EXISTS idx_events_created_at ON events(created_at)")
-        .execute(pool)
-        .await
-        .map_err(|e| AppError::Database(format!("Failed to create created_at index: {}", e)))?;
-
-    // Create snapshots table
-    sqlx::query!(
-        r#"
-        CREATE TABLE IF NOT EXISTS snapshots (
-            id UUID PRIMARY KEY,
-            stream_id VARCHAR NOT NULL,
-            version BIGINT NOT NULL,
-            data BYTEA NOT NULL,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(stream_id, version)
-        )
-        "#
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| AppError::Database(format!("Failed to create snapshots table: {}", e)))?;
-
-    sqlx::query!("CREATE INDEX IF NOT EXISTS idx_snapshots_stream_version ON snapshots(stream_id, version DESC)")
-        .execute(pool)
-        .await
-        .map_err(|e| AppError::Database(format!("Failed to create snapshots index: {}", e)))?;
-
-    info!("Database migrations completed");
-    Ok(())
-}
+/// GET /stats/timeseries?granularity=hour&days=7 — appends, body bytes and
+/// active streams per hour or day, oldest first, of the caller's tenant or
+/// the whole store. Postgres serves it from the rollup `stats_rollup`
+/// keeps, so the latest events may not be counted yet.
+async fn get_usage_timeseries(
+    State(state): State<AppState>,
+    Query(query): Query<TimeseriesQuery>,
+    tenant: Tenant,
+) -> Result<Json<serde_json::Value>> {
+    let days = query.days.unwrap_or(7);
+    let max_days = match query.granularity {
+        Granularity::Hour => MAX_HOURLY_DAYS,
+        Granularity::Day => MAX_DAILY_DAYS,
+    };
+    if !(1..=max_days).contains(&days) {
+        return Err(AppError::BadRequest(format!(
+            "days must be between 1 and {} for {} buckets",
+            max_days,
+            query.granularity.as_str()
+        )));
+    }
 
-async fn get_stream_version(pool: &PgPool, stream_id: &str) -> Result<i64> {
-    let version: Option<i64> = sqlx::query_scalar!(
-        "SELECT MAX(version) FROM events WHERE stream_id = $1",
-        stream_id
-    )
-    .fetch_one(pool)
-    .await
-    .map_err(|e| AppError::Database(e.to_string()))?;
+    let since = query
+        .granularity
+        .truncate(state.providers.now() - chrono::Duration::days(days));
+    let buckets = state
+        .storage
+        .usage_timeseries(tenant.0.as_deref(), query.granularity, since)
+        .await?;
 
-    Ok(version.unwrap_or(0))
+    Ok(Json(serde_json::json!({
+        "granularity": query.granularity,
+        "since": since,
+        "buckets": buckets,
+    })))
 }
 
-fn is_valid_stream_id(stream_id: &str) -> bool {
-    // Stream ID format: {project_id}/{workspace_id}/{stream_name}
-    stream_id.len() <= 255 && stream_id.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '/')
+fn is_json_content_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    essence.eq_ignore_ascii_case("application/json") || essence.ends_with("+json")
 }
 
 fn get_partition_key(stream_id: &str) -> String {
-    // Use project_id (first part) as partition key
+    // Use project_id (first part) as partition key; it is also the stream's tenant
     stream_id.split('/').next().unwrap_or(stream_id).to_string()
 }
 
 // Background task: Create snapshots periodically
-async fn snapshot_scheduler(pool: PgPool, config: Config) {
-    let mut interval = tokio::time::interval(Duration::from_secs(config.snapshot_interval_seconds));
-    
-    loop {
-        interval.tick().await;
-        
-        info!("Running scheduled snapshot creation...");
-        
-        // Find streams that need snapshots (version > last_snapshot_version + threshold)
-        let streams = match sqlx::query!(
-            r#"
-            SELECT e.stream_id, MAX(e.version) as current_version,
-                   COALESCE(s.version, 0) as snapshot_version
-            FROM events e
-            LEFT JOIN snapshots s ON e.stream_id = s.stream_id
-            GROUP BY e.stream_id, s.version
-            HAVING MAX(e.version) - COALESCE(s.version, 0) >= $1
-            "#,
-            config.snapshot_threshold
+async fn snapshot_scheduler(
+    job: Arc<Job>,
+    storage: Arc<dyn EventStorage>,
+    codec: Arc<SnapshotCodec>,
+    plugins: Arc<PluginHost>,
+    system_streams: SystemStreams,
+    config: Config,
+    providers: Providers,
+) {
+    let retention = SnapshotRetention::from_config(&config);
+    jobs::run_periodically("snapshot_scheduler", job, || {
+        snapshot_once(
+            storage.as_ref(),
+            &codec,
+            &plugins,
+            &system_streams,
+            &config,
+            &providers,
+            retention,
         )
-        .fetch_all(&pool)
-        .await
-        {
-            Ok(streams) => streams,
-            Err(e) => {
-                error!("Failed to query streams for snapshots: {}", e);
-                continue;
-            }
-        };
+    })
+    .await
+}
 
-        for stream in streams {
+/// Snapshots every stream at least `snapshot_threshold` events past its
+/// latest snapshot, or the threshold in its metadata, folded by the
+/// stream's reducer if it has one, then prunes the stream's older
+/// snapshots. Streams whose metadata sets `auto_snapshot: false` are
+/// skipped. Up to `snapshot_concurrency`
+/// streams are rebuilt at once, each from its previous snapshot and by at
+/// most `snapshot_batch_events` events per pass, so a stream far behind
+/// catches up over several passes instead of being loaded whole. System
+/// streams have no state to snapshot.
+async fn snapshot_once(
+    storage: &dyn EventStorage,
+    codec: &SnapshotCodec,
+    plugins: &Arc<PluginHost>,
+    system_streams: &SystemStreams,
+    config: &Config,
+    providers: &Providers,
+    retention: SnapshotRetention,
+) -> Result<String> {
+    // Only streams with a policy of their own; the rest use the default
+    let policies: HashMap<String, StreamMetadata> = storage
+        .all_stream_metadata()
+        .await?
+        .into_iter()
+        .filter(|(_, metadata)| metadata.has_snapshot_policy())
+        .collect();
+    let threshold_of = |stream_id: &str| match policies.get(stream_id) {
+        Some(metadata) => metadata.scheduled_snapshot_threshold(config.snapshot_threshold),
+        None => Some(config.snapshot_threshold),
+    };
+    // Candidates past the lowest threshold in use, narrowed per stream below
+    let lowest = policies
+        .values()
+        .filter_map(|metadata| metadata.scheduled_snapshot_threshold(config.snapshot_threshold))
+        .fold(config.snapshot_threshold, i64::min);
+    let streams = storage.snapshot_candidates(lowest).await?;
+    let reducers = &storage.snapshot_reducers().await?;
+    let batch_events = config.snapshot_batch_events;
+
+    let due = streams.iter().filter(|s| {
+        !system_streams::is_system_stream(&s.stream_id)
+            && threshold_of(&s.stream_id).is_some_and(|threshold| s.current_version - s.snapshot_version >= threshold)
+    });
+    let outcomes: Vec<bool> = futures::stream::iter(due)
+        .map(|stream| async move {
             let stream_id = &stream.stream_id;
-            let version = stream.current_version;
-
-            // Rebuild state from events to create snapshot
-            match rebuild_stream_state(&pool, stream_id, version).await {
-                Ok(state_data) => {
-                    let compressed_data = match serde_json::to_vec(&state_data)
-                        .and_then(|data| Ok(lz4_flex::compress(&data)))
-                    {
-                        Ok(data) => data,
-                        Err(e) => {
-                            error!("Failed to compress snapshot data for {}: {}", stream_id, e);
-                            continue;
-                        }
-                    };
-
-                    if let Err(e) = sqlx::query!(
-                        r#"
-                        INSERT INTO snapshots (id, stream_id, version, data, created_at)
-                        VALUES ($1, $2, $3, $4, NOW())
-                        ON CONFLICT (stream_id, version) DO NOTHING
-                        "#,
-                        Uuid::new_v4(),
+
+            let reducer = reducers::for_stream(reducers, stream_id);
+            let state = match reducer {
+                Some(reducer) => {
+                    reducers::reduce_batch(
+                        storage,
+                        codec,
+                        plugins,
+                        reducer,
+                        stream_id,
+                        stream.current_version,
+                        batch_events,
+                    )
+                    .await
+                }
+                None => {
+                    rebuild_stream_state(
+                        storage,
+                        codec,
                         stream_id,
-                        version,
-                        compressed_data
+                        stream.current_version,
+                        batch_events,
+                        providers.now(),
                     )
-                    .execute(&pool)
                     .await
-                    {
-                        error!("Failed to create snapshot for {}: {}", stream_id, e);
-                    } else {
-                        info!("Created snapshot for {} at version {}", stream_id, version);
-                    }
                 }
+            };
+            let (state_data, version) = match state {
+                Ok(state) => state,
                 Err(e) => {
                     error!("Failed to rebuild state for {}: {}", stream_id, e);
+                    return false;
                 }
+            };
+            let (compression, compressed_data) = match codec.encode(&state_data) {
+                Ok(encoded) => encoded,
+                Err(e) => {
+                    error!("Failed to compress snapshot data for {}: {}", stream_id, e);
+                    return false;
+                }
+            };
+
+            let snapshot = Snapshot {
+                id: providers.new_id(),
+                stream_id: stream_id.clone(),
+                version,
+                data: compressed_data,
+                compression,
+                reducer: reducer.map(|r| r.name.clone()),
+                created_at: providers.now(),
+            };
+
+            if let Err(e) = storage.insert_snapshot(&snapshot).await {
+                error!("Failed to create snapshot for {}: {}", stream_id, e);
+                return false;
             }
-        }
-
-        info!("Scheduled snapshot creation completed");
+            info!("Created snapshot for {} at version {}", stream_id, version);
+            system_streams.snapshot_created(&snapshot).await;
+            if let Err(e) = snapshots::prune(storage, stream_id, retention).await {
+                error!("Failed to prune snapshots of {}: {}", stream_id, e);
+            }
+            true
+        })
+        .buffer_unordered(config.snapshot_concurrency)
+        .collect()
+        .await;
+    let created = outcomes.iter().filter(|created| **created).count();
+    let failed = outcomes.len() - created;
+
+    if failed > 0 {
+        return Err(AppError::Internal(format!(
+            "Created {} snapshots, {} failed",
+            created, failed
+        )));
     }
+    Ok(format!("Created {} snapshots", created))
 }
 
 // Background task: Archive old streams
-async fn stream_archiver(pool: PgPool, config: Config) {
-    let mut interval = tokio::time::interval(Duration::from_secs(config.archive_interval_seconds));
-    
-    loop {
-        interval.tick().await;
-        
-        info!("Running stream archival...");
-        
-        // Archive streams older than threshold that have snapshots
-        let archive_threshold = Utc::now() - chrono::Duration::days(config.archive_days);
-        
-        match sqlx::query!(
-            r#"
-            UPDATE events 
-            SET archived = true 
-            WHERE created_at < $1 
-            AND stream_id IN (SELECT stream_id FROM snapshots)
-            AND archived = false
-            "#,
-            archive_threshold
-        )
-        .execute(&pool)
-        .await
-        {
-            Ok(result) => {
-                info!("Archived {} events", result.rows_affected());
-            }
-            Err(e) => {
-                error!("Failed to archive events: {}", e);
-            }
-        }
+async fn stream_archiver(
+    job: Arc<Job>,
+    storage: Arc<dyn EventStorage>,
+    cold_store: Option<Arc<ColdStore>>,
+    metrics: Metrics,
+    config: Config,
+) {
+    jobs::run_periodically("stream_archiver", job, || async {
+        let archived = archive_once(storage.as_ref(), cold_store.as_deref(), &metrics, config.archive_days).await?;
+        Ok(format!("Archived {} events", archived))
+    })
+    .await
+}
+
+// Background task: Physically delete truncated and expired events
+async fn scavenge_loop(
+    job: Arc<Job>,
+    scavenger: Arc<Scavenger>,
+    storage: Arc<dyn EventStorage>,
+    metrics: Metrics,
+    settings: ScavengeSettings,
+) {
+    jobs::run_periodically("scavenger", job, || scavenger.run(storage.as_ref(), &metrics, settings)).await
+}
 
-        sleep(Duration::from_secs(1)).await;
+/// One archival pass: moves events older than `archive_days` (or the
+/// stream's own `archive_after_days`) on snapshotted streams out of the hot
+/// table, to cold storage when it is configured and to the archive table
+/// otherwise. Returns the number of events moved.
+async fn archive_once(
+    storage: &dyn EventStorage,
+    cold_store: Option<&ColdStore>,
+    metrics: &Metrics,
+    archive_days: i64,
+) -> Result<u64> {
+    let overrides: HashMap<String, i64> = storage
+        .all_stream_metadata()
+        .await?
+        .into_iter()
+        .filter_map(|(stream_id, metadata)| metadata.archive_after_days.map(|days| (stream_id, days)))
+        .collect();
+
+    // One scan per distinct age, keeping the streams that use it
+    let mut ages: Vec<i64> = overrides.values().copied().collect();
+    ages.push(archive_days);
+    ages.sort_unstable();
+    ages.dedup();
+
+    let now = Utc::now();
+    let mut ranges = Vec::new();
+    for days in ages {
+        let threshold = now - chrono::Duration::days(days);
+        ranges.extend(
+            storage
+                .archivable_ranges(threshold)
+                .await?
+                .into_iter()
+                .filter(|range| overrides.get(&range.stream_id).copied().unwrap_or(archive_days) == days),
+        );
     }
+
+    let due: u64 = ranges.iter().map(|r| (r.to_version - r.from_version + 1) as u64).sum();
+    metrics.archive_backlog.set(due as i64);
+
+    let archived = match cold_store {
+        Some(cold_store) => {
+            let tiered = cold_storage::tier_out(storage, cold_store, metrics, &ranges).await?;
+            info!("Moved {} events to cold storage", tiered);
+            tiered
+        }
+        None => storage.archive_ranges(&ranges).await?,
+    };
+    metrics.archive_backlog.set(due.saturating_sub(archived) as i64);
+    Ok(archived)
 }
 
+/// The default snapshot state, the data of every event, for streams
+/// without a reducer. Continues from the newest such snapshot at or before
+/// `up_to_version` and takes at most `max_events` more; returns the state
+/// and the version it reached.
 async fn rebuild_stream_state(
-    pool: &PgPool,
+    storage: &dyn EventStorage,
+    codec: &SnapshotCodec,
     stream_id: &str,
     up_to_version: i64,
-) -> Result<serde_json::Value> {
-    let events = sqlx::query!(
-        "SELECT data FROM events WHERE stream_id = $1 AND version <= $2 ORDER BY version",
-        stream_id,
-        up_to_version
-    )
-    .fetch_all(pool)
-    .await
-    .map_err(|e| AppError::Database(e.to_string()))?;
+    max_events: i64,
+    reconstructed_at: chrono::DateTime<Utc>,
+) -> Result<(serde_json::Value, i64)> {
+    // Snapshots posted by clients have no reducer either; only resume from
+    // one in the shape written here
+    let previous = match storage.snapshot_at(stream_id, up_to_version).await? {
+        Some(snapshot) if snapshot.reducer.is_none() => {
+            let mut state = codec.decode(&snapshot)?;
+            let resumable = state.get("reconstructed_at").is_some()
+                && state.get("version").and_then(|v| v.as_i64()) == Some(snapshot.version);
+            match state.get_mut("events").map(serde_json::Value::take) {
+                Some(serde_json::Value::Array(events)) if resumable => Some((events, snapshot.version)),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    let (mut events, from_version) = previous.map_or((Vec::new(), 1), |(events, version)| (events, version + 1));
+    let up_to_version = up_to_version.min((from_version - 1).saturating_add(max_events));
+
+    events.extend(storage.load_stream_data(stream_id, from_version, up_to_version).await?);
 
-    // Simple state reconstruction - just collect all event data
-    let state: Vec<serde_json::Value> = events.into_iter().map(|row| row.data).collect();
-    
-    Ok(serde_json::json!({
-        "events": state,
+    let state = serde_json::json!({
+        "events": events,
         "version": up_to_version,
-        "reconstructed_at": Utc::now()
-    }))
+        "reconstructed_at": reconstructed_at
+    });
+    Ok((state, up_to_version))
+}
+
+// Background task: Fold new events into the running statistics behind
+// /stats and /stats/timeseries, a batch at a time until caught up
+async fn stats_rollup(job: Arc<Job>, storage: Arc<dyn EventStorage>) {
+    jobs::run_periodically("stats_rollup", job, || async {
+        let mut counted = 0;
+        loop {
+            let batch = storage.roll_up_stats(STATS_ROLLUP_BATCH).await?;
+            counted += batch;
+            if batch < STATS_ROLLUP_BATCH as u64 {
+                break;
+            }
+        }
+        Ok(format!("Counted {} events", counted))
+    })
+    .await
+}
+
+// Background task: Sample the bytes each project stores, for the billing
+// usage report
+async fn usage_sampler(job: Arc<Job>, storage: Arc<dyn EventStorage>) {
+    jobs::run_periodically("usage_sampler", job, || async {
+        let projects = storage.sample_storage_usage().await?;
+        Ok(format!("Sampled storage of {} projects", projects))
+    })
+    .await
+}
+
+// Background task: Pre-create upcoming time partitions so month rollovers
+// never wait on DDL
+async fn partition_maintainer(job: Arc<Job>, storage: Arc<dyn EventStorage>, config: Config) {
+    jobs::run_periodically("partition_maintainer", job, || async {
+        storage.prepare_partitions(config.partition_premake_months).await?;
+        Ok(format!(
+            "Partitions prepared {} months ahead",
+            config.partition_premake_months
+        ))
+    })
+    .await
 }
@@ -1,5 +1,54 @@
-use prometheus::{Counter, Histogram, IntCounter, Registry};
-use std::sync::Arc;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry,
+};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::models::Event;
+use crate::{get_partition_key, AppState};
+
+/// Partitions given their own label before the rest are folded into `other`.
+const DEFAULT_MAX_PARTITION_LABELS: usize = 100;
+/// The label value shared by values past the cap.
+const OTHER_LABEL: &str = "other";
+
+/// Caps the distinct values of a label fed by tenants or callers, like
+/// `partition_key`: the first values seen keep their own series, later ones
+/// share `other`, so a tenant explosion can't take the metrics endpoint
+/// down with it. Restarts reset which values made the cut.
+#[derive(Debug)]
+struct BoundedLabels {
+    seen: Mutex<HashSet<String>>,
+    max: usize,
+}
+
+impl BoundedLabels {
+    fn new(max: usize) -> Arc<Self> {
+        Arc::new(Self {
+            seen: Mutex::new(HashSet::new()),
+            max,
+        })
+    }
+
+    fn label(&self, value: String) -> String {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.contains(&value) {
+            return value;
+        }
+        if seen.len() < self.max {
+            seen.insert(value.clone());
+            return value;
+        }
+        OTHER_LABEL.to_string()
+    }
+}
 
 #[derive(Clone)]
 pub struct Metrics {
@@ -21,6 +70,56 @@ pub struct Metrics {
     pub snapshot_read_duration: Histogram,
     pub snapshots_created: IntCounter,
     pub snapshots_read: IntCounter,
+    pub deprecated_events_appended: IntCounterVec,
+    pub events_upcast: IntCounter,
+    pub events_tiered: IntCounter,
+    pub events_bulk_loaded: IntCounter,
+    pub events_scavenged: IntCounterVec,
+    pub cold_storage_reads: IntCounter,
+    pub read_cache_hits: IntCounter,
+    pub read_cache_misses: IntCounter,
+    pub requests_rate_limited: IntCounterVec,
+    pub quota_rejections: IntCounterVec,
+    pub bus_events_published: IntCounter,
+    pub bus_events_dropped: IntCounterVec,
+    pub bus_consumer_lag: IntGaugeVec,
+    pub dead_letters: IntCounter,
+    pub subscription_messages_dropped: IntCounter,
+    pub subscriptions_disconnected: IntCounter,
+    pub subscriptions_open: IntGauge,
+    pub db_connections: IntGaugeVec,
+    pub job_lag: GaugeVec,
+    pub archive_backlog: IntGauge,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub partition_events_appended: IntCounterVec,
+    pub partition_bytes_appended: IntCounterVec,
+    pub partition_events_read: IntCounterVec,
+    pub partition_errors: IntCounterVec,
+    pub slow_operations: IntCounterVec,
+    pub large_payloads: IntCounter,
+    pub errors_capture_dropped: IntCounter,
+    pub error_budget_alerts: IntCounterVec,
+    pub circuit_breaker_state: IntGauge,
+    pub circuit_breaker_trips: IntCounter,
+    pub circuit_breaker_rejections: IntCounter,
+    pub checksum_mismatches: IntCounter,
+    pub chain_breaks: IntCounter,
+    pub requests_shed: IntCounterVec,
+    pub requests_timed_out: IntCounter,
+    pub replication_lag_events: IntGauge,
+    pub replication_lag_seconds: Gauge,
+    pub replication_events_shipped: IntCounter,
+    pub replication_events_applied: IntCounter,
+    pub leader: IntGauge,
+    pub leader_epoch: IntGauge,
+    pub subscription_checkpoint: IntGaugeVec,
+    pub subscription_lag: IntGaugeVec,
+    pub subscription_in_flight: IntGaugeVec,
+    pub subscription_parked: IntGaugeVec,
+    partitions: Arc<BoundedLabels>,
+    /// Rate-limited callers, labelled under the same cap as partitions.
+    callers: Arc<BoundedLabels>,
 }
 
 impl Metrics {
@@ -120,6 +219,304 @@ impl Metrics {
             "Total number of snapshots read"
         ).expect("Failed to create metric");
 
+        let deprecated_events_appended = IntCounterVec::new(
+            Opts::new(
+                "event_store_deprecated_events_appended_total",
+                "Total number of events appended with a deprecated event type"
+            ),
+            &["event_type"]
+        ).expect("Failed to create metric");
+
+        let events_scavenged = IntCounterVec::new(
+            Opts::new(
+                "event_store_events_scavenged_total",
+                "Total number of events physically deleted by the scavenger"
+            ),
+            &["reason"]
+        ).expect("Failed to create metric");
+
+        let events_upcast = IntCounter::new(
+            "event_store_events_upcast_total",
+            "Total number of historical events rewritten to a successor type"
+        ).expect("Failed to create metric");
+
+        let events_tiered = IntCounter::new(
+            "event_store_events_tiered_total",
+            "Total number of events moved to cold storage"
+        ).expect("Failed to create metric");
+
+        let events_bulk_loaded = IntCounter::new(
+            "event_store_events_bulk_loaded_total",
+            "Total number of events written through the bulk load endpoint"
+        ).expect("Failed to create metric");
+
+        let cold_storage_reads = IntCounter::new(
+            "event_store_cold_storage_reads_total",
+            "Total number of reads served partly from cold storage"
+        ).expect("Failed to create metric");
+
+        let read_cache_hits = IntCounter::new(
+            "event_store_read_cache_hits_total",
+            "Total number of stream reads served from the read cache"
+        ).expect("Failed to create metric");
+
+        let read_cache_misses = IntCounter::new(
+            "event_store_read_cache_misses_total",
+            "Total number of stream reads that missed the read cache"
+        ).expect("Failed to create metric");
+
+        let requests_rate_limited = IntCounterVec::new(
+            Opts::new(
+                "event_store_requests_rate_limited_total",
+                "Total number of requests rejected by a rate limit"
+            ),
+            &["key"]
+        ).expect("Failed to create metric");
+
+        let quota_rejections = IntCounterVec::new(
+            Opts::new(
+                "event_store_quota_rejections_total",
+                "Total number of appends rejected for exceeding a stream quota"
+            ),
+            &["quota"]
+        ).expect("Failed to create metric");
+
+        let bus_events_published = IntCounter::new(
+            "event_store_bus_events_published_total",
+            "Total number of committed events published on the internal bus"
+        ).expect("Failed to create metric");
+
+        let bus_events_dropped = IntCounterVec::new(
+            Opts::new(
+                "event_store_bus_events_dropped_total",
+                "Total number of bus events dropped because a consumer fell behind"
+            ),
+            &["consumer"]
+        ).expect("Failed to create metric");
+
+        let bus_consumer_lag = IntGaugeVec::new(
+            Opts::new(
+                "event_store_bus_consumer_lag",
+                "Events queued for a bus consumer but not yet handled"
+            ),
+            &["consumer"]
+        ).expect("Failed to create metric");
+
+        let dead_letters = IntCounter::new(
+            "event_store_dead_letters_total",
+            "Total number of events dead-lettered after their delivery exhausted its retries"
+        ).expect("Failed to create metric");
+
+        let subscription_messages_dropped = IntCounter::new(
+            "event_store_subscription_messages_dropped_total",
+            "Total number of subscription messages dropped from a full subscriber buffer"
+        ).expect("Failed to create metric");
+
+        let subscriptions_disconnected = IntCounter::new(
+            "event_store_subscriptions_disconnected_total",
+            "Total number of subscriptions ended because their subscriber buffer overflowed"
+        ).expect("Failed to create metric");
+
+        let subscriptions_open = IntGauge::new(
+            "event_store_subscriptions_open",
+            "Streamed subscriptions currently open"
+        ).expect("Failed to create metric");
+
+        let db_connections = IntGaugeVec::new(
+            Opts::new(
+                "event_store_db_connections",
+                "Database pool connections, open and idle"
+            ),
+            &["state"]
+        ).expect("Failed to create metric");
+
+        let job_lag = GaugeVec::new(
+            Opts::new(
+                "event_store_job_lag_seconds",
+                "Seconds a background job is past the time its next scheduled run was due"
+            ),
+            &["job"]
+        ).expect("Failed to create metric");
+
+        let archive_backlog = IntGauge::new(
+            "event_store_archive_backlog_events",
+            "Events due for archival that the last archiver run left in the hot table"
+        ).expect("Failed to create metric");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new(
+                "event_store_http_requests_total",
+                "Total number of HTTP requests by route and status code"
+            ),
+            &["method", "route", "status"]
+        ).expect("Failed to create metric");
+
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "event_store_http_request_duration_seconds",
+                "Duration of HTTP requests by route"
+            ).buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 2.0, 5.0]),
+            &["method", "route"]
+        ).expect("Failed to create metric");
+
+        let partition_events_appended = IntCounterVec::new(
+            Opts::new(
+                "event_store_partition_events_appended_total",
+                "Total number of events appended per partition"
+            ),
+            &["partition_key"]
+        ).expect("Failed to create metric");
+
+        let partition_bytes_appended = IntCounterVec::new(
+            Opts::new(
+                "event_store_partition_bytes_appended_total",
+                "Total bytes of event data and payloads appended per partition"
+            ),
+            &["partition_key"]
+        ).expect("Failed to create metric");
+
+        let partition_events_read = IntCounterVec::new(
+            Opts::new(
+                "event_store_partition_events_read_total",
+                "Total number of events read per partition"
+            ),
+            &["partition_key"]
+        ).expect("Failed to create metric");
+
+        let partition_errors = IntCounterVec::new(
+            Opts::new(
+                "event_store_partition_errors_total",
+                "Total number of failed or rejected appends and reads per partition"
+            ),
+            &["partition_key", "operation"]
+        ).expect("Failed to create metric");
+
+        let slow_operations = IntCounterVec::new(
+            Opts::new(
+                "event_store_slow_operations_total",
+                "Total number of storage operations slower than the slow operation threshold"
+            ),
+            &["operation"]
+        ).expect("Failed to create metric");
+
+        let large_payloads = IntCounter::new(
+            "event_store_large_payloads_total",
+            "Total number of appended events larger than the large payload threshold"
+        ).expect("Failed to create metric");
+
+        let errors_capture_dropped = IntCounter::new(
+            "event_store_errors_capture_dropped_total",
+            "Total number of captured errors dropped because the error log writer fell behind"
+        ).expect("Failed to create metric");
+
+        let error_budget_alerts = IntCounterVec::new(
+            Opts::new(
+                "event_store_error_budget_alerts_total",
+                "Total number of alerts fired for error types over their error budget"
+            ),
+            &["error_type", "severity"]
+        ).expect("Failed to create metric");
+
+        let circuit_breaker_state = IntGauge::new(
+            "event_store_circuit_breaker_state",
+            "Database circuit breaker state: 0 closed, 1 half-open, 2 open"
+        ).expect("Failed to create metric");
+
+        let circuit_breaker_trips = IntCounter::new(
+            "event_store_circuit_breaker_trips_total",
+            "Total number of times the database circuit breaker opened"
+        ).expect("Failed to create metric");
+
+        let circuit_breaker_rejections = IntCounter::new(
+            "event_store_circuit_breaker_rejections_total",
+            "Total number of requests refused while the database circuit breaker was open"
+        ).expect("Failed to create metric");
+
+        let checksum_mismatches = IntCounter::new(
+            "event_store_checksum_mismatches_total",
+            "Total number of events found not to match their checksum during verification"
+        ).expect("Failed to create metric");
+
+        let chain_breaks = IntCounter::new(
+            "event_store_chain_breaks_total",
+            "Total number of events found to break their stream's hash chain during verification"
+        ).expect("Failed to create metric");
+
+        let requests_shed = IntCounterVec::new(
+            Opts::new(
+                "event_store_requests_shed_total",
+                "Total number of requests refused because too many of their class were in progress"
+            ),
+            &["class"]
+        ).expect("Failed to create metric");
+
+        let requests_timed_out = IntCounter::new(
+            "event_store_requests_timed_out_total",
+            "Total number of requests abandoned at their deadline"
+        ).expect("Failed to create metric");
+
+        let replication_lag_events = IntGauge::new(
+            "event_store_replication_lag_events",
+            "Global positions committed but not yet shipped to the replication target"
+        ).expect("Failed to create metric");
+
+        let replication_lag_seconds = Gauge::new(
+            "event_store_replication_lag_seconds",
+            "Age of the oldest committed event not yet shipped to the replication target"
+        ).expect("Failed to create metric");
+
+        let replication_events_shipped = IntCounter::new(
+            "event_store_replication_events_shipped_total",
+            "Total number of events shipped to the replication target"
+        ).expect("Failed to create metric");
+
+        let replication_events_applied = IntCounter::new(
+            "event_store_replication_events_applied_total",
+            "Total number of events received from a primary and stored"
+        ).expect("Failed to create metric");
+
+        let leader = IntGauge::new(
+            "event_store_leader",
+            "Whether this instance holds the leader lease: 1 leader, 0 follower"
+        ).expect("Failed to create metric");
+
+        let leader_epoch = IntGauge::new(
+            "event_store_leader_epoch",
+            "Epoch of the leader lease while this instance holds it, otherwise 0"
+        ).expect("Failed to create metric");
+
+        let subscription_checkpoint = IntGaugeVec::new(
+            Opts::new(
+                "event_store_subscription_checkpoint_position",
+                "Global position through which a persistent subscription group has acknowledged every event"
+            ),
+            &["group"]
+        ).expect("Failed to create metric");
+
+        let subscription_lag = IntGaugeVec::new(
+            Opts::new(
+                "event_store_subscription_lag_events",
+                "Global positions committed past a persistent subscription group's checkpoint"
+            ),
+            &["group"]
+        ).expect("Failed to create metric");
+
+        let subscription_in_flight = IntGaugeVec::new(
+            Opts::new(
+                "event_store_subscription_in_flight",
+                "Events delivered to a persistent subscription group's consumers and not yet acknowledged"
+            ),
+            &["group"]
+        ).expect("Failed to create metric");
+
+        let subscription_parked = IntGaugeVec::new(
+            Opts::new(
+                "event_store_subscription_parked",
+                "Events a persistent subscription group's consumers failed too often, held back from redelivery"
+            ),
+            &["group"]
+        ).expect("Failed to create metric");
+
         // Register all metrics
         registry.register(Box::new(event_append_requests.clone())).expect("Failed to register metric");
         registry.register(Box::new(event_append_errors.clone())).expect("Failed to register metric");
@@ -138,6 +535,53 @@ impl Metrics {
         registry.register(Box::new(snapshot_read_duration.clone())).expect("Failed to register metric");
         registry.register(Box::new(snapshots_created.clone())).expect("Failed to register metric");
         registry.register(Box::new(snapshots_read.clone())).expect("Failed to register metric");
+        registry.register(Box::new(deprecated_events_appended.clone())).expect("Failed to register metric");
+        registry.register(Box::new(events_upcast.clone())).expect("Failed to register metric");
+        registry.register(Box::new(events_tiered.clone())).expect("Failed to register metric");
+        registry.register(Box::new(events_bulk_loaded.clone())).expect("Failed to register metric");
+        registry.register(Box::new(events_scavenged.clone())).expect("Failed to register metric");
+        registry.register(Box::new(cold_storage_reads.clone())).expect("Failed to register metric");
+        registry.register(Box::new(read_cache_hits.clone())).expect("Failed to register metric");
+        registry.register(Box::new(read_cache_misses.clone())).expect("Failed to register metric");
+        registry.register(Box::new(requests_rate_limited.clone())).expect("Failed to register metric");
+        registry.register(Box::new(quota_rejections.clone())).expect("Failed to register metric");
+        registry.register(Box::new(bus_events_published.clone())).expect("Failed to register metric");
+        registry.register(Box::new(bus_events_dropped.clone())).expect("Failed to register metric");
+        registry.register(Box::new(bus_consumer_lag.clone())).expect("Failed to register metric");
+        registry.register(Box::new(dead_letters.clone())).expect("Failed to register metric");
+        registry.register(Box::new(subscription_messages_dropped.clone())).expect("Failed to register metric");
+        registry.register(Box::new(subscriptions_disconnected.clone())).expect("Failed to register metric");
+        registry.register(Box::new(subscriptions_open.clone())).expect("Failed to register metric");
+        registry.register(Box::new(db_connections.clone())).expect("Failed to register metric");
+        registry.register(Box::new(job_lag.clone())).expect("Failed to register metric");
+        registry.register(Box::new(archive_backlog.clone())).expect("Failed to register metric");
+        registry.register(Box::new(http_requests.clone())).expect("Failed to register metric");
+        registry.register(Box::new(http_request_duration.clone())).expect("Failed to register metric");
+        registry.register(Box::new(partition_events_appended.clone())).expect("Failed to register metric");
+        registry.register(Box::new(partition_bytes_appended.clone())).expect("Failed to register metric");
+        registry.register(Box::new(partition_events_read.clone())).expect("Failed to register metric");
+        registry.register(Box::new(partition_errors.clone())).expect("Failed to register metric");
+        registry.register(Box::new(slow_operations.clone())).expect("Failed to register metric");
+        registry.register(Box::new(large_payloads.clone())).expect("Failed to register metric");
+        registry.register(Box::new(errors_capture_dropped.clone())).expect("Failed to register metric");
+        registry.register(Box::new(error_budget_alerts.clone())).expect("Failed to register metric");
+        registry.register(Box::new(circuit_breaker_state.clone())).expect("Failed to register metric");
+        registry.register(Box::new(circuit_breaker_trips.clone())).expect("Failed to register metric");
+        registry.register(Box::new(circuit_breaker_rejections.clone())).expect("Failed to register metric");
+        registry.register(Box::new(checksum_mismatches.clone())).expect("Failed to register metric");
+        registry.register(Box::new(chain_breaks.clone())).expect("Failed to register metric");
+        registry.register(Box::new(requests_shed.clone())).expect("Failed to register metric");
+        registry.register(Box::new(requests_timed_out.clone())).expect("Failed to register metric");
+        registry.register(Box::new(replication_lag_events.clone())).expect("Failed to register metric");
+        registry.register(Box::new(replication_lag_seconds.clone())).expect("Failed to register metric");
+        registry.register(Box::new(replication_events_shipped.clone())).expect("Failed to register metric");
+        registry.register(Box::new(replication_events_applied.clone())).expect("Failed to register metric");
+        registry.register(Box::new(leader.clone())).expect("Failed to register metric");
+        registry.register(Box::new(leader_epoch.clone())).expect("Failed to register metric");
+        registry.register(Box::new(subscription_checkpoint.clone())).expect("Failed to register metric");
+        registry.register(Box::new(subscription_lag.clone())).expect("Failed to register metric");
+        registry.register(Box::new(subscription_in_flight.clone())).expect("Failed to register metric");
+        registry.register(Box::new(subscription_parked.clone())).expect("Failed to register metric");
 
         Self {
             registry,
@@ -158,6 +602,136 @@ impl Metrics {
             snapshot_read_duration,
             snapshots_created,
             snapshots_read,
+            deprecated_events_appended,
+            events_upcast,
+            events_tiered,
+            events_bulk_loaded,
+            events_scavenged,
+            cold_storage_reads,
+            read_cache_hits,
+            read_cache_misses,
+            requests_rate_limited,
+            quota_rejections,
+            bus_events_published,
+            bus_events_dropped,
+            bus_consumer_lag,
+            dead_letters,
+            subscription_messages_dropped,
+            subscriptions_disconnected,
+            subscriptions_open,
+            db_connections,
+            job_lag,
+            archive_backlog,
+            http_requests,
+            http_request_duration,
+            partition_events_appended,
+            partition_bytes_appended,
+            partition_events_read,
+            partition_errors,
+            slow_operations,
+            large_payloads,
+            errors_capture_dropped,
+            error_budget_alerts,
+            circuit_breaker_state,
+            circuit_breaker_trips,
+            circuit_breaker_rejections,
+            checksum_mismatches,
+            chain_breaks,
+            requests_shed,
+            requests_timed_out,
+            replication_lag_events,
+            replication_lag_seconds,
+            replication_events_shipped,
+            replication_events_applied,
+            leader,
+            leader_epoch,
+            subscription_checkpoint,
+            subscription_lag,
+            subscription_in_flight,
+            subscription_parked,
+            partitions: BoundedLabels::new(DEFAULT_MAX_PARTITION_LABELS),
+            callers: BoundedLabels::new(DEFAULT_MAX_PARTITION_LABELS),
+        }
+    }
+
+    /// Caps the partitions, and the rate-limited callers, given their own
+    /// label at `max`.
+    pub fn with_max_partition_labels(mut self, max: usize) -> Self {
+        self.partitions = BoundedLabels::new(max);
+        self.callers = BoundedLabels::new(max);
+        self
+    }
+
+    pub fn record_append(&self, event: &Event) {
+        let partition = self.partitions.label(get_partition_key(&event.stream_id));
+        let bytes = match &event.payload {
+            Some(payload) => payload.len(),
+            None => serde_json::to_vec(&event.data).map_or(0, |data| data.len()),
+        };
+        self.partition_events_appended.with_label_values(&[&partition]).inc();
+        self.partition_bytes_appended
+            .with_label_values(&[&partition])
+            .inc_by(bytes as u64);
+    }
+
+    pub fn record_read(&self, stream_id: &str, events: u64) {
+        self.events_read.inc_by(events);
+        self.partition_events_read
+            .with_label_values(&[&self.partitions.label(get_partition_key(stream_id))])
+            .inc_by(events);
+    }
+
+    /// Counts a request from `caller` rejected by the rate limit.
+    pub fn record_rate_limited(&self, caller: &str) {
+        self.requests_rate_limited
+            .with_label_values(&[&self.callers.label(caller.to_string())])
+            .inc();
+    }
+
+    /// Counts a failed `operation` ("append" or "read") on a stream against
+    /// its partition.
+    pub fn record_error(&self, stream_id: &str, operation: &str) {
+        self.partition_errors
+            .with_label_values(&[&self.partitions.label(get_partition_key(stream_id)), operation])
+            .inc();
+    }
+
+    /// Samples the gauges that describe current state rather than count
+    /// events, just before a scrape.
+    pub fn observe(&self, state: &AppState) {
+        if let Some((open, idle)) = state.storage.pool_connections() {
+            self.db_connections.with_label_values(&["open"]).set(open as i64);
+            self.db_connections.with_label_values(&["idle"]).set(idle as i64);
+        }
+        for (job, lag) in state.jobs.lag_seconds() {
+            self.job_lag.with_label_values(&[job]).set(lag);
         }
     }
 }
+
+/// Counts every response by method, matched route and status, so error
+/// rates don't depend on each handler bumping its own counters. Labelled by
+/// the route template, not the path, to keep stream ids out of the labels.
+pub async fn track_requests(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    state
+        .metrics
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    state
+        .metrics
+        .http_request_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
@@ -0,0 +1,664 @@
+use chrono::{DateTime, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::codec;
+use crate::event_filter::EventFilter;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub id: Uuid,
+    pub stream_id: String,
+    pub event_type: String,
+    pub data: serde_json::Value,
+    #[serde(default, with = "codec::binary", skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Vec<u8>>, // Opaque body for non-JSON content types
+    pub content_type: String,
+    pub metadata: Option<serde_json::Value>,
+    pub version: i64,
+    /// Global commit order across all streams.
+    #[serde(default)]
+    pub position: i64,
+    pub created_at: DateTime<Utc>,
+    /// Hex SHA-256 of the stored body; see `integrity::checksum`. Absent on
+    /// events appended before checksums were kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// Hash linking the event to the one before it in its stream; see
+    /// `integrity::Link`. Absent on events appended before the chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_hash: Option<String>,
+    /// The link event this one was read through with `resolve_links`; see
+    /// `links`. Never stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<EventLink>,
+}
+
+/// Where a resolved event sits in the stream that was read: the position
+/// of the link pointing at it, to page on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventLink {
+    pub id: Uuid,
+    pub stream_id: String,
+    pub version: i64,
+    pub position: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppendEventRequest {
+    /// Client-supplied event id. Appending the same id to the same stream
+    /// again within the dedup window returns the original event.
+    pub id: Option<Uuid>,
+    pub stream_id: String,
+    pub event_type: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+    #[serde(default, with = "codec::binary")]
+    pub payload: Option<Vec<u8>>,
+    pub content_type: Option<String>, // Defaults to application/json
+    pub metadata: Option<serde_json::Value>,
+    pub expected_version: Option<i64>,
+    /// Seconds after which the scavenger may delete the event, whatever the
+    /// retention rules say; e.g. for presence or heartbeat events. Kept
+    /// forever, or per the rules, when unset.
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>,
+    /// Versions other streams must be at for the append to go through,
+    /// checked in the same transaction; e.g. to append to an order only
+    /// while the customer stream is where the command saw it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preconditions: Vec<StreamPrecondition>,
+}
+
+/// The version another stream must be at for an append to be accepted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamPrecondition {
+    pub stream_id: String,
+    pub version: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AppendQuery {
+    /// On a version conflict, include the events appended since the
+    /// expected version in the 409 body.
+    #[serde(default)]
+    pub return_conflict_events: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventsQuery {
+    pub from_version: Option<i64>, // first version read; backward reads start at the head when unset
+    pub limit: Option<i64>,
+    pub direction: Option<String>, // "forward" or "backward", which pages down from from_version
+    #[serde(default)]
+    pub include_archived: bool,
+    pub wait: Option<String>, // long poll when no events follow from_version, e.g. "30s"
+    #[serde(default)]
+    pub resolve_links: bool, // return the events link events point at in their place
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LatestEventsQuery {
+    pub count: Option<i64>,
+    #[serde(default)]
+    pub resolve_links: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountQuery {
+    pub from: Option<i64>, // first version counted; the first visible one when unset
+    pub to: Option<i64>,   // last version counted; the head when unset
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventCount {
+    pub stream_id: String,
+    pub from: i64,
+    pub to: i64,
+    pub count: i64,
+}
+
+/// Width of the UTC buckets of usage statistics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+        }
+    }
+
+    /// Start of the bucket `time` falls in.
+    pub fn truncate(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let width = match self {
+            Granularity::Hour => chrono::Duration::hours(1),
+            Granularity::Day => chrono::Duration::days(1),
+        };
+        time.duration_trunc(width).unwrap_or(time)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimeseriesQuery {
+    #[serde(default)]
+    pub granularity: Granularity,
+    pub days: Option<i64>, // how far back, 7 by default
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: Uuid,
+    pub stream_id: String,
+    pub version: i64,
+    pub data: Vec<u8>, // Compressed data
+    #[serde(default)]
+    pub compression: SnapshotCompression,
+    /// The reducer that produced the data, for scheduled snapshots of
+    /// streams that have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reducer: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How a snapshot's data is compressed. Recorded with each snapshot, so
+/// changing `SNAPSHOT_COMPRESSION` only affects snapshots taken afterwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotCompression {
+    #[default]
+    Lz4,
+    Zstd,
+    /// Zstandard with the configured dictionary; only small snapshots.
+    ZstdDict,
+    Gzip,
+    None,
+}
+
+impl SnapshotCompression {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SnapshotCompression::Lz4 => "lz4",
+            SnapshotCompression::Zstd => "zstd",
+            SnapshotCompression::ZstdDict => "zstd-dict",
+            SnapshotCompression::Gzip => "gzip",
+            SnapshotCompression::None => "none",
+        }
+    }
+}
+
+impl std::str::FromStr for SnapshotCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "lz4" => Ok(SnapshotCompression::Lz4),
+            "zstd" => Ok(SnapshotCompression::Zstd),
+            "zstd-dict" => Ok(SnapshotCompression::ZstdDict),
+            "gzip" => Ok(SnapshotCompression::Gzip),
+            "none" => Ok(SnapshotCompression::None),
+            other => Err(format!("Unknown snapshot compression '{}'", other)),
+        }
+    }
+}
+
+/// How a large JSON event body is compressed at rest. Recorded with each
+/// event, so changing `EVENT_COMPRESSION` only affects events appended
+/// afterwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventCompression {
+    Lz4,
+    Zstd,
+    #[default]
+    None,
+}
+
+impl EventCompression {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventCompression::Lz4 => "lz4",
+            EventCompression::Zstd => "zstd",
+            EventCompression::None => "none",
+        }
+    }
+}
+
+impl std::str::FromStr for EventCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "lz4" => Ok(EventCompression::Lz4),
+            "zstd" => Ok(EventCompression::Zstd),
+            "none" => Ok(EventCompression::None),
+            other => Err(format!("Unknown event compression '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotQuery {
+    /// The newest snapshot at or before this version; the latest when unset.
+    pub version: Option<i64>,
+    /// The newest snapshot of the stream as it stood at this instant.
+    pub as_of: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateSnapshotRequest {
+    pub stream_id: String,
+    pub version: i64,
+    pub data: serde_json::Value,
+}
+
+/// Per-stream settings stored alongside the stream. Unknown keys are kept so
+/// clients can attach their own annotations.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamMetadata {
+    /// When set, only these event types may be appended to the stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_event_types: Option<Vec<String>>,
+    /// Streams under legal hold are never archived or purged.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub legal_hold: bool,
+    /// Archive events older than this many days instead of `ARCHIVE_DAYS`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_after_days: Option<i64>,
+    /// Versions below this are deleted: reads skip them at once and the
+    /// scavenger reclaims them later. The stream head is kept, hidden, so
+    /// versions continue where they left off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncate_before: Option<i64>,
+    /// Appends are rejected once the stream holds this many events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_events: Option<i64>,
+    /// Largest accepted event body (JSON data or binary payload), in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_payload_bytes: Option<usize>,
+    /// `false` keeps the snapshot scheduler away from the stream, e.g. for
+    /// telemetry that is never loaded as state. Snapshots can still be
+    /// posted by clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_snapshot: Option<bool>,
+    /// Events between scheduled snapshots instead of `SNAPSHOT_THRESHOLD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot_threshold: Option<i64>,
+    #[serde(flatten)]
+    pub custom: serde_json::Map<String, serde_json::Value>,
+}
+
+impl StreamMetadata {
+    pub fn allows_event_type(&self, event_type: &str) -> bool {
+        match &self.allowed_event_types {
+            Some(allowed) => allowed.iter().any(|t| t == event_type),
+            None => true,
+        }
+    }
+
+    /// Whether the metadata sets a snapshot policy of its own.
+    pub fn has_snapshot_policy(&self) -> bool {
+        self.auto_snapshot.is_some() || self.snapshot_threshold.is_some()
+    }
+
+    /// Events between scheduled snapshots of the stream, given the
+    /// configured default; `None` when it opted out of them.
+    pub fn scheduled_snapshot_threshold(&self, default: i64) -> Option<i64> {
+        match self.auto_snapshot {
+            Some(false) => None,
+            _ => Some(self.snapshot_threshold.unwrap_or(default)),
+        }
+    }
+}
+
+/// Marks an event type as superseded. Appends of a deprecated type still
+/// succeed but carry a warning; historical events can be rewritten to the
+/// successor type into a new stream generation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventTypeDeprecation {
+    pub event_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub successor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Upcaster: top-level fields to rename, old name -> new name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rename_fields: BTreeMap<String, String>,
+    /// Upcaster: top-level fields to add when absent, e.g. defaults for new
+    /// required fields.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub default_fields: serde_json::Map<String, serde_json::Value>,
+    pub deprecated_at: DateTime<Utc>,
+}
+
+/// What an API key or token may do: `read` for GET requests, `write` for
+/// appends and other mutations, `admin` for everything under `/admin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+/// The access level a caller's scopes grant. Each role includes the ones
+/// below it: writers can read, admins can do anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Reader,
+    Writer,
+    Admin,
+}
+
+impl Role {
+    /// The highest role granted by `scopes`, if any.
+    pub fn from_scopes(scopes: &[Scope]) -> Option<Role> {
+        scopes
+            .iter()
+            .map(|scope| match scope {
+                Scope::Read => Role::Reader,
+                Scope::Write => Role::Writer,
+                Scope::Admin => Role::Admin,
+            })
+            .max()
+    }
+}
+
+/// An API key as stored and listed. The secret itself is never stored; keys
+/// are looked up by the SHA-256 of the presented value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// The project, and so tenant, a non-admin key acts for. Admin keys are
+    /// unscoped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Fields of an event type that are stored encrypted under a per-subject key.
+/// `subject_field` names the top-level field holding the subject id (e.g. a
+/// user id); it stays in plaintext so the key can be found on read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionPolicy {
+    pub event_type: String,
+    pub subject_field: String,
+    pub fields: Vec<String>,
+    pub declared_at: DateTime<Utc>,
+}
+
+/// A registered project, or a workspace in one: the first one or two
+/// segments of the stream ids `{project}/{workspace}/{name}` under it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Namespace {
+    pub project: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Namespace {
+    /// `project`, or `project/workspace`.
+    pub fn id(&self) -> String {
+        match &self.workspace {
+            Some(workspace) => format!("{}/{}", self.project, workspace),
+            None => self.project.clone(),
+        }
+    }
+}
+
+/// A persistent subscription to the global log, shared by the consumers
+/// polling it: each matching event is leased to one consumer at a time
+/// until it's acknowledged, so every event is delivered at least once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionGroup {
+    pub name: String,
+    /// The tenant that created the group; only its streams are delivered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_prefix: Option<String>,
+    /// Only these event types; all when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_types: Option<Vec<String>>,
+    /// Only events passing this, as the GraphQL `filter` argument takes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<EventFilter>,
+    /// How long a consumer has to acknowledge an event before it's leased again.
+    pub ack_timeout_seconds: u64,
+    /// Deliveries of one event before it's parked.
+    pub max_attempts: u32,
+    /// Global position through which every event has been acknowledged.
+    pub checkpoint: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How long events are kept. `stream_pattern` is a stream id, or a prefix
+/// ending in `*` (`telemetry/*`, or `*` for every stream); `event_type`
+/// narrows the rule to one type. `keep_days` of `None` keeps events forever.
+///
+/// Where rules overlap the most specific one applies: an exact stream id
+/// over a prefix, a longer prefix over a shorter one, then a rule naming
+/// the event type over one that doesn't. Rules with an event type remove
+/// events from the middle of streams, leaving version gaps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionRule {
+    pub name: String,
+    pub stream_pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
+    pub keep_days: Option<i64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RetentionRule {
+    /// The stream id prefix, when the pattern is one.
+    pub fn stream_prefix(&self) -> Option<&str> {
+        self.stream_pattern.strip_suffix('*')
+    }
+
+    pub fn matches(&self, stream_id: &str, event_type: &str) -> bool {
+        stream_pattern_matches(&self.stream_pattern, stream_id)
+            && self.event_type.as_deref().map_or(true, |t| t == event_type)
+    }
+
+    /// Orders rules from least to most specific.
+    pub fn specificity(&self) -> (bool, usize, bool) {
+        let (exact, len) = stream_pattern_specificity(&self.stream_pattern);
+        (exact, len, self.event_type.is_some())
+    }
+}
+
+/// Whether `pattern` is a stream id, or a prefix of one ending in a single `*`.
+pub fn is_valid_stream_pattern(pattern: &str) -> bool {
+    !pattern.is_empty() && !pattern.trim_end_matches('*').contains('*') && !pattern.ends_with("**")
+}
+
+pub fn stream_pattern_matches(pattern: &str, stream_id: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => stream_id.starts_with(prefix),
+        None => stream_id == pattern,
+    }
+}
+
+/// Orders patterns from least to most specific: any prefix before an exact
+/// stream id, and shorter prefixes before longer ones.
+pub fn stream_pattern_specificity(pattern: &str) -> (bool, usize) {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => (false, prefix.len()),
+        None => (true, pattern.len()),
+    }
+}
+
+/// Folds a category of streams into real state for scheduled snapshots, in
+/// place of the raw event list. `stream_pattern` works as for retention
+/// rules, and the most specific matching reducer applies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotReducer {
+    pub name: String,
+    pub stream_pattern: String,
+    pub spec: ReducerSpec,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReducerSpec {
+    Transform(StateTransform),
+    /// A WASM plugin of kind `reducer`, by name. Its state starts as `null`.
+    Wasm { plugin: String },
+}
+
+/// A declarative reducer: starting from `initial`, each event applies the
+/// operations listed under its type, or under `*` when its type has none.
+/// Paths are JSON Pointers into the state; `from` is a JSON Pointer into the
+/// event (`/data/amount`, `/version`, `/created_at`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateTransform {
+    #[serde(default = "empty_object")]
+    pub initial: serde_json::Value,
+    pub handlers: BTreeMap<String, Vec<TransformOp>>,
+}
+
+fn empty_object() -> serde_json::Value {
+    serde_json::Value::Object(serde_json::Map::new())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TransformOp {
+    /// Sets `path` to a constant.
+    Set { path: String, value: serde_json::Value },
+    /// Sets `path` to a value from the event.
+    Copy { from: String, path: String },
+    /// Copies the fields of an object in the event into the object at `path`.
+    Merge {
+        from: String,
+        #[serde(default)]
+        path: String,
+    },
+    /// Adds `by`, or a number from the event, to the number at `path`.
+    Increment {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        by: Option<serde_json::Number>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from: Option<String>,
+    },
+    /// Pushes a value from the event onto the array at `path`.
+    Append { from: String, path: String },
+    Remove { path: String },
+}
+
+/// A sandboxed WASM module uploaded by an operator. Reducers fold a stream
+/// for scheduled snapshots; projections fold every event matching
+/// `stream_pattern`, in position order, into one document. The module
+/// itself is stored alongside.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plugin {
+    pub name: String,
+    pub kind: PluginKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_pattern: Option<String>,
+    pub sha256: String,
+    pub size_bytes: usize,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginKind {
+    Reducer,
+    Projection,
+}
+
+/// How far a projection plugin has folded the global event order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectionState {
+    pub name: String,
+    /// The module the state was built with; a new upload starts over.
+    pub plugin_sha256: String,
+    pub position: i64,
+    pub state: serde_json::Value,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An external destination for events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Sink {
+    /// POSTed as `{"source": ..., "events": [...]}`, one batch per request.
+    Webhook { url: String },
+    /// Produced through the Kafka REST proxy, keyed by stream id so each
+    /// stream's events stay in order within a partition.
+    Kafka { topic: String },
+    /// Rebuilds a projection plugin: its state is replaced by the fold of
+    /// the replayed events, and the projector carries on from where the
+    /// replay stopped. Pause the projector job while this runs. Replays only.
+    Projection { name: String },
+}
+
+/// One failed try at delivering an event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryAttempt {
+    pub at: DateTime<Utc>,
+    pub error: String,
+}
+
+/// An event whose delivery exhausted its retries, parked for inspection and
+/// redelivery. The event is kept as stored, so shredding a subject's key
+/// also covers its dead letters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: Uuid,
+    /// What was delivering it, e.g. `replay:<id>`.
+    pub source: String,
+    pub sink: Sink,
+    pub event: Event,
+    /// Oldest first, including failed redeliveries.
+    pub attempts: Vec<DeliveryAttempt>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterQuery {
+    pub source: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// A stream's place in the hash chain when an anchor was signed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnchoredHead {
+    pub stream_id: String,
+    pub version: i64,
+    pub chain_hash: String,
+}
+
+/// A signed record of the chain heads of every stream appended to since the
+/// previous anchor, so a rewrite of the log after it was signed, chain hashes
+/// and all, no longer matches a signature only the store could have made.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainAnchor {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    /// Log position the anchor covers up to.
+    pub position: i64,
+    /// Signature of the anchor before, which chains anchors together too.
+    pub previous: Option<String>,
+    pub heads: Vec<AnchoredHead>,
+    /// Hex Ed25519 signature over the anchor's canonical JSON, without this field.
+    #[serde(default)]
+    pub signature: String,
+}
@@ -0,0 +1,294 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::auth::Tenant;
+use crate::cold_storage;
+use crate::error::{AppError, Result};
+use crate::models::Namespace;
+use crate::storage::{ArchiveRange, EventStorage};
+use crate::AppState;
+
+/// How long a registration seen here is trusted before storage is asked
+/// again, bounding how long another instance's removal goes unnoticed.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+struct Registered {
+    ids: HashSet<String>,
+    loaded_at: Option<Instant>,
+}
+
+impl Registered {
+    fn allows(&self, stream_id: &str) -> bool {
+        let segments: Vec<&str> = stream_id.split('/').collect();
+        if !self.ids.contains(segments[0]) {
+            return false;
+        }
+        segments.len() < 3 || self.ids.contains(&format!("{}/{}", segments[0], segments[1]))
+    }
+}
+
+/// In-process view of the registered projects and workspaces, consulted on
+/// appends to new streams in strict mode. Loaded from storage at startup,
+/// kept in sync by this instance's `/projects` endpoints, and reloaded on a
+/// miss or once older than `REFRESH_INTERVAL`, so namespaces registered or
+/// removed through other instances are seen too.
+#[derive(Debug, Default)]
+pub struct NamespaceRegistry {
+    registered: RwLock<Registered>,
+}
+
+impl NamespaceRegistry {
+    pub async fn load(storage: &dyn EventStorage) -> Result<Self> {
+        let registry = Self::default();
+        registry.refresh(storage).await?;
+        Ok(registry)
+    }
+
+    async fn refresh(&self, storage: &dyn EventStorage) -> Result<()> {
+        let ids = storage.namespaces().await?.iter().map(Namespace::id).collect();
+        *self.registered.write().unwrap() = Registered {
+            ids,
+            loaded_at: Some(Instant::now()),
+        };
+        Ok(())
+    }
+
+    /// Whether `stream_id`'s project is registered and, when it has a
+    /// workspace segment (`{project}/{workspace}/{name}`), that workspace too.
+    pub async fn allows(&self, storage: &dyn EventStorage, stream_id: &str) -> Result<bool> {
+        {
+            let registered = self.registered.read().unwrap();
+            if registered.allows(stream_id)
+                && registered
+                    .loaded_at
+                    .is_some_and(|loaded_at| loaded_at.elapsed() < REFRESH_INTERVAL)
+            {
+                return Ok(true);
+            }
+        }
+
+        self.refresh(storage).await?;
+        Ok(self.registered.read().unwrap().allows(stream_id))
+    }
+
+    fn insert(&self, id: String) {
+        self.registered.write().unwrap().ids.insert(id);
+    }
+
+    /// Forgets `id` and everything under it.
+    fn remove(&self, id: &str) {
+        let prefix = format!("{}/", id);
+        self.registered
+            .write()
+            .unwrap()
+            .ids
+            .retain(|key| key != id && !key.starts_with(&prefix));
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateProjectRequest {
+    pub project: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWorkspaceRequest {
+    pub workspace: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamespaceDeletion {
+    pub namespace: String,
+    pub namespaces_removed: u64,
+    pub streams: usize,
+    pub events_archived: u64,
+    /// Under legal hold, so left as they were.
+    pub streams_on_hold: Vec<String>,
+}
+
+/// A project or workspace name: one non-empty stream id segment.
+fn validate_segment(kind: &str, name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 255
+        && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "Invalid {} name '{}': 1 to 255 letters, digits, '-' and '_'",
+            kind, name
+        )));
+    }
+    Ok(())
+}
+
+/// A tenant only sees and manages its own project.
+fn authorize_project(tenant: &Tenant, project: &str) -> Result<()> {
+    match &tenant.0 {
+        Some(t) if t != project => Err(AppError::Forbidden(format!(
+            "Tenant '{}' cannot access project {}",
+            t, project
+        ))),
+        _ => Ok(()),
+    }
+}
+
+async fn create(state: &AppState, namespace: Namespace) -> Result<(StatusCode, Json<Namespace>)> {
+    let id = namespace.id();
+    if !state.storage.insert_namespace(&namespace).await? {
+        return Err(AppError::Conflict(format!("{} is already registered", id)));
+    }
+    state.namespaces.insert(id.clone());
+    info!("Namespace registered: {}", id);
+
+    Ok((StatusCode::CREATED, Json(namespace)))
+}
+
+/// Removes the namespace `id` and archives the streams under it: every event
+/// but each stream's head, which stays so the stream's version is kept. The
+/// namespace is only unregistered once its streams are archived, so a failed
+/// deletion can be retried.
+async fn delete(state: &AppState, id: &str) -> Result<Json<NamespaceDeletion>> {
+    if !state.storage.namespaces().await?.iter().any(|n| n.id() == id) {
+        return Err(AppError::NotFound(format!("{} is not registered", id)));
+    }
+
+    let held: HashSet<String> = state.storage.legal_hold_streams().await?.into_iter().collect();
+    let heads = state.storage.stream_heads(&format!("{}/", id)).await?;
+    let (streams_on_hold, heads): (Vec<_>, Vec<_>) = heads.into_iter().partition(|(s, _)| held.contains(s));
+    let ranges: Vec<ArchiveRange> = heads
+        .iter()
+        .filter(|(_, head)| *head > 1)
+        .map(|(stream_id, head)| ArchiveRange {
+            stream_id: stream_id.clone(),
+            from_version: 1,
+            to_version: head - 1,
+        })
+        .collect();
+
+    let events_archived = match &state.cold_store {
+        Some(cold_store) => cold_storage::tier_out(state.storage.as_ref(), cold_store, &state.metrics, &ranges).await?,
+        None => state.storage.archive_ranges(&ranges).await?,
+    };
+    let namespaces_removed = state.storage.remove_namespace(id).await?;
+    state.namespaces.remove(id);
+    info!(
+        "Namespace removed: {} ({} streams, {} events archived, {} on legal hold)",
+        id,
+        heads.len(),
+        events_archived,
+        streams_on_hold.len()
+    );
+
+    Ok(Json(NamespaceDeletion {
+        namespace: id.to_string(),
+        namespaces_removed,
+        streams: heads.len(),
+        events_archived,
+        streams_on_hold: streams_on_hold.into_iter().map(|(s, _)| s).collect(),
+    }))
+}
+
+/// GET /projects
+pub async fn list_projects(State(state): State<AppState>, tenant: Tenant) -> Result<Json<Vec<Namespace>>> {
+    let projects = state
+        .storage
+        .namespaces()
+        .await?
+        .into_iter()
+        .filter(|n| n.workspace.is_none())
+        .filter(|n| tenant.0.as_deref().map_or(true, |t| t == n.project))
+        .collect();
+    Ok(Json(projects))
+}
+
+/// POST /projects
+pub async fn create_project(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<CreateProjectRequest>,
+) -> Result<(StatusCode, Json<Namespace>)> {
+    validate_segment("project", &request.project)?;
+    authorize_project(&tenant, &request.project)?;
+
+    let namespace = Namespace {
+        project: request.project,
+        workspace: None,
+        description: request.description,
+        created_at: state.providers.now(),
+    };
+    create(&state, namespace).await
+}
+
+/// DELETE /projects/:project (admin) — removes the project and its
+/// workspaces, and archives their streams.
+pub async fn delete_project(
+    Path(project): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<NamespaceDeletion>> {
+    authorize_project(&tenant, &project)?;
+    delete(&state, &project).await
+}
+
+/// GET /projects/:project/workspaces
+pub async fn list_workspaces(
+    Path(project): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<Vec<Namespace>>> {
+    authorize_project(&tenant, &project)?;
+
+    let namespaces = state.storage.namespaces().await?;
+    if !namespaces.iter().any(|n| n.project == project && n.workspace.is_none()) {
+        return Err(AppError::NotFound(format!("Project {} is not registered", project)));
+    }
+    Ok(Json(
+        namespaces
+            .into_iter()
+            .filter(|n| n.project == project && n.workspace.is_some())
+            .collect(),
+    ))
+}
+
+/// POST /projects/:project/workspaces
+pub async fn create_workspace(
+    Path(project): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<CreateWorkspaceRequest>,
+) -> Result<(StatusCode, Json<Namespace>)> {
+    authorize_project(&tenant, &project)?;
+    validate_segment("workspace", &request.workspace)?;
+    if !state.namespaces.allows(state.storage.as_ref(), &project).await? {
+        return Err(AppError::NotFound(format!("Project {} is not registered", project)));
+    }
+
+    let namespace = Namespace {
+        project,
+        workspace: Some(request.workspace),
+        description: request.description,
+        created_at: state.providers.now(),
+    };
+    create(&state, namespace).await
+}
+
+/// DELETE /projects/:project/workspaces/:workspace (admin) — removes the
+/// workspace and archives its streams.
+pub async fn delete_workspace(
+    Path((project, workspace)): Path<(String, String)>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<NamespaceDeletion>> {
+    authorize_project(&tenant, &project)?;
+    delete(&state, &format!("{}/{}", project, workspace)).await
+}
@@ -0,0 +1,128 @@
+use aws_sdk_s3::{error::DisplayErrorContext, primitives::ByteStream, Client};
+
+use crate::error::{AppError, Result};
+use crate::models::Event;
+
+/// A bucket in S3 or an S3-compatible store, shared by cold storage and
+/// backups.
+pub struct S3Bucket {
+    client: Client,
+    bucket: String,
+}
+
+impl S3Bucket {
+    /// Connects with the environment's AWS credentials. `endpoint` points at
+    /// S3-compatible stores such as MinIO, which need path-style addressing.
+    pub async fn connect(bucket: String, endpoint: Option<&str>) -> Self {
+        let sdk_config = aws_config::load_from_env().await;
+        let mut builder = aws_sdk_s3::config::Builder::from(&sdk_config);
+        if let Some(endpoint) = endpoint {
+            builder = builder.endpoint_url(endpoint).force_path_style(true);
+        }
+
+        Self {
+            client: Client::from_conf(builder.build()),
+            bucket,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.bucket
+    }
+
+    pub async fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> Result<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(|e| AppError::ColdStorage(format!("Failed to upload {}: {}", key, DisplayErrorContext(&e))))?;
+
+        Ok(())
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| AppError::ColdStorage(format!("Failed to fetch {}: {}", key, DisplayErrorContext(&e))))?;
+
+        let body = object
+            .body
+            .collect()
+            .await
+            .map_err(|e| AppError::ColdStorage(format!("Failed to read {}: {}", key, e)))?;
+
+        Ok(body.into_bytes().to_vec())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| AppError::ColdStorage(format!("Failed to delete {}: {}", key, DisplayErrorContext(&e))))?;
+
+        Ok(())
+    }
+
+    /// All keys under `prefix`, following continuation tokens.
+    pub async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut continuation = None;
+
+        loop {
+            let page = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation)
+                .send()
+                .await
+                .map_err(|e| AppError::ColdStorage(format!("Failed to list {}: {}", prefix, DisplayErrorContext(&e))))?;
+
+            keys.extend(page.contents().iter().filter_map(|o| o.key().map(str::to_string)));
+
+            match page.next_continuation_token() {
+                Some(token) => continuation = Some(token.to_string()),
+                None => break,
+            }
+        }
+
+        Ok(keys)
+    }
+}
+
+/// Serializes events as zstd-compressed NDJSON, the format of cold storage
+/// segments and backup chunks.
+pub fn encode_events(events: &[Event]) -> Result<Vec<u8>> {
+    let mut ndjson = Vec::new();
+    for event in events {
+        serde_json::to_writer(&mut ndjson, event)?;
+        ndjson.push(b'\n');
+    }
+
+    zstd::encode_all(ndjson.as_slice(), 3)
+        .map_err(|e| AppError::Internal(format!("Failed to compress events: {}", e)))
+}
+
+pub fn decode_events(compressed: &[u8]) -> Result<Vec<Event>> {
+    let ndjson = zstd::decode_all(compressed)
+        .map_err(|e| AppError::Internal(format!("Failed to decompress events: {}", e)))?;
+
+    ndjson
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(serde_json::from_slice(line)?))
+        .collect()
+}
@@ -0,0 +1,117 @@
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::Config;
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::AppState;
+
+/// Requests never held back: probes must keep answering however busy the
+/// service is.
+const EXEMPT_PATHS: &[&str] = &["/health", "/health/live", "/health/ready", "/metrics"];
+/// How long a shed client is told to wait before trying again.
+const SHED_RETRY_AFTER_SECONDS: u64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RequestClass {
+    Read,
+    Write,
+}
+
+impl RequestClass {
+    fn of(request: &Request) -> Self {
+        // The GraphQL schema has no mutations, so its POSTs only read
+        if matches!(*request.method(), Method::GET | Method::HEAD) || request.uri().path().starts_with("/graphql") {
+            RequestClass::Read
+        } else {
+            RequestClass::Write
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RequestClass::Read => "read",
+            RequestClass::Write => "write",
+        }
+    }
+}
+
+/// Caps on the requests handled at once. Reads and writes have separate
+/// budgets, so a burst of slow reads can't take the slots appends need. A
+/// request over its budget waits up to `load_shed_wait_ms` for a slot and
+/// is then refused with 503, rather than piling up on the runtime and the
+/// database pool.
+pub struct ConcurrencyLimits {
+    reads: Option<Arc<Semaphore>>,
+    writes: Option<Arc<Semaphore>>,
+    wait: Duration,
+}
+
+impl ConcurrencyLimits {
+    pub fn from_config(config: &Config) -> Self {
+        let limit = |max: usize| (max > 0).then(|| Arc::new(Semaphore::new(max)));
+        Self {
+            reads: limit(config.max_concurrent_reads),
+            writes: limit(config.max_concurrent_writes),
+            wait: Duration::from_millis(config.load_shed_wait_ms),
+        }
+    }
+
+    /// A slot for a request of `class`, held until the permit is dropped;
+    /// `None` when the class is unlimited.
+    async fn acquire(&self, class: RequestClass) -> Result<Option<OwnedSemaphorePermit>> {
+        let semaphore = match class {
+            RequestClass::Read => &self.reads,
+            RequestClass::Write => &self.writes,
+        };
+        let Some(semaphore) = semaphore else {
+            return Ok(None);
+        };
+
+        match tokio::time::timeout(self.wait, semaphore.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            _ => Err(AppError::Overloaded(
+                format!("Too many {} requests in progress", class.as_str()),
+                SHED_RETRY_AFTER_SECONDS,
+            )),
+        }
+    }
+}
+
+/// Holds each request to its class's concurrency limit, and to its
+/// deadline: an `X-Request-Deadline` header, or `request_timeout_ms` from
+/// now. A handler still running at the deadline is dropped with a 504.
+/// Streamed bodies are bounded only until their response starts.
+pub async fn limit(State(state): State<AppState>, request: Request, next: Next) -> Result<Response> {
+    if EXEMPT_PATHS.contains(&request.uri().path()) {
+        return Ok(next.run(request).await);
+    }
+
+    let class = RequestClass::of(&request);
+    let _permit = state.concurrency.acquire(class).await.map_err(|e| {
+        state.metrics.requests_shed.with_label_values(&[class.as_str()]).inc();
+        e
+    })?;
+
+    let (mut parts, body) = request.into_parts();
+    let deadline = Deadline::from_request_parts(&mut parts, &state).await?;
+    let request = Request::from_parts(parts, body);
+    let Some(remaining) = deadline.remaining()? else {
+        return Ok(next.run(request).await);
+    };
+
+    match tokio::time::timeout(remaining, next.run(request)).await {
+        Ok(response) => Ok(response),
+        Err(_) => {
+            state.metrics.requests_timed_out.inc();
+            Err(AppError::DeadlineExceeded(
+                "The request did not complete before its deadline".to_string(),
+            ))
+        }
+    }
+}
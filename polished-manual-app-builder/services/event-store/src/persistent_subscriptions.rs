@@ -0,0 +1,663 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::auth::Tenant;
+use crate::config::Config;
+use crate::encryption;
+use crate::error::{AppError, Result};
+use crate::event_filter::{EventFilter, EventMatcher};
+use crate::jobs::{self, Job};
+use crate::metrics::Metrics;
+use crate::models::{Event, SubscriptionGroup};
+use crate::storage::EventStorage;
+use crate::system_streams::is_system_stream;
+use crate::{get_partition_key, AppState};
+
+/// Events read from the log per query while filling a poll.
+const POLL_PAGE_SIZE: i64 = 500;
+
+/// Most events one poll hands out.
+const MAX_POLL_SIZE: usize = 1000;
+
+/// An event handed to a consumer and not yet acknowledged.
+#[derive(Debug)]
+struct Lease {
+    consumer: String,
+    event: Event,
+    attempts: u32,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct ConsumerStats {
+    acknowledged: u64,
+    last_seen_at: DateTime<Utc>,
+}
+
+/// A group's definition and its delivery state on this instance. Only the
+/// checkpoint is persisted: after a restart, every event past it is
+/// delivered again, which at-least-once delivery allows.
+#[derive(Debug)]
+struct Group {
+    definition: SubscriptionGroup,
+    /// The definition's `filter`, compiled.
+    matcher: Option<EventMatcher>,
+    /// Position of the last event read from the log.
+    read_position: i64,
+    in_flight: BTreeMap<i64, Lease>,
+    /// Failed or timed out, waiting to be delivered again; by position, with
+    /// the attempts so far.
+    retries: BTreeMap<i64, (Event, u32)>,
+    /// Failed `max_attempts` deliveries and held back until someone
+    /// acknowledges them; by position, with the consumer that failed each last.
+    parked: BTreeMap<i64, String>,
+    consumers: BTreeMap<String, ConsumerStats>,
+}
+
+impl Group {
+    fn new(definition: SubscriptionGroup) -> Result<Self> {
+        Ok(Self {
+            read_position: definition.checkpoint,
+            matcher: compile(&definition.filter)?,
+            definition,
+            in_flight: BTreeMap::new(),
+            retries: BTreeMap::new(),
+            parked: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        })
+    }
+
+    fn matches(&self, event: &Event) -> bool {
+        let group = &self.definition;
+        !is_system_stream(&event.stream_id)
+            && group
+                .tenant_id
+                .as_ref()
+                .map_or(true, |tenant| get_partition_key(&event.stream_id) == *tenant)
+            && group
+                .stream_prefix
+                .as_ref()
+                .map_or(true, |prefix| event.stream_id.starts_with(prefix.as_str()))
+            && group
+                .event_types
+                .as_ref()
+                .map_or(true, |types| types.contains(&event.event_type))
+            && self.matcher.as_ref().map_or(true, |matcher| matcher.matches(event))
+    }
+
+    /// Everything below the oldest event still owed an acknowledgement.
+    fn checkpoint(&self) -> i64 {
+        [
+            self.in_flight.keys().next(),
+            self.retries.keys().next(),
+            self.parked.keys().next(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map_or(self.read_position, |oldest| oldest - 1)
+    }
+
+    /// Sends a failed delivery back for another attempt, or parks it once
+    /// it has had `max_attempts`.
+    fn release(&mut self, position: i64, lease: Lease) {
+        if lease.attempts >= self.definition.max_attempts {
+            self.parked.insert(position, lease.consumer);
+        } else {
+            self.retries.insert(position, (lease.event, lease.attempts));
+        }
+    }
+
+    fn expire_leases(&mut self, now: DateTime<Utc>) {
+        let expired: Vec<i64> = self
+            .in_flight
+            .iter()
+            .filter(|(_, lease)| lease.expires_at <= now)
+            .map(|(position, _)| *position)
+            .collect();
+        for position in expired {
+            if let Some(lease) = self.in_flight.remove(&position) {
+                self.release(position, lease);
+            }
+        }
+    }
+
+    /// Forgets consumers last seen before `idle_since` that hold no leases;
+    /// their parked events stay parked.
+    fn forget_idle_consumers(&mut self, idle_since: DateTime<Utc>) {
+        let leased: Vec<&str> = self.in_flight.values().map(|lease| lease.consumer.as_str()).collect();
+        self.consumers
+            .retain(|name, stats| stats.last_seen_at >= idle_since || leased.contains(&name.as_str()));
+    }
+
+    fn seen(&mut self, consumer: &str, now: DateTime<Utc>) -> &mut ConsumerStats {
+        let stats = self.consumers.entry(consumer.to_string()).or_insert(ConsumerStats {
+            acknowledged: 0,
+            last_seen_at: now,
+        });
+        stats.last_seen_at = now;
+        stats
+    }
+
+    fn lag(&self, head_position: i64) -> SubscriptionLag {
+        let mut consumers: BTreeMap<&str, ConsumerLag> = self
+            .consumers
+            .iter()
+            .map(|(name, stats)| {
+                let lag = ConsumerLag {
+                    consumer: name.clone(),
+                    in_flight: 0,
+                    parked: 0,
+                    acknowledged: stats.acknowledged,
+                    last_seen_at: stats.last_seen_at,
+                };
+                (name.as_str(), lag)
+            })
+            .collect();
+        for lease in self.in_flight.values() {
+            if let Some(lag) = consumers.get_mut(lease.consumer.as_str()) {
+                lag.in_flight += 1;
+            }
+        }
+        for consumer in self.parked.values() {
+            if let Some(lag) = consumers.get_mut(consumer.as_str()) {
+                lag.parked += 1;
+            }
+        }
+
+        let checkpoint_position = self.checkpoint();
+        SubscriptionLag {
+            group: self.definition.name.clone(),
+            checkpoint_position,
+            head_position,
+            lag: (head_position - checkpoint_position).max(0),
+            read_position: self.read_position,
+            in_flight: self.in_flight.len(),
+            awaiting_redelivery: self.retries.len(),
+            parked: self.parked.len(),
+            consumers: consumers.into_values().collect(),
+        }
+    }
+}
+
+/// Persistent subscription groups by name, loaded from storage at startup.
+/// Consumers poll a group for events, which are leased to them until they
+/// acknowledge each one's receipt; unacknowledged events are delivered
+/// again, to any consumer, once their lease times out. Leases are held by
+/// the instance that handed them out, so a group's consumers should all
+/// poll the same instance.
+#[derive(Debug)]
+pub struct PersistentSubscriptions {
+    groups: RwLock<BTreeMap<String, Arc<Entry>>>,
+    /// Most events a group leases out at once.
+    max_in_flight: usize,
+    /// How long a consumer goes unseen before its group forgets it.
+    consumer_idle: chrono::Duration,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// The tenant that created the group, which never changes.
+    tenant_id: Option<String>,
+    group: Mutex<Group>,
+}
+
+impl Entry {
+    fn new(definition: SubscriptionGroup) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
+            tenant_id: definition.tenant_id.clone(),
+            group: Mutex::new(Group::new(definition)?),
+        }))
+    }
+
+    /// A tenant only sees the groups it created.
+    fn visible_to(&self, tenant: &Tenant) -> bool {
+        tenant.0.is_none() || tenant.0 == self.tenant_id
+    }
+}
+
+impl PersistentSubscriptions {
+    pub async fn load(storage: &dyn EventStorage, config: &Config) -> Result<Self> {
+        let groups = storage
+            .subscription_groups()
+            .await?
+            .into_iter()
+            .map(|group| Ok((group.name.clone(), Entry::new(group)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            groups: RwLock::new(groups),
+            max_in_flight: config.subscription_max_in_flight,
+            consumer_idle: chrono::Duration::seconds(config.subscription_consumer_idle_seconds as i64),
+        })
+    }
+
+    fn get(&self, name: &str, tenant: &Tenant) -> Result<Arc<Entry>> {
+        self.groups
+            .read()
+            .unwrap()
+            .get(name)
+            .filter(|entry| entry.visible_to(tenant))
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Subscription group {} not found", name)))
+    }
+
+    fn all(&self) -> Vec<Arc<Entry>> {
+        self.groups.read().unwrap().values().cloned().collect()
+    }
+
+    /// Returns timed-out leases for redelivery and forgets idle consumers.
+    fn tidy(&self, group: &mut Group, now: DateTime<Utc>) {
+        group.expire_leases(now);
+        group.forget_idle_consumers(now - self.consumer_idle);
+    }
+}
+
+/// The matcher for a group's `filter`, when it has one.
+fn compile(filter: &Option<EventFilter>) -> Result<Option<EventMatcher>> {
+    filter.clone().map(EventFilter::compile).transpose()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionGroupRequest {
+    pub stream_prefix: Option<String>,
+    pub event_types: Option<Vec<String>>,
+    /// Narrows delivery further, as the GraphQL `filter` argument does.
+    pub filter: Option<EventFilter>,
+    pub ack_timeout_seconds: Option<u64>,
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PollRequest {
+    pub consumer: String,
+    /// Defaults to 100.
+    pub max_events: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Delivery {
+    /// Acknowledge or reject the event with this.
+    pub receipt: i64,
+    /// 1 on the first delivery.
+    pub attempt: u32,
+    pub ack_deadline: DateTime<Utc>,
+    pub event: Event,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiptsRequest {
+    pub consumer: String,
+    pub receipts: Vec<i64>,
+    /// On a nack: park the events now instead of delivering them again.
+    #[serde(default)]
+    pub park: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReceiptsOutcome {
+    pub accepted: u64,
+    /// Receipts whose lease had expired or was held by another consumer;
+    /// those events are, or will be, delivered again.
+    pub stale: Vec<i64>,
+    pub checkpoint_position: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConsumerLag {
+    pub consumer: String,
+    pub in_flight: usize,
+    /// Events this consumer failed last before they were parked.
+    pub parked: usize,
+    pub acknowledged: u64,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionLag {
+    pub group: String,
+    pub checkpoint_position: i64,
+    /// Committed position of the global log.
+    pub head_position: i64,
+    pub lag: i64,
+    /// How far the group has read; events between the checkpoint and here
+    /// are in flight, awaiting redelivery or parked.
+    pub read_position: i64,
+    pub in_flight: usize,
+    pub awaiting_redelivery: usize,
+    pub parked: usize,
+    pub consumers: Vec<ConsumerLag>,
+}
+
+/// A group name, also used as a metric label.
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 255
+        && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "Invalid subscription group name '{}': 1 to 255 letters, digits, '-', '_' and '.'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn validate_consumer(consumer: &str) -> Result<()> {
+    if consumer.is_empty() || consumer.len() > 255 {
+        return Err(AppError::BadRequest("A consumer name needs 1 to 255 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// Writes the group's checkpoint when it has moved.
+async fn save_checkpoint(state: &AppState, group: &mut Group) -> Result<()> {
+    let checkpoint = group.checkpoint();
+    if checkpoint != group.definition.checkpoint {
+        group.definition.checkpoint = checkpoint;
+        state.storage.set_subscription_group(&group.definition).await?;
+    }
+    Ok(())
+}
+
+/// GET /subscriptions
+pub async fn list_groups(State(state): State<AppState>, tenant: Tenant) -> Result<Json<Vec<SubscriptionGroup>>> {
+    let mut groups = Vec::new();
+    for entry in state.persistent_subscriptions.all() {
+        if entry.visible_to(&tenant) {
+            groups.push(entry.group.lock().await.definition.clone());
+        }
+    }
+    Ok(Json(groups))
+}
+
+/// PUT /subscriptions/:group — creates the group, starting from the head of
+/// the log, or changes its filters and delivery settings, keeping its
+/// checkpoint.
+pub async fn set_group(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<SubscriptionGroupRequest>,
+) -> Result<(StatusCode, Json<SubscriptionGroup>)> {
+    validate_name(&name)?;
+    let max_attempts = request.max_attempts.unwrap_or(state.config.subscription_max_attempts);
+    if max_attempts == 0 {
+        return Err(AppError::BadRequest("max_attempts must be greater than 0".to_string()));
+    }
+    let ack_timeout_seconds = request
+        .ack_timeout_seconds
+        .unwrap_or(state.config.subscription_ack_timeout_seconds);
+    if ack_timeout_seconds == 0 {
+        return Err(AppError::BadRequest("ack_timeout_seconds must be greater than 0".to_string()));
+    }
+    let matcher = compile(&request.filter)?;
+
+    let existing = state.persistent_subscriptions.groups.read().unwrap().get(&name).cloned();
+    if let Some(entry) = existing {
+        if !entry.visible_to(&tenant) {
+            return Err(AppError::Conflict(format!("Subscription group {} already exists", name)));
+        }
+        let mut group = entry.group.lock().await;
+        group.definition.stream_prefix = request.stream_prefix;
+        group.definition.event_types = request.event_types;
+        group.definition.filter = request.filter;
+        group.matcher = matcher;
+        group.definition.ack_timeout_seconds = ack_timeout_seconds;
+        group.definition.max_attempts = max_attempts;
+        state.storage.set_subscription_group(&group.definition).await?;
+        info!("Subscription group updated: {}", name);
+        return Ok((StatusCode::OK, Json(group.definition.clone())));
+    }
+
+    let definition = SubscriptionGroup {
+        name: name.clone(),
+        tenant_id: tenant.0,
+        stream_prefix: request.stream_prefix,
+        event_types: request.event_types,
+        filter: request.filter,
+        ack_timeout_seconds,
+        max_attempts,
+        checkpoint: state.storage.committed_position().await?,
+        created_at: state.providers.now(),
+    };
+    state.storage.set_subscription_group(&definition).await?;
+    let entry = Entry::new(definition.clone())?;
+    state.persistent_subscriptions.groups.write().unwrap().insert(name.clone(), entry);
+    info!("Subscription group created: {} at position {}", name, definition.checkpoint);
+
+    Ok((StatusCode::CREATED, Json(definition)))
+}
+
+/// DELETE /subscriptions/:group — forgets the group, its checkpoint and
+/// any events in flight.
+pub async fn delete_group(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<StatusCode> {
+    state.persistent_subscriptions.get(&name, &tenant)?;
+    state.storage.remove_subscription_group(&name).await?;
+    state.persistent_subscriptions.groups.write().unwrap().remove(&name);
+
+    state.metrics.subscription_checkpoint.remove_label_values(&[&name]).ok();
+    state.metrics.subscription_lag.remove_label_values(&[&name]).ok();
+    state.metrics.subscription_in_flight.remove_label_values(&[&name]).ok();
+    state.metrics.subscription_parked.remove_label_values(&[&name]).ok();
+    info!("Subscription group removed: {}", name);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /subscriptions/:group/poll — leases up to `max_events` events to
+/// the consumer: first those due for redelivery, then new ones from the
+/// log. Each must be acknowledged by its deadline or it's delivered again.
+/// A group has at most `subscription_max_in_flight` events leased at once;
+/// past that, polls come back empty until some are acknowledged.
+pub async fn poll(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<PollRequest>,
+) -> Result<Json<Vec<Delivery>>> {
+    validate_consumer(&request.consumer)?;
+    let max_events = request.max_events.unwrap_or(100).clamp(1, MAX_POLL_SIZE);
+    let entry = state.persistent_subscriptions.get(&name, &tenant)?;
+    let mut group = entry.group.lock().await;
+
+    let now = state.providers.now();
+    let ack_deadline = now + chrono::Duration::seconds(group.definition.ack_timeout_seconds as i64);
+    state.persistent_subscriptions.tidy(&mut group, now);
+    group.seen(&request.consumer, now);
+    // A group only leases out so much at once; the rest waits in the log
+    let max_events = max_events.min(
+        state
+            .persistent_subscriptions
+            .max_in_flight
+            .saturating_sub(group.in_flight.len()),
+    );
+
+    let mut leased = Vec::new();
+    while leased.len() < max_events {
+        let Some((position, (event, attempts))) = group.retries.pop_first() else {
+            break;
+        };
+        leased.push((position, event, attempts + 1));
+    }
+
+    let committed = state.storage.committed_position().await?;
+    'read: while leased.len() < max_events && group.read_position < committed {
+        let page = state.storage.read_all(group.read_position, POLL_PAGE_SIZE).await?;
+        if page.is_empty() {
+            break;
+        }
+        for event in page {
+            if event.position > committed || leased.len() >= max_events {
+                break 'read;
+            }
+            group.read_position = event.position;
+            if group.matches(&event) {
+                leased.push((event.position, event, 1));
+            }
+        }
+    }
+
+    let mut events: Vec<Event> = leased.iter().map(|(_, event, _)| event.clone()).collect();
+    encryption::decrypt_events(&state, &mut events).await?;
+
+    let mut deliveries = Vec::with_capacity(leased.len());
+    for ((position, event, attempts), decrypted) in leased.into_iter().zip(events) {
+        group.in_flight.insert(
+            position,
+            Lease {
+                consumer: request.consumer.clone(),
+                event,
+                attempts,
+                expires_at: ack_deadline,
+            },
+        );
+        deliveries.push(Delivery {
+            receipt: position,
+            attempt: attempts,
+            ack_deadline,
+            event: decrypted,
+        });
+    }
+    save_checkpoint(&state, &mut group).await?;
+
+    Ok(Json(deliveries))
+}
+
+/// POST /subscriptions/:group/ack — confirms the consumer has handled the
+/// events; the checkpoint moves past them once everything before them is
+/// acknowledged too. Parked events can be acknowledged by any consumer, to
+/// skip them.
+pub async fn ack(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<ReceiptsRequest>,
+) -> Result<Json<ReceiptsOutcome>> {
+    settle(&state, &name, &tenant, request, true).await
+}
+
+/// POST /subscriptions/:group/nack — gives the events back for another
+/// delivery, or parks them with `park` or once they've had `max_attempts`.
+pub async fn nack(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<ReceiptsRequest>,
+) -> Result<Json<ReceiptsOutcome>> {
+    settle(&state, &name, &tenant, request, false).await
+}
+
+async fn settle(
+    state: &AppState,
+    name: &str,
+    tenant: &Tenant,
+    request: ReceiptsRequest,
+    acknowledge: bool,
+) -> Result<Json<ReceiptsOutcome>> {
+    validate_consumer(&request.consumer)?;
+    let entry = state.persistent_subscriptions.get(name, tenant)?;
+    let mut group = entry.group.lock().await;
+    let now = state.providers.now();
+    state.persistent_subscriptions.tidy(&mut group, now);
+
+    let mut accepted = 0;
+    let mut stale = Vec::new();
+    for receipt in request.receipts {
+        let held = group
+            .in_flight
+            .get(&receipt)
+            .is_some_and(|lease| lease.consumer == request.consumer);
+        if held {
+            let mut lease = group.in_flight.remove(&receipt).expect("lease just found");
+            if !acknowledge {
+                if request.park {
+                    lease.attempts = lease.attempts.max(group.definition.max_attempts);
+                }
+                group.release(receipt, lease);
+            }
+            accepted += 1;
+        } else if acknowledge && group.parked.remove(&receipt).is_some() {
+            accepted += 1;
+        } else {
+            stale.push(receipt);
+        }
+    }
+    if acknowledge {
+        group.seen(&request.consumer, now).acknowledged += accepted;
+    } else {
+        group.seen(&request.consumer, now);
+    }
+    save_checkpoint(state, &mut group).await?;
+
+    Ok(Json(ReceiptsOutcome {
+        accepted,
+        stale,
+        checkpoint_position: group.definition.checkpoint,
+    }))
+}
+
+/// GET /subscriptions/:group/lag — how far the group's checkpoint trails
+/// the head of the log, with each consumer's in-flight and parked events.
+pub async fn get_lag(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<SubscriptionLag>> {
+    let entry = state.persistent_subscriptions.get(&name, &tenant)?;
+    let head_position = state.storage.committed_position().await?;
+    let mut group = entry.group.lock().await;
+    state.persistent_subscriptions.tidy(&mut group, state.providers.now());
+
+    let lag = group.lag(head_position);
+    record(&state.metrics, &lag);
+    Ok(Json(lag))
+}
+
+fn record(metrics: &Metrics, lag: &SubscriptionLag) {
+    let group = lag.group.as_str();
+    metrics
+        .subscription_checkpoint
+        .with_label_values(&[group])
+        .set(lag.checkpoint_position);
+    metrics.subscription_lag.with_label_values(&[group]).set(lag.lag);
+    metrics.subscription_in_flight.with_label_values(&[group]).set(lag.in_flight as i64);
+    metrics.subscription_parked.with_label_values(&[group]).set(lag.parked as i64);
+}
+
+/// Background task: refreshes the `event_store_subscription_*` gauges, so
+/// lag alerts don't wait for someone to ask. Leases that have timed out
+/// count as in flight until the group's next poll.
+pub async fn observe_lag(
+    job: Arc<Job>,
+    subscriptions: Arc<PersistentSubscriptions>,
+    storage: Arc<dyn EventStorage>,
+    metrics: Metrics,
+) {
+    jobs::run_periodically("subscription_lag", job, || async {
+        let groups = subscriptions.all();
+        let head_position = storage.committed_position().await?;
+        let mut lagging = 0;
+        for entry in &groups {
+            let lag = entry.group.lock().await.lag(head_position);
+            if lag.lag > 0 {
+                lagging += 1;
+            }
+            record(&metrics, &lag);
+        }
+        Ok(format!("{} subscription groups, {} behind the head", groups.len(), lagging))
+    })
+    .await
+}
@@ -0,0 +1,346 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::{info, warn};
+use wasmtime::{Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use crate::auth::Tenant;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::jobs::{self, Job};
+use crate::models::{
+    is_valid_stream_pattern, stream_pattern_matches, Event, Plugin, PluginKind, ProjectionState, ReducerSpec,
+};
+use crate::storage::EventStorage;
+use crate::AppState;
+
+/// Events read per page while advancing a projection.
+const PROJECTION_PAGE_SIZE: i64 = 1_000;
+
+/// Hosts the uploaded WASM plugins. A module must import nothing and export:
+///
+/// - `memory`
+/// - `alloc(len: i32) -> i32`, returning a buffer for the host to write into
+/// - `fold(state_ptr: i32, state_len: i32, event_ptr: i32, event_len: i32) -> i64`,
+///   returning the new state as `ptr << 32 | len`
+///
+/// State and events are passed as JSON. Each batch of events runs in a
+/// fresh instance, capped in memory and in fuel per event.
+pub struct PluginHost {
+    engine: Engine,
+    modules: RwLock<HashMap<String, (Plugin, Module)>>,
+    max_memory_bytes: usize,
+    fuel_per_event: u64,
+}
+
+fn plugin_error(name: &str, e: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Plugin {} failed: {}", name, e))
+}
+
+impl PluginHost {
+    pub async fn load(storage: &dyn EventStorage, config: &Config) -> Result<Self> {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config)
+            .map_err(|e| AppError::Internal(format!("Failed to start the WASM engine: {}", e)))?;
+
+        let host = Self {
+            engine,
+            modules: RwLock::new(HashMap::new()),
+            max_memory_bytes: config.plugin_max_memory_bytes,
+            fuel_per_event: config.plugin_fuel_per_event,
+        };
+        for (plugin, bytes) in storage.plugins().await? {
+            // A module that no longer compiles shouldn't keep the server down
+            match host.compile(&bytes) {
+                Ok(module) => {
+                    host.modules.write().unwrap().insert(plugin.name.clone(), (plugin, module));
+                }
+                Err(e) => warn!("Plugin {} not loaded: {}", plugin.name, e),
+            }
+        }
+        Ok(host)
+    }
+
+    fn compile(&self, bytes: &[u8]) -> Result<Module> {
+        let module = Module::new(&self.engine, bytes)
+            .map_err(|e| AppError::BadRequest(format!("Invalid WASM module: {}", e)))?;
+
+        if let Some(import) = module.imports().next() {
+            return Err(AppError::BadRequest(format!(
+                "Plugins cannot import host functions, but the module imports {}::{}",
+                import.module(),
+                import.name()
+            )));
+        }
+        for export in ["memory", "alloc", "fold"] {
+            if module.get_export(export).is_none() {
+                return Err(AppError::BadRequest(format!("The module does not export {}", export)));
+            }
+        }
+        Ok(module)
+    }
+
+    pub fn list(&self) -> Vec<Plugin> {
+        let mut plugins: Vec<Plugin> = self.modules.read().unwrap().values().map(|(p, _)| p.clone()).collect();
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+        plugins
+    }
+
+    pub fn get(&self, name: &str) -> Option<Plugin> {
+        self.modules.read().unwrap().get(name).map(|(p, _)| p.clone())
+    }
+
+    /// Folds `events` into `state` with the named plugin, off the async
+    /// runtime.
+    pub async fn fold(self: &Arc<Self>, name: &str, state: Value, events: Vec<Event>) -> Result<Value> {
+        let module = self
+            .modules
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|(_, module)| module.clone())
+            .ok_or_else(|| AppError::NotFound(format!("Plugin {} not found", name)))?;
+        if events.is_empty() {
+            return Ok(state);
+        }
+
+        let host = self.clone();
+        let name = name.to_string();
+        tokio::task::spawn_blocking(move || host.run(&name, &module, &state, &events))
+            .await
+            .map_err(|e| AppError::Internal(format!("Plugin task failed: {}", e)))?
+    }
+
+    fn run(&self, name: &str, module: &Module, state: &Value, events: &[Event]) -> Result<Value> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.max_memory_bytes)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .set_fuel(self.fuel_per_event.saturating_mul(events.len() as u64))
+            .map_err(|e| plugin_error(name, e))?;
+
+        let instance = Instance::new(&mut store, module, &[]).map_err(|e| plugin_error(name, e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| plugin_error(name, "memory is not a memory"))?;
+        let alloc: TypedFunc<i32, i32> = instance
+            .get_typed_func(&mut store, "alloc")
+            .map_err(|e| plugin_error(name, e))?;
+        let fold: TypedFunc<(i32, i32, i32, i32), i64> = instance
+            .get_typed_func(&mut store, "fold")
+            .map_err(|e| plugin_error(name, e))?;
+
+        // The state stays in the instance between events; only the result is read back
+        let (mut state_ptr, mut state_len) = write(&mut store, memory, &alloc, &serde_json::to_vec(state)?)
+            .map_err(|e| plugin_error(name, e))?;
+        for event in events {
+            let (event_ptr, event_len) = write(&mut store, memory, &alloc, &serde_json::to_vec(event)?)
+                .map_err(|e| plugin_error(name, e))?;
+            let packed = fold
+                .call(&mut store, (state_ptr, state_len, event_ptr, event_len))
+                .map_err(|e| plugin_error(name, format!("{} v{}: {}", event.stream_id, event.version, e)))?;
+            state_ptr = (packed >> 32) as i32;
+            state_len = packed as i32;
+        }
+
+        let mut state = vec![0; state_len as u32 as usize];
+        memory
+            .read(&store, state_ptr as u32 as usize, &mut state)
+            .map_err(|e| plugin_error(name, e))?;
+        serde_json::from_slice(&state).map_err(|e| plugin_error(name, format!("state is not JSON: {}", e)))
+    }
+}
+
+/// Copies `bytes` into a buffer the module allocates.
+fn write(
+    store: &mut Store<StoreLimits>,
+    memory: Memory,
+    alloc: &TypedFunc<i32, i32>,
+    bytes: &[u8],
+) -> std::result::Result<(i32, i32), String> {
+    let len = i32::try_from(bytes.len()).map_err(|_| "input exceeds 2 GiB".to_string())?;
+    let ptr = alloc.call(&mut *store, len).map_err(|e| e.to_string())?;
+    memory
+        .write(&mut *store, ptr as u32 as usize, bytes)
+        .map_err(|e| e.to_string())?;
+    Ok((ptr, len))
+}
+
+/// Advances every projection plugin through the events appended since its
+/// last run. A projection whose module changed starts over from the first
+/// event; events already archived are not replayed.
+pub async fn project_once(storage: &dyn EventStorage, host: &Arc<PluginHost>) -> Result<String> {
+    let mut projected = 0;
+    let projections: Vec<Plugin> = host
+        .list()
+        .into_iter()
+        .filter(|p| p.kind == PluginKind::Projection)
+        .collect();
+
+    for plugin in &projections {
+        let pattern = plugin.stream_pattern.as_deref().unwrap_or("*");
+        let mut projection = match storage.projection(&plugin.name).await? {
+            Some(projection) if projection.plugin_sha256 == plugin.sha256 => projection,
+            _ => ProjectionState {
+                name: plugin.name.clone(),
+                plugin_sha256: plugin.sha256.clone(),
+                position: 0,
+                state: Value::Null,
+                updated_at: Utc::now(),
+            },
+        };
+
+        loop {
+            let page = storage.read_all(projection.position, PROJECTION_PAGE_SIZE).await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            let position = last.position;
+            let full = page.len() as i64 == PROJECTION_PAGE_SIZE;
+            let events: Vec<Event> = page
+                .into_iter()
+                .filter(|e| stream_pattern_matches(pattern, &e.stream_id))
+                .collect();
+            projected += events.len();
+
+            projection.state = host.fold(&plugin.name, projection.state, events).await?;
+            projection.position = position;
+            projection.updated_at = Utc::now();
+            storage.set_projection(&projection).await?;
+            if !full {
+                break;
+            }
+        }
+    }
+
+    Ok(format!("Projected {} events into {} projections", projected, projections.len()))
+}
+
+// Background task: Keep projection plugins up to date
+pub async fn projector(job: Arc<Job>, storage: Arc<dyn EventStorage>, host: Arc<PluginHost>) {
+    jobs::run_periodically("projector", job, || project_once(storage.as_ref(), &host)).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PluginQuery {
+    pub kind: PluginKind,
+    /// Required for projections: the streams they see.
+    pub stream_pattern: Option<String>,
+}
+
+/// GET /admin/plugins
+pub async fn list_plugins(State(state): State<AppState>) -> Json<Vec<Plugin>> {
+    Json(state.plugins.list())
+}
+
+/// PUT /admin/plugins/:name?kind=&stream_pattern= with the WASM module as
+/// the body. Replacing a projection's module rebuilds it from the start.
+pub async fn upload_plugin(
+    Path(name): Path<String>,
+    Query(query): Query<PluginQuery>,
+    State(state): State<AppState>,
+    module: Bytes,
+) -> Result<Json<Plugin>> {
+    match (query.kind, &query.stream_pattern) {
+        (PluginKind::Projection, None) => {
+            return Err(AppError::BadRequest("Projections need a stream_pattern".to_string()));
+        }
+        (PluginKind::Reducer, Some(_)) => {
+            return Err(AppError::BadRequest(
+                "Reducers take their streams from snapshot reducer rules, not stream_pattern".to_string(),
+            ));
+        }
+        (_, Some(pattern)) if !is_valid_stream_pattern(pattern) => {
+            return Err(AppError::BadRequest(
+                "stream_pattern must be a stream id or a prefix ending in a single '*'".to_string(),
+            ));
+        }
+        _ => {}
+    }
+    if let Some(existing) = state.plugins.get(&name) {
+        if existing.kind != query.kind {
+            return Err(AppError::Conflict(format!(
+                "Plugin {} is a {:?} plugin; remove it first to change its kind",
+                name, existing.kind
+            )));
+        }
+    }
+
+    let compiled = state.plugins.compile(&module)?;
+    let plugin = Plugin {
+        name: name.clone(),
+        kind: query.kind,
+        stream_pattern: query.stream_pattern,
+        sha256: hex::encode(Sha256::digest(&module)),
+        size_bytes: module.len(),
+        updated_at: Utc::now(),
+    };
+    state.storage.set_plugin(&plugin, &module).await?;
+    state
+        .plugins
+        .modules
+        .write()
+        .unwrap()
+        .insert(name, (plugin.clone(), compiled));
+    info!("Plugin {} uploaded ({:?}, {} bytes)", plugin.name, plugin.kind, plugin.size_bytes);
+
+    Ok(Json(plugin))
+}
+
+/// DELETE /admin/plugins/:name — refused while a snapshot reducer uses it.
+pub async fn remove_plugin(Path(name): Path<String>, State(state): State<AppState>) -> Result<Response> {
+    let reducers = state.storage.snapshot_reducers().await?;
+    if let Some(reducer) = reducers
+        .iter()
+        .find(|r| matches!(&r.spec, ReducerSpec::Wasm { plugin } if *plugin == name))
+    {
+        return Err(AppError::Conflict(format!(
+            "Snapshot reducer {} uses plugin {}",
+            reducer.name, name
+        )));
+    }
+
+    if !state.storage.remove_plugin(&name).await? {
+        return Err(AppError::NotFound(format!("Plugin {} not found", name)));
+    }
+    state.plugins.modules.write().unwrap().remove(&name);
+
+    info!("Plugin {} removed", name);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// GET /projections/:name — a projection's current state. Tenants can read
+/// projections whose pattern lies within their own streams.
+pub async fn get_projection(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+) -> Result<Json<ProjectionState>> {
+    let plugin = state
+        .plugins
+        .get(&name)
+        .filter(|p| p.kind == PluginKind::Projection)
+        .ok_or_else(|| AppError::NotFound(format!("Projection {} not found", name)))?;
+    tenant.authorize(plugin.stream_pattern.as_deref().unwrap_or("*"))?;
+
+    state
+        .storage
+        .projection(&name)
+        .await?
+        .filter(|p| p.plugin_sha256 == plugin.sha256)
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("Projection {} has not run yet", name)))
+}
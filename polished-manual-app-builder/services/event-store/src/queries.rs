@@ -0,0 +1,162 @@
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::auth::Tenant;
+use crate::codec::{Accept, Encoded};
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::models::Event;
+use crate::storage::{MergeCursor, MergeOrder};
+use crate::{blobs, encryption, links, visible_from, AppState, MAX_PAGE_SIZE};
+
+/// Most streams one merged read may name.
+const MAX_MERGED_STREAMS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct MergeQuery {
+    pub streams: String,       // comma-separated stream ids
+    pub after: Option<String>, // `next` of the previous page; the first events when unset
+    pub order: Option<String>, // "position" (default) or "timestamp"
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub resolve_links: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MergedPage {
+    pub events: Vec<Event>,
+    /// Passed back as `after` for the following page. Always set, so a
+    /// caller at the end polls with it until more events arrive.
+    pub next: String,
+}
+
+fn parse_order(order: Option<&str>) -> Result<MergeOrder> {
+    match order {
+        None | Some("position") => Ok(MergeOrder::Position),
+        Some("timestamp") => Ok(MergeOrder::Timestamp),
+        Some(other) => Err(AppError::BadRequest(format!(
+            "Unknown order {}; expected position or timestamp",
+            other
+        ))),
+    }
+}
+
+/// Cursors are a position, or for timestamp order the event's creation
+/// time in microseconds and its position: `1717171717000000_42`.
+fn encode_cursor(cursor: MergeCursor, order: MergeOrder) -> String {
+    match order {
+        MergeOrder::Position => cursor.position.to_string(),
+        MergeOrder::Timestamp => format!("{}_{}", cursor.created_at.timestamp_micros(), cursor.position),
+    }
+}
+
+fn parse_cursor(cursor: &str, order: MergeOrder) -> Result<MergeCursor> {
+    let invalid = || AppError::BadRequest(format!("Invalid cursor {} for {} order", cursor, encode_order(order)));
+    match order {
+        MergeOrder::Position => Ok(MergeCursor {
+            position: cursor.parse().map_err(|_| invalid())?,
+            ..MergeCursor::START
+        }),
+        MergeOrder::Timestamp => {
+            let (micros, position) = cursor.split_once('_').ok_or_else(invalid)?;
+            Ok(MergeCursor {
+                created_at: micros
+                    .parse()
+                    .ok()
+                    .and_then(DateTime::from_timestamp_micros)
+                    .ok_or_else(invalid)?,
+                position: position.parse().map_err(|_| invalid())?,
+            })
+        }
+    }
+}
+
+fn encode_order(order: MergeOrder) -> &'static str {
+    match order {
+        MergeOrder::Position => "position",
+        MergeOrder::Timestamp => "timestamp",
+    }
+}
+
+/// GET /queries/merge?streams=a,b,c — the events of several streams in one
+/// paged sequence, by global position or by creation time. Lets a process
+/// manager follow every aggregate it tracks with a single polling loop.
+/// Pages end at the committed position, so an event committing late with
+/// a lower position is never skipped by a cursor that moved past it.
+pub async fn merge_streams(
+    Query(query): Query<MergeQuery>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    deadline: Deadline,
+    Accept(format): Accept,
+    headers: HeaderMap,
+) -> Result<Encoded<MergedPage>> {
+    let start_time = std::time::Instant::now();
+    state.metrics.event_read_requests.inc();
+
+    let mut stream_ids: Vec<String> = Vec::new();
+    for stream_id in query.streams.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        if !stream_ids.iter().any(|s| s == stream_id) {
+            stream_ids.push(stream_id.to_string());
+        }
+    }
+    if stream_ids.is_empty() {
+        return Err(AppError::BadRequest("streams must name at least one stream".to_string()));
+    }
+    if stream_ids.len() > MAX_MERGED_STREAMS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} streams can be merged in one read",
+            MAX_MERGED_STREAMS
+        )));
+    }
+    for stream_id in &stream_ids {
+        tenant.authorize(stream_id)?;
+    }
+
+    let order = parse_order(query.order.as_deref())?;
+    let after = match query.after.as_deref() {
+        Some(cursor) => parse_cursor(cursor, order)?,
+        None => MergeCursor::START,
+    };
+    let limit = query.limit.unwrap_or(100).clamp(0, MAX_PAGE_SIZE);
+
+    let committed = state.storage.committed_position().await?;
+    let mut events = state
+        .storage
+        .read_streams(&stream_ids, after, order, limit, deadline)
+        .await
+        .map_err(|e| {
+            state.metrics.event_read_errors.inc();
+            e
+        })?;
+    if let Some(uncommitted) = events.iter().position(|e| e.position > committed) {
+        events.truncate(uncommitted);
+    }
+    let next = events.last().map_or(after, MergeCursor::after);
+
+    let mut visible = HashMap::new();
+    for stream_id in &stream_ids {
+        visible.insert(stream_id.as_str(), visible_from(&state, stream_id).await?);
+    }
+    events.retain(|e| e.version >= visible.get(e.stream_id.as_str()).copied().unwrap_or(0));
+
+    if query.resolve_links {
+        links::resolve_events(&state, &tenant, &mut events, deadline).await?;
+    }
+    if !blobs::wants_links(&headers) {
+        blobs::resolve_events(&state, &mut events).await?;
+    }
+    encryption::decrypt_events(&state, &mut events).await?;
+
+    state.metrics.event_read_duration.observe(start_time.elapsed().as_secs_f64());
+    Ok(Encoded(
+        format,
+        MergedPage {
+            events,
+            next: encode_cursor(next, order),
+        },
+    ))
+}
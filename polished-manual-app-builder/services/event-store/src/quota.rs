@@ -0,0 +1,158 @@
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::info;
+
+use crate::auth::Caller;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::StreamMetadata;
+use crate::AppState;
+
+/// Idle buckets are pruned once this many callers have been seen.
+const MAX_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token bucket per caller: each API key or bearer token project may make
+/// `rate` requests per second on average and up to `burst` at once.
+/// `TENANT_RATE_LIMITS` overrides the rate for the callers bound to a project.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    overrides: HashMap<String, f64>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(rate) = config.rate_limit_per_second.filter(|rate| *rate > 0.0) else {
+            return Ok(None);
+        };
+
+        // TENANT_RATE_LIMITS="acme=500,trial=5"
+        let mut overrides = HashMap::new();
+        for entry in config
+            .tenant_rate_limits
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let invalid = || AppError::Internal(format!("Invalid TENANT_RATE_LIMITS entry '{}'", entry));
+            let (tenant, rate) = entry.split_once('=').ok_or_else(invalid)?;
+            let rate: f64 = rate.trim().parse().map_err(|_| invalid())?;
+            overrides.insert(tenant.trim().to_string(), rate);
+        }
+
+        info!(
+            "Rate limiting enabled: {} requests/s, burst {}, {} tenant overrides",
+            rate,
+            config.rate_limit_burst,
+            overrides.len()
+        );
+        Ok(Some(Self {
+            rate,
+            burst: config.rate_limit_burst.max(1.0),
+            overrides,
+            buckets: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Takes a token from `key`'s bucket, or returns the whole seconds until
+    /// one is available. `project` selects a `TENANT_RATE_LIMITS` override.
+    pub fn check(&self, key: &str, project: Option<&str>) -> std::result::Result<(), u64> {
+        let rate = project
+            .and_then(|project| self.overrides.get(project))
+            .copied()
+            .unwrap_or(self.rate);
+        let burst = self.burst.max(rate);
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(key) {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.refilled_at).as_secs_f64() * rate < burst
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: burst,
+            refilled_at: now,
+        });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.refilled_at).as_secs_f64() * rate).min(burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / rate).ceil() as u64)
+        }
+    }
+}
+
+/// Middleware: applies the rate limit to authenticated callers, by their
+/// credentials rather than the `X-Tenant-Id` header, which a caller could
+/// rotate for a fresh bucket. Runs after `auth::authenticate`, which
+/// identifies the caller.
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Result<Response> {
+    let Some(limiter) = &state.rate_limiter else {
+        return Ok(next.run(request).await);
+    };
+    let Some(caller) = request.extensions().get::<Caller>() else {
+        return Ok(next.run(request).await);
+    };
+
+    if let Err(retry_after) = limiter.check(&caller.id, caller.project_id.as_deref()) {
+        state.metrics.record_rate_limited(&caller.id);
+        return Err(AppError::RateLimited(
+            format!("Rate limit exceeded for '{}'", caller.id),
+            retry_after.max(1),
+        ));
+    }
+    Ok(next.run(request).await)
+}
+
+/// Rejects an append of `payload_bytes` to `stream_id` when it would break
+/// the stream's quotas: its metadata's `max_events`/`max_payload_bytes`,
+/// falling back to `MAX_EVENTS_PER_STREAM`/`MAX_PAYLOAD_BYTES`.
+pub async fn check_stream_quotas(
+    state: &AppState,
+    stream_id: &str,
+    metadata: Option<&StreamMetadata>,
+    payload_bytes: usize,
+) -> Result<()> {
+    let max_payload_bytes = metadata
+        .and_then(|m| m.max_payload_bytes)
+        .or(state.config.max_payload_bytes);
+    if let Some(max) = max_payload_bytes {
+        if payload_bytes > max {
+            state.metrics.quota_rejections.with_label_values(&["max_payload_bytes"]).inc();
+            return Err(AppError::PayloadTooLarge(format!(
+                "Event payload is {} bytes; stream {} allows at most {}",
+                payload_bytes, stream_id, max
+            )));
+        }
+    }
+
+    let max_events = metadata.and_then(|m| m.max_events).or(state.config.max_events_per_stream);
+    if let Some(max) = max_events {
+        if state.storage.stream_version(stream_id).await? >= max {
+            state.metrics.quota_rejections.with_label_values(&["max_events"]).inc();
+            return Err(AppError::QuotaExceeded(format!(
+                "Stream {} has reached its limit of {} events",
+                stream_id, max
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use async_trait::async_trait;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::bus::BusConsumer;
+use crate::codec::BodyFormat;
+use crate::models::Event;
+use crate::storage::ReadDirection;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PageKey {
+    pub stream_id: String,
+    pub from_version: i64,
+    pub limit: i64,
+    pub backward: bool,
+    pub include_archived: bool,
+}
+
+impl PageKey {
+    pub fn new(
+        stream_id: &str,
+        from_version: i64,
+        limit: i64,
+        direction: ReadDirection,
+        include_archived: bool,
+    ) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            from_version,
+            limit,
+            backward: direction == ReadDirection::Backward,
+            include_archived,
+        }
+    }
+}
+
+struct CachedPage {
+    events: Arc<Vec<Event>>,
+    cached_at: Instant,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Pages {
+    entries: HashMap<PageKey, CachedPage>,
+    /// Least recently used first.
+    recency: BTreeMap<u64, PageKey>,
+    by_stream: HashMap<String, HashSet<PageKey>>,
+    tick: u64,
+}
+
+impl Pages {
+    fn remove(&mut self, key: &PageKey) {
+        if let Some(page) = self.entries.remove(key) {
+            self.recency.remove(&page.tick);
+            if let Some(keys) = self.by_stream.get_mut(&key.stream_id) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.by_stream.remove(&key.stream_id);
+                }
+            }
+        }
+    }
+}
+
+/// LRU cache of recently read event pages. Pages of a stream are dropped
+/// when the event bus reports a new event on it, and every page expires
+/// after `ttl` so changes that bypass the bus (archiving, bulk loads) are
+/// picked up eventually.
+pub struct ReadCache {
+    capacity: usize,
+    ttl: Duration,
+    pages: Mutex<Pages>,
+}
+
+impl ReadCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            pages: Mutex::new(Pages::default()),
+        }
+    }
+
+    pub fn get(&self, key: &PageKey) -> Option<Arc<Vec<Event>>> {
+        let mut pages = self.pages.lock().unwrap();
+        pages.tick += 1;
+        let tick = pages.tick;
+
+        let page = pages.entries.get_mut(key)?;
+        if page.cached_at.elapsed() > self.ttl {
+            pages.remove(key);
+            return None;
+        }
+        let previous = std::mem::replace(&mut page.tick, tick);
+        let events = page.events.clone();
+
+        pages.recency.remove(&previous);
+        pages.recency.insert(tick, key.clone());
+        Some(events)
+    }
+
+    pub fn insert(&self, key: PageKey, events: Arc<Vec<Event>>) {
+        let mut pages = self.pages.lock().unwrap();
+        pages.remove(&key);
+
+        while pages.entries.len() >= self.capacity {
+            let Some((_, oldest)) = pages.recency.pop_first() else {
+                break;
+            };
+            pages.remove(&oldest);
+        }
+
+        pages.tick += 1;
+        let tick = pages.tick;
+        pages.recency.insert(tick, key.clone());
+        pages
+            .by_stream
+            .entry(key.stream_id.clone())
+            .or_default()
+            .insert(key.clone());
+        pages.entries.insert(
+            key,
+            CachedPage {
+                events,
+                cached_at: Instant::now(),
+                tick,
+            },
+        );
+    }
+
+    pub fn invalidate_stream(&self, stream_id: &str) {
+        let mut pages = self.pages.lock().unwrap();
+        let keys = pages.by_stream.remove(stream_id).unwrap_or_default();
+        for key in keys {
+            if let Some(page) = pages.entries.remove(&key) {
+                pages.recency.remove(&page.tick);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        *self.pages.lock().unwrap() = Pages::default();
+    }
+}
+
+/// Drops cached pages of streams as new events commit.
+pub struct ReadCacheInvalidator {
+    cache: Arc<ReadCache>,
+}
+
+impl ReadCacheInvalidator {
+    pub fn new(cache: Arc<ReadCache>) -> Self {
+        Self { cache }
+    }
+}
+
+#[async_trait]
+impl BusConsumer for ReadCacheInvalidator {
+    fn name(&self) -> &str {
+        "read-cache"
+    }
+
+    async fn handle(&self, event: &Event) {
+        self.cache.invalidate_stream(&event.stream_id);
+    }
+}
+
+/// Strong ETag for a page of events in a given wire format. A redaction
+/// rewrites bodies in place, refreshing their checksums and the chain
+/// hashes after them, so those go in along with ids and versions.
+pub fn etag(events: &[Event], format: BodyFormat) -> String {
+    // FNV-1a: stable across processes and releases, unlike DefaultHasher
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+
+    feed(format.content_type().as_bytes());
+    for event in events {
+        feed(event.id.as_bytes());
+        feed(&event.version.to_be_bytes());
+        for digest in [&event.checksum, &event.chain_hash] {
+            match digest {
+                Some(digest) => {
+                    feed(&[1]);
+                    feed(digest.as_bytes());
+                }
+                None => feed(&[0]),
+            }
+        }
+    }
+
+    format!("\"{:016x}-{}\"", hash, events.len())
+}
+
+/// Whether an `If-None-Match` header matches `etag`.
+pub fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag || tag == "*")
+}
+
+/// Whether an `If-Match` header names `version`, the stream's head: the
+/// caller already holds the state or snapshot as of it. Checked before any
+/// events or snapshots are read, so a poller of an idle stream costs one
+/// head lookup.
+pub fn version_matches(headers: &HeaderMap, version: i64) -> bool {
+    headers
+        .get_all(header::IF_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/").trim_matches('"'))
+        .any(|tag| tag.parse() == Ok(version))
+}
+
+/// The 304 for a conditional read of a stream that is still at `version`.
+pub fn unchanged(version: i64) -> Response {
+    (StatusCode::NOT_MODIFIED, [(crate::STREAM_VERSION_HEADER, version.to_string())]).into_response()
+}
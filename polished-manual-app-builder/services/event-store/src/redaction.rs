@@ -0,0 +1,312 @@
+use axum::{extract::State, response::Json, Extension};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::blobs::BLOB_REF_MIME;
+use crate::cold_storage;
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::integrity::{self, Link};
+use crate::models::{ApiKey, Event};
+use crate::storage::{EventRewrite, ReadDirection};
+use crate::{is_json_content_type, AppState};
+
+/// Events read per page while redacting.
+const REDACTION_PAGE_SIZE: i64 = 500;
+
+/// Key of the object a redacted field is replaced with; its value is the
+/// redaction id, to look up in `$redactions`.
+pub const REDACTED_KEY: &str = "$redacted";
+
+#[derive(Debug, Deserialize)]
+pub struct RedactionRequest {
+    pub stream_id: String,
+    /// Defaults to the first version.
+    pub from_version: Option<i64>,
+    /// Defaults to the stream head.
+    pub to_version: Option<i64>,
+    /// JSON pointers into event data, e.g. `/customer/email`.
+    pub fields: Vec<String>,
+    /// Only events of these types; every type when unset.
+    pub event_types: Option<Vec<String>>,
+    /// Why, for the audit record: e.g. the takedown request it answers.
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RedactionReport {
+    pub redaction_id: Uuid,
+    pub stream_id: String,
+    pub from_version: i64,
+    pub to_version: i64,
+    pub fields: Vec<String>,
+    pub reason: String,
+    /// Name of the API key that asked, when keys are in use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_by: Option<String>,
+    pub events_redacted: u64,
+    /// Later events whose chain hash changed with the ones before them.
+    pub events_rechained: u64,
+    pub snapshots_deleted: u64,
+    /// Chain hash of the stream head before and after; anchors signed
+    /// before the redaction carry the old one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_head_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head_hash: Option<String>,
+    pub redacted_at: DateTime<Utc>,
+}
+
+/// Replaces every field of `data` named by `pointers` with `marker`.
+/// Returns whether any was there.
+fn redact(data: &mut Value, pointers: &[String], marker: &Value) -> bool {
+    let mut redacted = false;
+    for pointer in pointers {
+        if let Some(value) = data.pointer_mut(pointer) {
+            *value = marker.clone();
+            redacted = true;
+        }
+    }
+    redacted
+}
+
+/// POST /admin/redactions — for takedowns of data that wasn't encrypted
+/// under a shreddable key: replaces the named fields of a range of a
+/// stream's events, archived ones included, with `{"$redacted": <id>}`.
+/// Versions and ids stay; the checksums of redacted events and the chain
+/// hashes from the first of them to the head are recomputed, so the chain
+/// still verifies. Snapshots from the first redacted version on are
+/// deleted, to be rebuilt. All events are rewritten in one transaction
+/// that holds off appends, and the redaction is recorded in `$redactions`.
+/// An append that lands while the new hashes are computed makes it fail
+/// with a conflict, to be retried.
+pub async fn create_redaction(
+    State(state): State<AppState>,
+    api_key: Option<Extension<ApiKey>>,
+    Json(request): Json<RedactionRequest>,
+) -> Result<Json<RedactionReport>> {
+    if request.fields.is_empty() {
+        return Err(AppError::BadRequest("A redaction needs at least one field".to_string()));
+    }
+    if let Some(field) = request.fields.iter().find(|f| !f.starts_with('/')) {
+        return Err(AppError::BadRequest(format!(
+            "Field '{}' is not a JSON pointer, e.g. /customer/email",
+            field
+        )));
+    }
+    if request.reason.trim().is_empty() {
+        return Err(AppError::BadRequest("A redaction needs a reason".to_string()));
+    }
+
+    let stream_id = request.stream_id.as_str();
+    let head = state.storage.stream_version(stream_id).await?;
+    if head == 0 {
+        return Err(AppError::NotFound(format!("Stream {} not found", stream_id)));
+    }
+    let from_version = request.from_version.unwrap_or(1);
+    let to_version = request.to_version.unwrap_or(head).min(head);
+    if from_version < 1 || from_version > to_version {
+        return Err(AppError::BadRequest(format!(
+            "Invalid version range {} to {}",
+            from_version, to_version
+        )));
+    }
+    let metadata = state.storage.stream_metadata(stream_id).await?.unwrap_or_default();
+    if metadata.legal_hold {
+        return Err(AppError::Conflict(format!(
+            "Stream {} is under legal hold; lift it before redacting",
+            stream_id
+        )));
+    }
+
+    let redaction_id = state.providers.new_id();
+    let redacted_at = state.providers.now();
+    let marker = json!({ REDACTED_KEY: redaction_id });
+
+    // The chain continues from the event before the range, when it's still there
+    let mut previous = cold_storage::read_stream(
+        &state,
+        stream_id,
+        from_version - 1,
+        1,
+        ReadDirection::Forward,
+        true,
+        Deadline(None),
+    )
+    .await?
+    .into_iter()
+    .find(|e| e.version == from_version - 1)
+    .and_then(|e| e.chain_hash);
+
+    let mut rewrites = Vec::new();
+    let mut events_read = 0;
+    let mut first_redacted = None;
+    let mut previous_head_hash = None;
+    let mut read_head = 0;
+    let mut next = from_version;
+    loop {
+        let page = cold_storage::read_stream(
+            &state,
+            stream_id,
+            next,
+            REDACTION_PAGE_SIZE,
+            ReadDirection::Forward,
+            true,
+            Deadline(None),
+        )
+        .await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        read_head = last.version;
+        next = last.version + 1;
+        events_read += page.len() as i64;
+
+        for mut event in page {
+            previous_head_hash = event.chain_hash.clone();
+            let data = redact_event(&mut event, &request, to_version, &marker)?;
+            if data.is_some() {
+                first_redacted.get_or_insert(event.version);
+            }
+            // Hashes only change from the first redacted event on
+            if first_redacted.is_none() {
+                if event.chain_hash.is_some() {
+                    previous = event.chain_hash;
+                }
+                continue;
+            }
+
+            let chain_hash = match (&event.chain_hash, Link::event(&event)) {
+                (Some(_), Some(link)) => Some(link.hash(previous.as_deref())),
+                (chain_hash, _) => chain_hash.clone(),
+            };
+            if data.is_some() || chain_hash != event.chain_hash {
+                rewrites.push(EventRewrite {
+                    version: event.version,
+                    data,
+                    checksum: event.checksum.clone(),
+                    chain_hash: chain_hash.clone(),
+                });
+            }
+            if chain_hash.is_some() {
+                previous = chain_hash;
+            }
+        }
+    }
+
+    let requested_by = api_key.map(|Extension(key)| key.name);
+    let Some(first_redacted) = first_redacted else {
+        return Ok(Json(RedactionReport {
+            redaction_id,
+            stream_id: request.stream_id,
+            from_version,
+            to_version,
+            fields: request.fields,
+            reason: request.reason,
+            requested_by,
+            events_redacted: 0,
+            events_rechained: 0,
+            snapshots_deleted: 0,
+            head_hash: previous_head_hash.clone(),
+            previous_head_hash,
+            redacted_at,
+        }));
+    };
+
+    // Events tiered out to cold storage can't be rewritten in place
+    let local = state.storage.count_events(stream_id, from_version, read_head).await?;
+    if local < events_read {
+        return Err(AppError::Conflict(format!(
+            "{} events of {} from version {} are in cold storage and can't be redacted",
+            events_read - local,
+            stream_id,
+            from_version
+        )));
+    }
+
+    state
+        .storage
+        .rewrite_events(stream_id, read_head, previous_head_hash.as_deref(), &rewrites)
+        .await?;
+    if let Some(cache) = &state.read_cache {
+        cache.invalidate_stream(stream_id);
+    }
+
+    let stale: Vec<i64> = state
+        .storage
+        .snapshot_versions(stream_id)
+        .await?
+        .into_iter()
+        .filter(|v| *v >= first_redacted)
+        .collect();
+    let snapshots_deleted = state.storage.delete_snapshots(stream_id, &stale).await?;
+
+    let events_redacted = rewrites.iter().filter(|r| r.data.is_some()).count() as u64;
+    let head_hash = rewrites
+        .last()
+        .filter(|r| r.version == read_head)
+        .map_or(previous_head_hash.clone(), |r| r.chain_hash.clone());
+    let report = RedactionReport {
+        redaction_id,
+        stream_id: request.stream_id,
+        from_version,
+        to_version,
+        fields: request.fields,
+        reason: request.reason,
+        requested_by,
+        events_redacted,
+        events_rechained: rewrites.len() as u64 - events_redacted,
+        snapshots_deleted,
+        previous_head_hash,
+        head_hash,
+        redacted_at,
+    };
+    warn!(
+        "Redaction {} rewrote {} events of {} ({} redacted) by {}: {}",
+        redaction_id,
+        rewrites.len(),
+        report.stream_id,
+        events_redacted,
+        report.requested_by.as_deref().unwrap_or("admin"),
+        report.reason
+    );
+    state.system_streams.redaction_applied(serde_json::to_value(&report)?).await;
+
+    Ok(Json(report))
+}
+
+/// Redacts `event` in place when the request covers it, refreshing its
+/// checksum. Returns its new data, or `None` when nothing was redacted.
+fn redact_event(
+    event: &mut Event,
+    request: &RedactionRequest,
+    to_version: i64,
+    marker: &Value,
+) -> Result<Option<Value>> {
+    let covered = event.version <= to_version
+        && request
+            .event_types
+            .as_ref()
+            .map_or(true, |types| types.contains(&event.event_type));
+    if !covered {
+        return Ok(None);
+    }
+    if event.content_type == BLOB_REF_MIME {
+        return Err(AppError::Conflict(format!(
+            "Event {} ({} v{}) has an offloaded body, which can't be redacted in place",
+            event.id, event.stream_id, event.version
+        )));
+    }
+    if !is_json_content_type(&event.content_type) {
+        return Ok(None);
+    }
+    if !redact(&mut event.data, &request.fields, marker) {
+        return Ok(None);
+    }
+
+    event.checksum = Some(integrity::checksum(Some(&event.data), None));
+    Ok(Some(event.data.clone()))
+}
@@ -0,0 +1,340 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::models::{
+    is_valid_stream_pattern, stream_pattern_matches, stream_pattern_specificity, Event, PluginKind, ReducerSpec,
+    SnapshotReducer, StateTransform, TransformOp,
+};
+use crate::plugins::PluginHost;
+use crate::snapshots::SnapshotCodec;
+use crate::storage::{EventStorage, ReadDirection};
+use crate::AppState;
+
+/// Events folded per page while reducing a stream.
+const REDUCE_PAGE_SIZE: i64 = 1_000;
+
+/// The most specific reducer whose pattern matches `stream_id`.
+pub fn for_stream<'a>(reducers: &'a [SnapshotReducer], stream_id: &str) -> Option<&'a SnapshotReducer> {
+    reducers
+        .iter()
+        .filter(|r| stream_pattern_matches(&r.stream_pattern, stream_id))
+        .max_by_key(|r| stream_pattern_specificity(&r.stream_pattern))
+}
+
+/// Folds a stream up to `up_to_version` with `reducer`. Continues from the
+/// newest snapshot at or before that version when the same reducer,
+/// unchanged since, produced it; otherwise starts over from the first event
+/// still stored, since events tiered to cold storage are out of reach here.
+pub async fn reduce(
+    storage: &dyn EventStorage,
+    codec: &SnapshotCodec,
+    plugins: &Arc<PluginHost>,
+    reducer: &SnapshotReducer,
+    stream_id: &str,
+    up_to_version: i64,
+) -> Result<Value> {
+    let (state, _) = reduce_batch(storage, codec, plugins, reducer, stream_id, up_to_version, i64::MAX).await?;
+    Ok(state)
+}
+
+/// `reduce`, folding at most `max_events` versions past where it resumes.
+/// Returns the state and the version it reached, so the scheduler can
+/// snapshot a long stream a batch at a time.
+pub async fn reduce_batch(
+    storage: &dyn EventStorage,
+    codec: &SnapshotCodec,
+    plugins: &Arc<PluginHost>,
+    reducer: &SnapshotReducer,
+    stream_id: &str,
+    up_to_version: i64,
+    max_events: i64,
+) -> Result<(Value, i64)> {
+    let (mut state, from_version) = match storage.snapshot_at(stream_id, up_to_version).await? {
+        Some(snapshot)
+            if snapshot.reducer.as_deref() == Some(reducer.name.as_str())
+                && snapshot.created_at >= reducer.updated_at =>
+        {
+            (codec.decode(&snapshot)?, snapshot.version + 1)
+        }
+        _ => match &reducer.spec {
+            ReducerSpec::Transform(transform) => (transform.initial.clone(), 1),
+            ReducerSpec::Wasm { .. } => (Value::Null, 1),
+        },
+    };
+    let up_to_version = up_to_version.min((from_version - 1).saturating_add(max_events));
+
+    // Archived events are a prefix of the stream, so they come first
+    let archived = storage.read_archived(stream_id, from_version, up_to_version).await?;
+    let mut next = archived.last().map_or(from_version, |e| e.version + 1);
+    state = fold(plugins, reducer, state, archived).await?;
+
+    while next <= up_to_version {
+        let page = storage
+            .read_stream(stream_id, next, REDUCE_PAGE_SIZE, ReadDirection::Forward, Deadline(None))
+            .await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        next = last.version + 1;
+        let full = page.len() as i64 == REDUCE_PAGE_SIZE;
+        let events = page.into_iter().filter(|e| e.version <= up_to_version).collect();
+        state = fold(plugins, reducer, state, events).await?;
+        if !full {
+            break;
+        }
+    }
+
+    Ok((state, up_to_version))
+}
+
+async fn fold(plugins: &Arc<PluginHost>, reducer: &SnapshotReducer, mut state: Value, events: Vec<Event>) -> Result<Value> {
+    match &reducer.spec {
+        ReducerSpec::Transform(transform) => {
+            for event in &events {
+                apply(transform, &mut state, event).map_err(|e| {
+                    AppError::Internal(format!(
+                        "Reducer {} failed on {} v{}: {}",
+                        reducer.name, event.stream_id, event.version, e
+                    ))
+                })?;
+            }
+            Ok(state)
+        }
+        ReducerSpec::Wasm { plugin } => plugins.fold(plugin, state, events).await,
+    }
+}
+
+/// Applies the operations for one event to `state`.
+fn apply(transform: &StateTransform, state: &mut Value, event: &Event) -> std::result::Result<(), String> {
+    let Some(ops) = transform
+        .handlers
+        .get(&event.event_type)
+        .or_else(|| transform.handlers.get("*"))
+    else {
+        return Ok(());
+    };
+
+    let envelope = json!({
+        "id": event.id,
+        "stream_id": event.stream_id,
+        "event_type": event.event_type,
+        "data": event.data,
+        "metadata": event.metadata,
+        "version": event.version,
+        "position": event.position,
+        "created_at": event.created_at,
+    });
+    // Operations reading a field the event doesn't have are skipped
+    let read = |from: &str| envelope.pointer(from).cloned();
+
+    for op in ops {
+        match op {
+            TransformOp::Set { path, value } => *slot(state, path)? = value.clone(),
+            TransformOp::Copy { from, path } => {
+                if let Some(value) = read(from) {
+                    *slot(state, path)? = value;
+                }
+            }
+            TransformOp::Merge { from, path } => {
+                let Some(value) = read(from) else { continue };
+                let Value::Object(fields) = value else {
+                    return Err(format!("{} is not an object", from));
+                };
+                let target = slot(state, path)?;
+                if target.is_null() {
+                    *target = Value::Object(Map::new());
+                }
+                let Value::Object(target) = target else {
+                    return Err(format!("{} is not an object", path));
+                };
+                target.extend(fields);
+            }
+            TransformOp::Increment { path, by, from } => {
+                let amount = match (by, from) {
+                    (Some(by), _) => Value::Number(by.clone()),
+                    (None, Some(from)) => match read(from) {
+                        Some(value) => value,
+                        None => continue,
+                    },
+                    (None, None) => json!(1),
+                };
+                let target = slot(state, path)?;
+                let current = if target.is_null() { json!(0) } else { target.clone() };
+                *target = match (current.as_i64(), amount.as_i64()) {
+                    (Some(a), Some(b)) => json!(a.checked_add(b).ok_or_else(|| format!("{} overflowed", path))?),
+                    _ => match (current.as_f64(), amount.as_f64()) {
+                        (Some(a), Some(b)) => json!(a + b),
+                        _ => return Err(format!("{} is not a number", path)),
+                    },
+                };
+            }
+            TransformOp::Append { from, path } => {
+                let Some(value) = read(from) else { continue };
+                let target = slot(state, path)?;
+                if target.is_null() {
+                    *target = Value::Array(Vec::new());
+                }
+                let Value::Array(items) = target else {
+                    return Err(format!("{} is not an array", path));
+                };
+                items.push(value);
+            }
+            TransformOp::Remove { path } => {
+                let Some((parent, key)) = path.rsplit_once('/') else {
+                    *state = Value::Null;
+                    continue;
+                };
+                match state.pointer_mut(parent) {
+                    Some(Value::Object(fields)) => {
+                        fields.remove(&unescape(key));
+                    }
+                    Some(Value::Array(items)) => {
+                        if let Some(index) = key.parse::<usize>().ok().filter(|i| *i < items.len()) {
+                            items.remove(index);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// The value at JSON Pointer `path`, creating objects along the way as
+/// needed; `-` as the last array token appends.
+fn slot<'a>(state: &'a mut Value, path: &str) -> std::result::Result<&'a mut Value, String> {
+    if path.is_empty() {
+        return Ok(state);
+    }
+    let Some(tokens) = path.strip_prefix('/') else {
+        return Err(format!("{} is not a JSON Pointer", path));
+    };
+
+    tokens.split('/').try_fold(state, |current, token| {
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+        match current {
+            Value::Object(fields) => Ok(fields.entry(unescape(token)).or_insert(Value::Null)),
+            Value::Array(items) => {
+                if token == "-" {
+                    items.push(Value::Null);
+                }
+                let index = if token == "-" { items.len() - 1 } else { token.parse().unwrap_or(usize::MAX) };
+                items
+                    .get_mut(index)
+                    .ok_or_else(|| format!("{} is out of bounds in {}", token, path))
+            }
+            _ => Err(format!("{} runs into a scalar at {}", path, token)),
+        }
+    })
+}
+
+fn check_pointer(pointer: &str) -> Result<()> {
+    if pointer.is_empty() || pointer.starts_with('/') {
+        return Ok(());
+    }
+    Err(AppError::BadRequest(format!("'{}' is not a JSON Pointer", pointer)))
+}
+
+fn validate(transform: &StateTransform) -> Result<()> {
+    for op in transform.handlers.values().flatten() {
+        match op {
+            TransformOp::Set { path, .. } | TransformOp::Remove { path } => check_pointer(path)?,
+            TransformOp::Copy { from, path } | TransformOp::Merge { from, path } | TransformOp::Append { from, path } => {
+                check_pointer(from)?;
+                check_pointer(path)?;
+            }
+            TransformOp::Increment { path, by, from } => {
+                check_pointer(path)?;
+                if let Some(from) = from {
+                    check_pointer(from)?;
+                }
+                if by.is_some() && from.is_some() {
+                    return Err(AppError::BadRequest("increment takes by or from, not both".to_string()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotReducerRequest {
+    pub stream_pattern: String,
+    pub spec: ReducerSpec,
+}
+
+/// GET /admin/snapshot-reducers
+pub async fn list_reducers(State(state): State<AppState>) -> Result<Json<Vec<SnapshotReducer>>> {
+    Ok(Json(state.storage.snapshot_reducers().await?))
+}
+
+/// PUT /admin/snapshot-reducers/:name — replacing a reducer makes the next
+/// scheduled snapshot of its streams fold them from the start.
+pub async fn set_reducer(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(request): Json<SnapshotReducerRequest>,
+) -> Result<Json<SnapshotReducer>> {
+    if !is_valid_stream_pattern(&request.stream_pattern) {
+        return Err(AppError::BadRequest(
+            "stream_pattern must be a stream id or a prefix ending in a single '*'".to_string(),
+        ));
+    }
+    match &request.spec {
+        ReducerSpec::Transform(transform) => validate(transform)?,
+        ReducerSpec::Wasm { plugin } => {
+            if !state.plugins.get(plugin).is_some_and(|p| p.kind == PluginKind::Reducer) {
+                return Err(AppError::BadRequest(format!("No reducer plugin named {}", plugin)));
+            }
+        }
+    }
+
+    let existing = state.storage.snapshot_reducers().await?;
+    if let Some(other) = existing
+        .iter()
+        .find(|r| r.name != name && r.stream_pattern == request.stream_pattern)
+    {
+        return Err(AppError::Conflict(format!(
+            "Snapshot reducer {} already covers {}",
+            other.name, request.stream_pattern
+        )));
+    }
+
+    let reducer = SnapshotReducer {
+        name,
+        stream_pattern: request.stream_pattern,
+        spec: request.spec,
+        updated_at: Utc::now(),
+    };
+    state.storage.set_snapshot_reducer(&reducer).await?;
+    info!("Snapshot reducer {} set for {}", reducer.name, reducer.stream_pattern);
+
+    Ok(Json(reducer))
+}
+
+/// DELETE /admin/snapshot-reducers/:name
+pub async fn remove_reducer(Path(name): Path<String>, State(state): State<AppState>) -> Result<Response> {
+    if !state.storage.remove_snapshot_reducer(&name).await? {
+        return Err(AppError::NotFound(format!("Snapshot reducer {} not found", name)));
+    }
+
+    info!("Snapshot reducer {} removed", name);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
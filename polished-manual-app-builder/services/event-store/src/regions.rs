@@ -0,0 +1,272 @@
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{SecondsFormat, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::info;
+
+use crate::codec::BodyFormat;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::AppState;
+
+/// The region that handled the request; set on every response.
+pub static REGION_HEADER: HeaderName = HeaderName::from_static("x-event-store-region");
+/// On reads of a stream homed in another region, that region.
+pub static HOME_REGION_HEADER: HeaderName = HeaderName::from_static("x-stream-home-region");
+/// On those reads, when this store last held every event of its primary,
+/// and how many seconds ago that was.
+pub static SYNCED_AT_HEADER: HeaderName = HeaderName::from_static("x-synced-at");
+pub static STALENESS_HEADER: HeaderName = HeaderName::from_static("x-staleness-seconds");
+/// Marks a write forwarded to its home region, which must handle it.
+pub static FORWARDED_FROM_HEADER: HeaderName = HeaderName::from_static("x-forwarded-from-region");
+
+/// Longest a forwarded write may take, end to end.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Headers that describe one connection rather than the request, so are
+/// not passed on when forwarding.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "te",
+    "trailer",
+    "upgrade",
+    "host",
+    "content-length",
+];
+
+#[derive(Debug, Deserialize)]
+struct StreamOf {
+    stream_id: String,
+}
+
+/// Stream ownership across regions. Each stream prefix may be homed in one
+/// region, whose instance takes every write to its streams: writes that
+/// reach another region are forwarded there, so a stream's versions are
+/// only ever assigned in one place. Reads are served from the local store
+/// and say where the stream is homed; when that store is a replication
+/// secondary, also how current its copy is. Streams under no configured
+/// prefix are written where they arrive.
+pub struct Regions {
+    local: String,
+    /// Base URLs of the other regions' instances.
+    urls: HashMap<String, String>,
+    /// Stream prefixes and their regions, longest prefix first.
+    homes: Vec<(String, String)>,
+    client: reqwest::Client,
+}
+
+impl Regions {
+    /// `None` unless `region_homes` is set.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(homes_list) = &config.region_homes else {
+            return Ok(None);
+        };
+        let local = config
+            .region
+            .clone()
+            .ok_or_else(|| AppError::Internal("REGION_HOMES needs REGION".to_string()))?;
+
+        let mut urls = HashMap::new();
+        for (region, url) in pairs(config.region_urls.as_deref().unwrap_or_default(), "REGION_URLS")? {
+            urls.insert(region, url.trim_end_matches('/').to_string());
+        }
+
+        let mut homes = pairs(homes_list, "REGION_HOMES")?;
+        for (prefix, region) in &homes {
+            if *region != local && !urls.contains_key(region) {
+                return Err(AppError::Internal(format!(
+                    "REGION_HOMES homes {} in region {}, which has no entry in REGION_URLS",
+                    prefix, region
+                )));
+            }
+        }
+        homes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        info!("Region {}: {} stream prefixes homed across {} regions", local, homes.len(), urls.len() + 1);
+        Ok(Some(Self {
+            local,
+            urls,
+            homes,
+            client: reqwest::Client::new(),
+        }))
+    }
+
+    /// The region `stream_id` is homed in, when it isn't this one.
+    fn remote_home(&self, stream_id: &str) -> Option<&str> {
+        self.homes
+            .iter()
+            .find(|(prefix, _)| stream_id.starts_with(prefix.as_str()))
+            .map(|(_, region)| region.as_str())
+            .filter(|region| *region != self.local)
+    }
+
+    /// Sends a write to `region` as it arrived, credentials included, and
+    /// returns that region's response.
+    async fn forward(&self, region: &str, request: Request) -> Result<Response> {
+        let base = &self.urls[region];
+        let (parts, body) = request.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .map_err(|e| AppError::BadRequest(format!("Failed to read request body: {}", e)))?;
+        let path = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+        let method = reqwest::Method::from_bytes(parts.method.as_str().as_bytes())
+            .map_err(|e| AppError::BadRequest(format!("Unsupported method: {}", e)))?;
+
+        let mut forwarded = self
+            .client
+            .request(method, format!("{}{}", base, path))
+            .timeout(FORWARD_TIMEOUT)
+            .header(FORWARDED_FROM_HEADER.as_str(), &self.local)
+            .body(body);
+        for (name, value) in &parts.headers {
+            if !HOP_BY_HOP.contains(&name.as_str()) {
+                forwarded = forwarded.header(name.as_str(), value.as_bytes());
+            }
+        }
+        let upstream = forwarded
+            .send()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to forward the write to region {}: {}", region, e)))?;
+
+        let mut response = Response::builder()
+            .status(StatusCode::from_u16(upstream.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY));
+        for (name, value) in upstream.headers() {
+            if !HOP_BY_HOP.contains(&name.as_str()) {
+                response = response.header(name.as_str(), value.as_bytes());
+            }
+        }
+        let bytes = upstream
+            .bytes()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to read the response of region {}: {}", region, e)))?;
+        response
+            .body(Body::from(bytes))
+            .map_err(|e| AppError::Internal(format!("Invalid response from region {}: {}", region, e)))
+    }
+
+    async fn route(&self, state: &AppState, request: Request, next: Next) -> Result<Response> {
+        let reading = matches!(*request.method(), Method::GET | Method::HEAD);
+        let (stream_id, request) = stream_of(request, reading).await?;
+        let home = stream_id.as_deref().and_then(|id| self.remote_home(id)).map(str::to_string);
+
+        let Some(home) = home else {
+            return Ok(next.run(request).await);
+        };
+        if !reading {
+            if let Some(from) = request.headers().get(&FORWARDED_FROM_HEADER) {
+                // Regions that disagree on where a stream lives would pass its writes back and forth
+                return Err(AppError::Conflict(format!(
+                    "{} is homed in region {} by this instance, but region {} forwarded the write here",
+                    stream_id.unwrap_or_default(),
+                    home,
+                    from.to_str().unwrap_or_default()
+                )));
+            }
+            return self.forward(&home, request).await;
+        }
+
+        let mut response = next.run(request).await;
+        let headers = response.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&home) {
+            headers.insert(HOME_REGION_HEADER.clone(), value);
+        }
+        if let Some(synced_at) = state.replication.synced_at() {
+            let staleness = (Utc::now() - synced_at).num_milliseconds().max(0) as f64 / 1000.0;
+            let synced_at = synced_at.to_rfc3339_opts(SecondsFormat::Millis, true);
+            if let Ok(value) = HeaderValue::from_str(&synced_at) {
+                headers.insert(SYNCED_AT_HEADER.clone(), value);
+            }
+            headers.insert(STALENESS_HEADER.clone(), HeaderValue::from(staleness.ceil() as u64));
+        }
+        Ok(response)
+    }
+}
+
+/// `region=value` pairs of a comma-separated list, `setting` naming it in
+/// errors.
+fn pairs(list: &str, setting: &str) -> Result<Vec<(String, String)>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| AppError::Internal(format!("Invalid {} entry '{}'", setting, entry)))?;
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// The stream a request reads or writes, if it names exactly one: from the
+/// path under `/streams/`, or for `POST /events` from the body, which is
+/// read here and put back.
+async fn stream_of(request: Request, reading: bool) -> Result<(Option<String>, Request)> {
+    let path = request.uri().path();
+    if let Some(rest) = path.strip_prefix("/streams/") {
+        let segment = rest.split('/').next().unwrap_or_default();
+        return Ok((percent_decode(segment), request));
+    }
+    if reading || path != "/events" {
+        return Ok((None, request));
+    }
+
+    let format = match request.headers().get(header::CONTENT_TYPE) {
+        Some(value) => match BodyFormat::from_mime(value.to_str().unwrap_or_default()) {
+            Some(format) => format,
+            // Left to the handler to refuse
+            None => return Ok((None, request)),
+        },
+        None => BodyFormat::Json,
+    };
+    let (parts, body) = request.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Failed to read request body: {}", e)))?;
+    let stream_id = format.decode::<StreamOf>(&bytes).ok().map(|of| of.stream_id);
+    Ok((stream_id, Request::from_parts(parts, Body::from(bytes))))
+}
+
+/// Decodes `%XX` escapes, as stream ids holding `/` arrive in paths.
+/// `None` for invalid escapes or UTF-8.
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = segment.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Forwards writes to streams homed in other regions, and labels reads of
+/// them with their home and how current the local copy is.
+pub async fn route(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(regions) = state.regions.clone() else {
+        return next.run(request).await;
+    };
+    let mut response = match regions.route(&state, request, next).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    };
+    if !response.headers().contains_key(&REGION_HEADER) {
+        if let Ok(value) = HeaderValue::from_str(&regions.local) {
+            response.headers_mut().insert(REGION_HEADER.clone(), value);
+        }
+    }
+    response
+}
@@ -0,0 +1,328 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{stream_pattern_matches, Event, PluginKind, ProjectionState, Sink};
+use crate::{delivery, AppState};
+
+/// Finished replays kept for GET /admin/replays; older ones are forgotten.
+const MAX_FINISHED_REPLAYS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayRequest {
+    /// Replays events with `position > from_position`; from the start when unset.
+    #[serde(default)]
+    pub from_position: i64,
+    /// Last position replayed; the committed head when the replay starts if unset.
+    pub to_position: Option<i64>,
+    pub stream_prefix: Option<String>,
+    /// Only these event types; all when unset.
+    pub event_types: Option<Vec<String>>,
+    /// Only events created at or after this instant.
+    pub from_time: Option<DateTime<Utc>>,
+    /// Only events created before this instant.
+    pub to_time: Option<DateTime<Utc>>,
+    pub sink: Sink,
+}
+
+impl ReplayRequest {
+    fn matches(&self, event: &Event) -> bool {
+        self.stream_prefix.as_ref().map_or(true, |p| event.stream_id.starts_with(p.as_str()))
+            && self.event_types.as_ref().map_or(true, |t| t.contains(&event.event_type))
+            && self.from_time.map_or(true, |at| event.created_at >= at)
+            && self.to_time.map_or(true, |at| event.created_at < at)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayView {
+    pub id: Uuid,
+    pub status: ReplayStatus,
+    #[serde(flatten)]
+    pub request: ReplayRequest,
+    /// The end of the range, resolved when the replay started.
+    pub end_position: i64,
+    /// Last position read so far.
+    pub position: i64,
+    pub events_scanned: u64,
+    pub events_replayed: u64,
+    /// Matching events whose delivery failed; see GET /admin/dead-letters.
+    pub events_dead_lettered: u64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+struct Replay {
+    view: Mutex<ReplayView>,
+    cancelled: AtomicBool,
+}
+
+impl Replay {
+    fn view(&self) -> ReplayView {
+        self.view.lock().unwrap().clone()
+    }
+
+    fn finish(&self, status: ReplayStatus, error: Option<String>) {
+        let mut view = self.view.lock().unwrap();
+        view.status = status;
+        view.finished_at = Some(Utc::now());
+        view.error = error;
+    }
+}
+
+/// Replays started on this instance. Progress lives in memory only, so a
+/// restart forgets them; a replay cut short is started again from its last
+/// reported position.
+#[derive(Default)]
+pub struct Replays {
+    replays: Mutex<HashMap<Uuid, Arc<Replay>>>,
+}
+
+impl Replays {
+    fn get(&self, id: Uuid) -> Result<Arc<Replay>> {
+        self.replays
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Replay {} not found", id)))
+    }
+
+    fn insert(&self, replay: Arc<Replay>) {
+        let mut replays = self.replays.lock().unwrap();
+        let mut finished: Vec<(DateTime<Utc>, Uuid)> = replays
+            .iter()
+            .filter_map(|(id, r)| r.view.lock().unwrap().finished_at.map(|at| (at, *id)))
+            .collect();
+        if finished.len() >= MAX_FINISHED_REPLAYS {
+            finished.sort();
+            for (_, id) in &finished[..=finished.len() - MAX_FINISHED_REPLAYS] {
+                replays.remove(id);
+            }
+        }
+        let id = replay.view.lock().unwrap().id;
+        replays.insert(id, replay);
+    }
+}
+
+/// Sends one page of matching events to the sink. Returns false when the
+/// page was dead-lettered instead.
+async fn deliver(
+    state: &AppState,
+    replay_id: Uuid,
+    sink: &Sink,
+    projection: &mut Option<ProjectionState>,
+    events: Vec<Event>,
+    position: i64,
+) -> Result<bool> {
+    let Sink::Projection { name } = sink else {
+        return delivery::deliver(state, sink, &format!("replay:{}", replay_id), events).await;
+    };
+
+    // Projections see events as stored, the same as under the projector
+    let mut projection_state = projection.take().expect("projection sinks start with a state");
+    projection_state.state = state.plugins.fold(name, projection_state.state, events).await?;
+    projection_state.position = position;
+    projection_state.updated_at = Utc::now();
+    state.storage.set_projection(&projection_state).await?;
+    *projection = Some(projection_state);
+    Ok(true)
+}
+
+async fn run(state: AppState, replay: Arc<Replay>) {
+    let (id, request, mut position, end_position) = {
+        let view = replay.view.lock().unwrap();
+        (view.id, view.request.clone(), view.position, view.end_position)
+    };
+    let batch_size = state.config.replay_batch_size;
+
+    let mut projection = None;
+    let mut pattern = "*".to_string();
+    if let Sink::Projection { name } = &request.sink {
+        let Some(plugin) = state.plugins.get(name) else {
+            replay.finish(ReplayStatus::Failed, Some(format!("Projection {} was removed", name)));
+            return;
+        };
+        pattern = plugin.stream_pattern.unwrap_or_else(|| "*".to_string());
+        projection = Some(ProjectionState {
+            name: plugin.name,
+            plugin_sha256: plugin.sha256,
+            position,
+            state: Value::Null,
+            updated_at: Utc::now(),
+        });
+    }
+
+    let result: Result<()> = async {
+        while position < end_position {
+            if replay.cancelled.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            let page = state.storage.read_all(position, batch_size).await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            let scanned = page.iter().take_while(|e| e.position <= end_position).count();
+            let page_end = if scanned < page.len() { end_position } else { last.position };
+            let events: Vec<Event> = page
+                .into_iter()
+                .take(scanned)
+                .filter(|e| request.matches(e) && stream_pattern_matches(&pattern, &e.stream_id))
+                .collect();
+            let matched = events.len() as u64;
+
+            let delivered = if matched > 0 || projection.is_some() {
+                deliver(&state, id, &request.sink, &mut projection, events, page_end).await?
+            } else {
+                true
+            };
+
+            position = page_end;
+            let mut view = replay.view.lock().unwrap();
+            view.position = position;
+            view.events_scanned += scanned as u64;
+            if delivered {
+                view.events_replayed += matched;
+            } else {
+                view.events_dead_lettered += matched;
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) if replay.cancelled.load(Ordering::Relaxed) => {
+            info!("Replay {} cancelled at position {}", id, position);
+            replay.finish(ReplayStatus::Cancelled, None);
+        }
+        Ok(()) => {
+            info!("Replay {} completed at position {}", id, position);
+            replay.finish(ReplayStatus::Completed, None);
+        }
+        Err(e) => {
+            warn!("Replay {} failed at position {}: {}", id, position, e);
+            replay.finish(ReplayStatus::Failed, Some(e.to_string()));
+        }
+    }
+}
+
+/// POST /admin/replays — starts replaying a range of the global feed into
+/// a sink, for rebuilding downstream read models. Runs in the background;
+/// poll GET /admin/replays/:id for progress. Archived events are not
+/// replayed.
+pub async fn start_replay(
+    State(state): State<AppState>,
+    Json(request): Json<ReplayRequest>,
+) -> Result<(StatusCode, Json<ReplayView>)> {
+    match &request.sink {
+        Sink::Webhook { url } => {
+            reqwest::Url::parse(url).map_err(|e| AppError::BadRequest(format!("Invalid webhook url: {}", e)))?;
+        }
+        Sink::Kafka { topic } => {
+            if state.config.kafka_rest_url.is_none() {
+                return Err(AppError::BadRequest(
+                    "Kafka sinks need KAFKA_REST_URL to be configured".to_string(),
+                ));
+            }
+            let valid = !topic.is_empty()
+                && topic.len() <= 249
+                && topic.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+            if !valid {
+                return Err(AppError::BadRequest(format!("Invalid Kafka topic: {}", topic)));
+            }
+        }
+        Sink::Projection { name } => {
+            if !state.plugins.get(name).is_some_and(|p| p.kind == PluginKind::Projection) {
+                return Err(AppError::NotFound(format!("Projection {} not found", name)));
+            }
+        }
+    }
+    if request.from_position < 0 {
+        return Err(AppError::BadRequest("from_position cannot be negative".to_string()));
+    }
+
+    let committed = state.storage.committed_position().await?;
+    let end_position = request.to_position.map_or(committed, |to| to.min(committed));
+    if end_position < request.from_position {
+        return Err(AppError::BadRequest(format!(
+            "to_position {} is before from_position {}",
+            end_position, request.from_position
+        )));
+    }
+
+    let view = ReplayView {
+        id: Uuid::new_v4(),
+        status: ReplayStatus::Running,
+        position: request.from_position,
+        request,
+        end_position,
+        events_scanned: 0,
+        events_replayed: 0,
+        events_dead_lettered: 0,
+        started_at: Utc::now(),
+        finished_at: None,
+        error: None,
+    };
+    let replay = Arc::new(Replay {
+        view: Mutex::new(view.clone()),
+        cancelled: AtomicBool::new(false),
+    });
+    state.replays.insert(replay.clone());
+    info!(
+        "Replay {} started: positions {}..={} into {:?}",
+        view.id, view.position, view.end_position, view.request.sink
+    );
+    tokio::spawn(run(state, replay));
+
+    Ok((StatusCode::ACCEPTED, Json(view)))
+}
+
+/// GET /admin/replays — running and recently finished replays.
+pub async fn list_replays(State(state): State<AppState>) -> Json<Vec<ReplayView>> {
+    let mut views: Vec<ReplayView> = state.replays.replays.lock().unwrap().values().map(|r| r.view()).collect();
+    views.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+    Json(views)
+}
+
+/// GET /admin/replays/:id
+pub async fn get_replay(State(state): State<AppState>, Path(id): Path<Uuid>) -> Result<Json<ReplayView>> {
+    Ok(Json(state.replays.get(id)?.view()))
+}
+
+/// POST /admin/replays/:id/cancel — stops the replay after the batch in
+/// flight. Events already delivered stay delivered.
+pub async fn cancel_replay(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<(StatusCode, Json<ReplayView>)> {
+    let replay = state.replays.get(id)?;
+    if replay.view().status != ReplayStatus::Running {
+        return Err(AppError::Conflict(format!("Replay {} is not running", id)));
+    }
+    replay.cancelled.store(true, Ordering::Relaxed);
+    info!("Replay {} cancelling", id);
+    Ok((StatusCode::ACCEPTED, Json(replay.view())))
+}
@@ -0,0 +1,313 @@
+use axum::extract::{Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+
+use crate::auth::API_KEY_HEADER;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::jobs::{self, Job};
+use crate::metrics::Metrics;
+use crate::models::Event;
+use crate::storage::EventStorage;
+use crate::AppState;
+
+/// How long the secondary may take to answer one request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub position: i64,
+    /// When this secondary last held every event its primary had committed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+/// Events with positions in `(after, through]`, in position order. A batch
+/// may hold fewer events than the range suggests: positions left unused by
+/// rolled back appends are skipped over. With nothing new to ship, the
+/// primary sends an empty batch with `through` equal to `after`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicatedBatch {
+    pub after: i64,
+    pub through: i64,
+    /// Set on a batch that leaves the secondary holding every event the
+    /// primary had committed at this time, by the primary's clock.
+    #[serde(default)]
+    pub synced_at: Option<DateTime<Utc>>,
+    pub events: Vec<Event>,
+}
+
+/// How current a secondary is, as last reported by its primary. Kept in
+/// memory: unknown after a restart until the next batch arrives.
+#[derive(Debug, Default)]
+pub struct ReplicationStatus {
+    synced_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl ReplicationStatus {
+    pub fn synced_at(&self) -> Option<DateTime<Utc>> {
+        *self.synced_at.lock().unwrap()
+    }
+
+    fn record(&self, synced_at: DateTime<Utc>) {
+        let mut current = self.synced_at.lock().unwrap();
+        *current = Some(current.map_or(synced_at, |current| current.max(synced_at)));
+    }
+}
+
+/// Ships committed events to a secondary event store in global position
+/// order. The secondary keeps the checkpoint, moving it in the transaction
+/// that stores each batch, so a batch is applied exactly once whichever
+/// side restarts. Events keep their ids, versions and positions, so
+/// readers can fail over with their cursors.
+///
+/// Only the event log is replicated: stream metadata, snapshots, subject
+/// keys and configuration stay with each store. Deletions and truncations
+/// on the primary are not replayed, and archived events older than the
+/// secondary's checkpoint are never shipped; seed a secondary that joins
+/// late from a backup. Offloaded bodies keep pointing at the primary's
+/// blob bucket.
+pub struct Replicator {
+    client: reqwest::Client,
+    target: String,
+    api_key: String,
+    batch_size: i64,
+    storage: Arc<dyn EventStorage>,
+    metrics: Metrics,
+    /// The secondary's checkpoint as last seen; asked for again when a
+    /// batch doesn't start there, as after a response lost in transit.
+    checkpoint: Mutex<Option<i64>>,
+}
+
+impl Replicator {
+    /// `None` unless `replication_target_url` is set.
+    pub fn from_config(config: &Config, storage: Arc<dyn EventStorage>, metrics: Metrics) -> Option<Self> {
+        let target = config.replication_target_url.as_ref()?;
+        info!("Replicating events to {}", target);
+        Some(Self {
+            client: reqwest::Client::new(),
+            target: target.trim_end_matches('/').to_string(),
+            api_key: config.replication_api_key.clone().unwrap_or_default(),
+            batch_size: config.replication_batch_size,
+            storage,
+            metrics,
+            checkpoint: Mutex::new(None),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.target, path))
+            .header(API_KEY_HEADER.as_str(), &self.api_key)
+            .timeout(REQUEST_TIMEOUT)
+    }
+
+    async fn checkpoint(&self) -> Result<i64> {
+        if let Some(position) = *self.checkpoint.lock().unwrap() {
+            return Ok(position);
+        }
+        let checkpoint: Checkpoint = self
+            .request(reqwest::Method::GET, "/admin/replication/checkpoint")
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| AppError::Internal(format!("Failed to read the replication checkpoint: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Unreadable replication checkpoint: {}", e)))?;
+        *self.checkpoint.lock().unwrap() = Some(checkpoint.position);
+        Ok(checkpoint.position)
+    }
+
+    async fn send(&self, batch: &ReplicatedBatch) -> Result<()> {
+        let response = self
+            .request(reqwest::Method::POST, "/admin/replication/events")
+            .json(batch)
+            .send()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to ship events: {}", e)))?;
+        if response.status() == reqwest::StatusCode::CONFLICT {
+            return Err(AppError::Conflict(format!("The secondary is not at position {}", batch.after)));
+        }
+        response
+            .error_for_status()
+            .map_err(|e| AppError::Internal(format!("The secondary rejected events: {}", e)))?;
+        Ok(())
+    }
+
+    /// Ships batches until the secondary holds every event committed when
+    /// the run began, then tells it so; returns how many were shipped.
+    async fn ship(&self) -> Result<u64> {
+        let as_of = Utc::now();
+        let committed = self.storage.committed_position().await?;
+        let mut shipped = 0;
+        loop {
+            let after = self.checkpoint().await?;
+            if after > committed {
+                return Ok(shipped);
+            }
+
+            let mut events = if after < committed {
+                self.storage.read_all(after, self.batch_size).await?
+            } else {
+                Vec::new()
+            };
+            let full = events.len() as i64 == self.batch_size;
+            events.retain(|e| e.position <= committed);
+            // A short batch reaches the committed position; a full one ends at its last event
+            let through = match events.last() {
+                Some(last) if full => last.position,
+                _ => committed,
+            };
+            let batch = ReplicatedBatch {
+                after,
+                through,
+                synced_at: (through == committed).then_some(as_of),
+                events,
+            };
+
+            match self.send(&batch).await {
+                Ok(()) => *self.checkpoint.lock().unwrap() = Some(through),
+                Err(AppError::Conflict(_)) => {
+                    *self.checkpoint.lock().unwrap() = None;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+            shipped += batch.events.len() as u64;
+            self.metrics.replication_events_shipped.inc_by(batch.events.len() as u64);
+            if through == committed {
+                return Ok(shipped);
+            }
+        }
+    }
+
+    /// Sets the lag gauges from the last checkpoint seen.
+    async fn record_lag(&self) -> Result<()> {
+        let Some(checkpoint) = *self.checkpoint.lock().unwrap() else {
+            return Ok(());
+        };
+        let committed = self.storage.committed_position().await?;
+        let oldest = self.storage.read_all(checkpoint, 1).await?;
+        let age = oldest
+            .first()
+            .filter(|e| e.position <= committed)
+            .map_or(0.0, |e| (Utc::now() - e.created_at).num_milliseconds().max(0) as f64 / 1000.0);
+
+        self.metrics.replication_lag_events.set((committed - checkpoint).max(0));
+        self.metrics.replication_lag_seconds.set(age);
+        Ok(())
+    }
+}
+
+// Background task: Ship newly committed events to the secondary
+pub async fn replicate(job: Arc<Job>, replicator: Arc<Replicator>) {
+    jobs::run_periodically("replicator", job, || async {
+        let shipped = replicator.ship().await;
+        replicator.record_lag().await?;
+        Ok(format!("Shipped {} events", shipped?))
+    })
+    .await
+}
+
+fn require_secondary(state: &AppState) -> Result<()> {
+    if !state.config.replication_secondary {
+        return Err(AppError::BadRequest(
+            "This store is not a replication secondary; set REPLICATION_SECONDARY".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// GET /admin/replication/checkpoint — the position up to which this
+/// secondary holds its primary's events.
+pub async fn get_checkpoint(State(state): State<AppState>) -> Result<Json<Checkpoint>> {
+    require_secondary(&state)?;
+    let position = state.storage.replication_checkpoint().await?;
+    Ok(Json(Checkpoint {
+        position,
+        synced_at: state.replication.synced_at(),
+    }))
+}
+
+/// POST /admin/replication/events — stores a batch shipped by the primary.
+/// 409 when the batch doesn't start at this secondary's checkpoint.
+/// Empty batches that move nothing still report how current it is.
+pub async fn apply_batch(
+    State(state): State<AppState>,
+    Json(batch): Json<ReplicatedBatch>,
+) -> Result<Json<Checkpoint>> {
+    require_secondary(&state)?;
+    if batch.through < batch.after {
+        return Err(AppError::BadRequest("through cannot be less than after".to_string()));
+    }
+    let mut last = batch.after;
+    for event in &batch.events {
+        if event.position <= last || event.position > batch.through {
+            return Err(AppError::BadRequest(format!(
+                "Event {} at position {} is out of order or outside ({}, {}]",
+                event.id, event.position, batch.after, batch.through
+            )));
+        }
+        last = event.position;
+    }
+
+    if batch.through == batch.after {
+        let checkpoint = state.storage.replication_checkpoint().await?;
+        if checkpoint != batch.after {
+            return Err(AppError::Conflict(format!(
+                "Replication checkpoint is at {}, not {}",
+                checkpoint, batch.after
+            )));
+        }
+    } else {
+        state
+            .storage
+            .apply_replicated(batch.after, batch.through, &batch.events)
+            .await?;
+    }
+    if let Some(synced_at) = batch.synced_at {
+        state.replication.record(synced_at);
+    }
+    state.metrics.replication_events_applied.inc_by(batch.events.len() as u64);
+    // Replicated events bypass the append path, so caches and long polls learn of them here
+    for event in batch.events {
+        state.bus.publish(event).await;
+    }
+
+    Ok(Json(Checkpoint {
+        position: batch.through,
+        synced_at: state.replication.synced_at(),
+    }))
+}
+
+/// Whether a request would change the event log, which on a secondary
+/// only its primary may do.
+pub fn writes_events(method: &Method, path: &str) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return false;
+    }
+    path == "/events"
+        || path.starts_with("/streams/")
+        || path == "/admin/restore"
+        || path == "/admin/bulk-load"
+        || (path.starts_with("/admin/event-types/") && path.ends_with("/migrate"))
+}
+
+/// Refuses writes to a secondary's event log, which would take positions
+/// the primary has yet to ship.
+pub async fn read_only(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if state.config.replication_secondary && writes_events(request.method(), request.uri().path()) {
+        return AppError::Forbidden(
+            "This store is a replication secondary; write to its primary".to_string(),
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
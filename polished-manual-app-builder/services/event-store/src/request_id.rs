@@ -0,0 +1,41 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+/// Longest caller-supplied id kept; longer ones are replaced.
+const MAX_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The id of the request being handled, as returned in problem responses;
+/// None outside a request, e.g. in background jobs.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Gives each request an id: the caller's `X-Request-Id` when it sends a
+/// usable one (a proxy or another service may already have logged it),
+/// otherwise a new UUID. The id is recorded on the request span, so every
+/// log line written while handling the request carries it, is readable
+/// through `current`, and is echoed in the response's `X-Request-Id`.
+pub async fn assign(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_LENGTH && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+    tracing::Span::current().record("request_id", id.as_str());
+
+    let header = HeaderValue::from_str(&id).expect("request ids are visible ASCII");
+    let mut response = REQUEST_ID.scope(id, next.run(request)).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, header);
+    response
+}
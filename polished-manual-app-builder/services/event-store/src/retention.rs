@@ -0,0 +1,151 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::{AppError, Result};
+use crate::models::{is_valid_stream_pattern, RetentionRule};
+use crate::storage::Purge;
+use crate::AppState;
+
+/// One purge per rule that limits retention, each excluding the events that
+/// more specific rules govern, so a `keep forever` rule shields its streams
+/// from any broader rule. The scavenger carries them out.
+pub fn plan(rules: &[RetentionRule], now: DateTime<Utc>) -> Vec<Purge> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let days = rule.keep_days?;
+            Some(Purge {
+                rule: rule.clone(),
+                except: rules
+                    .iter()
+                    .filter(|other| other.specificity() > rule.specificity())
+                    .cloned()
+                    .collect(),
+                before: now - chrono::Duration::days(days),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetentionRuleRequest {
+    pub stream_pattern: String,
+    pub event_type: Option<String>,
+    /// Omit or null to keep matching events forever.
+    pub keep_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreamPurge {
+    pub stream_id: String,
+    pub events: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RulePreview {
+    pub rule: RetentionRule,
+    pub deletes_before: DateTime<Utc>,
+    pub events: u64,
+    pub streams: Vec<StreamPurge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RetentionPreview {
+    pub events: u64,
+    pub rules: Vec<RulePreview>,
+}
+
+/// GET /admin/retention/rules
+pub async fn list_rules(State(state): State<AppState>) -> Result<Json<Vec<RetentionRule>>> {
+    Ok(Json(state.storage.retention_rules().await?))
+}
+
+/// PUT /admin/retention/rules/:name
+pub async fn set_rule(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(request): Json<RetentionRuleRequest>,
+) -> Result<Json<RetentionRule>> {
+    if !is_valid_stream_pattern(&request.stream_pattern) {
+        return Err(AppError::BadRequest(
+            "stream_pattern must be a stream id or a prefix ending in a single '*'".to_string(),
+        ));
+    }
+    if request.keep_days.is_some_and(|days| days < 1) {
+        return Err(AppError::BadRequest("keep_days must be at least 1".to_string()));
+    }
+
+    let existing = state.storage.retention_rules().await?;
+    if let Some(other) = existing.iter().find(|r| {
+        r.name != name && r.stream_pattern == request.stream_pattern && r.event_type == request.event_type
+    }) {
+        return Err(AppError::Conflict(format!(
+            "Retention rule {} already covers {}",
+            other.name, request.stream_pattern
+        )));
+    }
+
+    let rule = RetentionRule {
+        name,
+        stream_pattern: request.stream_pattern,
+        event_type: request.event_type,
+        keep_days: request.keep_days,
+        updated_at: Utc::now(),
+    };
+    state.storage.set_retention_rule(&rule).await?;
+    info!(
+        "Retention rule {} set: {} keep {}",
+        rule.name,
+        rule.stream_pattern,
+        rule.keep_days.map_or("forever".to_string(), |d| format!("{} days", d))
+    );
+
+    Ok(Json(rule))
+}
+
+/// DELETE /admin/retention/rules/:name
+pub async fn remove_rule(Path(name): Path<String>, State(state): State<AppState>) -> Result<Response> {
+    if !state.storage.remove_retention_rule(&name).await? {
+        return Err(AppError::NotFound(format!("Retention rule {} not found", name)));
+    }
+
+    info!("Retention rule {} removed", name);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// GET /admin/retention/preview — what the scavenger would delete if it ran
+/// now, per rule and stream. Deletes nothing.
+pub async fn preview(State(state): State<AppState>) -> Result<Json<RetentionPreview>> {
+    let rules = state.storage.retention_rules().await?;
+    let mut previews = Vec::new();
+    let mut total = 0;
+
+    for purge in plan(&rules, Utc::now()) {
+        let streams: Vec<StreamPurge> = state
+            .storage
+            .purgeable_events(&purge)
+            .await?
+            .into_iter()
+            .map(|(stream_id, events)| StreamPurge { stream_id, events })
+            .collect();
+        let events = streams.iter().map(|s| s.events).sum();
+        total += events;
+        previews.push(RulePreview {
+            rule: purge.rule,
+            deletes_before: purge.before,
+            events,
+            streams,
+        });
+    }
+
+    Ok(Json(RetentionPreview {
+        events: total,
+        rules: previews,
+    }))
+}
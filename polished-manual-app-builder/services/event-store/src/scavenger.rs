@@ -0,0 +1,167 @@
+use axum::{extract::State, response::Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::metrics::Metrics;
+use crate::retention;
+use crate::storage::EventStorage;
+use crate::AppState;
+
+/// What one scavenger pass deleted.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScavengeReport {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Events below a stream's `truncate_before`.
+    pub truncated_events: u64,
+    /// Events past their retention rule.
+    pub expired_events: u64,
+    /// Events past the `ttl_seconds` they were appended with. Deleted even
+    /// in dry-run mode, which only covers retention rules.
+    pub ttl_expired_events: u64,
+    pub batches: u64,
+    /// Set when retention ran in dry-run mode: what it would have deleted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expired_events_dry_run: Option<u64>,
+}
+
+/// The most recent pass, for the admin endpoint.
+#[derive(Debug, Default)]
+pub struct Scavenger {
+    last: Mutex<Option<ScavengeReport>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScavengeSettings {
+    pub batch_size: i64,
+    pub batch_pause: Duration,
+    pub retention_dry_run: bool,
+}
+
+impl ScavengeSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            batch_size: config.scavenge_batch_size,
+            batch_pause: Duration::from_millis(config.scavenge_batch_pause_ms),
+            retention_dry_run: config.retention_dry_run,
+        }
+    }
+}
+
+/// Deletes in batches of `batch_size` until `delete` returns a short one,
+/// pausing between batches so no transaction holds locks for long and
+/// autovacuum can keep up with the dead rows.
+async fn in_batches<F, Fut>(settings: &ScavengeSettings, report: &mut ScavengeReport, mut delete: F) -> Result<u64>
+where
+    F: FnMut(i64) -> Fut,
+    Fut: std::future::Future<Output = Result<u64>>,
+{
+    let mut deleted = 0;
+    loop {
+        let batch = delete(settings.batch_size).await?;
+        report.batches += 1;
+        deleted += batch;
+        if (batch as i64) < settings.batch_size {
+            return Ok(deleted);
+        }
+        tokio::time::sleep(settings.batch_pause).await;
+    }
+}
+
+/// One pass: physically deletes truncated events, then events past their
+/// TTL, then events past their retention rule.
+pub async fn scavenge(
+    storage: &dyn EventStorage,
+    metrics: &Metrics,
+    settings: ScavengeSettings,
+) -> Result<ScavengeReport> {
+    let started = Instant::now();
+    let mut report = ScavengeReport {
+        started_at: Utc::now(),
+        duration_ms: 0,
+        truncated_events: 0,
+        expired_events: 0,
+        ttl_expired_events: 0,
+        batches: 0,
+        expired_events_dry_run: None,
+    };
+
+    report.truncated_events = in_batches(&settings, &mut report, |limit| storage.scavenge_truncated(limit)).await?;
+    metrics
+        .events_scavenged
+        .with_label_values(&["truncated"])
+        .inc_by(report.truncated_events);
+
+    let now = Utc::now();
+    report.ttl_expired_events =
+        in_batches(&settings, &mut report, |limit| storage.scavenge_expired(now, limit)).await?;
+    metrics
+        .events_scavenged
+        .with_label_values(&["ttl"])
+        .inc_by(report.ttl_expired_events);
+
+    let rules = storage.retention_rules().await?;
+    for purge in retention::plan(&rules, now) {
+        if settings.retention_dry_run {
+            let events: u64 = storage.purgeable_events(&purge).await?.iter().map(|(_, n)| n).sum();
+            if events > 0 {
+                info!(
+                    "Retention rule {} would delete {} events created before {}",
+                    purge.rule.name, events, purge.before
+                );
+            }
+            *report.expired_events_dry_run.get_or_insert(0) += events;
+            continue;
+        }
+
+        let events = in_batches(&settings, &mut report, |limit| storage.purge_events(&purge, limit)).await?;
+        if events > 0 {
+            info!(
+                "Retention rule {} deleted {} events created before {}",
+                purge.rule.name, events, purge.before
+            );
+        }
+        report.expired_events += events;
+    }
+    metrics
+        .events_scavenged
+        .with_label_values(&["expired"])
+        .inc_by(report.expired_events);
+
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    Ok(report)
+}
+
+impl Scavenger {
+    /// Runs a pass and keeps its report. Returns the job's summary line.
+    pub async fn run(&self, storage: &dyn EventStorage, metrics: &Metrics, settings: ScavengeSettings) -> Result<String> {
+        let report = scavenge(storage, metrics, settings).await?;
+        let summary = format!(
+            "Scavenged {} truncated, {} TTL-expired and {} expired events in {} batches",
+            report.truncated_events, report.ttl_expired_events, report.expired_events, report.batches
+        );
+        *self.last.lock().unwrap() = Some(report);
+        Ok(summary)
+    }
+}
+
+/// GET /admin/scavenger — the last pass and totals since startup. Run a
+/// pass now with `POST /admin/jobs/scavenger/run`.
+pub async fn status(State(state): State<AppState>) -> Json<Value> {
+    let last = state.scavenger.last.lock().unwrap().clone();
+    let scavenged = &state.metrics.events_scavenged;
+    Json(json!({
+        "last_run": last,
+        "total": {
+            "truncated_events": scavenged.with_label_values(&["truncated"]).get(),
+            "expired_events": scavenged.with_label_values(&["expired"]).get(),
+            "ttl_expired_events": scavenged.with_label_values(&["ttl"]).get(),
+        }
+    }))
+}
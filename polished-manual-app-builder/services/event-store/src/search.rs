@@ -0,0 +1,179 @@
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::Tenant;
+use crate::codec::{Accept, Encoded};
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::jobs::{self, Job};
+use crate::models::Event;
+use crate::storage::{EventStorage, SearchDocument, SearchHit};
+use crate::{blobs, encryption, visible_from, AppState, MAX_PAGE_SIZE};
+
+/// Longest query accepted by `GET /search`.
+const MAX_QUERY_LEN: usize = 256;
+/// How often index entries of events no longer stored are dropped.
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(86400);
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub before: Option<i64>, // `next` of the previous page; the newest matches when unset
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    /// Passed back as `before` for the following page; unset on the last.
+    pub next: Option<i64>,
+}
+
+/// JSON pointers of the fields `search_fields` names, e.g.
+/// `customer.email` becomes `/customer/email`. Empty while search is off.
+pub fn fields(config: &Config) -> Vec<String> {
+    config
+        .search_fields
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(|field| format!("/{}", field.replace('.', "/")))
+        .collect()
+}
+
+/// The text `fields` hold in an event's JSON body. Strings and numbers are
+/// indexed, arrays of them item by item. Encrypted fields are objects, so
+/// their ciphertext never reaches the index and shredding a subject's key
+/// leaves nothing of theirs searchable.
+fn content(event: &Event, fields: &[String]) -> String {
+    fn collect(value: &Value, parts: &mut Vec<String>) {
+        match value {
+            Value::String(text) => parts.push(text.clone()),
+            Value::Number(number) => parts.push(number.to_string()),
+            Value::Array(items) => items.iter().for_each(|item| collect(item, parts)),
+            _ => {}
+        }
+    }
+
+    let mut parts = Vec::new();
+    for field in fields {
+        if let Some(value) = event.data.pointer(field) {
+            collect(value, &mut parts);
+        }
+    }
+    parts.join(" ")
+}
+
+/// Indexes committed events from where the index left off, a batch at a
+/// time until caught up; returns how many were read.
+async fn index_once(storage: &dyn EventStorage, fields: &[String], batch_size: i64) -> Result<u64> {
+    let committed = storage.committed_position().await?;
+    let mut indexed = 0;
+    loop {
+        let after = storage.search_position().await?;
+        let mut events = storage.read_all(after, batch_size).await?;
+        let full = events.len() as i64 == batch_size;
+        events.retain(|e| e.position <= committed);
+        let Some(through) = events.last().map(|e| e.position) else {
+            return Ok(indexed);
+        };
+
+        let documents: Vec<SearchDocument> = events
+            .iter()
+            .map(|event| SearchDocument {
+                position: event.position,
+                stream_id: event.stream_id.clone(),
+                version: event.version,
+                content: content(event, fields),
+            })
+            .filter(|document| !document.content.is_empty())
+            .collect();
+        storage.index_search_documents(&documents, through).await?;
+        indexed += events.len() as u64;
+
+        if !full || through >= committed {
+            return Ok(indexed);
+        }
+    }
+}
+
+// Background task: Feed newly committed events to the full-text index
+pub async fn index_periodically(job: Arc<Job>, storage: Arc<dyn EventStorage>, config: Config) {
+    let fields = fields(&config);
+    jobs::run_periodically("search_indexer", job, || async {
+        let indexed = index_once(storage.as_ref(), &fields, config.search_batch_size).await?;
+        Ok(format!("Indexed {} events", indexed))
+    })
+    .await
+}
+
+// Background task: Drop the index entries of events no longer stored
+pub async fn prune_periodically(job: Arc<Job>, storage: Arc<dyn EventStorage>) {
+    jobs::run_periodically("search_pruner", job, || async {
+        let pruned = storage.prune_search_index().await?;
+        Ok(format!("Pruned {} index entries", pruned))
+    })
+    .await
+}
+
+/// GET /search?q=ORD-1234 — events whose indexed fields (`search_fields`)
+/// match, newest first, with the matches highlighted. Searches the
+/// caller's tenant, or every stream for callers without one. Events are
+/// indexed shortly after they commit; changing `search_fields` applies to
+/// events indexed afterwards.
+pub async fn search(
+    Query(query): Query<SearchQuery>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Accept(format): Accept,
+    headers: HeaderMap,
+) -> Result<Encoded<SearchResults>> {
+    if state.config.search_fields.is_none() {
+        return Err(AppError::BadRequest("Full-text search is disabled; set SEARCH_FIELDS".to_string()));
+    }
+    let q = query.q.trim();
+    if q.is_empty() || q.len() > MAX_QUERY_LEN {
+        return Err(AppError::BadRequest(format!(
+            "q must be between 1 and {} bytes",
+            MAX_QUERY_LEN
+        )));
+    }
+    let limit = query.limit.unwrap_or(20).clamp(1, MAX_PAGE_SIZE);
+
+    let mut hits = state
+        .storage
+        .search_events(tenant.0.as_deref(), q, query.before.unwrap_or(i64::MAX), limit)
+        .await?;
+    // Taken before hidden events are dropped, so the next page starts below them
+    let next = match hits.last() {
+        Some(hit) if hits.len() as i64 == limit => Some(hit.event.position),
+        _ => None,
+    };
+
+    let mut visible = HashMap::new();
+    for hit in &hits {
+        if !visible.contains_key(&hit.event.stream_id) {
+            let from = visible_from(&state, &hit.event.stream_id).await?;
+            visible.insert(hit.event.stream_id.clone(), from);
+        }
+    }
+    hits.retain(|hit| hit.event.version >= visible[&hit.event.stream_id]);
+
+    let mut events: Vec<Event> = hits.iter().map(|hit| hit.event.clone()).collect();
+    if !blobs::wants_links(&headers) {
+        blobs::resolve_events(&state, &mut events).await?;
+    }
+    encryption::decrypt_events(&state, &mut events).await?;
+    for (hit, event) in hits.iter_mut().zip(events) {
+        hit.event = event;
+    }
+
+    Ok(Encoded(format, SearchResults { hits, next }))
+}
@@ -0,0 +1,46 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::AppState;
+
+/// Awaits a storage operation on `stream_id`, logging and counting it when
+/// it takes longer than `slow_operation_threshold_ms`. Failed operations
+/// count too; a timeout is often the slowest of all.
+pub async fn timed<T>(state: &AppState, operation: &'static str, stream_id: &str, future: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let output = future.await;
+
+    let threshold = state.config.slow_operation_threshold_ms;
+    let elapsed = started.elapsed();
+    if threshold > 0 && elapsed >= Duration::from_millis(threshold) {
+        state.metrics.slow_operations.with_label_values(&[operation]).inc();
+        warn!(
+            "Slow {} on stream {}: {}ms (threshold {}ms)",
+            operation,
+            stream_id,
+            elapsed.as_millis(),
+            threshold
+        );
+    }
+    output
+}
+
+/// Logs and counts an event being appended with a payload larger than
+/// `large_payload_threshold_kb`. The append itself goes ahead; hard limits
+/// are the quotas' job.
+pub fn check_payload(state: &AppState, stream_id: &str, event_type: &str, bytes: usize) {
+    let threshold = state.config.large_payload_threshold_kb;
+    if threshold == 0 || (bytes as u64) <= threshold * 1024 {
+        return;
+    }
+
+    state.metrics.large_payloads.inc();
+    warn!(
+        "Large {} event appended to stream {}: {} KB (threshold {} KB)",
+        event_type,
+        stream_id,
+        bytes / 1024,
+        threshold
+    );
+}
@@ -0,0 +1,385 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::{Read, Write};
+use tracing::{error, info};
+
+use crate::auth::Tenant;
+use crate::codec::{Accept, Encoded};
+use crate::config::Config;
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::models::{Event, Snapshot, SnapshotCompression, SnapshotQuery};
+use crate::storage::{EventStorage, ReadDirection};
+use crate::{blobs, cold_storage, encryption, read_cache, reducers, AppState, STREAM_VERSION_HEADER};
+
+/// Events read per page while hydrating a stream's state.
+const HYDRATE_PAGE_SIZE: i64 = 1_000;
+
+/// Which of a stream's snapshots survive once a new one is stored.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotRetention {
+    pub keep_last: usize,
+    /// Beyond the newest `keep_last`, keep the newest snapshot in each run
+    /// of this many versions, thinning history for temporal queries.
+    pub keep_every: Option<i64>,
+}
+
+impl SnapshotRetention {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            keep_last: config.snapshot_keep_last,
+            keep_every: config.snapshot_keep_every_versions,
+        }
+    }
+
+    /// The versions to delete, given all of a stream's, oldest first.
+    fn expired(&self, versions: &[i64]) -> Vec<i64> {
+        let older = &versions[..versions.len().saturating_sub(self.keep_last)];
+        match self.keep_every {
+            None => older.to_vec(),
+            Some(every) => {
+                let run = |version: i64| (version - 1).div_euclid(every);
+                older
+                    .windows(2)
+                    .filter(|pair| run(pair[0]) == run(pair[1]))
+                    .map(|pair| pair[0])
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Applies `retention` to a stream's snapshots, returning how many were deleted.
+pub async fn prune(storage: &dyn EventStorage, stream_id: &str, retention: SnapshotRetention) -> Result<u64> {
+    let expired = retention.expired(&storage.snapshot_versions(stream_id).await?);
+    if expired.is_empty() {
+        return Ok(0);
+    }
+
+    let deleted = storage.delete_snapshots(stream_id, &expired).await?;
+    info!("Pruned {} snapshots of {}", deleted, stream_id);
+    Ok(deleted)
+}
+
+/// Compresses new snapshots with the configured algorithm, and decompresses
+/// stored ones with whichever algorithm they were written with.
+#[derive(Debug)]
+pub struct SnapshotCodec {
+    compression: SnapshotCompression,
+    zstd_level: i32,
+    dictionary: Option<Vec<u8>>,
+    dictionary_max_bytes: usize,
+    max_bytes: usize,
+}
+
+fn codec_error(failure: &str, e: impl std::fmt::Display) -> AppError {
+    error!("Snapshot {}: {}", failure.to_lowercase(), e);
+    AppError::Internal(failure.to_string())
+}
+
+impl SnapshotCodec {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let dictionary = config
+            .snapshot_zstd_dictionary_path
+            .as_ref()
+            .map(|path| {
+                std::fs::read(path).map_err(|e| {
+                    AppError::Internal(format!("Failed to read zstd dictionary {}: {}", path, e))
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            compression: config.snapshot_compression,
+            zstd_level: config.snapshot_zstd_level,
+            dictionary,
+            dictionary_max_bytes: config.snapshot_zstd_dictionary_max_bytes,
+            max_bytes: config.snapshot_max_bytes,
+        })
+    }
+
+    /// Serializes and compresses `data`, returning the algorithm used. Small
+    /// snapshots compress poorly on their own, so with zstd they use the
+    /// dictionary when one is configured.
+    pub fn encode(&self, data: &serde_json::Value) -> Result<(SnapshotCompression, Vec<u8>)> {
+        let raw = serde_json::to_vec(data)?;
+        if raw.len() > self.max_bytes {
+            return Err(AppError::BadRequest(format!(
+                "Snapshot is {} bytes, over the {} byte limit",
+                raw.len(),
+                self.max_bytes
+            )));
+        }
+
+        let compressed = match self.compression {
+            SnapshotCompression::Lz4 => (SnapshotCompression::Lz4, lz4_flex::compress(&raw)),
+            SnapshotCompression::Zstd | SnapshotCompression::ZstdDict => match &self.dictionary {
+                Some(dictionary) if raw.len() <= self.dictionary_max_bytes => {
+                    let data = zstd::bulk::Compressor::with_dictionary(self.zstd_level, dictionary)
+                        .and_then(|mut compressor| compressor.compress(&raw))
+                        .map_err(|e| codec_error("Compression failed", e))?;
+                    (SnapshotCompression::ZstdDict, data)
+                }
+                _ => {
+                    let data = zstd::bulk::compress(&raw, self.zstd_level).map_err(|e| codec_error("Compression failed", e))?;
+                    (SnapshotCompression::Zstd, data)
+                }
+            },
+            SnapshotCompression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                let data = encoder
+                    .write_all(&raw)
+                    .and_then(|_| encoder.finish())
+                    .map_err(|e| codec_error("Compression failed", e))?;
+                (SnapshotCompression::Gzip, data)
+            }
+            SnapshotCompression::None => (SnapshotCompression::None, raw),
+        };
+        Ok(compressed)
+    }
+
+    /// The state a snapshot holds, decompressed.
+    pub fn decode(&self, snapshot: &Snapshot) -> Result<serde_json::Value> {
+        let raw = match snapshot.compression {
+            SnapshotCompression::Lz4 => {
+                lz4_flex::decompress(&snapshot.data, self.max_bytes).map_err(|e| codec_error("Decompression failed", e))?
+            }
+            SnapshotCompression::Zstd => {
+                zstd::bulk::decompress(&snapshot.data, self.max_bytes).map_err(|e| codec_error("Decompression failed", e))?
+            }
+            SnapshotCompression::ZstdDict => {
+                let dictionary = self.dictionary.as_ref().ok_or_else(|| {
+                    codec_error("Decompression failed", "it needs a zstd dictionary, and none is configured")
+                })?;
+                zstd::bulk::Decompressor::with_dictionary(dictionary)
+                    .and_then(|mut decompressor| decompressor.decompress(&snapshot.data, self.max_bytes))
+                    .map_err(|e| codec_error("Decompression failed", e))?
+            }
+            SnapshotCompression::Gzip => {
+                let mut raw = Vec::new();
+                flate2::read::GzDecoder::new(snapshot.data.as_slice())
+                    .take(self.max_bytes as u64 + 1)
+                    .read_to_end(&mut raw)
+                    .map_err(|e| codec_error("Decompression failed", e))?;
+                if raw.len() > self.max_bytes {
+                    return Err(codec_error("Decompression failed", "it exceeds SNAPSHOT_MAX_BYTES"));
+                }
+                raw
+            }
+            SnapshotCompression::None => snapshot.data.clone(),
+        };
+
+        serde_json::from_slice(&raw).map_err(|e| codec_error("Deserialization failed", e))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotView {
+    pub stream_id: String,
+    pub version: i64,
+    pub created_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+impl SnapshotView {
+    pub fn new(snapshot: Snapshot, codec: &SnapshotCodec) -> Result<Self> {
+        Ok(Self {
+            data: codec.decode(&snapshot)?,
+            stream_id: snapshot.stream_id,
+            version: snapshot.version,
+            created_at: snapshot.created_at,
+        })
+    }
+}
+
+/// The version a query pins the stream at: `version`, or the last version
+/// created by `as_of`, whichever is earlier. `None` means the head.
+async fn pinned_version(state: &AppState, stream_id: &str, query: &SnapshotQuery) -> Result<Option<i64>> {
+    let as_of = match query.as_of {
+        Some(at) => Some(state.storage.version_at(stream_id, at).await?),
+        None => None,
+    };
+    Ok(match (query.version, as_of) {
+        (Some(version), Some(as_of)) => Some(version.min(as_of)),
+        (version, as_of) => version.or(as_of),
+    })
+}
+
+/// GET /snapshots/:stream_id?version=&as_of= — the newest snapshot at or
+/// before `version` or the instant `as_of` (the latest when both are
+/// omitted), so a client can rebuild the stream's state as of that point by
+/// replaying only the events after it. With `If-Match: <version>`, 304
+/// while the stream's head is still at that version.
+pub async fn get_snapshot(
+    Path(stream_id): Path<String>,
+    Query(query): Query<SnapshotQuery>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    headers: HeaderMap,
+) -> Result<Response> {
+    tenant.authorize(&stream_id)?;
+    let start_time = std::time::Instant::now();
+    state.metrics.snapshot_read_requests.inc();
+
+    let head = state.storage.stream_version(&stream_id).await?;
+    if head > 0 && read_cache::version_matches(&headers, head) {
+        return Ok(read_cache::unchanged(head));
+    }
+
+    let version = pinned_version(&state, &stream_id, &query).await?;
+    let snapshot = match version {
+        Some(version) => state.storage.snapshot_at(&stream_id, version).await,
+        None => state.storage.latest_snapshot(&stream_id).await,
+    }
+    .map_err(|e| {
+        state.metrics.snapshot_read_errors.inc();
+        e
+    })?;
+    let snapshot = snapshot.ok_or_else(|| {
+        AppError::NotFound(match version {
+            Some(version) => format!("No snapshot of {} at or before version {}", stream_id, version),
+            None => format!("No snapshot of {}", stream_id),
+        })
+    })?;
+
+    let view = SnapshotView::new(snapshot, &state.snapshot_codec)?;
+
+    state.metrics.snapshots_read.inc();
+    state.metrics.snapshot_read_duration.observe(start_time.elapsed().as_secs_f64());
+
+    Ok(([(STREAM_VERSION_HEADER, head.to_string())], Json(view)).into_response())
+}
+
+/// A stream's state in one response: its snapshot and every event after it.
+#[derive(Debug, Serialize)]
+pub struct StreamState {
+    pub stream_id: String,
+    /// Version of the last event, or of the snapshot when no events follow it.
+    pub version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<DateTime<Utc>>,
+    pub snapshot: Option<SnapshotView>,
+    pub events: Vec<Event>,
+    /// The snapshot with the events applied, when a snapshot reducer covers
+    /// the stream; clients without one replay `events` themselves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<serde_json::Value>,
+}
+
+/// GET /streams/:stream_id/state?version=&as_of= — the latest snapshot and
+/// the events after it, so loading an aggregate takes one round trip, with
+/// no window for an append to land between reading the snapshot and reading
+/// the events. With `version`, the state as of that version instead; with
+/// `as_of`, as of that instant, for audits of what was known when. The
+/// head version is returned in `X-Stream-Version`; sent back as
+/// `If-Match`, the read is a 304 until the stream advances.
+pub async fn get_stream_state(
+    Path(stream_id): Path<String>,
+    Query(query): Query<SnapshotQuery>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    deadline: Deadline,
+    Accept(format): Accept,
+    headers: HeaderMap,
+) -> Result<Response> {
+    tenant.authorize(&stream_id)?;
+    let start_time = std::time::Instant::now();
+    state.metrics.event_read_requests.inc();
+
+    // Pin the head first; events past it are not part of this state
+    let head = state.storage.stream_version(&stream_id).await?;
+    let up_to = pinned_version(&state, &stream_id, &query)
+        .await?
+        .map_or(head, |version| version.min(head));
+    if up_to < 1 {
+        return Err(AppError::NotFound(match query.as_of {
+            Some(at) if head > 0 => format!("Stream {} has no events as of {}", stream_id, at),
+            _ => format!("Stream {} not found", stream_id),
+        }));
+    }
+    if read_cache::version_matches(&headers, head) {
+        return Ok(read_cache::unchanged(head));
+    }
+
+    let snapshot = state.storage.snapshot_at(&stream_id, up_to).await?;
+    let mut from_version = snapshot.as_ref().map_or(1, |s| s.version + 1);
+    let mut events = Vec::new();
+    while from_version <= up_to {
+        let limit = HYDRATE_PAGE_SIZE.min(up_to - from_version + 1);
+        let page = cold_storage::read_stream(
+            &state,
+            &stream_id,
+            from_version,
+            limit,
+            ReadDirection::Forward,
+            true,
+            deadline,
+        )
+        .await
+        .map_err(|e| {
+            state.metrics.event_read_errors.inc();
+            state.metrics.record_error(&stream_id, "read");
+            e
+        })?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        from_version = last.version + 1;
+        let short = (page.len() as i64) < limit;
+        events.extend(page);
+        if short {
+            break;
+        }
+    }
+
+    let visible_from = crate::visible_from(&state, &stream_id).await?;
+    events.retain(|e| e.version >= visible_from);
+    blobs::resolve_events(&state, &mut events).await?;
+    encryption::decrypt_events(&state, &mut events).await?;
+
+    let snapshot = snapshot
+        .map(|snapshot| SnapshotView::new(snapshot, &state.snapshot_codec))
+        .transpose()?;
+    let version = events
+        .last()
+        .map(|e| e.version)
+        .or(snapshot.as_ref().map(|s| s.version))
+        .unwrap_or(0);
+
+    let reducers = state.storage.snapshot_reducers().await?;
+    let reduced = match reducers::for_stream(&reducers, &stream_id) {
+        Some(reducer) => Some(
+            reducers::reduce(
+                state.storage.as_ref(),
+                &state.snapshot_codec,
+                &state.plugins,
+                reducer,
+                &stream_id,
+                up_to,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    state.metrics.record_read(&stream_id, events.len() as u64);
+    state.metrics.event_read_duration.observe(start_time.elapsed().as_secs_f64());
+
+    let body = Encoded(
+        format,
+        StreamState {
+            stream_id,
+            version,
+            as_of: query.as_of,
+            snapshot,
+            events,
+            state: reduced,
+        },
+    );
+    Ok(([(STREAM_VERSION_HEADER, head.to_string())], body).into_response())
+}
@@ -0,0 +1,570 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use super::{
+    ArchiveRange, EventRewrite, EventStorage, MergeCursor, MergeOrder, MigrationStatus, NewEvent, ProjectSummary,
+    ProjectUsage, Purge, ReadDirection, SearchDocument, SearchHit, SnapshotCandidate, StoreStats, UsageBucket,
+};
+use crate::chaos::{Chaos, Fault};
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::models::{
+    ApiKey, ChainAnchor, DeadLetter, EncryptionPolicy, Event, EventTypeDeprecation, Granularity, Namespace,
+    Plugin, ProjectionState, RetentionRule, Snapshot, SnapshotReducer, StreamMetadata,
+    SubscriptionGroup,
+};
+
+/// Storage with faults injected in front of every call, as `Chaos` is set.
+/// A faulted call never reaches the backend: a dropped one fails as on a
+/// lost database connection, the others with a database error or, for
+/// writes that can conflict, a conflict.
+pub struct ChaosStorage {
+    inner: Arc<dyn EventStorage>,
+    chaos: Arc<Chaos>,
+}
+
+impl ChaosStorage {
+    pub fn new(inner: Arc<dyn EventStorage>, chaos: Arc<Chaos>) -> Self {
+        Self { inner, chaos }
+    }
+
+    async fn fault(&self, operation: &str, conflicts: bool) -> Result<()> {
+        match self.chaos.storage_fault(conflicts).await {
+            None => Ok(()),
+            Some(Fault::Drop) => Err(AppError::DatabaseUnavailable(
+                format!("Injected connection drop during {}", operation),
+                1,
+            )),
+            Some(Fault::Error) => Err(AppError::Database(format!("Injected failure of {}", operation))),
+            Some(Fault::Conflict) => Err(AppError::Conflict(format!("Injected conflict on {}", operation))),
+        }
+    }
+}
+
+#[async_trait]
+impl EventStorage for ChaosStorage {
+    async fn migrate(&self) -> Result<()> {
+        self.fault("migrate", false).await?;
+        self.inner.migrate().await
+    }
+
+    async fn append(
+        &self,
+        event: NewEvent,
+        expected_version: Option<i64>,
+        deadline: Deadline,
+    ) -> Result<Event> {
+        self.fault("append", true).await?;
+        self.inner.append(event, expected_version, deadline).await
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        limit: i64,
+        direction: ReadDirection,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>> {
+        self.fault("read_stream", false).await?;
+        self.inner.read_stream(stream_id, from_version, limit, direction, deadline).await
+    }
+
+    async fn scan_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        to_version: i64,
+        sink: mpsc::Sender<Event>,
+    ) -> Result<()> {
+        self.fault("scan_stream", false).await?;
+        self.inner.scan_stream(stream_id, from_version, to_version, sink).await
+    }
+
+    async fn read_latest(&self, stream_id: &str, count: i64, deadline: Deadline) -> Result<Vec<Event>> {
+        self.fault("read_latest", false).await?;
+        self.inner.read_latest(stream_id, count, deadline).await
+    }
+
+    async fn find_event(&self, stream_id: &str, id: Uuid, since: DateTime<Utc>) -> Result<Option<Event>> {
+        self.fault("find_event", false).await?;
+        self.inner.find_event(stream_id, id, since).await
+    }
+
+    async fn version_at(&self, stream_id: &str, at: DateTime<Utc>) -> Result<i64> {
+        self.fault("version_at", false).await?;
+        self.inner.version_at(stream_id, at).await
+    }
+
+    async fn count_events(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<i64> {
+        self.fault("count_events", false).await?;
+        self.inner.count_events(stream_id, from_version, to_version).await
+    }
+
+    async fn stream_version(&self, stream_id: &str) -> Result<i64> {
+        self.fault("stream_version", false).await?;
+        self.inner.stream_version(stream_id).await
+    }
+
+    async fn load_stream_data(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        up_to_version: i64,
+    ) -> Result<Vec<serde_json::Value>> {
+        self.fault("load_stream_data", false).await?;
+        self.inner.load_stream_data(stream_id, from_version, up_to_version).await
+    }
+
+    async fn replace_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        self.fault("replace_snapshot", false).await?;
+        self.inner.replace_snapshot(snapshot).await
+    }
+
+    async fn insert_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        self.fault("insert_snapshot", false).await?;
+        self.inner.insert_snapshot(snapshot).await
+    }
+
+    async fn latest_snapshot(&self, stream_id: &str) -> Result<Option<Snapshot>> {
+        self.fault("latest_snapshot", false).await?;
+        self.inner.latest_snapshot(stream_id).await
+    }
+
+    async fn snapshot_at(&self, stream_id: &str, version: i64) -> Result<Option<Snapshot>> {
+        self.fault("snapshot_at", false).await?;
+        self.inner.snapshot_at(stream_id, version).await
+    }
+
+    async fn snapshot_versions(&self, stream_id: &str) -> Result<Vec<i64>> {
+        self.fault("snapshot_versions", false).await?;
+        self.inner.snapshot_versions(stream_id).await
+    }
+
+    async fn delete_snapshots(&self, stream_id: &str, versions: &[i64]) -> Result<u64> {
+        self.fault("delete_snapshots", false).await?;
+        self.inner.delete_snapshots(stream_id, versions).await
+    }
+
+    async fn snapshot_candidates(&self, threshold: i64) -> Result<Vec<SnapshotCandidate>> {
+        self.fault("snapshot_candidates", false).await?;
+        self.inner.snapshot_candidates(threshold).await
+    }
+
+    async fn archivable_ranges(&self, threshold: DateTime<Utc>) -> Result<Vec<ArchiveRange>> {
+        self.fault("archivable_ranges", false).await?;
+        self.inner.archivable_ranges(threshold).await
+    }
+
+    async fn archive_ranges(&self, ranges: &[ArchiveRange]) -> Result<u64> {
+        self.fault("archive_ranges", false).await?;
+        self.inner.archive_ranges(ranges).await
+    }
+
+    async fn read_archived(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<Vec<Event>> {
+        self.fault("read_archived", false).await?;
+        self.inner.read_archived(stream_id, from_version, to_version).await
+    }
+
+    async fn rewrite_events(
+        &self,
+        stream_id: &str,
+        head: i64,
+        head_hash: Option<&str>,
+        rewrites: &[EventRewrite],
+    ) -> Result<u64> {
+        self.fault("rewrite_events", true).await?;
+        self.inner.rewrite_events(stream_id, head, head_hash, rewrites).await
+    }
+
+    async fn delete_events_through(&self, stream_id: &str, version: i64) -> Result<u64> {
+        self.fault("delete_events_through", false).await?;
+        self.inner.delete_events_through(stream_id, version).await
+    }
+
+    async fn prepare_partitions(&self, months_ahead: u32) -> Result<()> {
+        self.fault("prepare_partitions", false).await?;
+        self.inner.prepare_partitions(months_ahead).await
+    }
+
+    async fn roll_up_stats(&self, limit: i64) -> Result<u64> {
+        self.fault("roll_up_stats", false).await?;
+        self.inner.roll_up_stats(limit).await
+    }
+
+    async fn search_position(&self) -> Result<i64> {
+        self.fault("search_position", false).await?;
+        self.inner.search_position().await
+    }
+
+    async fn index_search_documents(&self, documents: &[SearchDocument], through: i64) -> Result<()> {
+        self.fault("index_search_documents", false).await?;
+        self.inner.index_search_documents(documents, through).await
+    }
+
+    async fn search_events(
+        &self,
+        tenant_id: Option<&str>,
+        query: &str,
+        before: i64,
+        limit: i64,
+    ) -> Result<Vec<SearchHit>> {
+        self.fault("search_events", false).await?;
+        self.inner.search_events(tenant_id, query, before, limit).await
+    }
+
+    async fn prune_search_index(&self) -> Result<u64> {
+        self.fault("prune_search_index", false).await?;
+        self.inner.prune_search_index().await
+    }
+
+    async fn migration_status(&self) -> Result<MigrationStatus> {
+        self.fault("migration_status", false).await?;
+        self.inner.migration_status().await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.fault("ping", false).await?;
+        self.inner.ping().await
+    }
+
+    async fn acquire_leadership(&self, holder: &str, lease: Duration) -> Result<Option<i64>> {
+        self.fault("acquire_leadership", false).await?;
+        self.inner.acquire_leadership(holder, lease).await
+    }
+
+    async fn replication_lag(&self) -> Result<Option<f64>> {
+        self.fault("replication_lag", false).await?;
+        self.inner.replication_lag().await
+    }
+
+    fn pool_connections(&self) -> Option<(u32, usize)> {
+        self.inner.pool_connections()
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        self.fault("stats", false).await?;
+        self.inner.stats().await
+    }
+
+    async fn is_empty(&self) -> Result<bool> {
+        self.fault("is_empty", false).await?;
+        self.inner.is_empty().await
+    }
+
+    async fn tenant_stats(&self, tenant_id: &str) -> Result<StoreStats> {
+        self.fault("tenant_stats", false).await?;
+        self.inner.tenant_stats(tenant_id).await
+    }
+
+    async fn usage_timeseries(
+        &self,
+        tenant_id: Option<&str>,
+        granularity: Granularity,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UsageBucket>> {
+        self.fault("usage_timeseries", false).await?;
+        self.inner.usage_timeseries(tenant_id, granularity, since).await
+    }
+
+    async fn stream_metadata(&self, stream_id: &str) -> Result<Option<StreamMetadata>> {
+        self.fault("stream_metadata", false).await?;
+        self.inner.stream_metadata(stream_id).await
+    }
+
+    async fn all_stream_metadata(&self) -> Result<Vec<(String, StreamMetadata)>> {
+        self.fault("all_stream_metadata", false).await?;
+        self.inner.all_stream_metadata().await
+    }
+
+    async fn set_stream_metadata(&self, stream_id: &str, metadata: &StreamMetadata) -> Result<()> {
+        self.fault("set_stream_metadata", false).await?;
+        self.inner.set_stream_metadata(stream_id, metadata).await
+    }
+
+    async fn project_summaries(&self) -> Result<Vec<ProjectSummary>> {
+        self.fault("project_summaries", false).await?;
+        self.inner.project_summaries().await
+    }
+
+    async fn sample_storage_usage(&self) -> Result<u64> {
+        self.fault("sample_storage_usage", false).await?;
+        self.inner.sample_storage_usage().await
+    }
+
+    async fn monthly_usage(&self, month: NaiveDate) -> Result<Vec<ProjectUsage>> {
+        self.fault("monthly_usage", false).await?;
+        self.inner.monthly_usage(month).await
+    }
+
+    async fn legal_hold_streams(&self) -> Result<Vec<String>> {
+        self.fault("legal_hold_streams", false).await?;
+        self.inner.legal_hold_streams().await
+    }
+
+    async fn stream_heads(&self, prefix: &str) -> Result<Vec<(String, i64)>> {
+        self.fault("stream_heads", false).await?;
+        self.inner.stream_heads(prefix).await
+    }
+
+    async fn committed_position(&self) -> Result<i64> {
+        self.fault("committed_position", false).await?;
+        self.inner.committed_position().await
+    }
+
+    async fn read_all(&self, after: i64, limit: i64) -> Result<Vec<Event>> {
+        self.fault("read_all", false).await?;
+        self.inner.read_all(after, limit).await
+    }
+
+    async fn read_all_with_archive(&self, after: i64, limit: i64) -> Result<Vec<Event>> {
+        self.fault("read_all_with_archive", false).await?;
+        self.inner.read_all_with_archive(after, limit).await
+    }
+
+    async fn read_streams(
+        &self,
+        stream_ids: &[String],
+        after: MergeCursor,
+        order: MergeOrder,
+        limit: i64,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>> {
+        self.fault("read_streams", false).await?;
+        self.inner.read_streams(stream_ids, after, order, limit, deadline).await
+    }
+
+    async fn import_events(&self, events: &[Event]) -> Result<()> {
+        self.fault("import_events", false).await?;
+        self.inner.import_events(events).await
+    }
+
+    async fn replication_checkpoint(&self) -> Result<i64> {
+        self.fault("replication_checkpoint", false).await?;
+        self.inner.replication_checkpoint().await
+    }
+
+    async fn apply_replicated(&self, after: i64, through: i64, events: &[Event]) -> Result<()> {
+        self.fault("apply_replicated", true).await?;
+        self.inner.apply_replicated(after, through, events).await
+    }
+
+    async fn import_stream(&self, stream_id: &str, events: Vec<Event>) -> Result<Vec<Event>> {
+        self.fault("import_stream", true).await?;
+        self.inner.import_stream(stream_id, events).await
+    }
+
+    async fn bulk_load(&self, events: Vec<Event>) -> Result<u64> {
+        self.fault("bulk_load", true).await?;
+        self.inner.bulk_load(events).await
+    }
+
+    async fn event_type_deprecations(&self) -> Result<Vec<EventTypeDeprecation>> {
+        self.fault("event_type_deprecations", false).await?;
+        self.inner.event_type_deprecations().await
+    }
+
+    async fn set_event_type_deprecation(&self, deprecation: &EventTypeDeprecation) -> Result<()> {
+        self.fault("set_event_type_deprecation", false).await?;
+        self.inner.set_event_type_deprecation(deprecation).await
+    }
+
+    async fn remove_event_type_deprecation(&self, event_type: &str) -> Result<bool> {
+        self.fault("remove_event_type_deprecation", false).await?;
+        self.inner.remove_event_type_deprecation(event_type).await
+    }
+
+    async fn streams_with_event_type(&self, event_type: &str) -> Result<Vec<String>> {
+        self.fault("streams_with_event_type", false).await?;
+        self.inner.streams_with_event_type(event_type).await
+    }
+
+    async fn api_keys(&self) -> Result<Vec<(String, ApiKey)>> {
+        self.fault("api_keys", false).await?;
+        self.inner.api_keys().await
+    }
+
+    async fn insert_api_key(&self, key_hash: &str, key: &ApiKey) -> Result<()> {
+        self.fault("insert_api_key", false).await?;
+        self.inner.insert_api_key(key_hash, key).await
+    }
+
+    async fn revoke_api_key(&self, id: Uuid) -> Result<bool> {
+        self.fault("revoke_api_key", false).await?;
+        self.inner.revoke_api_key(id).await
+    }
+
+    async fn purgeable_events(&self, purge: &Purge) -> Result<Vec<(String, u64)>> {
+        self.fault("purgeable_events", false).await?;
+        self.inner.purgeable_events(purge).await
+    }
+
+    async fn purge_events(&self, purge: &Purge, limit: i64) -> Result<u64> {
+        self.fault("purge_events", false).await?;
+        self.inner.purge_events(purge, limit).await
+    }
+
+    async fn scavenge_truncated(&self, limit: i64) -> Result<u64> {
+        self.fault("scavenge_truncated", false).await?;
+        self.inner.scavenge_truncated(limit).await
+    }
+
+    async fn scavenge_expired(&self, now: DateTime<Utc>, limit: i64) -> Result<u64> {
+        self.fault("scavenge_expired", false).await?;
+        self.inner.scavenge_expired(now, limit).await
+    }
+
+    async fn retention_rules(&self) -> Result<Vec<RetentionRule>> {
+        self.fault("retention_rules", false).await?;
+        self.inner.retention_rules().await
+    }
+
+    async fn set_retention_rule(&self, rule: &RetentionRule) -> Result<()> {
+        self.fault("set_retention_rule", false).await?;
+        self.inner.set_retention_rule(rule).await
+    }
+
+    async fn remove_retention_rule(&self, name: &str) -> Result<bool> {
+        self.fault("remove_retention_rule", false).await?;
+        self.inner.remove_retention_rule(name).await
+    }
+
+    async fn snapshot_reducers(&self) -> Result<Vec<SnapshotReducer>> {
+        self.fault("snapshot_reducers", false).await?;
+        self.inner.snapshot_reducers().await
+    }
+
+    async fn set_snapshot_reducer(&self, reducer: &SnapshotReducer) -> Result<()> {
+        self.fault("set_snapshot_reducer", false).await?;
+        self.inner.set_snapshot_reducer(reducer).await
+    }
+
+    async fn remove_snapshot_reducer(&self, name: &str) -> Result<bool> {
+        self.fault("remove_snapshot_reducer", false).await?;
+        self.inner.remove_snapshot_reducer(name).await
+    }
+
+    async fn plugins(&self) -> Result<Vec<(Plugin, Vec<u8>)>> {
+        self.fault("plugins", false).await?;
+        self.inner.plugins().await
+    }
+
+    async fn set_plugin(&self, plugin: &Plugin, module: &[u8]) -> Result<()> {
+        self.fault("set_plugin", false).await?;
+        self.inner.set_plugin(plugin, module).await
+    }
+
+    async fn remove_plugin(&self, name: &str) -> Result<bool> {
+        self.fault("remove_plugin", false).await?;
+        self.inner.remove_plugin(name).await
+    }
+
+    async fn projection(&self, name: &str) -> Result<Option<ProjectionState>> {
+        self.fault("projection", false).await?;
+        self.inner.projection(name).await
+    }
+
+    async fn set_projection(&self, projection: &ProjectionState) -> Result<()> {
+        self.fault("set_projection", false).await?;
+        self.inner.set_projection(projection).await
+    }
+
+    async fn add_dead_letters(&self, letters: &[DeadLetter]) -> Result<()> {
+        self.fault("add_dead_letters", false).await?;
+        self.inner.add_dead_letters(letters).await
+    }
+
+    async fn dead_letters(&self, source: Option<&str>, limit: i64) -> Result<Vec<DeadLetter>> {
+        self.fault("dead_letters", false).await?;
+        self.inner.dead_letters(source, limit).await
+    }
+
+    async fn dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>> {
+        self.fault("dead_letter", false).await?;
+        self.inner.dead_letter(id).await
+    }
+
+    async fn set_dead_letter(&self, letter: &DeadLetter) -> Result<()> {
+        self.fault("set_dead_letter", false).await?;
+        self.inner.set_dead_letter(letter).await
+    }
+
+    async fn remove_dead_letter(&self, id: Uuid) -> Result<bool> {
+        self.fault("remove_dead_letter", false).await?;
+        self.inner.remove_dead_letter(id).await
+    }
+
+    async fn encryption_policies(&self) -> Result<Vec<EncryptionPolicy>> {
+        self.fault("encryption_policies", false).await?;
+        self.inner.encryption_policies().await
+    }
+
+    async fn set_encryption_policy(&self, policy: &EncryptionPolicy) -> Result<()> {
+        self.fault("set_encryption_policy", false).await?;
+        self.inner.set_encryption_policy(policy).await
+    }
+
+    async fn remove_encryption_policy(&self, event_type: &str) -> Result<bool> {
+        self.fault("remove_encryption_policy", false).await?;
+        self.inner.remove_encryption_policy(event_type).await
+    }
+
+    async fn subject_key(&self, subject_id: &str) -> Result<Option<Vec<u8>>> {
+        self.fault("subject_key", false).await?;
+        self.inner.subject_key(subject_id).await
+    }
+
+    async fn insert_subject_key(&self, subject_id: &str, key: &[u8]) -> Result<Vec<u8>> {
+        self.fault("insert_subject_key", false).await?;
+        self.inner.insert_subject_key(subject_id, key).await
+    }
+
+    async fn delete_subject_key(&self, subject_id: &str) -> Result<bool> {
+        self.fault("delete_subject_key", false).await?;
+        self.inner.delete_subject_key(subject_id).await
+    }
+
+    async fn chain_anchors(&self, limit: i64) -> Result<Vec<ChainAnchor>> {
+        self.fault("chain_anchors", false).await?;
+        self.inner.chain_anchors(limit).await
+    }
+
+    async fn add_chain_anchor(&self, anchor: &ChainAnchor) -> Result<()> {
+        self.fault("add_chain_anchor", false).await?;
+        self.inner.add_chain_anchor(anchor).await
+    }
+
+    async fn namespaces(&self) -> Result<Vec<Namespace>> {
+        self.fault("namespaces", false).await?;
+        self.inner.namespaces().await
+    }
+
+    async fn insert_namespace(&self, namespace: &Namespace) -> Result<bool> {
+        self.fault("insert_namespace", false).await?;
+        self.inner.insert_namespace(namespace).await
+    }
+
+    async fn remove_namespace(&self, id: &str) -> Result<u64> {
+        self.fault("remove_namespace", false).await?;
+        self.inner.remove_namespace(id).await
+    }
+
+    async fn subscription_groups(&self) -> Result<Vec<SubscriptionGroup>> {
+        self.fault("subscription_groups", false).await?;
+        self.inner.subscription_groups().await
+    }
+
+    async fn set_subscription_group(&self, group: &SubscriptionGroup) -> Result<()> {
+        self.fault("set_subscription_group", false).await?;
+        self.inner.set_subscription_group(group).await
+    }
+
+    async fn remove_subscription_group(&self, name: &str) -> Result<bool> {
+        self.fault("remove_subscription_group", false).await?;
+        self.inner.remove_subscription_group(name).await
+    }
+}
@@ -0,0 +1,74 @@
+use tracing::error;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::models::EventCompression;
+
+/// Fast enough for the append path, and most of zstd's gain on JSON.
+const ZSTD_LEVEL: i32 = 3;
+
+/// A JSON event body stored compressed, in place of its JSONB `data`.
+#[derive(Debug, Clone)]
+pub struct Compressed {
+    pub compression: EventCompression,
+    pub bytes: Vec<u8>,
+}
+
+/// Compresses JSON event bodies larger than `event_compression_threshold_kb`.
+/// Smaller ones stay JSONB, so they can still be queried in SQL.
+#[derive(Debug, Clone, Copy)]
+pub struct EventCompressor {
+    compression: EventCompression,
+    threshold_bytes: usize,
+}
+
+fn codec_error(failure: &str, e: impl std::fmt::Display) -> AppError {
+    error!("Event data {}: {}", failure.to_lowercase(), e);
+    AppError::Internal(failure.to_string())
+}
+
+impl EventCompressor {
+    /// `None` when event compression is off.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        (config.event_compression != EventCompression::None).then(|| Self {
+            compression: config.event_compression,
+            threshold_bytes: config.event_compression_threshold_kb as usize * 1024,
+        })
+    }
+
+    /// The compressed form of `data`, or `None` when it's under the threshold
+    /// or doesn't get any smaller.
+    pub fn compress(&self, data: &serde_json::Value) -> Result<Option<Compressed>> {
+        let raw = serde_json::to_vec(data)?;
+        if raw.len() <= self.threshold_bytes {
+            return Ok(None);
+        }
+
+        let bytes = match self.compression {
+            EventCompression::Lz4 => lz4_flex::compress_prepend_size(&raw),
+            EventCompression::Zstd => {
+                zstd::bulk::compress(&raw, ZSTD_LEVEL).map_err(|e| codec_error("Compression failed", e))?
+            }
+            EventCompression::None => return Ok(None),
+        };
+        if bytes.len() >= raw.len() {
+            return Ok(None);
+        }
+        Ok(Some(Compressed {
+            compression: self.compression,
+            bytes,
+        }))
+    }
+}
+
+/// Restores a body stored compressed with `compression`.
+pub fn decompress_data(compression: EventCompression, bytes: &[u8]) -> Result<serde_json::Value> {
+    let raw = match compression {
+        EventCompression::Lz4 => {
+            lz4_flex::decompress_size_prepended(bytes).map_err(|e| codec_error("Decompression failed", e))?
+        }
+        EventCompression::Zstd => zstd::stream::decode_all(bytes).map_err(|e| codec_error("Decompression failed", e))?,
+        EventCompression::None => bytes.to_vec(),
+    };
+    serde_json::from_slice(&raw).map_err(|e| codec_error("Deserialization failed", e))
+}
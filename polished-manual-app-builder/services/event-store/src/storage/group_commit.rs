@@ -0,0 +1,106 @@
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{timeout_at, Instant};
+use tracing::info;
+
+use super::compression::Compressed;
+use super::{NewEvent, PostgresStorage};
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::models::Event;
+
+/// An append waiting for the next group commit.
+pub(super) struct PendingAppend {
+    pub event: NewEvent,
+    /// The event's body, when it's stored compressed.
+    pub compressed: Option<Compressed>,
+    pub expected_version: Option<i64>,
+    pub deadline: Deadline,
+    pub reply: oneshot::Sender<Result<Event>>,
+}
+
+impl PendingAppend {
+    /// Fails the append with its own copy of `error`, an error of the whole
+    /// batch. Errors that can't be copied reach it as database errors.
+    pub fn fail(self, error: &AppError) {
+        let error = match error {
+            AppError::Fenced(message) => AppError::Fenced(message.clone()),
+            AppError::DeadlineExceeded(message) => AppError::DeadlineExceeded(message.clone()),
+            AppError::DatabaseUnavailable(message, retry_after) => {
+                AppError::DatabaseUnavailable(message.clone(), *retry_after)
+            }
+            AppError::Conflict(message) => AppError::Conflict(message.clone()),
+            AppError::Database(message) => AppError::Database(message.clone()),
+            e => AppError::Database(e.to_string()),
+        };
+        let _ = self.reply.send(Err(error));
+    }
+}
+
+/// Coalesces concurrent appends into one transaction and one multi-row
+/// INSERT. The first append to arrive opens a window; everything queued
+/// before it closes (or before the batch is full) commits together.
+/// Appends to the same stream keep their arrival order.
+#[derive(Debug, Clone)]
+pub(super) struct GroupCommit {
+    sender: mpsc::Sender<PendingAppend>,
+}
+
+impl GroupCommit {
+    pub fn spawn(storage: PostgresStorage, window: Duration, max_batch: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(max_batch * 4);
+        tokio::spawn(run(storage, receiver, window, max_batch));
+
+        info!(
+            "Group commit enabled: {}ms window, up to {} appends per batch",
+            window.as_millis(),
+            max_batch
+        );
+        Self { sender }
+    }
+
+    pub async fn append(
+        &self,
+        event: NewEvent,
+        compressed: Option<Compressed>,
+        expected_version: Option<i64>,
+        deadline: Deadline,
+    ) -> Result<Event> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(PendingAppend {
+                event,
+                compressed,
+                expected_version,
+                deadline,
+                reply,
+            })
+            .await
+            .map_err(|_| AppError::Internal("Group commit writer stopped".to_string()))?;
+
+        response
+            .await
+            .map_err(|_| AppError::Internal("Group commit writer dropped an append".to_string()))?
+    }
+}
+
+async fn run(
+    storage: PostgresStorage,
+    mut receiver: mpsc::Receiver<PendingAppend>,
+    window: Duration,
+    max_batch: usize,
+) {
+    while let Some(first) = receiver.recv().await {
+        let close_at = Instant::now() + window;
+        let mut batch = vec![first];
+
+        while batch.len() < max_batch {
+            match timeout_at(close_at, receiver.recv()).await {
+                Ok(Some(pending)) => batch.push(pending),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        storage.append_batch(batch).await;
+    }
+}
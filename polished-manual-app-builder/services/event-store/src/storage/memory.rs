@@ -0,0 +1,1309 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use uuid::Uuid;
+
+use super::{
+    check_precondition, check_rewrite_head, stats_since, ArchiveRange, DailyCount, EventRewrite, EventStorage,
+    MergeCursor, MergeOrder, NewEvent, ProjectSummary, ProjectUsage, Purge, ReadDirection, SnapshotCandidate, StoreStats,
+    UsageBucket,
+};
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::get_partition_key;
+use crate::integrity::Link;
+use crate::models::{
+    ApiKey, ChainAnchor, DeadLetter, EncryptionPolicy, Event, EventTypeDeprecation, Granularity, Namespace,
+    Plugin, ProjectionState, RetentionRule, Snapshot, SnapshotReducer, StreamMetadata,
+    SubscriptionGroup,
+};
+
+#[derive(Debug, Clone)]
+struct StoredEvent {
+    event: Event,
+    partition_key: String,
+    tenant_id: String,
+}
+
+type Stream = Arc<RwLock<BTreeMap<i64, StoredEvent>>>;
+
+/// Volatile storage for tests and demos: nothing survives a restart.
+///
+/// Each stream is a `BTreeMap` keyed by version behind its own lock, so
+/// appends to different streams don't contend with each other.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    streams: RwLock<HashMap<String, Stream>>,
+    snapshots: RwLock<HashMap<String, BTreeMap<i64, Snapshot>>>,
+    metadata: RwLock<HashMap<String, StreamMetadata>>,
+    deprecations: RwLock<BTreeMap<String, EventTypeDeprecation>>,
+    /// API keys by key hash.
+    api_keys: RwLock<HashMap<String, ApiKey>>,
+    encryption_policies: RwLock<BTreeMap<String, EncryptionPolicy>>,
+    retention_rules: RwLock<BTreeMap<String, RetentionRule>>,
+    snapshot_reducers: RwLock<BTreeMap<String, SnapshotReducer>>,
+    plugins: RwLock<BTreeMap<String, (Plugin, Vec<u8>)>>,
+    projections: RwLock<HashMap<String, ProjectionState>>,
+    dead_letters: RwLock<HashMap<Uuid, DeadLetter>>,
+    subject_keys: RwLock<HashMap<String, Vec<u8>>>,
+    /// Archived events by stream, then version.
+    archive: RwLock<HashMap<String, BTreeMap<i64, Event>>>,
+    /// When events appended with a TTL expire, by stream, then version;
+    /// hot and archived alike.
+    expiries: RwLock<HashMap<String, BTreeMap<i64, DateTime<Utc>>>>,
+    /// Oldest first.
+    chain_anchors: RwLock<Vec<ChainAnchor>>,
+    namespaces: RwLock<BTreeMap<String, Namespace>>,
+    subscription_groups: RwLock<BTreeMap<String, SubscriptionGroup>>,
+    /// Last assigned global position.
+    position: Mutex<i64>,
+    /// Replication checkpoint, once a replicated batch has been applied.
+    replicated: Mutex<Option<i64>>,
+    /// Peak bytes stored per project and day.
+    storage_samples: Mutex<BTreeMap<(String, NaiveDate), i64>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn stream(&self, stream_id: &str) -> Option<Stream> {
+        self.streams.read().unwrap().get(stream_id).cloned()
+    }
+
+    fn stream_or_create(&self, stream_id: &str) -> Stream {
+        if let Some(stream) = self.stream(stream_id) {
+            return stream;
+        }
+        self.streams
+            .write()
+            .unwrap()
+            .entry(stream_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Stores events as they are, keeping their ids, versions and positions.
+    /// `position` is the held lock on the last assigned position.
+    fn insert_verbatim(&self, position: &mut i64, events: &[Event]) {
+        for event in events {
+            self.stream_or_create(&event.stream_id).write().unwrap().insert(
+                event.version,
+                StoredEvent {
+                    event: event.clone(),
+                    partition_key: get_partition_key(&event.stream_id),
+                    tenant_id: get_partition_key(&event.stream_id),
+                },
+            );
+            *position = (*position).max(event.position);
+        }
+    }
+
+    fn all_streams(&self) -> Vec<(String, Stream)> {
+        self.streams
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, stream)| (id.clone(), stream.clone()))
+            .collect()
+    }
+
+    /// Appends per UTC day since `stats_since`, of one tenant or all.
+    fn events_per_day(&self, tenant_id: Option<&str>) -> Vec<DailyCount> {
+        let since = stats_since();
+        let mut days: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+        for (_, stream) in self.all_streams() {
+            for stored in stream.read().unwrap().values() {
+                let day = stored.event.created_at.date_naive();
+                if day >= since && tenant_id.map_or(true, |tenant_id| stored.tenant_id == tenant_id) {
+                    *days.entry(day).or_default() += 1;
+                }
+            }
+        }
+        days.into_iter().map(|(day, events)| DailyCount { day, events }).collect()
+    }
+
+    /// Versions `purge` selects in one stream's hot events and archive.
+    fn purgeable_versions(&self, purge: &Purge, stream_id: &str) -> (Vec<i64>, Vec<i64>) {
+        if self.on_legal_hold(stream_id) {
+            return (Vec::new(), Vec::new());
+        }
+        let Some(stream) = self.stream(stream_id) else {
+            return (Vec::new(), Vec::new());
+        };
+        let stream = stream.read().unwrap();
+        let Some(head) = stream.keys().next_back().copied() else {
+            return (Vec::new(), Vec::new());
+        };
+        let selected = |event: &Event| {
+            event.version < head && event.created_at < purge.before && purge.matches(stream_id, &event.event_type)
+        };
+
+        let hot = stream.values().filter(|s| selected(&s.event)).map(|s| s.event.version).collect();
+        let archived = self
+            .archive
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .map(|archive| archive.values().filter(|e| selected(e)).map(|e| e.version).collect())
+            .unwrap_or_default();
+        (hot, archived)
+    }
+
+    fn on_legal_hold(&self, stream_id: &str) -> bool {
+        self.metadata
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .map(|m| m.legal_hold)
+            .unwrap_or(false)
+    }
+}
+
+/// Stored size of an event's body.
+fn body_size(event: &Event) -> i64 {
+    match &event.payload {
+        Some(payload) => payload.len() as i64,
+        None => event.data.to_string().len() as i64,
+    }
+}
+
+fn no_usage(project_id: &str) -> ProjectUsage {
+    ProjectUsage {
+        project_id: project_id.to_string(),
+        events: 0,
+        bytes_appended: 0,
+        avg_stored_bytes: 0,
+        peak_stored_bytes: 0,
+    }
+}
+
+#[async_trait]
+impl EventStorage for MemoryStorage {
+    async fn migrate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn append(
+        &self,
+        event: NewEvent,
+        expected_version: Option<i64>,
+        deadline: Deadline,
+    ) -> Result<Event> {
+        deadline.remaining()?;
+
+        // Streams named in preconditions are locked along with the target,
+        // all in id order, so appends with crossed preconditions can't deadlock
+        let mut stream_ids: Vec<String> = event.preconditions.iter().map(|p| p.stream_id.clone()).collect();
+        stream_ids.push(event.stream_id.clone());
+        stream_ids.sort();
+        stream_ids.dedup();
+        let streams: Vec<(String, Stream)> =
+            stream_ids.into_iter().map(|id| (id.clone(), self.stream_or_create(&id))).collect();
+        let mut locked: Vec<_> = streams.iter().map(|(id, stream)| (id, stream.write().unwrap())).collect();
+        for precondition in &event.preconditions {
+            let (_, other) = locked.iter().find(|(id, _)| **id == precondition.stream_id).unwrap();
+            check_precondition(precondition, other.keys().next_back().copied().unwrap_or(0))?;
+        }
+        let (_, stream) = locked.iter_mut().find(|(id, _)| **id == event.stream_id).unwrap();
+
+        let current_version = stream.keys().next_back().copied().unwrap_or(0);
+        if let Some(expected) = expected_version {
+            if current_version != expected {
+                return Err(AppError::Conflict(format!(
+                    "Version conflict: expected {}, got {}",
+                    expected, current_version
+                )));
+            }
+        }
+
+        let new_version = current_version + 1;
+        let previous = stream.values().next_back().and_then(|head| head.event.chain_hash.as_deref());
+        let chain_hash = Link::new_event(&event, new_version).hash(previous);
+
+        // Held until the event is visible so positions appear in order
+        let mut position = self.position.lock().unwrap();
+        *position += 1;
+
+        let stored = Event {
+            id: event.id,
+            stream_id: event.stream_id,
+            event_type: event.event_type,
+            data: event.data.unwrap_or(serde_json::Value::Null),
+            payload: event.payload,
+            content_type: event.content_type,
+            metadata: event.metadata,
+            version: new_version,
+            position: *position,
+            created_at: event.created_at,
+            checksum: Some(event.checksum),
+            chain_hash: Some(chain_hash),
+            link: None,
+        };
+
+        stream.insert(
+            new_version,
+            StoredEvent {
+                event: stored.clone(),
+                partition_key: event.partition_key,
+                tenant_id: event.tenant_id,
+            },
+        );
+        if let Some(expires_at) = event.expires_at {
+            self.expiries
+                .write()
+                .unwrap()
+                .entry(stored.stream_id.clone())
+                .or_default()
+                .insert(new_version, expires_at);
+        }
+
+        Ok(stored)
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        limit: i64,
+        direction: ReadDirection,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>> {
+        deadline.remaining()?;
+
+        let Some(stream) = self.stream(stream_id) else {
+            return Ok(Vec::new());
+        };
+        let stream = stream.read().unwrap();
+        let limit = limit.max(0) as usize;
+
+        Ok(match direction {
+            ReadDirection::Forward => stream.range(from_version..).take(limit).map(|(_, e)| e.event.clone()).collect(),
+            ReadDirection::Backward => {
+                stream.range(..=from_version).rev().take(limit).map(|(_, e)| e.event.clone()).collect()
+            }
+        })
+    }
+
+    async fn read_latest(&self, stream_id: &str, count: i64, deadline: Deadline) -> Result<Vec<Event>> {
+        deadline.remaining()?;
+
+        let Some(stream) = self.stream(stream_id) else {
+            return Ok(Vec::new());
+        };
+        let stream = stream.read().unwrap();
+        let mut events: Vec<Event> = stream
+            .values()
+            .rev()
+            .take(count.max(0) as usize)
+            .map(|e| e.event.clone())
+            .collect();
+        events.reverse();
+
+        Ok(events)
+    }
+
+    async fn find_event(&self, stream_id: &str, id: Uuid, since: DateTime<Utc>) -> Result<Option<Event>> {
+        let Some(stream) = self.stream(stream_id) else {
+            return Ok(None);
+        };
+        let stream = stream.read().unwrap();
+        Ok(stream
+            .values()
+            .rev()
+            .take_while(|e| e.event.created_at >= since)
+            .find(|e| e.event.id == id)
+            .map(|e| e.event.clone()))
+    }
+
+    async fn version_at(&self, stream_id: &str, at: DateTime<Utc>) -> Result<i64> {
+        let hot = self.stream(stream_id).and_then(|stream| {
+            let stream = stream.read().unwrap();
+            stream.values().rev().find(|e| e.event.created_at <= at).map(|e| e.event.version)
+        });
+        if let Some(version) = hot {
+            return Ok(version);
+        }
+
+        Ok(self
+            .archive
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .and_then(|archive| archive.values().rev().find(|e| e.created_at <= at).map(|e| e.version))
+            .unwrap_or(0))
+    }
+
+    async fn count_events(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<i64> {
+        if from_version > to_version {
+            return Ok(0);
+        }
+        let hot = self
+            .stream(stream_id)
+            .map_or(0, |stream| stream.read().unwrap().range(from_version..=to_version).count());
+        let archived = self
+            .archive
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .map_or(0, |archive| archive.range(from_version..=to_version).count());
+        Ok((hot + archived) as i64)
+    }
+
+    async fn stream_version(&self, stream_id: &str) -> Result<i64> {
+        Ok(self
+            .stream(stream_id)
+            .and_then(|s| s.read().unwrap().keys().next_back().copied())
+            .unwrap_or(0))
+    }
+
+    async fn load_stream_data(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        up_to_version: i64,
+    ) -> Result<Vec<serde_json::Value>> {
+        let Some(stream) = self.stream(stream_id) else {
+            return Ok(Vec::new());
+        };
+        if from_version > up_to_version {
+            return Ok(Vec::new());
+        }
+        let stream = stream.read().unwrap();
+        Ok(stream
+            .range(from_version..=up_to_version)
+            .filter(|(_, e)| e.event.payload.is_none())
+            .map(|(_, e)| e.event.data.clone())
+            .collect())
+    }
+
+    async fn replace_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        self.snapshots
+            .write()
+            .unwrap()
+            .entry(snapshot.stream_id.clone())
+            .or_default()
+            .insert(snapshot.version, snapshot.clone());
+        Ok(())
+    }
+
+    async fn insert_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        self.snapshots
+            .write()
+            .unwrap()
+            .entry(snapshot.stream_id.clone())
+            .or_default()
+            .entry(snapshot.version)
+            .or_insert_with(|| snapshot.clone());
+        Ok(())
+    }
+
+    async fn latest_snapshot(&self, stream_id: &str) -> Result<Option<Snapshot>> {
+        Ok(self
+            .snapshots
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .and_then(|versions| versions.values().next_back().cloned()))
+    }
+
+    async fn snapshot_at(&self, stream_id: &str, version: i64) -> Result<Option<Snapshot>> {
+        Ok(self
+            .snapshots
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .and_then(|versions| versions.range(..=version).next_back().map(|(_, s)| s.clone())))
+    }
+
+    async fn snapshot_versions(&self, stream_id: &str) -> Result<Vec<i64>> {
+        Ok(self
+            .snapshots
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .map(|versions| versions.keys().copied().collect())
+            .unwrap_or_default())
+    }
+
+    async fn delete_snapshots(&self, stream_id: &str, versions: &[i64]) -> Result<u64> {
+        let mut snapshots = self.snapshots.write().unwrap();
+        let Some(stream) = snapshots.get_mut(stream_id) else {
+            return Ok(0);
+        };
+        Ok(versions.iter().filter(|v| stream.remove(v).is_some()).count() as u64)
+    }
+
+    async fn snapshot_candidates(&self, threshold: i64) -> Result<Vec<SnapshotCandidate>> {
+        let snapshots = self.snapshots.read().unwrap();
+        let mut candidates = Vec::new();
+
+        for (stream_id, stream) in self.all_streams() {
+            let current_version = stream.read().unwrap().keys().next_back().copied().unwrap_or(0);
+            let snapshot_version = snapshots
+                .get(&stream_id)
+                .and_then(|v| v.keys().next_back().copied())
+                .unwrap_or(0);
+
+            if current_version - snapshot_version >= threshold {
+                candidates.push(SnapshotCandidate {
+                    stream_id,
+                    current_version,
+                    snapshot_version,
+                });
+            }
+        }
+
+        Ok(candidates)
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        let streams = self.all_streams();
+        let total_events = streams
+            .iter()
+            .map(|(_, s)| s.read().unwrap().len() as i64)
+            .sum();
+        let total_snapshots = self
+            .snapshots
+            .read()
+            .unwrap()
+            .values()
+            .map(|v| v.len() as i64)
+            .sum();
+
+        Ok(StoreStats {
+            total_events,
+            total_streams: streams.len() as i64,
+            total_snapshots,
+            storage_bytes: None,
+            events_per_day: self.events_per_day(None),
+        })
+    }
+
+    async fn is_empty(&self) -> Result<bool> {
+        Ok(self.all_streams().iter().all(|(_, s)| s.read().unwrap().is_empty())
+            && self.archive.read().unwrap().values().all(|archive| archive.is_empty()))
+    }
+
+    async fn tenant_stats(&self, tenant_id: &str) -> Result<StoreStats> {
+        let mut stats = StoreStats::default();
+        for (_, stream) in self.all_streams() {
+            let stream = stream.read().unwrap();
+            let owned = stream.values().filter(|stored| stored.tenant_id == tenant_id).count() as i64;
+            if owned > 0 {
+                stats.total_streams += 1;
+                stats.total_events += owned;
+            }
+        }
+        stats.total_snapshots = self
+            .snapshots
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(stream_id, _)| get_partition_key(stream_id) == tenant_id)
+            .map(|(_, snapshots)| snapshots.len() as i64)
+            .sum();
+        stats.events_per_day = self.events_per_day(Some(tenant_id));
+
+        Ok(stats)
+    }
+
+    async fn usage_timeseries(
+        &self,
+        tenant_id: Option<&str>,
+        granularity: Granularity,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UsageBucket>> {
+        let mut buckets: BTreeMap<DateTime<Utc>, UsageBucket> = BTreeMap::new();
+        for (_, stream) in self.all_streams() {
+            let stream = stream.read().unwrap();
+            let mut seen = Vec::new();
+            for stored in stream.values() {
+                let event = &stored.event;
+                if event.created_at < since || tenant_id.is_some_and(|tenant_id| stored.tenant_id != tenant_id) {
+                    continue;
+                }
+                let start = granularity.truncate(event.created_at);
+                let usage = buckets.entry(start).or_insert_with(|| UsageBucket {
+                    bucket: start,
+                    events: 0,
+                    bytes: 0,
+                    active_streams: 0,
+                });
+                usage.events += 1;
+                usage.bytes += body_size(event);
+                if !seen.contains(&start) {
+                    seen.push(start);
+                    usage.active_streams += 1;
+                }
+            }
+        }
+        Ok(buckets.into_values().collect())
+    }
+
+    async fn stream_metadata(&self, stream_id: &str) -> Result<Option<StreamMetadata>> {
+        Ok(self.metadata.read().unwrap().get(stream_id).cloned())
+    }
+
+    async fn all_stream_metadata(&self) -> Result<Vec<(String, StreamMetadata)>> {
+        let mut all: Vec<(String, StreamMetadata)> = self
+            .metadata
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, m)| (id.clone(), m.clone()))
+            .collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(all)
+    }
+
+    async fn set_stream_metadata(&self, stream_id: &str, metadata: &StreamMetadata) -> Result<()> {
+        self.metadata
+            .write()
+            .unwrap()
+            .insert(stream_id.to_string(), metadata.clone());
+        Ok(())
+    }
+
+    async fn project_summaries(&self) -> Result<Vec<ProjectSummary>> {
+        let mut projects: BTreeMap<String, ProjectSummary> = BTreeMap::new();
+
+        for (_, stream) in self.all_streams() {
+            let stream = stream.read().unwrap();
+            let Some(first) = stream.values().next() else {
+                continue;
+            };
+
+            let summary = projects
+                .entry(first.partition_key.clone())
+                .or_insert_with(|| ProjectSummary {
+                    project_id: first.partition_key.clone(),
+                    stream_count: 0,
+                    event_count: 0,
+                    oldest_event_at: None,
+                    newest_event_at: None,
+                });
+
+            summary.stream_count += 1;
+            summary.event_count += stream.len() as i64;
+            for stored in stream.values() {
+                let at = stored.event.created_at;
+                summary.oldest_event_at = Some(summary.oldest_event_at.map_or(at, |t| t.min(at)));
+                summary.newest_event_at = Some(summary.newest_event_at.map_or(at, |t| t.max(at)));
+            }
+        }
+
+        Ok(projects.into_values().collect())
+    }
+
+    async fn sample_storage_usage(&self) -> Result<u64> {
+        let mut stored: BTreeMap<String, i64> = BTreeMap::new();
+        for (_, stream) in self.all_streams() {
+            for stored_event in stream.read().unwrap().values() {
+                *stored.entry(stored_event.partition_key.clone()).or_default() += body_size(&stored_event.event);
+            }
+        }
+        for (stream_id, events) in self.archive.read().unwrap().iter() {
+            *stored.entry(get_partition_key(stream_id)).or_default() += events.values().map(body_size).sum::<i64>();
+        }
+
+        let today = Utc::now().date_naive();
+        let mut samples = self.storage_samples.lock().unwrap();
+        for (project_id, bytes) in &stored {
+            let peak = samples.entry((project_id.clone(), today)).or_default();
+            *peak = (*peak).max(*bytes);
+        }
+        Ok(stored.len() as u64)
+    }
+
+    async fn monthly_usage(&self, month: NaiveDate) -> Result<Vec<ProjectUsage>> {
+        let end = month + chrono::Months::new(1);
+        let mut projects: BTreeMap<String, ProjectUsage> = BTreeMap::new();
+        let mut appended: Vec<(String, Event)> = Vec::new();
+        for (_, stream) in self.all_streams() {
+            for stored in stream.read().unwrap().values() {
+                appended.push((stored.partition_key.clone(), stored.event.clone()));
+            }
+        }
+        for (stream_id, events) in self.archive.read().unwrap().iter() {
+            appended.extend(events.values().map(|event| (get_partition_key(stream_id), event.clone())));
+        }
+        for (project_id, event) in appended {
+            let day = event.created_at.date_naive();
+            if day >= month && day < end {
+                let project = projects.entry(project_id.clone()).or_insert_with(|| no_usage(&project_id));
+                project.events += 1;
+                project.bytes_appended += body_size(&event);
+            }
+        }
+
+        let mut samples: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+        for ((project_id, day), bytes) in self.storage_samples.lock().unwrap().iter() {
+            if *day >= month && *day < end {
+                samples.entry(project_id.clone()).or_default().push(*bytes);
+            }
+        }
+        for (project_id, bytes) in samples {
+            let project = projects.entry(project_id.clone()).or_insert_with(|| no_usage(&project_id));
+            project.avg_stored_bytes = bytes.iter().sum::<i64>() / bytes.len() as i64;
+            project.peak_stored_bytes = bytes.iter().copied().max().unwrap_or(0);
+        }
+
+        Ok(projects.into_values().collect())
+    }
+
+    async fn legal_hold_streams(&self) -> Result<Vec<String>> {
+        Ok(self
+            .metadata
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, m)| m.legal_hold)
+            .map(|(id, _)| id.clone())
+            .collect())
+    }
+
+    async fn stream_heads(&self, prefix: &str) -> Result<Vec<(String, i64)>> {
+        let mut heads: Vec<(String, i64)> = self
+            .all_streams()
+            .into_iter()
+            .filter(|(id, _)| id.starts_with(prefix))
+            .filter_map(|(id, stream)| {
+                let head = stream.read().unwrap().keys().next_back().copied();
+                head.map(|version| (id, version))
+            })
+            .collect();
+        heads.sort();
+        Ok(heads)
+    }
+
+    async fn archivable_ranges(&self, threshold: DateTime<Utc>) -> Result<Vec<ArchiveRange>> {
+        let snapshotted: Vec<String> = self.snapshots.read().unwrap().keys().cloned().collect();
+        let mut ranges = Vec::new();
+
+        for stream_id in snapshotted {
+            if self.on_legal_hold(&stream_id) {
+                continue;
+            }
+            let Some(stream) = self.stream(&stream_id) else {
+                continue;
+            };
+            let stream = stream.read().unwrap();
+            let Some(head) = stream.keys().next_back().copied() else {
+                continue;
+            };
+
+            let versions: Vec<i64> = stream
+                .range(..head)
+                .filter(|(_, e)| e.event.created_at < threshold)
+                .map(|(v, _)| *v)
+                .collect();
+            if let (Some(from), Some(to)) = (versions.first(), versions.last()) {
+                ranges.push(ArchiveRange {
+                    stream_id: stream_id.clone(),
+                    from_version: *from,
+                    to_version: *to,
+                });
+            }
+        }
+
+        ranges.sort_by(|a, b| a.stream_id.cmp(&b.stream_id));
+        Ok(ranges)
+    }
+
+    async fn archive_ranges(&self, ranges: &[ArchiveRange]) -> Result<u64> {
+        let mut archived = 0;
+
+        for range in ranges {
+            let Some(stream) = self.stream(&range.stream_id) else {
+                continue;
+            };
+            let mut stream = stream.write().unwrap();
+            let Some(head) = stream.keys().next_back().copied() else {
+                continue;
+            };
+
+            let to = range.to_version.min(head - 1);
+            if to < range.from_version {
+                continue;
+            }
+
+            let moved: Vec<i64> = stream.range(range.from_version..=to).map(|(v, _)| *v).collect();
+            let mut archive = self.archive.write().unwrap();
+            let archive = archive.entry(range.stream_id.clone()).or_default();
+            for v in moved {
+                if let Some(stored) = stream.remove(&v) {
+                    archive.insert(v, stored.event);
+                    archived += 1;
+                }
+            }
+        }
+
+        Ok(archived)
+    }
+
+    async fn read_archived(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<Vec<Event>> {
+        if from_version > to_version {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .archive
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .map(|archive| archive.range(from_version..=to_version).map(|(_, e)| e.clone()).collect())
+            .unwrap_or_default())
+    }
+
+    async fn rewrite_events(
+        &self,
+        stream_id: &str,
+        head: i64,
+        head_hash: Option<&str>,
+        rewrites: &[EventRewrite],
+    ) -> Result<u64> {
+        let Some(stream) = self.stream(stream_id) else {
+            check_rewrite_head(stream_id, (head, head_hash), (0, None))?;
+            return Ok(0);
+        };
+        let mut stream = stream.write().unwrap();
+        let current = stream.values().next_back().map_or((0, None), |stored| {
+            (stored.event.version, stored.event.chain_hash.as_deref())
+        });
+        check_rewrite_head(stream_id, (head, head_hash), current)?;
+        let mut archive = self.archive.write().unwrap();
+        let mut archived = archive.get_mut(stream_id);
+
+        let mut rewritten = 0;
+        for rewrite in rewrites {
+            let event = match stream.get_mut(&rewrite.version) {
+                Some(stored) => &mut stored.event,
+                None => match archived.as_mut().and_then(|a| a.get_mut(&rewrite.version)) {
+                    Some(event) => event,
+                    None => continue,
+                },
+            };
+            if let Some(data) = &rewrite.data {
+                event.data = data.clone();
+                event.payload = None;
+            }
+            event.checksum = rewrite.checksum.clone();
+            event.chain_hash = rewrite.chain_hash.clone();
+            rewritten += 1;
+        }
+        Ok(rewritten)
+    }
+
+    async fn delete_events_through(&self, stream_id: &str, version: i64) -> Result<u64> {
+        let Some(stream) = self.stream(stream_id) else {
+            return Ok(0);
+        };
+        let mut stream = stream.write().unwrap();
+        let Some(head) = stream.keys().next_back().copied() else {
+            return Ok(0);
+        };
+
+        let doomed: Vec<i64> = stream.range(..=version.min(head - 1)).map(|(v, _)| *v).collect();
+        for v in &doomed {
+            stream.remove(v);
+        }
+        Ok(doomed.len() as u64)
+    }
+
+    async fn committed_position(&self) -> Result<i64> {
+        Ok(*self.position.lock().unwrap())
+    }
+
+    async fn read_all(&self, after: i64, limit: i64) -> Result<Vec<Event>> {
+        let mut events: Vec<Event> = self
+            .all_streams()
+            .iter()
+            .flat_map(|(_, stream)| {
+                stream
+                    .read()
+                    .unwrap()
+                    .values()
+                    .filter(|e| e.event.position > after)
+                    .map(|e| e.event.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        events.sort_by_key(|e| e.position);
+        events.truncate(limit.max(0) as usize);
+        Ok(events)
+    }
+
+    async fn read_all_with_archive(&self, after: i64, limit: i64) -> Result<Vec<Event>> {
+        let mut events = self.read_all(after, i64::MAX).await?;
+        events.extend(
+            self.archive
+                .read()
+                .unwrap()
+                .values()
+                .flat_map(|stream| stream.values())
+                .filter(|e| e.position > after)
+                .cloned(),
+        );
+        events.sort_by_key(|e| e.position);
+        events.truncate(limit.max(0) as usize);
+        Ok(events)
+    }
+
+    async fn read_streams(
+        &self,
+        stream_ids: &[String],
+        after: MergeCursor,
+        order: MergeOrder,
+        limit: i64,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>> {
+        deadline.remaining()?;
+
+        let key = |e: &Event| match order {
+            MergeOrder::Position => (DateTime::UNIX_EPOCH, e.position),
+            MergeOrder::Timestamp => (e.created_at, e.position),
+        };
+        let after = match order {
+            MergeOrder::Position => (DateTime::UNIX_EPOCH, after.position),
+            MergeOrder::Timestamp => (after.created_at, after.position),
+        };
+        let mut events: Vec<Event> = stream_ids
+            .iter()
+            .filter_map(|stream_id| self.stream(stream_id))
+            .flat_map(|stream| {
+                stream
+                    .read()
+                    .unwrap()
+                    .values()
+                    .filter(|e| key(&e.event) > after)
+                    .map(|e| e.event.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        events.sort_by_key(key);
+        events.truncate(limit.max(0) as usize);
+        Ok(events)
+    }
+
+    async fn import_events(&self, events: &[Event]) -> Result<()> {
+        let mut position = self.position.lock().unwrap();
+        self.insert_verbatim(&mut position, events);
+        Ok(())
+    }
+
+    async fn replication_checkpoint(&self) -> Result<i64> {
+        let position = self.position.lock().unwrap();
+        Ok(self.replicated.lock().unwrap().unwrap_or(*position))
+    }
+
+    async fn apply_replicated(&self, after: i64, through: i64, events: &[Event]) -> Result<()> {
+        // Held throughout, so batches apply one at a time
+        let mut position = self.position.lock().unwrap();
+        let mut replicated = self.replicated.lock().unwrap();
+
+        let checkpoint = replicated.unwrap_or(*position);
+        if checkpoint != after {
+            return Err(AppError::Conflict(format!(
+                "Replication checkpoint is at {}, not {}",
+                checkpoint, after
+            )));
+        }
+
+        self.insert_verbatim(&mut position, events);
+        *position = (*position).max(through);
+        *replicated = Some(through);
+        Ok(())
+    }
+
+    async fn import_stream(&self, stream_id: &str, events: Vec<Event>) -> Result<Vec<Event>> {
+        let Some(first) = events.first() else {
+            return Ok(events);
+        };
+
+        let stream = self.stream_or_create(stream_id);
+        let mut stream = stream.write().unwrap();
+
+        let current_version = stream.keys().next_back().copied().unwrap_or(0);
+        if first.version != current_version + 1 {
+            return Err(AppError::Conflict(format!(
+                "Import starts at version {} but {} is at version {}",
+                first.version, stream_id, current_version
+            )));
+        }
+
+        let mut position = self.position.lock().unwrap();
+        let mut stored = Vec::with_capacity(events.len());
+        for mut event in events {
+            *position += 1;
+            event.position = *position;
+            stream.insert(
+                event.version,
+                StoredEvent {
+                    event: event.clone(),
+                    partition_key: get_partition_key(stream_id),
+                    tenant_id: get_partition_key(stream_id),
+                    },
+            );
+            stored.push(event);
+        }
+
+        Ok(stored)
+    }
+
+    async fn event_type_deprecations(&self) -> Result<Vec<EventTypeDeprecation>> {
+        Ok(self.deprecations.read().unwrap().values().cloned().collect())
+    }
+
+    async fn set_event_type_deprecation(&self, deprecation: &EventTypeDeprecation) -> Result<()> {
+        self.deprecations
+            .write()
+            .unwrap()
+            .insert(deprecation.event_type.clone(), deprecation.clone());
+        Ok(())
+    }
+
+    async fn remove_event_type_deprecation(&self, event_type: &str) -> Result<bool> {
+        Ok(self.deprecations.write().unwrap().remove(event_type).is_some())
+    }
+
+    async fn streams_with_event_type(&self, event_type: &str) -> Result<Vec<String>> {
+        let mut stream_ids: Vec<String> = self
+            .all_streams()
+            .into_iter()
+            .filter(|(_, stream)| {
+                stream
+                    .read()
+                    .unwrap()
+                    .values()
+                    .any(|e| e.event.event_type == event_type)
+            })
+            .map(|(id, _)| id)
+            .collect();
+        stream_ids.sort();
+        Ok(stream_ids)
+    }
+
+    async fn api_keys(&self) -> Result<Vec<(String, ApiKey)>> {
+        let mut keys: Vec<(String, ApiKey)> = self
+            .api_keys
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(hash, key)| (hash.clone(), key.clone()))
+            .collect();
+        keys.sort_by_key(|(_, key)| key.created_at);
+        Ok(keys)
+    }
+
+    async fn insert_api_key(&self, key_hash: &str, key: &ApiKey) -> Result<()> {
+        self.api_keys
+            .write()
+            .unwrap()
+            .insert(key_hash.to_string(), key.clone());
+        Ok(())
+    }
+
+    async fn revoke_api_key(&self, id: Uuid) -> Result<bool> {
+        let mut keys = self.api_keys.write().unwrap();
+        let before = keys.len();
+        keys.retain(|_, key| key.id != id);
+        Ok(keys.len() < before)
+    }
+
+    async fn purgeable_events(&self, purge: &Purge) -> Result<Vec<(String, u64)>> {
+        let mut counts = Vec::new();
+        for (stream_id, _) in self.all_streams() {
+            let (hot, archived) = self.purgeable_versions(purge, &stream_id);
+            let events = (hot.len() + archived.len()) as u64;
+            if events > 0 {
+                counts.push((stream_id, events));
+            }
+        }
+        counts.sort();
+        Ok(counts)
+    }
+
+    async fn purge_events(&self, purge: &Purge, limit: i64) -> Result<u64> {
+        let mut purged = 0;
+        for (stream_id, stream) in self.all_streams() {
+            let (hot, archived) = self.purgeable_versions(purge, &stream_id);
+            if let Some(archive) = self.archive.write().unwrap().get_mut(&stream_id) {
+                for version in archived.iter().take((limit - purged) as usize) {
+                    purged += archive.remove(version).is_some() as i64;
+                }
+            }
+            let mut stream = stream.write().unwrap();
+            for version in hot.iter().take((limit - purged) as usize) {
+                purged += stream.remove(version).is_some() as i64;
+            }
+            if purged >= limit {
+                break;
+            }
+        }
+        Ok(purged as u64)
+    }
+
+    async fn scavenge_truncated(&self, limit: i64) -> Result<u64> {
+        let truncated: Vec<(String, i64)> = self
+            .metadata
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, m)| !m.legal_hold)
+            .filter_map(|(id, m)| m.truncate_before.map(|before| (id.clone(), before)))
+            .collect();
+
+        let mut scavenged = 0;
+        for (stream_id, before) in truncated {
+            if let Some(archive) = self.archive.write().unwrap().get_mut(&stream_id) {
+                let doomed: Vec<i64> = archive.range(..before).map(|(v, _)| *v).take((limit - scavenged) as usize).collect();
+                for version in doomed {
+                    archive.remove(&version);
+                    scavenged += 1;
+                }
+            }
+            if let Some(stream) = self.stream(&stream_id) {
+                let mut stream = stream.write().unwrap();
+                let Some(head) = stream.keys().next_back().copied() else {
+                    continue;
+                };
+                let doomed: Vec<i64> = stream
+                    .range(..before.min(head))
+                    .map(|(v, _)| *v)
+                    .take((limit - scavenged) as usize)
+                    .collect();
+                for version in doomed {
+                    stream.remove(&version);
+                    scavenged += 1;
+                }
+            }
+            if scavenged >= limit {
+                break;
+            }
+        }
+        Ok(scavenged as u64)
+    }
+
+    async fn scavenge_expired(&self, now: DateTime<Utc>, limit: i64) -> Result<u64> {
+        let expired: Vec<(String, Vec<i64>)> = self
+            .expiries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(stream_id, _)| !self.on_legal_hold(stream_id))
+            .map(|(stream_id, expiries)| {
+                let versions = expiries.iter().filter(|(_, at)| **at < now).map(|(v, _)| *v).collect();
+                (stream_id.clone(), versions)
+            })
+            .collect();
+
+        let mut scavenged = 0;
+        for (stream_id, versions) in expired {
+            let head = self
+                .stream(&stream_id)
+                .and_then(|stream| stream.read().unwrap().keys().next_back().copied());
+            for version in versions.into_iter().filter(|v| Some(*v) != head) {
+                if scavenged >= limit {
+                    return Ok(scavenged as u64);
+                }
+                let archived = self
+                    .archive
+                    .write()
+                    .unwrap()
+                    .get_mut(&stream_id)
+                    .and_then(|archive| archive.remove(&version))
+                    .is_some();
+                let hot = !archived
+                    && self
+                        .stream(&stream_id)
+                        .and_then(|stream| stream.write().unwrap().remove(&version))
+                        .is_some();
+                if let Some(expiries) = self.expiries.write().unwrap().get_mut(&stream_id) {
+                    expiries.remove(&version);
+                }
+                scavenged += (archived || hot) as i64;
+            }
+        }
+        Ok(scavenged as u64)
+    }
+
+    async fn retention_rules(&self) -> Result<Vec<RetentionRule>> {
+        Ok(self.retention_rules.read().unwrap().values().cloned().collect())
+    }
+
+    async fn set_retention_rule(&self, rule: &RetentionRule) -> Result<()> {
+        self.retention_rules
+            .write()
+            .unwrap()
+            .insert(rule.name.clone(), rule.clone());
+        Ok(())
+    }
+
+    async fn remove_retention_rule(&self, name: &str) -> Result<bool> {
+        Ok(self.retention_rules.write().unwrap().remove(name).is_some())
+    }
+
+    async fn snapshot_reducers(&self) -> Result<Vec<SnapshotReducer>> {
+        Ok(self.snapshot_reducers.read().unwrap().values().cloned().collect())
+    }
+
+    async fn set_snapshot_reducer(&self, reducer: &SnapshotReducer) -> Result<()> {
+        self.snapshot_reducers
+            .write()
+            .unwrap()
+            .insert(reducer.name.clone(), reducer.clone());
+        Ok(())
+    }
+
+    async fn remove_snapshot_reducer(&self, name: &str) -> Result<bool> {
+        Ok(self.snapshot_reducers.write().unwrap().remove(name).is_some())
+    }
+
+    async fn plugins(&self) -> Result<Vec<(Plugin, Vec<u8>)>> {
+        Ok(self.plugins.read().unwrap().values().cloned().collect())
+    }
+
+    async fn set_plugin(&self, plugin: &Plugin, module: &[u8]) -> Result<()> {
+        self.plugins
+            .write()
+            .unwrap()
+            .insert(plugin.name.clone(), (plugin.clone(), module.to_vec()));
+        Ok(())
+    }
+
+    async fn remove_plugin(&self, name: &str) -> Result<bool> {
+        self.projections.write().unwrap().remove(name);
+        Ok(self.plugins.write().unwrap().remove(name).is_some())
+    }
+
+    async fn projection(&self, name: &str) -> Result<Option<ProjectionState>> {
+        Ok(self.projections.read().unwrap().get(name).cloned())
+    }
+
+    async fn set_projection(&self, projection: &ProjectionState) -> Result<()> {
+        self.projections
+            .write()
+            .unwrap()
+            .insert(projection.name.clone(), projection.clone());
+        Ok(())
+    }
+
+    async fn add_dead_letters(&self, letters: &[DeadLetter]) -> Result<()> {
+        let mut dead_letters = self.dead_letters.write().unwrap();
+        for letter in letters {
+            dead_letters.insert(letter.id, letter.clone());
+        }
+        Ok(())
+    }
+
+    async fn dead_letters(&self, source: Option<&str>, limit: i64) -> Result<Vec<DeadLetter>> {
+        let mut letters: Vec<DeadLetter> = self
+            .dead_letters
+            .read()
+            .unwrap()
+            .values()
+            .filter(|l| source.map_or(true, |source| l.source == source))
+            .cloned()
+            .collect();
+        letters.sort_by_key(|l| l.created_at);
+        letters.truncate(limit.max(0) as usize);
+        Ok(letters)
+    }
+
+    async fn dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>> {
+        Ok(self.dead_letters.read().unwrap().get(&id).cloned())
+    }
+
+    async fn set_dead_letter(&self, letter: &DeadLetter) -> Result<()> {
+        self.dead_letters.write().unwrap().insert(letter.id, letter.clone());
+        Ok(())
+    }
+
+    async fn remove_dead_letter(&self, id: Uuid) -> Result<bool> {
+        Ok(self.dead_letters.write().unwrap().remove(&id).is_some())
+    }
+
+    async fn encryption_policies(&self) -> Result<Vec<EncryptionPolicy>> {
+        Ok(self.encryption_policies.read().unwrap().values().cloned().collect())
+    }
+
+    async fn set_encryption_policy(&self, policy: &EncryptionPolicy) -> Result<()> {
+        self.encryption_policies
+            .write()
+            .unwrap()
+            .insert(policy.event_type.clone(), policy.clone());
+        Ok(())
+    }
+
+    async fn remove_encryption_policy(&self, event_type: &str) -> Result<bool> {
+        Ok(self.encryption_policies.write().unwrap().remove(event_type).is_some())
+    }
+
+    async fn subject_key(&self, subject_id: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.subject_keys.read().unwrap().get(subject_id).cloned())
+    }
+
+    async fn insert_subject_key(&self, subject_id: &str, key: &[u8]) -> Result<Vec<u8>> {
+        Ok(self
+            .subject_keys
+            .write()
+            .unwrap()
+            .entry(subject_id.to_string())
+            .or_insert_with(|| key.to_vec())
+            .clone())
+    }
+
+    async fn delete_subject_key(&self, subject_id: &str) -> Result<bool> {
+        Ok(self.subject_keys.write().unwrap().remove(subject_id).is_some())
+    }
+
+    async fn chain_anchors(&self, limit: i64) -> Result<Vec<ChainAnchor>> {
+        let anchors = self.chain_anchors.read().unwrap();
+        Ok(anchors.iter().rev().take(limit.max(0) as usize).cloned().collect())
+    }
+
+    async fn add_chain_anchor(&self, anchor: &ChainAnchor) -> Result<()> {
+        self.chain_anchors.write().unwrap().push(anchor.clone());
+        Ok(())
+    }
+
+    async fn namespaces(&self) -> Result<Vec<Namespace>> {
+        Ok(self.namespaces.read().unwrap().values().cloned().collect())
+    }
+
+    async fn insert_namespace(&self, namespace: &Namespace) -> Result<bool> {
+        let mut namespaces = self.namespaces.write().unwrap();
+        let id = namespace.id();
+        if namespaces.contains_key(&id) {
+            return Ok(false);
+        }
+        namespaces.insert(id, namespace.clone());
+        Ok(true)
+    }
+
+    async fn remove_namespace(&self, id: &str) -> Result<u64> {
+        let prefix = format!("{}/", id);
+        let mut namespaces = self.namespaces.write().unwrap();
+        let before = namespaces.len();
+        namespaces.retain(|key, _| key != id && !key.starts_with(&prefix));
+        Ok((before - namespaces.len()) as u64)
+    }
+
+    async fn subscription_groups(&self) -> Result<Vec<SubscriptionGroup>> {
+        Ok(self.subscription_groups.read().unwrap().values().cloned().collect())
+    }
+
+    async fn set_subscription_group(&self, group: &SubscriptionGroup) -> Result<()> {
+        self.subscription_groups
+            .write()
+            .unwrap()
+            .insert(group.name.clone(), group.clone());
+        Ok(())
+    }
+
+    async fn remove_subscription_group(&self, name: &str) -> Result<bool> {
+        Ok(self.subscription_groups.write().unwrap().remove(name).is_some())
+    }
+}
@@ -0,0 +1,804 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result, VersionConflict};
+use crate::models::{
+    ApiKey, ChainAnchor, DeadLetter, EncryptionPolicy, Event, EventTypeDeprecation, Granularity, Namespace, Plugin,
+    ProjectionState, RetentionRule, Snapshot, SnapshotReducer, StreamMetadata, StreamPrecondition, SubscriptionGroup,
+};
+
+#[cfg(feature = "chaos")]
+mod chaos;
+mod compression;
+mod group_commit;
+mod memory;
+mod postgres;
+mod retry;
+mod sqlite;
+mod version_cache;
+
+#[cfg(feature = "chaos")]
+pub use chaos::ChaosStorage;
+pub use compression::{decompress_data, EventCompressor};
+pub use memory::MemoryStorage;
+pub use postgres::PostgresStorage;
+pub use retry::RetryPolicy;
+pub use sqlite::SqliteStorage;
+
+/// Events per query when `scan_stream` falls back to paging.
+const SCAN_PAGE_SIZE: i64 = 1_000;
+
+/// An event ready to be persisted. Identity and timestamp are assigned by the
+/// caller; the storage backend assigns the stream version.
+#[derive(Debug, Clone)]
+pub struct NewEvent {
+    pub id: Uuid,
+    pub stream_id: String,
+    pub event_type: String,
+    pub data: Option<serde_json::Value>,
+    pub payload: Option<Vec<u8>>,
+    pub content_type: String,
+    pub metadata: Option<serde_json::Value>,
+    /// Owner of the stream; see `auth::Tenant`.
+    pub tenant_id: String,
+    pub partition_key: String,
+    pub created_at: DateTime<Utc>,
+    /// `integrity::checksum` of the body as given here.
+    pub checksum: String,
+    /// When the event's `ttl_seconds` runs out; see `scavenge_expired`.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Heads of other streams the append requires, checked while they are
+    /// locked against writes in the append's transaction.
+    pub preconditions: Vec<StreamPrecondition>,
+}
+
+/// The conflict an append reports when a stream in its preconditions is
+/// at `current_version` instead.
+fn check_precondition(precondition: &StreamPrecondition, current_version: i64) -> Result<()> {
+    if precondition.version == current_version {
+        return Ok(());
+    }
+    Err(AppError::VersionConflict(Box::new(VersionConflict {
+        stream_id: precondition.stream_id.clone(),
+        expected_version: precondition.version,
+        current_version,
+        events: None,
+    })))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadDirection {
+    Forward,
+    Backward,
+}
+
+/// How `read_streams` orders the events it merges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeOrder {
+    Position,
+    Timestamp,
+}
+
+/// Where a merged read resumes: after the event created at `created_at`
+/// with `position`. Reads in position order only compare positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MergeCursor {
+    pub created_at: DateTime<Utc>,
+    pub position: i64,
+}
+
+impl MergeCursor {
+    pub const START: MergeCursor = MergeCursor {
+        created_at: DateTime::UNIX_EPOCH,
+        position: 0,
+    };
+
+    pub fn after(event: &Event) -> Self {
+        Self {
+            created_at: event.created_at,
+            position: event.position,
+        }
+    }
+}
+
+/// Streams whose head has moved far enough past their last snapshot.
+#[derive(Debug, Clone)]
+pub struct SnapshotCandidate {
+    pub stream_id: String,
+    pub current_version: i64,
+    /// Version of the latest snapshot, or 0 when there is none.
+    pub snapshot_version: i64,
+}
+
+/// Per-project (partition_key) footprint of the event log.
+#[derive(Debug, Clone)]
+pub struct ProjectSummary {
+    pub project_id: String,
+    pub stream_count: i64,
+    pub event_count: i64,
+    pub oldest_event_at: Option<DateTime<Utc>>,
+    pub newest_event_at: Option<DateTime<Utc>>,
+}
+
+/// A project's billable usage over one calendar month (UTC).
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectUsage {
+    pub project_id: String,
+    /// Events appended in the month.
+    pub events: i64,
+    /// Body bytes of the events appended in the month.
+    pub bytes_appended: i64,
+    /// Mean of the daily peaks of bytes stored, over the days sampled.
+    pub avg_stored_bytes: i64,
+    pub peak_stored_bytes: i64,
+}
+
+/// Versions of a stream that are old enough to leave the local store. Never
+/// includes the stream head, which stays local so versioning continues.
+#[derive(Debug, Clone)]
+pub struct ArchiveRange {
+    pub stream_id: String,
+    pub from_version: i64,
+    pub to_version: i64,
+}
+
+/// New hashes for one stored event, and a new JSON body when it changed;
+/// see `EventStorage::rewrite_events`.
+#[derive(Debug, Clone)]
+pub struct EventRewrite {
+    pub version: i64,
+    /// `None` keeps the stored body.
+    pub data: Option<serde_json::Value>,
+    pub checksum: Option<String>,
+    pub chain_hash: Option<String>,
+}
+
+/// The conflict `rewrite_events` reports when the stream's head is no
+/// longer the `expected` version and chain hash the rewrites were computed
+/// from.
+fn check_rewrite_head(stream_id: &str, expected: (i64, Option<&str>), current: (i64, Option<&str>)) -> Result<()> {
+    if expected == current {
+        return Ok(());
+    }
+    Err(AppError::Conflict(format!(
+        "Stream {} moved to version {} while the rewrite was computed from version {}; retry",
+        stream_id, current.0, expected.0
+    )))
+}
+
+/// Events a retention rule removes: those created before `before` that
+/// `rule` matches and no more specific rule in `except` does. Streams under
+/// legal hold and stream heads are always kept.
+#[derive(Debug, Clone)]
+pub struct Purge {
+    pub rule: RetentionRule,
+    pub except: Vec<RetentionRule>,
+    pub before: DateTime<Utc>,
+}
+
+impl Purge {
+    pub fn matches(&self, stream_id: &str, event_type: &str) -> bool {
+        self.rule.matches(stream_id, event_type) && !self.except.iter().any(|r| r.matches(stream_id, event_type))
+    }
+
+    /// SQL conditions on `e.stream_id` and `e.event_type` selecting the
+    /// rule's events minus the exceptions, using numbered placeholders
+    /// (`$2`, or `?2` for SQLite) from `first_param` on, with the values to
+    /// bind to them in order.
+    fn conditions(&self, marker: char, first_param: usize) -> (String, Vec<String>) {
+        let mut params = Vec::new();
+        let mut placeholder = |value: &str| {
+            params.push(value.to_string());
+            format!("{}{}", marker, first_param + params.len() - 1)
+        };
+        let mut rule_sql = |rule: &RetentionRule| {
+            let mut sql = match rule.stream_prefix() {
+                // substr is 1-based in both dialects and avoids LIKE escaping
+                Some(prefix) => {
+                    let p = placeholder(prefix);
+                    format!("substr(e.stream_id, 1, length({p})) = {p}", p = p)
+                }
+                None => format!("e.stream_id = {}", placeholder(&rule.stream_pattern)),
+            };
+            if let Some(event_type) = &rule.event_type {
+                sql.push_str(&format!(" AND e.event_type = {}", placeholder(event_type)));
+            }
+            sql
+        };
+
+        let mut sql = format!("({})", rule_sql(&self.rule));
+        for except in &self.except {
+            sql.push_str(&format!(" AND NOT ({})", rule_sql(except)));
+        }
+        (sql, params)
+    }
+}
+
+/// Versioned migrations recorded as applied, those not yet run, and any
+/// applied by a newer build that this one doesn't know.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MigrationStatus {
+    pub applied: Vec<i64>,
+    pub pending: Vec<i64>,
+    pub unknown: Vec<i64>,
+}
+
+impl MigrationStatus {
+    fn new(migrator: &Migrator, applied: Vec<i64>) -> Self {
+        let known: Vec<i64> = migrator.iter().map(|migration| migration.version).collect();
+        let pending = known.iter().copied().filter(|version| !applied.contains(version)).collect();
+        let unknown = applied.iter().copied().filter(|version| !known.contains(version)).collect();
+        Self {
+            applied,
+            pending,
+            unknown,
+        }
+    }
+}
+
+/// Days covered by `StoreStats::events_per_day`, today included.
+pub const STATS_DAYS: i64 = 30;
+/// How far back hourly usage is kept; daily usage is kept for good.
+pub const MAX_HOURLY_DAYS: i64 = 90;
+
+#[derive(Debug, Clone, Default)]
+pub struct StoreStats {
+    pub total_events: i64,
+    pub total_streams: i64,
+    pub total_snapshots: i64,
+    /// Bytes on disk of events, archived events and snapshots, indexes
+    /// included, where the backend can tell.
+    pub storage_bytes: Option<i64>,
+    /// Events appended per UTC day over the last `STATS_DAYS` days, oldest
+    /// first; days without appends are left out.
+    pub events_per_day: Vec<DailyCount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyCount {
+    pub day: NaiveDate,
+    pub events: i64,
+}
+
+/// Usage in one bucket of a time series, from its start.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageBucket {
+    pub bucket: DateTime<Utc>,
+    pub events: i64,
+    /// Stored size of the event bodies appended.
+    pub bytes: i64,
+    /// Streams appended to in the bucket.
+    pub active_streams: i64,
+}
+
+/// An event's searchable text, as the search indexer extracts it.
+#[derive(Debug, Clone)]
+pub struct SearchDocument {
+    pub position: i64,
+    pub stream_id: String,
+    pub version: i64,
+    pub content: String,
+}
+
+/// An event matching a full-text search.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub event: Event,
+    /// Fragments of the indexed text around the matches, with each
+    /// matched term wrapped in `<mark></mark>`. Not otherwise escaped.
+    pub highlight: String,
+}
+
+/// First day covered by `StoreStats::events_per_day`.
+pub fn stats_since() -> NaiveDate {
+    Utc::now().date_naive() - chrono::Days::new(STATS_DAYS as u64 - 1)
+}
+
+/// Persistence operations the HTTP handlers and background tasks depend on.
+///
+/// Implementations must enforce optimistic concurrency in `append`: when
+/// `expected_version` is given and doesn't match the stream head, return
+/// `AppError::Conflict` without writing. Likewise, when a stream in the
+/// event's `preconditions` isn't at its version, return the
+/// `AppError::VersionConflict` of `check_precondition`, and keep that
+/// stream from being appended to until the append commits.
+#[async_trait]
+pub trait EventStorage: Send + Sync {
+    /// Creates or upgrades the backend's schema.
+    async fn migrate(&self) -> Result<()>;
+
+    async fn append(
+        &self,
+        event: NewEvent,
+        expected_version: Option<i64>,
+        deadline: Deadline,
+    ) -> Result<Event>;
+
+    /// Up to `limit` events of a stream starting at `from_version`: that
+    /// version and later ones in ascending order going forward, or that
+    /// version and earlier ones in descending order going backward, so a
+    /// backward read from `i64::MAX` starts at the head.
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        limit: i64,
+        direction: ReadDirection,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>>;
+
+    /// Sends the events of a stream with versions in `[from_version,
+    /// to_version]` to `sink` in version order as they are read, so large
+    /// ranges never sit in memory at once. Stops early, without error, when
+    /// the receiver goes away. The default pages through `read_stream`.
+    async fn scan_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        to_version: i64,
+        sink: mpsc::Sender<Event>,
+    ) -> Result<()> {
+        let mut next = from_version;
+        while next <= to_version {
+            let page = self
+                .read_stream(
+                    stream_id,
+                    next,
+                    (to_version - next + 1).min(SCAN_PAGE_SIZE),
+                    ReadDirection::Forward,
+                    Deadline(None),
+                )
+                .await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            next = last.version + 1;
+
+            for event in page {
+                if sink.send(event).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The newest `count` events of a stream, returned oldest first.
+    async fn read_latest(&self, stream_id: &str, count: i64, deadline: Deadline) -> Result<Vec<Event>>;
+
+    /// The event with `id` on a stream, if it was appended at or after
+    /// `since`. Backs deduplication of client-supplied event ids.
+    async fn find_event(&self, stream_id: &str, id: Uuid, since: DateTime<Utc>) -> Result<Option<Event>>;
+
+    /// Highest version of a stream created at or before `at`, counting
+    /// archived events; 0 when there is none.
+    async fn version_at(&self, stream_id: &str, at: DateTime<Utc>) -> Result<i64>;
+
+    /// Number of events of a stream with versions in `[from_version,
+    /// to_version]`, hot or archived. Events tiered out to cold storage are
+    /// not counted.
+    async fn count_events(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<i64>;
+
+    /// Current head version of a stream, 0 when it has no events.
+    async fn stream_version(&self, stream_id: &str) -> Result<i64>;
+
+    /// JSON payloads of a stream with versions in `[from_version,
+    /// up_to_version]`, in order.
+    async fn load_stream_data(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        up_to_version: i64,
+    ) -> Result<Vec<serde_json::Value>>;
+
+    /// Stores a snapshot, replacing any existing one at the same version.
+    async fn replace_snapshot(&self, snapshot: &Snapshot) -> Result<()>;
+
+    /// Stores a snapshot unless one already exists at the same version.
+    async fn insert_snapshot(&self, snapshot: &Snapshot) -> Result<()>;
+
+    async fn latest_snapshot(&self, stream_id: &str) -> Result<Option<Snapshot>>;
+
+    /// The newest snapshot of a stream at or before `version`.
+    async fn snapshot_at(&self, stream_id: &str, version: i64) -> Result<Option<Snapshot>>;
+
+    /// Versions of a stream's snapshots, oldest first.
+    async fn snapshot_versions(&self, stream_id: &str) -> Result<Vec<i64>>;
+
+    async fn delete_snapshots(&self, stream_id: &str, versions: &[i64]) -> Result<u64>;
+
+    async fn snapshot_candidates(&self, threshold: i64) -> Result<Vec<SnapshotCandidate>>;
+
+    /// Per stream, the local events created before `threshold` that may be
+    /// archived or moved to cold storage: snapshotted streams only, no legal
+    /// holds, and never the stream head.
+    async fn archivable_ranges(&self, threshold: DateTime<Utc>) -> Result<Vec<ArchiveRange>>;
+
+    /// Moves the events of each range from the hot table into the archive,
+    /// where reads only see them when asked to. Backends with time
+    /// partitions then drop past months left empty. Returns the number of
+    /// events moved.
+    async fn archive_ranges(&self, ranges: &[ArchiveRange]) -> Result<u64>;
+
+    /// Archived events of a stream with versions in `[from_version,
+    /// to_version]`, in version order.
+    async fn read_archived(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<Vec<Event>>;
+
+    /// Overwrites the checksum and chain hash of stored events, hot or
+    /// archived, and the body of those given one, which is then kept
+    /// uncompressed and dropped from the search index. All or nothing, and
+    /// with appends to the stream held off: fails with a conflict unless
+    /// its head is still at `head` with chain hash `head_hash`, the state
+    /// the rewrites were computed from. Returns the number of events
+    /// rewritten.
+    async fn rewrite_events(
+        &self,
+        stream_id: &str,
+        head: i64,
+        head_hash: Option<&str>,
+        rewrites: &[EventRewrite],
+    ) -> Result<u64>;
+
+    /// Removes local events up to and including `version`, keeping the stream
+    /// head. Returns the number of events deleted.
+    async fn delete_events_through(&self, stream_id: &str, version: i64) -> Result<u64>;
+
+    /// Pre-creates storage partitions for the current month and the next
+    /// `months_ahead` months. A no-op for backends without partitions.
+    async fn prepare_partitions(&self, _months_ahead: u32) -> Result<()> {
+        Ok(())
+    }
+
+    /// Folds up to `limit` more committed events into the backend's running
+    /// statistics; returns how many it took. A no-op for backends that
+    /// count on demand.
+    async fn roll_up_stats(&self, _limit: i64) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// Position up to which events have been given to the full-text index.
+    /// Always 0 for backends without full-text search.
+    async fn search_position(&self) -> Result<i64> {
+        Ok(0)
+    }
+
+    /// Adds `documents` to the full-text index, skipping any already there,
+    /// and moves its position up to `through`. A no-op for backends without
+    /// full-text search.
+    async fn index_search_documents(&self, _documents: &[SearchDocument], _through: i64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Events whose indexed text matches `query`, in web search syntax
+    /// (quoted phrases, `or`, `-term`), newest first and below position
+    /// `before`, of one tenant or the whole store. Events no longer stored
+    /// are left out.
+    async fn search_events(
+        &self,
+        _tenant_id: Option<&str>,
+        _query: &str,
+        _before: i64,
+        _limit: i64,
+    ) -> Result<Vec<SearchHit>> {
+        Ok(Vec::new())
+    }
+
+    /// Drops the index entries of events since deleted, purged or moved to
+    /// cold storage; returns how many.
+    async fn prune_search_index(&self) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// Which schema migrations have run. Backends without a schema have
+    /// nothing pending.
+    async fn migration_status(&self) -> Result<MigrationStatus> {
+        Ok(MigrationStatus::default())
+    }
+
+    /// Round-trips to the backend; used by the readiness probe.
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Takes the leader lease for `lease` if it is free or expired, or
+    /// renews it if `holder` has it, returning the holder's epoch; `None`
+    /// while another instance holds it. With fencing on, writes are refused
+    /// from then on unless made under the epoch last returned. Always
+    /// `None` for backends without leader election.
+    async fn acquire_leadership(&self, _holder: &str, _lease: Duration) -> Result<Option<i64>> {
+        Ok(None)
+    }
+
+    /// Seconds the slowest replica is behind this database, or `None` for
+    /// backends without replicas.
+    async fn replication_lag(&self) -> Result<Option<f64>> {
+        Ok(None)
+    }
+
+    /// Open and idle connections in the backend's pool, or `None` for
+    /// backends without one.
+    fn pool_connections(&self) -> Option<(u32, usize)> {
+        None
+    }
+
+    /// Store-wide totals. Backends may estimate them, or count as of the
+    /// last `roll_up_stats`, rather than scan every event.
+    async fn stats(&self) -> Result<StoreStats>;
+
+    /// Whether the store holds no events, hot or archived. Exact, unlike
+    /// `stats`.
+    async fn is_empty(&self) -> Result<bool>;
+
+    /// `stats` restricted to the streams of one tenant.
+    async fn tenant_stats(&self, tenant_id: &str) -> Result<StoreStats>;
+
+    /// Appends in `granularity` buckets starting at or after `since`,
+    /// oldest first, of one tenant or the whole store. Buckets without
+    /// appends are left out.
+    async fn usage_timeseries(
+        &self,
+        tenant_id: Option<&str>,
+        granularity: Granularity,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UsageBucket>>;
+
+    async fn stream_metadata(&self, stream_id: &str) -> Result<Option<StreamMetadata>>;
+
+    async fn all_stream_metadata(&self) -> Result<Vec<(String, StreamMetadata)>>;
+
+    async fn set_stream_metadata(&self, stream_id: &str, metadata: &StreamMetadata) -> Result<()>;
+
+    async fn project_summaries(&self) -> Result<Vec<ProjectSummary>>;
+
+    /// Records the bytes each project stores now, hot and archived events
+    /// alike, as today's sample; a day keeps its highest. Returns how many
+    /// projects were sampled.
+    async fn sample_storage_usage(&self) -> Result<u64>;
+
+    /// Usage of every project active in the month starting on `month`, by
+    /// project id.
+    async fn monthly_usage(&self, month: NaiveDate) -> Result<Vec<ProjectUsage>>;
+
+    /// Ids of all streams whose metadata places them under legal hold.
+    async fn legal_hold_streams(&self) -> Result<Vec<String>>;
+
+    /// Streams whose id starts with `prefix` and their head versions, in
+    /// stream id order.
+    async fn stream_heads(&self, prefix: &str) -> Result<Vec<(String, i64)>>;
+
+    /// Highest global position at or below which every event is committed.
+    /// Readers that stop here never miss an event that commits later with a
+    /// lower position.
+    async fn committed_position(&self) -> Result<i64>;
+
+    /// Events across all streams with `position > after`, in position order.
+    async fn read_all(&self, after: i64, limit: i64) -> Result<Vec<Event>>;
+
+    /// Like `read_all`, but also returns the events the archiver moved into
+    /// the archive, merged by position. Events tiered out to cold storage are
+    /// not included.
+    async fn read_all_with_archive(&self, after: i64, limit: i64) -> Result<Vec<Event>>;
+
+    /// Up to `limit` events of any of `stream_ids` past `after`, merged in
+    /// `order`; ties on a timestamp are broken by position. Hot events
+    /// only, like `read_all`.
+    async fn read_streams(
+        &self,
+        stream_ids: &[String],
+        after: MergeCursor,
+        order: MergeOrder,
+        limit: i64,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>>;
+
+    /// Inserts events verbatim, keeping their ids, versions and positions.
+    /// Used by restores into an empty store.
+    async fn import_events(&self, events: &[Event]) -> Result<()>;
+
+    /// Position up to which this store holds a primary's events: the last
+    /// replicated batch's `through`, or before the first one the highest
+    /// position stored here.
+    async fn replication_checkpoint(&self) -> Result<i64>;
+
+    /// Stores a batch replicated from a primary verbatim, like
+    /// `import_events`, and moves the checkpoint from `after` to `through`
+    /// in the same transaction. A checkpoint other than `after` means the
+    /// batch was already applied or one was missed: `AppError::Conflict`.
+    async fn apply_replicated(&self, after: i64, through: i64, events: &[Event]) -> Result<()>;
+
+    /// Appends events to one stream keeping their ids, versions and
+    /// timestamps but assigning new global positions. The first version must
+    /// directly follow the stream head, otherwise `AppError::Conflict`.
+    /// Returns the stored events.
+    async fn import_stream(&self, stream_id: &str, events: Vec<Event>) -> Result<Vec<Event>>;
+
+    /// Loads a batch of events spanning any number of streams in one
+    /// transaction, with the same rules as `import_stream` applied to each
+    /// stream. Events of a stream must be contiguous and in version order.
+    /// Returns the number of events written.
+    async fn bulk_load(&self, events: Vec<Event>) -> Result<u64> {
+        let mut streams: Vec<(String, Vec<Event>)> = Vec::new();
+        for event in events {
+            match streams.iter_mut().find(|(id, _)| *id == event.stream_id) {
+                Some((_, stream)) => stream.push(event),
+                None => streams.push((event.stream_id.clone(), vec![event])),
+            }
+        }
+
+        let mut loaded = 0;
+        for (stream_id, events) in streams {
+            loaded += self.import_stream(&stream_id, events).await?.len() as u64;
+        }
+        Ok(loaded)
+    }
+
+    async fn event_type_deprecations(&self) -> Result<Vec<EventTypeDeprecation>>;
+
+    async fn set_event_type_deprecation(&self, deprecation: &EventTypeDeprecation) -> Result<()>;
+
+    /// Lifts a deprecation; returns false if the event type wasn't deprecated.
+    async fn remove_event_type_deprecation(&self, event_type: &str) -> Result<bool>;
+
+    /// Ids of all streams containing at least one event of `event_type`.
+    async fn streams_with_event_type(&self, event_type: &str) -> Result<Vec<String>>;
+
+    /// All API keys with the hash of their secret.
+    async fn api_keys(&self) -> Result<Vec<(String, ApiKey)>>;
+
+    async fn insert_api_key(&self, key_hash: &str, key: &ApiKey) -> Result<()>;
+
+    /// Deletes a key; returns false if no key had that id.
+    async fn revoke_api_key(&self, id: Uuid) -> Result<bool>;
+
+    /// Per stream, how many events `purge` would delete, across the hot
+    /// table and the archive.
+    async fn purgeable_events(&self, purge: &Purge) -> Result<Vec<(String, u64)>>;
+
+    /// Deletes up to `limit` of the events `purgeable_events` counts, archived
+    /// ones first. Returns the number deleted; fewer than `limit` means none
+    /// are left.
+    async fn purge_events(&self, purge: &Purge, limit: i64) -> Result<u64>;
+
+    /// Deletes up to `limit` events below their stream's `truncate_before`,
+    /// archived ones first, skipping streams under legal hold and never the
+    /// stream head. Returns the number deleted.
+    async fn scavenge_truncated(&self, limit: i64) -> Result<u64>;
+
+    /// Deletes up to `limit` events whose `expires_at` is before `now`,
+    /// archived ones first, skipping streams under legal hold and never the
+    /// stream head. Returns the number deleted.
+    async fn scavenge_expired(&self, now: DateTime<Utc>, limit: i64) -> Result<u64>;
+
+    async fn retention_rules(&self) -> Result<Vec<RetentionRule>>;
+
+    async fn set_retention_rule(&self, rule: &RetentionRule) -> Result<()>;
+
+    /// Returns false if no rule had that name.
+    async fn remove_retention_rule(&self, name: &str) -> Result<bool>;
+
+    async fn snapshot_reducers(&self) -> Result<Vec<SnapshotReducer>>;
+
+    async fn set_snapshot_reducer(&self, reducer: &SnapshotReducer) -> Result<()>;
+
+    /// Returns false if no reducer had that name.
+    async fn remove_snapshot_reducer(&self, name: &str) -> Result<bool>;
+
+    /// Every plugin with its WASM module.
+    async fn plugins(&self) -> Result<Vec<(Plugin, Vec<u8>)>>;
+
+    async fn set_plugin(&self, plugin: &Plugin, module: &[u8]) -> Result<()>;
+
+    /// Removes a plugin and its projection state. Returns false if no plugin
+    /// had that name.
+    async fn remove_plugin(&self, name: &str) -> Result<bool>;
+
+    async fn projection(&self, name: &str) -> Result<Option<ProjectionState>>;
+
+    async fn set_projection(&self, projection: &ProjectionState) -> Result<()>;
+
+    async fn add_dead_letters(&self, letters: &[DeadLetter]) -> Result<()>;
+
+    /// Oldest first, optionally only those from one source.
+    async fn dead_letters(&self, source: Option<&str>, limit: i64) -> Result<Vec<DeadLetter>>;
+
+    async fn dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>>;
+
+    /// Overwrites a dead letter, e.g. to record a failed redelivery.
+    async fn set_dead_letter(&self, letter: &DeadLetter) -> Result<()>;
+
+    /// Returns false if no dead letter had that id.
+    async fn remove_dead_letter(&self, id: Uuid) -> Result<bool>;
+
+    async fn encryption_policies(&self) -> Result<Vec<EncryptionPolicy>>;
+
+    async fn set_encryption_policy(&self, policy: &EncryptionPolicy) -> Result<()>;
+
+    /// Returns false if the event type had no policy.
+    async fn remove_encryption_policy(&self, event_type: &str) -> Result<bool>;
+
+    async fn subject_key(&self, subject_id: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Stores `key` for a subject that has none and returns the subject's
+    /// key, which is an existing one if another writer got there first.
+    async fn insert_subject_key(&self, subject_id: &str, key: &[u8]) -> Result<Vec<u8>>;
+
+    /// Destroys a subject's key; returns false if it had none.
+    async fn delete_subject_key(&self, subject_id: &str) -> Result<bool>;
+
+    /// Hash-chain anchors, newest first.
+    async fn chain_anchors(&self, limit: i64) -> Result<Vec<ChainAnchor>>;
+
+    async fn add_chain_anchor(&self, anchor: &ChainAnchor) -> Result<()>;
+
+    /// Projects and workspaces, ordered by id.
+    async fn namespaces(&self) -> Result<Vec<Namespace>>;
+
+    /// Registers a namespace; returns false, changing nothing, if one with
+    /// its id already is.
+    async fn insert_namespace(&self, namespace: &Namespace) -> Result<bool>;
+
+    /// Removes the namespace with `id` and, for a project, its workspaces.
+    /// Returns the number removed.
+    async fn remove_namespace(&self, id: &str) -> Result<u64>;
+
+    /// Persistent subscription groups, ordered by name.
+    async fn subscription_groups(&self) -> Result<Vec<SubscriptionGroup>>;
+
+    /// Creates the group or replaces it, checkpoint included.
+    async fn set_subscription_group(&self, group: &SubscriptionGroup) -> Result<()>;
+
+    /// Returns false if there was no group with that name.
+    async fn remove_subscription_group(&self, name: &str) -> Result<bool>;
+}
+
+/// Whether `config` selects the Postgres backend.
+pub fn uses_postgres(config: &Config) -> bool {
+    config.storage.as_deref() != Some("memory") && !config.database_url.starts_with("sqlite:")
+}
+
+/// Builds the storage backend selected by the configuration: in-memory when
+/// `STORAGE=memory` (or `--memory`), SQLite for `sqlite:` URLs, Postgres
+/// otherwise.
+pub async fn connect(config: &Config) -> Result<Arc<dyn EventStorage>> {
+    if config.storage.as_deref() == Some("memory") {
+        info!("Using in-memory storage; events will not survive a restart");
+        return Ok(Arc::new(MemoryStorage::new()));
+    }
+
+    if !uses_postgres(config) {
+        let storage = SqliteStorage::connect(&config.database_url).await?;
+        return Ok(Arc::new(storage));
+    }
+
+    let wait = Duration::from_secs(config.db_startup_wait_seconds);
+    let mut storage = PostgresStorage::connect(
+        &config.database_url,
+        wait,
+        config.db_create_if_missing,
+        config.db_statement_cache_size,
+    )
+    .await?
+    .with_retry(RetryPolicy::from_config(config));
+    if let Some(compressor) = EventCompressor::from_config(config) {
+        storage = storage.with_compression(compressor);
+    }
+    if config.version_cache_size > 0 {
+        storage = storage.with_version_cache(config.version_cache_size);
+    }
+    if config.leader_lease_seconds.is_some() {
+        storage = storage.with_fencing();
+    }
+    if let Some(window_ms) = config.append_batch_window_ms.filter(|ms| *ms > 0) {
+        storage = storage.with_group_commit(Duration::from_millis(window_ms), config.append_batch_max);
+    }
+    Ok(Arc::new(storage))
+}
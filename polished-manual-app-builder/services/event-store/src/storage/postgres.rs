@@ -0,0 +1,3434 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, SecondsFormat, TimeZone, Utc};
+use sqlx::migrate::{MigrateDatabase, Migrator};
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{PgConnection, PgPool, Postgres, Row, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::compression::{self, Compressed, EventCompressor};
+use super::group_commit::{GroupCommit, PendingAppend};
+use super::retry::{self, RetryPolicy};
+use super::version_cache::VersionCache;
+use super::{
+    check_precondition, check_rewrite_head, stats_since, ArchiveRange, DailyCount, EventRewrite, EventStorage,
+    MergeCursor, MergeOrder, MigrationStatus, NewEvent, ProjectSummary, ProjectUsage, Purge, ReadDirection,
+    SearchDocument, SearchHit, SnapshotCandidate, StoreStats, UsageBucket, MAX_HOURLY_DAYS,
+};
+use crate::change_feed::{self, Change};
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::get_partition_key;
+use crate::integrity::Link;
+use crate::models::{
+    ApiKey, ChainAnchor, DeadLetter, EncryptionPolicy, Event, EventCompression, EventTypeDeprecation, Granularity,
+    Namespace, Plugin, ProjectionState, RetentionRule, Snapshot, SnapshotReducer, StreamMetadata,
+    SubscriptionGroup,
+};
+
+/// Postgres SQLSTATE raised when `statement_timeout` cancels a query.
+const QUERY_CANCELED: &str = "57014";
+/// Raised when two writers race to create the same partition.
+const DUPLICATE_TABLE: &str = "42P07";
+const UNIQUE_VIOLATION: &str = "23505";
+const INVALID_CATALOG_NAME: &str = "3D000";
+/// Raised when two instances race to create the database.
+const DUPLICATE_DATABASE: &str = "42P04";
+/// Pauses between attempts to reach the database on startup.
+const STARTUP_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_STARTUP_BACKOFF: Duration = Duration::from_secs(5);
+/// Rows encoded per `COPY` data message during bulk loads.
+const BULK_COPY_ROWS: usize = 1_000;
+
+// Stream reads, one fixed statement per direction so each is prepared once
+// per connection and served from the statement cache after that.
+const READ_STREAM_FORWARD: &str = r#"
+    SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+    FROM events
+    WHERE partition_key = $1 AND stream_id = $2 AND version >= $3
+    ORDER BY version ASC
+    LIMIT $4
+"#;
+const READ_STREAM_BACKWARD: &str = r#"
+    SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+    FROM events
+    WHERE partition_key = $1 AND stream_id = $2 AND version <= $3
+    ORDER BY version DESC
+    LIMIT $4
+"#;
+// Within a stream positions rise with versions, so each stream is read
+// along its (stream_id, version) index and only `limit` of its events are
+// merged.
+const READ_STREAMS_BY_POSITION: &str = r#"
+    SELECT e.*
+    FROM unnest($1::text[], $2::text[]) AS s(partition_key, stream_id)
+    CROSS JOIN LATERAL (
+        SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+        FROM events
+        WHERE partition_key = s.partition_key AND stream_id = s.stream_id AND position > $4
+        ORDER BY version
+        LIMIT $5
+    ) e
+    ORDER BY e.position
+    LIMIT $5
+"#;
+const READ_STREAMS_BY_TIMESTAMP: &str = r#"
+    SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+    FROM events
+    WHERE partition_key = ANY($1) AND stream_id = ANY($2) AND (created_at, position) > ($3, $4)
+    ORDER BY created_at, position
+    LIMIT $5
+"#;
+
+/// Versioned schema migrations, embedded at build time and recorded in
+/// `_sqlx_migrations` as they are applied.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Postgres backend. The events table is list-partitioned on
+/// `partition_key` (the project id), and each project partition is
+/// range-partitioned by `created_at` month. Partitions are created on first
+/// append and pre-created ahead of time by `prepare_partitions`, so each
+/// project's indexes stay small and the archiver can drop whole months.
+#[derive(Debug, Clone)]
+pub struct PostgresStorage {
+    pool: PgPool,
+    /// Month partitions known to exist, so appends only issue DDL when a
+    /// project or month is new.
+    partitions: Arc<Mutex<HashSet<String>>>,
+    /// Set when appends are batched; `append` then hands events to the
+    /// group commit writer instead of opening its own transaction.
+    group_commit: Option<GroupCommit>,
+    /// Stream heads written by this instance; `None` when disabled.
+    versions: Option<Arc<VersionCache>>,
+    /// Applied to reads, which are safe to run again.
+    retry: RetryPolicy,
+    /// Set when large JSON bodies are stored compressed.
+    compressor: Option<EventCompressor>,
+    /// With fencing on, the leader epoch this instance writes under; 0
+    /// while it doesn't hold the lease.
+    fence: Option<Arc<AtomicI64>>,
+}
+
+impl PostgresStorage {
+    /// Connects, waiting up to `wait` for Postgres to accept connections:
+    /// on first boot the service often starts before the database does. With
+    /// `create_if_missing`, a missing database is created; the migrations
+    /// then create everything in it. Each connection keeps up to
+    /// `statement_cache_size` prepared statements.
+    pub async fn connect(
+        database_url: &str,
+        wait: Duration,
+        create_if_missing: bool,
+        statement_cache_size: usize,
+    ) -> Result<Self> {
+        info!("Connecting to database...");
+
+        let give_up = Instant::now() + wait;
+        let mut backoff = STARTUP_BACKOFF;
+        let pool = loop {
+            let e = match open_pool(database_url, create_if_missing, statement_cache_size).await {
+                Ok(pool) => break pool,
+                Err(e) => e,
+            };
+            let not_ready = retry::is_transient(&e) || matches!(e, sqlx::Error::PoolTimedOut);
+            if not_ready && Instant::now() + backoff < give_up {
+                warn!("Database not ready, retrying in {}ms: {}", backoff.as_millis(), e);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_STARTUP_BACKOFF);
+                continue;
+            }
+
+            let missing = matches!(&e, sqlx::Error::Database(db) if db.code().as_deref() == Some(INVALID_CATALOG_NAME));
+            let hint = if missing && !create_if_missing {
+                "; set DB_CREATE_IF_MISSING=true to create it on startup"
+            } else {
+                ""
+            };
+            return Err(AppError::Database(format!("Failed to connect to database: {}{}", e, hint)));
+        };
+
+        info!("Database connection established");
+        Ok(Self {
+            pool,
+            partitions: Arc::new(Mutex::new(HashSet::new())),
+            group_commit: None,
+            versions: None,
+            retry: RetryPolicy::none(),
+            compressor: None,
+            fence: None,
+        })
+    }
+
+    /// Caches the head version of up to `capacity` streams in memory.
+    pub fn with_version_cache(mut self, capacity: usize) -> Self {
+        self.versions = Some(Arc::new(VersionCache::new(capacity)));
+        self
+    }
+
+    /// Retries reads that fail with a transient error, such as a dropped
+    /// connection during a failover or a serialization failure.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Stores the JSON bodies of new events compressed once they're over the
+    /// compressor's threshold. Reads decompress them whatever the setting.
+    pub fn with_compression(mut self, compressor: EventCompressor) -> Self {
+        self.compressor = Some(compressor);
+        self
+    }
+
+    /// Refuses writes unless this instance holds the leader lease, as set
+    /// by `acquire_leadership`. Must come before `with_group_commit`.
+    pub fn with_fencing(mut self) -> Self {
+        self.fence = Some(Arc::new(AtomicI64::new(0)));
+        self
+    }
+
+    /// With fencing on, fails unless the lease still carries this
+    /// instance's epoch, and share-locks it until `conn`'s transaction
+    /// ends: a takeover waits for the write, and writes that start after
+    /// it are refused.
+    async fn check_fence(&self, conn: &mut PgConnection) -> Result<()> {
+        let Some(fence) = &self.fence else {
+            return Ok(());
+        };
+        let ours = fence.load(Ordering::SeqCst);
+        let current: Option<i64> = sqlx::query_scalar("SELECT epoch FROM leader_lease FOR SHARE")
+            .fetch_optional(conn)
+            .await
+            .map_err(classify)?;
+
+        match current {
+            Some(epoch) if ours == epoch => Ok(()),
+            Some(epoch) if ours != 0 => Err(AppError::Fenced(format!(
+                "This instance led in epoch {}, superseded by epoch {}",
+                ours, epoch
+            ))),
+            _ => Err(AppError::Fenced("This instance is not the leader".to_string())),
+        }
+    }
+
+    /// The compressed form of `event`'s body, when it should be stored so.
+    fn compress(&self, event: &NewEvent) -> Result<Option<Compressed>> {
+        match (&self.compressor, &event.data) {
+            (Some(compressor), Some(data)) => compressor.compress(data),
+            _ => Ok(None),
+        }
+    }
+
+    /// Routes appends through a group commit writer that flushes every
+    /// `window` or `max_batch` appends, whichever comes first.
+    pub fn with_group_commit(mut self, window: Duration, max_batch: usize) -> Self {
+        self.group_commit = Some(GroupCommit::spawn(self.clone(), window, max_batch.max(1)));
+        self
+    }
+
+    /// Commits a group of appends in one transaction. Streams are locked in a
+    /// fixed order, versions are assigned in arrival order, and appends that
+    /// fail their expected version, or whose deadline passed while queued, are
+    /// rejected individually without affecting the rest of the batch. The
+    /// transaction runs under the earliest deadline left.
+    pub(super) async fn append_batch(&self, batch: Vec<PendingAppend>) {
+        let mut live = Vec::with_capacity(batch.len());
+        for pending in batch {
+            match pending.deadline.remaining() {
+                Ok(_) => live.push(pending),
+                Err(e) => pending.fail(&e),
+            }
+        }
+        let batch = live;
+        if batch.is_empty() {
+            return;
+        }
+        let deadline = Deadline(batch.iter().filter_map(|p| p.deadline.0).min());
+
+        let mut stream_ids: Vec<String> = batch.iter().map(|p| p.event.stream_id.clone()).collect();
+        stream_ids.sort();
+        stream_ids.dedup();
+
+        let (mut tx, mut heads) = match self.lock_streams(&stream_ids, &deadline).await {
+            Ok(locked) => locked,
+            Err(e) => {
+                for pending in batch {
+                    pending.fail(&e);
+                }
+                return;
+            }
+        };
+
+        let mut accepted = Vec::with_capacity(batch.len());
+        let mut versions = Vec::with_capacity(batch.len());
+        let mut chain_hashes = Vec::with_capacity(batch.len());
+        for pending in batch {
+            let (head, chain_hash) = heads.entry(pending.event.stream_id.clone()).or_default();
+            if let Some(expected) = pending.expected_version {
+                if *head != expected {
+                    let _ = pending.reply.send(Err(AppError::Conflict(format!(
+                        "Version conflict: expected {}, got {}",
+                        expected, *head
+                    ))));
+                    continue;
+                }
+            }
+            *head += 1;
+            *chain_hash = Some(Link::new_event(&pending.event, *head).hash(chain_hash.as_deref()));
+            versions.push(*head);
+            chain_hashes.push(chain_hash.clone());
+            accepted.push(pending);
+        }
+        if accepted.is_empty() {
+            return;
+        }
+
+        let result = async {
+            let positions: Vec<(Uuid, i64)> = sqlx::query_as(
+                r#"
+                INSERT INTO events (id, stream_id, event_type, data, payload, content_type, metadata, version, created_at, partition_key, tenant_id, data_compression, checksum, chain_hash, expires_at)
+                SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, created_at, partition_key, tenant_id, data_compression, checksum, chain_hash, expires_at
+                FROM UNNEST($1::uuid[], $2::varchar[], $3::varchar[], $4::jsonb[], $5::bytea[], $6::varchar[], $7::jsonb[], $8::int8[], $9::timestamptz[], $10::varchar[], $11::varchar[], $12::varchar[], $13::varchar[], $14::varchar[], $15::timestamptz[])
+                    WITH ORDINALITY AS t(id, stream_id, event_type, data, payload, content_type, metadata, version, created_at, partition_key, tenant_id, data_compression, checksum, chain_hash, expires_at, n)
+                ORDER BY n
+                RETURNING id, position
+                "#,
+            )
+            .bind(accepted.iter().map(|p| p.event.id).collect::<Vec<_>>())
+            .bind(accepted.iter().map(|p| p.event.stream_id.clone()).collect::<Vec<_>>())
+            .bind(accepted.iter().map(|p| p.event.event_type.clone()).collect::<Vec<_>>())
+            .bind(
+                accepted
+                    .iter()
+                    .map(|p| p.event.data.clone().filter(|_| p.compressed.is_none()))
+                    .collect::<Vec<_>>(),
+            )
+            .bind(
+                accepted
+                    .iter()
+                    .map(|p| match &p.compressed {
+                        Some(compressed) => Some(compressed.bytes.clone()),
+                        None => p.event.payload.clone(),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .bind(accepted.iter().map(|p| p.event.content_type.clone()).collect::<Vec<_>>())
+            .bind(accepted.iter().map(|p| p.event.metadata.clone()).collect::<Vec<_>>())
+            .bind(&versions)
+            .bind(accepted.iter().map(|p| p.event.created_at).collect::<Vec<_>>())
+            .bind(accepted.iter().map(|p| p.event.partition_key.clone()).collect::<Vec<_>>())
+            .bind(accepted.iter().map(|p| p.event.tenant_id.clone()).collect::<Vec<_>>())
+            .bind(
+                accepted
+                    .iter()
+                    .map(|p| p.compressed.as_ref().map(|c| c.compression.as_str()))
+                    .collect::<Vec<_>>(),
+            )
+            .bind(accepted.iter().map(|p| p.event.checksum.clone()).collect::<Vec<_>>())
+            .bind(&chain_hashes)
+            .bind(accepted.iter().map(|p| p.event.expires_at).collect::<Vec<_>>())
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Failed to insert event batch: {}", e);
+                classify(e)
+            })?;
+
+            let positions: HashMap<Uuid, i64> = positions.into_iter().collect();
+
+            let changes = accepted
+                .iter()
+                .zip(&versions)
+                .map(|(pending, version)| Change {
+                    partition_key: pending.event.partition_key.clone(),
+                    stream_id: pending.event.stream_id.clone(),
+                    version: *version,
+                    position: positions.get(&pending.event.id).copied().unwrap_or_default(),
+                })
+                .collect::<Vec<_>>();
+            notify_changes(&mut tx, &changes).await?;
+
+            tx.commit().await.map_err(classify)?;
+            Ok::<_, AppError>(positions)
+        }
+        .await;
+
+        let positions = match result {
+            Ok(positions) => positions,
+            Err(e) => {
+                // The whole transaction rolled back; every accepted append fails with it
+                for pending in accepted {
+                    pending.fail(&e);
+                }
+                return;
+            }
+        };
+
+        for ((pending, version), chain_hash) in accepted.into_iter().zip(versions).zip(chain_hashes) {
+            let event = pending.event;
+            if let Some(cache) = &self.versions {
+                cache.set(&event.stream_id, version);
+            }
+            let _ = pending.reply.send(Ok(Event {
+                position: positions.get(&event.id).copied().unwrap_or_default(),
+                id: event.id,
+                stream_id: event.stream_id,
+                event_type: event.event_type,
+                data: event.data.unwrap_or(serde_json::Value::Null),
+                payload: event.payload,
+                content_type: event.content_type,
+                metadata: event.metadata,
+                version,
+                created_at: event.created_at,
+                checksum: Some(event.checksum),
+                chain_hash,
+                link: None,
+            }));
+        }
+    }
+
+    /// Opens a transaction holding the append locks of `stream_ids` (sorted,
+    /// so concurrent batches can't deadlock) and reads the version and chain
+    /// hash of their heads.
+    async fn lock_streams(
+        &self,
+        stream_ids: &[String],
+        deadline: &Deadline,
+    ) -> Result<(Transaction<'static, Postgres>, HashMap<String, (i64, Option<String>)>)> {
+        let partition_keys: Vec<String> = stream_ids.iter().map(|id| get_partition_key(id)).collect();
+        let partition_keys = &partition_keys;
+        let mut tx = self.begin(deadline).await?;
+        self.check_fence(&mut tx).await?;
+        lock_stream_ids(&mut tx, stream_ids).await?;
+
+        let heads = sqlx::query_as::<_, (String, i64, Option<String>)>(
+            r#"
+            SELECT DISTINCT ON (stream_id) stream_id, version, chain_hash
+            FROM events
+            WHERE partition_key = ANY($1) AND stream_id = ANY($2)
+            ORDER BY stream_id, version DESC
+            "#,
+        )
+        .bind(&partition_keys)
+        .bind(stream_ids)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(classify)?
+        .into_iter()
+        .map(|(stream_id, version, chain_hash)| (stream_id, (version, chain_hash)))
+        .collect();
+
+        Ok((tx, heads))
+    }
+
+    /// Creates the project and month partitions that will hold an event
+    /// created at `at`, if they don't exist yet.
+    async fn ensure_partition(&self, partition_key: &str, at: DateTime<Utc>) -> Result<()> {
+        let (start, end) = month_bounds(at);
+        let table = month_table_name(partition_key, start);
+        if self.partitions.lock().unwrap().contains(&table) {
+            return Ok(());
+        }
+
+        let mut conn = self.pool.acquire().await.map_err(classify)?;
+        create_partitions(&mut conn, "events", partition_key, start, end).await?;
+
+        // Months that have already ended may be dropped by the archiver, so
+        // only cache partitions that are still receiving appends.
+        if end > Utc::now() {
+            self.partitions.lock().unwrap().insert(table);
+        }
+        Ok(())
+    }
+
+    /// Brings databases set up before versioned migrations to the baseline
+    /// schema where plain DDL can't: the events table may predate
+    /// partitioning or the tenant stamp. A no-op on anything newer.
+    async fn upgrade_legacy_schema(&self) -> Result<()> {
+        let pool = &self.pool;
+        let events_kind: Option<String> = sqlx::query_scalar(
+            "SELECT relkind::text FROM pg_class WHERE oid = to_regclass('events')",
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| AppError::Database(format!("Failed to inspect events table: {}", e)))?;
+
+        match events_kind.as_deref() {
+            Some("r") => {
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS event_partitions (
+                        table_name VARCHAR PRIMARY KEY,
+                        partition_key VARCHAR NOT NULL,
+                        range_start TIMESTAMPTZ NOT NULL,
+                        range_end TIMESTAMPTZ NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| AppError::Database(format!("Failed to create event_partitions table: {}", e)))?;
+
+                // Binary payload support for tables created before it existed
+                sqlx::query(
+                    r#"
+                    ALTER TABLE events
+                        ALTER COLUMN data DROP NOT NULL,
+                        ADD COLUMN IF NOT EXISTS payload BYTEA,
+                        ADD COLUMN IF NOT EXISTS content_type VARCHAR NOT NULL DEFAULT 'application/json',
+                        ADD COLUMN IF NOT EXISTS position BIGSERIAL
+                    "#,
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| AppError::Database(format!("Failed to add payload columns: {}", e)))?;
+
+                self.convert_to_partitioned().await?;
+            }
+            Some(_) => {
+                // Every stream's tenant so far is its partition key
+                sqlx::query("ALTER TABLE events ADD COLUMN IF NOT EXISTS tenant_id VARCHAR")
+                    .execute(pool)
+                    .await
+                    .map_err(|e| AppError::Database(format!("Failed to add tenant_id column: {}", e)))?;
+                sqlx::query("UPDATE events SET tenant_id = partition_key WHERE tenant_id IS NULL")
+                    .execute(pool)
+                    .await
+                    .map_err(|e| AppError::Database(format!("Failed to backfill tenant_id: {}", e)))?;
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds a pre-partitioning events table as a partitioned one, copying
+    /// every row across in a single transaction.
+    async fn convert_to_partitioned(&self) -> Result<()> {
+        info!("Converting events table to a partitioned table...");
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+
+        sqlx::query(&create_events_table_sql("events_partitioned"))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::Database(format!("Failed to create partitioned events table: {}", e)))?;
+
+        let months = sqlx::query(
+            "SELECT DISTINCT partition_key, date_trunc('month', created_at) AS month FROM events",
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(classify)?;
+
+        for row in &months {
+            let key: String = row.try_get("partition_key")?;
+            let (start, end) = month_bounds(row.try_get("month")?);
+            create_partitions(&mut tx, "events_partitioned", &key, start, end).await?;
+        }
+
+        let copied = sqlx::query(
+            r#"
+            INSERT INTO events_partitioned (id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id)
+            SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, partition_key
+            FROM events
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::Database(format!("Failed to copy events into partitions: {}", e)))?
+        .rows_affected();
+
+        sqlx::query(
+            "SELECT setval(pg_get_serial_sequence('events_partitioned', 'position'), COALESCE(MAX(position), 0) + 1, false) FROM events_partitioned",
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(classify)?;
+
+        sqlx::query("DROP TABLE events")
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+        sqlx::query("ALTER TABLE events_partitioned RENAME TO events")
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+
+        tx.commit().await.map_err(classify)?;
+
+        info!("Moved {} events into {} partitions", copied, months.len());
+        Ok(())
+    }
+
+    /// Detaches and drops month partitions that have ended and hold no
+    /// events, once archiving has moved their rows out.
+    async fn drop_empty_partitions(&self) -> Result<()> {
+        let expired = sqlx::query(
+            "SELECT table_name, partition_key FROM event_partitions WHERE range_end <= date_trunc('month', NOW()) ORDER BY range_start",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        for row in &expired {
+            // Table names come from month_table_name, never from user input
+            let table: String = row.try_get("table_name")?;
+            let partition_key: String = row.try_get("partition_key")?;
+
+            let mut tx = self.pool.begin().await.map_err(classify)?;
+            // Locks out late imports into the month while it is checked and dropped
+            sqlx::query(&format!("LOCK TABLE {} IN ACCESS EXCLUSIVE MODE", table))
+                .execute(&mut *tx)
+                .await
+                .map_err(classify)?;
+            let empty: bool = sqlx::query_scalar(&format!("SELECT NOT EXISTS (SELECT 1 FROM {})", table))
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(classify)?;
+            if !empty {
+                continue;
+            }
+
+            sqlx::query(&format!(
+                "ALTER TABLE {} DETACH PARTITION {}",
+                partition_table_name(&partition_key),
+                table
+            ))
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+            sqlx::query(&format!("DROP TABLE {}", table))
+                .execute(&mut *tx)
+                .await
+                .map_err(classify)?;
+            sqlx::query("DELETE FROM event_partitions WHERE table_name = $1")
+                .bind(&table)
+                .execute(&mut *tx)
+                .await
+                .map_err(classify)?;
+            tx.commit().await.map_err(classify)?;
+
+            info!("Dropped empty partition {}", table);
+        }
+
+        Ok(())
+    }
+
+    /// Opens a transaction whose statements are cancelled by Postgres once the
+    /// deadline passes.
+    async fn begin(&self, deadline: &Deadline) -> Result<Transaction<'static, Postgres>> {
+        let remaining = deadline.remaining()?;
+        self.begin_with_timeout(remaining).await.map_err(classify)
+    }
+
+    /// Opens a transaction whose statements are cancelled after `timeout`.
+    async fn begin_with_timeout(&self, timeout: Option<Duration>) -> sqlx::Result<Transaction<'static, Postgres>> {
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(timeout) = timeout {
+            // set_config, unlike SET LOCAL, takes the value as a parameter, so
+            // this is one cached statement rather than one per timeout
+            let millis = timeout.as_millis().max(1);
+            sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+                .bind(millis.to_string())
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        Ok(tx)
+    }
+}
+
+/// Opens the pool, first creating the database if asked to and it doesn't
+/// exist yet.
+async fn open_pool(database_url: &str, create_if_missing: bool, statement_cache_size: usize) -> sqlx::Result<PgPool> {
+    if create_if_missing && !Postgres::database_exists(database_url).await? {
+        match Postgres::create_database(database_url).await {
+            Ok(()) => info!("Created the database"),
+            Err(sqlx::Error::Database(db)) if db.code().as_deref() == Some(DUPLICATE_DATABASE) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let options = PgConnectOptions::from_str(database_url)?.statement_cache_capacity(statement_cache_size);
+    PgPool::connect_with(options).await
+}
+
+/// Maps a sqlx error, turning statement-timeout cancellations into
+/// `DeadlineExceeded` instead of a generic database error.
+pub(super) fn classify(e: sqlx::Error) -> AppError {
+    if let sqlx::Error::Database(db) = &e {
+        if db.code().as_deref() == Some(QUERY_CANCELED) {
+            return AppError::DeadlineExceeded("Query cancelled at request deadline".to_string());
+        }
+    }
+    AppError::Database(e.to_string())
+}
+
+/// Appends one event as a CSV record for `COPY bulk_events`. Unquoted empty
+/// fields are NULL in CSV format; everything else is quoted.
+fn write_csv_row(out: &mut String, event: &Event, seq: usize) -> Result<()> {
+    fn field(out: &mut String, value: Option<&str>) {
+        if let Some(value) = value {
+            out.push('"');
+            out.push_str(&value.replace('"', "\"\""));
+            out.push('"');
+        }
+    }
+
+    let data = match event.payload {
+        Some(_) => None,
+        None => Some(serde_json::to_string(&event.data)?),
+    };
+    let payload = event.payload.as_ref().map(|bytes| {
+        let mut hex = String::with_capacity(2 + bytes.len() * 2);
+        hex.push_str("\\x");
+        for byte in bytes {
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        hex
+    });
+    let metadata = event.metadata.as_ref().map(serde_json::to_string).transpose()?;
+
+    field(out, Some(&event.id.to_string()));
+    out.push(',');
+    field(out, Some(&event.stream_id));
+    out.push(',');
+    field(out, Some(&event.event_type));
+    out.push(',');
+    field(out, data.as_deref());
+    out.push(',');
+    field(out, payload.as_deref());
+    out.push(',');
+    field(out, Some(&event.content_type));
+    out.push(',');
+    field(out, metadata.as_deref());
+    out.push(',');
+    out.push_str(&event.version.to_string());
+    out.push(',');
+    // Postgres would round finer timestamps to the microsecond; chain hashes truncate
+    field(out, Some(&event.created_at.to_rfc3339_opts(SecondsFormat::Micros, true)));
+    out.push(',');
+    field(out, Some(&get_partition_key(&event.stream_id)));
+    out.push(',');
+    field(out, event.checksum.as_deref());
+    out.push(',');
+    field(out, event.chain_hash.as_deref());
+    out.push(',');
+    out.push_str(&seq.to_string());
+    out.push('\n');
+    Ok(())
+}
+
+fn create_events_table_sql(table: &str) -> String {
+    format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {} (
+            id UUID NOT NULL,
+            stream_id VARCHAR NOT NULL,
+            event_type VARCHAR NOT NULL,
+            data JSONB,
+            payload BYTEA,
+            content_type VARCHAR NOT NULL DEFAULT 'application/json',
+            metadata JSONB,
+            version BIGINT NOT NULL,
+            position BIGSERIAL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            partition_key VARCHAR NOT NULL,
+            tenant_id VARCHAR,
+            PRIMARY KEY (partition_key, id, created_at),
+            UNIQUE (partition_key, stream_id, version, created_at)
+        ) PARTITION BY LIST (partition_key)
+        "#,
+        table
+    )
+}
+
+/// Creates a project's partition and its month sub-partition covering
+/// [start, end), recording the month in `event_partitions`. Partition bounds
+/// can't be bound as parameters, so they are quoted as literals.
+async fn create_partitions(
+    conn: &mut PgConnection,
+    parent: &str,
+    partition_key: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<()> {
+    let project_table = partition_table_name(partition_key);
+    let month_table = month_table_name(partition_key, start);
+
+    execute_ddl(
+        conn,
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} FOR VALUES IN ('{}') PARTITION BY RANGE (created_at)",
+            project_table,
+            parent,
+            partition_key.replace('\'', "''")
+        ),
+    )
+    .await?;
+    execute_ddl(
+        conn,
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} FOR VALUES FROM ('{}') TO ('{}')",
+            month_table,
+            project_table,
+            start.to_rfc3339(),
+            end.to_rfc3339()
+        ),
+    )
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO event_partitions (table_name, partition_key, range_start, range_end)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (table_name) DO NOTHING
+        "#,
+    )
+    .bind(&month_table)
+    .bind(partition_key)
+    .bind(start)
+    .bind(end)
+    .execute(conn)
+    .await
+    .map_err(classify)?;
+
+    Ok(())
+}
+
+/// Runs partition DDL, treating a concurrent creation of the same table as
+/// success.
+async fn execute_ddl(conn: &mut PgConnection, sql: &str) -> Result<()> {
+    match sqlx::query(sql).execute(conn).await {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::Database(db))
+            if matches!(db.code().as_deref(), Some(DUPLICATE_TABLE | UNIQUE_VIOLATION)) =>
+        {
+            Ok(())
+        }
+        Err(e) => {
+            error!("Failed to create partition: {}", e);
+            Err(classify(e))
+        }
+    }
+}
+
+/// First instant of the month containing `at`, and of the month after it.
+fn month_bounds(at: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = Utc.with_ymd_and_hms(at.year(), at.month(), 1, 0, 0, 0).unwrap();
+    let end = if at.month() == 12 {
+        Utc.with_ymd_and_hms(at.year() + 1, 1, 1, 0, 0, 0).unwrap()
+    } else {
+        Utc.with_ymd_and_hms(at.year(), at.month() + 1, 1, 0, 0, 0).unwrap()
+    };
+    (start, end)
+}
+
+/// A stable, identifier-safe table name for a partition key: a readable
+/// prefix plus an FNV-1a hash so distinct keys never collide after sanitizing.
+fn partition_table_name(partition_key: &str) -> String {
+    let readable: String = partition_key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .take(24)
+        .collect();
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in partition_key.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    format!("events_p_{}_{:016x}", readable, hash)
+}
+
+fn month_table_name(partition_key: &str, month_start: DateTime<Utc>) -> String {
+    format!("{}_{}", partition_table_name(partition_key), month_start.format("%Y%m"))
+}
+
+fn check_expected_version(expected_version: Option<i64>, current_version: i64) -> Result<()> {
+    match expected_version {
+        Some(expected) if expected != current_version => Err(AppError::Conflict(format!(
+            "Version conflict: expected {}, got {}",
+            expected, current_version
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Inserts `event` at `version` unless the stream already has that version
+/// or a later one, returning its position. `None` means the head read
+/// before the insert was stale. With `compressed`, that is stored in place
+/// of the event's JSON body. The append is announced on the change feed in
+/// the same statement, as `notify_changes` would, saving a round trip.
+async fn insert_event(
+    conn: &mut PgConnection,
+    event: &NewEvent,
+    compressed: Option<&Compressed>,
+    chain_hash: &str,
+    version: i64,
+) -> Result<Option<i64>> {
+    sqlx::query_scalar(
+        r#"
+        WITH inserted AS (
+            INSERT INTO events (id, stream_id, event_type, data, payload, content_type, metadata, version, created_at, partition_key, tenant_id, data_compression, checksum, chain_hash, expires_at)
+            SELECT $1::uuid, $2::varchar, $3::varchar, $4::jsonb, $5::bytea, $6::varchar, $7::jsonb, $8::int8, $9::timestamptz, $10::varchar, $11::varchar, $12::varchar, $13::varchar, $14::varchar, $16::timestamptz
+            WHERE NOT EXISTS (
+                SELECT 1 FROM events WHERE partition_key = $10 AND stream_id = $2 AND version >= $8
+            )
+            RETURNING position
+        )
+        SELECT i.position FROM inserted i
+        CROSS JOIN LATERAL (
+            SELECT pg_notify($15, json_build_object(
+                'partition_key', $10::varchar, 'stream_id', $2::varchar, 'version', $8::int8, 'position', i.position
+            )::text)
+        ) announced
+        "#,
+    )
+    .bind(event.id)
+    .bind(&event.stream_id)
+    .bind(&event.event_type)
+    .bind(event.data.as_ref().filter(|_| compressed.is_none()))
+    .bind(compressed.map_or(event.payload.as_deref(), |c| Some(c.bytes.as_slice())))
+    .bind(&event.content_type)
+    .bind(&event.metadata)
+    .bind(version)
+    .bind(event.created_at)
+    .bind(&event.partition_key)
+    .bind(&event.tenant_id)
+    .bind(compressed.map(|c| c.compression.as_str()))
+    .bind(&event.checksum)
+    .bind(chain_hash)
+    .bind(change_feed::channel(&event.partition_key))
+    .bind(event.expires_at)
+    .fetch_optional(conn)
+    .await
+    .map_err(|e| {
+        error!("Failed to insert event: {}", e);
+        classify(e)
+    })
+}
+
+/// The chain hash of the event at `version` of `event`'s stream, which
+/// `event` will follow.
+async fn chain_hash_at(conn: &mut PgConnection, event: &NewEvent, version: i64) -> Result<Option<String>> {
+    let previous: Option<Option<String>> = sqlx::query_scalar(
+        "SELECT chain_hash FROM events WHERE partition_key = $1 AND stream_id = $2 AND version = $3",
+    )
+    .bind(&event.partition_key)
+    .bind(&event.stream_id)
+    .bind(version)
+    .fetch_optional(conn)
+    .await
+    .map_err(classify)?;
+
+    Ok(previous.flatten())
+}
+
+/// The head version of `event`'s stream and its chain hash, read together
+/// so an uncached append makes one round trip for both.
+async fn stream_head(conn: &mut PgConnection, event: &NewEvent) -> Result<(i64, Option<String>)> {
+    let head: Option<(i64, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT version, chain_hash FROM events
+        WHERE partition_key = $1 AND stream_id = $2
+        ORDER BY version DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(&event.partition_key)
+    .bind(&event.stream_id)
+    .fetch_optional(conn)
+    .await
+    .map_err(classify)?;
+
+    Ok(head.unwrap_or((0, None)))
+}
+
+/// Takes the append locks of `stream_ids` until the transaction ends, in
+/// sorted order so concurrent writers can't deadlock.
+async fn lock_stream_ids(conn: &mut PgConnection, stream_ids: &[String]) -> Result<()> {
+    sqlx::query(
+        "SELECT pg_advisory_xact_lock(hashtextextended(s, 0)) FROM (SELECT DISTINCT s FROM UNNEST($1::text[]) s ORDER BY s) t",
+    )
+    .bind(stream_ids)
+    .execute(conn)
+    .await
+    .map_err(classify)?;
+
+    Ok(())
+}
+
+/// Inserts an event as it is, keeping its id, version and position.
+///
+/// The events table's unique constraints include `created_at`, which
+/// partitioning requires, so they can't stop a second event taking a
+/// version. The insert skips a version already in the stream, live or
+/// archived, and reports a conflict; that check is only sound while the
+/// caller holds the stream's append lock (`lock_stream_ids`).
+async fn insert_verbatim(conn: &mut PgConnection, event: &Event) -> Result<()> {
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO events (id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id, checksum, chain_hash)
+        SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $11, $12, $13
+        WHERE NOT EXISTS (SELECT 1 FROM events WHERE partition_key = $11 AND stream_id = $2 AND version = $8)
+        AND NOT EXISTS (SELECT 1 FROM events_archive WHERE partition_key = $11 AND stream_id = $2 AND version = $8)
+        "#,
+    )
+    .bind(event.id)
+    .bind(&event.stream_id)
+    .bind(&event.event_type)
+    .bind(event.payload.is_none().then_some(&event.data))
+    .bind(&event.payload)
+    .bind(&event.content_type)
+    .bind(&event.metadata)
+    .bind(event.version)
+    .bind(event.position)
+    .bind(event.created_at)
+    .bind(get_partition_key(&event.stream_id))
+    .bind(&event.checksum)
+    .bind(&event.chain_hash)
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        error!("Failed to import event {}: {}", event.id, e);
+        classify(e)
+    })?;
+
+    if inserted.rows_affected() == 0 {
+        return Err(AppError::Conflict(format!(
+            "Version {} of {} already exists",
+            event.version, event.stream_id
+        )));
+    }
+    Ok(())
+}
+
+/// Announces appends on their partitions' change feed channels. Postgres
+/// holds the notifications until the transaction commits, and drops them if
+/// it rolls back.
+async fn notify_changes(conn: &mut PgConnection, changes: &[Change]) -> Result<()> {
+    let channels: Vec<String> = changes.iter().map(|c| change_feed::channel(&c.partition_key)).collect();
+    let payloads = changes
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    sqlx::query("SELECT pg_notify(channel, payload) FROM UNNEST($1::text[], $2::text[]) AS t(channel, payload)")
+        .bind(channels)
+        .bind(payloads)
+        .execute(conn)
+        .await
+        .map_err(|e| {
+            error!("Failed to notify change feed: {}", e);
+            classify(e)
+        })?;
+
+    Ok(())
+}
+
+async fn get_stream_version(conn: &mut PgConnection, stream_id: &str) -> sqlx::Result<i64> {
+    let version: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM events WHERE partition_key = $1 AND stream_id = $2")
+            .bind(get_partition_key(stream_id))
+            .bind(stream_id)
+            .fetch_one(conn)
+            .await?;
+
+    Ok(version.unwrap_or(0))
+}
+
+/// The WHERE clause for a purge over `events` or `events_archive` aliased
+/// `e`, with `$1` the cutoff time, and the string parameters that follow.
+fn purge_filter(purge: &Purge) -> (String, Vec<String>) {
+    let (conditions, params) = purge.conditions('$', 2);
+    let filter = format!(
+        r#"
+        e.created_at < $1
+        AND {}
+        AND e.stream_id NOT IN (
+            SELECT stream_id FROM stream_metadata
+            WHERE COALESCE((metadata->>'legal_hold')::boolean, false)
+        )
+        AND e.version < (
+            SELECT MAX(h.version) FROM events h
+            WHERE h.partition_key = e.partition_key AND h.stream_id = e.stream_id
+        )
+        "#,
+        conditions
+    );
+    (filter, params)
+}
+
+fn event_from_row(row: &sqlx::postgres::PgRow) -> Result<Event> {
+    let (data, payload) = event_body(row)?;
+    Ok(Event {
+        id: row.try_get("id")?,
+        stream_id: row.try_get("stream_id")?,
+        event_type: row.try_get("event_type")?,
+        data,
+        payload,
+        content_type: row.try_get("content_type")?,
+        metadata: row.try_get("metadata")?,
+        version: row.try_get("version")?,
+        position: row.try_get("position")?,
+        created_at: row.try_get("created_at")?,
+        checksum: row.try_get("checksum")?,
+        chain_hash: row.try_get("chain_hash")?,
+        link: None,
+    })
+}
+
+/// An event's `data` and `payload`, decompressing a JSON body stored
+/// compressed in `payload`.
+fn event_body(row: &sqlx::postgres::PgRow) -> Result<(serde_json::Value, Option<Vec<u8>>)> {
+    let data: Option<serde_json::Value> = row.try_get("data")?;
+    let payload: Option<Vec<u8>> = row.try_get("payload")?;
+    let compression: Option<String> = row.try_get("data_compression")?;
+
+    match (compression, payload) {
+        (Some(compression), Some(bytes)) => {
+            let compression: EventCompression = compression.parse().map_err(AppError::Database)?;
+            Ok((compression::decompress_data(compression, &bytes)?, None))
+        }
+        (_, payload) => Ok((data.unwrap_or(serde_json::Value::Null), payload)),
+    }
+}
+
+fn daily_count_from_row(row: &sqlx::postgres::PgRow) -> Result<DailyCount> {
+    Ok(DailyCount {
+        day: row.try_get("day")?,
+        events: row.try_get("events")?,
+    })
+}
+
+fn snapshot_from_row(row: &sqlx::postgres::PgRow) -> Result<Snapshot> {
+    Ok(Snapshot {
+        id: row.try_get("id")?,
+        stream_id: row.try_get("stream_id")?,
+        version: row.try_get("version")?,
+        data: row.try_get("data")?,
+        compression: row.try_get::<String, _>("compression")?.parse().map_err(AppError::Database)?,
+        reducer: row.try_get("reducer")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+#[async_trait]
+impl EventStorage for PostgresStorage {
+    async fn migrate(&self) -> Result<()> {
+        info!("Running database migrations...");
+        self.upgrade_legacy_schema().await?;
+
+        MIGRATOR
+            .run(&self.pool)
+            .await
+            .map_err(|e| AppError::Database(format!("Migration failed: {}", e)))?;
+
+        info!("Database migrations completed");
+        Ok(())
+    }
+
+    async fn append(
+        &self,
+        event: NewEvent,
+        expected_version: Option<i64>,
+        deadline: Deadline,
+    ) -> Result<Event> {
+        self.ensure_partition(&event.partition_key, event.created_at).await?;
+        let compressed = self.compress(&event)?;
+        // Group commit batches lock only the streams they write to
+        if let Some(group_commit) = self.group_commit.as_ref().filter(|_| event.preconditions.is_empty()) {
+            deadline.remaining()?;
+            return group_commit.append(event, compressed, expected_version, deadline).await;
+        }
+        let mut tx = self.begin(&deadline).await?;
+        self.check_fence(&mut tx).await?;
+
+        // The unique constraint includes created_at (a partition column), so it
+        // can't catch two writers racing for the same version; serialize
+        // appends per stream instead. Streams named in preconditions are
+        // locked too, all in a fixed order, so they hold still until commit.
+        let mut stream_ids: Vec<&str> = event.preconditions.iter().map(|p| p.stream_id.as_str()).collect();
+        stream_ids.push(&event.stream_id);
+        sqlx::query(
+            "SELECT pg_advisory_xact_lock(hashtextextended(s, 0)) FROM (SELECT DISTINCT s FROM UNNEST($1::text[]) s ORDER BY s) t",
+        )
+        .bind(&stream_ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(classify)?;
+        for precondition in &event.preconditions {
+            let current_version = get_stream_version(&mut tx, &precondition.stream_id).await.map_err(classify)?;
+            check_precondition(precondition, current_version)?;
+        }
+
+        // Get the current head for optimistic concurrency control, confirming
+        // a cached one before reporting a conflict against it
+        let cached = self.versions.as_ref().and_then(|v| v.get(&event.stream_id));
+        let (mut current_version, mut previous) = match cached {
+            Some(version) if expected_version.map_or(true, |e| e == version) => {
+                (version, chain_hash_at(&mut tx, &event, version).await?)
+            }
+            _ => stream_head(&mut tx, &event).await?,
+        };
+        check_expected_version(expected_version, current_version)?;
+
+        let mut new_version = current_version + 1;
+        let mut chain_hash = Link::new_event(&event, new_version).hash(previous.as_deref());
+        let mut position = insert_event(&mut tx, &event, compressed.as_ref(), &chain_hash, new_version).await?;
+        if position.is_none() {
+            // The cached head was stale; another instance wrote to the stream
+            (current_version, previous) = stream_head(&mut tx, &event).await?;
+            check_expected_version(expected_version, current_version)?;
+            new_version = current_version + 1;
+            chain_hash = Link::new_event(&event, new_version).hash(previous.as_deref());
+            position = insert_event(&mut tx, &event, compressed.as_ref(), &chain_hash, new_version).await?;
+        }
+        let position = position.ok_or_else(|| {
+            AppError::Internal(format!("Version {} of {} already exists", new_version, event.stream_id))
+        })?;
+
+        tx.commit().await.map_err(classify)?;
+        if let Some(versions) = &self.versions {
+            versions.set(&event.stream_id, new_version);
+        }
+
+        Ok(Event {
+            id: event.id,
+            stream_id: event.stream_id,
+            event_type: event.event_type,
+            data: event.data.unwrap_or(serde_json::Value::Null),
+            payload: event.payload,
+            content_type: event.content_type,
+            metadata: event.metadata,
+            version: new_version,
+            position,
+            created_at: event.created_at,
+            checksum: Some(event.checksum),
+            chain_hash: Some(chain_hash),
+            link: None,
+        })
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        limit: i64,
+        direction: ReadDirection,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>> {
+        let query_str = match direction {
+            ReadDirection::Forward => READ_STREAM_FORWARD,
+            ReadDirection::Backward => READ_STREAM_BACKWARD,
+        };
+
+        let rows = self
+            .retry
+            .run_within("read_stream", &deadline, |timeout| async move {
+                let mut tx = self.begin_with_timeout(timeout).await?;
+                let rows = sqlx::query(query_str)
+                    .bind(get_partition_key(stream_id))
+                    .bind(stream_id)
+                    .bind(from_version)
+                    .bind(limit)
+                    .fetch_all(&mut *tx)
+                    .await
+                    .map_err(|e| {
+                        error!("Failed to fetch events: {}", e);
+                        e
+                    })?;
+                tx.commit().await?;
+                Ok(rows)
+            })
+            .await?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn scan_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        to_version: i64,
+        sink: mpsc::Sender<Event>,
+    ) -> Result<()> {
+        let partition_key = get_partition_key(stream_id);
+        let mut rows = sqlx::query(
+            r#"
+            SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+            FROM events
+            WHERE partition_key = $1 AND stream_id = $2 AND version BETWEEN $3 AND $4
+            ORDER BY version ASC
+            "#,
+        )
+        .bind(&partition_key)
+        .bind(stream_id)
+        .bind(from_version)
+        .bind(to_version)
+        .fetch(&self.pool);
+
+        while let Some(row) = rows.next().await {
+            let row = row.map_err(|e| {
+                error!("Failed to scan events: {}", e);
+                classify(e)
+            })?;
+            if sink.send(event_from_row(&row)?).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn read_latest(&self, stream_id: &str, count: i64, deadline: Deadline) -> Result<Vec<Event>> {
+        let rows = self
+            .retry
+            .run_within("read_latest", &deadline, |timeout| async move {
+                let mut tx = self.begin_with_timeout(timeout).await?;
+                // Walks the stream's unique index backwards from the head, then restores chronological order
+                let rows = sqlx::query(
+                    r#"
+                    SELECT * FROM (
+                        SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+                        FROM events
+                        WHERE partition_key = $1 AND stream_id = $2
+                        ORDER BY version DESC
+                        LIMIT $3
+                    ) latest
+                    ORDER BY version ASC
+                    "#,
+                )
+                .bind(get_partition_key(stream_id))
+                .bind(stream_id)
+                .bind(count)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| {
+                    error!("Failed to fetch latest events: {}", e);
+                    e
+                })?;
+                tx.commit().await?;
+                Ok(rows)
+            })
+            .await?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn find_event(&self, stream_id: &str, id: Uuid, since: DateTime<Utc>) -> Result<Option<Event>> {
+        // The primary key leads with (partition_key, id), and `since` prunes old partitions
+        let row = self
+            .retry
+            .run("find_event", || {
+                sqlx::query(
+                    r#"
+                    SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+                    FROM events
+                    WHERE partition_key = $1 AND id = $2 AND created_at >= $3 AND stream_id = $4
+                    "#,
+                )
+                .bind(get_partition_key(stream_id))
+                .bind(id)
+                .bind(since)
+                .bind(stream_id)
+                .fetch_optional(&self.pool)
+            })
+            .await?;
+
+        row.as_ref().map(event_from_row).transpose()
+    }
+
+    async fn version_at(&self, stream_id: &str, at: DateTime<Utc>) -> Result<i64> {
+        let version: Option<i64> = self
+            .retry
+            .run("version_at", || {
+                sqlx::query_scalar(
+                    r#"
+                    SELECT GREATEST(
+                        (SELECT MAX(version) FROM events WHERE partition_key = $1 AND stream_id = $2 AND created_at <= $3),
+                        (SELECT MAX(version) FROM events_archive WHERE partition_key = $1 AND stream_id = $2 AND created_at <= $3)
+                    )
+                    "#,
+                )
+                .bind(get_partition_key(stream_id))
+                .bind(stream_id)
+                .bind(at)
+                .fetch_one(&self.pool)
+            })
+            .await?;
+
+        Ok(version.unwrap_or(0))
+    }
+
+    async fn count_events(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<i64> {
+        self.retry
+            .run("count_events", || {
+                sqlx::query_scalar(
+                    r#"
+                    SELECT
+                        (SELECT COUNT(*) FROM events WHERE partition_key = $1 AND stream_id = $2 AND version BETWEEN $3 AND $4)
+                        + (SELECT COUNT(*) FROM events_archive WHERE partition_key = $1 AND stream_id = $2 AND version BETWEEN $3 AND $4)
+                    "#,
+                )
+                .bind(get_partition_key(stream_id))
+                .bind(stream_id)
+                .bind(from_version)
+                .bind(to_version)
+                .fetch_one(&self.pool)
+            })
+            .await
+    }
+
+    async fn stream_version(&self, stream_id: &str) -> Result<i64> {
+        self.retry
+            .run("stream_version", || async {
+                let mut conn = self.pool.acquire().await?;
+                get_stream_version(&mut conn, stream_id).await
+            })
+            .await
+    }
+
+    async fn load_stream_data(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        up_to_version: i64,
+    ) -> Result<Vec<serde_json::Value>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT data, payload, data_compression FROM events
+            WHERE partition_key = $1 AND stream_id = $2 AND version BETWEEN $3 AND $4
+            AND (data IS NOT NULL OR data_compression IS NOT NULL)
+            ORDER BY version
+            "#,
+        )
+        .bind(get_partition_key(stream_id))
+        .bind(stream_id)
+        .bind(from_version)
+        .bind(up_to_version)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        rows.iter().map(|row| Ok(event_body(row)?.0)).collect()
+    }
+
+    async fn replace_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO snapshots (id, stream_id, version, data, compression, reducer, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (stream_id, version)
+            DO UPDATE SET id = EXCLUDED.id, data = EXCLUDED.data, compression = EXCLUDED.compression,
+                          reducer = EXCLUDED.reducer, created_at = EXCLUDED.created_at
+            "#,
+        )
+        .bind(snapshot.id)
+        .bind(&snapshot.stream_id)
+        .bind(snapshot.version)
+        .bind(&snapshot.data)
+        .bind(snapshot.compression.as_str())
+        .bind(&snapshot.reducer)
+        .bind(snapshot.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Failed to store snapshot: {}", e);
+            AppError::Database(e.to_string())
+        })?;
+
+        Ok(())
+    }
+
+    async fn insert_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO snapshots (id, stream_id, version, data, compression, reducer, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (stream_id, version) DO NOTHING
+            "#,
+        )
+        .bind(snapshot.id)
+        .bind(&snapshot.stream_id)
+        .bind(snapshot.version)
+        .bind(&snapshot.data)
+        .bind(snapshot.compression.as_str())
+        .bind(&snapshot.reducer)
+        .bind(snapshot.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn latest_snapshot(&self, stream_id: &str) -> Result<Option<Snapshot>> {
+        let row = sqlx::query(
+            "SELECT id, stream_id, version, data, compression, reducer, created_at FROM snapshots WHERE stream_id = $1 ORDER BY version DESC LIMIT 1",
+        )
+        .bind(stream_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch snapshot: {}", e);
+            AppError::Database(e.to_string())
+        })?;
+
+        row.as_ref().map(snapshot_from_row).transpose()
+    }
+
+    async fn snapshot_at(&self, stream_id: &str, version: i64) -> Result<Option<Snapshot>> {
+        let row = self
+            .retry
+            .run("snapshot_at", || {
+                sqlx::query(
+                    r#"
+                    SELECT id, stream_id, version, data, compression, reducer, created_at FROM snapshots
+                    WHERE stream_id = $1 AND version <= $2
+                    ORDER BY version DESC LIMIT 1
+                    "#,
+                )
+                .bind(stream_id)
+                .bind(version)
+                .fetch_optional(&self.pool)
+            })
+            .await?;
+
+        row.as_ref().map(snapshot_from_row).transpose()
+    }
+
+    async fn snapshot_versions(&self, stream_id: &str) -> Result<Vec<i64>> {
+        let rows = sqlx::query("SELECT version FROM snapshots WHERE stream_id = $1 ORDER BY version")
+            .bind(stream_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter().map(|row| Ok(row.try_get("version")?)).collect()
+    }
+
+    async fn delete_snapshots(&self, stream_id: &str, versions: &[i64]) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM snapshots WHERE stream_id = $1 AND version = ANY($2)")
+            .bind(stream_id)
+            .bind(versions)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn snapshot_candidates(&self, threshold: i64) -> Result<Vec<SnapshotCandidate>> {
+        // Find streams that need snapshots (version > last_snapshot_version + threshold)
+        let rows = sqlx::query(
+            r#"
+            SELECT e.stream_id, MAX(e.version) as current_version,
+                   COALESCE(s.version, 0) as snapshot_version
+            FROM events e
+            LEFT JOIN (
+                SELECT stream_id, MAX(version) AS version FROM snapshots GROUP BY stream_id
+            ) s ON e.stream_id = s.stream_id
+            GROUP BY e.stream_id, s.version
+            HAVING MAX(e.version) - COALESCE(s.version, 0) >= $1
+            "#,
+        )
+        .bind(threshold)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(SnapshotCandidate {
+                    stream_id: row.try_get("stream_id")?,
+                    current_version: row.try_get("current_version")?,
+                    snapshot_version: row.try_get("snapshot_version")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn archivable_ranges(&self, threshold: DateTime<Utc>) -> Result<Vec<ArchiveRange>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT e.stream_id, MIN(e.version) AS from_version, MAX(e.version) AS to_version
+            FROM events e
+            WHERE e.created_at < $1
+            AND e.stream_id IN (SELECT stream_id FROM snapshots)
+            AND e.stream_id NOT IN (
+                SELECT stream_id FROM stream_metadata
+                WHERE COALESCE((metadata->>'legal_hold')::boolean, false)
+            )
+            AND e.version < (
+                SELECT MAX(h.version) FROM events h
+                WHERE h.partition_key = e.partition_key AND h.stream_id = e.stream_id
+            )
+            GROUP BY e.stream_id
+            ORDER BY e.stream_id
+            "#,
+        )
+        .bind(threshold)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(ArchiveRange {
+                    stream_id: row.try_get("stream_id")?,
+                    from_version: row.try_get("from_version")?,
+                    to_version: row.try_get("to_version")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn archive_ranges(&self, ranges: &[ArchiveRange]) -> Result<u64> {
+        let mut archived = 0;
+
+        for range in ranges {
+            let mut tx = self.pool.begin().await.map_err(classify)?;
+            lock_stream_ids(&mut tx, &[range.stream_id.clone()]).await?;
+            let result = sqlx::query(
+                r#"
+                WITH moved AS (
+                    DELETE FROM events
+                    WHERE partition_key = $1 AND stream_id = $2 AND version BETWEEN $3 AND $4
+                    AND version < (SELECT MAX(version) FROM events WHERE partition_key = $1 AND stream_id = $2)
+                    RETURNING *
+                )
+                INSERT INTO events_archive
+                    (id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id, data_compression, checksum, chain_hash, expires_at)
+                SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id, data_compression, checksum, chain_hash, expires_at
+                FROM moved
+                "#,
+            )
+            .bind(get_partition_key(&range.stream_id))
+            .bind(&range.stream_id)
+            .bind(range.from_version)
+            .bind(range.to_version)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+            tx.commit().await.map_err(classify)?;
+
+            archived += result.rows_affected();
+        }
+
+        self.drop_empty_partitions().await?;
+        Ok(archived)
+    }
+
+    async fn read_archived(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<Vec<Event>> {
+        let rows = self
+            .retry
+            .run("read_archived", || {
+                sqlx::query(
+                    r#"
+                    SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+                    FROM events_archive
+                    WHERE partition_key = $1 AND stream_id = $2 AND version BETWEEN $3 AND $4
+                    ORDER BY version ASC
+                    "#,
+                )
+                .bind(get_partition_key(stream_id))
+                .bind(stream_id)
+                .bind(from_version)
+                .bind(to_version)
+                .fetch_all(&self.pool)
+            })
+            .await?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn rewrite_events(
+        &self,
+        stream_id: &str,
+        head: i64,
+        head_hash: Option<&str>,
+        rewrites: &[EventRewrite],
+    ) -> Result<u64> {
+        let partition_key = get_partition_key(stream_id);
+        let (mut tx, heads) = self.lock_streams(&[stream_id.to_string()], &Deadline(None)).await?;
+        let (current, current_hash) = heads.get(stream_id).cloned().unwrap_or((0, None));
+        check_rewrite_head(stream_id, (head, head_hash), (current, current_hash.as_deref()))?;
+        let mut rewritten = 0;
+
+        for rewrite in rewrites {
+            for table in ["events", "events_archive"] {
+                let result = sqlx::query(&format!(
+                    r#"
+                    UPDATE {}
+                    SET data = COALESCE($4, data),
+                        payload = CASE WHEN $4 IS NULL THEN payload END,
+                        data_compression = CASE WHEN $4 IS NULL THEN data_compression END,
+                        checksum = $5, chain_hash = $6
+                    WHERE partition_key = $1 AND stream_id = $2 AND version = $3
+                    "#,
+                    table
+                ))
+                .bind(&partition_key)
+                .bind(stream_id)
+                .bind(rewrite.version)
+                .bind(&rewrite.data)
+                .bind(&rewrite.checksum)
+                .bind(&rewrite.chain_hash)
+                .execute(&mut *tx)
+                .await
+                .map_err(classify)?;
+                if result.rows_affected() > 0 {
+                    rewritten += 1;
+                    break;
+                }
+            }
+        }
+
+        let redacted: Vec<i64> = rewrites.iter().filter(|r| r.data.is_some()).map(|r| r.version).collect();
+        sqlx::query("DELETE FROM event_search WHERE partition_key = $1 AND stream_id = $2 AND version = ANY($3)")
+            .bind(&partition_key)
+            .bind(stream_id)
+            .bind(&redacted)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+
+        tx.commit().await.map_err(classify)?;
+        Ok(rewritten)
+    }
+
+    async fn delete_events_through(&self, stream_id: &str, version: i64) -> Result<u64> {
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+        lock_stream_ids(&mut tx, &[stream_id.to_string()]).await?;
+        let result = sqlx::query(
+            r#"
+            DELETE FROM events
+            WHERE partition_key = $1 AND stream_id = $2 AND version <= $3
+            AND version < (SELECT MAX(version) FROM events WHERE partition_key = $1 AND stream_id = $2)
+            "#,
+        )
+        .bind(get_partition_key(stream_id))
+        .bind(stream_id)
+        .bind(version)
+        .execute(&mut *tx)
+        .await
+        .map_err(classify)?;
+        tx.commit().await.map_err(classify)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn migration_status(&self) -> Result<MigrationStatus> {
+        let tracked: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(classify)?;
+        let applied = if tracked {
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(classify)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(MigrationStatus::new(&MIGRATOR, applied))
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await.map_err(classify)?;
+        Ok(())
+    }
+
+    fn pool_connections(&self) -> Option<(u32, usize)> {
+        Some((self.pool.size(), self.pool.num_idle()))
+    }
+
+    async fn acquire_leadership(&self, holder: &str, lease: Duration) -> Result<Option<i64>> {
+        // Expiry is judged by the database's clock, so instances' clocks may drift
+        let epoch: Option<i64> = sqlx::query_scalar(
+            r#"
+            INSERT INTO leader_lease (holder, epoch, expires_at)
+            VALUES ($1, 1, now() + make_interval(secs => $2))
+            ON CONFLICT (id) DO UPDATE SET
+                epoch = CASE
+                    WHEN leader_lease.holder = EXCLUDED.holder THEN leader_lease.epoch
+                    ELSE leader_lease.epoch + 1
+                END,
+                holder = EXCLUDED.holder,
+                expires_at = EXCLUDED.expires_at
+            WHERE leader_lease.holder = EXCLUDED.holder OR leader_lease.expires_at < now()
+            RETURNING epoch
+            "#,
+        )
+        .bind(holder)
+        .bind(lease.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        if let Some(fence) = &self.fence {
+            let previous = fence.swap(epoch.unwrap_or(0), Ordering::SeqCst);
+            // Heads cached before the last takeover may have moved since
+            if previous != epoch.unwrap_or(0) {
+                if let Some(versions) = &self.versions {
+                    versions.clear();
+                }
+            }
+        }
+        Ok(epoch)
+    }
+
+    async fn replication_lag(&self) -> Result<Option<f64>> {
+        // NULL when no standby is streaming from us
+        sqlx::query_scalar("SELECT EXTRACT(EPOCH FROM MAX(replay_lag))::float8 FROM pg_stat_replication")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(classify)
+    }
+
+    async fn prepare_partitions(&self, months_ahead: u32) -> Result<()> {
+        let keys: Vec<String> = sqlx::query_scalar("SELECT DISTINCT partition_key FROM event_partitions")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        for key in &keys {
+            let mut month = month_bounds(Utc::now()).0;
+            for _ in 0..=months_ahead {
+                self.ensure_partition(key, month).await?;
+                month = month_bounds(month).1;
+            }
+        }
+
+        info!("Partitions prepared {} months ahead for {} projects", months_ahead, keys.len());
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        // Live row counts kept by the statistics collector and the stats
+        // rollup, instead of counting every event
+        let row = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COALESCE(SUM(s.n_live_tup), 0)::BIGINT
+                 FROM pg_partition_tree('events') t JOIN pg_stat_user_tables s ON s.relid = t.relid
+                 WHERE t.isleaf) AS total_events,
+                (SELECT COALESCE(SUM(streams_created), 0)::BIGINT FROM event_stats_daily) AS total_streams,
+                COALESCE((SELECT n_live_tup FROM pg_stat_user_tables WHERE relid = 'snapshots'::regclass), 0) AS total_snapshots,
+                (SELECT COALESCE(SUM(pg_total_relation_size(relid)), 0)::BIGINT FROM pg_partition_tree('events'))
+                    + pg_total_relation_size('events_archive')
+                    + pg_total_relation_size('snapshots') AS storage_bytes
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        let events_per_day = sqlx::query(
+            r#"
+            SELECT day, SUM(events)::BIGINT AS events FROM event_stats_daily
+            WHERE day >= $1
+            GROUP BY day
+            ORDER BY day
+            "#,
+        )
+        .bind(stats_since())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(StoreStats {
+            total_events: row.try_get("total_events")?,
+            total_streams: row.try_get("total_streams")?,
+            total_snapshots: row.try_get("total_snapshots")?,
+            storage_bytes: Some(row.try_get("storage_bytes")?),
+            events_per_day: events_per_day.iter().map(daily_count_from_row).collect::<Result<_>>()?,
+        })
+    }
+
+    async fn is_empty(&self) -> Result<bool> {
+        sqlx::query_scalar("SELECT NOT EXISTS (SELECT 1 FROM events) AND NOT EXISTS (SELECT 1 FROM events_archive)")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(classify)
+    }
+
+    async fn tenant_stats(&self, tenant_id: &str) -> Result<StoreStats> {
+        // The tenant's project partition and its months hold all its events
+        let row = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COALESCE(SUM(s.n_live_tup), 0)::BIGINT
+                 FROM pg_partition_tree(to_regclass($2)) t JOIN pg_stat_user_tables s ON s.relid = t.relid
+                 WHERE t.isleaf) AS total_events,
+                (SELECT COALESCE(SUM(streams_created), 0)::BIGINT FROM event_stats_daily WHERE partition_key = $1) AS total_streams,
+                (SELECT COUNT(*) FROM snapshots WHERE split_part(stream_id, '/', 1) = $1) AS total_snapshots,
+                (SELECT COALESCE(SUM(pg_total_relation_size(relid)), 0)::BIGINT FROM pg_partition_tree(to_regclass($2))) AS storage_bytes
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(partition_table_name(tenant_id))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        let events_per_day = sqlx::query(
+            r#"
+            SELECT day, events FROM event_stats_daily
+            WHERE partition_key = $1 AND day >= $2
+            ORDER BY day
+            "#,
+        )
+        .bind(tenant_id)
+        .bind(stats_since())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(StoreStats {
+            total_events: row.try_get("total_events")?,
+            total_streams: row.try_get("total_streams")?,
+            total_snapshots: row.try_get("total_snapshots")?,
+            storage_bytes: Some(row.try_get("storage_bytes")?),
+            events_per_day: events_per_day.iter().map(daily_count_from_row).collect::<Result<_>>()?,
+        })
+    }
+
+    async fn roll_up_stats(&self, limit: i64) -> Result<u64> {
+        let committed = self.committed_position().await?;
+
+        // The cursor row lock keeps instances from counting a batch twice
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+        let after: i64 = sqlx::query_scalar("SELECT position FROM event_stats_cursor FOR UPDATE")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(classify)?;
+        let row = sqlx::query(
+            r#"
+            WITH batch AS (
+                SELECT partition_key, created_at, version, position FROM events
+                WHERE position > $1 AND position <= $2
+                ORDER BY position
+                LIMIT $3
+            ), counted AS (
+                INSERT INTO event_stats_daily (partition_key, day, events, streams_created)
+                SELECT partition_key, (created_at AT TIME ZONE 'UTC')::date, COUNT(*), COUNT(*) FILTER (WHERE version = 1)
+                FROM batch
+                GROUP BY 1, 2
+                ON CONFLICT (partition_key, day) DO UPDATE
+                SET events = event_stats_daily.events + EXCLUDED.events,
+                    streams_created = event_stats_daily.streams_created + EXCLUDED.streams_created
+            )
+            SELECT COUNT(*) AS counted, MAX(position) AS last FROM batch
+            "#,
+        )
+        .bind(after)
+        .bind(committed)
+        .bind(limit)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(classify)?;
+
+        let counted: i64 = row.try_get("counted")?;
+        if let Some(last) = row.try_get::<Option<i64>, _>("last")? {
+            // The same batch into the time series. A stream counts as active
+            // in a bucket only the first time it is seen there.
+            sqlx::query(
+                r#"
+                WITH bucketed AS (
+                    SELECT g.granularity,
+                           date_trunc(g.granularity, e.created_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS bucket,
+                           e.partition_key, e.stream_id,
+                           COALESCE(octet_length(e.payload), octet_length(e.data::text), 0) AS bytes
+                    FROM events e CROSS JOIN (VALUES ('hour'), ('day')) AS g(granularity)
+                    WHERE e.position > $1 AND e.position <= $2
+                ), joined AS (
+                    INSERT INTO event_stats_streams (granularity, bucket, partition_key, stream_id)
+                    SELECT DISTINCT granularity, bucket, partition_key, stream_id FROM bucketed
+                    ON CONFLICT DO NOTHING
+                    RETURNING granularity, bucket, partition_key
+                ), active AS (
+                    SELECT granularity, bucket, partition_key, COUNT(*) AS streams FROM joined GROUP BY 1, 2, 3
+                )
+                INSERT INTO event_stats_timeseries (granularity, bucket, partition_key, events, bytes, active_streams)
+                SELECT b.granularity, b.bucket, b.partition_key, COUNT(*), SUM(b.bytes), COALESCE(MAX(a.streams), 0)
+                FROM bucketed b LEFT JOIN active a USING (granularity, bucket, partition_key)
+                GROUP BY 1, 2, 3
+                ON CONFLICT (granularity, bucket, partition_key) DO UPDATE
+                SET events = event_stats_timeseries.events + EXCLUDED.events,
+                    bytes = event_stats_timeseries.bytes + EXCLUDED.bytes,
+                    active_streams = event_stats_timeseries.active_streams + EXCLUDED.active_streams
+                "#,
+            )
+            .bind(after)
+            .bind(last)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+
+            sqlx::query("UPDATE event_stats_cursor SET position = $1")
+                .bind(last)
+                .execute(&mut *tx)
+                .await
+                .map_err(classify)?;
+        }
+        // Events land in past buckets only when imported, so membership is
+        // kept for two days, hourly buckets for as long as can be queried
+        sqlx::query("DELETE FROM event_stats_streams WHERE bucket < NOW() - INTERVAL '2 days'")
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+        sqlx::query("DELETE FROM event_stats_timeseries WHERE granularity = 'hour' AND bucket < $1")
+            .bind(Utc::now() - chrono::Duration::days(MAX_HOURLY_DAYS))
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+        tx.commit().await.map_err(classify)?;
+        Ok(counted as u64)
+    }
+
+    async fn usage_timeseries(
+        &self,
+        tenant_id: Option<&str>,
+        granularity: Granularity,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UsageBucket>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT bucket, SUM(events)::BIGINT AS events, SUM(bytes)::BIGINT AS bytes,
+                   SUM(active_streams)::BIGINT AS active_streams
+            FROM event_stats_timeseries
+            WHERE granularity = $1 AND bucket >= $2 AND ($3::text IS NULL OR partition_key = $3)
+            GROUP BY bucket
+            ORDER BY bucket
+            "#,
+        )
+        .bind(granularity.as_str())
+        .bind(since)
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(UsageBucket {
+                    bucket: row.try_get("bucket")?,
+                    events: row.try_get("events")?,
+                    bytes: row.try_get("bytes")?,
+                    active_streams: row.try_get("active_streams")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn stream_metadata(&self, stream_id: &str) -> Result<Option<StreamMetadata>> {
+        let row = self
+            .retry
+            .run("stream_metadata", || {
+                sqlx::query("SELECT metadata FROM stream_metadata WHERE stream_id = $1")
+                    .bind(stream_id)
+                    .fetch_optional(&self.pool)
+            })
+            .await?;
+
+        match row {
+            Some(row) => {
+                let metadata: serde_json::Value = row.try_get("metadata")?;
+                Ok(Some(serde_json::from_value(metadata)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn all_stream_metadata(&self) -> Result<Vec<(String, StreamMetadata)>> {
+        let rows = sqlx::query("SELECT stream_id, metadata FROM stream_metadata ORDER BY stream_id")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let metadata: serde_json::Value = row.try_get("metadata")?;
+                Ok((row.try_get("stream_id")?, serde_json::from_value(metadata)?))
+            })
+            .collect()
+    }
+
+    async fn set_stream_metadata(&self, stream_id: &str, metadata: &StreamMetadata) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO stream_metadata (stream_id, metadata, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (stream_id) DO UPDATE SET metadata = EXCLUDED.metadata, updated_at = NOW()
+            "#,
+        )
+        .bind(stream_id)
+        .bind(serde_json::to_value(metadata)?)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn project_summaries(&self) -> Result<Vec<ProjectSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT partition_key,
+                   COUNT(DISTINCT stream_id) AS stream_count,
+                   COUNT(*) AS event_count,
+                   MIN(created_at) AS oldest_event_at,
+                   MAX(created_at) AS newest_event_at
+            FROM events
+            GROUP BY partition_key
+            ORDER BY partition_key
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(ProjectSummary {
+                    project_id: row.try_get("partition_key")?,
+                    stream_count: row.try_get("stream_count")?,
+                    event_count: row.try_get("event_count")?,
+                    oldest_event_at: row.try_get("oldest_event_at")?,
+                    newest_event_at: row.try_get("newest_event_at")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn sample_storage_usage(&self) -> Result<u64> {
+        // Every project the stats rollup has seen. Hot events are measured
+        // by the size of the project's partitions, archived ones by their rows.
+        let projects: Vec<String> = sqlx::query_scalar("SELECT DISTINCT partition_key FROM event_stats_daily")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+        let tables: Vec<String> = projects.iter().map(|p| partition_table_name(p)).collect();
+
+        let result = sqlx::query(
+            r#"
+            WITH projects AS (
+                SELECT * FROM unnest($1::text[], $2::text[]) AS p(partition_key, table_name)
+            ), archived AS (
+                SELECT partition_key, SUM(pg_column_size(a.*))::BIGINT AS bytes
+                FROM events_archive a
+                GROUP BY partition_key
+            )
+            INSERT INTO usage_storage_daily (partition_key, day, bytes)
+            SELECT p.partition_key, (NOW() AT TIME ZONE 'UTC')::date,
+                   COALESCE((SELECT SUM(pg_total_relation_size(t.relid))
+                             FROM pg_partition_tree(to_regclass(p.table_name)) t), 0)::BIGINT
+                       + COALESCE(a.bytes, 0)
+            FROM projects p LEFT JOIN archived a USING (partition_key)
+            ON CONFLICT (partition_key, day) DO UPDATE
+            SET bytes = GREATEST(usage_storage_daily.bytes, EXCLUDED.bytes)
+            "#,
+        )
+        .bind(&projects)
+        .bind(&tables)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn monthly_usage(&self, month: NaiveDate) -> Result<Vec<ProjectUsage>> {
+        let end = month + chrono::Months::new(1);
+        // Appends come from the daily buckets of the usage time series
+        let rows = sqlx::query(
+            r#"
+            WITH appended AS (
+                SELECT partition_key, SUM(events)::BIGINT AS events, SUM(bytes)::BIGINT AS bytes
+                FROM event_stats_timeseries
+                WHERE granularity = 'day' AND bucket >= $1 AND bucket < $2
+                GROUP BY partition_key
+            ), stored AS (
+                SELECT partition_key, AVG(bytes)::BIGINT AS avg_bytes, MAX(bytes) AS peak_bytes
+                FROM usage_storage_daily
+                WHERE day >= $3 AND day < $4
+                GROUP BY partition_key
+            )
+            SELECT partition_key AS project_id,
+                   COALESCE(a.events, 0) AS events,
+                   COALESCE(a.bytes, 0) AS bytes_appended,
+                   COALESCE(s.avg_bytes, 0) AS avg_stored_bytes,
+                   COALESCE(s.peak_bytes, 0) AS peak_stored_bytes
+            FROM appended a FULL JOIN stored s USING (partition_key)
+            ORDER BY partition_key
+            "#,
+        )
+        .bind(month.and_time(NaiveTime::MIN).and_utc())
+        .bind(end.and_time(NaiveTime::MIN).and_utc())
+        .bind(month)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(ProjectUsage {
+                    project_id: row.try_get("project_id")?,
+                    events: row.try_get("events")?,
+                    bytes_appended: row.try_get("bytes_appended")?,
+                    avg_stored_bytes: row.try_get("avg_stored_bytes")?,
+                    peak_stored_bytes: row.try_get("peak_stored_bytes")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn legal_hold_streams(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT stream_id FROM stream_metadata WHERE COALESCE((metadata->>'legal_hold')::boolean, false)",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| Ok(row.try_get("stream_id")?))
+            .collect()
+    }
+
+    async fn stream_heads(&self, prefix: &str) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT stream_id, MAX(version) AS version
+            FROM events
+            WHERE substr(stream_id, 1, length($1)) = $1
+            GROUP BY stream_id
+            ORDER BY stream_id
+            "#,
+        )
+        .bind(prefix)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| Ok((row.try_get("stream_id")?, row.try_get("version")?)))
+            .collect()
+    }
+
+    async fn committed_position(&self) -> Result<i64> {
+        // Positions come from a sequence, so a transaction can hold a lower
+        // position than one that already committed. Take the last allocated
+        // position, then wait for every transaction running now to finish.
+        let allocated: Option<i64> = sqlx::query_scalar(
+            "SELECT pg_sequence_last_value(pg_get_serial_sequence('events', 'position')::regclass)",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        let xmax: String = sqlx::query_scalar("SELECT pg_snapshot_xmax(pg_current_snapshot())::text")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        for _ in 0..600 {
+            let settled: bool =
+                sqlx::query_scalar("SELECT pg_snapshot_xmin(pg_current_snapshot()) >= $1::xid8")
+                    .bind(&xmax)
+                    .fetch_one(&self.pool)
+                    .await
+                    .map_err(classify)?;
+            if settled {
+                return Ok(allocated.unwrap_or(0));
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        Err(AppError::DeadlineExceeded(
+            "Timed out waiting for in-flight appends to commit".to_string(),
+        ))
+    }
+
+    async fn read_all(&self, after: i64, limit: i64) -> Result<Vec<Event>> {
+        let rows = self
+            .retry
+            .run("read_all", || {
+                sqlx::query(
+                    r#"
+                    SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+                    FROM events
+                    WHERE position > $1
+                    ORDER BY position
+                    LIMIT $2
+                    "#,
+                )
+                .bind(after)
+                .bind(limit)
+                .fetch_all(&self.pool)
+            })
+            .await?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn read_all_with_archive(&self, after: i64, limit: i64) -> Result<Vec<Event>> {
+        let rows = self
+            .retry
+            .run("read_all_with_archive", || {
+                sqlx::query(
+                    r#"
+                    SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+                    FROM events
+                    WHERE position > $1
+                    UNION ALL
+                    SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+                    FROM events_archive
+                    WHERE position > $1
+                    ORDER BY position
+                    LIMIT $2
+                    "#,
+                )
+                .bind(after)
+                .bind(limit)
+                .fetch_all(&self.pool)
+            })
+            .await?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn search_position(&self) -> Result<i64> {
+        sqlx::query_scalar("SELECT position FROM event_search_cursor")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(classify)
+    }
+
+    async fn index_search_documents(&self, documents: &[SearchDocument], through: i64) -> Result<()> {
+        let positions: Vec<i64> = documents.iter().map(|d| d.position).collect();
+        let partition_keys: Vec<String> = documents.iter().map(|d| get_partition_key(&d.stream_id)).collect();
+        let stream_ids: Vec<String> = documents.iter().map(|d| d.stream_id.clone()).collect();
+        let versions: Vec<i64> = documents.iter().map(|d| d.version).collect();
+        let contents: Vec<String> = documents.iter().map(|d| d.content.clone()).collect();
+
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+        sqlx::query(
+            r#"
+            INSERT INTO event_search (position, partition_key, stream_id, version, content, document)
+            SELECT d.position, d.partition_key, d.stream_id, d.version, d.content, to_tsvector('simple', d.content)
+            FROM unnest($1::bigint[], $2::text[], $3::text[], $4::bigint[], $5::text[])
+                AS d(position, partition_key, stream_id, version, content)
+            ON CONFLICT (position) DO NOTHING
+            "#,
+        )
+        .bind(&positions)
+        .bind(&partition_keys)
+        .bind(&stream_ids)
+        .bind(&versions)
+        .bind(&contents)
+        .execute(&mut *tx)
+        .await
+        .map_err(classify)?;
+        // Instances indexing the same batch at once only ever move it forward
+        sqlx::query("UPDATE event_search_cursor SET position = GREATEST(position, $1)")
+            .bind(through)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+        tx.commit().await.map_err(classify)?;
+        Ok(())
+    }
+
+    async fn search_events(
+        &self,
+        tenant_id: Option<&str>,
+        query: &str,
+        before: i64,
+        limit: i64,
+    ) -> Result<Vec<SearchHit>> {
+        // Headlines are built for the page only, and the event is read from
+        // wherever it is kept now; the position check skips a recreated stream
+        let rows = self
+            .retry
+            .run("search_events", || {
+                sqlx::query(
+                    r#"
+                    WITH hits AS (
+                        SELECT s.position, s.partition_key, s.stream_id, s.version, s.content
+                        FROM event_search s
+                        WHERE s.document @@ websearch_to_tsquery('simple', $1)
+                          AND s.position < $2 AND ($3::text IS NULL OR s.partition_key = $3)
+                        ORDER BY s.position DESC
+                        LIMIT $4
+                    )
+                    SELECT ts_headline('simple', h.content, websearch_to_tsquery('simple', $1),
+                                       'StartSel=<mark>, StopSel=</mark>, MaxFragments=3') AS highlight,
+                           e.*
+                    FROM hits h
+                    JOIN LATERAL (
+                        SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+                        FROM events
+                        WHERE partition_key = h.partition_key AND stream_id = h.stream_id
+                          AND version = h.version AND position = h.position
+                        UNION ALL
+                        SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, data_compression, checksum, chain_hash
+                        FROM events_archive
+                        WHERE partition_key = h.partition_key AND stream_id = h.stream_id
+                          AND version = h.version AND position = h.position
+                        LIMIT 1
+                    ) e ON true
+                    ORDER BY h.position DESC
+                    "#,
+                )
+                .bind(query)
+                .bind(before)
+                .bind(tenant_id)
+                .bind(limit)
+                .fetch_all(&self.pool)
+            })
+            .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(SearchHit {
+                    event: event_from_row(row)?,
+                    highlight: row.try_get("highlight")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn prune_search_index(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM event_search s
+            WHERE NOT EXISTS (
+                SELECT 1 FROM events e
+                WHERE e.partition_key = s.partition_key AND e.stream_id = s.stream_id
+                  AND e.version = s.version AND e.position = s.position
+            ) AND NOT EXISTS (
+                SELECT 1 FROM events_archive a
+                WHERE a.partition_key = s.partition_key AND a.stream_id = s.stream_id
+                  AND a.version = s.version AND a.position = s.position
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn read_streams(
+        &self,
+        stream_ids: &[String],
+        after: MergeCursor,
+        order: MergeOrder,
+        limit: i64,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>> {
+        let query_str = match order {
+            MergeOrder::Position => READ_STREAMS_BY_POSITION,
+            MergeOrder::Timestamp => READ_STREAMS_BY_TIMESTAMP,
+        };
+        let partition_keys: Vec<String> = stream_ids.iter().map(|id| get_partition_key(id)).collect();
+        let partition_keys = &partition_keys;
+
+        let rows = self
+            .retry
+            .run_within("read_streams", &deadline, |timeout| async move {
+                let mut tx = self.begin_with_timeout(timeout).await?;
+                let rows = sqlx::query(query_str)
+                    .bind(partition_keys)
+                    .bind(stream_ids)
+                    .bind(after.created_at)
+                    .bind(after.position)
+                    .bind(limit)
+                    .fetch_all(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                Ok(rows)
+            })
+            .await?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn import_events(&self, events: &[Event]) -> Result<()> {
+        for event in events {
+            self.ensure_partition(&get_partition_key(&event.stream_id), event.created_at)
+                .await?;
+        }
+
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+        self.check_fence(&mut tx).await?;
+        let stream_ids: Vec<String> = events.iter().map(|e| e.stream_id.clone()).collect();
+        lock_stream_ids(&mut tx, &stream_ids).await?;
+        for event in events {
+            insert_verbatim(&mut tx, event).await?;
+        }
+
+        // Continue numbering after the imported positions
+        sqlx::query(
+            "SELECT setval(pg_get_serial_sequence('events', 'position'), COALESCE(MAX(position), 0) + 1, false) FROM events",
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(classify)?;
+
+        tx.commit().await.map_err(classify)?;
+        if let Some(versions) = &self.versions {
+            versions.clear();
+        }
+        Ok(())
+    }
+
+    async fn replication_checkpoint(&self) -> Result<i64> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(
+                (SELECT position FROM replication_checkpoint),
+                GREATEST((SELECT MAX(position) FROM events), (SELECT MAX(position) FROM events_archive)),
+                0
+            )
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(classify)
+    }
+
+    async fn apply_replicated(&self, after: i64, through: i64, events: &[Event]) -> Result<()> {
+        for event in events {
+            self.ensure_partition(&get_partition_key(&event.stream_id), event.created_at)
+                .await?;
+        }
+
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+        self.check_fence(&mut tx).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO replication_checkpoint (position)
+            SELECT COALESCE(GREATEST((SELECT MAX(position) FROM events), (SELECT MAX(position) FROM events_archive)), 0)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(classify)?;
+
+        let checkpoint: i64 = sqlx::query_scalar("SELECT position FROM replication_checkpoint FOR UPDATE")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(classify)?;
+        if checkpoint != after {
+            return Err(AppError::Conflict(format!(
+                "Replication checkpoint is at {}, not {}",
+                checkpoint, after
+            )));
+        }
+
+        let stream_ids: Vec<String> = events.iter().map(|e| e.stream_id.clone()).collect();
+        lock_stream_ids(&mut tx, &stream_ids).await?;
+        for event in events {
+            insert_verbatim(&mut tx, event).await?;
+        }
+        let changes: Vec<Change> = events
+            .iter()
+            .map(|event| Change {
+                partition_key: get_partition_key(&event.stream_id),
+                stream_id: event.stream_id.clone(),
+                version: event.version,
+                position: event.position,
+            })
+            .collect();
+        notify_changes(&mut tx, &changes).await?;
+
+        // The sequence stands where the primary's did, so committed_position
+        // and any later append carry on from the batch
+        sqlx::query("SELECT setval(pg_get_serial_sequence('events', 'position'), $1)")
+            .bind(through)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+        sqlx::query("UPDATE replication_checkpoint SET position = $1")
+            .bind(through)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+
+        tx.commit().await.map_err(classify)?;
+        if let Some(versions) = &self.versions {
+            versions.clear();
+        }
+        Ok(())
+    }
+
+    async fn import_stream(&self, stream_id: &str, events: Vec<Event>) -> Result<Vec<Event>> {
+        let Some(first) = events.first() else {
+            return Ok(events);
+        };
+        let first_version = first.version;
+        let partition_key = get_partition_key(stream_id);
+        for event in &events {
+            self.ensure_partition(&partition_key, event.created_at).await?;
+        }
+
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+        self.check_fence(&mut tx).await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+            .bind(stream_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+
+        let current_version = get_stream_version(&mut tx, stream_id).await.map_err(classify)?;
+        if first_version != current_version + 1 {
+            return Err(AppError::Conflict(format!(
+                "Import starts at version {} but {} is at version {}",
+                first_version, stream_id, current_version
+            )));
+        }
+
+        let mut stored = Vec::with_capacity(events.len());
+        for mut event in events {
+            event.position = sqlx::query_scalar(
+                r#"
+                INSERT INTO events (id, stream_id, event_type, data, payload, content_type, metadata, version, created_at, partition_key, tenant_id, checksum, chain_hash)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10, $11, $12)
+                RETURNING position
+                "#,
+            )
+            .bind(event.id)
+            .bind(stream_id)
+            .bind(&event.event_type)
+            .bind(event.payload.is_none().then_some(&event.data))
+            .bind(&event.payload)
+            .bind(&event.content_type)
+            .bind(&event.metadata)
+            .bind(event.version)
+            .bind(event.created_at)
+            .bind(&partition_key)
+            .bind(&event.checksum)
+            .bind(&event.chain_hash)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| {
+                error!("Failed to import event {}: {}", event.id, e);
+                classify(e)
+            })?;
+            stored.push(event);
+        }
+
+        tx.commit().await.map_err(classify)?;
+        if let (Some(versions), Some(last)) = (&self.versions, stored.last()) {
+            versions.set(stream_id, last.version);
+        }
+        Ok(stored)
+    }
+
+    /// Streams the batch into a temporary table with `COPY ... FROM STDIN`,
+    /// checks every stream's first version against its head under the
+    /// per-stream append locks, then moves the rows into `events` with a
+    /// single `INSERT ... SELECT`, which assigns positions in order.
+    async fn bulk_load(&self, events: Vec<Event>) -> Result<u64> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+
+        let mut months = HashSet::new();
+        for event in &events {
+            let partition_key = get_partition_key(&event.stream_id);
+            if months.insert((partition_key.clone(), month_bounds(event.created_at).0)) {
+                self.ensure_partition(&partition_key, event.created_at).await?;
+            }
+        }
+
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+        self.check_fence(&mut tx).await?;
+        sqlx::query(
+            r#"
+            CREATE TEMP TABLE bulk_events (
+                id UUID NOT NULL,
+                stream_id VARCHAR NOT NULL,
+                event_type VARCHAR NOT NULL,
+                data JSONB,
+                payload BYTEA,
+                content_type VARCHAR NOT NULL,
+                metadata JSONB,
+                version BIGINT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                partition_key VARCHAR NOT NULL,
+                checksum VARCHAR,
+                chain_hash VARCHAR,
+                seq BIGINT NOT NULL
+            ) ON COMMIT DROP
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(classify)?;
+
+        // Encode up front so a bad event fails before COPY starts
+        let mut chunks = Vec::new();
+        for (chunk, rows) in events.chunks(BULK_COPY_ROWS).enumerate() {
+            let mut csv = String::new();
+            for (i, event) in rows.iter().enumerate() {
+                write_csv_row(&mut csv, event, chunk * BULK_COPY_ROWS + i)?;
+            }
+            chunks.push(csv.into_bytes());
+        }
+
+        let mut copy = (*tx)
+            .copy_in_raw(
+                "COPY bulk_events (id, stream_id, event_type, data, payload, content_type, metadata, version, created_at, partition_key, checksum, chain_hash, seq) FROM STDIN (FORMAT csv)",
+            )
+            .await
+            .map_err(classify)?;
+        for chunk in chunks {
+            if let Err(e) = copy.send(chunk).await {
+                let _ = copy.abort("bulk load failed").await;
+                return Err(classify(e));
+            }
+        }
+        let loaded = copy.finish().await.map_err(classify)?;
+
+        // Lock in a fixed order so concurrent bulk loads can't deadlock
+        sqlx::query(
+            "SELECT pg_advisory_xact_lock(hashtextextended(stream_id, 0)) FROM (SELECT DISTINCT stream_id FROM bulk_events ORDER BY stream_id) s",
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(classify)?;
+
+        let conflict = sqlx::query(
+            r#"
+            SELECT b.stream_id, b.first_version, COALESCE(MAX(e.version), 0) AS current_version
+            FROM (
+                SELECT stream_id, partition_key, MIN(version) AS first_version
+                FROM bulk_events
+                GROUP BY stream_id, partition_key
+            ) b
+            LEFT JOIN events e ON e.partition_key = b.partition_key AND e.stream_id = b.stream_id
+            GROUP BY b.stream_id, b.first_version
+            HAVING b.first_version <> COALESCE(MAX(e.version), 0) + 1
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(classify)?;
+        if let Some(row) = conflict {
+            let stream_id: String = row.get("stream_id");
+            let first_version: i64 = row.get("first_version");
+            let current_version: i64 = row.get("current_version");
+            return Err(AppError::Conflict(format!(
+                "Import starts at version {} but {} is at version {}",
+                first_version, stream_id, current_version
+            )));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO events (id, stream_id, event_type, data, payload, content_type, metadata, version, created_at, partition_key, tenant_id, checksum, chain_hash)
+            SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, created_at, partition_key, partition_key, checksum, chain_hash
+            FROM bulk_events
+            ORDER BY seq
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            error!("Failed to bulk load events: {}", e);
+            classify(e)
+        })?;
+
+        tx.commit().await.map_err(classify)?;
+        if let Some(versions) = &self.versions {
+            for event in &events {
+                versions.invalidate(&event.stream_id);
+            }
+        }
+        Ok(loaded)
+    }
+
+    async fn event_type_deprecations(&self) -> Result<Vec<EventTypeDeprecation>> {
+        let rows = sqlx::query("SELECT deprecation FROM event_type_deprecations ORDER BY event_type")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let deprecation: serde_json::Value = row.try_get("deprecation")?;
+                Ok(serde_json::from_value(deprecation)?)
+            })
+            .collect()
+    }
+
+    async fn set_event_type_deprecation(&self, deprecation: &EventTypeDeprecation) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO event_type_deprecations (event_type, deprecation, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (event_type) DO UPDATE SET deprecation = EXCLUDED.deprecation, updated_at = NOW()
+            "#,
+        )
+        .bind(&deprecation.event_type)
+        .bind(serde_json::to_value(deprecation)?)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn remove_event_type_deprecation(&self, event_type: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM event_type_deprecations WHERE event_type = $1")
+            .bind(event_type)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn streams_with_event_type(&self, event_type: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT DISTINCT stream_id FROM events WHERE event_type = $1 ORDER BY stream_id")
+            .bind(event_type)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| Ok(row.try_get("stream_id")?))
+            .collect()
+    }
+
+    async fn api_keys(&self) -> Result<Vec<(String, ApiKey)>> {
+        let rows = sqlx::query("SELECT key_hash, api_key FROM api_keys ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let key: serde_json::Value = row.try_get("api_key")?;
+                Ok((row.try_get("key_hash")?, serde_json::from_value(key)?))
+            })
+            .collect()
+    }
+
+    async fn insert_api_key(&self, key_hash: &str, key: &ApiKey) -> Result<()> {
+        sqlx::query("INSERT INTO api_keys (id, key_hash, api_key, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(key.id)
+            .bind(key_hash)
+            .bind(serde_json::to_value(key)?)
+            .bind(key.created_at)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn revoke_api_key(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn purgeable_events(&self, purge: &Purge) -> Result<Vec<(String, u64)>> {
+        let filter = purge_filter(purge);
+        let sql = format!(
+            r#"
+            SELECT stream_id, COUNT(*) AS events FROM (
+                SELECT e.stream_id FROM events e WHERE {filter}
+                UNION ALL
+                SELECT e.stream_id FROM events_archive e WHERE {filter}
+            ) purgeable
+            GROUP BY stream_id
+            ORDER BY stream_id
+            "#,
+            filter = filter.0
+        );
+
+        let mut query = sqlx::query(&sql).bind(purge.before);
+        for param in &filter.1 {
+            query = query.bind(param);
+        }
+        let rows = query.fetch_all(&self.pool).await.map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let events: i64 = row.try_get("events")?;
+                Ok((row.try_get("stream_id")?, events as u64))
+            })
+            .collect()
+    }
+
+    async fn purge_events(&self, purge: &Purge, limit: i64) -> Result<u64> {
+        let (filter, params) = purge_filter(purge);
+        let limit_param = params.len() + 2;
+        let mut purged = 0;
+
+        for (table, key) in [
+            ("events_archive", "partition_key, stream_id, version"),
+            ("events", "partition_key, id, created_at"),
+        ] {
+            let sql = format!(
+                "DELETE FROM {table} WHERE ({key}) IN (SELECT {key} FROM {table} e WHERE {filter} LIMIT ${limit_param})",
+                table = table,
+                key = key,
+                filter = filter,
+                limit_param = limit_param
+            );
+            let mut query = sqlx::query(&sql).bind(purge.before);
+            for param in &params {
+                query = query.bind(param);
+            }
+            purged += query
+                .bind(limit - purged as i64)
+                .execute(&self.pool)
+                .await
+                .map_err(classify)?
+                .rows_affected();
+            if purged as i64 >= limit {
+                break;
+            }
+        }
+
+        Ok(purged)
+    }
+
+    async fn scavenge_truncated(&self, limit: i64) -> Result<u64> {
+        let mut scavenged = 0;
+
+        for (table, key, keep_head) in [
+            ("events_archive", "partition_key, stream_id, version", ""),
+            (
+                "events",
+                "partition_key, id, created_at",
+                "AND e.version < (SELECT MAX(h.version) FROM events h WHERE h.partition_key = e.partition_key AND h.stream_id = e.stream_id)",
+            ),
+        ] {
+            let sql = format!(
+                r#"
+                DELETE FROM {table} WHERE ({key}) IN (
+                    SELECT {key} FROM {table} e
+                    JOIN stream_metadata m ON m.stream_id = e.stream_id
+                    WHERE e.version < (m.metadata->>'truncate_before')::bigint
+                    AND NOT COALESCE((m.metadata->>'legal_hold')::boolean, false)
+                    {keep_head}
+                    LIMIT $1
+                )
+                "#,
+                table = table,
+                key = key,
+                keep_head = keep_head
+            );
+            scavenged += sqlx::query(&sql)
+                .bind(limit - scavenged as i64)
+                .execute(&self.pool)
+                .await
+                .map_err(classify)?
+                .rows_affected();
+            if scavenged as i64 >= limit {
+                break;
+            }
+        }
+
+        Ok(scavenged)
+    }
+
+    async fn scavenge_expired(&self, now: DateTime<Utc>, limit: i64) -> Result<u64> {
+        let mut scavenged = 0;
+
+        for (table, key, keep_head) in [
+            ("events_archive", "partition_key, stream_id, version", ""),
+            (
+                "events",
+                "partition_key, id, created_at",
+                "AND e.version < (SELECT MAX(h.version) FROM events h WHERE h.partition_key = e.partition_key AND h.stream_id = e.stream_id)",
+            ),
+        ] {
+            let sql = format!(
+                r#"
+                DELETE FROM {table} WHERE ({key}) IN (
+                    SELECT {key} FROM {table} e
+                    WHERE e.expires_at < $1
+                    AND e.stream_id NOT IN (
+                        SELECT stream_id FROM stream_metadata
+                        WHERE COALESCE((metadata->>'legal_hold')::boolean, false)
+                    )
+                    {keep_head}
+                    LIMIT $2
+                )
+                "#,
+                table = table,
+                key = key,
+                keep_head = keep_head
+            );
+            scavenged += sqlx::query(&sql)
+                .bind(now)
+                .bind(limit - scavenged as i64)
+                .execute(&self.pool)
+                .await
+                .map_err(classify)?
+                .rows_affected();
+            if scavenged as i64 >= limit {
+                break;
+            }
+        }
+
+        Ok(scavenged)
+    }
+
+    async fn retention_rules(&self) -> Result<Vec<RetentionRule>> {
+        let rows = sqlx::query("SELECT rule FROM retention_rules ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let rule: serde_json::Value = row.try_get("rule")?;
+                Ok(serde_json::from_value(rule)?)
+            })
+            .collect()
+    }
+
+    async fn set_retention_rule(&self, rule: &RetentionRule) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO retention_rules (name, rule, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (name) DO UPDATE SET rule = EXCLUDED.rule, updated_at = NOW()
+            "#,
+        )
+        .bind(&rule.name)
+        .bind(serde_json::to_value(rule)?)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn remove_retention_rule(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM retention_rules WHERE name = $1")
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn snapshot_reducers(&self) -> Result<Vec<SnapshotReducer>> {
+        let rows = sqlx::query("SELECT reducer FROM snapshot_reducers ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let reducer: serde_json::Value = row.try_get("reducer")?;
+                Ok(serde_json::from_value(reducer)?)
+            })
+            .collect()
+    }
+
+    async fn set_snapshot_reducer(&self, reducer: &SnapshotReducer) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO snapshot_reducers (name, reducer, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (name) DO UPDATE SET reducer = EXCLUDED.reducer, updated_at = NOW()
+            "#,
+        )
+        .bind(&reducer.name)
+        .bind(serde_json::to_value(reducer)?)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn remove_snapshot_reducer(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM snapshot_reducers WHERE name = $1")
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn plugins(&self) -> Result<Vec<(Plugin, Vec<u8>)>> {
+        let rows = sqlx::query("SELECT plugin, module FROM plugins ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let plugin: serde_json::Value = row.try_get("plugin")?;
+                Ok((serde_json::from_value(plugin)?, row.try_get("module")?))
+            })
+            .collect()
+    }
+
+    async fn set_plugin(&self, plugin: &Plugin, module: &[u8]) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO plugins (name, plugin, module, updated_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (name) DO UPDATE SET plugin = EXCLUDED.plugin, module = EXCLUDED.module, updated_at = NOW()
+            "#,
+        )
+        .bind(&plugin.name)
+        .bind(serde_json::to_value(plugin)?)
+        .bind(module)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn remove_plugin(&self, name: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await.map_err(classify)?;
+
+        sqlx::query("DELETE FROM projections WHERE name = $1")
+            .bind(name)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+        let result = sqlx::query("DELETE FROM plugins WHERE name = $1")
+            .bind(name)
+            .execute(&mut *tx)
+            .await
+            .map_err(classify)?;
+
+        tx.commit().await.map_err(classify)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn projection(&self, name: &str) -> Result<Option<ProjectionState>> {
+        let row = sqlx::query("SELECT projection FROM projections WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        row.map(|row| {
+            let projection: serde_json::Value = row.try_get("projection")?;
+            Ok(serde_json::from_value(projection)?)
+        })
+        .transpose()
+    }
+
+    async fn set_projection(&self, projection: &ProjectionState) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO projections (name, projection, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (name) DO UPDATE SET projection = EXCLUDED.projection, updated_at = NOW()
+            "#,
+        )
+        .bind(&projection.name)
+        .bind(serde_json::to_value(projection)?)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn add_dead_letters(&self, letters: &[DeadLetter]) -> Result<()> {
+        let mut ids = Vec::with_capacity(letters.len());
+        let mut sources = Vec::with_capacity(letters.len());
+        let mut documents = Vec::with_capacity(letters.len());
+        let mut created_ats = Vec::with_capacity(letters.len());
+        for letter in letters {
+            ids.push(letter.id);
+            sources.push(letter.source.clone());
+            documents.push(serde_json::to_value(letter)?);
+            created_ats.push(letter.created_at);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO dead_letters (id, source, dead_letter, created_at)
+            SELECT * FROM UNNEST($1::uuid[], $2::varchar[], $3::jsonb[], $4::timestamptz[])
+            "#,
+        )
+        .bind(ids)
+        .bind(sources)
+        .bind(documents)
+        .bind(created_ats)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn dead_letters(&self, source: Option<&str>, limit: i64) -> Result<Vec<DeadLetter>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT dead_letter FROM dead_letters
+            WHERE $1::varchar IS NULL OR source = $1
+            ORDER BY created_at
+            LIMIT $2
+            "#,
+        )
+        .bind(source)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let letter: serde_json::Value = row.try_get("dead_letter")?;
+                Ok(serde_json::from_value(letter)?)
+            })
+            .collect()
+    }
+
+    async fn dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>> {
+        let row = sqlx::query("SELECT dead_letter FROM dead_letters WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        row.map(|row| {
+            let letter: serde_json::Value = row.try_get("dead_letter")?;
+            Ok(serde_json::from_value(letter)?)
+        })
+        .transpose()
+    }
+
+    async fn set_dead_letter(&self, letter: &DeadLetter) -> Result<()> {
+        sqlx::query("UPDATE dead_letters SET dead_letter = $1 WHERE id = $2")
+            .bind(serde_json::to_value(letter)?)
+            .bind(letter.id)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn remove_dead_letter(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM dead_letters WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn encryption_policies(&self) -> Result<Vec<EncryptionPolicy>> {
+        let rows = sqlx::query("SELECT policy FROM encryption_policies ORDER BY event_type")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let policy: serde_json::Value = row.try_get("policy")?;
+                Ok(serde_json::from_value(policy)?)
+            })
+            .collect()
+    }
+
+    async fn set_encryption_policy(&self, policy: &EncryptionPolicy) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO encryption_policies (event_type, policy, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (event_type) DO UPDATE SET policy = EXCLUDED.policy, updated_at = NOW()
+            "#,
+        )
+        .bind(&policy.event_type)
+        .bind(serde_json::to_value(policy)?)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn remove_encryption_policy(&self, event_type: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM encryption_policies WHERE event_type = $1")
+            .bind(event_type)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn subject_key(&self, subject_id: &str) -> Result<Option<Vec<u8>>> {
+        sqlx::query_scalar("SELECT key FROM subject_keys WHERE subject_id = $1")
+            .bind(subject_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(classify)
+    }
+
+    async fn insert_subject_key(&self, subject_id: &str, key: &[u8]) -> Result<Vec<u8>> {
+        sqlx::query("INSERT INTO subject_keys (subject_id, key) VALUES ($1, $2) ON CONFLICT (subject_id) DO NOTHING")
+            .bind(subject_id)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        sqlx::query_scalar("SELECT key FROM subject_keys WHERE subject_id = $1")
+            .bind(subject_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(classify)
+    }
+
+    async fn delete_subject_key(&self, subject_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM subject_keys WHERE subject_id = $1")
+            .bind(subject_id)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn chain_anchors(&self, limit: i64) -> Result<Vec<ChainAnchor>> {
+        let rows = sqlx::query("SELECT anchor FROM chain_anchors ORDER BY created_at DESC LIMIT $1")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let anchor: serde_json::Value = row.try_get("anchor")?;
+                Ok(serde_json::from_value(anchor)?)
+            })
+            .collect()
+    }
+
+    async fn add_chain_anchor(&self, anchor: &ChainAnchor) -> Result<()> {
+        sqlx::query("INSERT INTO chain_anchors (id, position, anchor, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(anchor.id)
+            .bind(anchor.position)
+            .bind(serde_json::to_value(anchor)?)
+            .bind(anchor.created_at)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn namespaces(&self) -> Result<Vec<Namespace>> {
+        let rows = sqlx::query("SELECT namespace FROM namespaces ORDER BY id")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let namespace: serde_json::Value = row.try_get("namespace")?;
+                Ok(serde_json::from_value(namespace)?)
+            })
+            .collect()
+    }
+
+    async fn insert_namespace(&self, namespace: &Namespace) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO namespaces (id, namespace, created_at) VALUES ($1, $2, $3) ON CONFLICT (id) DO NOTHING",
+        )
+        .bind(namespace.id())
+        .bind(serde_json::to_value(namespace)?)
+        .bind(namespace.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_namespace(&self, id: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM namespaces WHERE id = $1 OR substr(id, 1, length($1) + 1) = $1 || '/'")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn subscription_groups(&self) -> Result<Vec<SubscriptionGroup>> {
+        let rows = sqlx::query("SELECT subscription_group FROM subscription_groups ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        rows.iter()
+            .map(|row| {
+                let group: serde_json::Value = row.try_get("subscription_group")?;
+                Ok(serde_json::from_value(group)?)
+            })
+            .collect()
+    }
+
+    async fn set_subscription_group(&self, group: &SubscriptionGroup) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO subscription_groups (name, subscription_group, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (name) DO UPDATE SET subscription_group = EXCLUDED.subscription_group, updated_at = NOW()
+            "#,
+        )
+        .bind(&group.name)
+        .bind(serde_json::to_value(group)?)
+        .execute(&self.pool)
+        .await
+        .map_err(classify)?;
+
+        Ok(())
+    }
+
+    async fn remove_subscription_group(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM subscription_groups WHERE name = $1")
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(classify)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
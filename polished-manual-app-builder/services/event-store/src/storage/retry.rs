@@ -0,0 +1,112 @@
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::postgres::classify;
+use crate::config::Config;
+use crate::deadline::Deadline;
+use crate::error::Result;
+
+/// SQLSTATEs that say nothing about the query itself: the transaction was
+/// rolled back to settle a conflict, or the server is going away, as it does
+/// during a failover. Class 08 (connection exceptions) counts too.
+const TRANSIENT_STATES: &[&str] = &[
+    "40001", // serialization_failure
+    "40P01", // deadlock_detected
+    "57P01", // admin_shutdown
+    "57P02", // crash_shutdown
+    "57P03", // cannot_connect_now
+];
+
+/// Whether running the same statement again could succeed.
+pub fn is_transient(e: &sqlx::Error) -> bool {
+    match e {
+        // Connection reset or refused
+        sqlx::Error::Io(_) => true,
+        sqlx::Error::Database(db) => db
+            .code()
+            .is_some_and(|code| code.starts_with("08") || TRANSIENT_STATES.contains(&&*code)),
+        _ => false,
+    }
+}
+
+/// Runs idempotent operations again after a transient database error,
+/// pausing `backoff` before the first retry and doubling it for each one
+/// after. Each pause is jittered so instances that lost the database at the
+/// same moment don't all come back at once. Errors are only surfaced once
+/// the retries are spent, or straight away when they aren't transient.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    retries: u32,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            retries: config.db_retry_attempts,
+            backoff: Duration::from_millis(config.db_retry_backoff_ms),
+        }
+    }
+
+    /// Never retries.
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    pub async fn run<T, F, Fut>(&self, operation: &'static str, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = sqlx::Result<T>>,
+    {
+        self.run_within(operation, &Deadline(None), |_| attempt()).await
+    }
+
+    /// Like `run`, for operations bounded by a request deadline. Each attempt
+    /// gets the time left, and no retry is made that would start after it.
+    pub async fn run_within<T, F, Fut>(&self, operation: &'static str, deadline: &Deadline, mut attempt: F) -> Result<T>
+    where
+        F: FnMut(Option<Duration>) -> Fut,
+        Fut: Future<Output = sqlx::Result<T>>,
+    {
+        let mut backoff = self.backoff;
+        let mut retries = 0;
+        loop {
+            let remaining = deadline.remaining()?;
+            let e = match attempt(remaining).await {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+
+            let pause = jittered(backoff);
+            let out_of_time = deadline
+                .remaining()
+                .map_or(true, |remaining| remaining.is_some_and(|remaining| pause >= remaining));
+            if retries >= self.retries || out_of_time || !is_transient(&e) {
+                return Err(classify(e));
+            }
+
+            retries += 1;
+            warn!(
+                "Transient database error in {}, retry {} of {} in {}ms: {}",
+                operation,
+                retries,
+                self.retries,
+                pause.as_millis(),
+                e
+            );
+            tokio::time::sleep(pause).await;
+            backoff *= 2;
+        }
+    }
+}
+
+/// Somewhere between half and all of `backoff`.
+fn jittered(backoff: Duration) -> Duration {
+    let fraction = (Uuid::new_v4().as_u128() % 1024) as u32;
+    backoff / 2 + backoff / 2 * fraction / 1024
+}
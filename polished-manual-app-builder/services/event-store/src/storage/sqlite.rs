@@ -0,0 +1,1894 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::migrate::Migrator;
+use sqlx::{Row, SqliteConnection};
+use std::str::FromStr;
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::{
+    check_precondition, check_rewrite_head, stats_since, ArchiveRange, DailyCount, EventRewrite, EventStorage,
+    MergeCursor, MergeOrder, MigrationStatus, NewEvent, ProjectSummary, ProjectUsage, Purge, ReadDirection,
+    SnapshotCandidate, StoreStats, UsageBucket,
+};
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::get_partition_key;
+use crate::integrity::Link;
+use crate::models::{
+    ApiKey, ChainAnchor, DeadLetter, EncryptionPolicy, Event, EventTypeDeprecation, Granularity, Namespace,
+    Plugin, ProjectionState, RetentionRule, Snapshot, SnapshotReducer, StreamMetadata,
+    SubscriptionGroup,
+};
+
+/// SQLite backend for single-node and embedded deployments.
+///
+/// UUIDs and JSON documents are stored as TEXT, timestamps as RFC 3339 TEXT.
+/// SQLite has no statement timeout, so deadlines are only checked before a
+/// query is issued.
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        info!("Opening SQLite database...");
+
+        let options = SqliteConnectOptions::from_str(database_url)
+            .map_err(|e| AppError::Database(format!("Invalid SQLite URL: {}", e)))?
+            .create_if_missing(true)
+            .foreign_keys(true);
+
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .map_err(|e| AppError::Database(format!("Failed to open SQLite database: {}", e)))?;
+
+        info!("SQLite database opened");
+        Ok(Self { pool })
+    }
+
+    /// Appends per UTC day since `stats_since`, of one tenant or all.
+    async fn events_per_day(&self, tenant_id: Option<&str>) -> Result<Vec<DailyCount>> {
+        let since = stats_since().and_time(NaiveTime::MIN).and_utc();
+        let rows = sqlx::query(
+            r#"
+            SELECT substr(created_at, 1, 10) AS day, COUNT(*) AS events FROM events
+            WHERE created_at >= ?1 AND (?2 IS NULL OR tenant_id = ?2)
+            GROUP BY day
+            ORDER BY day
+            "#,
+        )
+        .bind(since)
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(DailyCount {
+                    day: row.try_get("day")?,
+                    events: row.try_get("events")?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn db_error(e: sqlx::Error) -> AppError {
+    AppError::Database(e.to_string())
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.is_unique_violation())
+}
+
+fn to_json_text(value: &Option<serde_json::Value>) -> Result<Option<String>> {
+    Ok(value.as_ref().map(serde_json::to_string).transpose()?)
+}
+
+fn from_json_text(text: Option<String>) -> Result<Option<serde_json::Value>> {
+    Ok(text.map(|t| serde_json::from_str(&t)).transpose()?)
+}
+
+fn parse_uuid(text: &str) -> Result<Uuid> {
+    Uuid::parse_str(text).map_err(|e| AppError::Internal(format!("Corrupt id {}: {}", text, e)))
+}
+
+async fn get_stream_version(conn: &mut SqliteConnection, stream_id: &str) -> Result<i64> {
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM events WHERE stream_id = ?")
+        .bind(stream_id)
+        .fetch_one(conn)
+        .await
+        .map_err(db_error)?;
+
+    Ok(version.unwrap_or(0))
+}
+
+async fn get_chain_hash(conn: &mut SqliteConnection, stream_id: &str, version: i64) -> Result<Option<String>> {
+    let chain_hash: Option<Option<String>> =
+        sqlx::query_scalar("SELECT chain_hash FROM events WHERE stream_id = ? AND version = ?")
+            .bind(stream_id)
+            .bind(version)
+            .fetch_optional(conn)
+            .await
+            .map_err(db_error)?;
+
+    Ok(chain_hash.flatten())
+}
+
+/// Inserts an event as it is, keeping its id, version and position.
+async fn insert_verbatim(conn: &mut SqliteConnection, event: &Event) -> Result<()> {
+    let data = event.payload.is_none().then(|| event.data.clone());
+    sqlx::query(
+        r#"
+        INSERT INTO events (id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id, checksum, chain_hash)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(event.id.to_string())
+    .bind(&event.stream_id)
+    .bind(&event.event_type)
+    .bind(to_json_text(&data)?)
+    .bind(&event.payload)
+    .bind(&event.content_type)
+    .bind(to_json_text(&event.metadata)?)
+    .bind(event.version)
+    .bind(event.position)
+    .bind(event.created_at)
+    .bind(get_partition_key(&event.stream_id))
+    .bind(get_partition_key(&event.stream_id))
+    .bind(&event.checksum)
+    .bind(&event.chain_hash)
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        error!("Failed to import event {}: {}", event.id, e);
+        db_error(e)
+    })?;
+
+    Ok(())
+}
+
+/// The WHERE clause for a purge over `events` or `events_archive` aliased
+/// `e`, with `?1` the cutoff time, and the string parameters that follow.
+fn purge_filter(purge: &Purge) -> (String, Vec<String>) {
+    let (conditions, params) = purge.conditions('?', 2);
+    let filter = format!(
+        r#"
+        e.created_at < ?1
+        AND {}
+        AND e.stream_id NOT IN (
+            SELECT stream_id FROM stream_metadata
+            WHERE COALESCE(json_extract(metadata, '$.legal_hold'), 0) = 1
+        )
+        AND e.version < (SELECT MAX(h.version) FROM events h WHERE h.stream_id = e.stream_id)
+        "#,
+        conditions
+    );
+    (filter, params)
+}
+
+fn event_from_row(row: &SqliteRow) -> Result<Event> {
+    let id: String = row.try_get("id")?;
+    Ok(Event {
+        id: parse_uuid(&id)?,
+        stream_id: row.try_get("stream_id")?,
+        event_type: row.try_get("event_type")?,
+        data: from_json_text(row.try_get("data")?)?.unwrap_or(serde_json::Value::Null),
+        payload: row.try_get("payload")?,
+        content_type: row.try_get("content_type")?,
+        metadata: from_json_text(row.try_get("metadata")?)?,
+        version: row.try_get("version")?,
+        position: row.try_get("position")?,
+        created_at: row.try_get("created_at")?,
+        checksum: row.try_get("checksum")?,
+        chain_hash: row.try_get("chain_hash")?,
+        link: None,
+    })
+}
+
+fn snapshot_from_row(row: &SqliteRow) -> Result<Snapshot> {
+    let id: String = row.try_get("id")?;
+    Ok(Snapshot {
+        id: parse_uuid(&id)?,
+        stream_id: row.try_get("stream_id")?,
+        version: row.try_get("version")?,
+        data: row.try_get("data")?,
+        compression: row.try_get::<String, _>("compression")?.parse().map_err(AppError::Database)?,
+        reducer: row.try_get("reducer")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+/// Versioned schema migrations, embedded at build time and recorded in
+/// `_sqlx_migrations` as they are applied.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+#[async_trait]
+impl EventStorage for SqliteStorage {
+    async fn migrate(&self) -> Result<()> {
+        info!("Running SQLite migrations...");
+
+        // WAL lets readers proceed while an append is being written
+        sqlx::query("PRAGMA journal_mode = WAL")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| AppError::Database(format!("Failed to enable WAL: {}", e)))?;
+
+        // Tenant stamp for databases created before it existed; SQLite has no
+        // ADD COLUMN IF NOT EXISTS, and the baseline indexes the column
+        let has_events: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'events')")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(db_error)?;
+        let has_tenant: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pragma_table_info('events') WHERE name = 'tenant_id')")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(db_error)?;
+        if has_events && !has_tenant {
+            sqlx::query("ALTER TABLE events ADD COLUMN tenant_id TEXT")
+                .execute(&self.pool)
+                .await
+                .map_err(|e| AppError::Database(format!("SQLite migration failed: {}", e)))?;
+            sqlx::query("UPDATE events SET tenant_id = partition_key")
+                .execute(&self.pool)
+                .await
+                .map_err(|e| AppError::Database(format!("SQLite migration failed: {}", e)))?;
+        }
+
+        MIGRATOR
+            .run(&self.pool)
+            .await
+            .map_err(|e| AppError::Database(format!("SQLite migration failed: {}", e)))?;
+
+        info!("SQLite migrations completed");
+        Ok(())
+    }
+
+    async fn append(
+        &self,
+        event: NewEvent,
+        expected_version: Option<i64>,
+        deadline: Deadline,
+    ) -> Result<Event> {
+        deadline.remaining()?;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let current_version = get_stream_version(&mut tx, &event.stream_id).await?;
+
+        if let Some(expected) = expected_version {
+            if current_version != expected {
+                return Err(AppError::Conflict(format!(
+                    "Version conflict: expected {}, got {}",
+                    expected, current_version
+                )));
+            }
+        }
+
+        // Checked in the transaction's snapshot: under WAL, a write to any stream
+        // committed since makes the insert below fail instead of landing
+        for precondition in &event.preconditions {
+            check_precondition(precondition, get_stream_version(&mut tx, &precondition.stream_id).await?)?;
+        }
+
+        let new_version = current_version + 1;
+        let previous = get_chain_hash(&mut tx, &event.stream_id, current_version).await?;
+        let chain_hash = Link::new_event(&event, new_version).hash(previous.as_deref());
+
+        // SQLite has a single writer, so MAX + 1 is a gap-free commit order
+        let position: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO events (id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id, checksum, chain_hash, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, (SELECT COALESCE(MAX(position), 0) + 1 FROM events), ?, ?, ?, ?, ?, ?)
+            RETURNING position
+            "#,
+        )
+        .bind(event.id.to_string())
+        .bind(&event.stream_id)
+        .bind(&event.event_type)
+        .bind(to_json_text(&event.data)?)
+        .bind(&event.payload)
+        .bind(&event.content_type)
+        .bind(to_json_text(&event.metadata)?)
+        .bind(new_version)
+        .bind(event.created_at)
+        .bind(&event.partition_key)
+        .bind(&event.tenant_id)
+        .bind(&event.checksum)
+        .bind(&chain_hash)
+        .bind(event.expires_at)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| {
+            // A concurrent writer took this version between our read and insert
+            if is_unique_violation(&e) {
+                return AppError::Conflict(format!(
+                    "Version conflict: version {} of {} was written concurrently",
+                    new_version, event.stream_id
+                ));
+            }
+            error!("Failed to insert event: {}", e);
+            db_error(e)
+        })?;
+
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(Event {
+            id: event.id,
+            stream_id: event.stream_id,
+            event_type: event.event_type,
+            data: event.data.unwrap_or(serde_json::Value::Null),
+            payload: event.payload,
+            content_type: event.content_type,
+            metadata: event.metadata,
+            version: new_version,
+            position,
+            created_at: event.created_at,
+            checksum: Some(event.checksum),
+            chain_hash: Some(chain_hash),
+            link: None,
+        })
+    }
+
+    async fn read_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        limit: i64,
+        direction: ReadDirection,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>> {
+        deadline.remaining()?;
+
+        let query_str = match direction {
+            ReadDirection::Forward => {
+                "SELECT * FROM events WHERE stream_id = ? AND version >= ? ORDER BY version ASC LIMIT ?"
+            }
+            ReadDirection::Backward => {
+                "SELECT * FROM events WHERE stream_id = ? AND version <= ? ORDER BY version DESC LIMIT ?"
+            }
+        };
+
+        let rows = sqlx::query(query_str)
+            .bind(stream_id)
+            .bind(from_version)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                error!("Failed to fetch events: {}", e);
+                db_error(e)
+            })?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn scan_stream(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        to_version: i64,
+        sink: mpsc::Sender<Event>,
+    ) -> Result<()> {
+        let mut rows = sqlx::query(
+            "SELECT * FROM events WHERE stream_id = ? AND version BETWEEN ? AND ? ORDER BY version ASC",
+        )
+        .bind(stream_id)
+        .bind(from_version)
+        .bind(to_version)
+        .fetch(&self.pool);
+
+        while let Some(row) = rows.next().await {
+            let row = row.map_err(|e| {
+                error!("Failed to scan events: {}", e);
+                db_error(e)
+            })?;
+            if sink.send(event_from_row(&row)?).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn read_latest(&self, stream_id: &str, count: i64, deadline: Deadline) -> Result<Vec<Event>> {
+        deadline.remaining()?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM (
+                SELECT * FROM events WHERE stream_id = ? ORDER BY version DESC LIMIT ?
+            ) ORDER BY version ASC
+            "#,
+        )
+        .bind(stream_id)
+        .bind(count)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch latest events: {}", e);
+            db_error(e)
+        })?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn find_event(&self, stream_id: &str, id: Uuid, since: DateTime<Utc>) -> Result<Option<Event>> {
+        let row = sqlx::query("SELECT * FROM events WHERE id = ? AND stream_id = ? AND created_at >= ?")
+            .bind(id.to_string())
+            .bind(stream_id)
+            .bind(since)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        row.as_ref().map(event_from_row).transpose()
+    }
+
+    async fn version_at(&self, stream_id: &str, at: DateTime<Utc>) -> Result<i64> {
+        let version: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(version) FROM (
+                SELECT version FROM events WHERE stream_id = ?1 AND created_at <= ?2
+                UNION ALL
+                SELECT version FROM events_archive WHERE stream_id = ?1 AND created_at <= ?2
+            )
+            "#,
+        )
+        .bind(stream_id)
+        .bind(at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(version.unwrap_or(0))
+    }
+
+    async fn count_events(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<i64> {
+        sqlx::query_scalar(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM events WHERE stream_id = ?1 AND version BETWEEN ?2 AND ?3)
+                + (SELECT COUNT(*) FROM events_archive WHERE stream_id = ?1 AND version BETWEEN ?2 AND ?3)
+            "#,
+        )
+        .bind(stream_id)
+        .bind(from_version)
+        .bind(to_version)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)
+    }
+
+    async fn stream_version(&self, stream_id: &str) -> Result<i64> {
+        let mut conn = self.pool.acquire().await.map_err(db_error)?;
+        get_stream_version(&mut conn, stream_id).await
+    }
+
+    async fn load_stream_data(
+        &self,
+        stream_id: &str,
+        from_version: i64,
+        up_to_version: i64,
+    ) -> Result<Vec<serde_json::Value>> {
+        let rows: Vec<String> = sqlx::query_scalar(
+            "SELECT data FROM events WHERE stream_id = ? AND version BETWEEN ? AND ? AND data IS NOT NULL ORDER BY version",
+        )
+        .bind(stream_id)
+        .bind(from_version)
+        .bind(up_to_version)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|data| Ok(serde_json::from_str(data)?))
+            .collect()
+    }
+
+    async fn replace_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO snapshots (id, stream_id, version, data, compression, reducer, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (stream_id, version) DO UPDATE
+            SET id = excluded.id, data = excluded.data, compression = excluded.compression,
+                reducer = excluded.reducer, created_at = excluded.created_at
+            "#,
+        )
+        .bind(snapshot.id.to_string())
+        .bind(&snapshot.stream_id)
+        .bind(snapshot.version)
+        .bind(&snapshot.data)
+        .bind(snapshot.compression.as_str())
+        .bind(&snapshot.reducer)
+        .bind(snapshot.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn insert_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO snapshots (id, stream_id, version, data, compression, reducer, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(snapshot.id.to_string())
+        .bind(&snapshot.stream_id)
+        .bind(snapshot.version)
+        .bind(&snapshot.data)
+        .bind(snapshot.compression.as_str())
+        .bind(&snapshot.reducer)
+        .bind(snapshot.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn latest_snapshot(&self, stream_id: &str) -> Result<Option<Snapshot>> {
+        let row = sqlx::query("SELECT * FROM snapshots WHERE stream_id = ? ORDER BY version DESC LIMIT 1")
+            .bind(stream_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        row.as_ref().map(snapshot_from_row).transpose()
+    }
+
+    async fn snapshot_at(&self, stream_id: &str, version: i64) -> Result<Option<Snapshot>> {
+        let row = sqlx::query(
+            "SELECT * FROM snapshots WHERE stream_id = ? AND version <= ? ORDER BY version DESC LIMIT 1",
+        )
+        .bind(stream_id)
+        .bind(version)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        row.as_ref().map(snapshot_from_row).transpose()
+    }
+
+    async fn snapshot_versions(&self, stream_id: &str) -> Result<Vec<i64>> {
+        let rows = sqlx::query("SELECT version FROM snapshots WHERE stream_id = ? ORDER BY version")
+            .bind(stream_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter().map(|row| Ok(row.try_get("version")?)).collect()
+    }
+
+    async fn delete_snapshots(&self, stream_id: &str, versions: &[i64]) -> Result<u64> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut deleted = 0;
+
+        for version in versions {
+            deleted += sqlx::query("DELETE FROM snapshots WHERE stream_id = ? AND version = ?")
+                .bind(stream_id)
+                .bind(version)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?
+                .rows_affected();
+        }
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(deleted)
+    }
+
+    async fn snapshot_candidates(&self, threshold: i64) -> Result<Vec<SnapshotCandidate>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT e.stream_id, MAX(e.version) AS current_version, COALESCE(MAX(s.version), 0) AS snapshot_version
+            FROM events e
+            LEFT JOIN (
+                SELECT stream_id, MAX(version) AS version FROM snapshots GROUP BY stream_id
+            ) s ON e.stream_id = s.stream_id
+            GROUP BY e.stream_id
+            HAVING MAX(e.version) - COALESCE(MAX(s.version), 0) >= ?
+            "#,
+        )
+        .bind(threshold)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(SnapshotCandidate {
+                    stream_id: row.try_get("stream_id")?,
+                    current_version: row.try_get("current_version")?,
+                    snapshot_version: row.try_get("snapshot_version")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        let row = sqlx::query(
+            r#"
+            SELECT (SELECT COUNT(*) FROM events) AS total_events,
+                   (SELECT COUNT(DISTINCT stream_id) FROM events) AS total_streams,
+                   (SELECT COUNT(*) FROM snapshots) AS total_snapshots,
+                   (SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()) AS storage_bytes
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(StoreStats {
+            total_events: row.try_get("total_events")?,
+            total_streams: row.try_get("total_streams")?,
+            total_snapshots: row.try_get("total_snapshots")?,
+            storage_bytes: Some(row.try_get("storage_bytes")?),
+            events_per_day: self.events_per_day(None).await?,
+        })
+    }
+
+    async fn is_empty(&self) -> Result<bool> {
+        sqlx::query_scalar("SELECT NOT EXISTS (SELECT 1 FROM events) AND NOT EXISTS (SELECT 1 FROM events_archive)")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn tenant_stats(&self, tenant_id: &str) -> Result<StoreStats> {
+        let row = sqlx::query(
+            r#"
+            SELECT (SELECT COUNT(*) FROM events WHERE tenant_id = ?1) AS total_events,
+                   (SELECT COUNT(DISTINCT stream_id) FROM events WHERE tenant_id = ?1) AS total_streams,
+                   (SELECT COUNT(*) FROM snapshots
+                    WHERE stream_id = ?1 OR substr(stream_id, 1, length(?1) + 1) = ?1 || '/') AS total_snapshots
+            "#,
+        )
+        .bind(tenant_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(StoreStats {
+            total_events: row.try_get("total_events")?,
+            total_streams: row.try_get("total_streams")?,
+            total_snapshots: row.try_get("total_snapshots")?,
+            // One file holds every tenant
+            storage_bytes: None,
+            events_per_day: self.events_per_day(Some(tenant_id)).await?,
+        })
+    }
+
+    // Counted from the events on each request; single-node stores are small
+    // enough not to need the rollup the Postgres backend keeps.
+    async fn usage_timeseries(
+        &self,
+        tenant_id: Option<&str>,
+        granularity: Granularity,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<UsageBucket>> {
+        // Timestamps are stored as RFC 3339 text, so a bucket is a prefix
+        let (prefix, start) = match granularity {
+            Granularity::Hour => (13, ":00:00+00:00"),
+            Granularity::Day => (10, "T00:00:00+00:00"),
+        };
+        let rows = sqlx::query(
+            r#"
+            SELECT substr(created_at, 1, ?1) || ?2 AS bucket, COUNT(*) AS events,
+                   SUM(COALESCE(length(payload), length(data), 0)) AS bytes,
+                   COUNT(DISTINCT stream_id) AS active_streams
+            FROM events
+            WHERE created_at >= ?3 AND (?4 IS NULL OR tenant_id = ?4)
+            GROUP BY bucket
+            ORDER BY bucket
+            "#,
+        )
+        .bind(prefix)
+        .bind(start)
+        .bind(since)
+        .bind(tenant_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(UsageBucket {
+                    bucket: row.try_get("bucket")?,
+                    events: row.try_get("events")?,
+                    bytes: row.try_get("bytes")?,
+                    active_streams: row.try_get("active_streams")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn stream_metadata(&self, stream_id: &str) -> Result<Option<StreamMetadata>> {
+        let metadata: Option<String> =
+            sqlx::query_scalar("SELECT metadata FROM stream_metadata WHERE stream_id = ?")
+                .bind(stream_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(db_error)?;
+
+        Ok(metadata.map(|m| serde_json::from_str(&m)).transpose()?)
+    }
+
+    async fn all_stream_metadata(&self) -> Result<Vec<(String, StreamMetadata)>> {
+        let rows = sqlx::query("SELECT stream_id, metadata FROM stream_metadata ORDER BY stream_id")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                let metadata: String = row.try_get("metadata")?;
+                Ok((row.try_get("stream_id")?, serde_json::from_str(&metadata)?))
+            })
+            .collect()
+    }
+
+    async fn set_stream_metadata(&self, stream_id: &str, metadata: &StreamMetadata) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO stream_metadata (stream_id, metadata, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT (stream_id) DO UPDATE SET metadata = excluded.metadata, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(stream_id)
+        .bind(serde_json::to_string(metadata)?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn project_summaries(&self) -> Result<Vec<ProjectSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT partition_key,
+                   COUNT(DISTINCT stream_id) AS stream_count,
+                   COUNT(*) AS event_count,
+                   MIN(created_at) AS oldest_event_at,
+                   MAX(created_at) AS newest_event_at
+            FROM events
+            GROUP BY partition_key
+            ORDER BY partition_key
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(ProjectSummary {
+                    project_id: row.try_get("partition_key")?,
+                    stream_count: row.try_get("stream_count")?,
+                    event_count: row.try_get("event_count")?,
+                    oldest_event_at: row.try_get("oldest_event_at")?,
+                    newest_event_at: row.try_get("newest_event_at")?,
+                })
+            })
+            .collect()
+    }
+
+    // One file holds every project, so a project's share is the size of
+    // its event bodies
+    async fn sample_storage_usage(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO usage_storage_daily (partition_key, day, bytes)
+            SELECT partition_key, ?1, SUM(COALESCE(length(payload), length(data), 0))
+            FROM (SELECT partition_key, payload, data FROM events
+                  UNION ALL
+                  SELECT partition_key, payload, data FROM events_archive)
+            WHERE true
+            GROUP BY partition_key
+            ON CONFLICT (partition_key, day) DO UPDATE SET bytes = MAX(bytes, excluded.bytes)
+            "#,
+        )
+        .bind(Utc::now().date_naive())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn monthly_usage(&self, month: NaiveDate) -> Result<Vec<ProjectUsage>> {
+        let end = month + chrono::Months::new(1);
+        let rows = sqlx::query(
+            r#"
+            WITH appended AS (
+                SELECT partition_key, COUNT(*) AS events,
+                       SUM(COALESCE(length(payload), length(data), 0)) AS bytes
+                FROM (SELECT partition_key, payload, data, created_at FROM events
+                      UNION ALL
+                      SELECT partition_key, payload, data, created_at FROM events_archive)
+                WHERE created_at >= ?1 AND created_at < ?2
+                GROUP BY partition_key
+            ), stored AS (
+                SELECT partition_key, CAST(AVG(bytes) AS INTEGER) AS avg_bytes, MAX(bytes) AS peak_bytes
+                FROM usage_storage_daily
+                WHERE day >= ?3 AND day < ?4
+                GROUP BY partition_key
+            ), projects AS (
+                SELECT partition_key FROM appended UNION SELECT partition_key FROM stored
+            )
+            SELECT p.partition_key AS project_id,
+                   COALESCE(a.events, 0) AS events,
+                   COALESCE(a.bytes, 0) AS bytes_appended,
+                   COALESCE(s.avg_bytes, 0) AS avg_stored_bytes,
+                   COALESCE(s.peak_bytes, 0) AS peak_stored_bytes
+            FROM projects p
+            LEFT JOIN appended a ON a.partition_key = p.partition_key
+            LEFT JOIN stored s ON s.partition_key = p.partition_key
+            ORDER BY p.partition_key
+            "#,
+        )
+        .bind(month.and_time(NaiveTime::MIN).and_utc())
+        .bind(end.and_time(NaiveTime::MIN).and_utc())
+        .bind(month)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(ProjectUsage {
+                    project_id: row.try_get("project_id")?,
+                    events: row.try_get("events")?,
+                    bytes_appended: row.try_get("bytes_appended")?,
+                    avg_stored_bytes: row.try_get("avg_stored_bytes")?,
+                    peak_stored_bytes: row.try_get("peak_stored_bytes")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn legal_hold_streams(&self) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT stream_id FROM stream_metadata WHERE COALESCE(json_extract(metadata, '$.legal_hold'), 0) = 1",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)
+    }
+
+    async fn stream_heads(&self, prefix: &str) -> Result<Vec<(String, i64)>> {
+        sqlx::query_as(
+            r#"
+            SELECT stream_id, MAX(version)
+            FROM events
+            WHERE substr(stream_id, 1, length(?1)) = ?1
+            GROUP BY stream_id
+            ORDER BY stream_id
+            "#,
+        )
+        .bind(prefix)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)
+    }
+
+    async fn archivable_ranges(&self, threshold: DateTime<Utc>) -> Result<Vec<ArchiveRange>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT e.stream_id, MIN(e.version) AS from_version, MAX(e.version) AS to_version
+            FROM events e
+            WHERE e.created_at < ?
+            AND e.stream_id IN (SELECT stream_id FROM snapshots)
+            AND e.stream_id NOT IN (
+                SELECT stream_id FROM stream_metadata
+                WHERE COALESCE(json_extract(metadata, '$.legal_hold'), 0) = 1
+            )
+            AND e.version < (SELECT MAX(h.version) FROM events h WHERE h.stream_id = e.stream_id)
+            GROUP BY e.stream_id
+            ORDER BY e.stream_id
+            "#,
+        )
+        .bind(threshold)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(ArchiveRange {
+                    stream_id: row.try_get("stream_id")?,
+                    from_version: row.try_get("from_version")?,
+                    to_version: row.try_get("to_version")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn archive_ranges(&self, ranges: &[ArchiveRange]) -> Result<u64> {
+        let mut archived = 0;
+
+        for range in ranges {
+            let mut tx = self.pool.begin().await.map_err(db_error)?;
+            // Same predicate for both statements, so exactly the copied rows go
+            let predicate = r#"
+                WHERE stream_id = ?1 AND version BETWEEN ?2 AND ?3
+                AND version < (SELECT MAX(version) FROM events WHERE stream_id = ?1)
+            "#;
+            sqlx::query(&format!(
+                r#"
+                INSERT INTO events_archive
+                    (id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id, checksum, chain_hash, expires_at)
+                SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id, checksum, chain_hash, expires_at
+                FROM events
+                {}
+                "#,
+                predicate
+            ))
+            .bind(&range.stream_id)
+            .bind(range.from_version)
+            .bind(range.to_version)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            let result = sqlx::query(&format!("DELETE FROM events {}", predicate))
+                .bind(&range.stream_id)
+                .bind(range.from_version)
+                .bind(range.to_version)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            tx.commit().await.map_err(db_error)?;
+
+            archived += result.rows_affected();
+        }
+
+        Ok(archived)
+    }
+
+    async fn read_archived(&self, stream_id: &str, from_version: i64, to_version: i64) -> Result<Vec<Event>> {
+        let rows = sqlx::query(
+            "SELECT * FROM events_archive WHERE stream_id = ? AND version BETWEEN ? AND ? ORDER BY version ASC",
+        )
+        .bind(stream_id)
+        .bind(from_version)
+        .bind(to_version)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn rewrite_events(
+        &self,
+        stream_id: &str,
+        head: i64,
+        head_hash: Option<&str>,
+        rewrites: &[EventRewrite],
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        // Read in the transaction's snapshot: under WAL, an append committed
+        // since makes the updates below fail instead of landing
+        let current = get_stream_version(&mut tx, stream_id).await?;
+        let current_hash = get_chain_hash(&mut tx, stream_id, current).await?;
+        check_rewrite_head(stream_id, (head, head_hash), (current, current_hash.as_deref()))?;
+        let mut rewritten = 0;
+
+        for rewrite in rewrites {
+            let data = rewrite.data.as_ref().map(serde_json::to_string).transpose()?;
+            for table in ["events", "events_archive"] {
+                let result = sqlx::query(&format!(
+                    r#"
+                    UPDATE {}
+                    SET data = COALESCE(?3, data),
+                        payload = CASE WHEN ?3 IS NULL THEN payload END,
+                        checksum = ?4, chain_hash = ?5
+                    WHERE stream_id = ?1 AND version = ?2
+                    "#,
+                    table
+                ))
+                .bind(stream_id)
+                .bind(rewrite.version)
+                .bind(&data)
+                .bind(&rewrite.checksum)
+                .bind(&rewrite.chain_hash)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+                if result.rows_affected() > 0 {
+                    rewritten += 1;
+                    break;
+                }
+            }
+        }
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(rewritten)
+    }
+
+    async fn delete_events_through(&self, stream_id: &str, version: i64) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM events
+            WHERE stream_id = ?1 AND version <= ?2
+            AND version < (SELECT MAX(version) FROM events WHERE stream_id = ?1)
+            "#,
+        )
+        .bind(stream_id)
+        .bind(version)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn migration_status(&self) -> Result<MigrationStatus> {
+        let tracked: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+        let applied = if tracked {
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_error)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(MigrationStatus::new(&MIGRATOR, applied))
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await.map_err(db_error)?;
+        Ok(())
+    }
+
+    fn pool_connections(&self) -> Option<(u32, usize)> {
+        Some((self.pool.size(), self.pool.num_idle()))
+    }
+
+    async fn committed_position(&self) -> Result<i64> {
+        // Single writer: everything visible is committed
+        let position: Option<i64> = sqlx::query_scalar("SELECT MAX(position) FROM events")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(position.unwrap_or(0))
+    }
+
+    async fn read_all(&self, after: i64, limit: i64) -> Result<Vec<Event>> {
+        let rows = sqlx::query("SELECT * FROM events WHERE position > ? ORDER BY position LIMIT ?")
+            .bind(after)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn read_all_with_archive(&self, after: i64, limit: i64) -> Result<Vec<Event>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, checksum, chain_hash
+            FROM events WHERE position > ?1
+            UNION ALL
+            SELECT id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, checksum, chain_hash
+            FROM events_archive WHERE position > ?1
+            ORDER BY position
+            LIMIT ?2
+            "#,
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn read_streams(
+        &self,
+        stream_ids: &[String],
+        after: MergeCursor,
+        order: MergeOrder,
+        limit: i64,
+        deadline: Deadline,
+    ) -> Result<Vec<Event>> {
+        deadline.remaining()?;
+
+        let query_str = match order {
+            MergeOrder::Position => {
+                r#"
+                SELECT * FROM events
+                WHERE stream_id IN (SELECT value FROM json_each(?1)) AND position > ?3
+                ORDER BY position
+                LIMIT ?4
+                "#
+            }
+            MergeOrder::Timestamp => {
+                r#"
+                SELECT * FROM events
+                WHERE stream_id IN (SELECT value FROM json_each(?1))
+                    AND (created_at > ?2 OR (created_at = ?2 AND position > ?3))
+                ORDER BY created_at, position
+                LIMIT ?4
+                "#
+            }
+        };
+
+        let rows = sqlx::query(query_str)
+            .bind(serde_json::to_string(stream_ids)?)
+            .bind(after.created_at)
+            .bind(after.position)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter().map(event_from_row).collect()
+    }
+
+    async fn import_events(&self, events: &[Event]) -> Result<()> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        for event in events {
+            insert_verbatim(&mut tx, event).await?;
+        }
+
+        tx.commit().await.map_err(db_error)
+    }
+
+    async fn replication_checkpoint(&self) -> Result<i64> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(
+                (SELECT position FROM replication_checkpoint),
+                MAX(
+                    COALESCE((SELECT MAX(position) FROM events), 0),
+                    COALESCE((SELECT MAX(position) FROM events_archive), 0)
+                )
+            )
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)
+    }
+
+    async fn apply_replicated(&self, after: i64, through: i64, events: &[Event]) -> Result<()> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        // Single writer: no other transaction can move the checkpoint meanwhile
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO replication_checkpoint (id, position)
+            SELECT 1, MAX(
+                COALESCE((SELECT MAX(position) FROM events), 0),
+                COALESCE((SELECT MAX(position) FROM events_archive), 0)
+            )
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        let checkpoint: i64 = sqlx::query_scalar("SELECT position FROM replication_checkpoint")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        if checkpoint != after {
+            return Err(AppError::Conflict(format!(
+                "Replication checkpoint is at {}, not {}",
+                checkpoint, after
+            )));
+        }
+
+        for event in events {
+            insert_verbatim(&mut tx, event).await?;
+        }
+        sqlx::query("UPDATE replication_checkpoint SET position = ?")
+            .bind(through)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)
+    }
+
+    async fn import_stream(&self, stream_id: &str, events: Vec<Event>) -> Result<Vec<Event>> {
+        let Some(first) = events.first() else {
+            return Ok(events);
+        };
+        let first_version = first.version;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        let current_version = get_stream_version(&mut tx, stream_id).await?;
+        if first_version != current_version + 1 {
+            return Err(AppError::Conflict(format!(
+                "Import starts at version {} but {} is at version {}",
+                first_version, stream_id, current_version
+            )));
+        }
+
+        let mut stored = Vec::with_capacity(events.len());
+        for mut event in events {
+            let data = event.payload.is_none().then(|| event.data.clone());
+            event.position = sqlx::query_scalar(
+                r#"
+                INSERT INTO events (id, stream_id, event_type, data, payload, content_type, metadata, version, position, created_at, partition_key, tenant_id, checksum, chain_hash)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, (SELECT COALESCE(MAX(position), 0) + 1 FROM events), ?, ?, ?, ?, ?)
+                RETURNING position
+                "#,
+            )
+            .bind(event.id.to_string())
+            .bind(stream_id)
+            .bind(&event.event_type)
+            .bind(to_json_text(&data)?)
+            .bind(&event.payload)
+            .bind(&event.content_type)
+            .bind(to_json_text(&event.metadata)?)
+            .bind(event.version)
+            .bind(event.created_at)
+            .bind(get_partition_key(stream_id))
+            .bind(get_partition_key(stream_id))
+            .bind(&event.checksum)
+            .bind(&event.chain_hash)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| {
+                if is_unique_violation(&e) {
+                    return AppError::Conflict(format!(
+                        "Version conflict: version {} of {} was written concurrently",
+                        event.version, stream_id
+                    ));
+                }
+                error!("Failed to import event {}: {}", event.id, e);
+                db_error(e)
+            })?;
+            stored.push(event);
+        }
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(stored)
+    }
+
+    async fn event_type_deprecations(&self) -> Result<Vec<EventTypeDeprecation>> {
+        let rows: Vec<String> =
+            sqlx::query_scalar("SELECT deprecation FROM event_type_deprecations ORDER BY event_type")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_error)?;
+
+        rows.iter()
+            .map(|d| Ok(serde_json::from_str(d)?))
+            .collect()
+    }
+
+    async fn set_event_type_deprecation(&self, deprecation: &EventTypeDeprecation) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO event_type_deprecations (event_type, deprecation, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT (event_type) DO UPDATE SET deprecation = excluded.deprecation, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&deprecation.event_type)
+        .bind(serde_json::to_string(deprecation)?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_event_type_deprecation(&self, event_type: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM event_type_deprecations WHERE event_type = ?")
+            .bind(event_type)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn streams_with_event_type(&self, event_type: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar("SELECT DISTINCT stream_id FROM events WHERE event_type = ? ORDER BY stream_id")
+            .bind(event_type)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn api_keys(&self) -> Result<Vec<(String, ApiKey)>> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT key_hash, api_key FROM api_keys ORDER BY created_at")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_error)?;
+
+        rows.into_iter()
+            .map(|(hash, key)| Ok((hash, serde_json::from_str(&key)?)))
+            .collect()
+    }
+
+    async fn insert_api_key(&self, key_hash: &str, key: &ApiKey) -> Result<()> {
+        sqlx::query("INSERT INTO api_keys (id, key_hash, api_key, created_at) VALUES (?, ?, ?, ?)")
+            .bind(key.id.to_string())
+            .bind(key_hash)
+            .bind(serde_json::to_string(key)?)
+            .bind(key.created_at)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn revoke_api_key(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn purgeable_events(&self, purge: &Purge) -> Result<Vec<(String, u64)>> {
+        let filter = purge_filter(purge);
+        let sql = format!(
+            r#"
+            SELECT stream_id, COUNT(*) AS events FROM (
+                SELECT e.stream_id FROM events e WHERE {filter}
+                UNION ALL
+                SELECT e.stream_id FROM events_archive e WHERE {filter}
+            )
+            GROUP BY stream_id
+            ORDER BY stream_id
+            "#,
+            filter = filter.0
+        );
+
+        let mut query = sqlx::query(&sql).bind(purge.before);
+        for param in &filter.1 {
+            query = query.bind(param);
+        }
+        let rows = query.fetch_all(&self.pool).await.map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                let events: i64 = row.try_get("events")?;
+                Ok((row.try_get("stream_id")?, events as u64))
+            })
+            .collect()
+    }
+
+    async fn purge_events(&self, purge: &Purge, limit: i64) -> Result<u64> {
+        let (filter, params) = purge_filter(purge);
+        let limit_param = params.len() + 2;
+        let mut purged = 0;
+
+        for table in ["events_archive", "events"] {
+            let sql = format!(
+                "DELETE FROM {table} WHERE id IN (SELECT e.id FROM {table} e WHERE {filter} LIMIT ?{limit_param})",
+                table = table,
+                filter = filter,
+                limit_param = limit_param
+            );
+            let mut query = sqlx::query(&sql).bind(purge.before);
+            for param in &params {
+                query = query.bind(param);
+            }
+            purged += query
+                .bind(limit - purged as i64)
+                .execute(&self.pool)
+                .await
+                .map_err(db_error)?
+                .rows_affected();
+            if purged as i64 >= limit {
+                break;
+            }
+        }
+
+        Ok(purged)
+    }
+
+    async fn scavenge_truncated(&self, limit: i64) -> Result<u64> {
+        let mut scavenged = 0;
+
+        for (table, keep_head) in [
+            ("events_archive", ""),
+            (
+                "events",
+                "AND e.version < (SELECT MAX(h.version) FROM events h WHERE h.stream_id = e.stream_id)",
+            ),
+        ] {
+            let sql = format!(
+                r#"
+                DELETE FROM {table} WHERE id IN (
+                    SELECT e.id FROM {table} e
+                    JOIN stream_metadata m ON m.stream_id = e.stream_id
+                    WHERE e.version < json_extract(m.metadata, '$.truncate_before')
+                    AND COALESCE(json_extract(m.metadata, '$.legal_hold'), 0) = 0
+                    {keep_head}
+                    LIMIT ?
+                )
+                "#,
+                table = table,
+                keep_head = keep_head
+            );
+            scavenged += sqlx::query(&sql)
+                .bind(limit - scavenged as i64)
+                .execute(&self.pool)
+                .await
+                .map_err(db_error)?
+                .rows_affected();
+            if scavenged as i64 >= limit {
+                break;
+            }
+        }
+
+        Ok(scavenged)
+    }
+
+    async fn scavenge_expired(&self, now: DateTime<Utc>, limit: i64) -> Result<u64> {
+        let mut scavenged = 0;
+
+        for (table, keep_head) in [
+            ("events_archive", ""),
+            (
+                "events",
+                "AND e.version < (SELECT MAX(h.version) FROM events h WHERE h.stream_id = e.stream_id)",
+            ),
+        ] {
+            let sql = format!(
+                r#"
+                DELETE FROM {table} WHERE id IN (
+                    SELECT e.id FROM {table} e
+                    WHERE e.expires_at < ?1
+                    AND e.stream_id NOT IN (
+                        SELECT stream_id FROM stream_metadata
+                        WHERE COALESCE(json_extract(metadata, '$.legal_hold'), 0) = 1
+                    )
+                    {keep_head}
+                    LIMIT ?2
+                )
+                "#,
+                table = table,
+                keep_head = keep_head
+            );
+            scavenged += sqlx::query(&sql)
+                .bind(now)
+                .bind(limit - scavenged as i64)
+                .execute(&self.pool)
+                .await
+                .map_err(db_error)?
+                .rows_affected();
+            if scavenged as i64 >= limit {
+                break;
+            }
+        }
+
+        Ok(scavenged)
+    }
+
+    async fn retention_rules(&self) -> Result<Vec<RetentionRule>> {
+        let rows: Vec<String> = sqlx::query_scalar("SELECT rule FROM retention_rules ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter()
+            .map(|r| Ok(serde_json::from_str(r)?))
+            .collect()
+    }
+
+    async fn set_retention_rule(&self, rule: &RetentionRule) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO retention_rules (name, rule, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT (name) DO UPDATE SET rule = excluded.rule, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&rule.name)
+        .bind(serde_json::to_string(rule)?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_retention_rule(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM retention_rules WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn snapshot_reducers(&self) -> Result<Vec<SnapshotReducer>> {
+        let rows: Vec<String> = sqlx::query_scalar("SELECT reducer FROM snapshot_reducers ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter()
+            .map(|r| Ok(serde_json::from_str(r)?))
+            .collect()
+    }
+
+    async fn set_snapshot_reducer(&self, reducer: &SnapshotReducer) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO snapshot_reducers (name, reducer, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT (name) DO UPDATE SET reducer = excluded.reducer, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&reducer.name)
+        .bind(serde_json::to_string(reducer)?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_snapshot_reducer(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM snapshot_reducers WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn plugins(&self) -> Result<Vec<(Plugin, Vec<u8>)>> {
+        let rows = sqlx::query("SELECT plugin, module FROM plugins ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                let plugin: String = row.try_get("plugin")?;
+                Ok((serde_json::from_str(&plugin)?, row.try_get("module")?))
+            })
+            .collect()
+    }
+
+    async fn set_plugin(&self, plugin: &Plugin, module: &[u8]) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO plugins (name, plugin, module, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (name) DO UPDATE SET plugin = excluded.plugin, module = excluded.module, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&plugin.name)
+        .bind(serde_json::to_string(plugin)?)
+        .bind(module)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_plugin(&self, name: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        sqlx::query("DELETE FROM projections WHERE name = ?")
+            .bind(name)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        let result = sqlx::query("DELETE FROM plugins WHERE name = ?")
+            .bind(name)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn projection(&self, name: &str) -> Result<Option<ProjectionState>> {
+        let row: Option<String> = sqlx::query_scalar("SELECT projection FROM projections WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        row.map(|p| Ok(serde_json::from_str(&p)?)).transpose()
+    }
+
+    async fn set_projection(&self, projection: &ProjectionState) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO projections (name, projection, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT (name) DO UPDATE SET projection = excluded.projection, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&projection.name)
+        .bind(serde_json::to_string(projection)?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn add_dead_letters(&self, letters: &[DeadLetter]) -> Result<()> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+
+        for letter in letters {
+            sqlx::query("INSERT INTO dead_letters (id, source, dead_letter, created_at) VALUES (?, ?, ?, ?)")
+                .bind(letter.id.to_string())
+                .bind(&letter.source)
+                .bind(serde_json::to_string(letter)?)
+                .bind(letter.created_at)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+
+        tx.commit().await.map_err(db_error)?;
+        Ok(())
+    }
+
+    async fn dead_letters(&self, source: Option<&str>, limit: i64) -> Result<Vec<DeadLetter>> {
+        let rows: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT dead_letter FROM dead_letters
+            WHERE ? IS NULL OR source = ?
+            ORDER BY created_at
+            LIMIT ?
+            "#,
+        )
+        .bind(source)
+        .bind(source)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter().map(|l| Ok(serde_json::from_str(l)?)).collect()
+    }
+
+    async fn dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>> {
+        let row: Option<String> = sqlx::query_scalar("SELECT dead_letter FROM dead_letters WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        row.map(|l| Ok(serde_json::from_str(&l)?)).transpose()
+    }
+
+    async fn set_dead_letter(&self, letter: &DeadLetter) -> Result<()> {
+        sqlx::query("UPDATE dead_letters SET dead_letter = ? WHERE id = ?")
+            .bind(serde_json::to_string(letter)?)
+            .bind(letter.id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_dead_letter(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM dead_letters WHERE id = ?")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn encryption_policies(&self) -> Result<Vec<EncryptionPolicy>> {
+        let rows: Vec<String> =
+            sqlx::query_scalar("SELECT policy FROM encryption_policies ORDER BY event_type")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_error)?;
+
+        rows.iter()
+            .map(|p| Ok(serde_json::from_str(p)?))
+            .collect()
+    }
+
+    async fn set_encryption_policy(&self, policy: &EncryptionPolicy) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO encryption_policies (event_type, policy, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT (event_type) DO UPDATE SET policy = excluded.policy, updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&policy.event_type)
+        .bind(serde_json::to_string(policy)?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_encryption_policy(&self, event_type: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM encryption_policies WHERE event_type = ?")
+            .bind(event_type)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn subject_key(&self, subject_id: &str) -> Result<Option<Vec<u8>>> {
+        sqlx::query_scalar("SELECT key FROM subject_keys WHERE subject_id = ?")
+            .bind(subject_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn insert_subject_key(&self, subject_id: &str, key: &[u8]) -> Result<Vec<u8>> {
+        sqlx::query("INSERT INTO subject_keys (subject_id, key, created_at) VALUES (?, ?, ?) ON CONFLICT (subject_id) DO NOTHING")
+            .bind(subject_id)
+            .bind(key)
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        sqlx::query_scalar("SELECT key FROM subject_keys WHERE subject_id = ?")
+            .bind(subject_id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn delete_subject_key(&self, subject_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM subject_keys WHERE subject_id = ?")
+            .bind(subject_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn chain_anchors(&self, limit: i64) -> Result<Vec<ChainAnchor>> {
+        let rows: Vec<String> = sqlx::query_scalar("SELECT anchor FROM chain_anchors ORDER BY created_at DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter().map(|a| Ok(serde_json::from_str(a)?)).collect()
+    }
+
+    async fn add_chain_anchor(&self, anchor: &ChainAnchor) -> Result<()> {
+        sqlx::query("INSERT INTO chain_anchors (id, position, anchor, created_at) VALUES (?, ?, ?, ?)")
+            .bind(anchor.id.to_string())
+            .bind(anchor.position)
+            .bind(serde_json::to_string(anchor)?)
+            .bind(anchor.created_at)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn namespaces(&self) -> Result<Vec<Namespace>> {
+        let rows: Vec<String> = sqlx::query_scalar("SELECT namespace FROM namespaces ORDER BY id")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter()
+            .map(|r| Ok(serde_json::from_str(r)?))
+            .collect()
+    }
+
+    async fn insert_namespace(&self, namespace: &Namespace) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO namespaces (id, namespace, created_at) VALUES (?, ?, ?) ON CONFLICT (id) DO NOTHING",
+        )
+        .bind(namespace.id())
+        .bind(serde_json::to_string(namespace)?)
+        .bind(namespace.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_namespace(&self, id: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM namespaces WHERE id = ?1 OR substr(id, 1, length(?1) + 1) = ?1 || '/'")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn subscription_groups(&self) -> Result<Vec<SubscriptionGroup>> {
+        let rows: Vec<String> = sqlx::query_scalar("SELECT subscription_group FROM subscription_groups ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.iter()
+            .map(|r| Ok(serde_json::from_str(r)?))
+            .collect()
+    }
+
+    async fn set_subscription_group(&self, group: &SubscriptionGroup) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO subscription_groups (name, subscription_group, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT (name) DO UPDATE SET subscription_group = excluded.subscription_group,
+                                             updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&group.name)
+        .bind(serde_json::to_string(group)?)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_subscription_group(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM subscription_groups WHERE name = ?")
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
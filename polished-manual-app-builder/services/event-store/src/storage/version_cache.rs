@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Head versions of recently written streams, so appends can skip the
+/// `MAX(version)` lookup. Entries are written after commit. Other instances
+/// sharing the database can make an entry stale, so a cached head is only a
+/// hint: appends guard their insert and fall back to the database when it
+/// turns out to be wrong.
+#[derive(Debug)]
+pub(super) struct VersionCache {
+    capacity: usize,
+    versions: Mutex<HashMap<String, i64>>,
+}
+
+impl VersionCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            versions: Mutex::new(HashMap::with_capacity(capacity.min(4096))),
+        }
+    }
+
+    pub fn get(&self, stream_id: &str) -> Option<i64> {
+        self.versions.lock().unwrap().get(stream_id).copied()
+    }
+
+    pub fn set(&self, stream_id: &str, version: i64) {
+        let mut versions = self.versions.lock().unwrap();
+        if versions.len() >= self.capacity && !versions.contains_key(stream_id) {
+            // Evict an arbitrary entry; a miss only costs one query
+            if let Some(evicted) = versions.keys().next().cloned() {
+                versions.remove(&evicted);
+            }
+        }
+        versions.insert(stream_id.to_string(), version);
+    }
+
+    pub fn invalidate(&self, stream_id: &str) {
+        self.versions.lock().unwrap().remove(stream_id);
+    }
+
+    pub fn clear(&self) {
+        self.versions.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,216 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::Tenant;
+use crate::cold_storage;
+use crate::deadline::Deadline;
+use crate::deprecation::{copy_event, copy_progress};
+use crate::error::{AppError, Result};
+use crate::storage::ReadDirection;
+use crate::AppState;
+
+/// Events copied per page.
+const COPY_PAGE_SIZE: i64 = 500;
+
+/// Where to copy a stream: an explicit `target_stream_id`, or the source id
+/// with its project and/or workspace segment replaced.
+#[derive(Debug, Deserialize)]
+pub struct CopyStreamRequest {
+    pub target_stream_id: Option<String>,
+    pub project: Option<String>,
+    pub workspace: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreamCopy {
+    pub source_stream_id: String,
+    pub target_stream_id: String,
+    pub events_copied: u64,
+    /// Whether the source was deleted after the copy.
+    pub moved: bool,
+}
+
+impl CopyStreamRequest {
+    fn target(&self, source: &str) -> Result<String> {
+        if let Some(target) = &self.target_stream_id {
+            if self.project.is_some() || self.workspace.is_some() {
+                return Err(AppError::BadRequest(
+                    "Name either target_stream_id or project and workspace, not both".to_string(),
+                ));
+            }
+            return Ok(target.clone());
+        }
+        if self.project.is_none() && self.workspace.is_none() {
+            return Err(AppError::BadRequest(
+                "A copy needs a target_stream_id, project or workspace".to_string(),
+            ));
+        }
+
+        let mut segments: Vec<&str> = source.split('/').collect();
+        if let Some(project) = &self.project {
+            segments[0] = project.as_str();
+        }
+        if let Some(workspace) = &self.workspace {
+            if segments.len() < 3 {
+                return Err(AppError::BadRequest(format!(
+                    "Stream {} has no workspace segment to replace",
+                    source
+                )));
+            }
+            segments[1] = workspace.as_str();
+        }
+        Ok(segments.join("/"))
+    }
+}
+
+/// POST /streams/:stream_id/copy — duplicates the stream's events, archived
+/// ones included, into a new stream at the same versions. Each copy keeps
+/// its original timestamp and records its source in `migrated_from`; the
+/// stream metadata is copied too. Retrying a copy that failed partway
+/// resumes it after the last event copied.
+pub async fn copy_stream(
+    Path(stream_id): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<CopyStreamRequest>,
+) -> Result<Json<StreamCopy>> {
+    let target = request.target(&stream_id)?;
+    Ok(Json(copy(&state, &tenant, &stream_id, &target, false).await?))
+}
+
+/// POST /streams/:stream_id/move — copies the stream as above, then deletes
+/// the source the way `DELETE /streams/:stream_id` does, leaving
+/// `moved_to` in its metadata. Fails without deleting anything when the
+/// source was appended to during the copy; retry to copy the rest.
+pub async fn move_stream(
+    Path(stream_id): Path<String>,
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(request): Json<CopyStreamRequest>,
+) -> Result<Json<StreamCopy>> {
+    let target = request.target(&stream_id)?;
+    Ok(Json(copy(&state, &tenant, &stream_id, &target, true).await?))
+}
+
+async fn copy(
+    state: &AppState,
+    tenant: &Tenant,
+    stream_id: &str,
+    target: &str,
+    delete_source: bool,
+) -> Result<StreamCopy> {
+    tenant.authorize(target)?;
+    state.stream_ids.validate(target)?;
+    if target == stream_id {
+        return Err(AppError::BadRequest("A stream cannot be copied onto itself".to_string()));
+    }
+    if state.config.namespaces_strict && !state.namespaces.allows(state.storage.as_ref(), target).await? {
+        return Err(AppError::BadRequest(format!(
+            "Stream {} is outside a registered project and workspace",
+            target
+        )));
+    }
+
+    if state.storage.stream_version(stream_id).await? == 0 {
+        return Err(AppError::NotFound(format!("Stream {} not found", stream_id)));
+    }
+    // Versions are kept, so an earlier copy left the target at the version it copied through
+    let (copied, copied_through) = copy_progress(state, stream_id, target).await?;
+    if copied != copied_through {
+        return Err(AppError::Conflict(format!("Target stream {} already has events", target)));
+    }
+    if copied > 0 {
+        info!("Resuming copy of {} into {} after version {}", stream_id, target, copied);
+    }
+    let mut metadata = state.storage.stream_metadata(stream_id).await?.unwrap_or_default();
+    // Versions are kept, so the copy can't start past a deleted prefix
+    if let Some(truncated) = metadata.truncate_before.filter(|v| *v > 1) {
+        return Err(AppError::Conflict(format!(
+            "Versions of {} below {} are deleted; only whole streams can be copied",
+            stream_id, truncated
+        )));
+    }
+
+    let mut events_copied = copied as u64;
+    let mut from_version = copied + 1;
+    loop {
+        let page = cold_storage::read_stream(
+            state,
+            stream_id,
+            from_version,
+            COPY_PAGE_SIZE,
+            ReadDirection::Forward,
+            true,
+            Deadline(None),
+        )
+        .await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        from_version = last.version + 1;
+
+        for event in page {
+            // Expired or scavenged events leave gaps no append can reproduce
+            if event.version != events_copied as i64 + 1 {
+                return Err(AppError::Conflict(format!(
+                    "Stream {} has no version {}; {} holds versions 1 to {} of it",
+                    stream_id,
+                    events_copied + 1,
+                    target,
+                    events_copied
+                )));
+            }
+            let new_event = copy_event(event, state.providers.new_id(), target, None)?;
+            let stored = state
+                .storage
+                .append(new_event, Some(events_copied as i64), Deadline(None))
+                .await?;
+            state.bus.publish(stored).await;
+            events_copied += 1;
+        }
+    }
+
+    let mut target_metadata = metadata.clone();
+    target_metadata.truncate_before = None;
+    target_metadata.custom.remove("moved_to");
+    target_metadata.custom.insert("copied_from".to_string(), stream_id.into());
+    state.storage.set_stream_metadata(target, &target_metadata).await?;
+
+    if delete_source {
+        let copied_head = events_copied as i64;
+        // An append that lands after this check stays readable: truncation
+        // only hides the versions copied
+        let head = state.storage.stream_version(stream_id).await?;
+        if head != copied_head {
+            return Err(AppError::Conflict(format!(
+                "Stream {} was appended to during the move and is at version {}; {} holds versions 1 to {}",
+                stream_id, head, target, copied_head
+            )));
+        }
+        metadata.truncate_before = Some(copied_head + 1);
+        metadata.custom.insert("moved_to".to_string(), target.into());
+        state.storage.set_stream_metadata(stream_id, &metadata).await?;
+        if let Some(cache) = &state.read_cache {
+            cache.invalidate_stream(stream_id);
+        }
+        state.system_streams.stream_deleted(stream_id, copied_head).await;
+    }
+    info!(
+        "Stream {} {} to {} ({} events)",
+        stream_id,
+        if delete_source { "moved" } else { "copied" },
+        target,
+        events_copied
+    );
+
+    Ok(StreamCopy {
+        source_stream_id: stream_id.to_string(),
+        target_stream_id: target.to_string(),
+        events_copied,
+        moved: delete_source,
+    })
+}
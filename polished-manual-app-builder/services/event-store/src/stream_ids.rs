@@ -0,0 +1,102 @@
+use regex::Regex;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::system_streams::is_system_stream;
+
+const MAX_STREAM_ID_LEN: usize = 255;
+
+/// One check client stream ids must pass.
+pub trait StreamIdRule: Send + Sync {
+    /// Why `stream_id` is refused, or `None` if the rule allows it.
+    fn check(&self, stream_id: &str) -> Option<String>;
+}
+
+/// `$`-prefixed ids belong to the store's own system streams.
+struct Reserved;
+
+impl StreamIdRule for Reserved {
+    fn check(&self, stream_id: &str) -> Option<String> {
+        is_system_stream(stream_id).then(|| "stream ids starting with '$' are reserved for system streams".to_string())
+    }
+}
+
+/// Up to 255 letters, digits, `-`, `_` and `/`.
+struct Charset;
+
+impl StreamIdRule for Charset {
+    fn check(&self, stream_id: &str) -> Option<String> {
+        if stream_id.is_empty() || stream_id.len() > MAX_STREAM_ID_LEN {
+            return Some(format!("stream ids must be 1 to {} bytes long", MAX_STREAM_ID_LEN));
+        }
+        (!stream_id.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '/'))
+            .then(|| "stream ids may only contain letters, digits, '-', '_' and '/'".to_string())
+    }
+}
+
+/// `{project}/{workspace}/{name}`: the project is the partition and the
+/// tenant, so a stream id without one lands in a partition of its own.
+struct Structure;
+
+impl StreamIdRule for Structure {
+    fn check(&self, stream_id: &str) -> Option<String> {
+        let segments: Vec<&str> = stream_id.split('/').collect();
+        (segments.len() != 3 || segments.iter().any(|s| s.is_empty()))
+            .then(|| "stream ids must be {project}/{workspace}/{name}".to_string())
+    }
+}
+
+/// A deployment's own pattern, from `STREAM_ID_PATTERNS`.
+struct Pattern(Regex);
+
+impl StreamIdRule for Pattern {
+    fn check(&self, stream_id: &str) -> Option<String> {
+        (!self.0.is_match(stream_id)).then(|| format!("stream ids must match {}", self.0.as_str()))
+    }
+}
+
+/// The rules a stream id a client names must pass to be written to: not
+/// reserved, the allowed characters, the documented structure unless
+/// `STREAM_ID_REQUIRE_STRUCTURE` is off, then every pattern in
+/// `STREAM_ID_PATTERNS`. Streams the store writes itself, and events
+/// arriving by replication, aren't checked.
+#[derive(Clone)]
+pub struct StreamIdPolicy {
+    rules: Vec<Arc<dyn StreamIdRule>>,
+}
+
+impl StreamIdPolicy {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut policy = Self {
+            rules: vec![Arc::new(Reserved), Arc::new(Charset)],
+        };
+        if config.stream_id_require_structure {
+            policy = policy.with_rule(Structure);
+        }
+        for pattern in config.stream_id_pattern_list() {
+            let regex = Regex::new(pattern)
+                .map_err(|e| AppError::Internal(format!("Invalid STREAM_ID_PATTERNS entry '{}': {}", pattern, e)))?;
+            policy = policy.with_rule(Pattern(regex));
+        }
+        Ok(policy)
+    }
+
+    /// Adds a rule checked after the ones already there.
+    pub fn with_rule(mut self, rule: impl StreamIdRule + 'static) -> Self {
+        self.rules.push(Arc::new(rule));
+        self
+    }
+
+    pub fn is_valid(&self, stream_id: &str) -> bool {
+        self.rules.iter().all(|rule| rule.check(stream_id).is_none())
+    }
+
+    /// `BadRequest` naming the first rule `stream_id` breaks.
+    pub fn validate(&self, stream_id: &str) -> Result<()> {
+        match self.rules.iter().find_map(|rule| rule.check(stream_id)) {
+            Some(reason) => Err(AppError::BadRequest(format!("Invalid stream_id {}: {}", stream_id, reason))),
+            None => Ok(()),
+        }
+    }
+}
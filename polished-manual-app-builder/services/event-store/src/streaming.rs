@@ -0,0 +1,188 @@
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::error;
+
+use crate::cold_storage;
+use crate::deadline::Deadline;
+use crate::error::{AppError, Result};
+use crate::models::Event;
+use crate::storage::ReadDirection;
+use crate::AppState;
+
+pub const NDJSON_MIME: &str = "application/x-ndjson";
+
+/// Events buffered between the storage scan and the response encoder.
+const SCAN_BUFFER: usize = 1_024;
+/// Versions fetched from the archive or cold storage per request.
+const OLDER_BATCH: i64 = 10_000;
+/// Encoded bytes buffered before a body chunk is sent.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    /// One event per line (NDJSON).
+    Lines,
+    /// A single JSON array.
+    Array,
+}
+
+/// Reads a stream forward from `from_version` through `to_version` on a
+/// background task, including any prefix that has been moved to cold
+/// storage, or to the archive table when `include_archived` is set. The
+/// receiver yields events in version order and ends with an error item if
+/// the read fails part-way.
+pub fn scan(
+    state: AppState,
+    stream_id: String,
+    from_version: i64,
+    to_version: i64,
+    include_archived: bool,
+) -> mpsc::Receiver<Result<Event>> {
+    let (sender, receiver) = mpsc::channel(SCAN_BUFFER);
+
+    tokio::spawn(async move {
+        if let Err(e) = produce(&state, &stream_id, from_version.max(1), to_version, include_archived, &sender).await {
+            error!("Streaming read of {} failed: {}", stream_id, e);
+            let _ = sender.send(Err(e)).await;
+        }
+    });
+
+    receiver
+}
+
+async fn produce(
+    state: &AppState,
+    stream_id: &str,
+    from_version: i64,
+    to_version: i64,
+    include_archived: bool,
+    sender: &mpsc::Sender<Result<Event>>,
+) -> Result<()> {
+    let mut from_version = from_version;
+    let mut count = 0;
+
+    if state.cold_store.is_some() || include_archived {
+        // Archived and tiered events are always a prefix of the stream; serve them first
+        let local_from = state
+            .storage
+            .read_stream(stream_id, from_version, 1, ReadDirection::Forward, Deadline(None))
+            .await?
+            .first()
+            .map(|e| e.version);
+        let older_to = local_from.map_or(to_version, |v| (v - 1).min(to_version));
+        if older_to >= from_version {
+            // One segment's worth at a time, so memory stays bounded
+            while from_version <= older_to {
+                let to = from_version.saturating_add(OLDER_BATCH - 1).min(older_to);
+                let batch = cold_storage::read_older(state, stream_id, from_version, to, include_archived).await?;
+                if batch.is_empty() {
+                    break;
+                }
+                for event in batch {
+                    count += 1;
+                    if sender.send(Ok(event)).await.is_err() {
+                        state.metrics.record_read(stream_id, count);
+                        return Ok(());
+                    }
+                }
+                from_version = to + 1;
+            }
+            from_version = older_to.saturating_add(1);
+        }
+    }
+
+    let (events, mut scanned) = mpsc::channel(SCAN_BUFFER);
+    let scan = {
+        let storage = state.storage.clone();
+        let stream_id = stream_id.to_string();
+        tokio::spawn(async move { storage.scan_stream(&stream_id, from_version, to_version, events).await })
+    };
+
+    while let Some(event) = scanned.recv().await {
+        count += 1;
+        if sender.send(Ok(event)).await.is_err() {
+            break;
+        }
+    }
+    drop(scanned);
+    state.metrics.record_read(stream_id, count);
+
+    scan.await
+        .map_err(|e| AppError::Internal(format!("Stream scan task failed: {}", e)))?
+}
+
+/// Whether the client asked for NDJSON, which always streams.
+pub fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| {
+            accept
+                .split(',')
+                .any(|mime| mime.split(';').next().unwrap_or("").trim() == NDJSON_MIME)
+        })
+}
+
+/// Encodes events as NDJSON, one event per line.
+pub fn ndjson_body(events: mpsc::Receiver<Result<Event>>) -> Body {
+    encode_body(events, Framing::Lines)
+}
+
+/// Encodes events as a single JSON array, written incrementally.
+pub fn json_array_body(events: mpsc::Receiver<Result<Event>>) -> Body {
+    encode_body(events, Framing::Array)
+}
+
+fn encode_body(mut events: mpsc::Receiver<Result<Event>>, framing: Framing) -> Body {
+    let (sender, receiver) = mpsc::channel::<Result<Bytes>>(4);
+
+    tokio::spawn(async move {
+        let mut chunk = Vec::with_capacity(CHUNK_BYTES);
+        let mut first = true;
+        if framing == Framing::Array {
+            chunk.push(b'[');
+        }
+
+        while let Some(event) = events.recv().await {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    // Headers are already sent; aborting the body is all we can do
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+
+            if framing == Framing::Array && !first {
+                chunk.push(b',');
+            }
+            first = false;
+            if let Err(e) = serde_json::to_writer(&mut chunk, &event) {
+                let _ = sender.send(Err(e.into())).await;
+                return;
+            }
+            if framing == Framing::Lines {
+                chunk.push(b'\n');
+            }
+
+            // Flush when the buffer is full or the reader has caught up
+            if chunk.len() >= CHUNK_BYTES || events.is_empty() {
+                let full = std::mem::replace(&mut chunk, Vec::with_capacity(CHUNK_BYTES));
+                if sender.send(Ok(Bytes::from(full))).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        if framing == Framing::Array {
+            chunk.push(b']');
+        }
+        if !chunk.is_empty() {
+            let _ = sender.send(Ok(Bytes::from(chunk))).await;
+        }
+    });
+
+    Body::from_stream(ReceiverStream::new(receiver))
+}
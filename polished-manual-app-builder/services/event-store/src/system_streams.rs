@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::bus::{BusConsumer, EventBus};
+use crate::config::Config;
+use crate::deadline::Deadline;
+use crate::models::{Event, Snapshot};
+use crate::providers::Providers;
+use crate::storage::{EventStorage, NewEvent};
+use crate::{get_partition_key, integrity};
+
+/// Streams created and deleted: `$stream-created`, `$stream-deleted`.
+pub const STREAMS: &str = "$streams";
+/// Snapshots taken, by request or by the scheduler: `$snapshot-created`.
+pub const SNAPSHOTS: &str = "$snapshots";
+/// GraphQL subscriptions opened over `/graphql/stream`: `$subscription-created`.
+pub const SUBSCRIPTIONS: &str = "$subscriptions";
+/// Admin rewrites of stored events over `/admin/redactions`: `$redaction-applied`.
+pub const REDACTIONS: &str = "$redactions";
+
+/// Whether `stream_id` is a system stream. Client stream ids can't start
+/// with `$`, so only the store writes to these.
+pub fn is_system_stream(stream_id: &str) -> bool {
+    stream_id.starts_with('$')
+}
+
+/// Records store lifecycle events in the `$`-prefixed system streams, where
+/// operators and projections read them like any other stream. Each system
+/// stream is its own partition, readable with the admin role. Recording is
+/// best effort: a failure is logged, never returned to the request that
+/// caused it.
+#[derive(Clone)]
+pub struct SystemStreams {
+    storage: Arc<dyn EventStorage>,
+    bus: EventBus,
+    providers: Providers,
+    enabled: bool,
+}
+
+impl SystemStreams {
+    pub fn new(storage: Arc<dyn EventStorage>, bus: EventBus, config: &Config, providers: Providers) -> Self {
+        Self {
+            storage,
+            bus,
+            providers,
+            // A secondary receives its primary's system events by replication
+            enabled: config.system_streams && !config.replication_secondary,
+        }
+    }
+
+    async fn record(&self, stream_id: &str, event_type: &str, data: Value) {
+        if !self.enabled {
+            return;
+        }
+
+        let partition_key = get_partition_key(stream_id);
+        let event = NewEvent {
+            id: self.providers.new_id(),
+            stream_id: stream_id.to_string(),
+            event_type: event_type.to_string(),
+            checksum: integrity::checksum(Some(&data), None),
+            data: Some(data),
+            payload: None,
+            content_type: "application/json".to_string(),
+            metadata: None,
+            tenant_id: partition_key.clone(),
+            partition_key,
+            created_at: self.providers.now(),
+            expires_at: None,
+            preconditions: Vec::new(),
+        };
+        match self.storage.append(event, None, Deadline(None)).await {
+            Ok(event) => {
+                // Not awaited: this may be running on the bus's own consumer task
+                let bus = self.bus.clone();
+                tokio::spawn(async move { bus.publish(event).await });
+            }
+            Err(e) => warn!("Failed to record {} in {}: {}", event_type, stream_id, e),
+        }
+    }
+
+    pub async fn stream_deleted(&self, stream_id: &str, version: i64) {
+        let data = json!({ "stream_id": stream_id, "version": version });
+        self.record(STREAMS, "$stream-deleted", data).await;
+    }
+
+    pub async fn snapshot_created(&self, snapshot: &Snapshot) {
+        let data = json!({
+            "snapshot_id": snapshot.id,
+            "stream_id": snapshot.stream_id,
+            "version": snapshot.version,
+            "reducer": snapshot.reducer,
+        });
+        self.record(SNAPSHOTS, "$snapshot-created", data).await;
+    }
+
+    pub async fn redaction_applied(&self, data: Value) {
+        self.record(REDACTIONS, "$redaction-applied", data).await;
+    }
+
+    pub async fn subscription_created(&self, subscription_id: Uuid, tenant: Option<&str>, window: Option<usize>) {
+        let data = json!({ "subscription_id": subscription_id, "tenant_id": tenant, "window": window });
+        self.record(SUBSCRIPTIONS, "$subscription-created", data).await;
+    }
+}
+
+/// Sees every committed event, so a stream's first one records
+/// `$stream-created` whichever write path appended it.
+#[async_trait]
+impl BusConsumer for SystemStreams {
+    fn name(&self) -> &str {
+        "system_streams"
+    }
+
+    async fn handle(&self, event: &Event) {
+        if event.version != 1 || is_system_stream(&event.stream_id) {
+            return;
+        }
+        let data = json!({
+            "stream_id": event.stream_id,
+            "event_type": event.event_type,
+            "created_at": event.created_at,
+        });
+        self.record(STREAMS, "$stream-created", data).await;
+    }
+}
@@ -1,10 +1,34 @@
 use anyhow::Result;
-use opentelemetry::{trace::TraceError, KeyValue};
+use axum::http::{HeaderMap, Request};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{Span as _, SpanKind, Tracer as _};
+use opentelemetry::{global, trace::TraceError, KeyValue};
 use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::{TextMapCompositePropagator, TraceContextPropagator};
 use opentelemetry_sdk::{trace, Resource};
+use std::time::{Duration, SystemTime};
+use tracing::field::{Field, Visit};
+use tracing::{Level, Subscriber};
+use tracing_opentelemetry::{OpenTelemetrySpanExt, OtelData, PreSampledTracer};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::{self, Layer};
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-pub fn init() -> Result<()> {
+/// The target sqlx logs each statement under.
+const QUERY_TARGET: &str = "sqlx::query";
+
+/// Logs go to stdout, or to stderr for CLI commands that write their output
+/// to stdout.
+pub fn init(log_to_stderr: bool) -> Result<()> {
+    // Continue W3C traces from callers; uber-trace-id still works for older ones
+    let propagators: Vec<Box<dyn TextMapPropagator + Send + Sync>> = vec![
+        Box::new(TraceContextPropagator::new()),
+        Box::new(opentelemetry_jaeger::Propagator::new()),
+    ];
+    global::set_text_map_propagator(TextMapCompositePropagator::new(propagators));
+
     // Initialize OpenTelemetry tracer if Jaeger endpoint is provided
     let tracer = if let Ok(jaeger_endpoint) = std::env::var("JAEGER_ENDPOINT") {
         opentelemetry_otlp::new_pipeline()
@@ -24,7 +48,6 @@ pub fn init() -> Result<()> {
             .map_err(|e| anyhow::anyhow!("Failed to initialize tracer: {}", e))?
     } else {
         // Fallback to no-op tracer
-        opentelemetry::global::set_text_map_propagator(opentelemetry_jaeger::Propagator::new());
         trace::TracerProvider::builder()
             .with_config(
                 trace::config().with_resource(Resource::new(vec![
@@ -36,7 +59,14 @@ pub fn init() -> Result<()> {
             .tracer("event-store")
     };
 
-    // Initialize tracing subscriber
+    let writer = if log_to_stderr {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+
+    // Initialize tracing subscriber. Statement events only feed the query
+    // spans, so the log doesn't get a line per query.
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::fmt::layer()
@@ -44,11 +74,134 @@ pub fn init() -> Result<()> {
                 .with_thread_ids(true)
                 .with_file(true)
                 .with_line_number(true)
+                .with_writer(writer)
                 .json()
+                .with_filter(env_filter()?)
         )
-        .with(EnvFilter::from_default_env().add_directive("event_store=info".parse()?))
-        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .with(tracing_opentelemetry::layer().with_tracer(tracer.clone()).with_filter(env_filter()?))
+        .with(QuerySpans { tracer }.with_filter(Targets::new().with_target(QUERY_TARGET, Level::DEBUG)))
         .init();
 
     Ok(())
 }
+
+fn env_filter() -> Result<EnvFilter> {
+    Ok(EnvFilter::from_default_env().add_directive("event_store=info".parse()?))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// The span a request is handled in. It continues the caller's trace when
+/// the request carries `traceparent` (and `tracestate`), so a slow append
+/// shows up under the span of the service that made it.
+pub fn request_span<B>(request: &Request<B>) -> tracing::Span {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        traceparent = tracing::field::Empty,
+        request_id = tracing::field::Empty,
+    );
+    if let Some(traceparent) = request.headers().get("traceparent").and_then(|v| v.to_str().ok()) {
+        span.record("traceparent", traceparent);
+    }
+
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(request.headers())));
+    span.set_parent(parent);
+    span
+}
+
+/// What sqlx records about a finished statement.
+#[derive(Default)]
+struct QueryFields {
+    summary: String,
+    statement: String,
+    rows_affected: u64,
+    rows_returned: u64,
+    elapsed_secs: f64,
+}
+
+impl Visit for QueryFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = value.to_string(),
+            "db.statement" => self.statement = value.trim().to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "rows_affected" => self.rows_affected = value,
+            "rows_returned" => self.rows_returned = value,
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Turns the event sqlx logs after each statement into a child span of the
+/// span the statement ran in, named by its summary and timed from its
+/// elapsed time. Statements outside any span (background jobs) are skipped.
+struct QuerySpans {
+    tracer: opentelemetry_sdk::trace::Tracer,
+}
+
+impl<S> Layer<S> for QuerySpans
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: layer::Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let parent = {
+            let mut extensions = span.extensions_mut();
+            let Some(data) = extensions.get_mut::<OtelData>() else {
+                return;
+            };
+            self.tracer.sampled_context(data)
+        };
+
+        let mut fields = QueryFields::default();
+        event.record(&mut fields);
+        // Short statements are logged whole as the summary
+        let statement = if fields.statement.is_empty() {
+            fields.summary.clone()
+        } else {
+            fields.statement
+        };
+
+        let end = SystemTime::now();
+        let start = end - Duration::from_secs_f64(fields.elapsed_secs.max(0.0));
+        let mut query = self
+            .tracer
+            .span_builder(fields.summary)
+            .with_kind(SpanKind::Client)
+            .with_start_time(start)
+            .with_attributes(vec![
+                KeyValue::new("db.statement", statement),
+                KeyValue::new("db.rows_affected", fields.rows_affected as i64),
+                KeyValue::new("db.rows_returned", fields.rows_returned as i64),
+            ])
+            .start_with_context(&self.tracer, &parent);
+        query.end_with_timestamp(end);
+    }
+}
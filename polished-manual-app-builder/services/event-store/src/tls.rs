@@ -0,0 +1,139 @@
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+use crate::config::Config;
+use crate::error::{AppError, Result};
+
+/// The PEM files a TLS listener is built from.
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert_path: String,
+    pub key_path: String,
+    /// CA bundle client certificates must chain to; clients need no
+    /// certificate when unset.
+    pub client_ca_path: Option<String>,
+}
+
+impl TlsFiles {
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        match (&config.tls_cert_path, &config.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => Ok(Some(Self {
+                cert_path: cert_path.clone(),
+                key_path: key_path.clone(),
+                client_ca_path: config.tls_client_ca_path.clone(),
+            })),
+            (None, None) if config.tls_client_ca_path.is_none() => Ok(None),
+            _ => Err(AppError::Internal(
+                "TLS needs both TLS_CERT_PATH and TLS_KEY_PATH (TLS_CLIENT_CA_PATH requires them too)".to_string(),
+            )),
+        }
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &str> {
+        [Some(self.cert_path.as_str()), Some(self.key_path.as_str()), self.client_ca_path.as_deref()]
+            .into_iter()
+            .flatten()
+    }
+
+    /// Latest modification time across the files, for change detection.
+    async fn modified(&self) -> Option<SystemTime> {
+        let mut latest = None;
+        for path in self.paths() {
+            let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+            latest = latest.max(Some(modified));
+        }
+        latest
+    }
+
+    async fn server_config(&self) -> Result<Arc<ServerConfig>> {
+        let provider = Arc::new(ring::default_provider());
+        let certs = read_certs(&self.cert_path).await?;
+        let key = read_key(&self.key_path).await?;
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| AppError::Internal(format!("Invalid TLS settings: {}", e)))?;
+        let builder = match &self.client_ca_path {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(path).await? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| AppError::Internal(format!("Invalid client CA in {}: {}", path, e)))?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                    .build()
+                    .map_err(|e| AppError::Internal(format!("Invalid client CA in {}: {}", path, e)))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| AppError::Internal(format!("Invalid TLS certificate or key: {}", e)))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+
+    pub async fn rustls_config(&self) -> Result<RustlsConfig> {
+        Ok(RustlsConfig::from_config(self.server_config().await?))
+    }
+}
+
+async fn read_pem(path: &str) -> Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read {}: {}", path, e)))
+}
+
+async fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let pem = read_pem(path).await?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| AppError::Internal(format!("Invalid certificate in {}: {}", path, e)))?;
+    if certs.is_empty() {
+        return Err(AppError::Internal(format!("No certificates in {}", path)));
+    }
+    Ok(certs)
+}
+
+async fn read_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let pem = read_pem(path).await?;
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .map_err(|e| AppError::Internal(format!("Invalid private key in {}: {}", path, e)))?
+        .ok_or_else(|| AppError::Internal(format!("No private key in {}", path)))
+}
+
+/// Reloads the certificate, key and client CA when any of the files changes,
+/// so rotated certificates are served without a restart. New connections
+/// get the new certificate; established ones keep theirs.
+pub async fn reload_on_change(tls: RustlsConfig, files: TlsFiles, interval: Duration) {
+    let mut loaded = files.modified().await;
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let modified = files.modified().await;
+        if modified.is_none() || modified == loaded {
+            continue;
+        }
+
+        // A rotation that is half-written fails to parse; keep serving the old one
+        match files.server_config().await {
+            Ok(config) => {
+                tls.reload_from_config(config);
+                loaded = modified;
+                info!("TLS certificate reloaded from {}", files.cert_path);
+            }
+            Err(e) => error!("TLS certificate reload failed, keeping the current one: {}", e),
+        }
+    }
+}
+
@@ -0,0 +1,60 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::{AppError, Result};
+use crate::AppState;
+
+/// The UI's files, compiled into the binary: name, content type, body.
+const ASSETS: &[(&str, &str, &str)] = &[
+    ("index.html", "text/html; charset=utf-8", include_str!("ui/index.html")),
+    ("app.js", "text/javascript; charset=utf-8", include_str!("ui/app.js")),
+    ("app.css", "text/css; charset=utf-8", include_str!("ui/app.css")),
+];
+
+/// Scripts and styles only from the UI itself, and requests only to this
+/// service, so event data shown in the page can't run or load anything.
+const CONTENT_SECURITY_POLICY: &str = "default-src 'self'; img-src 'self' data:; frame-ancestors 'none'";
+
+/// Whether `path` is one of the UI's files. They are served without
+/// credentials, and past an open circuit breaker so the UI loads during an
+/// incident; the pages fetch everything they show from the API with the
+/// key entered in them.
+pub fn is_ui_path(path: &str) -> bool {
+    path == "/ui" || path.starts_with("/ui/")
+}
+
+/// GET /ui — the admin web UI: stream browsing with live tailing, search, events,
+/// snapshots and metadata, projections and jobs, replays and dead letters.
+pub async fn index(State(state): State<AppState>) -> Result<Response> {
+    serve(&state, "index.html")
+}
+
+/// GET /ui/:file — the UI's scripts and styles.
+pub async fn asset(State(state): State<AppState>, Path(file): Path<String>) -> Result<Response> {
+    serve(&state, &file)
+}
+
+fn serve(state: &AppState, file: &str) -> Result<Response> {
+    let asset = ASSETS
+        .iter()
+        .find(|(name, _, _)| *name == file)
+        .filter(|_| state.config.ui_enabled);
+    let Some((_, content_type, body)) = asset else {
+        return Err(AppError::NotFound(format!("No UI file {}", file)));
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, *content_type),
+            // Revalidated on every load, so an upgrade never mixes old and new files
+            (header::CACHE_CONTROL, "no-cache"),
+            (header::CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        *body,
+    )
+        .into_response())
+}
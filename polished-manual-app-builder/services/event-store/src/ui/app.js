@@ -0,0 +1,450 @@
+'use strict';
+
+// Admin UI for the event store. Every page is built from the public API,
+// called with the API key and tenant saved in this browser.
+
+const PAGE_SIZE = 50;
+const TAIL_WAIT = '30s';
+
+const view = document.getElementById('view');
+// Bumped on every navigation, so loops started by a page stop once it is left
+let generation = 0;
+
+function credentials() {
+  return {
+    apiKey: localStorage.getItem('eventStoreApiKey') || '',
+    tenant: localStorage.getItem('eventStoreTenant') || '',
+  };
+}
+
+async function request(method, path, body) {
+  const { apiKey, tenant } = credentials();
+  const headers = { Accept: 'application/json' };
+  if (apiKey) headers['X-Api-Key'] = apiKey;
+  if (tenant) headers['X-Tenant-Id'] = tenant;
+  if (body !== undefined) headers['Content-Type'] = 'application/json';
+
+  const response = await fetch(path, {
+    method,
+    headers,
+    body: body === undefined ? undefined : JSON.stringify(body),
+  });
+  if (!response.ok && response.status !== 304) {
+    let detail = `${response.status} ${response.statusText}`;
+    try {
+      detail = (await response.json()).detail || detail;
+    } catch (_) {
+      // Not a problem details body
+    }
+    throw new Error(detail);
+  }
+  return response;
+}
+
+async function api(method, path, body) {
+  const response = await request(method, path, body);
+  if (response.status === 204 || response.status === 304) return null;
+  const text = await response.text();
+  return text ? JSON.parse(text) : null;
+}
+
+function streamPath(streamId) {
+  return `/streams/${encodeURIComponent(streamId)}`;
+}
+
+// Elements are built with text nodes only, so event data is never parsed as HTML
+function el(tag, attributes, ...children) {
+  const element = document.createElement(tag);
+  for (const [name, value] of Object.entries(attributes || {})) {
+    if (name.startsWith('on')) {
+      element.addEventListener(name.slice(2), value);
+    } else {
+      element.setAttribute(name, value);
+    }
+  }
+  for (const child of children.flat()) {
+    if (child === null || child === undefined) continue;
+    element.append(child instanceof Node ? child : String(child));
+  }
+  return element;
+}
+
+function json(value) {
+  return el('pre', null, JSON.stringify(value, null, 2));
+}
+
+function time(value) {
+  return value ? new Date(value).toLocaleString() : '';
+}
+
+function failure(error) {
+  return el('div', { class: 'error' }, error.message);
+}
+
+function table(columns, rows) {
+  return el(
+    'table',
+    null,
+    el('thead', null, el('tr', null, columns.map((column) => el('th', null, column)))),
+    el('tbody', null, rows),
+  );
+}
+
+// Replaces `target`'s content with what `load` builds, or with its error
+async function fill(target, load) {
+  target.replaceChildren(el('p', { class: 'muted' }, 'Loading…'));
+  try {
+    target.replaceChildren(...[await load()].flat());
+  } catch (error) {
+    target.replaceChildren(failure(error));
+  }
+}
+
+function action(label, run, done) {
+  return el('button', {
+    onclick: async (event) => {
+      event.stopPropagation();
+      try {
+        await run();
+        done();
+      } catch (error) {
+        alert(error.message);
+      }
+    },
+  }, label);
+}
+
+// Streams
+
+function openStreamForm() {
+  const input = el('input', { placeholder: 'project/workspace/stream', size: 48, required: '' });
+  return el('form', {
+    class: 'toolbar',
+    onsubmit: (event) => {
+      event.preventDefault();
+      location.hash = `#/streams/${encodeURIComponent(input.value.trim())}`;
+    },
+  }, input, el('button', { type: 'submit' }, 'Open stream'));
+}
+
+async function statsSection() {
+  const stats = await api('GET', '/stats');
+  return table(
+    ['Events', 'Streams', 'Snapshots', 'Stored'],
+    [el('tr', null,
+      el('td', null, stats.total_events),
+      el('td', null, stats.total_streams),
+      el('td', null, stats.total_snapshots),
+      el('td', null, stats.storage_bytes === null ? '' : `${(stats.storage_bytes / 1048576).toFixed(1)} MiB`),
+    )],
+  );
+}
+
+// Streams created lately, from the $streams system stream
+async function recentStreams() {
+  const events = await api('GET', `${streamPath('$streams')}/events?direction=backward&limit=${PAGE_SIZE}`);
+  const created = events.filter((event) => event.event_type === '$stream-created');
+  if (created.length === 0) return el('p', { class: 'muted' }, 'No streams recorded in $streams.');
+  return table(['Stream', 'First event', 'Created'], created.map((event) => el('tr', {
+    class: 'clickable',
+    onclick: () => { location.hash = `#/streams/${encodeURIComponent(event.data.stream_id)}`; },
+  },
+  el('td', null, event.data.stream_id),
+  el('td', null, event.data.event_type),
+  el('td', null, time(event.data.created_at)))));
+}
+
+// The highlight's <mark> tags become elements; everything else stays text
+function highlighted(highlight) {
+  return highlight.split(/(<mark>.*?<\/mark>)/).map((part) => {
+    const match = /^<mark>(.*)<\/mark>$/.exec(part);
+    return match ? el('mark', null, match[1]) : part;
+  });
+}
+
+function searchSection() {
+  const input = el('input', { placeholder: 'Order id, email…', size: 48, required: '' });
+  const rows = el('tbody');
+  const status = el('div');
+  const more = el('button', { hidden: '' }, 'More results');
+  let next = null;
+
+  async function load() {
+    const before = next === null ? '' : `&before=${next}`;
+    const results = await api('GET', `/search?q=${encodeURIComponent(input.value.trim())}${before}`);
+    for (const hit of results.hits) {
+      const event = hit.event;
+      const detail = el('tr', { hidden: '' }, el('td', { colspan: 4 }, json(event)));
+      rows.append(el('tr', {
+        class: 'clickable',
+        onclick: () => { detail.hidden = !detail.hidden; },
+      },
+      el('td', null, el('a', { href: `#/streams/${encodeURIComponent(event.stream_id)}` }, event.stream_id)),
+      el('td', null, `${event.event_type} v${event.version}`),
+      el('td', null, time(event.created_at)),
+      el('td', null, highlighted(hit.highlight))), detail);
+    }
+    next = results.next;
+    more.hidden = next === null;
+  }
+
+  more.addEventListener('click', () => load().catch((error) => status.replaceChildren(failure(error))));
+  const form = el('form', {
+    class: 'toolbar',
+    onsubmit: (event) => {
+      event.preventDefault();
+      rows.replaceChildren();
+      next = null;
+      fill(status, async () => {
+        await load();
+        return rows.childElementCount === 0 ? el('p', { class: 'muted' }, 'No matching events.') : [];
+      });
+    },
+  }, input, el('button', { type: 'submit' }, 'Search'));
+
+  return el('section', null,
+    el('h3', null, 'Search events'),
+    form,
+    status,
+    el('table', null,
+      el('thead', null, el('tr', null, ['Stream', 'Event', 'Created', 'Match'].map((column) => el('th', null, column)))),
+      rows),
+    more);
+}
+
+function homePage() {
+  const stats = el('div');
+  const recent = el('div');
+  view.replaceChildren(
+    el('section', null, el('h2', null, 'Streams'), openStreamForm(), stats),
+    searchSection(),
+    el('section', null, el('h3', null, 'Recently created'), recent),
+  );
+  fill(stats, statsSection);
+  fill(recent, recentStreams);
+}
+
+function eventRow(event) {
+  const detail = el('tr', { hidden: '' }, el('td', { colspan: 5 }, json(event)));
+  const row = el('tr', {
+    class: 'clickable',
+    onclick: () => { detail.hidden = !detail.hidden; },
+  },
+  el('td', null, event.version),
+  el('td', null, event.event_type),
+  el('td', null, event.position),
+  el('td', null, event.content_type),
+  el('td', null, time(event.created_at)));
+  return [row, detail];
+}
+
+function streamPage(streamId) {
+  const current = generation;
+  const path = streamPath(streamId);
+  const head = el('span', { class: 'muted' });
+  const rows = el('tbody');
+  const status = el('div');
+  const more = el('button', { hidden: '' }, 'Older events');
+  const tail = el('button', null, 'Tail');
+  const snapshot = el('div');
+  const metadata = el('div');
+  let oldest = null;
+  let newest = 0;
+  let tailing = false;
+
+  async function loadHead() {
+    const response = await request('HEAD', path);
+    newest = Number(response.headers.get('X-Stream-Version')) || 0;
+    head.textContent = `version ${newest}`;
+  }
+
+  async function loadOlder() {
+    const from = oldest === null ? '' : `&from_version=${oldest - 1}`;
+    const query = `direction=backward&include_archived=true&limit=${PAGE_SIZE}${from}`;
+    const events = await api('GET', `${path}/events?${query}`);
+    rows.append(...events.flatMap(eventRow));
+    if (events.length > 0) oldest = events[events.length - 1].version;
+    more.hidden = events.length < PAGE_SIZE || oldest <= 1;
+  }
+
+  // Long polls past the head, adding events to the top as they are appended
+  async function follow() {
+    while (tailing && generation === current) {
+      try {
+        const query = `from_version=${newest + 1}&limit=${PAGE_SIZE}&wait=${TAIL_WAIT}`;
+        const events = await api('GET', `${path}/events?${query}`);
+        for (const event of events) {
+          rows.prepend(...eventRow(event));
+          newest = event.version;
+        }
+        head.textContent = `version ${newest}`;
+      } catch (error) {
+        status.replaceChildren(failure(error));
+        tailing = false;
+        tail.textContent = 'Tail';
+      }
+    }
+  }
+
+  more.addEventListener('click', () => loadOlder().catch((error) => status.replaceChildren(failure(error))));
+  tail.addEventListener('click', () => {
+    tailing = !tailing;
+    tail.replaceChildren(tailing ? el('span', { class: 'live' }, '● Live') : 'Tail');
+    if (tailing) {
+      status.replaceChildren();
+      follow();
+    }
+  });
+
+  view.replaceChildren(
+    el('section', null,
+      el('h2', null, streamId, ' ', head),
+      el('div', { class: 'toolbar' }, tail, el('a', { href: '#/' }, 'All streams')),
+      status,
+      el('table', null,
+        el('thead', null, el('tr', null,
+          ['Version', 'Type', 'Position', 'Content type', 'Created'].map((column) => el('th', null, column)))),
+        rows),
+      more),
+    el('section', null, el('h3', null, 'Latest snapshot'), snapshot),
+    el('section', null, el('h3', null, 'Metadata'), metadata),
+  );
+
+  fill(status, async () => {
+    await loadHead();
+    await loadOlder();
+    return [];
+  });
+  fill(snapshot, async () => json(await api('GET', `/snapshots/${encodeURIComponent(streamId)}/latest`)));
+  fill(metadata, async () => json(await api('GET', `${path}/metadata`)));
+}
+
+// Projections
+
+async function pluginsSection() {
+  const plugins = await api('GET', '/admin/plugins');
+  if (plugins.length === 0) return el('p', { class: 'muted' }, 'No plugins uploaded.');
+  return table(['Name', 'Kind', 'Streams', 'Updated', ''], plugins.map((plugin) => {
+    const state = el('div');
+    const load = async () => json(await api('GET', `/projections/${encodeURIComponent(plugin.name)}`));
+    const show = plugin.kind === 'projection' ? el('button', { onclick: () => fill(state, load) }, 'State') : null;
+    return el('tr', null,
+      el('td', null, plugin.name),
+      el('td', null, plugin.kind),
+      el('td', null, plugin.stream_pattern || '*'),
+      el('td', null, time(plugin.updated_at)),
+      el('td', null, show, state));
+  }));
+}
+
+async function jobsSection(target) {
+  const reload = () => fill(target, () => jobsSection(target));
+  const jobs = await api('GET', '/admin/jobs');
+  return table(['Job', 'Every', 'Runs', 'Failures', 'Last run', 'Result', ''], jobs.map((job) => {
+    const name = encodeURIComponent(job.name);
+    return el('tr', null,
+      el('td', null, job.name, job.paused ? el('span', { class: 'muted' }, ' (paused)') : null),
+      el('td', null, `${job.interval_seconds}s`),
+      el('td', null, job.runs),
+      el('td', null, job.failures),
+      el('td', null, time(job.last_started_at)),
+      el('td', null, job.last_result ? job.last_result.message : ''),
+      el('td', null,
+        action('Run', () => api('POST', `/admin/jobs/${name}/run`), reload),
+        job.paused
+          ? action('Resume', () => api('POST', `/admin/jobs/${name}/resume`), reload)
+          : action('Pause', () => api('POST', `/admin/jobs/${name}/pause`), reload)));
+  }));
+}
+
+function projectionsPage() {
+  const plugins = el('div');
+  const jobs = el('div');
+  view.replaceChildren(
+    el('section', null, el('h2', null, 'Projections and reducers'), plugins),
+    el('section', null, el('h3', null, 'Background jobs'), jobs),
+  );
+  fill(plugins, pluginsSection);
+  fill(jobs, () => jobsSection(jobs));
+}
+
+// Subscriptions
+
+async function replaysSection(target) {
+  const reload = () => fill(target, () => replaysSection(target));
+  const replays = await api('GET', '/admin/replays');
+  if (replays.length === 0) return el('p', { class: 'muted' }, 'No replays.');
+  const columns = ['Id', 'Status', 'Sink', 'Progress', 'Replayed', 'Dead-lettered', 'Started', ''];
+  return table(columns, replays.map((replay) => el('tr', null,
+    el('td', null, replay.id),
+    el('td', null, replay.status, replay.error ? el('div', { class: 'muted' }, replay.error) : null),
+    el('td', null, replay.sink.url || replay.sink.topic || replay.sink.type),
+    el('td', null, `${replay.position} / ${replay.end_position}`),
+    el('td', null, replay.events_replayed),
+    el('td', null, replay.events_dead_lettered),
+    el('td', null, time(replay.started_at)),
+    el('td', null, replay.status === 'running'
+      ? action('Cancel', () => api('POST', `/admin/replays/${replay.id}/cancel`), reload)
+      : null))));
+}
+
+async function deadLettersSection(target) {
+  const reload = () => fill(target, () => deadLettersSection(target));
+  const letters = await api('GET', '/admin/dead-letters');
+  if (letters.length === 0) return el('p', { class: 'muted' }, 'No dead letters.');
+  return table(['Source', 'Stream', 'Version', 'Attempts', 'Created', ''], letters.flatMap((letter) => {
+    const detail = el('tr', { hidden: '' }, el('td', { colspan: 6 }, json(letter)));
+    const row = el('tr', {
+      class: 'clickable',
+      onclick: () => { detail.hidden = !detail.hidden; },
+    },
+    el('td', null, letter.source),
+    el('td', null, letter.event.stream_id),
+    el('td', null, letter.event.version),
+    el('td', null, letter.attempts.length),
+    el('td', null, time(letter.created_at)),
+    el('td', null,
+      action('Redeliver', () => api('POST', `/admin/dead-letters/${letter.id}/redeliver`), reload),
+      action('Discard', () => api('DELETE', `/admin/dead-letters/${letter.id}`), reload)));
+    return [row, detail];
+  }));
+}
+
+function subscriptionsPage() {
+  const replays = el('div');
+  const letters = el('div');
+  view.replaceChildren(
+    el('section', null, el('h2', null, 'Replays'), replays),
+    el('section', null, el('h3', null, 'Dead letters'), letters),
+  );
+  fill(replays, () => replaysSection(replays));
+  fill(letters, () => deadLettersSection(letters));
+}
+
+// Navigation
+
+function route() {
+  generation += 1;
+  const hash = location.hash.replace(/^#/, '') || '/';
+  if (hash.startsWith('/streams/')) {
+    streamPage(decodeURIComponent(hash.slice('/streams/'.length)));
+  } else if (hash === '/projections') {
+    projectionsPage();
+  } else if (hash === '/subscriptions') {
+    subscriptionsPage();
+  } else {
+    homePage();
+  }
+}
+
+document.getElementById('credentials').addEventListener('submit', (event) => {
+  event.preventDefault();
+  localStorage.setItem('eventStoreApiKey', document.getElementById('api-key').value);
+  localStorage.setItem('eventStoreTenant', document.getElementById('tenant').value.trim());
+  route();
+});
+document.getElementById('api-key').value = credentials().apiKey;
+document.getElementById('tenant').value = credentials().tenant;
+
+window.addEventListener('hashchange', route);
+route();
@@ -0,0 +1,85 @@
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+use crate::storage::ProjectUsage;
+use crate::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    pub month: Option<String>,  // "2024-06"; the current month when unset
+    pub format: Option<String>, // "json" (default) or "csv"
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    pub month: String,
+    pub generated_at: DateTime<Utc>,
+    pub projects: Vec<ProjectUsage>,
+}
+
+/// GET /admin/usage?month=2024-06 — events and bytes each project appended
+/// in a calendar month (UTC), with the average and peak of its daily
+/// stored bytes, for billing. `?format=csv` downloads the same rows.
+/// Stored bytes come from the samples `usage_sampler` takes, appends from
+/// the stats rollup, so the current month is still filling in.
+pub async fn usage_report(State(state): State<AppState>, Query(query): Query<UsageQuery>) -> Result<Response> {
+    let month = match query.month.as_deref() {
+        Some(month) => NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+            .map_err(|_| AppError::BadRequest(format!("Invalid month {}; expected YYYY-MM", month)))?,
+        None => Utc::now().date_naive().with_day(1).expect("every month has a first day"),
+    };
+
+    let report = UsageReport {
+        month: month.format("%Y-%m").to_string(),
+        generated_at: Utc::now(),
+        projects: state.storage.monthly_usage(month).await?,
+    };
+
+    if query.format.as_deref() == Some("csv") {
+        let filename = format!("attachment; filename=\"usage-{}.csv\"", report.month);
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (header::CONTENT_DISPOSITION, filename),
+            ],
+            to_csv(&report),
+        )
+            .into_response());
+    }
+
+    Ok(Json(report).into_response())
+}
+
+fn to_csv(report: &UsageReport) -> String {
+    let mut out = String::from("month,project_id,events,bytes_appended,avg_stored_bytes,peak_stored_bytes\n");
+
+    for project in &report.projects {
+        let fields = [
+            report.month.clone(),
+            project.project_id.clone(),
+            project.events.to_string(),
+            project.bytes_appended.to_string(),
+            project.avg_stored_bytes.to_string(),
+            project.peak_stored_bytes.to_string(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
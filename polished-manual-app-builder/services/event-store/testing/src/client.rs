@@ -0,0 +1,233 @@
+use chrono::{DateTime, Utc};
+use reqwest::{Method, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The store answered with a problem response.
+    #[error("{} {}: {}", .0.status, .0.code, .0.detail)]
+    Api(Problem),
+}
+
+/// A problem response body; see `GET /errors/catalog`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Problem {
+    pub status: u16,
+    pub code: String,
+    pub category: String,
+    pub detail: String,
+    #[serde(default)]
+    pub retryable: bool,
+}
+
+/// An event as the store returns it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Event {
+    pub id: Uuid,
+    pub stream_id: String,
+    pub event_type: String,
+    pub data: Value,
+    pub content_type: String,
+    pub metadata: Option<Value>,
+    pub version: i64,
+    pub position: i64,
+    pub created_at: DateTime<Utc>,
+    pub checksum: Option<String>,
+    pub chain_hash: Option<String>,
+}
+
+/// The body of `POST /events`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AppendEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    pub stream_id: String,
+    pub event_type: String,
+    pub data: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<i64>,
+}
+
+impl AppendEvent {
+    pub fn new(stream_id: &str, event_type: &str, data: Value) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            event_type: event_type.to_string(),
+            data,
+            ..Self::default()
+        }
+    }
+
+    pub fn expecting(mut self, version: i64) -> Self {
+        self.expected_version = Some(version);
+        self
+    }
+}
+
+/// A stored snapshot, without its encoded data; read the data back with
+/// `Client::latest_snapshot`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: Uuid,
+    pub stream_id: String,
+    pub version: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Typed access to the HTTP API. Endpoints without a method here are
+/// reachable through `request`.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    tenant: Option<String>,
+}
+
+impl Client {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.into(),
+            api_key: None,
+            tenant: None,
+        }
+    }
+
+    /// The same store, called with `X-Api-Key`.
+    pub fn with_api_key(&self, api_key: &str) -> Self {
+        Self {
+            api_key: Some(api_key.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// The same store, called with `X-Tenant-Id`.
+    pub fn with_tenant(&self, tenant: &str) -> Self {
+        Self {
+            tenant: Some(tenant.to_string()),
+            ..self.clone()
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A request to `path`, e.g. `/stats`, with this client's credentials.
+    pub fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut request = self.http.request(method, format!("{}{}", self.url, path));
+        if let Some(api_key) = &self.api_key {
+            request = request.header("x-api-key", api_key);
+        }
+        if let Some(tenant) = &self.tenant {
+            request = request.header("x-tenant-id", tenant);
+        }
+        request
+    }
+
+    /// Sends a request, turning problem responses into `Error::Api`.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let response = request.send().await?;
+        if response.status().is_success() || response.status().is_redirection() {
+            return Ok(response);
+        }
+        let status = response.status().as_u16();
+        let text = response.text().await?;
+        Err(Error::Api(serde_json::from_str(&text).unwrap_or(Problem {
+            status,
+            code: String::new(),
+            category: String::new(),
+            detail: text,
+            retryable: false,
+        })))
+    }
+
+    pub(crate) async fn ready(&self) -> bool {
+        let ready = self.http.get(format!("{}/health/ready", self.url)).send().await;
+        ready.is_ok_and(|response| response.status().is_success())
+    }
+
+    pub async fn append(&self, event: &AppendEvent) -> Result<Event> {
+        let response = self.send(self.request(Method::POST, "/events").json(event)).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Up to `limit` events of a stream from `from_version` on.
+    pub async fn read_stream(&self, stream_id: &str, from_version: i64, limit: i64) -> Result<Vec<Event>> {
+        let path = format!(
+            "{}/events?from_version={}&limit={}",
+            stream_path(stream_id),
+            from_version,
+            limit
+        );
+        let response = self.send(self.request(Method::GET, &path)).await?;
+        Ok(response.json().await?)
+    }
+
+    /// The stream's head version; 0 when it has no events.
+    pub async fn stream_version(&self, stream_id: &str) -> Result<i64> {
+        match self.send(self.request(Method::HEAD, &stream_path(stream_id))).await {
+            Ok(response) => Ok(response
+                .headers()
+                .get("x-stream-version")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(0)),
+            Err(Error::Api(problem)) if problem.status == 404 => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The stream's metadata, as `PUT /streams/:stream_id/metadata` took it.
+    pub async fn stream_metadata(&self, stream_id: &str) -> Result<Value> {
+        let path = format!("{}/metadata", stream_path(stream_id));
+        let response = self.send(self.request(Method::GET, &path)).await?;
+        Ok(response.json().await?)
+    }
+
+    pub async fn set_stream_metadata(&self, stream_id: &str, metadata: &Value) -> Result<Value> {
+        let path = format!("{}/metadata", stream_path(stream_id));
+        let response = self.send(self.request(Method::PUT, &path).json(metadata)).await?;
+        Ok(response.json().await?)
+    }
+
+    pub async fn create_snapshot(&self, stream_id: &str, version: i64, data: Value) -> Result<SnapshotInfo> {
+        let body = serde_json::json!({ "stream_id": stream_id, "version": version, "data": data });
+        let response = self.send(self.request(Method::POST, "/snapshots").json(&body)).await?;
+        Ok(response.json().await?)
+    }
+
+    /// The data of the stream's latest snapshot, if it has one.
+    pub async fn latest_snapshot(&self, stream_id: &str) -> Result<Option<Value>> {
+        let path = format!("/snapshots/{}/latest", encode(stream_id));
+        let response = self.send(self.request(Method::GET, &path)).await?;
+        Ok(response.json().await?)
+    }
+}
+
+fn stream_path(stream_id: &str) -> String {
+    format!("/streams/{}", encode(stream_id))
+}
+
+/// Percent-encodes a stream id for a path segment; ids hold `/`.
+fn encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
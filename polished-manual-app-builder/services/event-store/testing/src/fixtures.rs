@@ -0,0 +1,50 @@
+//! Seed data for tests. Streams are named `{project}/tests/{random}`, so
+//! tests sharing a store never see each other's events.
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::client::{AppendEvent, Client, Event, SnapshotInfo};
+
+pub const EVENT_TYPE: &str = "TestEvent";
+
+/// A stream id no other test uses.
+pub fn unique_stream_id(project: &str) -> String {
+    format!("{}/tests/{}", project, Uuid::new_v4().simple())
+}
+
+/// Appends one event per `data` value to `stream_id`, in order, each
+/// expecting the version before it. Panics on failure.
+pub async fn append_all(client: &Client, stream_id: &str, event_type: &str, data: Vec<Value>) -> Vec<Event> {
+    let mut version = client.stream_version(stream_id).await.expect("Failed to read the stream version");
+    let mut events = Vec::with_capacity(data.len());
+    for data in data {
+        let event = AppendEvent::new(stream_id, event_type, data).expecting(version);
+        let event = client.append(&event).await.expect("Failed to append a fixture event");
+        version = event.version;
+        events.push(event);
+    }
+    events
+}
+
+/// A new stream of `count` `TestEvent`s whose data is `{"n": 1}`, `{"n": 2}`,
+/// and so on; returns its id.
+pub async fn stream(client: &Client, project: &str, count: usize) -> String {
+    let stream_id = unique_stream_id(project);
+    let data = (1..=count).map(|n| json!({ "n": n })).collect();
+    append_all(client, &stream_id, EVENT_TYPE, data).await;
+    stream_id
+}
+
+/// A new stream like `stream`, with a snapshot at its head holding the
+/// events' data as an array; returns its id and the snapshot.
+pub async fn stream_with_snapshot(client: &Client, project: &str, count: usize) -> (String, SnapshotInfo) {
+    let stream_id = unique_stream_id(project);
+    let data: Vec<Value> = (1..=count).map(|n| json!({ "n": n })).collect();
+    append_all(client, &stream_id, EVENT_TYPE, data.clone()).await;
+    let snapshot = client
+        .create_snapshot(&stream_id, count as i64, json!({ "events": data }))
+        .await
+        .expect("Failed to create a fixture snapshot");
+    (stream_id, snapshot)
+}
@@ -0,0 +1,299 @@
+//! End-to-end tests against the in-memory backend, through
+//! `event-store-testing`. Use `TestStore::postgres` for behaviour that
+//! depends on the Postgres backend; those tests need Docker.
+
+use chrono::DateTime;
+use event_store_testing::{fixtures, AppendEvent, Backend, Client, Error, TestStore, DETERMINISTIC_CLOCK_START};
+use std::time::Duration;
+use reqwest::Method;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const BINARY: &str = env!("CARGO_BIN_EXE_event-store");
+const ADMIN_KEY: &str = "e2e-admin-key";
+/// Reads and writes the `acme` project only.
+const WRITER_KEY: &str = "e2e-writer-key";
+/// Reads the `acme` project only.
+const READER_KEY: &str = "e2e-reader-key";
+
+/// A store with authentication on: an admin key, and a writer and a reader
+/// key bound to `acme`.
+async fn store_with_keys() -> TestStore {
+    let keys = format!(
+        "{}=admin,{}=read+write@acme,{}=read@acme",
+        ADMIN_KEY, WRITER_KEY, READER_KEY
+    );
+    TestStore::builder(BINARY).env("API_KEYS", &keys).start().await
+}
+
+/// Registers `project` and its `workspace` under `/projects`.
+async fn register(client: &Client, project: &str, workspace: &str) {
+    let request = client.request(Method::POST, "/projects").json(&json!({ "project": project }));
+    client.send(request).await.unwrap();
+    let path = format!("/projects/{}/workspaces", project);
+    let request = client.request(Method::POST, &path).json(&json!({ "workspace": workspace }));
+    client.send(request).await.unwrap();
+}
+
+/// The status of the problem response `result` should be.
+fn rejected_with<T: std::fmt::Debug>(result: Result<T, Error>) -> u16 {
+    match result {
+        Err(Error::Api(problem)) => problem.status,
+        other => panic!("Expected a problem response, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn boots_and_reads_back_an_appended_event() {
+    let store = TestStore::memory(BINARY).await;
+    let stream_id = fixtures::unique_stream_id("acme");
+
+    let append = AppendEvent::new(&stream_id, fixtures::EVENT_TYPE, json!({ "sku": "A-1", "qty": 2 }));
+    let appended = store.client().append(&append).await.unwrap();
+    assert_eq!(appended.version, 1);
+
+    let events = store.client().read_stream(&stream_id, 1, 10).await.unwrap();
+    assert_eq!(events, [appended]);
+    assert_eq!(events[0].event_type, fixtures::EVENT_TYPE);
+    assert_eq!(events[0].data, json!({ "sku": "A-1", "qty": 2 }));
+}
+
+#[tokio::test]
+async fn reads_back_appended_events_in_order() {
+    let store = TestStore::memory(BINARY).await;
+    let stream_id = fixtures::stream(store.client(), "acme", 3).await;
+
+    let events = store.client().read_stream(&stream_id, 1, 10).await.unwrap();
+    assert_eq!(events.iter().map(|e| e.version).collect::<Vec<_>>(), [1, 2, 3]);
+    assert_eq!(events[2].data, json!({ "n": 3 }));
+    assert_eq!(store.client().stream_version(&stream_id).await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn refuses_an_append_at_a_stale_version() {
+    let store = TestStore::memory(BINARY).await;
+    let stream_id = fixtures::stream(store.client(), "acme", 2).await;
+
+    let stale = AppendEvent::new(&stream_id, fixtures::EVENT_TYPE, json!({})).expecting(1);
+    match store.client().append(&stale).await {
+        Err(Error::Api(problem)) => assert_eq!(problem.status, 409),
+        other => panic!("Expected a version conflict, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn returns_the_latest_snapshot() {
+    let store = TestStore::memory(BINARY).await;
+    let (stream_id, snapshot) = fixtures::stream_with_snapshot(store.client(), "acme", 2).await;
+
+    assert_eq!(snapshot.version, 2);
+    let data = store.client().latest_snapshot(&stream_id).await.unwrap();
+    assert_eq!(data, Some(json!({ "events": [{ "n": 1 }, { "n": 2 }] })));
+}
+
+#[tokio::test]
+async fn deterministic_stores_give_the_same_ids_and_times() {
+    let store = TestStore::builder(BINARY).deterministic().start().await;
+
+    let event = AppendEvent::new("acme/tests/clock", fixtures::EVENT_TYPE, json!({}));
+    let event = store.client().append(&event).await.unwrap();
+    assert_eq!(event.id, Uuid::from_u128(1));
+    assert_eq!(event.created_at, DateTime::parse_from_rfc3339(DETERMINISTIC_CLOCK_START).unwrap());
+}
+
+#[tokio::test]
+async fn writers_cannot_truncate_or_release_a_stream_through_its_metadata() {
+    let store = store_with_keys().await;
+    let admin = store.client().with_api_key(ADMIN_KEY);
+    let writer = store.client().with_api_key(WRITER_KEY);
+    let stream_id = fixtures::stream(&writer, "acme", 2).await;
+    admin.set_stream_metadata(&stream_id, &json!({ "legal_hold": true })).await.unwrap();
+
+    let release = writer.set_stream_metadata(&stream_id, &json!({})).await;
+    assert_eq!(rejected_with(release), 403);
+    let truncate = writer
+        .set_stream_metadata(&stream_id, &json!({ "legal_hold": true, "truncate_before": 3 }))
+        .await;
+    assert_eq!(rejected_with(truncate), 403);
+
+    // Other fields stay the writer's to change
+    writer
+        .set_stream_metadata(&stream_id, &json!({ "legal_hold": true, "max_events": 10 }))
+        .await
+        .unwrap();
+    let metadata = writer.stream_metadata(&stream_id).await.unwrap();
+    assert_eq!(metadata, json!({ "legal_hold": true, "max_events": 10 }));
+    assert_eq!(writer.read_stream(&stream_id, 1, 10).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn deleting_a_project_removes_its_workspaces_and_archives_their_streams() {
+    let store = TestStore::builder(BINARY).env("NAMESPACES_STRICT", "true").start().await;
+    let client = store.client();
+    let project = client.request(Method::POST, "/projects").json(&json!({ "project": "acme" }));
+    client.send(project).await.unwrap();
+    for workspace in ["tests", "ledger"] {
+        let workspace = client
+            .request(Method::POST, "/projects/acme/workspaces")
+            .json(&json!({ "workspace": workspace }));
+        client.send(workspace).await.unwrap();
+    }
+
+    let tests = fixtures::stream(client, "acme", 3).await;
+    let ledger = format!("acme/ledger/{}", Uuid::new_v4().simple());
+    fixtures::append_all(client, &ledger, fixtures::EVENT_TYPE, vec![json!({}), json!({})]).await;
+
+    let deletion: Value = client
+        .send(client.request(Method::DELETE, "/projects/acme"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(deletion["namespaces_removed"], 3);
+    assert_eq!(deletion["streams"], 2);
+    assert_eq!(deletion["events_archived"], 3);
+
+    // The heads stay, so versions carry on; new streams need the project back
+    assert_eq!(client.stream_version(&tests).await.unwrap(), 3);
+    assert_eq!(client.stream_version(&ledger).await.unwrap(), 2);
+    let workspaces = client.send(client.request(Method::GET, "/projects/acme/workspaces")).await;
+    assert_eq!(rejected_with(workspaces), 404);
+    let new_stream = AppendEvent::new(&fixtures::unique_stream_id("acme"), fixtures::EVENT_TYPE, json!({}));
+    assert_eq!(rejected_with(client.append(&new_stream).await), 400);
+}
+
+#[tokio::test]
+async fn keys_bound_to_a_project_cannot_reach_other_projects() {
+    let store = store_with_keys().await;
+    let admin = store.client().with_api_key(ADMIN_KEY);
+    let writer = store.client().with_api_key(WRITER_KEY);
+    let theirs = fixtures::stream(&admin, "globex", 1).await;
+
+    let append = AppendEvent::new(&theirs, fixtures::EVENT_TYPE, json!({}));
+    assert_eq!(rejected_with(writer.append(&append).await), 403);
+    assert_eq!(rejected_with(writer.read_stream(&theirs, 1, 10).await), 403);
+    let project = writer.request(Method::POST, "/projects").json(&json!({ "project": "globex" }));
+    assert_eq!(rejected_with(writer.send(project).await), 403);
+    assert_eq!(admin.stream_version(&theirs).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn only_admins_reach_admin_routes_and_only_writers_append() {
+    let store = store_with_keys().await;
+    let writer = store.client().with_api_key(WRITER_KEY);
+    let reader = store.client().with_api_key(READER_KEY);
+    let stream_id = fixtures::stream(&writer, "acme", 1).await;
+
+    assert_eq!(rejected_with(store.client().read_stream(&stream_id, 1, 10).await), 401);
+    let append = AppendEvent::new(&stream_id, fixtures::EVENT_TYPE, json!({}));
+    assert_eq!(rejected_with(reader.append(&append).await), 403);
+    assert_eq!(reader.read_stream(&stream_id, 1, 10).await.unwrap().len(), 1);
+
+    let path = format!("/streams/{}", stream_id.replace('/', "%2F"));
+    assert_eq!(rejected_with(writer.send(writer.request(Method::DELETE, &path)).await), 403);
+    assert_eq!(rejected_with(writer.send(writer.request(Method::GET, "/admin/jobs")).await), 403);
+    let project = writer.request(Method::DELETE, "/projects/acme");
+    assert_eq!(rejected_with(writer.send(project).await), 403);
+    assert_eq!(writer.stream_version(&stream_id).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn strict_namespaces_refuse_streams_outside_registered_workspaces() {
+    let store = TestStore::builder(BINARY).env("NAMESPACES_STRICT", "true").start().await;
+    let client = store.client();
+    let stream_id = fixtures::unique_stream_id("acme");
+    let append = AppendEvent::new(&stream_id, fixtures::EVENT_TYPE, json!({}));
+    assert_eq!(rejected_with(client.append(&append).await), 400);
+
+    // The project alone doesn't cover its workspaces
+    let project = client.request(Method::POST, "/projects").json(&json!({ "project": "acme" }));
+    client.send(project).await.unwrap();
+    assert_eq!(rejected_with(client.append(&append).await), 400);
+    let unknown = client.request(Method::POST, "/projects/globex/workspaces").json(&json!({ "workspace": "tests" }));
+    assert_eq!(rejected_with(client.send(unknown).await), 404);
+
+    register(client, "initech", "tests").await;
+    let elsewhere = AppendEvent::new(&fixtures::unique_stream_id("initech"), fixtures::EVENT_TYPE, json!({}));
+    assert_eq!(client.append(&elsewhere).await.unwrap().version, 1);
+    assert_eq!(rejected_with(client.append(&append).await), 400);
+}
+
+#[tokio::test]
+async fn redactions_need_an_admin_and_a_stream_off_legal_hold() {
+    let store = store_with_keys().await;
+    let admin = store.client().with_api_key(ADMIN_KEY);
+    let writer = store.client().with_api_key(WRITER_KEY);
+    let stream_id = fixtures::stream(&writer, "acme", 2).await;
+    let redaction = json!({ "stream_id": stream_id, "fields": ["/n"], "reason": "Takedown request" });
+
+    let by_writer = writer.request(Method::POST, "/admin/redactions").json(&redaction);
+    assert_eq!(rejected_with(writer.send(by_writer).await), 403);
+    admin.set_stream_metadata(&stream_id, &json!({ "legal_hold": true })).await.unwrap();
+    let on_hold = admin.request(Method::POST, "/admin/redactions").json(&redaction);
+    assert_eq!(rejected_with(admin.send(on_hold).await), 409);
+
+    let events = admin.read_stream(&stream_id, 1, 10).await.unwrap();
+    assert_eq!(events.iter().map(|e| e.data.clone()).collect::<Vec<_>>(), [json!({ "n": 1 }), json!({ "n": 2 })]);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn group_commit_rejects_only_the_stale_appends_of_a_batch() {
+    let store = TestStore::builder(BINARY)
+        .backend(Backend::Postgres)
+        .env("APPEND_BATCH_WINDOW_MS", "200")
+        .start()
+        .await;
+    let client = store.client();
+    let contested = fixtures::stream(client, "acme", 1).await;
+    let other = fixtures::unique_stream_id("acme");
+
+    let first = AppendEvent::new(&contested, fixtures::EVENT_TYPE, json!({ "n": 2 })).expecting(1);
+    let second = AppendEvent::new(&contested, fixtures::EVENT_TYPE, json!({ "n": 2 })).expecting(1);
+    let unrelated = AppendEvent::new(&other, fixtures::EVENT_TYPE, json!({})).expecting(0);
+    let (first, second, unrelated) =
+        tokio::join!(client.append(&first), client.append(&second), client.append(&unrelated));
+
+    let (won, lost) = if first.is_ok() { (first, second) } else { (second, first) };
+    assert_eq!(won.unwrap().version, 2);
+    assert_eq!(rejected_with(lost), 409);
+    assert_eq!(unrelated.unwrap().version, 1);
+    assert_eq!(client.stream_version(&contested).await.unwrap(), 2);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn followers_refuse_writes_while_another_instance_holds_the_lease() {
+    let leader = TestStore::builder(BINARY)
+        .backend(Backend::Postgres)
+        .env("LEADER_LEASE_SECONDS", "30")
+        .start()
+        .await;
+    let stream_id = fixtures::unique_stream_id("acme");
+    let append = AppendEvent::new(&stream_id, fixtures::EVENT_TYPE, json!({}));
+    // The lease is taken by the first election after startup
+    let mut appended = leader.client().append(&append).await;
+    for _ in 0..50 {
+        if appended.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        appended = leader.client().append(&append).await;
+    }
+    assert_eq!(appended.unwrap().version, 1);
+
+    let follower = TestStore::builder(BINARY)
+        .sharing_database_of(&leader)
+        .env("LEADER_LEASE_SECONDS", "30")
+        .start()
+        .await;
+    match follower.client().append(&append).await {
+        Err(Error::Api(problem)) => {
+            assert_eq!(problem.status, 409);
+            assert_eq!(problem.code, "fenced");
+        }
+        other => panic!("Expected the write to be fenced, got {:?}", other),
+    }
+    assert_eq!(follower.client().read_stream(&stream_id, 1, 10).await.unwrap().len(), 1);
+}